@@ -0,0 +1,128 @@
+//! 工具执行审批策略
+//!
+//! Agent 在工具通过预验证、即将执行时询问 `ApprovalPolicy`。CLI 的 `[y/N/a]` 确认回调
+//! （`ConfirmFn`）只是其中一种实现；嵌入方可以实现自己的策略，按工具名、参数（路径）、
+//! 时间段等做审批，无需改动 Agent。
+//!
+//! 策略拿到当前 `SecurityPolicy`，自行决定各自主级别下是否介入：`ConfirmFnApproval`
+//! 只在 Supervised 模式下询问用户，与原有行为一致。
+
+use async_trait::async_trait;
+
+use super::loop_::ConfirmFn;
+use crate::providers::ToolCall;
+use crate::security::SecurityPolicy;
+
+/// 用户拒绝时返回给 LLM 的工具结果
+pub const USER_DENIED_MESSAGE: &str = "用户拒绝执行该工具";
+
+/// 审批时附加在参数中的工具预览（`Tool::confirmation_preview`，如覆盖文件的 diff）；
+/// 只用于展示，实际执行使用原始参数
+pub const PREVIEW_ARG: &str = "_preview";
+
+/// 审批时附加在参数中的一次性提醒（如工作区有未提交改动）；只用于展示
+pub const NOTICE_ARG: &str = "_notice";
+
+/// 审批结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// 允许执行
+    Approve,
+    /// 拒绝执行，可附原因（原因会作为工具结果告知 LLM）
+    Deny(Option<String>),
+}
+
+impl Decision {
+    /// 拒绝时写入 history 的工具结果
+    pub fn denial_message(&self) -> Option<String> {
+        match self {
+            Decision::Approve => None,
+            Decision::Deny(None) => Some(USER_DENIED_MESSAGE.to_string()),
+            Decision::Deny(Some(reason)) => Some(format!("[失败] 审批拒绝: {}", reason)),
+        }
+    }
+}
+
+/// 工具执行审批策略
+#[async_trait]
+pub trait ApprovalPolicy: Send + Sync {
+    /// 对一次工具调用做出审批
+    async fn approve(&self, call: &ToolCall, policy: &SecurityPolicy) -> Decision;
+}
+
+/// 基于确认回调的审批（CLI 默认）：仅 Supervised 模式询问，其余模式直接放行
+pub struct ConfirmFnApproval {
+    confirm: ConfirmFn,
+}
+
+impl ConfirmFnApproval {
+    pub fn new(confirm: ConfirmFn) -> Self {
+        Self { confirm }
+    }
+}
+
+#[async_trait]
+impl ApprovalPolicy for ConfirmFnApproval {
+    async fn approve(&self, call: &ToolCall, policy: &SecurityPolicy) -> Decision {
+        if !policy.requires_confirmation() || (self.confirm)(&call.name, &call.arguments) {
+            Decision::Approve
+        } else {
+            Decision::Deny(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::AutonomyLevel;
+
+    fn call(name: &str) -> ToolCall {
+        ToolCall {
+            id: "1".to_string(),
+            name: name.to_string(),
+            arguments: serde_json::json!({}),
+        }
+    }
+
+    fn policy(autonomy: AutonomyLevel) -> SecurityPolicy {
+        SecurityPolicy {
+            autonomy,
+            allowed_commands: vec![],
+            workspace_dir: std::path::PathBuf::from("/tmp"),
+            blocked_paths: vec![],
+            http_allowed_hosts: vec![],
+            injection_check: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn confirm_fn_only_consulted_in_supervised_mode() {
+        let deny_all = ConfirmFnApproval::new(Box::new(|_, _| false));
+        assert_eq!(
+            deny_all
+                .approve(&call("shell"), &policy(AutonomyLevel::Supervised))
+                .await,
+            Decision::Deny(None)
+        );
+        assert_eq!(
+            deny_all
+                .approve(&call("shell"), &policy(AutonomyLevel::Full))
+                .await,
+            Decision::Approve
+        );
+    }
+
+    #[test]
+    fn denial_messages() {
+        assert_eq!(Decision::Approve.denial_message(), None);
+        assert_eq!(
+            Decision::Deny(None).denial_message().as_deref(),
+            Some(USER_DENIED_MESSAGE)
+        );
+        assert!(Decision::Deny(Some("非工作时间".to_string()))
+            .denial_message()
+            .unwrap()
+            .starts_with("[失败]"));
+    }
+}
@@ -0,0 +1,179 @@
+//! 辅助模型：Phase 1 路由 / 历史摘要使用的独立（通常更便宜）Provider + 模型
+//!
+//! 由 `[agent.routing]` / `[agent.summary]` 配置，创建 Agent 时构造一次并保存在 Agent 上；
+//! 省略 provider 时只换模型，Provider 在调用时取 Agent 当前的主 Provider。
+//! 调用失败时 Agent 回退到主 Provider，语义与未配置时一致。
+
+use std::sync::Arc;
+
+use crate::config::{AuxModelConfig, Config};
+use crate::providers::{OfflineState, Provider, ReliableProvider};
+
+/// 已构造好的辅助 Provider + 模型名
+pub struct AuxModel {
+    /// 独立的辅助 Provider；None 表示配置省略了 provider，每次调用使用 Agent 当前的主 Provider
+    /// （`/switch` 之后随之切换）
+    pub provider: Option<Box<dyn Provider>>,
+    /// 独立 Provider 的名称（日志用），与 `provider` 同时为 None
+    pub provider_name: Option<String>,
+    pub model: String,
+}
+
+impl AuxModel {
+    /// 直接由 Provider 构造（测试 / 自定义场景）
+    pub fn new(provider: Box<dyn Provider>, provider_name: &str, model: &str) -> Self {
+        Self {
+            provider: Some(provider),
+            provider_name: Some(provider_name.to_string()),
+            model: model.to_string(),
+        }
+    }
+
+    /// 只换模型、沿用 Agent 当前主 Provider 的辅助模型
+    pub fn on_main_provider(model: &str) -> Self {
+        Self {
+            provider: None,
+            provider_name: None,
+            model: model.to_string(),
+        }
+    }
+
+    /// 从配置构造；未配置（或 provider / model 都省略）时返回 None
+    ///
+    /// - `provider` 省略 → 沿用 Agent 当前的主 Provider（调用时解析，不在创建时绑定）
+    /// - `model` 省略 → 用所指定 Provider 的 `model`
+    ///
+    /// 引用了未配置的 Provider 时记录警告并返回 None（退回主模型，不阻断启动）。
+    pub fn from_config(
+        config: &Config,
+        aux: Option<&AuxModelConfig>,
+        offline: Arc<OfflineState>,
+    ) -> Option<Self> {
+        let aux = aux?;
+        let Some(provider_name) = aux.provider.as_deref() else {
+            return aux.model.as_deref().map(Self::on_main_provider);
+        };
+        let Some(provider_config) = config.providers.get(provider_name) else {
+            tracing::warn!(
+                "辅助模型引用的 Provider '{}' 未配置，改用主模型",
+                provider_name
+            );
+            return None;
+        };
+        let model = aux
+            .model
+            .clone()
+            .unwrap_or_else(|| provider_config.model.clone());
+
+        let provider = Box::new(ReliableProvider::for_provider(
+            provider_name,
+            provider_config,
+            &config.reliability,
+            offline,
+        ));
+        Some(Self::new(provider, provider_name, &model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+
+    fn config_with(providers: &[(&str, &str)]) -> Config {
+        let mut config = Config::default();
+        for (name, model) in providers {
+            config.providers.insert(
+                name.to_string(),
+                ProviderConfig {
+                    base_url: "echo://".to_string(),
+                    api_key: String::new(),
+                    model: model.to_string(),
+                    auth_style: None,
+                    headers: Default::default(),
+                    endpoint_path: None,
+                    max_tools: None,
+                    max_tool_schema_bytes: None,
+                    context_window: None,
+                    max_tokens: None,
+                },
+            );
+        }
+        config
+    }
+
+    fn offline() -> Arc<OfflineState> {
+        Arc::new(OfflineState::new())
+    }
+
+    fn aux(provider: Option<&str>, model: Option<&str>) -> AuxModelConfig {
+        AuxModelConfig {
+            provider: provider.map(String::from),
+            model: model.map(String::from),
+        }
+    }
+
+    #[test]
+    fn unset_returns_none() {
+        let config = config_with(&[("main", "big")]);
+        assert!(AuxModel::from_config(&config, None, offline()).is_none());
+        assert!(AuxModel::from_config(&config, Some(&aux(None, None)), offline()).is_none());
+    }
+
+    #[test]
+    fn model_only_reuses_main_provider() {
+        let config = config_with(&[("main", "big")]);
+        let m = AuxModel::from_config(&config, Some(&aux(None, Some("small"))), offline()).unwrap();
+        // 不绑定 Provider：调用时使用 Agent 当前的主 Provider
+        assert!(m.provider.is_none());
+        assert!(m.provider_name.is_none());
+        assert_eq!(m.model, "small");
+    }
+
+    #[test]
+    fn provider_only_uses_that_providers_default_model() {
+        let config = config_with(&[("main", "big"), ("cheap", "tiny")]);
+        let m = AuxModel::from_config(&config, Some(&aux(Some("cheap"), None)), offline()).unwrap();
+        assert_eq!(m.provider_name.as_deref(), Some("cheap"));
+        assert_eq!(m.model, "tiny");
+    }
+
+    #[test]
+    fn unknown_provider_falls_back_to_none() {
+        let config = config_with(&[("main", "big")]);
+        assert!(
+            AuxModel::from_config(&config, Some(&aux(Some("missing"), Some("x"))), offline())
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn aux_provider_shares_the_offline_flag() {
+        let config = config_with(&[("main", "big"), ("cheap", "tiny")]);
+        let offline = offline();
+        let m = AuxModel::from_config(&config, Some(&aux(Some("cheap"), None)), offline.clone())
+            .unwrap();
+        let messages = [crate::providers::ConversationMessage::Chat(
+            crate::providers::ChatMessage {
+                role: "user".to_string(),
+                content: "hi".to_string(),
+                reasoning_content: None,
+                turn: 0,
+            },
+        )];
+        let params = crate::providers::GenerationParams::with_temperature(0.1);
+        // 离线后辅助调用与主 Provider 一样快速失败（不会真的发出请求）
+        offline.mark_offline("connection refused");
+        let err = m
+            .provider
+            .as_ref()
+            .unwrap()
+            .chat_with_tools(&messages, &[], &m.model, params)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            crate::providers::offline::offline_error_message()
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! 对话分支：`/branch [name]` 在一次性分支里试探"如果这样做会怎样"，结束后回到主对话
+//!
+//! Provider / 工具 / 记忆目前由 Agent 以 `Box` 独占，无法复制出第二个 Agent；因此 `Agent::fork`
+//! 把主对话状态（history、路由状态、安全策略、会话目标）保存到 `AgentBranch`，Agent 本身带着
+//! 这些状态的副本继续作为分支运行。`drop_branch` 原样恢复主对话；`merge_branch` 恢复后只追加一条
+//! 分支结论摘要（复用历史压缩的摘要流程），不带入分支的完整记录。分支只在内存中，最多一层。
+
+use crate::agent::changes::ChangeSummary;
+use crate::agent::goal::SessionGoal;
+use crate::providers::{ChatMessage, ConversationMessage, Provenance};
+use crate::security::SecurityPolicy;
+
+/// 未指定名称时的分支名
+pub const DEFAULT_BRANCH_NAME: &str = "branch";
+
+/// fork 时保存的主对话状态，分支结束后原样恢复
+pub struct AgentBranch {
+    pub(super) name: String,
+    /// fork 时的 Turn 编号：之后的消息属于分支
+    pub(super) fork_turn: u64,
+    pub(super) history: Vec<ConversationMessage>,
+    pub(super) current_turn: u64,
+    pub(super) routed_skill_content: Option<String>,
+    pub(super) routed_skill_names: Vec<String>,
+    pub(super) routed_tool_names: Vec<String>,
+    pub(super) policy: SecurityPolicy,
+    pub(super) read_only: bool,
+    pub(super) goal: Option<SessionGoal>,
+    pub(super) last_changes: Option<ChangeSummary>,
+    pub(super) last_provenance: Option<Provenance>,
+}
+
+impl AgentBranch {
+    /// 分支名（提示符中显示）
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 分支里新产生的消息（Turn 编号大于 fork 时的编号）
+    pub(super) fn new_messages(&self, history: &[ConversationMessage]) -> Vec<ConversationMessage> {
+        history
+            .iter()
+            .filter(|m| m.turn() > self.fork_turn)
+            .cloned()
+            .collect()
+    }
+}
+
+/// 合并时追加到主对话的结论消息
+pub(super) fn merge_message(name: &str, summary: &str, turn: u64) -> ConversationMessage {
+    ConversationMessage::Chat(ChatMessage {
+        role: "system".to_string(),
+        content: format!("[分支 {} 的结论]\n{}", name, summary.trim()),
+        reasoning_content: None,
+        turn,
+    })
+}
@@ -0,0 +1,216 @@
+//! AgentBuilder：带默认值的 Agent 构造器
+//!
+//! `Agent::new` 的位置参数多，测试和嵌入方大多只关心其中一两个。Builder 只要求 Provider
+//! 和模型名，其余取默认值：
+//!
+//! | 部件 | 默认值 |
+//! |------|--------|
+//! | 工具 | 无 |
+//! | Memory | `NoopMemory`（不持久化） |
+//! | 安全策略 | `SecurityPolicy::default()`，自主级别改为 Full |
+//! | Provider 名称 / base_url | `"custom"` / 空（只用于展示与日志） |
+//! | temperature | 0.7（与 `[default] temperature` 默认值一致） |
+//! | Skills / 身份文件 | 无 |
+//!
+//! `build` 只是转调 `Agent::new`；确认回调、上下文窗口等仍用 Agent 上的 setter 设置。
+
+use super::Agent;
+use crate::memory::{Memory, NoopMemory};
+use crate::providers::Provider;
+use crate::security::{AutonomyLevel, SecurityPolicy};
+use crate::skills::SkillMeta;
+use crate::tools::Tool;
+
+/// 默认 temperature（与 `DefaultConfig` 默认值一致）
+const DEFAULT_TEMPERATURE: f64 = 0.7;
+
+/// 默认 Provider 名称（只用于展示与日志）
+const DEFAULT_PROVIDER_NAME: &str = "custom";
+
+/// Agent 构造器（`Agent::builder(provider, model)`）
+pub struct AgentBuilder {
+    provider: Box<dyn Provider>,
+    model: String,
+    tools: Vec<Box<dyn Tool>>,
+    memory: Box<dyn Memory>,
+    policy: SecurityPolicy,
+    provider_name: String,
+    base_url: String,
+    temperature: f64,
+    skills: Vec<SkillMeta>,
+    identity_context: Option<String>,
+}
+
+impl AgentBuilder {
+    pub fn new(provider: Box<dyn Provider>, model: impl Into<String>) -> Self {
+        Self {
+            provider,
+            model: model.into(),
+            tools: Vec::new(),
+            memory: Box::new(NoopMemory),
+            policy: SecurityPolicy {
+                autonomy: AutonomyLevel::Full,
+                ..SecurityPolicy::default()
+            },
+            provider_name: DEFAULT_PROVIDER_NAME.to_string(),
+            base_url: String::new(),
+            temperature: DEFAULT_TEMPERATURE,
+            skills: Vec::new(),
+            identity_context: None,
+        }
+    }
+
+    /// 替换全部工具
+    pub fn tools(mut self, tools: Vec<Box<dyn Tool>>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// 追加一个工具
+    pub fn tool(mut self, tool: impl Tool + 'static) -> Self {
+        self.tools.push(Box::new(tool));
+        self
+    }
+
+    pub fn memory(mut self, memory: Box<dyn Memory>) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn policy(mut self, policy: SecurityPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 只修改当前安全策略的自主级别
+    pub fn autonomy(mut self, autonomy: AutonomyLevel) -> Self {
+        self.policy.autonomy = autonomy;
+        self
+    }
+
+    /// Provider 名称与 base_url（`/config`、报告、日志中展示）
+    pub fn provider_info(
+        mut self,
+        provider_name: impl Into<String>,
+        base_url: impl Into<String>,
+    ) -> Self {
+        self.provider_name = provider_name.into();
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn skills(mut self, skills: Vec<SkillMeta>) -> Self {
+        self.skills = skills;
+        self
+    }
+
+    /// 身份文件内容（见 `identity::load_identity_context`）
+    pub fn identity(mut self, identity_context: impl Into<String>) -> Self {
+        self.identity_context = Some(identity_context.into());
+        self
+    }
+
+    pub fn build(self) -> Agent {
+        Agent::new(
+            self.provider,
+            self.tools,
+            self.memory,
+            self.policy,
+            self.provider_name,
+            self.base_url,
+            self.model,
+            self.temperature,
+            self.skills,
+            self.identity_context,
+        )
+    }
+}
+
+impl Agent {
+    /// 用默认值构造 Agent，只需指定 Provider 与模型（见 `AgentBuilder`）
+    pub fn builder(provider: Box<dyn Provider>, model: impl Into<String>) -> AgentBuilder {
+        AgentBuilder::new(provider, model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{ChatResponse, ConversationMessage, GenerationParams, ToolSpec};
+    use color_eyre::eyre::Result;
+
+    /// 不会被调用的 Provider（只检查构造结果）
+    struct IdleProvider;
+
+    #[async_trait::async_trait]
+    impl Provider for IdleProvider {
+        async fn chat_with_tools(
+            &self,
+            _messages: &[ConversationMessage],
+            _tools: &[ToolSpec],
+            _model: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            unreachable!("构造测试不应调用 Provider")
+        }
+    }
+
+    /// 集成测试的 `test_agent` 基于这些默认值构造，
+    /// 与 `Agent::new` 的等价性见 tests/e2e_agent.rs `e2_builder_matches_test_agent`
+    #[test]
+    fn defaults() {
+        let agent = Agent::builder(Box::new(IdleProvider), "m").build();
+        assert_eq!(agent.model(), "m");
+        assert_eq!(agent.provider_name(), "custom");
+        assert_eq!(agent.base_url(), "");
+        assert!((agent.temperature() - 0.7).abs() < f64::EPSILON);
+        assert!(agent.tool_names().is_empty());
+        assert!(agent.skills().is_empty());
+        assert!(agent.identity_context().is_none());
+        assert!(agent.history().is_empty());
+
+        let policy = agent.policy();
+        let default = SecurityPolicy::default();
+        assert_eq!(policy.autonomy, AutonomyLevel::Full);
+        assert_eq!(policy.allowed_commands, default.allowed_commands);
+        assert_eq!(policy.blocked_paths, default.blocked_paths);
+        assert_eq!(policy.injection_check, default.injection_check);
+    }
+
+    #[test]
+    fn setters_override_defaults() {
+        let policy = SecurityPolicy {
+            allowed_commands: vec!["echo".to_string()],
+            ..SecurityPolicy::default()
+        };
+        let agent = Agent::builder(Box::new(IdleProvider), "m")
+            .policy(policy)
+            .autonomy(AutonomyLevel::ReadOnly)
+            .tool(crate::tools::shell::ShellTool)
+            .tool(crate::tools::file::FileReadTool)
+            .provider_info("deepseek", "https://api.deepseek.com/v1")
+            .temperature(0.0)
+            .identity("用户偏好：中文")
+            .build();
+
+        assert_eq!(agent.tool_names(), vec!["shell", "file_read"]);
+        assert_eq!(agent.policy().autonomy, AutonomyLevel::ReadOnly);
+        assert_eq!(agent.policy().allowed_commands, vec!["echo"]);
+        assert_eq!(agent.provider_name(), "deepseek");
+        assert_eq!(agent.base_url(), "https://api.deepseek.com/v1");
+        assert!(agent.temperature().abs() < f64::EPSILON);
+        assert_eq!(agent.identity_context(), Some("用户偏好：中文"));
+
+        // tools() 整体替换
+        let agent = Agent::builder(Box::new(IdleProvider), "m")
+            .tool(crate::tools::shell::ShellTool)
+            .tools(Vec::new())
+            .build();
+        assert!(agent.tool_names().is_empty());
+    }
+}
@@ -0,0 +1,532 @@
+//! 每轮对话的工作区文件变更摘要（"这一轮 Agent 动了哪些文件"）
+//!
+//! 两个信息源合并：
+//! - 工作区在 git 仓库内：对比轮次前后的 `git status --porcelain`
+//! - 从工具参数追踪的文件（file_write 的 path、shell 命令中的重定向 / tee / touch 等目标）：
+//!   执行前记录 size + mtime，轮次结束后对比，可发现已经处于 dirty 状态的文件被再次修改
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 每类最多列出的文件数，超出部分折叠为 "+N"
+const MAX_LISTED: usize = 8;
+
+/// 单个文件的轻量状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        Some(Self {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+}
+
+/// 变更类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChangeKind {
+    Modified,
+    Created,
+    Deleted,
+}
+
+/// 一轮对话的文件变更（路径相对工作区，无法相对化时为绝对路径）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSummary {
+    pub modified: Vec<String>,
+    pub created: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl ChangeSummary {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.created.is_empty() && self.deleted.is_empty()
+    }
+
+    /// 一行摘要：`✎ modified: src/main.rs, Cargo.toml · created: notes.md`
+    pub fn format_line(&self) -> String {
+        let english = crate::config::Config::get_language().is_english();
+        let groups = [
+            (if english { "modified" } else { "修改" }, &self.modified),
+            (if english { "created" } else { "新建" }, &self.created),
+            (if english { "deleted" } else { "删除" }, &self.deleted),
+        ];
+        let parts: Vec<String> = groups
+            .iter()
+            .filter(|(_, files)| !files.is_empty())
+            .map(|(label, files)| {
+                let mut listed = files
+                    .iter()
+                    .take(MAX_LISTED)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if files.len() > MAX_LISTED {
+                    listed.push_str(&format!(" +{}", files.len() - MAX_LISTED));
+                }
+                format!("{}: {}", label, listed)
+            })
+            .collect();
+        format!("✎ {}", parts.join(" · "))
+    }
+}
+
+/// 单轮变更追踪器：首个工具执行前创建，轮次结束时 `finish()`
+pub struct ChangeTracker {
+    workspace: PathBuf,
+    /// git 仓库根目录 + 轮次开始时的 porcelain 状态（None = 不在 git 仓库中）
+    git_before: Option<(PathBuf, BTreeMap<PathBuf, String>)>,
+    /// 工具参数中出现的文件 → 首次出现时的状态（None = 当时不存在）
+    tracked: BTreeMap<PathBuf, Option<FileStamp>>,
+}
+
+impl ChangeTracker {
+    /// 记录轮次开始时的工作区状态
+    pub async fn start(workspace: &Path) -> Self {
+        // git 返回的根目录是规范化路径（如 macOS 的 /private/tmp），工作区也统一规范化
+        let workspace = workspace
+            .canonicalize()
+            .unwrap_or_else(|_| workspace.to_path_buf());
+        let git_before = match git_toplevel(&workspace).await {
+            Some(root) => git_status(&root).await.map(|status| (root, status)),
+            None => None,
+        };
+        Self {
+            workspace,
+            git_before,
+            tracked: BTreeMap::new(),
+        }
+    }
+
+    /// 工具执行前调用：从参数中提取可能被写入的文件，记录执行前状态
+    pub fn record_tool_call(&mut self, tool_name: &str, args: &serde_json::Value) {
+        let targets: Vec<String> = match tool_name {
+            "file_write" => args
+                .get("path")
+                .and_then(|v| v.as_str())
+                .map(|p| vec![p.to_string()])
+                .unwrap_or_default(),
+            "shell" => args
+                .get("command")
+                .and_then(|v| v.as_str())
+                .map(shell_write_targets)
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        for target in targets {
+            let path = self.resolve(&target);
+            // 同一文件多次出现时保留最早的状态
+            self.tracked
+                .entry(path.clone())
+                .or_insert_with(|| FileStamp::of(&path));
+        }
+    }
+
+    /// 对比轮次前后的状态，生成变更摘要
+    pub async fn finish(self) -> ChangeSummary {
+        let mut changes: BTreeMap<PathBuf, ChangeKind> = BTreeMap::new();
+
+        if let Some((root, before)) = &self.git_before {
+            if let Some(after) = git_status(root).await {
+                for (path, status) in &after {
+                    if before.get(path) != Some(status) {
+                        changes.insert(root.join(path), classify_git_status(status));
+                    }
+                }
+                // 从 status 中消失的条目（commit / stash 等）不算文件内容变更，忽略
+            }
+        }
+
+        // 参数追踪的文件以实际 size/mtime 为准（可覆盖 git 的判断）
+        for (path, before) in &self.tracked {
+            let after = FileStamp::of(path);
+            match (before, after) {
+                (None, Some(_)) => {
+                    changes.insert(path.clone(), ChangeKind::Created);
+                }
+                (Some(_), None) => {
+                    changes.insert(path.clone(), ChangeKind::Deleted);
+                }
+                (Some(a), Some(b)) if *a != b => {
+                    changes.insert(path.clone(), ChangeKind::Modified);
+                }
+                _ => {
+                    // 未变化：git status 变动只可能来自暂存区，不报告
+                    changes.remove(path);
+                }
+            }
+        }
+
+        let mut summary = ChangeSummary::default();
+        for (path, kind) in changes {
+            let display = self.display(&path);
+            match kind {
+                ChangeKind::Modified => summary.modified.push(display),
+                ChangeKind::Created => summary.created.push(display),
+                ChangeKind::Deleted => summary.deleted.push(display),
+            }
+        }
+        summary
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.workspace.join(path)
+        }
+    }
+
+    fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.workspace)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+}
+
+/// porcelain 状态码 → 变更类型
+fn classify_git_status(status: &str) -> ChangeKind {
+    if status == "??" || status.starts_with('A') {
+        ChangeKind::Created
+    } else if status.contains('D') {
+        ChangeKind::Deleted
+    } else {
+        ChangeKind::Modified
+    }
+}
+
+async fn git_toplevel(workspace: &Path) -> Option<PathBuf> {
+    let output = tokio::process::Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(workspace)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let root = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!root.is_empty()).then(|| PathBuf::from(root))
+}
+
+/// `git status --porcelain -z`：路径（相对仓库根）→ 两字符状态码
+async fn git_status(root: &Path) -> Option<BTreeMap<PathBuf, String>> {
+    let output = tokio::process::Command::new("git")
+        .args(["status", "--porcelain", "-z", "--untracked-files=all"])
+        .current_dir(root)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_porcelain_z(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// 解析 `-z` 格式：`XY path\0`，重命名为 `XY new\0old\0`
+fn parse_porcelain_z(raw: &str) -> BTreeMap<PathBuf, String> {
+    let mut result = BTreeMap::new();
+    let mut fields = raw.split('\0');
+    while let Some(entry) = fields.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        let (status, path) = entry.split_at(2);
+        if status.starts_with('R') || status.starts_with('C') {
+            // 下一个字段是原路径
+            fields.next();
+        }
+        result.insert(PathBuf::from(&path[1..]), status.to_string());
+    }
+    result
+}
+
+/// 从 shell 命令中提取会被写入 / 删除的文件
+///
+/// 只做轻量词法分析，覆盖常见形式：`>` / `>>` 重定向、`tee`、`touch`、`rm`、
+/// `cp` / `mv` 的目标、`sed -i` 的文件参数。
+pub fn shell_write_targets(command: &str) -> Vec<String> {
+    let tokens = shell_tokens(command);
+    let mut targets = Vec::new();
+
+    for segment in tokens.split(|t| matches!(t.as_str(), "|" | "||" | "&&" | ";" | "&")) {
+        // 重定向目标（`> f`、`>>f`、`2>err.log`、`echo a>f`），同时收集去掉重定向后的参数
+        let mut words: Vec<&str> = Vec::new();
+        let mut iter = segment.iter();
+        while let Some(tok) = iter.next() {
+            let Some(idx) = tok.find('>') else {
+                words.push(tok);
+                continue;
+            };
+            let (prefix, redirect) = tok.split_at(idx);
+            if !prefix.is_empty() && !prefix.chars().all(|c| c.is_ascii_digit()) {
+                words.push(prefix);
+            }
+            let target = redirect.trim_start_matches('>');
+            if target.is_empty() {
+                if let Some(next) = iter.next() {
+                    push_target(&mut targets, next);
+                }
+            } else if !target.starts_with('&') {
+                push_target(&mut targets, target);
+            }
+        }
+        let Some((&program, args)) = words.split_first() else {
+            continue;
+        };
+        let operands: Vec<&str> = args
+            .iter()
+            .copied()
+            .filter(|a| !a.starts_with('-'))
+            .collect();
+        match program.rsplit('/').next().unwrap_or(program) {
+            "tee" | "touch" | "rm" => {
+                for op in &operands {
+                    push_target(&mut targets, op);
+                }
+            }
+            "cp" if operands.len() >= 2 => {
+                push_target(&mut targets, operands[operands.len() - 1]);
+            }
+            "mv" => {
+                for op in &operands {
+                    push_target(&mut targets, op);
+                }
+            }
+            "sed" if args.iter().any(|a| a.starts_with("-i")) => {
+                // 第一个非选项参数是脚本，其余是文件
+                for op in operands.iter().skip(1) {
+                    push_target(&mut targets, op);
+                }
+            }
+            _ => {}
+        }
+    }
+    targets
+}
+
+fn push_target(targets: &mut Vec<String>, candidate: &str) {
+    let candidate = candidate.trim();
+    if candidate.is_empty()
+        || candidate.starts_with("/dev/")
+        || candidate.contains(['$', '*', '?', '`'])
+        || targets.iter().any(|t| t == candidate)
+    {
+        return;
+    }
+    targets.push(candidate.to_string());
+}
+
+/// 按空白切分，支持单/双引号；`|`、`;`、`&&` 等控制符即使紧贴也单独成词
+fn shell_tokens(command: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars().peekable();
+
+    let flush = |current: &mut String, tokens: &mut Vec<String>| {
+        if !current.is_empty() {
+            tokens.push(std::mem::take(current));
+        }
+    };
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                c if c.is_whitespace() => flush(&mut current, &mut tokens),
+                ';' => {
+                    flush(&mut current, &mut tokens);
+                    tokens.push(";".to_string());
+                }
+                '|' | '&' => {
+                    // `2>&1`、`>&2` 中的 & 属于重定向
+                    if c == '&' && current.ends_with('>') {
+                        current.push(c);
+                        continue;
+                    }
+                    flush(&mut current, &mut tokens);
+                    let mut op = c.to_string();
+                    if chars.peek() == Some(&c) {
+                        op.push(chars.next().unwrap_or(c));
+                    }
+                    tokens.push(op);
+                }
+                _ => current.push(c),
+            },
+        }
+    }
+    flush(&mut current, &mut tokens);
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(status.status.success(), "git {:?} 失败", args);
+    }
+
+    fn temp_repo() -> TempDir {
+        let tmp = TempDir::new().unwrap();
+        git(tmp.path(), &["init", "-q"]);
+        git(tmp.path(), &["config", "user.email", "t@example.com"]);
+        git(tmp.path(), &["config", "user.name", "t"]);
+        std::fs::write(tmp.path().join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(tmp.path().join("old.txt"), "old\n").unwrap();
+        std::fs::write(tmp.path().join("dirty.txt"), "v1\n").unwrap();
+        git(tmp.path(), &["add", "."]);
+        git(tmp.path(), &["commit", "-qm", "init"]);
+        // 轮次开始前就已经 dirty
+        std::fs::write(tmp.path().join("dirty.txt"), "v2\n").unwrap();
+        tmp
+    }
+
+    #[tokio::test]
+    async fn git_repo_changes_are_classified() {
+        let repo = temp_repo();
+        let ws = repo.path();
+        let mut tracker = ChangeTracker::start(ws).await;
+
+        // 模拟本轮工具调用
+        tracker.record_tool_call("file_write", &serde_json::json!({"path": "main.rs"}));
+        std::fs::write(ws.join("main.rs"), "fn main() { println!(); }\n").unwrap();
+        tracker.record_tool_call(
+            "shell",
+            &serde_json::json!({"command": "echo hi > notes.md && rm old.txt"}),
+        );
+        std::fs::write(ws.join("notes.md"), "hi\n").unwrap();
+        std::fs::remove_file(ws.join("old.txt")).unwrap();
+
+        let summary = tracker.finish().await;
+        assert_eq!(summary.modified, vec!["main.rs"]);
+        assert_eq!(summary.created, vec!["notes.md"]);
+        assert_eq!(summary.deleted, vec!["old.txt"]);
+    }
+
+    #[tokio::test]
+    async fn already_dirty_file_only_reported_when_touched() {
+        let repo = temp_repo();
+        let ws = repo.path();
+
+        // 未触碰 dirty.txt：不报告
+        let tracker = ChangeTracker::start(ws).await;
+        assert!(tracker.finish().await.is_empty());
+
+        // shell 重定向再次写入 dirty.txt：git status 不变，但参数追踪能发现
+        let mut tracker = ChangeTracker::start(ws).await;
+        tracker.record_tool_call(
+            "shell",
+            &serde_json::json!({"command": "printf 'v3 longer' >> dirty.txt"}),
+        );
+        std::fs::write(ws.join("dirty.txt"), "v3 longer\n").unwrap();
+        let summary = tracker.finish().await;
+        assert_eq!(summary.modified, vec!["dirty.txt"]);
+        assert!(summary.created.is_empty());
+    }
+
+    #[tokio::test]
+    async fn non_git_workspace_uses_tracked_files() {
+        let tmp = TempDir::new().unwrap();
+        let ws = tmp.path();
+        std::fs::write(ws.join("a.txt"), "a").unwrap();
+
+        let mut tracker = ChangeTracker::start(ws).await;
+        assert!(tracker.git_before.is_none());
+        tracker.record_tool_call("file_write", &serde_json::json!({"path": "a.txt"}));
+        tracker.record_tool_call(
+            "shell",
+            &serde_json::json!({"command": "cat a.txt | tee out/b.txt"}),
+        );
+        // 未在参数中出现的文件不会被发现（非 git 模式的已知限制）
+        std::fs::write(ws.join("untracked.txt"), "x").unwrap();
+        std::fs::write(ws.join("a.txt"), "aaaa").unwrap();
+        std::fs::create_dir_all(ws.join("out")).unwrap();
+        std::fs::write(ws.join("out/b.txt"), "b").unwrap();
+
+        let summary = tracker.finish().await;
+        assert_eq!(summary.modified, vec!["a.txt"]);
+        assert_eq!(summary.created, vec!["out/b.txt"]);
+    }
+
+    #[test]
+    fn shell_targets_extracted_from_redirects_and_commands() {
+        assert_eq!(
+            shell_write_targets("cargo build 2>&1 > build.log"),
+            vec!["build.log"]
+        );
+        assert_eq!(
+            shell_write_targets("echo a >>notes.md; echo b>/dev/null; ls 2>err.log"),
+            vec!["notes.md", "err.log"]
+        );
+        assert_eq!(shell_write_targets("echo b>out.txt"), vec!["out.txt"]);
+        assert_eq!(
+            shell_write_targets("ls | tee -a out.txt && touch x y"),
+            vec!["out.txt", "x", "y"]
+        );
+        assert_eq!(
+            shell_write_targets("cp src/a.rs \"dst dir/a.rs\""),
+            vec!["dst dir/a.rs"]
+        );
+        assert_eq!(
+            shell_write_targets("mv a.txt b.txt"),
+            vec!["a.txt", "b.txt"]
+        );
+        assert_eq!(
+            shell_write_targets("sed -i 's/a/b/' Cargo.toml"),
+            vec!["Cargo.toml"]
+        );
+        assert!(shell_write_targets("grep -r foo src").is_empty());
+        assert!(shell_write_targets("echo $HOME > $OUT").is_empty());
+    }
+
+    #[test]
+    fn porcelain_z_parsing_handles_renames() {
+        let parsed = parse_porcelain_z(" M src/main.rs\0?? new file.md\0R  b.rs\0a.rs\0");
+        assert_eq!(parsed[Path::new("src/main.rs")], " M");
+        assert_eq!(parsed[Path::new("new file.md")], "??");
+        assert_eq!(parsed[Path::new("b.rs")], "R ");
+        assert!(!parsed.contains_key(Path::new("a.rs")));
+    }
+
+    #[test]
+    fn summary_line_format() {
+        let summary = ChangeSummary {
+            modified: vec!["src/main.rs".into(), "Cargo.toml".into()],
+            created: vec!["notes.md".into()],
+            deleted: vec![],
+        };
+        assert_eq!(
+            summary.format_line(),
+            "✎ modified: src/main.rs, Cargo.toml · created: notes.md"
+        );
+
+        let many = ChangeSummary {
+            modified: (0..10).map(|i| format!("f{}", i)).collect(),
+            ..Default::default()
+        };
+        assert!(many.format_line().ends_with("f7 +2"));
+    }
+}
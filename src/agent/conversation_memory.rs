@@ -0,0 +1,207 @@
+//! 对话摘要写入记忆前的筛选（`[memory] store_conversations`）
+//!
+//! 每轮结束后 `run_turn` 调用 [`ConversationFilter::summarize`]：返回 None 表示本轮不写入。
+//! `filtered`（默认）跳过三类无用摘要：
+//! - 双方都很短且没有调用工具的寒暄（"ok" / "谢谢" → "不客气"）
+//! - 没有最终回复（工具迭代用尽 / 空回复）或回复本身是失败提示
+//! - 与上一条已保存摘要规范化后相同的重复内容
+//!
+//! 调用了工具的回合额外记录工具名、成败与最后一个工具的结果，
+//! 之后 recall 到的是"做了什么、结果如何"，而不只是一问一答。
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::loop_::ToolFeedback;
+use crate::config::{MemoryConfig, StoreConversations};
+
+/// 工具结果摘要的最大长度（字符）
+const OUTCOME_MAX_CHARS: usize = 120;
+
+/// 失败提示前缀（与工具结果的约定一致）
+const FAILURE_PREFIXES: &[&str] = &["[失败]", "[错误]", "Error:"];
+
+/// 对话摘要筛选器（Agent 持有，记住上一条已保存摘要的指纹）
+#[derive(Debug, Clone)]
+pub struct ConversationFilter {
+    mode: StoreConversations,
+    min_user_chars: usize,
+    min_reply_chars: usize,
+    last_digest: Option<u64>,
+}
+
+impl Default for ConversationFilter {
+    fn default() -> Self {
+        Self::from_config(&MemoryConfig::default())
+    }
+}
+
+impl ConversationFilter {
+    pub fn from_config(config: &MemoryConfig) -> Self {
+        Self {
+            mode: config.store_conversations,
+            min_user_chars: config.store_min_user_chars,
+            min_reply_chars: config.store_min_reply_chars,
+            last_digest: None,
+        }
+    }
+
+    /// 生成本轮要保存的摘要；None 表示跳过
+    pub fn summarize(
+        &mut self,
+        user_msg: &str,
+        reply: &str,
+        tools: &[ToolFeedback],
+    ) -> Option<String> {
+        let summary = build_summary(user_msg, reply, tools);
+        match self.mode {
+            StoreConversations::Off => return None,
+            StoreConversations::All => return Some(summary),
+            StoreConversations::Filtered => {}
+        }
+
+        let reply = reply.trim();
+        if reply.is_empty() || is_failure(reply) {
+            return None;
+        }
+        if tools.is_empty()
+            && user_msg.trim().chars().count() < self.min_user_chars
+            && reply.chars().count() < self.min_reply_chars
+        {
+            return None;
+        }
+        let digest = digest(&summary);
+        if self.last_digest == Some(digest) {
+            return None;
+        }
+        self.last_digest = Some(digest);
+        Some(summary)
+    }
+}
+
+fn is_failure(text: &str) -> bool {
+    FAILURE_PREFIXES.iter().any(|p| text.starts_with(p))
+}
+
+/// 无工具：`User / Assistant` 两行；有工具时插入 `Tools` 与 `Outcome`
+fn build_summary(user_msg: &str, reply: &str, tools: &[ToolFeedback]) -> String {
+    let Some(last) = tools.last() else {
+        return format!("User: {}\nAssistant: {}", user_msg, reply);
+    };
+    let ran = tools
+        .iter()
+        .map(|t| {
+            let status = if is_failure(&t.content) {
+                "failed"
+            } else {
+                "ok"
+            };
+            format!("{}({})", t.tool, status)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let outcome = last
+        .summary
+        .as_deref()
+        .unwrap_or(&last.content)
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("");
+    format!(
+        "User: {}\nTools: {}\nOutcome: {}\nAssistant: {}",
+        user_msg,
+        ran,
+        truncate_chars(outcome.trim(), OUTCOME_MAX_CHARS),
+        reply
+    )
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+/// 规范化指纹：只保留字母数字并转小写（忽略标点、空白与大小写差异）
+fn digest(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for c in text.chars().filter(|c| c.is_alphanumeric()) {
+        for lower in c.to_lowercase() {
+            lower.hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feedback(tool: &str, summary: Option<&str>, content: &str) -> ToolFeedback {
+        ToolFeedback {
+            tool: tool.to_string(),
+            summary: summary.map(str::to_string),
+            content: content.to_string(),
+        }
+    }
+
+    fn filter(mode: StoreConversations) -> ConversationFilter {
+        ConversationFilter::from_config(&MemoryConfig {
+            store_conversations: mode,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn filtered_skips_trivial_failed_and_repeated_turns() {
+        let mut f = filter(StoreConversations::Filtered);
+        let long_reply = "Rust 的所有权规则保证每个值只有一个所有者，离开作用域时自动释放，借用检查器在编译期防止悬垂引用和数据竞争，无需垃圾回收，也没有运行时开销，这就是 Rust 能同时兼顾内存安全与性能的关键。";
+
+        assert_eq!(f.summarize("ok", "好的", &[]), None);
+        assert_eq!(f.summarize("解释一下 Rust 的所有权", "", &[]), None);
+        assert_eq!(
+            f.summarize("解释一下 Rust 的所有权", "[错误] 请求超时", &[]),
+            None
+        );
+        // 短问题配长回答照常保存
+        assert!(f.summarize("所有权？", long_reply, &[]).is_some());
+        // 只差标点和大小写也算重复
+        assert_eq!(
+            f.summarize("所有权!", &format!("{} ", long_reply), &[]),
+            None
+        );
+        // 不相邻的重复照常保存
+        assert!(f
+            .summarize("再讲讲生命周期标注的用法", long_reply, &[])
+            .is_some());
+        assert!(f.summarize("所有权？", long_reply, &[]).is_some());
+    }
+
+    #[test]
+    fn tool_turns_record_tools_and_outcome() {
+        let mut f = filter(StoreConversations::Filtered);
+        let tools = [
+            feedback("file_read", None, "[失败] 文件不存在"),
+            feedback("shell", Some("exit 0\ntest result: ok"), "..."),
+        ];
+        // 有工具时即使双方都很短也保存
+        let summary = f.summarize("跑测试", "通过了", &tools).unwrap();
+        assert_eq!(
+            summary,
+            "User: 跑测试\nTools: file_read(failed), shell(ok)\nOutcome: exit 0\nAssistant: 通过了"
+        );
+    }
+
+    #[test]
+    fn all_and_off_ignore_heuristics() {
+        let mut all = filter(StoreConversations::All);
+        assert!(all.summarize("ok", "好的", &[]).is_some());
+        assert!(all.summarize("ok", "好的", &[]).is_some());
+        assert!(all.summarize("hi", "", &[]).is_some());
+
+        let mut off = filter(StoreConversations::Off);
+        let long = "x".repeat(200);
+        assert_eq!(off.summarize(&long, &long, &[]), None);
+    }
+}
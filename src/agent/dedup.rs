@@ -0,0 +1,195 @@
+//! 本轮工具调用去重
+//!
+//! 模型常在同一 Turn 内用完全相同的参数连续调用同一工具（失败后尤甚），白白消耗迭代次数。
+//! `CallLedger` 以 (工具名, 规范化参数哈希) 为键记录本轮调用：
+//! - 已成功的调用再次出现：不执行，返回缓存结果（带 `[重复调用，返回缓存结果]` 前缀）
+//! - 已失败的调用：允许重试一次，第二次失败后直接短路，提示模型换方法
+//!
+//! 任何一次真实执行都可能改变外部状态，因此执行后会丢弃其他调用的缓存结果，
+//! 只对"中间没有其他执行"的重复调用返回缓存。`Tool::cacheable` 返回 false 的调用
+//! （如 `date`、`ps`、`memory_recall`）不进入账本。
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::turns::is_failed_tool_result;
+
+/// 缓存结果前缀
+pub const CACHED_PREFIX: &str = "[重复调用，返回缓存结果]";
+
+/// 同一调用失败两次后的短路提示
+pub const REPEATED_FAILURE_MESSAGE: &str = "[失败] 该调用已失败两次，请换一种方法或询问用户";
+
+/// 同一调用允许失败的次数（首次 + 一次重试）
+const MAX_FAILURES: u32 = 2;
+
+/// 账本键：工具名 + 规范化参数哈希
+pub type CallKey = (String, u64);
+
+/// 账本对一次调用的判定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedgerCheck {
+    /// 正常执行
+    Execute,
+    /// 本轮已成功执行过，直接返回缓存内容（已加前缀）
+    Cached(String),
+    /// 本轮已失败两次，不再执行
+    ShortCircuit,
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    success: Option<String>,
+    failures: u32,
+}
+
+/// 本轮工具调用账本（每个 Turn 开始时清空）
+#[derive(Debug, Default)]
+pub struct CallLedger {
+    entries: HashMap<CallKey, Entry>,
+}
+
+impl CallLedger {
+    /// 生成调用键（对象字段按 key 排序后哈希，字段顺序不同视为同一调用）
+    pub fn key(tool_name: &str, args: &serde_json::Value) -> CallKey {
+        let mut hasher = DefaultHasher::new();
+        canonical_json(args).hash(&mut hasher);
+        (tool_name.to_string(), hasher.finish())
+    }
+
+    /// 清空账本（新 Turn）
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 执行前检查
+    pub fn check(&self, key: &CallKey) -> LedgerCheck {
+        match self.entries.get(key) {
+            Some(Entry {
+                success: Some(content),
+                ..
+            }) => LedgerCheck::Cached(format!("{}\n{}", CACHED_PREFIX, content)),
+            Some(entry) if entry.failures >= MAX_FAILURES => LedgerCheck::ShortCircuit,
+            _ => LedgerCheck::Execute,
+        }
+    }
+
+    /// 记录一次真实执行的结果（LLM 最终看到的内容）
+    pub fn record(&mut self, key: CallKey, content: &str) {
+        // 执行可能改变了外部状态，其他调用的缓存结果不再可信
+        self.invalidate_except(Some(&key));
+        let entry = self.entries.entry(key).or_default();
+        if is_failed_tool_result(content) {
+            entry.failures += 1;
+            entry.success = None;
+        } else {
+            entry.success = Some(content.to_string());
+        }
+    }
+
+    /// 不可缓存的调用执行后，丢弃全部缓存结果（失败计数保留）
+    pub fn invalidate(&mut self) {
+        self.invalidate_except(None);
+    }
+
+    fn invalidate_except(&mut self, keep: Option<&CallKey>) {
+        for (key, entry) in self.entries.iter_mut() {
+            if Some(key) != keep {
+                entry.success = None;
+            }
+        }
+    }
+}
+
+/// 规范化 JSON：对象字段按 key 排序，其余原样输出
+fn canonical_json(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut fields: Vec<_> = map.iter().collect();
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+            let inner: Vec<String> = fields
+                .into_iter()
+                .map(|(k, v)| {
+                    format!(
+                        "{}:{}",
+                        serde_json::Value::String(k.clone()),
+                        canonical_json(v)
+                    )
+                })
+                .collect();
+            format!("{{{}}}", inner.join(","))
+        }
+        serde_json::Value::Array(items) => {
+            let inner: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", inner.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn key_ignores_field_order() {
+        let a = json!({"path": "a.txt", "opts": {"x": 1, "y": [1, 2]}});
+        let b: serde_json::Value =
+            serde_json::from_str(r#"{"opts":{"y":[1,2],"x":1},"path":"a.txt"}"#).unwrap();
+        assert_eq!(
+            CallLedger::key("file_read", &a),
+            CallLedger::key("file_read", &b)
+        );
+        assert_ne!(
+            CallLedger::key("file_read", &a),
+            CallLedger::key("file_write", &a)
+        );
+        assert_ne!(
+            CallLedger::key("file_read", &a),
+            CallLedger::key("file_read", &json!({"path": "b.txt"}))
+        );
+    }
+
+    #[test]
+    fn success_is_cached_with_prefix() {
+        let mut ledger = CallLedger::default();
+        let key = CallLedger::key("file_read", &json!({"path": "a"}));
+        assert_eq!(ledger.check(&key), LedgerCheck::Execute);
+
+        ledger.record(key.clone(), "hello");
+        match ledger.check(&key) {
+            LedgerCheck::Cached(content) => {
+                assert!(content.starts_with(CACHED_PREFIX));
+                assert!(content.ends_with("hello"));
+            }
+            other => panic!("expected cached, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failure_allows_one_retry_then_short_circuits() {
+        let mut ledger = CallLedger::default();
+        let key = CallLedger::key("shell", &json!({"command": "make"}));
+
+        ledger.record(key.clone(), "[失败] exit 2");
+        assert_eq!(ledger.check(&key), LedgerCheck::Execute);
+        ledger.record(key.clone(), "[失败] exit 2");
+        assert_eq!(ledger.check(&key), LedgerCheck::ShortCircuit);
+
+        ledger.clear();
+        assert_eq!(ledger.check(&key), LedgerCheck::Execute);
+    }
+
+    #[test]
+    fn other_execution_invalidates_cached_results() {
+        let mut ledger = CallLedger::default();
+        let read = CallLedger::key("file_read", &json!({"path": "a"}));
+        let write = CallLedger::key("file_write", &json!({"path": "a", "content": "x"}));
+
+        ledger.record(read.clone(), "old");
+        ledger.record(write, "ok");
+        assert_eq!(ledger.check(&read), LedgerCheck::Execute);
+    }
+}
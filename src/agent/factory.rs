@@ -0,0 +1,746 @@
+//! Agent 工厂：缓存构造 Agent 所需的不可变部件
+//!
+//! Routine 每次执行、Telegram 每个新 chat、daemon 每条消息都要新建 Agent。Provider 客户端
+//! （含 fallback 与辅助模型）、Skills 目录扫描、身份文件读取、安全策略模板只依赖配置和磁盘文件，
+//! 由工厂构建一次后缓存；`create_agent` 只做 `create_tools` + `Agent::new`。
+//!
+//! 缓存失效：
+//! - 配置版本号（`LiveConfig::generation`）变化 → 下次 `create_agent` 时整体重建
+//! - `invalidate_skills` / `invalidate_identity` → 只重新扫描 Skills / 重新读取身份文件。
+//!   写入方（REPL 的 `/skill new|edit|delete`、`/identity edit|reload`）通过 `ChangeSignal` 通知，
+//!   同一进程内的工厂（Routine、REPL 托管的 Telegram）在下次创建 Agent 前调用对应 hook；
+//!   daemon 是独立进程，需要 `rrclaw reload`
+//!
+//! daemon 的 `rrclaw chat --workspace` 用 `create_agent_in`：每个工作目录各有一份安全策略、
+//! 项目级 Skills 与身份文件，按工作目录 + 配置版本缓存，Provider 仍共用。
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use color_eyre::eyre::{eyre, Result};
+
+use super::{Agent, AuxModel, ConversationFilter};
+use crate::config::{Config, LiveConfig, ProviderConfig, RrclawPaths};
+use crate::memory::Memory;
+use crate::providers::{
+    resolve_context_window, OfflineState, Provider, ReliableProvider, RetryConfig, ToolLimits,
+};
+use crate::security::redact::Redactor;
+use crate::security::SecurityPolicy;
+use crate::skills::SkillMeta;
+
+/// 由 Provider 配置构造客户端（默认 `providers::create_provider`，测试可替换为计数 fake）
+pub type ProviderBuilder = Arc<dyn Fn(&ProviderConfig) -> Box<dyn Provider> + Send + Sync>;
+
+/// 工厂内部计数（观察缓存是否生效）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FactoryStats {
+    /// 构造 Provider 客户端的次数（主 Provider + fallback）
+    pub provider_builds: usize,
+    /// 扫描 Skills 目录的次数
+    pub skill_scans: usize,
+    /// 读取身份文件的次数
+    pub identity_loads: usize,
+    /// 创建的 Agent 数
+    pub agents_created: usize,
+}
+
+/// Skills / 身份文件的修改计数：写入方递增，工厂创建 Agent 前比对，有变化时刷新对应部件
+#[derive(Debug, Default)]
+pub struct ChangeSignal {
+    skills: AtomicU64,
+    identity: AtomicU64,
+}
+
+impl ChangeSignal {
+    /// 进程级共享实例（工厂默认使用）
+    pub fn global() -> Arc<ChangeSignal> {
+        static GLOBAL: OnceLock<Arc<ChangeSignal>> = OnceLock::new();
+        GLOBAL.get_or_init(Arc::default).clone()
+    }
+
+    /// Skills 目录有变化（新建 / 编辑 / 删除技能）
+    pub fn skills_changed(&self) {
+        self.skills.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// 身份文件有变化
+    pub fn identity_changed(&self) {
+        self.identity.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn counts(&self) -> (u64, u64) {
+        (
+            self.skills.load(Ordering::Acquire),
+            self.identity.load(Ordering::Acquire),
+        )
+    }
+}
+
+/// 辅助模型的共享部件（每个 Agent 各自包一层 `AuxModel`）
+#[derive(Clone)]
+struct SharedAux {
+    /// None：沿用 Agent 的主 Provider（见 `AuxModel::provider`）
+    provider: Option<(Arc<dyn Provider>, String)>,
+    model: String,
+}
+
+impl SharedAux {
+    fn from_config(
+        config: &Config,
+        aux: Option<&crate::config::AuxModelConfig>,
+        offline: Arc<OfflineState>,
+    ) -> Option<Self> {
+        AuxModel::from_config(config, aux, offline).map(|aux| Self {
+            provider: aux
+                .provider
+                .zip(aux.provider_name)
+                .map(|(provider, name)| (Arc::from(provider), name)),
+            model: aux.model,
+        })
+    }
+
+    fn to_aux_model(&self) -> AuxModel {
+        match &self.provider {
+            Some((provider, name)) => AuxModel::new(Box::new(provider.clone()), name, &self.model),
+            None => AuxModel::on_main_provider(&self.model),
+        }
+    }
+}
+
+/// 某个配置版本下构建好的部件
+#[derive(Clone)]
+struct Prepared {
+    generation: u64,
+    config: Arc<Config>,
+    provider: Arc<dyn Provider>,
+    routing: Option<SharedAux>,
+    summary: Option<SharedAux>,
+    skills: Vec<SkillMeta>,
+    identity_context: Option<String>,
+    policy: SecurityPolicy,
+    summary_scrubber: Option<Redactor>,
+}
+
+/// 某个工作目录下的部件（`create_agent_in`）
+struct WorkspaceParts {
+    generation: u64,
+    skills: Vec<SkillMeta>,
+    identity_context: Option<String>,
+    policy: SecurityPolicy,
+}
+
+/// Agent 工厂（Routine / Telegram / daemon 共用）
+pub struct AgentFactory {
+    config: Arc<LiveConfig>,
+    memory: Arc<dyn Memory>,
+    paths: RrclawPaths,
+    workspace_dir: PathBuf,
+    /// 安全策略的受保护路径（默认 `SecurityPolicy::default()`）
+    blocked_paths: Vec<PathBuf>,
+    load_skills: bool,
+    load_identity: bool,
+    offline: Option<Arc<OfflineState>>,
+    provider_builder: ProviderBuilder,
+    changes: Arc<ChangeSignal>,
+    /// 已处理到的 `ChangeSignal` 计数（skills, identity）
+    seen_changes: Mutex<(u64, u64)>,
+    cache: Mutex<Option<Arc<Prepared>>>,
+    /// 规范化后的工作目录 → 该目录的部件
+    workspaces: Mutex<HashMap<PathBuf, Arc<WorkspaceParts>>>,
+    provider_builds: AtomicUsize,
+    skill_scans: AtomicUsize,
+    identity_loads: AtomicUsize,
+    agents_created: AtomicUsize,
+}
+
+impl AgentFactory {
+    /// 默认不加载 Skills 和身份文件，工作目录为当前目录
+    pub fn new(config: Arc<LiveConfig>, memory: Arc<dyn Memory>, paths: RrclawPaths) -> Self {
+        Self {
+            config,
+            memory,
+            paths,
+            workspace_dir: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            blocked_paths: SecurityPolicy::default().blocked_paths,
+            load_skills: false,
+            load_identity: false,
+            offline: None,
+            provider_builder: Arc::new(crate::providers::create_provider),
+            seen_changes: Mutex::new(ChangeSignal::global().counts()),
+            changes: ChangeSignal::global(),
+            cache: Mutex::new(None),
+            workspaces: Mutex::new(HashMap::new()),
+            provider_builds: AtomicUsize::new(0),
+            skill_scans: AtomicUsize::new(0),
+            identity_loads: AtomicUsize::new(0),
+            agents_created: AtomicUsize::new(0),
+        }
+    }
+
+    /// 加载 Skills（内置 + 全局 + 项目级）
+    pub fn with_skills(mut self) -> Self {
+        self.load_skills = true;
+        self
+    }
+
+    /// 加载身份文件（USER.md / SOUL.md / AGENT.md）
+    pub fn with_identity(mut self) -> Self {
+        self.load_identity = true;
+        self
+    }
+
+    /// Provider 使用指定的离线状态（默认全局实例）
+    pub fn with_offline_state(mut self, offline: Arc<OfflineState>) -> Self {
+        self.offline = Some(offline);
+        self
+    }
+
+    /// 指定工作目录（默认当前目录）
+    pub fn with_workspace_dir(mut self, workspace_dir: PathBuf) -> Self {
+        self.workspace_dir = workspace_dir;
+        self
+    }
+
+    /// 替换受保护路径（测试的工作目录在 /tmp 下，需要放开）
+    #[cfg(test)]
+    fn with_blocked_paths(mut self, blocked_paths: Vec<PathBuf>) -> Self {
+        self.blocked_paths = blocked_paths;
+        self
+    }
+
+    /// 使用指定的修改通知（默认全局实例）
+    pub fn with_change_signal(mut self, changes: Arc<ChangeSignal>) -> Self {
+        self.seen_changes = Mutex::new(changes.counts());
+        self.changes = changes;
+        self
+    }
+
+    /// 替换 Provider 构造函数
+    pub fn with_provider_builder(mut self, builder: ProviderBuilder) -> Self {
+        self.provider_builder = builder;
+        self
+    }
+
+    /// 工厂使用的目录布局
+    pub fn paths(&self) -> &RrclawPaths {
+        &self.paths
+    }
+
+    /// 工厂使用的配置
+    pub fn live_config(&self) -> &Arc<LiveConfig> {
+        &self.config
+    }
+
+    /// 用缓存部件创建一个新 Agent（配置版本变化时先重建缓存）
+    pub fn create_agent(&self) -> Result<Agent> {
+        self.apply_changes();
+        let prepared = self.prepared()?;
+        self.build_agent(
+            &prepared,
+            prepared.skills.clone(),
+            prepared.identity_context.clone(),
+            prepared.policy.clone(),
+        )
+    }
+
+    /// 在指定工作目录下创建 Agent：安全策略、项目级 Skills、身份文件都以该目录为准
+    ///
+    /// 目录不存在或位于受保护路径下时报错。
+    pub fn create_agent_in(&self, workspace_dir: &Path) -> Result<Agent> {
+        self.apply_changes();
+        let prepared = self.prepared()?;
+        let parts = self.workspace_parts(&prepared, workspace_dir)?;
+        self.build_agent(
+            &prepared,
+            parts.skills.clone(),
+            parts.identity_context.clone(),
+            parts.policy.clone(),
+        )
+    }
+
+    fn build_agent(
+        &self,
+        prepared: &Prepared,
+        skills: Vec<SkillMeta>,
+        identity_context: Option<String>,
+        policy: SecurityPolicy,
+    ) -> Result<Agent> {
+        let config = &prepared.config;
+        let provider_key = &config.default.provider;
+        let provider_config = config
+            .providers
+            .get(provider_key)
+            .ok_or_else(|| eyre!("Provider '{}' 未配置", provider_key))?;
+
+        let tools = if config.security.safe_mode {
+            Vec::new()
+        } else {
+            crate::tools::create_tools(
+                (**config).clone(),
+                prepared.provider.clone(),
+                self.paths.data_dir(),
+                self.paths.log_dir(),
+                self.paths.config_file().to_path_buf(),
+                skills.clone(),
+                self.memory.clone(),
+                None, // 工厂创建的 Agent 不注册 RoutineTool（避免循环调度）
+            )
+        };
+        let mut agent = Agent::new(
+            Box::new(prepared.provider.clone()),
+            tools,
+            Box::new(self.memory.clone()),
+            policy,
+            provider_key.clone(),
+            provider_config.base_url.clone(),
+            config.default.model.clone(),
+            config.default.temperature,
+            skills,
+            identity_context,
+        );
+        agent.set_tool_limits(ToolLimits::for_provider(provider_config));
+        agent.set_context_window(resolve_context_window(
+            provider_key,
+            provider_config,
+            &config.default.model,
+        ));
+        agent.set_routing_model(prepared.routing.as_ref().map(SharedAux::to_aux_model));
+        agent.set_summary_model(prepared.summary.as_ref().map(SharedAux::to_aux_model));
+        agent.set_summary_scrubber(prepared.summary_scrubber.clone());
+        agent.configure_system_prompt(&config.agent);
+        agent.set_conversation_filter(ConversationFilter::from_config(&config.memory));
+        if config.security.safe_mode {
+            agent.enter_safe_mode();
+        }
+        self.agents_created.fetch_add(1, Ordering::Relaxed);
+        Ok(agent)
+    }
+
+    /// 刷新已存在的 Agent（如 Telegram 缓存的会话）：安全策略 + 身份文件，对话历史保留
+    pub fn refresh_agent(&self, agent: &mut Agent) {
+        let config = self.config.snapshot();
+        agent.apply_security_config(&config.security);
+        if self.load_identity {
+            let workspace_dir = agent.policy().workspace_dir.clone();
+            agent.reload_identity(&workspace_dir, self.paths.home());
+        }
+    }
+
+    /// Skills 目录有变化（新建/编辑/删除技能）：重新扫描
+    pub fn invalidate_skills(&self) {
+        self.update_cached(|factory, prepared| {
+            prepared.skills = factory.scan_skills(&factory.workspace_dir);
+        });
+        self.workspaces.lock().unwrap().clear();
+    }
+
+    /// 身份文件有变化：重新读取
+    pub fn invalidate_identity(&self) {
+        self.update_cached(|factory, prepared| {
+            prepared.identity_context = factory.read_identity(&factory.workspace_dir);
+        });
+        self.workspaces.lock().unwrap().clear();
+    }
+
+    /// 处理上次创建 Agent 之后的 `ChangeSignal` 通知
+    fn apply_changes(&self) {
+        let current = self.changes.counts();
+        let seen = std::mem::replace(&mut *self.seen_changes.lock().unwrap(), current);
+        if current.0 != seen.0 {
+            self.invalidate_skills();
+        }
+        if current.1 != seen.1 {
+            self.invalidate_identity();
+        }
+    }
+
+    /// 丢弃全部缓存，下次创建 Agent 时重建
+    pub fn invalidate(&self) {
+        *self.cache.lock().unwrap() = None;
+        self.workspaces.lock().unwrap().clear();
+    }
+
+    pub fn stats(&self) -> FactoryStats {
+        FactoryStats {
+            provider_builds: self.provider_builds.load(Ordering::Relaxed),
+            skill_scans: self.skill_scans.load(Ordering::Relaxed),
+            identity_loads: self.identity_loads.load(Ordering::Relaxed),
+            agents_created: self.agents_created.load(Ordering::Relaxed),
+        }
+    }
+
+    fn prepared(&self) -> Result<Arc<Prepared>> {
+        let generation = self.config.generation();
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(prepared) = cache.as_ref().filter(|p| p.generation == generation) {
+            return Ok(prepared.clone());
+        }
+        let prepared = Arc::new(self.prepare(generation)?);
+        *cache = Some(prepared.clone());
+        Ok(prepared)
+    }
+
+    /// 工作目录的部件：同一配置版本内复用，版本变化后重新扫描
+    fn workspace_parts(
+        &self,
+        prepared: &Prepared,
+        workspace_dir: &Path,
+    ) -> Result<Arc<WorkspaceParts>> {
+        let mut policy = prepared.policy.clone();
+        policy.set_workspace(workspace_dir)?;
+        let key = policy.workspace_dir.clone();
+
+        let mut workspaces = self.workspaces.lock().unwrap();
+        if let Some(parts) = workspaces
+            .get(&key)
+            .filter(|p| p.generation == prepared.generation)
+        {
+            return Ok(parts.clone());
+        }
+        let parts = Arc::new(WorkspaceParts {
+            generation: prepared.generation,
+            skills: self.scan_skills(&key),
+            identity_context: self.read_identity(&key),
+            policy,
+        });
+        workspaces.insert(key, parts.clone());
+        Ok(parts)
+    }
+
+    /// 修改已缓存的部件（无缓存时什么都不做，下次创建时自然重建）
+    fn update_cached(&self, update: impl FnOnce(&Self, &mut Prepared)) {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(prepared) = cache.as_mut() {
+            let mut next = (**prepared).clone();
+            update(self, &mut next);
+            *prepared = Arc::new(next);
+        }
+    }
+
+    /// Provider 共用的离线标记（未指定时为全局实例）
+    fn offline(&self) -> Arc<OfflineState> {
+        self.offline.clone().unwrap_or_else(OfflineState::global)
+    }
+
+    fn prepare(&self, generation: u64) -> Result<Prepared> {
+        let config = self.config.snapshot();
+        let provider_key = &config.default.provider;
+        let provider_config = config
+            .providers
+            .get(provider_key)
+            .ok_or_else(|| eyre!("Provider '{}' 未配置", provider_key))?;
+
+        let fallbacks: Vec<Box<dyn Provider>> = config
+            .reliability
+            .fallback_providers
+            .iter()
+            .filter_map(|name| config.providers.get(name))
+            .map(|pc| self.build_provider(pc))
+            .collect();
+        // metrics 标签：主 Provider + 已配置的 fallback（顺序与上面一致）
+        let names = std::iter::once(provider_key.clone())
+            .chain(
+                config
+                    .reliability
+                    .fallback_providers
+                    .iter()
+                    .filter(|name| config.providers.contains_key(*name))
+                    .cloned(),
+            )
+            .collect();
+        let provider = ReliableProvider::with_fallbacks(
+            self.build_provider(provider_config),
+            fallbacks,
+            RetryConfig::from_reliability(&config.reliability),
+        )
+        .with_names(names)
+        .with_offline_state(self.offline());
+
+        let policy = SecurityPolicy {
+            autonomy: crate::security::sandbox::clamp_autonomy(
+                config.security.autonomy.clone(),
+                config.security.require_sandbox,
+            ),
+            allowed_commands: config.security.allowed_commands.clone(),
+            workspace_dir: self.workspace_dir.clone(),
+            blocked_paths: self.blocked_paths.clone(),
+            http_allowed_hosts: config.security.http_allowed_hosts.clone(),
+            injection_check: config.security.injection_check,
+        };
+
+        Ok(Prepared {
+            generation,
+            routing: SharedAux::from_config(&config, config.agent.routing.as_ref(), self.offline()),
+            summary: SharedAux::from_config(&config, config.agent.summary.as_ref(), self.offline()),
+            provider: Arc::new(provider),
+            skills: self.scan_skills(&self.workspace_dir),
+            identity_context: self.read_identity(&self.workspace_dir),
+            policy,
+            summary_scrubber: Redactor::for_memory(&config),
+            config,
+        })
+    }
+
+    fn build_provider(&self, config: &ProviderConfig) -> Box<dyn Provider> {
+        self.provider_builds.fetch_add(1, Ordering::Relaxed);
+        (self.provider_builder)(config)
+    }
+
+    fn scan_skills(&self, workspace_dir: &Path) -> Vec<SkillMeta> {
+        if !self.load_skills {
+            return Vec::new();
+        }
+        self.skill_scans.fetch_add(1, Ordering::Relaxed);
+        let builtin = crate::skills::builtin_skills(Config::get_language());
+        crate::skills::load_skills(workspace_dir, &self.paths.skills_dir(), builtin)
+    }
+
+    fn read_identity(&self, workspace_dir: &Path) -> Option<String> {
+        if !self.load_identity {
+            return None;
+        }
+        self.identity_loads.fetch_add(1, Ordering::Relaxed);
+        super::identity::load_identity_context(workspace_dir, self.paths.home())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::NoopMemory;
+    use crate::providers::{ChatResponse, ConversationMessage, GenerationParams, ToolSpec};
+
+    struct FakeProvider;
+
+    #[async_trait::async_trait]
+    impl Provider for FakeProvider {
+        async fn chat_with_tools(
+            &self,
+            _messages: &[ConversationMessage],
+            _tools: &[ToolSpec],
+            _model: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            Ok(ChatResponse {
+                text: Some("ok".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            })
+        }
+    }
+
+    fn provider_config() -> ProviderConfig {
+        ProviderConfig {
+            base_url: "fake://".to_string(),
+            api_key: String::new(),
+            model: "fake-model".to_string(),
+            auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        }
+    }
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.default.provider = "fake".to_string();
+        config.default.model = "fake-model".to_string();
+        config
+            .providers
+            .insert("fake".to_string(), provider_config());
+        config
+    }
+
+    /// 带计数 fake Provider 的工厂：Skills / 身份文件都在临时目录
+    fn factory(home: &std::path::Path) -> (AgentFactory, Arc<AtomicUsize>) {
+        let builds = Arc::new(AtomicUsize::new(0));
+        let counter = builds.clone();
+        let factory = AgentFactory::new(
+            Arc::new(LiveConfig::new(config())),
+            Arc::new(NoopMemory),
+            RrclawPaths::in_home(home),
+        )
+        .with_workspace_dir(home.to_path_buf())
+        // tempdir 位于 /tmp（默认受保护），create_agent_in 会拒绝
+        .with_blocked_paths(vec![])
+        .with_skills()
+        .with_identity()
+        .with_provider_builder(Arc::new(move |_: &ProviderConfig| -> Box<dyn Provider> {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Box::new(FakeProvider)
+        }))
+        // 独立实例：其他测试的全局通知不影响计数
+        .with_change_signal(Arc::default());
+        (factory, builds)
+    }
+
+    #[tokio::test]
+    async fn repeated_agents_reuse_prepared_pieces() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("USER.md"), "我叫小明").unwrap();
+        let (factory, builds) = factory(tmp.path());
+
+        for _ in 0..10 {
+            let mut agent = factory.create_agent().unwrap();
+            assert_eq!(agent.process_message("hi").await.unwrap(), "ok");
+        }
+
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            factory.stats(),
+            FactoryStats {
+                provider_builds: 1,
+                skill_scans: 1,
+                identity_loads: 1,
+                agents_created: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn config_reload_rebuilds_everything() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (factory, builds) = factory(tmp.path());
+        factory.create_agent().unwrap();
+
+        let mut next = config();
+        next.default.temperature = 0.1;
+        factory.live_config().replace(next);
+        let agent = factory.create_agent().unwrap();
+
+        assert!((agent.temperature() - 0.1).abs() < f64::EPSILON);
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+        let stats = factory.stats();
+        assert_eq!((stats.skill_scans, stats.identity_loads), (2, 2));
+    }
+
+    #[test]
+    fn invalidation_hooks_refresh_only_their_piece() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (factory, builds) = factory(tmp.path());
+        let before = factory.create_agent().unwrap();
+        assert!(before.identity_context().is_none());
+
+        std::fs::write(tmp.path().join("USER.md"), "偏好简洁回答").unwrap();
+        factory.invalidate_identity();
+        let after = factory.create_agent().unwrap();
+        assert!(after
+            .identity_context()
+            .is_some_and(|ctx| ctx.contains("偏好简洁回答")));
+
+        factory.invalidate_skills();
+        factory.create_agent().unwrap();
+
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        let stats = factory.stats();
+        assert_eq!((stats.skill_scans, stats.identity_loads), (2, 2));
+
+        factory.invalidate();
+        factory.create_agent().unwrap();
+        assert_eq!(builds.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn change_signal_triggers_the_matching_hook() {
+        let tmp = tempfile::tempdir().unwrap();
+        let changes = Arc::new(ChangeSignal::default());
+        let (factory, builds) = factory(tmp.path());
+        let factory = factory.with_change_signal(changes.clone());
+        factory.create_agent().unwrap();
+
+        std::fs::write(tmp.path().join("USER.md"), "偏好简洁回答").unwrap();
+        changes.identity_changed();
+        let agent = factory.create_agent().unwrap();
+        assert!(agent
+            .identity_context()
+            .is_some_and(|ctx| ctx.contains("偏好简洁回答")));
+        assert_eq!(factory.stats().skill_scans, 1);
+
+        changes.skills_changed();
+        factory.create_agent().unwrap();
+        // 通知只处理一次
+        factory.create_agent().unwrap();
+
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        let stats = factory.stats();
+        assert_eq!((stats.skill_scans, stats.identity_loads), (2, 2));
+    }
+
+    fn write_project_skill(workspace: &std::path::Path, name: &str) {
+        let dir = workspace.join(".rrclaw").join("skills").join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("SKILL.md"),
+            format!("---\nname: {}\ndescription: 项目技能\n---\n\n步骤", name),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn each_workspace_gets_its_own_skills_identity_and_policy() {
+        let home = tempfile::tempdir().unwrap();
+        let project_a = tempfile::tempdir().unwrap();
+        let project_b = tempfile::tempdir().unwrap();
+        write_project_skill(project_a.path(), "deploy-a");
+        write_project_skill(project_b.path(), "lint-b");
+        std::fs::create_dir_all(project_b.path().join(".rrclaw")).unwrap();
+        std::fs::write(project_b.path().join(".rrclaw/AGENT.md"), "本项目用 pnpm").unwrap();
+        let (factory, builds) = factory(home.path());
+
+        let skill_names = |agent: &Agent| -> Vec<String> {
+            agent
+                .skills()
+                .iter()
+                .filter(|s| s.source == crate::skills::SkillSource::Project)
+                .map(|s| s.name.clone())
+                .collect()
+        };
+        let a = factory.create_agent_in(project_a.path()).unwrap();
+        let b = factory.create_agent_in(project_b.path()).unwrap();
+        assert_eq!(skill_names(&a), vec!["deploy-a"]);
+        assert_eq!(skill_names(&b), vec!["lint-b"]);
+        assert_eq!(
+            a.policy().workspace_dir,
+            project_a.path().canonicalize().unwrap()
+        );
+        assert_eq!(
+            b.policy().workspace_dir,
+            project_b.path().canonicalize().unwrap()
+        );
+        assert!(a.identity_context().is_none());
+        assert!(b
+            .identity_context()
+            .is_some_and(|ctx| ctx.contains("本项目用 pnpm")));
+
+        // 默认 Agent 不受影响；同一工作目录复用缓存，Provider 始终只构建一次
+        assert!(skill_names(&factory.create_agent().unwrap()).is_empty());
+        factory.create_agent_in(project_a.path()).unwrap();
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        assert_eq!(factory.stats().skill_scans, 3);
+
+        assert!(factory
+            .create_agent_in(&home.path().join("missing"))
+            .is_err());
+    }
+
+    #[test]
+    fn missing_default_provider_is_an_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut bad = config();
+        bad.providers.clear();
+        let factory = AgentFactory::new(
+            Arc::new(LiveConfig::new(bad)),
+            Arc::new(NoopMemory),
+            RrclawPaths::in_home(tmp.path()),
+        );
+        assert!(factory.create_agent().is_err());
+        assert_eq!(factory.stats().agents_created, 0);
+    }
+}
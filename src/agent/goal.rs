@@ -0,0 +1,215 @@
+//! 会话目标（`/goal`）
+//!
+//! 长任务中早期上下文会被历史压缩吃掉，目标因此不放在 history 里，而是挂在 Agent 上、
+//! 每轮注入 system prompt 的 `[当前目标]` 段，不受压缩影响。模型通过内置的 `goal_update`
+//! 工具记录完成的子目标或修改计划（由 Agent 直接处理，不经审批）；CLI 按 session 持久化到
+//! memory.db 的 `session_goals` 表。为控制 prompt 大小，只保留最近 `MAX_COMPLETED` 个子目标。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::i18n::Language;
+use crate::providers::ToolSpec;
+
+/// 内置工具名
+pub const GOAL_TOOL_NAME: &str = "goal_update";
+/// system prompt 中保留的最近完成子目标数（更早的只计数）
+pub const MAX_COMPLETED: usize = 8;
+/// 目标 / 子目标 / 计划单项的最大字符数
+const MAX_ITEM_CHARS: usize = 300;
+
+/// 当前会话的目标
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionGoal {
+    pub goal: String,
+    /// 模型给出的后续计划（`goal_update` 的 `plan` 参数，整体替换）
+    #[serde(default)]
+    pub plan: Option<String>,
+    /// 最近完成的子目标（最多 `MAX_COMPLETED` 个）
+    #[serde(default)]
+    pub completed: Vec<String>,
+    /// 因超出上限被移出列表的早期子目标数
+    #[serde(default)]
+    pub dropped: usize,
+}
+
+impl SessionGoal {
+    pub fn new(goal: &str) -> Self {
+        Self {
+            goal: clip(goal),
+            plan: None,
+            completed: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// 记录一个完成的子目标，超出上限时丢弃最早的
+    pub fn complete(&mut self, step: &str) {
+        self.completed.push(clip(step));
+        if self.completed.len() > MAX_COMPLETED {
+            let excess = self.completed.len() - MAX_COMPLETED;
+            self.completed.drain(..excess);
+            self.dropped += excess;
+        }
+    }
+
+    /// 替换后续计划（空字符串清除）
+    pub fn set_plan(&mut self, plan: &str) {
+        let plan = plan.trim();
+        self.plan = (!plan.is_empty()).then(|| clip(plan));
+    }
+
+    /// system prompt 中的 `[当前目标]` 段
+    pub fn prompt_section(&self, lang: Language) -> String {
+        let english = lang.is_english();
+        let mut out = format!(
+            "{}\n{}\n",
+            if english {
+                "[Current Goal]"
+            } else {
+                "[当前目标]"
+            },
+            self.goal
+        );
+        out.push_str(&self.progress(lang));
+        out.push_str(if english {
+            "Call goal_update when you finish a sub-step or need to revise the plan. Keep working toward this goal until the user marks it done."
+        } else {
+            "完成子步骤或需要调整计划时调用 goal_update。在用户标记完成前，始终围绕该目标推进。"
+        });
+        out
+    }
+
+    /// `/goal show` 与 prompt 共用的进度描述（已完成列表 + 计划）
+    pub fn progress(&self, lang: Language) -> String {
+        let english = lang.is_english();
+        let mut out = String::new();
+        if !self.completed.is_empty() {
+            out.push_str(if english {
+                "Completed:\n"
+            } else {
+                "已完成：\n"
+            });
+            if self.dropped > 0 {
+                out.push_str(&if english {
+                    format!("- ({} earlier step(s))\n", self.dropped)
+                } else {
+                    format!("- （更早的 {} 项）\n", self.dropped)
+                });
+            }
+            for step in &self.completed {
+                out.push_str(&format!("- {}\n", step));
+            }
+        }
+        if let Some(plan) = &self.plan {
+            out.push_str(&if english {
+                format!("Plan: {}\n", plan)
+            } else {
+                format!("计划：{}\n", plan)
+            });
+        }
+        out
+    }
+}
+
+/// `goal_update` 工具定义（仅在有活动目标时随请求发送）
+pub fn tool_spec() -> ToolSpec {
+    ToolSpec {
+        name: GOAL_TOOL_NAME.to_string(),
+        description: "Record progress on the current session goal: mark a sub-step as completed and/or replace the remaining plan.".to_string(),
+        parameters: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "completed": {
+                    "type": "string",
+                    "description": "A sub-step that was just finished (one short sentence)"
+                },
+                "plan": {
+                    "type": "string",
+                    "description": "The revised remaining plan (replaces the previous plan; empty string clears it)"
+                }
+            }
+        }),
+    }
+}
+
+/// 执行 `goal_update`：成功时返回给用户的状态行，失败时返回错误说明
+pub fn apply_update(goal: Option<&mut SessionGoal>, args: &Value) -> Result<String, String> {
+    let Some(goal) = goal else {
+        return Err("当前没有活动目标（用户可用 /goal set 设置）".to_string());
+    };
+    let completed = args
+        .get("completed")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let plan = args.get("plan").and_then(Value::as_str);
+    if completed.is_none() && plan.is_none() {
+        return Err("需要 completed 或 plan 参数".to_string());
+    }
+
+    let mut status = Vec::new();
+    if let Some(step) = completed {
+        goal.complete(step);
+        status.push(format!("✓ {}", clip(step)));
+    }
+    if let Some(plan) = plan {
+        goal.set_plan(plan);
+        status.push(match &goal.plan {
+            Some(plan) => format!("→ {}", plan),
+            None => "→ (plan cleared)".to_string(),
+        });
+    }
+    Ok(status.join("  "))
+}
+
+/// 按字符截断
+fn clip(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= MAX_ITEM_CHARS {
+        return text.to_string();
+    }
+    let mut clipped: String = text.chars().take(MAX_ITEM_CHARS).collect();
+    clipped.push('…');
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completed_list_is_capped_and_counts_dropped() {
+        let mut goal = SessionGoal::new("migrate to color_eyre");
+        for i in 0..MAX_COMPLETED + 3 {
+            goal.complete(&format!("step {}", i));
+        }
+        assert_eq!(goal.completed.len(), MAX_COMPLETED);
+        assert_eq!(goal.dropped, 3);
+        assert_eq!(goal.completed[0], "step 3");
+
+        let section = goal.prompt_section(Language::English);
+        assert!(section.starts_with("[Current Goal]\nmigrate to color_eyre\n"));
+        assert!(section.contains("(3 earlier step(s))"));
+        assert!(!section.contains("step 2\n"));
+    }
+
+    #[test]
+    fn apply_update_records_steps_and_plan() {
+        let mut goal = SessionGoal::new("ship v2");
+        let status = apply_update(
+            Some(&mut goal),
+            &serde_json::json!({"completed": "wrote tests", "plan": "update docs"}),
+        )
+        .unwrap();
+        assert_eq!(status, "✓ wrote tests  → update docs");
+        assert_eq!(goal.completed, vec!["wrote tests"]);
+        assert_eq!(goal.plan.as_deref(), Some("update docs"));
+
+        apply_update(Some(&mut goal), &serde_json::json!({"plan": ""})).unwrap();
+        assert_eq!(goal.plan, None);
+
+        assert!(apply_update(Some(&mut goal), &serde_json::json!({})).is_err());
+        assert!(apply_update(None, &serde_json::json!({"completed": "x"})).is_err());
+    }
+}
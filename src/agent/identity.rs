@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::debug;
 
 /// 单个身份文件的配置
@@ -9,7 +9,7 @@ struct IdentityFile {
     relative_path: &'static str,
 }
 
-/// 全局身份文件（相对于 data_dir，即 ~/.rrclaw/）
+/// 全局身份文件（相对于配置根目录，即 `RrclawPaths::home()`）
 const GLOBAL_FILES: &[IdentityFile] = &[IdentityFile {
     section_name: "用户偏好",
     relative_path: "USER.md",
@@ -32,18 +32,48 @@ const MAX_FILE_BYTES: usize = 8 * 1024;
 ///
 /// # 参数
 /// - `workspace_dir`: 当前工作目录（项目目录）
-/// - `data_dir`: RRClaw 数据目录（通常是 `~/.rrclaw/`）
+/// - `data_dir`: RRClaw 配置根目录（`~/.rrclaw/` 或 `$XDG_CONFIG_HOME/rrclaw/`）
 ///
 /// # 返回
 /// - `Some(String)`: 有内容时返回合并后的 Markdown 文本
 /// - `None`: 所有文件均不存在或为空
 pub fn load_identity_context(workspace_dir: &Path, data_dir: &Path) -> Option<String> {
-    let mut sections: Vec<(String, String)> = Vec::new(); // (section_name, content)
+    let sections = collect_sections(workspace_dir, data_dir);
+    if sections.is_empty() {
+        return None;
+    }
+
+    // 合并所有节，使用清晰的分隔符
+    let mut result = String::new();
+    for (name, _, content) in &sections {
+        result.push_str(&format!("### {}\n{}\n\n", name, content.trim()));
+    }
+
+    debug!(
+        "已加载 {} 个身份文件，合并后 {} 字符",
+        sections.len(),
+        result.len()
+    );
+
+    Some(result.trim_end().to_string())
+}
+
+/// 实际生效（存在且非空）的身份文件路径，按注入顺序排列（`/whoami` 展示用）
+pub fn loaded_identity_files(workspace_dir: &Path, data_dir: &Path) -> Vec<PathBuf> {
+    collect_sections(workspace_dir, data_dir)
+        .into_iter()
+        .map(|(_, path, _)| path)
+        .collect()
+}
+
+/// 按优先级读取身份文件：`(节名, 路径, 内容)`，跳过不存在或纯空白的文件
+fn collect_sections(workspace_dir: &Path, data_dir: &Path) -> Vec<(String, PathBuf, String)> {
+    let mut sections = Vec::new();
 
     // 辅助闭包：只在内容非纯空白时加入
-    let mut push_if_nonempty = |name: &str, content: String| {
+    let mut push_if_nonempty = |name: &str, path: PathBuf, content: String| {
         if !content.trim().is_empty() {
-            sections.push((name.to_string(), content));
+            sections.push((name.to_string(), path, content));
         }
     };
 
@@ -51,7 +81,7 @@ pub fn load_identity_context(workspace_dir: &Path, data_dir: &Path) -> Option<St
     for file in GLOBAL_FILES {
         let path = data_dir.join(file.relative_path);
         if let Some(content) = read_file_safe(&path) {
-            push_if_nonempty(file.section_name, content);
+            push_if_nonempty(file.section_name, path, content);
         }
     }
 
@@ -60,36 +90,20 @@ pub fn load_identity_context(workspace_dir: &Path, data_dir: &Path) -> Option<St
     let global_soul_path = data_dir.join(SOUL_GLOBAL);
 
     if let Some(content) = read_file_safe(&project_soul_path) {
-        push_if_nonempty("Agent 人格（项目级）", content);
+        push_if_nonempty("Agent 人格（项目级）", project_soul_path, content);
     } else if let Some(content) = read_file_safe(&global_soul_path) {
-        push_if_nonempty("Agent 人格", content);
+        push_if_nonempty("Agent 人格", global_soul_path, content);
     }
 
     // 3. 项目行为约定文件
     for file in PROJECT_FILES {
         let path = workspace_dir.join(file.relative_path);
         if let Some(content) = read_file_safe(&path) {
-            push_if_nonempty(file.section_name, content);
+            push_if_nonempty(file.section_name, path, content);
         }
     }
 
-    if sections.is_empty() {
-        return None;
-    }
-
-    // 合并所有节，使用清晰的分隔符
-    let mut result = String::new();
-    for (name, content) in &sections {
-        result.push_str(&format!("### {}\n{}\n\n", name, content.trim()));
-    }
-
-    debug!(
-        "已加载 {} 个身份文件，合并后 {} 字符",
-        sections.len(),
-        result.len()
-    );
-
-    Some(result.trim_end().to_string())
+    sections
 }
 
 /// 安全读取文件内容
@@ -240,6 +254,25 @@ mod tests {
         assert!(!content.contains("全局人格"));
     }
 
+    #[test]
+    fn loaded_files_follow_soul_priority_and_skip_empty() {
+        let workspace = tempdir().unwrap();
+        let data_dir = tempdir().unwrap();
+        write_file(data_dir.path(), "USER.md", "偏好");
+        write_file(data_dir.path(), "SOUL.md", "全局人格");
+        write_file(&workspace.path().join(".rrclaw"), "SOUL.md", "项目人格");
+        write_file(&workspace.path().join(".rrclaw"), "AGENT.md", "  \n");
+
+        let files = loaded_identity_files(workspace.path(), data_dir.path());
+        assert_eq!(
+            files,
+            vec![
+                data_dir.path().join("USER.md"),
+                workspace.path().join(".rrclaw/SOUL.md"),
+            ]
+        );
+    }
+
     #[test]
     fn empty_file_returns_none() {
         let workspace = tempdir().unwrap();
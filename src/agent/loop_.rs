@@ -1,15 +1,34 @@
-use color_eyre::eyre::Result;
+use std::sync::Arc;
+
+use color_eyre::eyre::{eyre, Context, Result};
 use tracing::{debug, info, warn};
 
 use tokio::sync::mpsc;
-
+use tokio_util::sync::CancellationToken;
+
+use super::approval::{ApprovalPolicy, ConfirmFnApproval, NOTICE_ARG, PREVIEW_ARG};
+use super::aux_model::AuxModel;
+use super::branch::{self, AgentBranch};
+use super::changes::{ChangeSummary, ChangeTracker};
+use super::conversation_memory::ConversationFilter;
+use super::dedup::{CallKey, CallLedger, LedgerCheck, CACHED_PREFIX, REPEATED_FAILURE_MESSAGE};
+use super::goal::{self, SessionGoal};
+use super::pin::PinnedProvider;
+use super::prompts::{self, PromptItem, PromptMode, SystemPromptContext, ToolsSection};
+use super::suggestions::{self, SuggestionFilter};
+use super::tokens;
+use super::turns;
 use crate::memory::{Memory, MemoryCategory};
 use crate::providers::{
-    ChatMessage, ConversationMessage, Provider, StreamEvent, ToolSpec, ToolStatusKind,
+    ChatMessage, ChatResponse, ConversationMessage, EventSink, GenerationParams, Provenance,
+    Provider, StreamEvent, ToolCall, ToolLimits, ToolSpec, ToolStatusKind,
 };
+use crate::security::redact::Redactor;
 use crate::security::{AutonomyLevel, SecurityPolicy};
+use crate::skills::usage::{NoopSkillUsage, SkillTrigger, SkillUsageRecorder};
 use crate::skills::SkillMeta;
-use crate::tools::Tool;
+use crate::tools::git::{GitTool, WorktreeState};
+use crate::tools::{Tool, ToolOutputKind, ToolRisk};
 
 const MAX_TOOL_ITERATIONS: usize = 10;
 const MAX_HISTORY_SIZE: usize = 50;
@@ -18,9 +37,16 @@ const MAX_HISTORY_SIZE: usize = 50;
 const COMPACT_THRESHOLD: usize = 40;
 /// 每次压缩的窗口大小（前 N 条被摘要）
 const COMPACT_WINDOW: usize = 30;
+/// history 估算 token 数超过上下文窗口的此比例时也触发压缩（长消息少条数的场景）
+const COMPACT_TOKEN_RATIO: f64 = 0.6;
 /// 压缩生成的摘要最大字符数
 const COMPACT_SUMMARY_MAX_CHARS: usize = 1500;
 
+/// 取消令牌触发后本轮返回的错误信息（Routine 执行日志原样记录）
+pub const CANCELLED_MESSAGE: &str = "cancelled by user";
+/// 取消后未执行的工具调用写入 history 的结果（保持 tool_call / tool_result 配对）
+const CANCELLED_TOOL_RESULT: &str = "[已取消] 执行已被用户取消，未运行此工具";
+
 /// Phase 1 路由结果
 #[derive(Debug, Clone, PartialEq)]
 pub enum RouteResult {
@@ -32,6 +58,61 @@ pub enum RouteResult {
     NeedClarification(String),
 }
 
+/// 脏工作区提醒最多列出的文件数
+const DIRTY_NOTICE_MAX_PATHS: usize = 3;
+
+/// 工作区有未提交改动时给用户的提醒（`security.warn_dirty_worktree`）
+fn dirty_worktree_message(paths: &[String]) -> String {
+    let mut shown = paths
+        .iter()
+        .take(DIRTY_NOTICE_MAX_PATHS)
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if paths.len() > DIRTY_NOTICE_MAX_PATHS {
+        shown.push_str(", …");
+    }
+    if crate::config::Config::get_language().is_english() {
+        format!(
+            "The workspace has {} uncommitted change(s) ({}); the agent's edits will be mixed in. \
+             Commit first, or run /safety stash (bring them back later with /safety restore).",
+            paths.len(),
+            shown
+        )
+    } else {
+        format!(
+            "工作区有 {} 处未提交的改动（{}），Agent 的修改会与之混在一起。\
+             建议先提交，或执行 /safety stash 暂存（之后用 /safety restore 恢复）。",
+            paths.len(),
+            shown
+        )
+    }
+}
+
+fn is_context_overflow(err: &color_eyre::eyre::Report) -> bool {
+    crate::providers::is_context_overflow_error(&format!("{:#}", err))
+}
+
+/// 仍超出上下文窗口时返回给用户的说明（`compacted`：本轮是否已压缩过 history）
+fn context_overflow_message(compacted: bool) -> &'static str {
+    let english = crate::config::Config::get_language().is_english();
+    match (compacted, english) {
+        (true, true) => {
+            "The conversation is too long for the model's context window. Earlier messages have \
+             been summarized; please send your message again, or use /new to start a fresh conversation."
+        }
+        (true, false) => {
+            "对话过长，超出了模型的上下文窗口。已开始把早期对话压缩为摘要，请重新发送；\
+             仍然失败时可用 /new 开始新对话。"
+        }
+        (false, true) => {
+            "The message is too long for the model's context window. Please shorten it \
+             or split it into several messages."
+        }
+        (false, false) => "消息过长，超出了模型的上下文窗口。请缩短内容或分多条发送。",
+    }
+}
+
 /// 从可能包含 markdown 代码块的文本中提取 JSON 字符串
 fn extract_json(text: &str) -> &str {
     let text = text.trim();
@@ -82,106 +163,97 @@ fn parse_route_result(text: &str) -> RouteResult {
     RouteResult::Direct
 }
 
-/// 构造 Phase 1 的 system prompt，按语言分发
-fn build_routing_prompt(skills: &[SkillMeta], lang: crate::i18n::Language) -> String {
-    match lang {
-        crate::i18n::Language::English => build_routing_prompt_en(skills),
-        crate::i18n::Language::Chinese => build_routing_prompt_zh(skills),
-    }
-}
-
-fn build_routing_prompt_en(skills: &[SkillMeta]) -> String {
-    let mut prompt = String::new();
-
-    // [1] Identity
-    prompt.push_str("You are RRClaw's routing assistant. Your only job is to analyze the user's message and decide which behavior guides (skills) to load.\n\n");
-
-    // [2] Constraints (hard-coded, non-skippable)
-    prompt.push_str("[Constraints]\n");
-    prompt.push_str("- Do not call any tools\n");
-    prompt.push_str("- Output JSON only, no other text\n\n");
+/// 工具执行确认回调
+/// 参数: (tool_name, tool_arguments) → 返回 true 表示允许执行
+pub type ConfirmFn = Box<dyn Fn(&str, &serde_json::Value) -> bool + Send + Sync>;
 
-    // [3] Available skills (L1 metadata)
-    if skills.is_empty() {
-        prompt.push_str("[Available Skills]\nNo skills available.\n\n");
-    } else {
-        prompt.push_str("[Available Skills]\n");
-        for skill in skills {
-            prompt.push_str(&format!("- {}: {}\n", skill.name, skill.description));
-        }
-        prompt.push('\n');
-    }
-
-    // [4] Output format
-    prompt.push_str("[Output Format]\n");
-    prompt.push_str("Output valid JSON, one of three cases:\n\n");
-    prompt.push_str("1. Skills needed (clear intent, matching skill found):\n");
-    prompt.push_str("   {\"skills\": [\"skill-name\"], \"direct\": false}\n\n");
-    prompt.push_str("2. No skill needed, intent is clear:\n");
-    prompt.push_str("   {\"skills\": [], \"direct\": true}\n\n");
-    prompt.push_str("3. Intent unclear, need clarification (only when truly ambiguous):\n");
-    prompt.push_str(
-        "   {\"skills\": [], \"direct\": false, \"question\": \"your clarification question\"}\n\n",
-    );
-
-    // [5] Principles
-    prompt.push_str("[Principles]\n");
-    prompt.push_str("- When intent is clear, choose direct: true even if no skill matches\n");
-    prompt.push_str(
-        "- Skills are enhancements, not gates — don't ask the user when no skill matches\n",
-    );
-    prompt.push_str("- Only return a question when the intent is genuinely ambiguous and proceeding would go wrong\n");
-    prompt.push_str("- Use the same language as the user in the question field\n");
-
-    prompt
+/// 一轮对话的事件出口：`process_message` 与 `process_message_stream` 共用 `run_turn`，只在此处分流
+enum TurnSink {
+    /// 非流式：事件直接丢弃，Provider 走 `chat_with_tools`
+    Silent,
+    /// 流式：事件经 `EventSink` 发送，Provider 走 `chat_stream`（文本增量经 `SuggestionFilter` 转发到 `tx`）
+    Stream {
+        tx: mpsc::Sender<StreamEvent>,
+        events: EventSink,
+    },
 }
 
-fn build_routing_prompt_zh(skills: &[SkillMeta]) -> String {
-    let mut prompt = String::new();
+impl TurnSink {
+    fn is_streaming(&self) -> bool {
+        matches!(self, Self::Stream { .. })
+    }
 
-    // [1] 身份
-    prompt.push_str("你是 RRClaw 的路由助手。你的唯一任务是分析用户消息，决定需要加载哪些行为指南（skill）。\n\n");
+    async fn send(&mut self, event: StreamEvent) {
+        if let Self::Stream { events, .. } = self {
+            events.send(event).await;
+        }
+    }
 
-    // [2] 安全约束（硬编码，不可跳过）
-    prompt.push_str("【约束】\n");
-    prompt.push_str("- 禁止调用任何工具\n");
-    prompt.push_str("- 只输出 JSON，不做其他任何操作\n\n");
+    async fn flush(&mut self) {
+        if let Self::Stream { events, .. } = self {
+            events.flush().await;
+        }
+    }
 
-    // [3] 可用 Skill 目录（L1 元数据）
-    if skills.is_empty() {
-        prompt.push_str("【可用 Skill】\n暂无可用 skill。\n\n");
-    } else {
-        prompt.push_str("【可用 Skill】\n");
-        for skill in skills {
-            prompt.push_str(&format!("- {}: {}\n", skill.name, skill.description));
-        }
-        prompt.push('\n');
-    }
-
-    // [4] 输出格式说明
-    prompt.push_str("【输出格式】\n");
-    prompt.push_str("必须输出合法 JSON，三种情况之一：\n\n");
-    prompt.push_str("1. 需要加载 skill（意图明确且有匹配 skill）：\n");
-    prompt.push_str("   {\"skills\": [\"skill-name\"], \"direct\": false}\n\n");
-    prompt.push_str("2. 无需 skill，意图清晰可直接执行：\n");
-    prompt.push_str("   {\"skills\": [], \"direct\": true}\n\n");
-    prompt.push_str("3. 意图模糊，需要向用户澄清（仅在真正无法判断时使用）：\n");
-    prompt.push_str("   {\"skills\": [], \"direct\": false, \"question\": \"你的澄清问题\"}\n\n");
-
-    // [5] 判断原则
-    prompt.push_str("【判断原则】\n");
-    prompt.push_str("- 用户意图清晰时，即使没有匹配的 skill，也应选择 direct: true\n");
-    prompt.push_str("- skill 是增强，不是门槛——没有 skill 可以匹配时不要问用户\n");
-    prompt.push_str("- 只有用户表达含糊、继续执行会走错方向时，才返回 question\n");
-    prompt.push_str("- question 字段使用中文，简洁明确\n");
-
-    prompt
+    /// 调用 Provider（流式前先补发暂存事件，保证与 Provider 直接发出的增量顺序一致）
+    async fn chat(
+        &mut self,
+        provider: &dyn Provider,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        params: GenerationParams,
+    ) -> Result<ChatResponse> {
+        match self {
+            Self::Silent => {
+                provider
+                    .chat_with_tools(messages, tools, model, params)
+                    .await
+            }
+            Self::Stream { tx, events } => {
+                events.flush().await;
+                // 文本增量经 SuggestionFilter 转发，suggestions 块不显示给用户
+                let (inner_tx, mut inner_rx) = crate::providers::stream_channel();
+                let mut out = EventSink::new(tx.clone());
+                let forward = tokio::spawn(async move {
+                    let mut filter = SuggestionFilter::default();
+                    while let Some(event) = inner_rx.recv().await {
+                        let event = match event {
+                            StreamEvent::Text(delta) => {
+                                let visible = filter.push(&delta);
+                                if visible.is_empty() {
+                                    continue;
+                                }
+                                StreamEvent::Text(visible)
+                            }
+                            StreamEvent::Done(response) => {
+                                let tail = filter.finish();
+                                if !tail.is_empty() {
+                                    out.send(StreamEvent::Text(tail)).await;
+                                }
+                                StreamEvent::Done(response)
+                            }
+                            other => other,
+                        };
+                        out.send(event).await;
+                    }
+                    let tail = filter.finish();
+                    if !tail.is_empty() {
+                        out.send(StreamEvent::Text(tail)).await;
+                    }
+                    out.flush().await;
+                });
+                let result = provider
+                    .chat_stream(messages, tools, model, params, inner_tx)
+                    .await;
+                // 等转发完成，保证之后 EventSink 发出的事件排在本次文本之后
+                let _ = forward.await;
+                result
+            }
+        }
+    }
 }
 
-/// 工具执行确认回调
-/// 参数: (tool_name, tool_arguments) → 返回 true 表示允许执行
-pub type ConfirmFn = Box<dyn Fn(&str, &serde_json::Value) -> bool + Send + Sync>;
-
 /// AI Agent 核心
 pub struct Agent {
     provider: Box<dyn Provider>,
@@ -191,22 +263,109 @@ pub struct Agent {
     provider_name: String,
     base_url: String,
     model: String,
-    temperature: f64,
+    /// 配置中的生成参数（`/param reset` 恢复到此）
+    default_params: GenerationParams,
+    /// 当前生效的生成参数（`/temp`、`/param set` 会话内覆盖）
+    params: GenerationParams,
     history: Vec<ConversationMessage>,
-    confirm_fn: Option<ConfirmFn>,
+    /// 当前 Turn 编号（每次 process_message 递增，写入 history 的消息都带此标记）
+    current_turn: u64,
+    /// 工具执行审批策略（未设置时直接执行）
+    approval: Option<Box<dyn ApprovalPolicy>>,
     /// L1 元数据，用于 system prompt 技能列表（不含 SkillTool 本身）
     skills_meta: Vec<SkillMeta>,
     /// Phase 1 路由后加载的 skill 内容，每次 process_message 重置
     routed_skill_content: Option<String>,
+    /// Phase 1 路由命中的 skill 名称（反馈记录用），直接执行时清空
+    routed_skill_names: Vec<String>,
     /// Phase 1.5 关键词路由后的工具名列表，每次 process_message 重置
     /// 空列表表示降级：暴露所有工具
     routed_tool_names: Vec<String>,
     /// 启动时加载的身份文件内容
     identity_context: Option<String>,
+    /// `[agent] system_prompt_prefix`：插入在 system prompt 最前面
+    system_prompt_prefix: Option<String>,
+    /// `[agent] system_prompt_override`：替换内置身份描述与决策原则
+    system_prompt_override: Option<String>,
     /// 当前执行的 Routine 名称（None 表示普通对话模式）
     routine_name: Option<String>,
     /// P7-3: 本轮已处理参数缺失并注入完整 schema 的工具名集合（每轮重置）
     expanded_tools: std::collections::HashSet<String>,
+    /// 本轮工具调用账本（重复调用返回缓存 / 连续失败短路，每轮重置）
+    call_ledger: CallLedger,
+    /// 本轮工具产生的结构化输出（供非流式 Channel 渲染，每轮重置）
+    rich_outputs: Vec<RichToolOutput>,
+    /// 本轮工具结果的用户视图（供非流式 Channel 按 tool_verbosity 展示，每轮重置）
+    tool_feedback: Vec<ToolFeedback>,
+    /// 是否在每轮结束后生成工作区文件变更摘要（`[cli] show_changes`）
+    track_changes: bool,
+    /// 本轮变更追踪器（首个工具执行前创建，每轮重置）
+    change_tracker: Option<ChangeTracker>,
+    /// 上一轮的文件变更摘要（无变更时为 None）
+    last_changes: Option<ChangeSummary>,
+    /// 上一轮最终回复的来源（Provider / 模型 / 重试次数，未经 ReliableProvider 时为 None）
+    last_provenance: Option<Provenance>,
+    /// 本轮工具给出的后续建议（每轮重置）
+    tool_suggestions: Vec<String>,
+    /// 上一轮的后续建议（最终回复的 suggestions 块 + 工具建议，最多 3 条）
+    last_suggestions: Vec<String>,
+    /// 通道能显示快捷回复时，system prompt 要求模型附带后续建议
+    quick_replies: bool,
+    /// 写入类工具执行前提醒工作区有未提交改动（`security.warn_dirty_worktree`）
+    warn_dirty_worktree: bool,
+    /// 最近一次检查工作区 git 状态的 Turn（每轮最多检查一次）
+    dirty_checked_turn: u64,
+    /// 已提醒过，或检查时工作区干净（之后的改动来自 Agent 自己）：本会话不再检查
+    dirty_guard_done: bool,
+    /// 当前 Provider 对 tools 数组的限制
+    tool_limits: ToolLimits,
+    /// 当前模型的上下文窗口（tokens），决定按 token 触发压缩的阈值
+    context_window: usize,
+    /// 安全模式：不挂载任何工具，纯对话（`enter_safe_mode`）
+    safe_mode: bool,
+    /// 各工具累计调用次数（超限裁剪时 MCP 工具按此排序保留）
+    tool_usage: std::collections::HashMap<String, u32>,
+    /// 本轮因 Provider 限制未携带的工具数（system prompt 提示用，每轮重置）
+    omitted_tool_count: usize,
+    /// Phase 1 路由专用模型（`[agent.routing]`），None 时用主 Provider
+    routing_model: Option<AuxModel>,
+    /// 历史压缩摘要专用模型（`[agent.summary]`），None 时用主 Provider
+    summary_model: Option<AuxModel>,
+    /// Skill 使用统计（默认不记录）
+    skill_usage: Arc<dyn SkillUsageRecorder>,
+    /// 会话目标（`/goal`），每轮注入 system prompt，不受历史压缩影响
+    goal: Option<SessionGoal>,
+    /// 只读回合（`/ask` 单条 / `/readonly` 会话开关）：只暴露、只执行 `ToolRisk::Read` 工具
+    read_only: bool,
+    /// 对话摘要写入记忆前的脱敏器（`[memory] scrub`）；默认只处理常见密钥形态
+    summary_scrubber: Option<Redactor>,
+    /// 哪些回合的对话摘要写入记忆（`[memory] store_conversations`）
+    conversation_filter: ConversationFilter,
+    /// 协作式取消令牌（Routine 执行时设置）：Provider 调用期间触发即放弃等待，工具执行前检查
+    cancel: Option<CancellationToken>,
+    /// 当前所在分支名（`fork` 后设置，`drop_branch` / `merge_branch` 清除）
+    branch_name: Option<String>,
+}
+
+/// 工具结构化输出（随 `StreamEvent::ToolOutput` 发送，或通过 `take_rich_outputs` 取回）
+#[derive(Debug, Clone)]
+pub struct RichToolOutput {
+    pub tool: String,
+    pub kind: ToolOutputKind,
+    /// 工具原始文本输出（与 LLM 看到的内容一致）
+    pub content: String,
+}
+
+/// 工具结果的用户视图（随 `StreamEvent::ToolFeedback` 发送，或通过 `take_tool_feedback` 取回）
+///
+/// 只用于展示；写入 history 的工具结果不受影响。
+#[derive(Debug, Clone)]
+pub struct ToolFeedback {
+    pub tool: String,
+    /// 工具给出的简短摘要（如 "exit 0" + 末尾几行）
+    pub summary: Option<String>,
+    /// 完整输出（与 LLM 看到的内容一致）
+    pub content: String,
 }
 
 impl Agent {
@@ -231,38 +390,334 @@ impl Agent {
             provider_name,
             base_url,
             model,
-            temperature,
+            default_params: GenerationParams::with_temperature(temperature),
+            params: GenerationParams::with_temperature(temperature),
             history: Vec::new(),
-            confirm_fn: None,
+            current_turn: 0,
+            approval: None,
             skills_meta,
             routed_skill_content: None,
+            routed_skill_names: Vec::new(),
             routed_tool_names: Vec::new(),
             identity_context,
+            system_prompt_prefix: None,
+            system_prompt_override: None,
             routine_name: None,
             expanded_tools: std::collections::HashSet::new(),
+            call_ledger: CallLedger::default(),
+            rich_outputs: Vec::new(),
+            tool_feedback: Vec::new(),
+            track_changes: false,
+            change_tracker: None,
+            last_changes: None,
+            last_provenance: None,
+            tool_suggestions: Vec::new(),
+            last_suggestions: Vec::new(),
+            quick_replies: false,
+            warn_dirty_worktree: false,
+            dirty_checked_turn: 0,
+            dirty_guard_done: false,
+            tool_limits: ToolLimits::default(),
+            context_window: crate::providers::DEFAULT_CONTEXT_WINDOW,
+            safe_mode: false,
+            tool_usage: std::collections::HashMap::new(),
+            omitted_tool_count: 0,
+            routing_model: None,
+            summary_model: None,
+            skill_usage: Arc::new(NoopSkillUsage),
+            goal: None,
+            read_only: false,
+            summary_scrubber: Some(Redactor::default()),
+            conversation_filter: ConversationFilter::default(),
+            cancel: None,
+            branch_name: None,
         }
     }
 
     /// 手动注入技能上下文（/skill <name> 用）
     /// 将技能指令作为 user 消息推入 history，LLM 下一轮自然遵循
     pub fn inject_skill_context(&mut self, skill_name: &str, instructions: &str) {
+        self.record_skill_use(skill_name, SkillTrigger::Manual);
         let msg = ConversationMessage::Chat(ChatMessage {
             role: "user".to_string(),
             content: format!("[技能指令: {}]\n{}", skill_name, instructions),
             reasoning_content: None,
+            // 归入下一次用户输入所在的 Turn
+            turn: self.current_turn + 1,
         });
         self.history.push(msg);
     }
 
     /// 设置工具执行确认回调（用于 Supervised 模式）
     pub fn set_confirm_fn(&mut self, f: ConfirmFn) {
-        self.confirm_fn = Some(f);
+        self.approval = Some(Box::new(ConfirmFnApproval::new(f)));
+    }
+
+    /// 设置自定义审批策略（替换确认回调）
+    pub fn set_approval_policy(&mut self, policy: Box<dyn ApprovalPolicy>) {
+        self.approval = Some(policy);
+    }
+
+    /// 询问审批策略；被拒绝时返回写入 history 的工具结果
+    ///
+    /// 工具提供确认预览（如 file_write 的 diff）时，以 `PREVIEW_ARG` 附加在参数中交给审批策略。
+    /// 一次性提醒（如脏工作区）以 `NOTICE_ARG` 附加。
+    /// 未设置审批策略时只拦截 `Tool::requires_approval` 的调用。
+    async fn check_approval(&self, tc: &ToolCall, notice: Option<String>) -> Option<String> {
+        let tool = self.tools.iter().find(|t| t.name() == tc.name);
+        let Some(approval) = self.approval.as_ref() else {
+            // 没有审批策略（如 Telegram）：必须确认的调用无人可问，直接拒绝
+            let gated = tool.is_some_and(|t| t.requires_approval(&tc.arguments));
+            return gated.then(|| {
+                info!("无审批策略，拒绝需要确认的工具调用: {}", tc.name);
+                format!(
+                    "[失败] {} 的这次调用需要用户确认，但当前通道无法确认，已拒绝执行",
+                    tc.name
+                )
+            });
+        };
+        let preview = tool.and_then(|t| t.confirmation_preview(&tc.arguments, &self.policy));
+        let decision = if preview.is_some() || notice.is_some() {
+            let mut shown = tc.clone();
+            if let Some(args) = shown.arguments.as_object_mut() {
+                for (key, text) in [(PREVIEW_ARG, preview), (NOTICE_ARG, notice)] {
+                    if let Some(text) = text {
+                        args.insert(key.to_string(), serde_json::Value::String(text));
+                    }
+                }
+            }
+            approval.approve(&shown, &self.policy).await
+        } else {
+            approval.approve(tc, &self.policy).await
+        };
+        let message = decision.denial_message()?;
+        info!("审批拒绝执行工具: {}", tc.name);
+        Some(message)
+    }
+
+    /// 脏工作区提醒：本会话首次在有未提交改动的 git 工作区执行写入类工具前返回提醒文本
+    ///
+    /// 每轮最多检查一次；提醒过一次，或检查时工作区干净后，本会话不再检查。
+    async fn dirty_worktree_notice(&mut self, tool_name: &str) -> Option<String> {
+        if !self.warn_dirty_worktree
+            || self.dirty_guard_done
+            || self.dirty_checked_turn == self.current_turn
+        {
+            return None;
+        }
+        let writes = self
+            .tools
+            .iter()
+            .find(|t| t.name() == tool_name)
+            .is_some_and(|t| t.risk() == ToolRisk::Write);
+        if !writes {
+            return None;
+        }
+        self.dirty_checked_turn = self.current_turn;
+        match GitTool::worktree_state(&self.policy.workspace_dir).await {
+            WorktreeState::NotRepo => None,
+            WorktreeState::Clean => {
+                self.dirty_guard_done = true;
+                None
+            }
+            WorktreeState::Dirty(paths) => {
+                self.dirty_guard_done = true;
+                info!("工作区有 {} 处未提交改动，提醒用户", paths.len());
+                Some(dirty_worktree_message(&paths))
+            }
+        }
+    }
+
+    /// 取出上一轮工具产生的结构化输出（Telegram 等非流式 Channel 用）
+    pub fn take_rich_outputs(&mut self) -> Vec<RichToolOutput> {
+        std::mem::take(&mut self.rich_outputs)
+    }
+
+    /// 取出上一轮工具结果的用户视图（Telegram 等非流式 Channel 用）
+    pub fn take_tool_feedback(&mut self) -> Vec<ToolFeedback> {
+        std::mem::take(&mut self.tool_feedback)
+    }
+
+    /// 设置当前 Provider 的工具限制（创建 Agent / 切换 Provider 后调用）
+    pub fn set_tool_limits(&mut self, limits: ToolLimits) {
+        self.tool_limits = limits;
+    }
+
+    /// 设置当前模型的上下文窗口（创建 Agent / 切换 Provider 后调用，见 `providers::resolve_context_window`）
+    pub fn set_context_window(&mut self, tokens: usize) {
+        self.context_window = tokens;
+    }
+
+    /// 当前模型的上下文窗口（tokens）
+    pub fn context_window(&self) -> usize {
+        self.context_window
+    }
+
+    /// 进入安全模式（`rrclaw agent --safe` / `security.safe_mode`）
+    ///
+    /// 移除全部工具与技能并切到 ReadOnly：system prompt 与请求中都不再出现任何工具，
+    /// 与 ReadOnly 的区别是工具根本不会被告知给模型。
+    pub fn enter_safe_mode(&mut self) {
+        self.tools.clear();
+        self.skills_meta.clear();
+        self.policy.autonomy = crate::security::AutonomyLevel::ReadOnly;
+        self.safe_mode = true;
+    }
+
+    /// 是否处于安全模式
+    pub fn is_safe_mode(&self) -> bool {
+        self.safe_mode
+    }
+
+    /// 设置 Phase 1 路由专用模型（None 表示沿用主 Provider）
+    pub fn set_routing_model(&mut self, model: Option<AuxModel>) {
+        self.routing_model = model;
+    }
+
+    /// 设置历史压缩摘要专用模型（None 表示沿用主 Provider）
+    pub fn set_summary_model(&mut self, model: Option<AuxModel>) {
+        self.summary_model = model;
+    }
+
+    /// 设置对话摘要的脱敏器（None = 原文保存，`[memory] scrub = false`）
+    pub fn set_summary_scrubber(&mut self, scrubber: Option<Redactor>) {
+        self.summary_scrubber = scrubber;
+    }
+
+    /// 设置对话摘要的保存策略（`[memory] store_conversations` 及阈值）
+    pub fn set_conversation_filter(&mut self, filter: ConversationFilter) {
+        self.conversation_filter = filter;
+    }
+
+    /// 设置 Skill 使用统计句柄（`/skill stats` 的数据来源）
+    pub fn set_skill_usage_recorder(&mut self, recorder: Arc<dyn SkillUsageRecorder>) {
+        self.skill_usage = recorder;
+    }
+
+    /// 记录一次 skill 加载（名称不在当前 skill 列表中时忽略）
+    fn record_skill_use(&self, skill_name: &str, trigger: SkillTrigger) {
+        if let Some(meta) = self.skills_meta.iter().find(|s| s.name == skill_name) {
+            self.skill_usage.record(&meta.name, &meta.source, trigger);
+        }
+    }
+
+    /// 按 `[agent.routing]` / `[agent.summary]` 构造辅助模型（创建 Agent 后调用一次）
+    ///
+    /// 省略 provider 的配置只换模型，调用时使用当前主 Provider（`/switch` 后随之切换）。
+    pub fn configure_aux_models(&mut self, config: &crate::config::Config) {
+        let offline = crate::providers::OfflineState::global;
+        self.routing_model =
+            AuxModel::from_config(config, config.agent.routing.as_ref(), offline());
+        self.summary_model =
+            AuxModel::from_config(config, config.agent.summary.as_ref(), offline());
+    }
+
+    /// 按 `[agent]` 配置设置 system prompt 前缀 / 覆盖（空白内容视为未配置）
+    pub fn configure_system_prompt(&mut self, config: &crate::config::AgentConfig) {
+        let non_blank = |text: &Option<String>| {
+            text.as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+        };
+        self.system_prompt_prefix = non_blank(&config.system_prompt_prefix);
+        self.system_prompt_override = non_blank(&config.system_prompt_override);
+    }
+
+    /// 无工具的辅助调用：优先走辅助模型，失败时回退主 Provider + 主模型
+    async fn chat_aux(
+        &self,
+        aux: Option<&AuxModel>,
+        messages: &[ConversationMessage],
+        temperature: f64,
+        purpose: &str,
+    ) -> Result<ChatResponse> {
+        if let Some(aux) = aux {
+            // 未指定辅助 Provider 时用当前主 Provider，只换模型
+            let provider = aux.provider.as_deref().unwrap_or(self.provider.as_ref());
+            let provider_name = aux.provider_name.as_deref().unwrap_or(&self.provider_name);
+            match provider
+                .chat_with_tools(
+                    messages,
+                    &[],
+                    &aux.model,
+                    GenerationParams::with_temperature(temperature),
+                )
+                .await
+            {
+                Ok(resp) => return Ok(resp),
+                Err(e) => warn!(
+                    "{}模型 {}/{} 调用失败，回退主模型: {:#}",
+                    purpose, provider_name, aux.model, e
+                ),
+            }
+        }
+        self.provider
+            .chat_with_tools(
+                messages,
+                &[],
+                &self.model,
+                GenerationParams::with_temperature(temperature),
+            )
+            .await
+    }
+
+    /// 开启/关闭每轮文件变更摘要
+    pub fn set_track_changes(&mut self, enabled: bool) {
+        self.track_changes = enabled;
+    }
+
+    /// 通道能显示快捷回复（REPL 编号选项 / Telegram 回复键盘）时开启，
+    /// system prompt 会要求模型在最终回复末尾附带后续建议
+    pub fn set_quick_replies(&mut self, enabled: bool) {
+        self.quick_replies = enabled;
+    }
+
+    /// 开关脏工作区提醒（`security.warn_dirty_worktree`，REPL 创建 Agent 时设置）
+    pub fn set_warn_dirty_worktree(&mut self, enabled: bool) {
+        self.warn_dirty_worktree = enabled;
+    }
+
+    /// 取出上一轮的文件变更摘要（无变更或未开启时为 None）
+    pub fn take_change_summary(&mut self) -> Option<ChangeSummary> {
+        self.last_changes.take()
+    }
+
+    /// 上一轮最终回复的来源（REPL 提示行、`--json` 输出）
+    pub fn last_provenance(&self) -> Option<&Provenance> {
+        self.last_provenance.as_ref()
+    }
+
+    /// 上一轮的后续建议（REPL 编号选项、Telegram 回复键盘）
+    pub fn last_suggestions(&self) -> &[String] {
+        &self.last_suggestions
+    }
+
+    /// 工具执行前：首次调用时快照工作区，并记录参数中涉及的文件
+    async fn track_tool_call(&mut self, name: &str, args: &serde_json::Value) {
+        if !self.track_changes {
+            return;
+        }
+        if self.change_tracker.is_none() {
+            self.change_tracker = Some(ChangeTracker::start(&self.policy.workspace_dir).await);
+        }
+        if let Some(tracker) = self.change_tracker.as_mut() {
+            tracker.record_tool_call(name, args);
+        }
+    }
+
+    /// 轮次结束：对比快照生成变更摘要
+    async fn finish_change_tracking(&mut self) {
+        if let Some(tracker) = self.change_tracker.take() {
+            let summary = tracker.finish().await;
+            self.last_changes = (!summary.is_empty()).then_some(summary);
+        }
     }
 
     /// Phase 1 路由：调用轻量 LLM 决定需要加载哪些 skill
     async fn route(&self, user_message: &str) -> Result<RouteResult> {
         let lang = crate::config::Config::get_language();
-        let routing_prompt = build_routing_prompt(&self.skills_meta, lang);
+        let routing_prompt = prompts::routing_prompt(&self.skills_meta, lang);
 
         // 取最近 2 条纯文本历史（跳过 ToolCalls/ToolResults），
         // 让路由 LLM 理解对话上下文，避免对"方案B"/"继续"等短消息误判为 NeedClarification
@@ -282,23 +737,19 @@ impl Agent {
             role: "system".to_string(),
             content: routing_prompt,
             reasoning_content: None,
+            turn: 0,
         })];
         messages.extend(recent_context);
         messages.push(ConversationMessage::Chat(ChatMessage {
             role: "user".to_string(),
             content: user_message.to_string(),
             reasoning_content: None,
+            turn: 0,
         }));
 
-        // Phase 1 不传工具，温度极低保证输出稳定
+        // Phase 1 不传工具（禁止工具调用），低温度保证路由输出确定性
         let response = self
-            .provider
-            .chat_with_tools(
-                &messages,
-                &[], // 空工具列表，Phase 1 禁止工具调用
-                &self.model,
-                0.1, // 低温度，路由输出要确定性
-            )
+            .chat_aux(self.routing_model.as_ref(), &messages, 0.1, "路由")
             .await;
 
         match response {
@@ -309,13 +760,33 @@ impl Agent {
             }
             Ok(resp) => {
                 let text = resp.text.unwrap_or_default();
-                Ok(parse_route_result(&text))
+                let result = parse_route_result(&text);
+                self.record_route_usage(&result);
+                Ok(result)
+            }
+        }
+    }
+
+    /// 路由结果计入 skill 使用统计（路由调用失败降级的 Direct 不计）
+    fn record_route_usage(&self, result: &RouteResult) {
+        if self.skills_meta.is_empty() {
+            return;
+        }
+        let catalog: Vec<String> = self.skills_meta.iter().map(|s| s.name.clone()).collect();
+        self.skill_usage.record_route(
+            &catalog,
+            matches!(result, RouteResult::NeedClarification(_)),
+        );
+        if let RouteResult::Skills(names) = result {
+            for name in names {
+                self.record_skill_use(name, SkillTrigger::Routed);
             }
         }
     }
 
     /// 加载 skill L2 内容，存到临时字段，Phase 2 构建 system prompt 时使用
     fn inject_routed_skills(&mut self, skill_names: &[String]) {
+        self.routed_skill_names = skill_names.to_vec();
         let mut content = String::new();
         for name in skill_names {
             // 使用 src/skills/mod.rs 中的 load_skill_content(name, skills) -> Result<SkillContent>
@@ -338,6 +809,16 @@ impl Agent {
         }
     }
 
+    /// 可用 Skills 的元数据（内置 + 全局 + 项目级）
+    pub fn skills(&self) -> &[SkillMeta] {
+        &self.skills_meta
+    }
+
+    /// 上一轮 Phase 1 路由加载的 skill 名称
+    pub fn routed_skills(&self) -> &[String] {
+        &self.routed_skill_names
+    }
+
     /// 获取当前对话历史（用于持久化）
     pub fn history(&self) -> &[ConversationMessage] {
         &self.history
@@ -348,6 +829,22 @@ impl Agent {
     pub fn set_history(&mut self, history: Vec<ConversationMessage>) {
         self.history = history;
         self.sanitize_history();
+        // 旧版历史没有 Turn 标记，按 user 消息补齐
+        self.current_turn = turns::assign_missing_turns(&mut self.history);
+    }
+
+    /// 以 JSON 导出当前 history（`/history save`）
+    pub fn export_history(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.history).wrap_err("序列化对话历史失败")
+    }
+
+    /// 从 JSON 导入 history（`/history load`），经 `set_history` 清理，返回被丢弃的消息数
+    pub fn import_history(&mut self, json: &str) -> Result<usize> {
+        let history: Vec<ConversationMessage> =
+            serde_json::from_str(json).wrap_err("解析对话历史 JSON 失败")?;
+        let total = history.len();
+        self.set_history(history);
+        Ok(total - self.history.len())
     }
 
     /// 清空对话历史（/new 命令用）
@@ -355,6 +852,85 @@ impl Agent {
         self.history.clear();
     }
 
+    /// 从当前对话开一个分支：保存主对话状态，Agent 带着副本继续作为分支运行
+    ///
+    /// 分支最多一层，已在分支中时返回错误。Provider / 工具 / 记忆不复制，分支与主对话共用。
+    pub fn fork(&mut self, name: Option<String>) -> Result<AgentBranch> {
+        if let Some(current) = &self.branch_name {
+            return Err(eyre!("已在分支 '{}' 中，不支持嵌套分支", current));
+        }
+        let name = name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| branch::DEFAULT_BRANCH_NAME.to_string());
+        self.branch_name = Some(name.clone());
+        Ok(AgentBranch {
+            name,
+            fork_turn: self.current_turn,
+            history: self.history.clone(),
+            current_turn: self.current_turn,
+            routed_skill_content: self.routed_skill_content.clone(),
+            routed_skill_names: self.routed_skill_names.clone(),
+            routed_tool_names: self.routed_tool_names.clone(),
+            policy: self.policy.clone(),
+            read_only: self.read_only,
+            goal: self.goal.clone(),
+            last_changes: self.last_changes.clone(),
+            last_provenance: self.last_provenance.clone(),
+        })
+    }
+
+    /// 当前所在分支名（不在分支中时为 None）
+    pub fn branch_name(&self) -> Option<&str> {
+        self.branch_name.as_deref()
+    }
+
+    /// 丢弃分支：原样恢复 fork 时的主对话
+    pub fn drop_branch(&mut self, branch: AgentBranch) {
+        self.branch_name = None;
+        self.history = branch.history;
+        self.current_turn = branch.current_turn;
+        self.routed_skill_content = branch.routed_skill_content;
+        self.routed_skill_names = branch.routed_skill_names;
+        self.routed_tool_names = branch.routed_tool_names;
+        self.policy = branch.policy;
+        self.read_only = branch.read_only;
+        self.goal = branch.goal;
+        self.last_changes = branch.last_changes;
+        self.last_provenance = branch.last_provenance;
+    }
+
+    /// 用历史压缩的摘要流程总结分支里新产生的对话；分支内没有新消息时为 None
+    ///
+    /// 需在 `merge_branch` 之前调用（此时 history 仍是分支的）；失败时分支保持不变，可重试或丢弃。
+    pub async fn summarize_branch(&self, branch: &AgentBranch) -> Result<Option<String>> {
+        let messages = branch.new_messages(&self.history);
+        if messages.is_empty() {
+            return Ok(None);
+        }
+        self.summarize_history(&messages).await.map(Some)
+    }
+
+    /// 合并分支：恢复主对话，再追加一条分支结论摘要（不带入分支的完整记录）
+    pub fn merge_branch(&mut self, branch: AgentBranch, summary: Option<String>) {
+        let name = branch.name.clone();
+        self.drop_branch(branch);
+        if let Some(summary) = summary {
+            self.current_turn += 1;
+            self.history
+                .push(branch::merge_message(&name, &summary, self.current_turn));
+        }
+    }
+
+    /// 当前会话目标
+    pub fn goal(&self) -> Option<&SessionGoal> {
+        self.goal.as_ref()
+    }
+
+    /// 设置或清除会话目标（`/goal set` / `/goal done`、恢复 session 时调用）
+    pub fn set_goal(&mut self, goal: Option<SessionGoal>) {
+        self.goal = goal;
+    }
+
     /// 获取当前 Provider 名
     pub fn provider_name(&self) -> &str {
         &self.provider_name
@@ -389,9 +965,43 @@ impl Agent {
         self.model = model;
     }
 
+    /// 临时换用另一个 Provider（`@provider` 单条消息固定），返回换下来的一组；再交换一次即恢复
+    pub fn swap_provider(&mut self, pinned: PinnedProvider) -> PinnedProvider {
+        let (provider, provider_name, base_url, model) = pinned;
+        (
+            std::mem::replace(&mut self.provider, provider),
+            std::mem::replace(&mut self.provider_name, provider_name),
+            std::mem::replace(&mut self.base_url, base_url),
+            std::mem::replace(&mut self.model, model),
+        )
+    }
+
     /// 获取当前温度
     pub fn temperature(&self) -> f64 {
-        self.temperature
+        self.params.temperature
+    }
+
+    /// 当前生效的生成参数
+    pub fn generation_params(&self) -> GenerationParams {
+        self.params
+    }
+
+    /// 会话内修改单个生成参数（`/temp`、`/param set`，不持久化）；校验失败时不改动
+    pub fn set_generation_param(&mut self, name: &str, value: &str) -> Result<()> {
+        let mut params = self.params;
+        params.set(name, value)?;
+        self.params = params;
+        Ok(())
+    }
+
+    /// 配置中的生成参数（未被会话覆盖时的取值）
+    pub fn default_generation_params(&self) -> GenerationParams {
+        self.default_params
+    }
+
+    /// 恢复配置中的生成参数（`/param reset`）
+    pub fn reset_generation_params(&mut self) {
+        self.params = self.default_params;
     }
 
     /// 获取安全策略引用
@@ -404,11 +1014,134 @@ impl Agent {
         self.policy.autonomy = level;
     }
 
+    /// 替换 shell 命令白名单（`/mode` 编辑 allowed_commands）
+    pub fn set_allowed_commands(&mut self, commands: Vec<String>) {
+        self.policy.allowed_commands = commands;
+    }
+
+    /// 以指定自主级别处理一条消息（REPL `!` 前缀一次性授权），结束后恢复原级别
+    pub async fn process_message_as(
+        &mut self,
+        level: AutonomyLevel,
+        user_msg: &str,
+    ) -> Result<String> {
+        let previous = std::mem::replace(&mut self.policy.autonomy, level);
+        let result = self.process_message(user_msg).await;
+        self.policy.autonomy = previous;
+        result
+    }
+
+    /// `process_message_as` 的流式版本
+    pub async fn process_message_stream_as(
+        &mut self,
+        level: AutonomyLevel,
+        user_msg: &str,
+        tx: mpsc::Sender<StreamEvent>,
+    ) -> Result<String> {
+        let previous = std::mem::replace(&mut self.policy.autonomy, level);
+        let result = self.process_message_stream(user_msg, tx).await;
+        self.policy.autonomy = previous;
+        result
+    }
+
+    /// 开关只读回合（`/readonly`）：与自主级别无关，只允许 `ToolRisk::Read` 工具
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// 是否处于只读回合
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 以只读回合处理一条消息（`/ask` 前缀），结束后恢复原设置
+    pub async fn process_message_read_only(&mut self, user_msg: &str) -> Result<String> {
+        let previous = std::mem::replace(&mut self.read_only, true);
+        let result = self.process_message(user_msg).await;
+        self.read_only = previous;
+        result
+    }
+
+    /// `process_message_read_only` 的流式版本
+    pub async fn process_message_stream_read_only(
+        &mut self,
+        user_msg: &str,
+        tx: mpsc::Sender<StreamEvent>,
+    ) -> Result<String> {
+        let previous = std::mem::replace(&mut self.read_only, true);
+        let result = self.process_message_stream(user_msg, tx).await;
+        self.read_only = previous;
+        result
+    }
+
+    /// 本轮是否向模型暴露该工具（只读回合只保留 Read 类）
+    fn is_exposed(&self, tool: &dyn Tool) -> bool {
+        !self.read_only || tool.risk() == ToolRisk::Read
+    }
+
+    /// 只读回合中拒绝非 Read 类工具（含未知工具与 goal_update），返回拒绝原因
+    fn read_only_rejection(&self, name: &str) -> Option<String> {
+        if !self.read_only {
+            return None;
+        }
+        let allowed = self
+            .tools
+            .iter()
+            .find(|t| t.name() == name)
+            .is_some_and(|t| t.risk() == ToolRisk::Read);
+        (!allowed).then(|| {
+            format!(
+                "只读回合（/ask）禁止调用 '{}'：本轮只允许读取类工具，不能修改任何内容",
+                name
+            )
+        })
+    }
+
+    /// 热更新安全策略（`rrclaw reload`）；workspace_dir / blocked_paths 保持不变
+    pub fn apply_security_config(&mut self, security: &crate::config::SecurityConfig) {
+        // 安全模式保持 ReadOnly，不随配置热更新放开
+        if !self.safe_mode {
+            self.policy.autonomy = crate::security::sandbox::clamp_autonomy(
+                security.autonomy.clone(),
+                security.require_sandbox,
+            );
+        }
+        self.policy.allowed_commands = security.allowed_commands.clone();
+        self.policy.http_allowed_hosts = security.http_allowed_hosts.clone();
+        self.policy.injection_check = security.injection_check;
+        self.warn_dirty_worktree = security.warn_dirty_worktree;
+    }
+
     /// 标记当前 Agent 为 Routine 执行模式（注入 Routine 专属 system prompt 段）
     pub fn set_routine_name(&mut self, name: String) {
         self.routine_name = Some(name);
     }
 
+    /// 设置取消令牌：触发后正在等待的 Provider 调用立即以 `CANCELLED_MESSAGE` 失败，
+    /// 尚未执行的工具调用跳过（已在执行的工具不会被中断）
+    pub fn set_cancel_token(&mut self, token: CancellationToken) {
+        self.cancel = Some(token);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|t| t.is_cancelled())
+    }
+
+    /// 等待一次 Provider 调用；设置了取消令牌时，令牌触发即放弃等待
+    async fn unless_cancelled<T>(
+        &self,
+        call: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let Some(token) = &self.cancel else {
+            return call.await;
+        };
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => Err(eyre!(CANCELLED_MESSAGE)),
+            result = call => result,
+        }
+    }
+
     /// 重新加载身份文件（无需重启）
     /// 调用方需提供 data_dir（Agent 自身不存储，避免扩大结构体）
     pub fn reload_identity(&mut self, workspace_dir: &std::path::Path, data_dir: &std::path::Path) {
@@ -419,40 +1152,76 @@ impl Agent {
         }
     }
 
+    /// 切换工作目录：校验后更新安全策略（相对路径按新目录解析），并重新加载项目级身份文件
+    ///
+    /// 项目级 Skills 由调用方按新目录重新扫描（`skills::load_skills`）。
+    pub fn set_workspace(
+        &mut self,
+        workspace_dir: &std::path::Path,
+        data_dir: &std::path::Path,
+    ) -> Result<()> {
+        self.policy.set_workspace(workspace_dir)?;
+        let workspace_dir = self.policy.workspace_dir.clone();
+        self.reload_identity(&workspace_dir, data_dir);
+        Ok(())
+    }
+
+    /// 当前注入 system prompt 的身份文件内容
+    pub fn identity_context(&self) -> Option<&str> {
+        self.identity_context.as_deref()
+    }
+
     /// 获取所有已加载工具的名称列表
     pub fn tool_names(&self) -> Vec<&str> {
         self.tools.iter().map(|t| t.name()).collect()
     }
 
+    /// 估算下一次请求的输入 token 数（system prompt + history + tools，不含新消息与记忆召回）
+    pub fn estimate_request_tokens(&self) -> usize {
+        let (specs, _) = self.apply_tool_limits(self.build_tool_specs(""));
+        tokens::estimate_tokens(&self.build_system_prompt(&[]))
+            + self
+                .history
+                .iter()
+                .map(tokens::estimate_message_tokens)
+                .sum::<usize>()
+            + tokens::estimate_tool_spec_tokens(&specs)
+    }
+
     /// 清理 history 中无效的消息序列
-    /// - 移除开头孤立的 ToolResult（没有对应的 AssistantToolCalls）
-    /// - 移除中间孤立的 ToolResult（前面不是 AssistantToolCalls 或 ToolResult）
+    /// - 移除孤立的 ToolResult（id 不属于紧邻的 AssistantToolCalls，或该 id 已有结果）
+    /// - 移除结果不完整的 AssistantToolCalls 及其已有结果（旧版本消息解析失败被跳过、进程中断等）
     fn sanitize_history(&mut self) {
         if self.history.is_empty() {
             return;
         }
-        // 逐条检查，保留合法序列
-        let mut cleaned = Vec::with_capacity(self.history.len());
-        for msg in self.history.drain(..) {
-            match &msg {
-                ConversationMessage::ToolResult { .. } => {
-                    // ToolResult 只能出现在 AssistantToolCalls 或另一个 ToolResult 之后
-                    let prev_ok = cleaned.last().is_some_and(|prev| {
-                        matches!(
-                            prev,
-                            ConversationMessage::AssistantToolCalls { .. }
-                                | ConversationMessage::ToolResult { .. }
-                        )
-                    });
-                    if prev_ok {
-                        cleaned.push(msg);
-                    } else {
-                        debug!("清理孤立 ToolResult: {:?}", msg);
-                    }
+        let mut cleaned: Vec<ConversationMessage> = Vec::with_capacity(self.history.len());
+        // 当前 AssistantToolCalls 块的起始位置与尚未得到结果的 tool call id
+        let mut block: Option<(usize, Vec<String>)> = None;
+        for msg in std::mem::take(&mut self.history) {
+            if let ConversationMessage::ToolResult { tool_call_id, .. } = &msg {
+                let pending = block.as_mut().and_then(|(_, open)| {
+                    open.iter()
+                        .position(|id| id == tool_call_id)
+                        .map(|i| open.remove(i))
+                });
+                if pending.is_some() {
+                    cleaned.push(msg);
+                } else {
+                    debug!("清理孤立 ToolResult: {:?}", msg);
                 }
-                _ => cleaned.push(msg),
+                continue;
+            }
+            close_tool_block(&mut cleaned, block.take());
+            if let ConversationMessage::AssistantToolCalls { tool_calls, .. } = &msg {
+                block = Some((
+                    cleaned.len(),
+                    tool_calls.iter().map(|tc| tc.id.clone()).collect(),
+                ));
             }
+            cleaned.push(msg);
         }
+        close_tool_block(&mut cleaned, block);
         self.history = cleaned;
     }
 
@@ -476,229 +1245,7 @@ impl Agent {
 
     /// 处理一条用户消息，返回 AI 最终回复
     pub async fn process_message(&mut self, user_msg: &str) -> Result<String> {
-        // 0. 新 Turn: 清空旧 reasoning_content（节省 token，DeepSeek/MiniMax 文档建议）
-        self.clear_old_reasoning_content();
-
-        // ─── Phase 1: 路由 ───────────────────────────────────────────
-        let route_result = self.route(user_msg).await?;
-
-        match route_result {
-            RouteResult::NeedClarification(question) => {
-                // 直接返回澄清问题字符串，不写入 history，不执行任何工具
-                // CLI/Telegram 层收到后直接展示给用户
-                return Ok(question);
-            }
-            RouteResult::Skills(skill_names) => {
-                // 加载对应 skill 的 L2 内容，注入到本次 Phase 2 的 system prompt
-                self.inject_routed_skills(&skill_names);
-            }
-            RouteResult::Direct => {
-                // 清空本次临时注入的 skill（上一轮可能有残留）
-                self.routed_skill_content = None;
-            }
-        }
-
-        // ─── Phase 1.5: 关键词工具路由 ────────────────────────────────
-        self.routed_tool_names = crate::agent::tool_groups::route_tools(user_msg);
-        if !self.routed_tool_names.is_empty() {
-            debug!("Phase 1.5 工具路由: {:?}", self.routed_tool_names);
-        }
-
-        // ─── Phase 2: 正常 Agent Loop ────────────────────────────────
-        // 1. Memory recall
-        let memories = self.memory.recall(user_msg, 5).await.unwrap_or_default();
-
-        // 2. 构造 system prompt（使用路由后的工具列表）
-        let system_prompt = self.build_system_prompt(&memories);
-
-        // 3. 添加用户消息到 history
-        self.history.push(ConversationMessage::Chat(ChatMessage {
-            role: "user".to_string(),
-            content: user_msg.to_string(),
-            reasoning_content: None,
-        }));
-
-        // 4. Tool call 循环（工具 spec 由 build_tool_specs 统一管理）
-        // P7-3: tool_specs 可变，允许在循环内按需升级工具 schema
-        let mut tool_specs = self.build_tool_specs(user_msg);
-        // P7-3: 每轮重置已扩展集合
-        self.expanded_tools.clear();
-        let mut final_text = String::new();
-
-        for iteration in 0..MAX_TOOL_ITERATIONS {
-            // 构造消息列表：system + history
-            let mut messages = vec![ConversationMessage::Chat(ChatMessage {
-                role: "system".to_string(),
-                content: system_prompt.clone(),
-                reasoning_content: None,
-            })];
-            messages.extend(self.history.clone());
-
-            debug!(
-                "iteration={}, history_len={}",
-                iteration,
-                self.history.len()
-            );
-            debug!("system_prompt:\n{}", system_prompt);
-            debug!("messages_to_llm: {:?}", messages);
-
-            // 调用 Provider
-            let response = self
-                .provider
-                .chat_with_tools(&messages, &tool_specs, &self.model, self.temperature)
-                .await?;
-
-            debug!(
-                "response: text={:?}, tool_calls_count={}",
-                response.text.as_deref().map(|t| truncate_str(t, 100)),
-                response.tool_calls.len()
-            );
-
-            if response.tool_calls.is_empty() {
-                // 无 tool calls — 最终回复
-                final_text = response.text.unwrap_or_default();
-                if final_text.is_empty() {
-                    warn!("模型返回空文本回复");
-                }
-                self.history.push(ConversationMessage::Chat(ChatMessage {
-                    role: "assistant".to_string(),
-                    content: final_text.clone(),
-                    reasoning_content: response.reasoning_content.clone(),
-                }));
-                break;
-            }
-
-            // 有 tool calls — 记录并逐个执行
-            self.history.push(ConversationMessage::AssistantToolCalls {
-                text: response.text.clone(),
-                reasoning_content: response.reasoning_content.clone(),
-                tool_calls: response.tool_calls.clone(),
-            });
-
-            for tc in &response.tool_calls {
-                // 预验证: 在确认前检查安全策略（避免确认后被拒绝）
-                if let Some(tool) = self.tools.iter().find(|t| t.name() == tc.name) {
-                    if let Some(rejection) = tool.pre_validate(&tc.arguments, &self.policy) {
-                        info!("工具预验证失败: {} - {}", tc.name, rejection);
-                        self.history.push(ConversationMessage::ToolResult {
-                            tool_call_id: tc.id.clone(),
-                            content: format!("[失败] {}", rejection),
-                        });
-                        continue;
-                    }
-                }
-
-                // ─── P7-3: 动态 Schema 补充 ──────────────────────────────────────────
-                // 检测必填参数缺失（每轮每个工具只触发一次，避免死循环）
-                if !self.expanded_tools.contains(&tc.name) {
-                    let missing = {
-                        self.tools
-                            .iter()
-                            .find(|t| t.name() == tc.name)
-                            .map(|t| {
-                                find_missing_required_params(&t.parameters_schema(), &tc.arguments)
-                            })
-                            .unwrap_or_default()
-                    };
-                    if !missing.is_empty() {
-                        self.expanded_tools.insert(tc.name.clone());
-                        // 升级 MCP 工具为 L2 完整 schema（对内置工具无副作用）
-                        if let Some(tool) = self.tools.iter_mut().find(|t| t.name() == tc.name) {
-                            tool.load_full_schema();
-                        }
-                        // 更新 tool_specs 供下一迭代使用
-                        if let Some(tool) = self.tools.iter().find(|t| t.name() == tc.name) {
-                            let new_spec = tool.spec();
-                            if let Some(spec) = tool_specs.iter_mut().find(|s| s.name == tc.name) {
-                                *spec = new_spec;
-                            } else {
-                                tool_specs.push(new_spec);
-                            }
-                        }
-                        debug!(
-                            "P7-3: 工具 '{}' 缺少参数 {:?}，已注入完整 schema",
-                            tc.name, missing
-                        );
-                        self.history.push(ConversationMessage::ToolResult {
-                            tool_call_id: tc.id.clone(),
-                            content: format!(
-                                "[参数缺失] 工具 '{}' 缺少必填参数: {}。完整参数说明已在工具列表中更新，请用正确参数重新调用。",
-                                tc.name,
-                                missing.join(", ")
-                            ),
-                        });
-                        continue;
-                    }
-                }
-                // ─── P7-3 结束 ────────────────────────────────────────────────────────
-
-                // Supervised 模式: 执行前需用户确认
-                if self.policy.requires_confirmation() {
-                    if let Some(confirm) = &self.confirm_fn {
-                        if !confirm(&tc.name, &tc.arguments) {
-                            info!("用户拒绝执行工具: {}", tc.name);
-                            self.history.push(ConversationMessage::ToolResult {
-                                tool_call_id: tc.id.clone(),
-                                content: "用户拒绝执行该工具".to_string(),
-                            });
-                            continue;
-                        }
-                    }
-                }
-
-                info!("执行工具: {} args={}", tc.name, tc.arguments);
-                let result = self.execute_tool(&tc.name, tc.arguments.clone()).await;
-                debug!("工具结果: {}", truncate_str(&result, 200));
-
-                // MCP 工具首次调用后升级为 L2 完整 schema（下轮用户消息生效）
-                if tc.name.starts_with("mcp_") {
-                    if let Some(tool) = self.tools.iter_mut().find(|t| t.name() == tc.name) {
-                        if !tool.is_full_schema_loaded() {
-                            tool.load_full_schema();
-                            debug!("MCP 工具 '{}' 已升级为 L2 完整 schema", tc.name);
-                        }
-                    }
-                }
-
-                // ─── Prompt Injection 检测 ───────────────────────────────────────────
-                // 只检测外部数据工具（shell/file_read/git/http_request）；
-                // 内部工具（memory_*/skill/self_info/config）返回受控内容，跳过检测
-                let final_content =
-                    if self.policy.injection_check && needs_injection_check(&tc.name) {
-                        let injection = crate::security::injection::check_tool_result(&result);
-                        if let Some(ref sev) = injection.severity {
-                            info!(
-                                tool = %tc.name,
-                                severity = ?sev,
-                                reason = ?injection.reason,
-                                "Prompt injection detected in tool result"
-                            );
-                        }
-                        injection.sanitized
-                    } else {
-                        result
-                    };
-                // ─── 检测结束 ─────────────────────────────────────────────────────────
-
-                self.history.push(ConversationMessage::ToolResult {
-                    tool_call_id: tc.id.clone(),
-                    content: final_content,
-                });
-            }
-        }
-
-        // 5. Memory store — 保存对话摘要
-        let summary = format!("User: {}\nAssistant: {}", user_msg, final_text);
-        let key = format!("conv_{}", chrono::Utc::now().timestamp_millis());
-        let _ = self
-            .memory
-            .store(&key, &summary, MemoryCategory::Conversation)
-            .await;
-
-        // 6. 裁剪 history
-        self.compact_history_if_needed().await;
-
-        Ok(final_text)
+        self.run_turn(user_msg, &mut TurnSink::Silent).await
     }
 
     /// 处理一条用户消息（流式版本）
@@ -708,17 +1255,29 @@ impl Agent {
         user_msg: &str,
         tx: mpsc::Sender<StreamEvent>,
     ) -> Result<String> {
+        let mut sink = TurnSink::Stream {
+            events: EventSink::new(tx.clone()),
+            tx,
+        };
+        self.run_turn(user_msg, &mut sink).await
+    }
+
+    /// 一轮对话的核心流程（流式 / 非流式共用，区别只在 `sink`）
+    async fn run_turn(&mut self, user_msg: &str, sink: &mut TurnSink) -> Result<String> {
         // 0. 新 Turn: 清空旧 reasoning_content（节省 token，DeepSeek/MiniMax 文档建议）
         self.clear_old_reasoning_content();
+        self.tool_suggestions.clear();
+        self.last_suggestions.clear();
 
         // ─── Phase 1: 路由 ───────────────────────────────────────────
-        let route_result = self.route(user_msg).await?;
+        let route_result = self.unless_cancelled(self.route(user_msg)).await?;
 
         match route_result {
             RouteResult::NeedClarification(question) => {
-                // 通过 tx 发送澄清问题，不写入 history，不执行任何工具
-                // 必须走 tx 发送，否则 stream_message 里 Ok(_) 会丢弃返回值
-                let _ = tx.send(StreamEvent::Text(question.clone())).await;
+                // 澄清问题不写入 history，不执行任何工具
+                // 流式时必须走 sink 发送，否则 stream_message 里 Ok(_) 会丢弃返回值
+                sink.send(StreamEvent::Text(question.clone())).await;
+                sink.flush().await;
                 return Ok(question);
             }
             RouteResult::Skills(skill_names) => {
@@ -728,551 +1287,572 @@ impl Agent {
             RouteResult::Direct => {
                 // 清空本次临时注入的 skill（上一轮可能有残留）
                 self.routed_skill_content = None;
+                self.routed_skill_names.clear();
             }
         }
 
         // ─── Phase 1.5: 关键词工具路由 ────────────────────────────────
         self.routed_tool_names = crate::agent::tool_groups::route_tools(user_msg);
         if !self.routed_tool_names.is_empty() {
-            debug!("Phase 1.5 工具路由(stream): {:?}", self.routed_tool_names);
+            debug!("Phase 1.5 工具路由: {:?}", self.routed_tool_names);
         }
 
         // ─── Phase 2: 正常 Agent Loop ────────────────────────────────
         // 1. Memory recall
         let memories = self.memory.recall(user_msg, 5).await.unwrap_or_default();
 
-        // 2. 构造 system prompt（使用路由后的工具列表）
-        let system_prompt = self.build_system_prompt(&memories);
-
-        // 3. 添加用户消息到 history
+        // 2. 工具 spec（按 Provider 限制裁剪；P7-3: 可变，允许在循环内按需升级工具 schema）
+        let (mut tool_specs, omitted) = self.apply_tool_limits(self.build_tool_specs(user_msg));
+        self.omitted_tool_count = omitted;
+        let mut tool_limit_retried = false;
+        let mut context_retried = false;
+
+        // 3. 构造 system prompt（使用路由后的工具列表）
+        let mut system_prompt = self.build_system_prompt(&memories);
+
+        // 4. 添加用户消息到 history（开启新 Turn）
+        self.current_turn += 1;
+        info!(
+            "Turn {}: prompt v{}, system prompt {} 字节",
+            self.current_turn,
+            prompts::PROMPT_VERSION,
+            system_prompt.len()
+        );
         self.history.push(ConversationMessage::Chat(ChatMessage {
             role: "user".to_string(),
             content: user_msg.to_string(),
             reasoning_content: None,
+            turn: self.current_turn,
         }));
 
-        // 4. Tool call 循环（工具 spec 由 build_tool_specs 统一管理）
-        // P7-3: tool_specs 可变，允许在循环内按需升级工具 schema
-        let mut tool_specs = self.build_tool_specs(user_msg);
-        // P7-3: 每轮重置已扩展集合（stream 版本共享同一 expanded_tools）
+        // 5. Tool call 循环（工具 spec 由 build_tool_specs 统一管理）
+        // P7-3: 每轮重置已扩展集合
         self.expanded_tools.clear();
+        self.call_ledger.clear();
+        self.rich_outputs.clear();
+        self.tool_feedback.clear();
+        self.change_tracker = None;
+        self.last_changes = None;
+        self.last_provenance = None;
         let mut final_text = String::new();
 
         for iteration in 0..MAX_TOOL_ITERATIONS {
+            // 构造消息列表：system + history
             let mut messages = vec![ConversationMessage::Chat(ChatMessage {
                 role: "system".to_string(),
                 content: system_prompt.clone(),
                 reasoning_content: None,
+                turn: 0,
             })];
             messages.extend(self.history.clone());
 
             debug!(
-                "stream iteration={}, history_len={}",
+                "iteration={}, streaming={}, history_len={}",
                 iteration,
+                sink.is_streaming(),
                 self.history.len()
             );
             debug!("system_prompt:\n{}", system_prompt);
             debug!("messages_to_llm: {:?}", messages);
 
             // 发送 Thinking 状态
-            let _ = tx.send(StreamEvent::Thinking).await;
+            sink.send(StreamEvent::Thinking).await;
 
-            // 流式调用 Provider
-            let response = self
-                .provider
-                .chat_stream(
+            // 调用 Provider（工具过多被拒时只保留优先工具重试一次）
+            // 两种重试都在下一次迭代用新的 system prompt / history 重新构造消息列表
+            let response = match self
+                .unless_cancelled(sink.chat(
+                    self.provider.as_ref(),
                     &messages,
                     &tool_specs,
                     &self.model,
-                    self.temperature,
-                    tx.clone(),
-                )
-                .await?;
+                    self.params,
+                ))
+                .await
+            {
+                Ok(response) => response,
+                Err(e) if !tool_limit_retried && self.should_retry_with_fewer_tools(&e) => {
+                    tool_limit_retried = true;
+                    tool_specs = self.reduce_to_priority_tools(tool_specs);
+                    // 省略的工具数变了，system prompt 里的提示要同步
+                    system_prompt = self.build_system_prompt(&memories);
+                    continue;
+                }
+                // 上下文超限：立即压缩 history 后重试一次
+                Err(e) if is_context_overflow(&e) => {
+                    if !context_retried && self.force_compact_history().await {
+                        context_retried = true;
+                        continue;
+                    }
+                    warn!("压缩后仍超出上下文窗口: {:#}", e);
+                    return Err(eyre!(context_overflow_message(context_retried)));
+                }
+                Err(e) => return Err(e),
+            };
 
             debug!(
-                "stream response: text={:?}, tool_calls_count={}",
+                "response: text={:?}, tool_calls_count={}",
                 response.text.as_deref().map(|t| truncate_str(t, 100)),
                 response.tool_calls.len()
             );
 
             if response.tool_calls.is_empty() {
-                final_text = response.text.unwrap_or_default();
+                // 无 tool calls — 最终回复
+                self.last_provenance = response.provenance.clone();
+                // suggestions 块只给 UI，不进入 history 和返回文本
+                let (text, suggested) = suggestions::extract(&response.text.unwrap_or_default());
+                final_text = text;
+                self.last_suggestions = suggestions::merge(suggested, &self.tool_suggestions);
                 if final_text.is_empty() {
-                    warn!("流式: 模型返回空文本回复");
+                    warn!("模型返回空文本回复");
                 }
                 self.history.push(ConversationMessage::Chat(ChatMessage {
                     role: "assistant".to_string(),
                     content: final_text.clone(),
                     reasoning_content: response.reasoning_content.clone(),
+                    turn: self.current_turn,
                 }));
                 break;
             }
 
             // 有 tool calls — 先停止 thinking spinner（避免和确认提示冲突）
-            let _ = tx.send(StreamEvent::Done(response.clone())).await;
-            // 等待 print_handle 处理 Done 事件（清理 spinner），避免和确认提示竞争
-            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            if sink.is_streaming() {
+                sink.send(StreamEvent::Done(response.clone())).await;
+                // 等待 print_handle 处理 Done 事件（清理 spinner），避免和确认提示竞争
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
 
-            // 有 tool calls — tool call 阶段不流式输出文本给用户
+            // 记录并逐个执行（tool call 阶段不流式输出文本给用户）
             self.history.push(ConversationMessage::AssistantToolCalls {
                 text: response.text.clone(),
                 reasoning_content: response.reasoning_content.clone(),
                 tool_calls: response.tool_calls.clone(),
+                turn: self.current_turn,
             });
 
             for tc in &response.tool_calls {
-                // 预验证: 在确认前检查安全策略（避免确认后被拒绝）
-                if let Some(tool) = self.tools.iter().find(|t| t.name() == tc.name) {
-                    if let Some(rejection) = tool.pre_validate(&tc.arguments, &self.policy) {
-                        info!("工具预验证失败: {} - {}", tc.name, rejection);
-                        self.history.push(ConversationMessage::ToolResult {
-                            tool_call_id: tc.id.clone(),
-                            content: format!("[失败] {}", rejection),
-                        });
-                        continue;
-                    }
-                }
-
-                // ─── P7-3: 动态 Schema 补充 ──────────────────────────────────────────
-                // 检测必填参数缺失（每轮每个工具只触发一次，避免死循环）
-                if !self.expanded_tools.contains(&tc.name) {
-                    let missing = {
-                        self.tools
-                            .iter()
-                            .find(|t| t.name() == tc.name)
-                            .map(|t| {
-                                find_missing_required_params(&t.parameters_schema(), &tc.arguments)
-                            })
-                            .unwrap_or_default()
-                    };
-                    if !missing.is_empty() {
-                        self.expanded_tools.insert(tc.name.clone());
-                        // 升级 MCP 工具为 L2 完整 schema（对内置工具无副作用）
-                        if let Some(tool) = self.tools.iter_mut().find(|t| t.name() == tc.name) {
-                            tool.load_full_schema();
-                        }
-                        // 更新 tool_specs 供下一迭代使用
-                        if let Some(tool) = self.tools.iter().find(|t| t.name() == tc.name) {
-                            let new_spec = tool.spec();
-                            if let Some(spec) = tool_specs.iter_mut().find(|s| s.name == tc.name) {
-                                *spec = new_spec;
-                            } else {
-                                tool_specs.push(new_spec);
-                            }
-                        }
-                        debug!(
-                            "P7-3(stream): 工具 '{}' 缺少参数 {:?}，已注入完整 schema",
-                            tc.name, missing
-                        );
-                        self.history.push(ConversationMessage::ToolResult {
-                            tool_call_id: tc.id.clone(),
-                            content: format!(
-                                "[参数缺失] 工具 '{}' 缺少必填参数: {}。完整参数说明已在工具列表中更新，请用正确参数重新调用。",
-                                tc.name,
-                                missing.join(", ")
-                            ),
-                        });
-                        continue;
-                    }
-                }
-                // ─── P7-3 结束 ────────────────────────────────────────────────────────
-
-                // Supervised 模式: 执行前需用户确认
-                if self.policy.requires_confirmation() {
-                    if let Some(confirm) = &self.confirm_fn {
-                        if !confirm(&tc.name, &tc.arguments) {
-                            info!("用户拒绝执行工具: {}", tc.name);
-                            self.history.push(ConversationMessage::ToolResult {
-                                tool_call_id: tc.id.clone(),
-                                content: "用户拒绝执行该工具".to_string(),
-                            });
-                            continue;
-                        }
-                    }
-                }
-
-                // 发送执行状态
-                let cmd_summary = if tc.name == "shell" {
-                    tc.arguments
-                        .get("command")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or(&tc.name)
-                        .to_string()
+                // 已取消：剩余工具不再执行（下一次 Provider 调用前返回取消错误）
+                let content = if self.is_cancelled() {
+                    CANCELLED_TOOL_RESULT.to_string()
                 } else {
-                    tc.name.clone()
+                    self.handle_tool_call(tc, &mut tool_specs, sink).await
                 };
-                let _ = tx
-                    .send(StreamEvent::ToolStatus {
-                        name: tc.name.clone(),
-                        status: ToolStatusKind::Running(cmd_summary.clone()),
-                    })
-                    .await;
-
-                info!("执行工具: {} args={}", tc.name, tc.arguments);
-                let result = self.execute_tool(&tc.name, tc.arguments.clone()).await;
-                debug!("工具结果: {}", truncate_str(&result, 200));
-
-                // MCP 工具首次调用后升级为 L2 完整 schema（下轮用户消息生效）
-                if tc.name.starts_with("mcp_") {
-                    if let Some(tool) = self.tools.iter_mut().find(|t| t.name() == tc.name) {
-                        if !tool.is_full_schema_loaded() {
-                            tool.load_full_schema();
-                            debug!("MCP 工具 '{}' 已升级为 L2 完整 schema", tc.name);
-                        }
-                    }
-                }
-
-                // 发送执行结果状态
-                if result.starts_with("[失败]") || result.starts_with("[错误]") {
-                    let _ = tx
-                        .send(StreamEvent::ToolStatus {
-                            name: tc.name.clone(),
-                            status: ToolStatusKind::Failed(truncate_str(&result, 200)),
-                        })
-                        .await;
-                } else {
-                    // 成功时显示首行预览
-                    let summary = if result.len() > 80 {
-                        let first_line = result.lines().next().unwrap_or("");
-                        let preview = truncate_str(first_line, 60);
-                        format!("{} (共{}字节)", preview, result.len())
-                    } else {
-                        truncate_str(&result, 80)
-                    };
-                    let _ = tx
-                        .send(StreamEvent::ToolStatus {
-                            name: tc.name.clone(),
-                            status: ToolStatusKind::Success(summary),
-                        })
-                        .await;
-                }
-
-                // ─── Prompt Injection 检测 ───────────────────────────────────────────
-                // 只检测外部数据工具（shell/file_read/git/http_request）；
-                // 内部工具（memory_*/skill/self_info/config）返回受控内容，跳过检测
-                let final_content =
-                    if self.policy.injection_check && needs_injection_check(&tc.name) {
-                        let injection = crate::security::injection::check_tool_result(&result);
-                        if let Some(ref sev) = injection.severity {
-                            info!(
-                                tool = %tc.name,
-                                severity = ?sev,
-                                reason = ?injection.reason,
-                                "Prompt injection detected in tool result"
-                            );
-                        }
-                        injection.sanitized
-                    } else {
-                        result
-                    };
-                // ─── 检测结束 ─────────────────────────────────────────────────────────
-
                 self.history.push(ConversationMessage::ToolResult {
                     tool_call_id: tc.id.clone(),
-                    content: final_content,
+                    content,
+                    turn: self.current_turn,
                 });
             }
         }
 
-        // 5. Memory store
-        let summary = format!("User: {}\nAssistant: {}", user_msg, final_text);
-        let key = format!("conv_{}", chrono::Utc::now().timestamp_millis());
-        let _ = self
-            .memory
-            .store(&key, &summary, MemoryCategory::Conversation)
-            .await;
+        self.finish_change_tracking().await;
+
+        // 6. Memory store — 保存对话摘要（先筛选；脱敏只作用于摘要，返回给用户的回复保持原样）
+        if let Some(summary) =
+            self.conversation_filter
+                .summarize(user_msg, &final_text, &self.tool_feedback)
+        {
+            let summary = match &self.summary_scrubber {
+                Some(scrubber) => scrubber.redact(&summary),
+                None => summary,
+            };
+            let key = format!("conv_{}", chrono::Utc::now().timestamp_millis());
+            if let Err(e) = self
+                .memory
+                .store(&key, &summary, MemoryCategory::Conversation)
+                .await
+            {
+                crate::memory::report_write_error("保存对话摘要", &e);
+            }
+        } else {
+            debug!("跳过保存本轮对话摘要");
+        }
 
-        // 6. 裁剪 history
+        // 7. 裁剪 history
         self.compact_history_if_needed().await;
 
         Ok(final_text)
     }
 
-    /// 执行工具，返回结果文本
-    async fn execute_tool(&self, name: &str, args: serde_json::Value) -> String {
-        let tool = match self.tools.iter().find(|t| t.name() == name) {
-            Some(t) => t,
-            None => return format!("[错误] 未知工具: {}", name),
-        };
+    /// 处理单个 tool call，返回写入 history 的 ToolResult 内容
+    ///
+    /// 依次经过：只读回合拒绝 → goal_update → 预验证 → P7-3 参数补全 → 重复调用拦截 →
+    /// 脏工作区提醒 → 审批 → 执行。
+    async fn handle_tool_call(
+        &mut self,
+        tc: &ToolCall,
+        tool_specs: &mut Vec<ToolSpec>,
+        sink: &mut TurnSink,
+    ) -> String {
+        // 只读回合：非 Read 类工具一律拒绝，不进入确认与执行
+        if let Some(rejection) = self.read_only_rejection(&tc.name) {
+            info!("只读回合拒绝工具: {}", tc.name);
+            return format!("[失败] {}", rejection);
+        }
 
-        match tool.execute(args, &self.policy).await {
-            Ok(result) => {
-                if result.success {
-                    result.output
-                } else {
-                    // 保留 output + error，让 LLM 自己判断
-                    let error = result.error.unwrap_or_else(|| "未知错误".to_string());
-                    if result.output.is_empty() {
-                        format!("[失败] {}", error)
+        // goal_update 只修改会话目标，由 Agent 直接处理（不经审批）
+        if tc.name == goal::GOAL_TOOL_NAME {
+            let (content, status) = self.apply_goal_update(&tc.arguments);
+            if let Some(status) = status {
+                sink.send(StreamEvent::GoalUpdate(status)).await;
+            }
+            return content;
+        }
+
+        // 预验证: 在确认前检查安全策略（避免确认后被拒绝）
+        if let Some(tool) = self.tools.iter().find(|t| t.name() == tc.name) {
+            if let Some(rejection) = tool.pre_validate(&tc.arguments, &self.policy) {
+                info!("工具预验证失败: {} - {}", tc.name, rejection);
+                return format!("[失败] {}", rejection);
+            }
+        }
+
+        // ─── P7-3: 动态 Schema 补充 ──────────────────────────────────────────
+        // 检测必填参数缺失（每轮每个工具只触发一次，避免死循环）
+        if !self.expanded_tools.contains(&tc.name) {
+            let missing = {
+                self.tools
+                    .iter()
+                    .find(|t| t.name() == tc.name)
+                    .map(|t| find_missing_required_params(&t.parameters_schema(), &tc.arguments))
+                    .unwrap_or_default()
+            };
+            if !missing.is_empty() {
+                self.expanded_tools.insert(tc.name.clone());
+                // 升级 MCP 工具为 L2 完整 schema（对内置工具无副作用）
+                if let Some(tool) = self.tools.iter_mut().find(|t| t.name() == tc.name) {
+                    tool.load_full_schema();
+                }
+                // 更新 tool_specs 供下一迭代使用
+                if let Some(tool) = self.tools.iter().find(|t| t.name() == tc.name) {
+                    let new_spec = tool.spec();
+                    if let Some(spec) = tool_specs.iter_mut().find(|s| s.name == tc.name) {
+                        *spec = new_spec;
                     } else {
-                        format!("[失败] {}\n[部分输出]\n{}", error, result.output)
+                        tool_specs.push(new_spec);
                     }
                 }
+                debug!(
+                    "P7-3: 工具 '{}' 缺少参数 {:?}，已注入完整 schema",
+                    tc.name, missing
+                );
+                return format!(
+                    "[参数缺失] 工具 '{}' 缺少必填参数: {}。完整参数说明已在工具列表中更新，请用正确参数重新调用。",
+                    tc.name,
+                    missing.join(", ")
+                );
             }
-            Err(e) => format!("[错误] {}", e),
         }
-    }
-
-    /// 构造 system prompt，实时读取语言配置后分发到对应语言版本
-    fn build_system_prompt(&self, memories: &[crate::memory::MemoryEntry]) -> String {
-        let lang = crate::config::Config::get_language();
-        match lang {
-            crate::i18n::Language::English => self.build_system_prompt_en(memories),
-            crate::i18n::Language::Chinese => self.build_system_prompt_zh(memories),
+        // ─── P7-3 结束 ────────────────────────────────────────────────────────
+
+        // 本轮重复调用：成功过的返回缓存，失败两次的直接短路（确认前处理，不打扰用户）
+        let ledger_key = self.ledger_key(&tc.name, &tc.arguments);
+        if let Some(content) = ledger_key.as_ref().and_then(|k| self.deduplicate(k)) {
+            info!("拦截重复工具调用: {}", tc.name);
+            let status = if content == REPEATED_FAILURE_MESSAGE {
+                ToolStatusKind::Failed(content.clone())
+            } else {
+                ToolStatusKind::Success(CACHED_PREFIX.to_string())
+            };
+            sink.send(StreamEvent::ToolStatus {
+                name: tc.name.clone(),
+                status,
+            })
+            .await;
+            return content;
         }
-    }
-
-    fn build_system_prompt_en(&self, memories: &[crate::memory::MemoryEntry]) -> String {
-        let mut parts = Vec::new();
 
-        // [0] Custom user context (identity file)
-        if let Some(identity) = &self.identity_context {
-            parts.push(format!("[Custom Context]\n{}", identity));
+        // 脏工作区提醒：Supervised 随确认提示展示，其他模式直接发给 UI
+        let mut notice = self.dirty_worktree_notice(&tc.name).await;
+        if self.approval.is_none() || !self.policy.requires_confirmation() {
+            if let Some(text) = notice.take() {
+                sink.send(StreamEvent::Notice(text)).await;
+            }
         }
 
-        // [1] Identity
-        parts.push("You are RRClaw, a safety-first AI assistant.".to_string());
-
-        // [2] Available tools (filtered by Phase 1.5 routing; empty list = show all)
-        if !self.tools.is_empty() {
-            let mut tools_desc = "You can use the following tools:\n".to_string();
+        // 审批策略（CLI 默认: Supervised 模式下询问用户）
+        if let Some(content) = self.check_approval(tc, notice).await {
+            return content;
+        }
 
-            for tool in &self.tools {
-                if tool.name().starts_with("mcp_") {
-                    continue;
-                }
-                let is_active = self.routed_tool_names.is_empty()
-                    || self.routed_tool_names.iter().any(|n| n == tool.name())
-                    || tool.name() == "skill";
-                if is_active {
-                    tools_desc.push_str(&format!("- {}: {}\n", tool.name(), tool.description()));
-                }
+        // 发送执行状态
+        let cmd_summary = if tc.name == "shell" {
+            tc.arguments
+                .get("command")
+                .and_then(|v| v.as_str())
+                .unwrap_or(&tc.name)
+                .to_string()
+        } else {
+            tc.name.clone()
+        };
+        sink.send(StreamEvent::ToolStatus {
+            name: tc.name.clone(),
+            status: ToolStatusKind::Running(cmd_summary),
+        })
+        .await;
+
+        info!("执行工具: {} args={}", tc.name, tc.arguments);
+        self.track_tool_call(&tc.name, &tc.arguments).await;
+        *self.tool_usage.entry(tc.name.clone()).or_default() += 1;
+        if tc.name == "skill" {
+            if let Some(name) = tc.arguments.get("name").and_then(|v| v.as_str()) {
+                self.record_skill_use(name, SkillTrigger::Tool);
             }
-
-            let mcp_tools: Vec<_> = self
-                .tools
-                .iter()
-                .filter(|t| t.name().starts_with("mcp_"))
-                .collect();
-            if !mcp_tools.is_empty() {
-                tools_desc.push_str("\n[MCP Tools] (available on demand; full parameter schema is loaded automatically on first call):\n");
-                for tool in &mcp_tools {
-                    tools_desc.push_str(&format!("- {}: {}\n", tool.name(), tool.description()));
+        }
+        let (result, kind, user_facing, suggested) =
+            self.execute_tool(&tc.name, tc.arguments.clone()).await;
+        self.tool_suggestions.extend(suggested);
+        debug!("工具结果: {}", truncate_str(&result, 200));
+
+        // MCP 工具首次调用后升级为 L2 完整 schema（下轮用户消息生效）
+        if tc.name.starts_with("mcp_") {
+            if let Some(tool) = self.tools.iter_mut().find(|t| t.name() == tc.name) {
+                if !tool.is_full_schema_loaded() {
+                    tool.load_full_schema();
+                    debug!("MCP 工具 '{}' 已升级为 L2 完整 schema", tc.name);
                 }
             }
-
-            parts.push(tools_desc);
         }
 
-        // [2.5] Available skills (L1 metadata, excluding SkillTool itself)
-        let display_skills: Vec<&SkillMeta> = self
-            .skills_meta
-            .iter()
-            .filter(|s| s.name != "skill")
-            .collect();
-        if !display_skills.is_empty() {
-            let mut skills_section =
-                "[Available Skills] (use the skill tool to load detailed instructions when needed)\n".to_string();
-            for skill in &display_skills {
-                skills_section.push_str(&format!("- {}: {}\n", skill.name, skill.description));
+        // 发送执行结果状态
+        if result.starts_with("[失败]") || result.starts_with("[错误]") {
+            sink.send(StreamEvent::ToolStatus {
+                name: tc.name.clone(),
+                status: ToolStatusKind::Failed(truncate_str(&result, 200)),
+            })
+            .await;
+        } else {
+            // 成功时显示首行预览
+            let summary = if result.len() > 80 {
+                let first_line = result.lines().next().unwrap_or("");
+                let preview = truncate_str(first_line, 60);
+                format!("{} (共{}字节)", preview, result.len())
+            } else {
+                truncate_str(&result, 80)
+            };
+            sink.send(StreamEvent::ToolStatus {
+                name: tc.name.clone(),
+                status: ToolStatusKind::Success(summary),
+            })
+            .await;
+            // 结构化输出：Channel 可据此渲染 diff / 表格等
+            if let Some(kind) = kind {
+                sink.send(StreamEvent::ToolOutput {
+                    name: tc.name.clone(),
+                    kind: kind.clone(),
+                    content: result.clone(),
+                })
+                .await;
+                self.rich_outputs.push(RichToolOutput {
+                    tool: tc.name.clone(),
+                    kind,
+                    content: result.clone(),
+                });
             }
-            parts.push(skills_section);
         }
+        // 用户视图：UI 按 tool_verbosity 决定展示摘要、完整输出或不展示
+        sink.send(StreamEvent::ToolFeedback {
+            name: tc.name.clone(),
+            summary: user_facing.clone(),
+            content: result.clone(),
+        })
+        .await;
+        self.tool_feedback.push(ToolFeedback {
+            tool: tc.name.clone(),
+            summary: user_facing,
+            content: result.clone(),
+        });
 
-        // [3] Security rules
-        let security_rules = match self.policy.autonomy {
-            AutonomyLevel::ReadOnly => "Read-only mode: do not attempt to call any tools.",
-            AutonomyLevel::Supervised => concat!(
-                "Supervised mode: call tools directly. ",
-                "The system will automatically prompt the user for confirmation before execution. ",
-                "Do not ask for confirmation in your text — just issue the tool call."
-            ),
-            AutonomyLevel::Full => {
-                "Full mode: you can execute tools autonomously within the allowed-commands list."
+        // ─── Prompt Injection 检测 ───────────────────────────────────────────
+        // 只检测外部数据工具（shell/file_read/git/http_request/read_url/web_search）；
+        // 内部工具（memory_*/skill/self_info/config）返回受控内容，跳过检测
+        let final_content = if self.policy.injection_check && needs_injection_check(&tc.name) {
+            let injection = crate::security::injection::check_tool_result(&result);
+            if let Some(ref sev) = injection.severity {
+                info!(
+                    tool = %tc.name,
+                    severity = ?sev,
+                    reason = ?injection.reason,
+                    "Prompt injection detected in tool result"
+                );
             }
+            injection.sanitized
+        } else {
+            result
         };
-        parts.push(security_rules.to_string());
+        // ─── 检测结束 ─────────────────────────────────────────────────────────
 
-        // [4] Memory context
-        if !memories.is_empty() {
-            let mut memory_section = "[Relevant Memories]\n".to_string();
-            for entry in memories {
-                memory_section.push_str(&format!("- {}\n", entry.content));
-            }
-            parts.push(memory_section);
-        }
-
-        // [4.5] Routed skill L2 behavior guide (Phase 1 result, reset each turn)
-        if let Some(skill_content) = &self.routed_skill_content {
-            parts.push(format!("[Behavior Guide]\n{}", skill_content));
-        }
-
-        // [4.6] Routine execution rules (only in routine mode)
-        if let Some(name) = &self.routine_name {
-            parts.push(format!(
-                "[Routine Execution Rules]\n\
-                 You are executing scheduled task '{name}'. This is an automated task with no user interaction.\n\
-                 - If the message starts with [Previously successful approach], try that approach first\n\
-                 - After completing the task successfully, record the effective method with memory_store:\n\
-                 \x20 - key: \"routine:{name}:approach\"\n\
-                 \x20 - category: \"custom\"\n\
-                 \x20 - content: describe the successful method (URL, headers, data extraction path, etc.)\n\
-                 - If you find a better method, overwrite the existing record\n\
-                 - Do not update the record on failure",
-                name = name,
-            ));
+        match ledger_key {
+            Some(key) => self.call_ledger.record(key, &final_content),
+            None => self.call_ledger.invalidate(),
         }
-
-        // [5] Environment info
-        let workspace = self.policy.workspace_dir.display();
-        let env_info = format!(
-            "Working directory: {}\nCurrent time: {}",
-            workspace,
-            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-        );
-        parts.push(env_info);
-
-        // [6] Decision principles
-        parts.push(concat!(
-            "[Decision Principles]\n",
-            "1. Check before acting: use self_info to query uncertain info (paths, config, capabilities) — don't guess\n",
-            "2. Ask when stuck: if you can't find or infer the answer, ask the user directly\n",
-            "3. Explain intent: briefly explain why you need a tool before calling it\n",
-            "4. Reflect on failure: analyze root cause before retrying\n",
-            "   - 1st failure: analyze cause, try a different approach\n",
-            "   - 2nd failure: explain the situation to the user and ask for guidance\n",
-            "   - Don't attempt the same goal more than 3 times\n",
-            "5. Reply in the user's language\n",
-            "6. Use memory: store user preferences with memory_store when told; use memory_recall when unsure if something was discussed before\n",
-            "7. When HTTP requests are blocked by SSRF protection: explain the situation to the user and ask if they want to add the address to the allowlist. After agreement, use the config tool to add it (e.g., set security.http_allowed_hosts to [\"localhost\"]), then retry",
-        ).to_string());
-
-        parts.join("\n\n")
+        final_content
     }
 
-    fn build_system_prompt_zh(&self, memories: &[crate::memory::MemoryEntry]) -> String {
-        let mut parts = Vec::new();
+    /// 可缓存调用的账本键（未知工具或 `cacheable() == false` 时为 None）
+    fn ledger_key(&self, name: &str, args: &serde_json::Value) -> Option<CallKey> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == name)
+            .filter(|t| t.cacheable(args))
+            .map(|_| CallLedger::key(name, args))
+    }
 
-        // [0] 用户定制上下文（身份文件）
-        if let Some(identity) = &self.identity_context {
-            parts.push(format!("[用户定制上下文]\n{}", identity));
+    /// 查询账本：需要跳过执行时返回替代的工具结果
+    fn deduplicate(&self, key: &CallKey) -> Option<String> {
+        match self.call_ledger.check(key) {
+            LedgerCheck::Execute => None,
+            LedgerCheck::Cached(content) => Some(content),
+            LedgerCheck::ShortCircuit => Some(REPEATED_FAILURE_MESSAGE.to_string()),
         }
+    }
 
-        // [1] 身份描述
-        parts.push("你是 RRClaw，一个安全优先的 AI 助手。".to_string());
-
-        // [2] 可用工具描述（根据 Phase 1.5 路由结果过滤；空列表 = 显示所有）
-        if !self.tools.is_empty() {
-            let mut tools_desc = "你可以使用以下工具:\n".to_string();
-
-            for tool in &self.tools {
-                if tool.name().starts_with("mcp_") {
-                    continue;
-                }
-                let is_active = self.routed_tool_names.is_empty()
-                    || self.routed_tool_names.iter().any(|n| n == tool.name())
-                    || tool.name() == "skill";
-                if is_active {
-                    tools_desc.push_str(&format!("- {}: {}\n", tool.name(), tool.description()));
-                }
+    /// 处理 `goal_update`，返回 (LLM 可见结果, 成功时给用户的状态行)
+    fn apply_goal_update(&mut self, args: &serde_json::Value) -> (String, Option<String>) {
+        match goal::apply_update(self.goal.as_mut(), args) {
+            Ok(status) => {
+                crate::metrics::record_tool(goal::GOAL_TOOL_NAME, "success");
+                info!("会话目标更新: {}", status);
+                ("目标进度已更新".to_string(), Some(status))
             }
-
-            let mcp_tools: Vec<_> = self
-                .tools
-                .iter()
-                .filter(|t| t.name().starts_with("mcp_"))
-                .collect();
-            if !mcp_tools.is_empty() {
-                tools_desc
-                    .push_str("\n[MCP 工具]（需要时可用，首次调用后自动获取完整参数说明）:\n");
-                for tool in &mcp_tools {
-                    tools_desc.push_str(&format!("- {}: {}\n", tool.name(), tool.description()));
-                }
+            Err(e) => {
+                crate::metrics::record_tool(goal::GOAL_TOOL_NAME, "failure");
+                (format!("[失败] {}", e), None)
             }
-
-            parts.push(tools_desc);
         }
+    }
 
-        // [2.5] 可用技能列表（L1 元数据，仅当有 skills 时注入）
-        let display_skills: Vec<&SkillMeta> = self
-            .skills_meta
-            .iter()
-            .filter(|s| s.name != "skill")
-            .collect();
-        if !display_skills.is_empty() {
-            let mut skills_section = "[可用技能]（需要时用 skill 工具加载详细指令）\n".to_string();
-            for skill in &display_skills {
-                skills_section.push_str(&format!("- {}: {}\n", skill.name, skill.description));
+    /// 执行工具，返回结果文本 + 结构化元数据（仅成功时）+ 用户摘要
+    ///
+    /// 文本部分即 LLM 可见内容，元数据和摘要只给 Channel 渲染，不影响 LLM 输入。
+    async fn execute_tool(
+        &self,
+        name: &str,
+        args: serde_json::Value,
+    ) -> (String, Option<ToolOutputKind>, Option<String>, Vec<String>) {
+        let tool = match self.tools.iter().find(|t| t.name() == name) {
+            Some(t) => t,
+            None => {
+                crate::metrics::record_tool(name, "unknown");
+                return (format!("[错误] 未知工具: {}", name), None, None, Vec::new());
             }
-            parts.push(skills_section);
-        }
-
-        // [3] 安全规则
-        let security_rules = match self.policy.autonomy {
-            AutonomyLevel::ReadOnly => "当前为只读模式，不要尝试执行任何工具。",
-            AutonomyLevel::Supervised => concat!(
-                "当前为 Supervised 模式。你应该直接调用工具，系统会自动弹出确认提示让用户决定是否执行。",
-                "不要在文本中请求用户确认，直接发起 tool call 即可。"
-            ),
-            AutonomyLevel::Full => "你可以自主执行工具，但须遵守白名单限制。",
         };
-        parts.push(security_rules.to_string());
 
-        // [4] 记忆上下文
-        if !memories.is_empty() {
-            let mut memory_section = "[相关记忆]\n".to_string();
-            for entry in memories {
-                memory_section.push_str(&format!("- {}\n", entry.content));
+        match tool.execute(args, &self.policy).await {
+            Ok(result) => {
+                crate::metrics::record_tool(
+                    name,
+                    if result.success { "success" } else { "failure" },
+                );
+                let metadata = result.metadata_line();
+                let user_facing = result.user_facing;
+                let suggested = result.suggestions;
+                let (text, kind) = if result.success {
+                    (result.output, result.kind)
+                } else {
+                    // 保留 output + error，让 LLM 自己判断
+                    let error = result.error.unwrap_or_else(|| "未知错误".to_string());
+                    if result.output.is_empty() {
+                        (format!("[失败] {}", error), None)
+                    } else {
+                        (
+                            format!("[失败] {}\n[部分输出]\n{}", error, result.output),
+                            None,
+                        )
+                    }
+                };
+                // 退出码、耗时附在末尾，供 LLM 诊断
+                let text = match metadata {
+                    Some(line) => format!("{}\n{}", text.trim_end(), line),
+                    None => text,
+                };
+                (text, kind, user_facing, suggested)
+            }
+            Err(e) => {
+                crate::metrics::record_tool(name, "error");
+                (format!("[错误] {}", e), None, None, Vec::new())
             }
-            parts.push(memory_section);
-        }
-
-        // [4.5] 已路由的 skill L2 行为指南（Phase 1 结果，每轮重置）
-        if let Some(skill_content) = &self.routed_skill_content {
-            parts.push(format!("[行为指南]\n{}", skill_content));
-        }
-
-        // [4.6] Routine 执行规范（仅在 Routine 模式下注入）
-        if let Some(name) = &self.routine_name {
-            parts.push(format!(
-                "[Routine 执行规范]\n\
-                 你正在执行定时任务 '{name}'，这是一个自动化任务，不会有用户交互。\n\
-                 - 如果消息前缀有 [历史成功方法参考]，优先尝试该方法\n\
-                 - 成功完成任务后，用 memory_store 记录有效方法：\n\
-                 \x20 - key: \"routine:{name}:approach\"\n\
-                 \x20 - category: \"custom\"\n\
-                 \x20 - content: 描述成功方法（使用的 URL、headers、数据提取路径等）\n\
-                 - 如果发现更好的方法，直接覆盖旧记录\n\
-                 - 失败时不要更新记录",
-                name = name,
-            ));
         }
+    }
 
-        // [5] 环境信息
-        let workspace = self.policy.workspace_dir.display();
-        let env_info = format!(
-            "工作目录: {}\n当前时间: {}",
-            workspace,
-            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-        );
-        parts.push(env_info);
+    /// 构造 system prompt，实时读取语言配置
+    fn build_system_prompt(&self, memories: &[crate::memory::MemoryEntry]) -> String {
+        self.system_prompt_in(crate::config::Config::get_language(), memories)
+    }
 
-        // [6] 决策原则
-        parts.push(concat!(
-            "[决策原则]\n",
-            "1. 先查后做: 不确定的信息（路径、配置、能力）先用 self_info 工具查询，不要猜测\n",
-            "2. 不知道就问: 如果查不到也推理不出，直接问用户，不要盲目尝试\n",
-            "3. 说明意图: 调用工具前简短说明为什么需要这个工具\n",
-            "4. 失败时反思: 工具失败后先分析原因，再决定下一步\n",
-            "   - 第 1 次失败: 分析原因，换一种方式\n",
-            "   - 第 2 次失败: 向用户说明情况，询问建议\n",
-            "   - 不要同一个目标尝试超过 3 次\n",
-            "5. 用中文回复，除非用户使用其他语言\n",
-            "6. 善用记忆: 当用户告知偏好或重要信息时，用 memory_store 保存；不确定之前是否讨论过时，用 memory_recall 检索\n",
-            "7. HTTP 请求被 SSRF 防护阻止时: 向用户说明情况，询问是否要将该地址加入白名单。用户同意后，用 config 工具添加（如 /config set security.http_allowed_hosts 添加 [\"localhost\"]），然后重新尝试请求",
-        ).to_string());
+    fn system_prompt_in(
+        &self,
+        lang: crate::i18n::Language,
+        memories: &[crate::memory::MemoryEntry],
+    ) -> String {
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        prompts::system_prompt(&self.prompt_context(lang, memories, now))
+    }
 
-        parts.join("\n\n")
+    /// 从当前状态收集 system prompt 的输入（模板与拼接见 `prompts`）
+    fn prompt_context<'a>(
+        &'a self,
+        lang: crate::i18n::Language,
+        memories: &'a [crate::memory::MemoryEntry],
+        now: String,
+    ) -> SystemPromptContext<'a> {
+        let item = |tool: &'a dyn Tool| PromptItem {
+            name: tool.name(),
+            description: tool.description(),
+        };
+        // 内置工具按 Phase 1.5 路由结果过滤（空列表 = 显示所有），MCP 工具单独列出
+        let tools = (!self.tools.is_empty()).then(|| ToolsSection {
+            tools: self
+                .tools
+                .iter()
+                .filter(|t| !t.name().starts_with("mcp_") && self.is_exposed(t.as_ref()))
+                .filter(|t| {
+                    self.routed_tool_names.is_empty()
+                        || self.routed_tool_names.iter().any(|n| n == t.name())
+                        || t.name() == "skill"
+                })
+                .map(|t| item(t.as_ref()))
+                .collect(),
+            mcp_tools: self
+                .tools
+                .iter()
+                .filter(|t| t.name().starts_with("mcp_") && self.is_exposed(t.as_ref()))
+                .map(|t| item(t.as_ref()))
+                .collect(),
+            omitted: self.omitted_tool_count,
+        });
+        let mode = if self.safe_mode {
+            PromptMode::Safe
+        } else if self.read_only {
+            PromptMode::Ask
+        } else {
+            PromptMode::Autonomy(self.policy.autonomy.clone())
+        };
+        SystemPromptContext {
+            lang,
+            prefix: self.system_prompt_prefix.as_deref(),
+            identity: self.identity_context.as_deref(),
+            override_text: self.system_prompt_override.as_deref(),
+            tools,
+            // L1 元数据，不含 SkillTool 本身
+            skills: self
+                .skills_meta
+                .iter()
+                .filter(|s| s.name != "skill")
+                .map(|s| PromptItem {
+                    name: &s.name,
+                    description: &s.description,
+                })
+                .collect(),
+            mode,
+            goal: self.goal.as_ref(),
+            memories: memories.iter().map(|m| m.content.as_str()).collect(),
+            behavior_guide: self.routed_skill_content.as_deref(),
+            routine: self.routine_name.as_deref(),
+            workspace: &self.policy.workspace_dir,
+            now,
+            quick_replies: self.quick_replies,
+        }
     }
 
     /// 构造本轮对话的工具 spec 列表（传给 Provider）
@@ -1288,27 +1868,136 @@ impl Agent {
             return self
                 .tools
                 .iter()
-                .filter(|t| t.name() == tool_name)
+                .filter(|t| t.name() == tool_name && self.is_exposed(t.as_ref()))
                 .map(|t| t.spec())
                 .collect();
         }
 
         // Priority 2: Phase 1.5 关键词路由结果
-        if !self.routed_tool_names.is_empty() {
+        let mut specs: Vec<ToolSpec> = if !self.routed_tool_names.is_empty() {
             debug!("工具路由激活: {:?}", self.routed_tool_names);
-            return self
-                .tools
+            self.tools
                 .iter()
                 .filter(|t| {
                     self.routed_tool_names.iter().any(|n| n == t.name()) || t.name() == "skill"
                     // skill 工具始终可用（C 辅助路径）
                 })
+                .filter(|t| self.is_exposed(t.as_ref()))
                 .map(|t| t.spec())
-                .collect();
+                .collect()
+        } else {
+            // Fallback: 所有工具（无关键词匹配）
+            self.tools
+                .iter()
+                .filter(|t| self.is_exposed(t.as_ref()))
+                .map(|t| t.spec())
+                .collect()
+        };
+
+        // 有活动目标时附带 goal_update（安全模式 / 只读回合不带）
+        if self.goal.is_some() && !self.safe_mode && !self.read_only {
+            specs.push(goal::tool_spec());
+        }
+        specs
+    }
+
+    /// 是否为优先工具：Phase 1.5 路由命中、skill、mcp_list_tools（裁剪时最先保留）
+    fn is_priority_tool(&self, name: &str) -> bool {
+        name == "skill"
+            || name == goal::GOAL_TOOL_NAME
+            || name == crate::mcp::catalog::LIST_TOOLS_NAME
+            || self.routed_tool_names.iter().any(|n| n == name)
+    }
+
+    /// 按 Provider 限制（`max_tools` / `max_tool_schema_bytes`）裁剪工具 spec
+    ///
+    /// 未超限时原样返回；超限时按 优先工具 → 内置工具 → MCP 工具（调用次数降序）排序后截断。
+    /// 返回 (保留的 spec, 丢弃数量)。
+    fn apply_tool_limits(&self, specs: Vec<ToolSpec>) -> (Vec<ToolSpec>, usize) {
+        let sizes: Vec<usize> = specs
+            .iter()
+            .map(|s| serde_json::to_vec(s).map(|v| v.len()).unwrap_or(0))
+            .collect();
+        let fits_count = self
+            .tool_limits
+            .max_tools
+            .is_none_or(|max| specs.len() <= max);
+        let fits_bytes = self
+            .tool_limits
+            .max_tool_schema_bytes
+            .is_none_or(|max| sizes.iter().sum::<usize>() <= max);
+        if fits_count && fits_bytes {
+            return (specs, 0);
         }
 
-        // Fallback: 所有工具（无关键词匹配）
-        self.tools.iter().map(|t| t.spec()).collect()
+        let mut ranked: Vec<(ToolSpec, usize)> = specs.into_iter().zip(sizes).collect();
+        // sort_by_key 是稳定排序，同级保持原有顺序
+        ranked.sort_by_key(|(spec, _)| {
+            if self.is_priority_tool(&spec.name) {
+                (0, std::cmp::Reverse(0))
+            } else if !spec.name.starts_with("mcp_") {
+                (1, std::cmp::Reverse(0))
+            } else {
+                let used = self.tool_usage.get(&spec.name).copied().unwrap_or(0);
+                (2, std::cmp::Reverse(used))
+            }
+        });
+
+        let total = ranked.len();
+        let mut kept = Vec::new();
+        let mut used_bytes = 0usize;
+        for (spec, size) in ranked {
+            if self
+                .tool_limits
+                .max_tools
+                .is_some_and(|max| kept.len() >= max)
+            {
+                break;
+            }
+            if self
+                .tool_limits
+                .max_tool_schema_bytes
+                .is_some_and(|max| used_bytes + size > max)
+            {
+                continue;
+            }
+            used_bytes += size;
+            kept.push(spec);
+        }
+        let omitted = total - kept.len();
+        info!(
+            "工具数超出 Provider 限制，保留 {} 个，省略 {} 个",
+            kept.len(),
+            omitted
+        );
+        (kept, omitted)
+    }
+
+    /// Provider 是否因工具过多 / 过大拒绝了请求
+    fn should_retry_with_fewer_tools(&self, err: &color_eyre::eyre::Report) -> bool {
+        crate::providers::is_too_many_tools_error(&format!("{:#}", err))
+    }
+
+    /// 只保留优先工具（无 Phase 1.5 路由时保留内置工具），用于被拒后的重试
+    fn reduce_to_priority_tools(&mut self, specs: Vec<ToolSpec>) -> Vec<ToolSpec> {
+        let before = specs.len();
+        let reduced: Vec<ToolSpec> = specs
+            .into_iter()
+            .filter(|s| {
+                if self.routed_tool_names.is_empty() {
+                    !s.name.starts_with("mcp_") || s.name == crate::mcp::catalog::LIST_TOOLS_NAME
+                } else {
+                    self.is_priority_tool(&s.name)
+                }
+            })
+            .collect();
+        warn!(
+            "Provider 拒绝了 {} 个工具，仅保留 {} 个优先工具重试",
+            before,
+            reduced.len()
+        );
+        self.omitted_tool_count += before - reduced.len();
+        reduced
     }
 
     /// 预处理用户输入，尝试自动路由到专用工具
@@ -1366,22 +2055,59 @@ impl Agent {
         }
     }
 
-    /// 压缩 history：超过阈值时用 LLM 摘要替代早期消息
+    /// 按 token 触发压缩的阈值（上下文窗口的 `COMPACT_TOKEN_RATIO`）
+    fn compact_token_budget(&self) -> usize {
+        (self.context_window as f64 * COMPACT_TOKEN_RATIO) as usize
+    }
+
+    /// 压缩 history：条数或估算 token 数超过阈值时用 LLM 摘要替代早期消息
     /// 如果 LLM 摘要失败，回退到旧的硬截断策略
     async fn compact_history_if_needed(&mut self) {
-        if self.history.len() < COMPACT_THRESHOLD {
+        let history_tokens: usize = self
+            .history
+            .iter()
+            .map(tokens::estimate_message_tokens)
+            .sum();
+        // 条数触发：固定压缩前 COMPACT_WINDOW 条；token 触发：压缩前 3/4，保留最近的消息
+        let window = if self.history.len() >= COMPACT_THRESHOLD {
+            COMPACT_WINDOW
+        } else if self.history.len() >= 4 && history_tokens >= self.compact_token_budget() {
+            self.history.len() * 3 / 4
+        } else {
             return;
-        }
+        };
 
         tracing::info!(
-            "history 达到 {} 条，触发压缩（窗口: {} 条）",
+            "history 达到 {} 条 / ~{} tokens（上下文窗口 {}），触发压缩（窗口: {} 条）",
             self.history.len(),
-            COMPACT_WINDOW
+            history_tokens,
+            self.context_window,
+            window
         );
+        self.compact_front(window).await;
+    }
+
+    /// Provider 报上下文超限后立即压缩（不看阈值）：摘要前 3/4，保留最近的消息
+    ///
+    /// 返回 history 是否变短（没有可压缩的内容时为 false，无需重试）
+    async fn force_compact_history(&mut self) -> bool {
+        let before = self.history.len();
+        if before < 2 {
+            return false;
+        }
+        tracing::warn!("上下文超出模型限制，立即压缩 history（{} 条）", before);
+        self.compact_front((before * 3 / 4).max(1)).await;
+        self.history.len() < before
+    }
 
-        // 取前 COMPACT_WINDOW 条作为压缩对象
+    /// 用 LLM 摘要替换 history 前 `window` 条（不截断工具调用对）；摘要失败时硬截断
+    async fn compact_front(&mut self, window: usize) {
+        // 取前 window 条作为压缩对象
         // 但要确保不截断 AssistantToolCalls + ToolResult 对
-        let window_end = find_safe_window_end(&self.history, COMPACT_WINDOW);
+        let window_end = find_safe_window_end(&self.history, window);
+        if window_end == 0 {
+            return;
+        }
         let to_compress = &self.history[..window_end];
 
         match self.summarize_history(to_compress).await {
@@ -1390,8 +2116,12 @@ impl Agent {
                 // 用摘要消息替换被压缩的部分
                 let summary_msg = ConversationMessage::Chat(ChatMessage {
                     role: "system".to_string(),
-                    content: format!("[对话摘要 - 早期上下文]\n{}", summary),
+                    content: format!("{}\n{}", prompts::SUMMARY_MESSAGE_HEADER, summary),
                     reasoning_content: None,
+                    // 沿用被压缩的最后一个 Turn，保持 Turn 编号递增
+                    turn: window_end
+                        .checked_sub(1)
+                        .map_or(0, |last| self.history[last].turn()),
                 });
                 let remaining = self.history[window_end..].to_vec();
                 let remaining_len = remaining.len();
@@ -1425,25 +2155,19 @@ impl Agent {
             transcript
         };
 
-        let summary_prompt = format!(
-            "请将以下对话历史压缩成简洁摘要（不超过 {} 字符）。\n\
-             保留：用户的核心需求、重要决策、已解决的问题、关键信息（路径/命令/配置）。\n\
-             忽略：闲聊、重复内容、工具执行的详细输出。\n\
-             用中文输出，以「对话摘要：」开头。\n\n\
-             ---\n{}\n---",
-            COMPACT_SUMMARY_MAX_CHARS, transcript_truncated
-        );
+        let summary_prompt =
+            prompts::summary_request(COMPACT_SUMMARY_MAX_CHARS, &transcript_truncated);
 
         let summary_messages = vec![ConversationMessage::Chat(ChatMessage {
             role: "user".to_string(),
             content: summary_prompt,
             reasoning_content: None,
+            turn: 0,
         })];
 
-        // 直接调用 provider，不传 tools（摘要不需要 tool call）
+        // 不传 tools（摘要不需要 tool call）
         let response = self
-            .provider
-            .chat_with_tools(&summary_messages, &[], &self.model, 0.3)
+            .chat_aux(self.summary_model.as_ref(), &summary_messages, 0.3, "摘要")
             .await?;
 
         let summary = response.text.unwrap_or_default();
@@ -1456,11 +2180,19 @@ impl Agent {
     }
 }
 
-/// 找到安全的压缩窗口终点：不截断 AssistantToolCalls + ToolResult 对
-/// 从 ideal_end 向前找，直到找到一个安全切割点
+/// 找到安全的压缩窗口终点：只在 Turn 边界切割，不截断 AssistantToolCalls + ToolResult 对
+/// 取不超过 ideal_end 的最后一个 Turn 边界
 fn find_safe_window_end(history: &[ConversationMessage], ideal_end: usize) -> usize {
     let end = ideal_end.min(history.len());
-    // 从 end 向前找第一个 Chat 消息（安全切割点）
+    if let Some(boundary) = turns::turn_spans(history)
+        .iter()
+        .map(|span| span.end)
+        .filter(|&b| b <= end)
+        .max()
+    {
+        return boundary;
+    }
+    // 单个 Turn 超过窗口（长工具链）：退回到 Chat 消息之后切割
     for i in (0..end).rev() {
         if matches!(history[i], ConversationMessage::Chat(_)) {
             return i + 1;
@@ -1471,53 +2203,69 @@ fn find_safe_window_end(history: &[ConversationMessage], ideal_end: usize) -> us
 }
 
 /// 将 history 格式化为摘要 prompt 用的可读文本
+/// 按 Turn 分组，有工具调用的 Turn 末尾附上调用次数和失败次数
 fn format_history_for_summary(messages: &[ConversationMessage]) -> String {
     let mut out = String::new();
-    for msg in messages {
-        match msg {
-            ConversationMessage::Chat(cm) => {
-                if cm.role == "system" {
-                    continue; // 跳过 system 消息
+    for span in turns::turn_spans(messages) {
+        let mut tool_calls_count = 0;
+        let mut failed_count = 0;
+        for msg in &messages[span] {
+            match msg {
+                ConversationMessage::Chat(cm) => {
+                    if cm.role == "system" {
+                        continue; // 跳过 system 消息
+                    }
+                    let role_label = if cm.role == "user" {
+                        "用户"
+                    } else {
+                        "助手"
+                    };
+                    let content = if cm.content.len() > 500 {
+                        truncate_str(&cm.content, 500)
+                    } else {
+                        cm.content.clone()
+                    };
+                    out.push_str(&format!("[{}]: {}\n\n", role_label, content));
                 }
-                let role_label = if cm.role == "user" {
-                    "用户"
-                } else {
-                    "助手"
-                };
-                let content = if cm.content.len() > 500 {
-                    truncate_str(&cm.content, 500)
-                } else {
-                    cm.content.clone()
-                };
-                out.push_str(&format!("[{}]: {}\n\n", role_label, content));
-            }
-            ConversationMessage::AssistantToolCalls {
-                text, tool_calls, ..
-            } => {
-                if let Some(t) = text {
-                    if !t.is_empty() {
-                        out.push_str(&format!("[助手]: {}\n", t));
+                ConversationMessage::AssistantToolCalls {
+                    text, tool_calls, ..
+                } => {
+                    if let Some(t) = text {
+                        if !t.is_empty() {
+                            out.push_str(&format!("[助手]: {}\n", t));
+                        }
                     }
+                    tool_calls_count += tool_calls.len();
+                    let tool_names: Vec<&str> =
+                        tool_calls.iter().map(|tc| tc.name.as_str()).collect();
+                    out.push_str(&format!("[工具调用]: {}\n\n", tool_names.join(", ")));
+                }
+                ConversationMessage::ToolResult { content, .. } => {
+                    if turns::is_failed_tool_result(content) {
+                        failed_count += 1;
+                    }
+                    let preview = if content.len() > 200 {
+                        truncate_str(content, 200)
+                    } else {
+                        content.clone()
+                    };
+                    out.push_str(&format!("[工具结果]: {}\n\n", preview));
                 }
-                let tool_names: Vec<&str> = tool_calls.iter().map(|tc| tc.name.as_str()).collect();
-                out.push_str(&format!("[工具调用]: {}\n\n", tool_names.join(", ")));
-            }
-            ConversationMessage::ToolResult { content, .. } => {
-                let preview = if content.len() > 200 {
-                    truncate_str(content, 200)
-                } else {
-                    content.clone()
-                };
-                out.push_str(&format!("[工具结果]: {}\n\n", preview));
             }
         }
+        if tool_calls_count > 0 {
+            out.push_str(&format!(
+                "[本轮工具]: {} 次调用，{} 次失败\n\n",
+                tool_calls_count, failed_count
+            ));
+        }
     }
     out
 }
 
 /// 判断工具结果是否需要注入检测
 ///
-/// 外部数据工具（shell、file_read、git、http_request）需要检测，
+/// 外部数据工具（shell、file_read、grep、git、http_request、read_url、web_search）需要检测，
 /// 因为其内容来自外部/用户环境，存在恶意构造的可能。
 ///
 /// 内部工具（memory_*、skill、self_info、config）返回的是系统自身受控内容，
@@ -1527,7 +2275,16 @@ fn format_history_for_summary(messages: &[ConversationMessage]) -> String {
 fn needs_injection_check(tool_name: &str) -> bool {
     matches!(
         tool_name,
-        "shell" | "file_read" | "file_write" | "git" | "http_request"
+        "shell"
+            | "file_read"
+            | "grep"
+            | "file_write"
+            | "git"
+            | "git_commit"
+            | "http_request"
+            | "read_url"
+            | "web_search"
+            | "document_read"
     )
 }
 
@@ -1577,9 +2334,20 @@ fn truncate_str(s: &str, max_bytes: usize) -> String {
     format!("{}...(共{}字节)", &s[..end], s.len())
 }
 
+/// AssistantToolCalls 块结束：仍有 tool call 没有结果时，整块（调用 + 已有结果）丢弃
+fn close_tool_block(cleaned: &mut Vec<ConversationMessage>, block: Option<(usize, Vec<String>)>) {
+    if let Some((start, open)) = block {
+        if !open.is_empty() {
+            debug!("清理结果不完整的 tool call: {:?}", open);
+            cleaned.truncate(start);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::agent::approval::Decision;
     use crate::memory::MemoryEntry;
     use crate::providers::{ChatResponse, ToolCall};
     use crate::skills::SkillSource;
@@ -1606,7 +2374,7 @@ mod tests {
             _messages: &[ConversationMessage],
             _tools: &[ToolSpec],
             _model: &str,
-            _temperature: f64,
+            _params: GenerationParams,
         ) -> Result<ChatResponse> {
             let mut responses = self.responses.lock().unwrap();
             if responses.is_empty() {
@@ -1614,6 +2382,7 @@ mod tests {
                     text: Some("默认回复".to_string()),
                     reasoning_content: None,
                     tool_calls: vec![],
+                    provenance: None,
                 })
             } else {
                 Ok(responses.remove(0))
@@ -1691,11 +2460,13 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             ChatResponse {
                 text: Some("你好！".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -1724,6 +2495,7 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // Phase 2 first response: tool call
             ChatResponse {
@@ -1734,23 +2506,511 @@ mod tests {
                     name: "shell".to_string(),
                     arguments: serde_json::json!({"command": "ls"}),
                 }],
+                provenance: None,
+            },
+            // Phase 2 second response: final text
+            ChatResponse {
+                text: Some("目录中有 file.txt".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+
+        let mock_tool = MockTool {
+            tool_name: "shell".to_string(),
+            result: "file.txt".to_string(),
+        };
+
+        let mut agent = Agent::new(
+            Box::new(provider),
+            vec![Box::new(mock_tool)],
+            Box::new(MockMemory),
+            test_policy(),
+            "test".to_string(),
+            "http://test".to_string(),
+            "test-model".to_string(),
+            0.7,
+            vec![],
+            None,
+        );
+
+        let reply = agent.process_message("列出文件").await.unwrap();
+        assert_eq!(reply, "目录中有 file.txt");
+    }
+
+    fn routed_direct() -> ChatResponse {
+        ChatResponse {
+            text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        }
+    }
+
+    fn tool_call(id: &str, name: &str, arguments: serde_json::Value) -> ChatResponse {
+        ChatResponse {
+            text: Some("让我看看".to_string()),
+            reasoning_content: Some("需要先调用工具".to_string()),
+            tool_calls: vec![ToolCall {
+                id: id.to_string(),
+                name: name.to_string(),
+                arguments,
+            }],
+            provenance: None,
+        }
+    }
+
+    /// 同一脚本分别经 `process_message` 与 `process_message_stream` 执行，返回 (回复, history)
+    async fn run_script_both_ways(
+        script: impl Fn() -> Vec<ChatResponse>,
+        messages: &[&str],
+    ) -> [(Vec<String>, serde_json::Value); 2] {
+        let agent = |responses| {
+            Agent::new(
+                Box::new(MockProvider::new(responses)),
+                vec![Box::new(MockTool {
+                    tool_name: "shell".to_string(),
+                    result: "file.txt".to_string(),
+                })],
+                Box::new(MockMemory),
+                test_policy(),
+                "test".to_string(),
+                "http://test".to_string(),
+                "test-model".to_string(),
+                0.7,
+                vec![],
+                None,
+            )
+        };
+
+        let mut plain = agent(script());
+        let mut plain_replies = Vec::new();
+        for msg in messages {
+            plain_replies.push(plain.process_message(msg).await.unwrap());
+        }
+
+        let mut streaming = agent(script());
+        let mut stream_replies = Vec::new();
+        for msg in messages {
+            let (tx, mut rx) = crate::providers::stream_channel();
+            let drain = tokio::spawn(async move { while rx.recv().await.is_some() {} });
+            stream_replies.push(streaming.process_message_stream(msg, tx).await.unwrap());
+            drain.await.unwrap();
+        }
+
+        [
+            (
+                plain_replies,
+                serde_json::to_value(plain.history()).unwrap(),
+            ),
+            (
+                stream_replies,
+                serde_json::to_value(streaming.history()).unwrap(),
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn streaming_and_plain_turns_build_identical_history() {
+        let script = || {
+            vec![
+                routed_direct(),
+                tool_call("call_1", "shell", serde_json::json!({"command": "ls"})),
+                // 重复调用与未知工具都走同一套处理
+                tool_call("call_2", "shell", serde_json::json!({"command": "ls"})),
+                tool_call("call_3", "no_such_tool", serde_json::json!({})),
+                ChatResponse {
+                    text: Some("目录中有 file.txt".to_string()),
+                    reasoning_content: Some("整理结果".to_string()),
+                    tool_calls: vec![],
+                    provenance: None,
+                },
+                // 第二轮：清理旧 reasoning 后直接回复
+                routed_direct(),
+                ChatResponse {
+                    text: Some("好的".to_string()),
+                    reasoning_content: None,
+                    tool_calls: vec![],
+                    provenance: None,
+                },
+            ]
+        };
+
+        let [(plain_replies, plain), (stream_replies, streaming)] =
+            run_script_both_ways(script, &["列出文件", "谢谢"]).await;
+        assert_eq!(plain_replies, vec!["目录中有 file.txt", "好的"]);
+        assert_eq!(plain_replies, stream_replies);
+        assert_eq!(plain, streaming);
+        assert_eq!(plain.as_array().unwrap().len(), 10);
+    }
+
+    #[tokio::test]
+    async fn streaming_and_plain_clarification_leave_history_untouched() {
+        let script = || {
+            vec![ChatResponse {
+                text: Some(
+                    r#"{"skills": [], "direct": false, "question": "要列出哪个目录？"}"#
+                        .to_string(),
+                ),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            }]
+        };
+
+        let [(plain_replies, plain), (stream_replies, streaming)] =
+            run_script_both_ways(script, &["列一下"]).await;
+        assert_eq!(plain_replies, vec!["要列出哪个目录？"]);
+        assert_eq!(plain_replies, stream_replies);
+        assert_eq!(plain, streaming);
+        assert_eq!(plain, serde_json::json!([]));
+    }
+
+    /// 返回 Diff 元数据的 mock 工具
+    struct DiffTool;
+
+    #[async_trait::async_trait]
+    impl Tool for DiffTool {
+        fn name(&self) -> &str {
+            "git"
+        }
+        fn description(&self) -> &str {
+            "Mock diff tool"
+        }
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+        async fn execute(
+            &self,
+            _args: serde_json::Value,
+            _policy: &SecurityPolicy,
+        ) -> Result<ToolResult> {
+            Ok(ToolResult {
+                success: true,
+                output: "+added".to_string(),
+                error: None,
+                kind: Some(ToolOutputKind::Diff),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_output_kind_forwarded_without_changing_llm_text() {
+        let provider = MockProvider::new(vec![
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "git".to_string(),
+                    arguments: serde_json::json!({"action": "diff"}),
+                }],
+                provenance: None,
+            },
+            ChatResponse {
+                text: Some("done".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+
+        let mut agent = Agent::new(
+            Box::new(provider),
+            vec![Box::new(DiffTool)],
+            Box::new(MockMemory),
+            test_policy(),
+            "test".to_string(),
+            "http://test".to_string(),
+            "test-model".to_string(),
+            0.7,
+            vec![],
+            None,
+        );
+
+        let (tx, mut rx) = crate::providers::stream_channel();
+        agent.process_message_stream("看看 diff", tx).await.unwrap();
+
+        let mut forwarded = None;
+        while let Ok(event) = rx.try_recv() {
+            if let StreamEvent::ToolOutput { kind, content, .. } = event {
+                forwarded = Some((kind, content));
+            }
+        }
+        let (kind, content) = forwarded.expect("应收到 StreamEvent::ToolOutput");
+        assert_eq!(kind, ToolOutputKind::Diff);
+        assert_eq!(content, "+added");
+
+        // LLM 可见的 ToolResult 文本保持原样
+        let tool_result = agent.history().iter().find_map(|m| match m {
+            ConversationMessage::ToolResult { content, .. } => Some(content.clone()),
+            _ => None,
+        });
+        assert_eq!(tool_result.as_deref(), Some("+added"));
+
+        let outputs = agent.take_rich_outputs();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].tool, "git");
+        assert!(agent.take_rich_outputs().is_empty());
+    }
+
+    /// 附带后续建议的 mock 工具
+    struct SuggestingTool;
+
+    #[async_trait::async_trait]
+    impl Tool for SuggestingTool {
+        fn name(&self) -> &str {
+            "git"
+        }
+        fn description(&self) -> &str {
+            "Mock tool with follow-ups"
+        }
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+        async fn execute(
+            &self,
+            _args: serde_json::Value,
+            _policy: &SecurityPolicy,
+        ) -> Result<ToolResult> {
+            Ok(ToolResult {
+                success: true,
+                output: "+fixed".to_string(),
+                suggestions: vec!["提交这些改动".to_string(), "查看 diff".to_string()],
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn suggestions_block_is_stripped_from_stream_reply_and_history() {
+        let answer = "改好了。\n\n```suggestions\n- 运行测试\n- 提交这些改动\n```";
+        let provider = MockProvider::new(vec![
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "git".to_string(),
+                    arguments: serde_json::json!({"action": "diff"}),
+                }],
+                provenance: None,
+            },
+            ChatResponse {
+                text: Some(answer.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+        let mut agent = agent_with_tools(Box::new(provider), vec![Box::new(SuggestingTool)]);
+
+        let (tx, mut rx) = crate::providers::stream_channel();
+        let reply = agent.process_message_stream("修一下", tx).await.unwrap();
+        let mut shown = String::new();
+        while let Ok(event) = rx.try_recv() {
+            if let StreamEvent::Text(text) = event {
+                shown.push_str(&text);
+            }
+        }
+
+        assert_eq!(reply, "改好了。");
+        assert!(shown.starts_with("改好了。"), "{}", shown);
+        assert!(!shown.contains("suggestions") && !shown.contains("运行测试"));
+        assert!(agent.history().iter().all(|m| match m {
+            ConversationMessage::Chat(c) => !c.content.contains("```suggestions"),
+            _ => true,
+        }));
+        assert_eq!(
+            agent.last_suggestions(),
+            ["运行测试", "提交这些改动", "查看 diff"]
+        );
+
+        // 下一轮没有建议：清空
+        agent.process_message("谢谢").await.unwrap();
+        assert!(agent.last_suggestions().is_empty());
+    }
+
+    /// 同时给出完整输出与用户摘要的 mock 工具
+    struct SummaryTool;
+
+    #[async_trait::async_trait]
+    impl Tool for SummaryTool {
+        fn name(&self) -> &str {
+            "shell"
+        }
+        fn description(&self) -> &str {
+            "Mock shell tool"
+        }
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+        async fn execute(
+            &self,
+            _args: serde_json::Value,
+            _policy: &SecurityPolicy,
+        ) -> Result<ToolResult> {
+            Ok(ToolResult {
+                success: true,
+                output: "line1\nline2\nline3\nline4".to_string(),
+                user_facing: Some("exit 0\nline4".to_string()),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_feedback_does_not_change_history_content() {
+        let provider = MockProvider::new(vec![
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "shell".to_string(),
+                    arguments: serde_json::json!({"command": "ls"}),
+                }],
+                provenance: None,
             },
-            // Phase 2 second response: final text
             ChatResponse {
-                text: Some("目录中有 file.txt".to_string()),
+                text: Some("done".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
+        let mut agent = agent_with_tools(Box::new(provider), vec![Box::new(SummaryTool)]);
+
+        let (tx, mut rx) = crate::providers::stream_channel();
+        agent.process_message_stream("跑一下", tx).await.unwrap();
+
+        let mut feedback = None;
+        while let Ok(event) = rx.try_recv() {
+            if let StreamEvent::ToolFeedback {
+                summary, content, ..
+            } = event
+            {
+                feedback = Some((summary, content));
+            }
+        }
+        let (summary, content) = feedback.expect("应收到 StreamEvent::ToolFeedback");
+        assert_eq!(summary.as_deref(), Some("exit 0\nline4"));
+        assert_eq!(content, "line1\nline2\nline3\nline4");
+
+        // 摘要只给用户看，history 中仍是完整输出
+        let tool_result = agent.history().iter().find_map(|m| match m {
+            ConversationMessage::ToolResult { content, .. } => Some(content.clone()),
+            _ => None,
+        });
+        assert_eq!(tool_result.as_deref(), Some("line1\nline2\nline3\nline4"));
 
-        let mock_tool = MockTool {
-            tool_name: "shell".to_string(),
-            result: "file.txt".to_string(),
+        let collected = agent.take_tool_feedback();
+        assert_eq!(collected.len(), 1);
+        assert_eq!(collected[0].tool, "shell");
+    }
+
+    #[tokio::test]
+    async fn change_summary_reports_files_written_by_tools() {
+        let tmp = tempfile::tempdir().unwrap();
+        let write_call = |id: &str, path: &str| ChatResponse {
+            text: None,
+            reasoning_content: None,
+            tool_calls: vec![ToolCall {
+                id: id.to_string(),
+                name: "file_write".to_string(),
+                arguments: serde_json::json!({"path": path, "content": "hello"}),
+            }],
+            provenance: None,
+        };
+        let direct = || ChatResponse {
+            text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
         };
+        let done = || ChatResponse {
+            text: Some("done".to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        };
+        let provider = MockProvider::new(vec![
+            direct(),
+            write_call("call_1", "notes.md"),
+            done(),
+            // 第二轮没有工具调用
+            direct(),
+            done(),
+        ]);
 
         let mut agent = Agent::new(
             Box::new(provider),
-            vec![Box::new(mock_tool)],
+            vec![Box::new(crate::tools::file::FileWriteTool::new())],
+            Box::new(MockMemory),
+            SecurityPolicy {
+                workspace_dir: tmp.path().to_path_buf(),
+                ..test_policy()
+            },
+            "test".to_string(),
+            "http://test".to_string(),
+            "test-model".to_string(),
+            0.7,
+            vec![],
+            None,
+        );
+        agent.set_track_changes(true);
+
+        agent.process_message("写个笔记").await.unwrap();
+        assert!(tmp.path().join("notes.md").exists());
+        let changes = agent.take_change_summary().expect("应有变更摘要");
+        assert_eq!(changes.created, vec!["notes.md"]);
+        assert!(agent.take_change_summary().is_none());
+
+        agent.process_message("谢谢").await.unwrap();
+        assert!(agent.take_change_summary().is_none());
+    }
+
+    /// 5 个内置工具 + 95 个 MCP 工具
+    fn hundred_tools() -> Vec<Box<dyn Tool>> {
+        (0..100)
+            .map(|i| {
+                let tool_name = if i < 5 {
+                    format!("builtin_{}", i)
+                } else {
+                    format!("mcp_srv_tool_{:02}", i)
+                };
+                Box::new(MockTool {
+                    tool_name,
+                    result: "ok".to_string(),
+                }) as Box<dyn Tool>
+            })
+            .collect()
+    }
+
+    fn agent_with_tools(provider: Box<dyn Provider>, tools: Vec<Box<dyn Tool>>) -> Agent {
+        Agent::new(
+            provider,
+            tools,
             Box::new(MockMemory),
             test_policy(),
             "test".to_string(),
@@ -1759,10 +3019,224 @@ mod tests {
             0.7,
             vec![],
             None,
+        )
+    }
+
+    #[tokio::test]
+    async fn set_workspace_resolves_tool_paths_and_identity() {
+        let workspace = tempfile::TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("notes.txt"), "TODO: 补测试").unwrap();
+        std::fs::create_dir(workspace.path().join(".rrclaw")).unwrap();
+        std::fs::write(
+            workspace.path().join(".rrclaw/AGENT.md"),
+            "用 cargo nextest",
+        )
+        .unwrap();
+        let home = tempfile::TempDir::new().unwrap();
+
+        let provider = MockProvider::new(vec![
+            routed_direct(),
+            tool_call(
+                "call_1",
+                "file_read",
+                serde_json::json!({"path": "notes.txt"}),
+            ),
+            ChatResponse {
+                text: Some("有一个 TODO".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+        let mut agent = agent_with_tools(
+            Box::new(provider),
+            vec![Box::new(crate::tools::file::FileReadTool)],
+        );
+        assert!(agent
+            .set_workspace(&workspace.path().join("missing"), home.path())
+            .is_err());
+        agent.set_workspace(workspace.path(), home.path()).unwrap();
+        assert_eq!(
+            agent.policy().workspace_dir,
+            workspace.path().canonicalize().unwrap()
+        );
+        assert!(agent
+            .identity_context()
+            .is_some_and(|c| c.contains("用 cargo nextest")));
+
+        // 进程当前目录不变，相对路径按新的工作目录解析
+        agent
+            .process_message("看看 notes.txt 的 TODO")
+            .await
+            .unwrap();
+        let result = agent.history().iter().find_map(|m| match m {
+            ConversationMessage::ToolResult { content, .. } => Some(content.clone()),
+            _ => None,
+        });
+        assert!(
+            result
+                .as_deref()
+                .is_some_and(|c| c.contains("TODO: 补测试")),
+            "{:?}",
+            result
         );
+    }
 
-        let reply = agent.process_message("列出文件").await.unwrap();
-        assert_eq!(reply, "目录中有 file.txt");
+    #[tokio::test]
+    async fn failed_shell_result_shows_exit_code_to_model() {
+        let agent = agent_with_tools(
+            Box::new(MockProvider::new(vec![])),
+            vec![Box::new(crate::tools::shell::ShellTool)],
+        );
+        let (result, _, _, _) = agent
+            .execute_tool(
+                "shell",
+                serde_json::json!({"command": "ls /rrclaw-no-such-dir"}),
+            )
+            .await;
+        assert!(result.starts_with("[失败]"));
+        let metadata = result.lines().last().unwrap();
+        assert!(metadata.starts_with("[exit_code="));
+        assert!(!metadata.starts_with("[exit_code=0"));
+        assert!(metadata.contains("duration_ms="));
+    }
+
+    #[test]
+    fn tool_limits_prioritize_routed_then_builtin_then_frequent_mcp() {
+        let mut agent = agent_with_tools(Box::new(MockProvider::new(vec![])), hundred_tools());
+        agent.set_tool_limits(ToolLimits {
+            max_tools: Some(10),
+            max_tool_schema_bytes: None,
+        });
+        agent.routed_tool_names = vec!["mcp_srv_tool_50".to_string()];
+        agent.tool_usage.insert("mcp_srv_tool_90".to_string(), 7);
+        agent.tool_usage.insert("mcp_srv_tool_80".to_string(), 3);
+
+        let all: Vec<ToolSpec> = agent.tools.iter().map(|t| t.spec()).collect();
+        let (kept, omitted) = agent.apply_tool_limits(all);
+        let names: Vec<&str> = kept.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(omitted, 90);
+        assert_eq!(
+            names,
+            vec![
+                "mcp_srv_tool_50",
+                "builtin_0",
+                "builtin_1",
+                "builtin_2",
+                "builtin_3",
+                "builtin_4",
+                "mcp_srv_tool_90",
+                "mcp_srv_tool_80",
+                "mcp_srv_tool_05",
+                "mcp_srv_tool_06",
+            ]
+        );
+    }
+
+    #[test]
+    fn tool_limits_enforce_schema_bytes_and_skip_when_within_limits() {
+        let mut agent = agent_with_tools(Box::new(MockProvider::new(vec![])), hundred_tools());
+        let all: Vec<ToolSpec> = agent.tools.iter().map(|t| t.spec()).collect();
+
+        // 默认无限制：原样返回
+        let (kept, omitted) = agent.apply_tool_limits(all.clone());
+        assert_eq!((kept.len(), omitted), (100, 0));
+
+        agent.set_tool_limits(ToolLimits {
+            max_tools: None,
+            max_tool_schema_bytes: Some(2000),
+        });
+        let (kept, omitted) = agent.apply_tool_limits(all);
+        let bytes: usize = kept
+            .iter()
+            .map(|s| serde_json::to_vec(s).unwrap().len())
+            .sum();
+        assert!(bytes <= 2000);
+        assert_eq!(kept.len() + omitted, 100);
+        assert!(kept.iter().take(5).all(|s| s.name.starts_with("builtin_")));
+    }
+
+    /// 工具数超过阈值时返回 "too many functions" 的 Provider，记录每次请求的工具数
+    struct ToolCapProvider {
+        cap: usize,
+        calls: std::sync::Mutex<Vec<usize>>,
+        /// 每次请求的 system prompt
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for ToolCapProvider {
+        async fn chat_with_tools(
+            &self,
+            messages: &[ConversationMessage],
+            tools: &[ToolSpec],
+            _model: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            self.calls.lock().unwrap().push(tools.len());
+            if let Some(ConversationMessage::Chat(system)) = messages.first() {
+                self.prompts.lock().unwrap().push(system.content.clone());
+            }
+            if tools.len() > self.cap {
+                color_eyre::eyre::bail!(
+                    "API 返回错误 400: {{\"error\":{{\"message\":\"Too many functions: at most {} are allowed\"}}}}",
+                    self.cap
+                );
+            }
+            let text = if tools.is_empty() {
+                r#"{"skills": [], "direct": true}"#
+            } else {
+                "done"
+            };
+            Ok(ChatResponse {
+                text: Some(text.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn too_many_tools_error_retries_with_priority_tools() {
+        let provider = std::sync::Arc::new(ToolCapProvider {
+            cap: 10,
+            calls: std::sync::Mutex::new(vec![]),
+            prompts: std::sync::Mutex::new(vec![]),
+        });
+
+        struct Shared(std::sync::Arc<ToolCapProvider>);
+        #[async_trait::async_trait]
+        impl Provider for Shared {
+            async fn chat_with_tools(
+                &self,
+                messages: &[ConversationMessage],
+                tools: &[ToolSpec],
+                model: &str,
+                params: GenerationParams,
+            ) -> Result<ChatResponse> {
+                self.0.chat_with_tools(messages, tools, model, params).await
+            }
+        }
+
+        // 与生产环境一样包在 ReliableProvider 里：被拒的错误经错误链识别
+        let reliable = crate::providers::ReliableProvider::new(
+            Box::new(Shared(provider.clone())),
+            crate::providers::RetryConfig::default(),
+        )
+        .with_offline_state(Arc::new(crate::providers::OfflineState::new()));
+        let mut agent = agent_with_tools(Box::new(reliable), hundred_tools());
+        let reply = agent.process_message("hi there").await.unwrap();
+        assert_eq!(reply, "done");
+        // 路由(0 个工具) → 全量 100 个被拒 → 只保留 5 个内置工具重试
+        assert_eq!(*provider.calls.lock().unwrap(), vec![0, 100, 5]);
+        assert_eq!(agent.omitted_tool_count, 95);
+
+        // 重试的 system prompt 按新的省略数重建
+        let prompts = provider.prompts.lock().unwrap();
+        let mentions_omitted = |p: &str| p.contains("95 个工具") || p.contains("95 tool(s)");
+        assert!(!mentions_omitted(&prompts[1]));
+        assert!(mentions_omitted(&prompts[2]), "{}", prompts[2]);
     }
 
     #[tokio::test]
@@ -1773,6 +3247,7 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // Phase 2 first response: unknown tool call
             ChatResponse {
@@ -1783,12 +3258,14 @@ mod tests {
                     name: "nonexistent".to_string(),
                     arguments: serde_json::json!({}),
                 }],
+                provenance: None,
             },
             // Phase 2 second response: final text
             ChatResponse {
                 text: Some("抱歉".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -1827,6 +3304,60 @@ mod tests {
         assert!(prompt.contains("RRClaw"));
     }
 
+    fn prompt_agent(identity: Option<String>, agent_config: &crate::config::AgentConfig) -> Agent {
+        let mut agent = Agent::new(
+            Box::new(MockProvider::new(vec![])),
+            vec![],
+            Box::new(MockMemory),
+            test_policy(),
+            "test".to_string(),
+            "http://test".to_string(),
+            "test".to_string(),
+            0.7,
+            vec![],
+            identity,
+        );
+        agent.configure_system_prompt(agent_config);
+        agent
+    }
+
+    #[test]
+    fn system_prompt_prefix_comes_before_everything() {
+        let config = crate::config::AgentConfig {
+            system_prompt_prefix: Some("  You serve the ACME ops team.\n".to_string()),
+            ..Default::default()
+        };
+        let agent = prompt_agent(Some("偏好简洁".to_string()), &config);
+        let prompt = agent.build_system_prompt(&[]);
+        assert!(prompt.starts_with("You serve the ACME ops team.\n\n[Custom Context]"));
+        assert!(prompt.contains("You are RRClaw, a safety-first AI assistant."));
+        assert!(prompt.contains("[Decision Principles]"));
+    }
+
+    #[test]
+    fn system_prompt_override_replaces_identity_and_principles() {
+        let config = crate::config::AgentConfig {
+            system_prompt_override: Some("You are OpsBot. Commands first.".to_string()),
+            ..Default::default()
+        };
+        let agent = prompt_agent(None, &config);
+        let prompt = agent.build_system_prompt(&[]);
+        assert!(prompt.starts_with("You are OpsBot. Commands first."));
+        assert!(!prompt.contains("You are RRClaw"));
+        assert!(!prompt.contains("[Decision Principles]"));
+        // 安全规则与环境信息仍保留
+        assert!(prompt.contains("Full mode"));
+        assert!(prompt.contains("Working directory:"));
+
+        // 空白覆盖视为未配置
+        let blank = crate::config::AgentConfig {
+            system_prompt_override: Some("   ".to_string()),
+            ..Default::default()
+        };
+        let prompt = prompt_agent(None, &blank).build_system_prompt(&[]);
+        assert!(prompt.contains("You are RRClaw"));
+    }
+
     #[test]
     fn system_prompt_injects_identity_context_before_rrclaw_description() {
         let identity = "### 用户偏好\n你是专属助手 Max，简洁直接".to_string();
@@ -1950,6 +3481,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn follow_up_principle_only_when_channel_shows_quick_replies() {
+        let mut agent = Agent::new(
+            Box::new(MockProvider::new(vec![])),
+            vec![],
+            Box::new(MockMemory),
+            test_policy(),
+            "test".to_string(),
+            "http://test".to_string(),
+            "test".to_string(),
+            0.7,
+            vec![],
+            None,
+        );
+        assert!(!agent.build_system_prompt(&[]).contains("```suggestions"));
+        agent.set_quick_replies(true);
+        assert!(agent.build_system_prompt(&[]).contains("```suggestions"));
+    }
+
     // --- pre_select_tool 测试 ---
 
     #[test]
@@ -2025,6 +3575,7 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // Phase 2 first response: tool call
             ChatResponse {
@@ -2035,12 +3586,14 @@ mod tests {
                     name: "shell".to_string(),
                     arguments: serde_json::json!({"command": "ls"}),
                 }],
+                provenance: None,
             },
             // Phase 2 second response: final text after tool execution
             ChatResponse {
                 text: Some("执行完成".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -2056,7 +3609,121 @@ mod tests {
             Box::new(provider),
             vec![Box::new(mock_tool)],
             Box::new(MockMemory),
-            policy,
+            policy,
+            "test".to_string(),
+            "http://test".to_string(),
+            "test-model".to_string(),
+            0.7,
+            vec![],
+            None,
+        );
+
+        // 确认回调: 始终允许
+        agent.set_confirm_fn(Box::new(|_name, _args| true));
+
+        let reply = agent.process_message("列出文件").await.unwrap();
+        assert_eq!(reply, "执行完成");
+    }
+
+    #[tokio::test]
+    async fn supervised_confirm_denies_execution() {
+        let provider = MockProvider::new(vec![
+            // Phase 1 routing response
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            // Phase 2 first response: dangerous tool call
+            ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "shell".to_string(),
+                    arguments: serde_json::json!({"command": "rm -rf /"}),
+                }],
+                provenance: None,
+            },
+            // Phase 2 second response: after tool was denied
+            ChatResponse {
+                text: Some("好的，已取消".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+
+        let mock_tool = MockTool {
+            tool_name: "shell".to_string(),
+            result: "should not run".to_string(),
+        };
+
+        let mut policy = test_policy();
+        policy.autonomy = AutonomyLevel::Supervised;
+
+        let mut agent = Agent::new(
+            Box::new(provider),
+            vec![Box::new(mock_tool)],
+            Box::new(MockMemory),
+            policy,
+            "test".to_string(),
+            "http://test".to_string(),
+            "test-model".to_string(),
+            0.7,
+            vec![],
+            None,
+        );
+
+        // 确认回调: 始终拒绝
+        agent.set_confirm_fn(Box::new(|_name, _args| false));
+
+        let reply = agent.process_message("删除所有文件").await.unwrap();
+        assert_eq!(reply, "好的，已取消");
+    }
+
+    #[tokio::test]
+    async fn full_mode_skips_confirmation() {
+        let provider = MockProvider::new(vec![
+            // Phase 1 routing response
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            // Phase 2 first response: tool call
+            ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "shell".to_string(),
+                    arguments: serde_json::json!({"command": "ls"}),
+                }],
+                provenance: None,
+            },
+            // Phase 2 second response: final text (no confirm prompt in Full mode)
+            ChatResponse {
+                text: Some("完成".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+
+        let mock_tool = MockTool {
+            tool_name: "shell".to_string(),
+            result: "file.txt".to_string(),
+        };
+
+        // Full 模式 — 不需要确认
+        let mut agent = Agent::new(
+            Box::new(provider),
+            vec![Box::new(mock_tool)],
+            Box::new(MockMemory),
+            test_policy(), // Full mode
             "test".to_string(),
             "http://test".to_string(),
             "test-model".to_string(),
@@ -2065,48 +3732,47 @@ mod tests {
             None,
         );
 
-        // 确认回调: 始终允许
-        agent.set_confirm_fn(Box::new(|_name, _args| true));
+        // 设置一个会 panic 的确认回调（不应被调用）
+        agent.set_confirm_fn(Box::new(|_name, _args| {
+            panic!("Full 模式不应调用确认回调");
+        }));
 
         let reply = agent.process_message("列出文件").await.unwrap();
-        assert_eq!(reply, "执行完成");
+        assert_eq!(reply, "完成");
     }
 
     #[tokio::test]
-    async fn supervised_confirm_denies_execution() {
+    async fn one_shot_full_mode_reverts_autonomy() {
         let provider = MockProvider::new(vec![
-            // Phase 1 routing response
             ChatResponse {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
-            // Phase 2 first response: dangerous tool call
             ChatResponse {
                 text: None,
                 reasoning_content: None,
                 tool_calls: vec![ToolCall {
                     id: "call_1".to_string(),
                     name: "shell".to_string(),
-                    arguments: serde_json::json!({"command": "rm -rf /"}),
+                    arguments: serde_json::json!({"command": "ls"}),
                 }],
+                provenance: None,
             },
-            // Phase 2 second response: after tool was denied
             ChatResponse {
-                text: Some("好的，已取消".to_string()),
+                text: Some("完成".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
-
         let mock_tool = MockTool {
             tool_name: "shell".to_string(),
-            result: "should not run".to_string(),
+            result: "file.txt".to_string(),
         };
-
         let mut policy = test_policy();
         policy.autonomy = AutonomyLevel::Supervised;
-
         let mut agent = Agent::new(
             Box::new(provider),
             vec![Box::new(mock_tool)],
@@ -2119,52 +3785,256 @@ mod tests {
             vec![],
             None,
         );
+        agent.set_confirm_fn(Box::new(|_name, _args| {
+            panic!("一次性 Full 模式不应调用确认回调");
+        }));
 
-        // 确认回调: 始终拒绝
-        agent.set_confirm_fn(Box::new(|_name, _args| false));
+        let reply = agent
+            .process_message_as(AutonomyLevel::Full, "列出文件")
+            .await
+            .unwrap();
+        assert_eq!(reply, "完成");
+        assert!(agent
+            .history()
+            .iter()
+            .any(|m| matches!(m, ConversationMessage::ToolResult { content, .. } if content == "file.txt")));
+        assert_eq!(agent.policy().autonomy, AutonomyLevel::Supervised);
+    }
 
-        let reply = agent.process_message("删除所有文件").await.unwrap();
-        assert_eq!(reply, "好的，已取消");
+    /// 只读审批策略：放行 file_read，拒绝其余工具
+    struct ReadOnlyApproval;
+
+    #[async_trait::async_trait]
+    impl ApprovalPolicy for ReadOnlyApproval {
+        async fn approve(&self, call: &ToolCall, _policy: &SecurityPolicy) -> Decision {
+            if call.name == "file_read" {
+                Decision::Approve
+            } else {
+                Decision::Deny(Some("只读模式".to_string()))
+            }
+        }
     }
 
     #[tokio::test]
-    async fn full_mode_skips_confirmation() {
+    async fn approval_policy_approves_reads_and_denies_writes() {
         let provider = MockProvider::new(vec![
-            // Phase 1 routing response
             ChatResponse {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
-            // Phase 2 first response: tool call
             ChatResponse {
                 text: None,
                 reasoning_content: None,
-                tool_calls: vec![ToolCall {
-                    id: "call_1".to_string(),
-                    name: "shell".to_string(),
-                    arguments: serde_json::json!({"command": "ls"}),
-                }],
+                tool_calls: vec![
+                    ToolCall {
+                        id: "call_read".to_string(),
+                        name: "file_read".to_string(),
+                        arguments: serde_json::json!({"path": "a.txt"}),
+                    },
+                    ToolCall {
+                        id: "call_write".to_string(),
+                        name: "file_write".to_string(),
+                        arguments: serde_json::json!({"path": "a.txt", "content": "x"}),
+                    },
+                ],
+                provenance: None,
             },
-            // Phase 2 second response: final text (no confirm prompt in Full mode)
             ChatResponse {
-                text: Some("完成".to_string()),
+                text: Some("读取完成，写入被拒绝".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
+        // Full 模式下同样生效：策略自行决定是否介入
+        let mut agent = agent_with_tools(
+            Box::new(provider),
+            vec![
+                Box::new(MockTool {
+                    tool_name: "file_read".to_string(),
+                    result: "hello".to_string(),
+                }),
+                Box::new(MockTool {
+                    tool_name: "file_write".to_string(),
+                    result: "should not run".to_string(),
+                }),
+            ],
+        );
+        agent.set_approval_policy(Box::new(ReadOnlyApproval));
+
+        agent.process_message("读取并修改 a.txt").await.unwrap();
+
+        let result_of = |id: &str| {
+            agent.history().iter().find_map(|m| match m {
+                ConversationMessage::ToolResult {
+                    tool_call_id,
+                    content,
+                    ..
+                } if tool_call_id == id => Some(content.clone()),
+                _ => None,
+            })
+        };
+        assert_eq!(result_of("call_read").as_deref(), Some("hello"));
+        let denied = result_of("call_write").unwrap();
+        assert!(denied.starts_with("[失败]"), "{}", denied);
+        assert!(denied.contains("只读模式"));
+    }
 
-        let mock_tool = MockTool {
-            tool_name: "shell".to_string(),
-            result: "file.txt".to_string(),
+    /// 每次调用都必须经过用户确认的工具
+    struct GatedTool;
+
+    #[async_trait::async_trait]
+    impl Tool for GatedTool {
+        fn name(&self) -> &str {
+            "gated"
+        }
+        fn description(&self) -> &str {
+            "Gated tool"
+        }
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+        fn requires_approval(&self, _args: &serde_json::Value) -> bool {
+            true
+        }
+        async fn execute(
+            &self,
+            _args: serde_json::Value,
+            _policy: &SecurityPolicy,
+        ) -> Result<ToolResult> {
+            Ok(ToolResult {
+                success: true,
+                output: "gated output".to_string(),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn approval_required_tool_denied_without_policy() {
+        let responses = || {
+            vec![
+                ChatResponse {
+                    text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                    reasoning_content: None,
+                    tool_calls: vec![],
+                    provenance: None,
+                },
+                ChatResponse {
+                    text: None,
+                    reasoning_content: None,
+                    tool_calls: vec![ToolCall {
+                        id: "call_gated".to_string(),
+                        name: "gated".to_string(),
+                        arguments: serde_json::json!({}),
+                    }],
+                    provenance: None,
+                },
+                ChatResponse {
+                    text: Some("完成".to_string()),
+                    reasoning_content: None,
+                    tool_calls: vec![],
+                    provenance: None,
+                },
+            ]
+        };
+        let gated_result = |agent: &Agent| {
+            agent.history().iter().find_map(|m| match m {
+                ConversationMessage::ToolResult { content, .. } => Some(content.clone()),
+                _ => None,
+            })
         };
 
-        // Full 模式 — 不需要确认
+        // 没有审批策略：无人可问，拒绝执行
+        let mut agent = agent_with_tools(
+            Box::new(MockProvider::new(responses())),
+            vec![Box::new(GatedTool)],
+        );
+        agent.process_message("运行 gated").await.unwrap();
+        let denied = gated_result(&agent).unwrap();
+        assert!(denied.starts_with("[失败]"), "{}", denied);
+        assert!(!denied.contains("gated output"));
+
+        // Supervised + 确认回调：用户同意后执行
+        let mut agent = agent_with_tools(
+            Box::new(MockProvider::new(responses())),
+            vec![Box::new(GatedTool)],
+        );
+        agent.set_autonomy(AutonomyLevel::Supervised);
+        agent.set_confirm_fn(Box::new(|_, _| true));
+        agent.process_message("运行 gated").await.unwrap();
+        assert!(gated_result(&agent).unwrap().contains("gated output"));
+    }
+
+    // --- 脏工作区提醒 ---
+
+    /// 带一次提交的临时仓库；`dirty` 时再留一个未提交的改动
+    fn temp_repo(dirty: bool) -> tempfile::TempDir {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .output()
+                .unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "t@example.com"]);
+        git(&["config", "user.name", "t"]);
+        std::fs::write(tmp.path().join("a.txt"), "one\n").unwrap();
+        git(&["add", "a.txt"]);
+        git(&["commit", "-m", "init"]);
+        if dirty {
+            std::fs::write(tmp.path().join("a.txt"), "mine\n").unwrap();
+        }
+        tmp
+    }
+
+    /// 每轮：路由 → 调用写入类工具 `touch` → 最终回复
+    fn touch_turns(turns: usize) -> Vec<ChatResponse> {
+        (0..turns)
+            .flat_map(|i| {
+                [
+                    ChatResponse {
+                        text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                        reasoning_content: None,
+                        tool_calls: vec![],
+                        provenance: None,
+                    },
+                    ChatResponse {
+                        text: None,
+                        reasoning_content: None,
+                        tool_calls: vec![ToolCall {
+                            id: format!("call_touch_{}", i),
+                            name: "touch".to_string(),
+                            arguments: serde_json::json!({"n": i}),
+                        }],
+                        provenance: None,
+                    },
+                    ChatResponse {
+                        text: Some("完成".to_string()),
+                        reasoning_content: None,
+                        tool_calls: vec![],
+                        provenance: None,
+                    },
+                ]
+            })
+            .collect()
+    }
+
+    fn agent_in_repo(repo: &std::path::Path, turns: usize) -> Agent {
+        let mut policy = test_policy();
+        policy.workspace_dir = repo.canonicalize().unwrap();
         let mut agent = Agent::new(
-            Box::new(provider),
-            vec![Box::new(mock_tool)],
+            Box::new(MockProvider::new(touch_turns(turns))),
+            vec![Box::new(MockTool {
+                tool_name: "touch".to_string(),
+                result: "ok".to_string(),
+            })],
             Box::new(MockMemory),
-            test_policy(), // Full mode
+            policy,
             "test".to_string(),
             "http://test".to_string(),
             "test-model".to_string(),
@@ -2172,14 +4042,268 @@ mod tests {
             vec![],
             None,
         );
+        agent.set_warn_dirty_worktree(true);
+        agent
+    }
 
-        // 设置一个会 panic 的确认回调（不应被调用）
-        agent.set_confirm_fn(Box::new(|_name, _args| {
-            panic!("Full 模式不应调用确认回调");
+    /// 流式处理一条消息，返回收到的 Notice
+    async fn notices_of(agent: &mut Agent, msg: &str) -> Vec<String> {
+        let (tx, mut rx) = crate::providers::stream_channel();
+        agent.process_message_stream(msg, tx).await.unwrap();
+        let mut notices = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let StreamEvent::Notice(text) = event {
+                notices.push(text);
+            }
+        }
+        notices
+    }
+
+    #[tokio::test]
+    async fn dirty_worktree_notice_once_per_session_in_full_mode() {
+        let repo = temp_repo(true);
+        let mut agent = agent_in_repo(repo.path(), 2);
+
+        let first = notices_of(&mut agent, "改一下").await;
+        assert_eq!(first.len(), 1, "{:?}", first);
+        assert!(first[0].contains("a.txt"), "{}", first[0]);
+        assert!(first[0].contains("/safety stash"), "{}", first[0]);
+
+        // 已提醒过：之后的回合不再提醒
+        assert!(notices_of(&mut agent, "再改一下").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn clean_worktree_settles_the_guard() {
+        let repo = temp_repo(false);
+        let mut agent = agent_in_repo(repo.path(), 2);
+        assert!(notices_of(&mut agent, "改一下").await.is_empty());
+
+        // 之后的改动视为 Agent 自己的修改，不再提醒
+        std::fs::write(repo.path().join("a.txt"), "agent\n").unwrap();
+        assert!(notices_of(&mut agent, "再改一下").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dirty_worktree_notice_disabled_or_outside_repo() {
+        let repo = temp_repo(true);
+        let mut agent = agent_in_repo(repo.path(), 1);
+        agent.set_warn_dirty_worktree(false);
+        assert!(notices_of(&mut agent, "改一下").await.is_empty());
+
+        let plain = tempfile::TempDir::new().unwrap();
+        let mut agent = agent_in_repo(plain.path(), 1);
+        assert!(notices_of(&mut agent, "改一下").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dirty_worktree_notice_goes_into_supervised_confirmation() {
+        let repo = temp_repo(true);
+        let mut agent = agent_in_repo(repo.path(), 1);
+        agent.set_autonomy(AutonomyLevel::Supervised);
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let captured = seen.clone();
+        agent.set_confirm_fn(Box::new(move |_, args| {
+            captured.lock().unwrap().push(args.clone());
+            true
         }));
 
-        let reply = agent.process_message("列出文件").await.unwrap();
-        assert_eq!(reply, "完成");
+        assert!(notices_of(&mut agent, "改一下").await.is_empty());
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        let notice = seen[0][NOTICE_ARG].as_str().unwrap();
+        assert!(notice.contains("a.txt"), "{}", notice);
+        assert_eq!(seen[0]["n"], 0);
+    }
+
+    // --- 计数 Mock Tool（重复调用去重测试用）---
+    struct CountingTool {
+        tool_name: &'static str,
+        success: bool,
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Tool for CountingTool {
+        fn name(&self) -> &str {
+            self.tool_name
+        }
+        fn description(&self) -> &str {
+            "counting tool"
+        }
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object", "properties": {}})
+        }
+        fn cacheable(&self, _args: &serde_json::Value) -> bool {
+            self.tool_name != "memory_recall"
+        }
+        async fn execute(
+            &self,
+            _args: serde_json::Value,
+            _policy: &SecurityPolicy,
+        ) -> Result<ToolResult> {
+            let n = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            Ok(ToolResult {
+                success: self.success,
+                output: format!("call #{}", n),
+                error: (!self.success).then(|| "boom".to_string()),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// 路由响应 + `repeats` 次相同参数的调用 + 最终回复
+    fn repeated_call_provider(tool_name: &str, repeats: usize) -> MockProvider {
+        let mut responses = vec![ChatResponse {
+            text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        }];
+        for i in 0..repeats {
+            responses.push(ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: format!("call_{}", i),
+                    name: tool_name.to_string(),
+                    // 字段顺序不同也视为同一调用
+                    arguments: if i % 2 == 0 {
+                        serde_json::json!({"path": "a.txt", "limit": 10})
+                    } else {
+                        serde_json::from_str(r#"{"limit": 10, "path": "a.txt"}"#).unwrap()
+                    },
+                }],
+                provenance: None,
+            });
+        }
+        responses.push(ChatResponse {
+            text: Some("完成".to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        });
+        MockProvider::new(responses)
+    }
+
+    fn counting_agent(
+        tool_name: &'static str,
+        repeats: usize,
+        success: bool,
+    ) -> (Agent, Arc<std::sync::atomic::AtomicUsize>) {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let tool = CountingTool {
+            tool_name,
+            success,
+            calls: calls.clone(),
+        };
+        let agent = agent_with_tools(
+            Box::new(repeated_call_provider(tool_name, repeats)),
+            vec![Box::new(tool)],
+        );
+        (agent, calls)
+    }
+
+    fn tool_results(agent: &Agent) -> Vec<String> {
+        agent
+            .history()
+            .iter()
+            .filter_map(|m| match m {
+                ConversationMessage::ToolResult { content, .. } => Some(content.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn repeated_successful_call_returns_cached_result() {
+        let (mut agent, calls) = counting_agent("file_read", 3, true);
+
+        agent.process_message("读文件").await.unwrap();
+
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let results = tool_results(&agent);
+        assert_eq!(results[0], "call #1");
+        for cached in &results[1..] {
+            assert!(cached.starts_with(CACHED_PREFIX));
+            assert!(cached.ends_with("call #1"));
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_failed_call_retries_once_then_short_circuits() {
+        let (mut agent, calls) = counting_agent("file_read", 4, false);
+
+        agent.process_message("读文件").await.unwrap();
+
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+        let results = tool_results(&agent);
+        assert!(results[1].starts_with("[失败] boom"));
+        assert_eq!(results[2], REPEATED_FAILURE_MESSAGE);
+        assert_eq!(results[3], REPEATED_FAILURE_MESSAGE);
+    }
+
+    #[tokio::test]
+    async fn non_cacheable_tool_always_executes() {
+        let (mut agent, calls) = counting_agent("memory_recall", 3, true);
+
+        agent.process_message("回忆一下").await.unwrap();
+
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert!(tool_results(&agent)
+            .iter()
+            .all(|r| !r.starts_with(CACHED_PREFIX)));
+    }
+
+    #[tokio::test]
+    async fn call_ledger_resets_each_turn() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let tool = CountingTool {
+            tool_name: "file_read",
+            success: true,
+            calls: calls.clone(),
+        };
+        let mut responses = repeated_call_provider("file_read", 1)
+            .responses
+            .into_inner()
+            .unwrap();
+        responses.extend(
+            repeated_call_provider("file_read", 1)
+                .responses
+                .into_inner()
+                .unwrap(),
+        );
+        let mut agent =
+            agent_with_tools(Box::new(MockProvider::new(responses)), vec![Box::new(tool)]);
+
+        agent.process_message("读文件").await.unwrap();
+        agent.process_message("再读一次").await.unwrap();
+
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn request_token_estimate_grows_with_history() {
+        let provider = MockProvider::new(vec![
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            ChatResponse {
+                text: Some("这是一段比较长的回复内容".repeat(20)),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+        let mut agent = agent_with_tools(Box::new(provider), vec![]);
+        let before = agent.estimate_request_tokens();
+        assert!(before > 0, "system prompt 本身也有 token");
+
+        agent.process_message("你好").await.unwrap();
+        assert!(agent.estimate_request_tokens() >= before + 240);
     }
 
     #[test]
@@ -2202,6 +4326,7 @@ mod tests {
                 role: "user".to_string(),
                 content: format!("msg {}", i),
                 reasoning_content: None,
+                turn: 0,
             }));
         }
         assert_eq!(agent.history.len(), 60);
@@ -2219,6 +4344,7 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             ChatResponse {
                 text: None,
@@ -2228,11 +4354,13 @@ mod tests {
                     name: "shell".to_string(),
                     arguments: serde_json::json!({"command": "ls"}),
                 }],
+                provenance: None,
             },
             ChatResponse {
                 text: Some("目录中有 file.txt".to_string()),
                 reasoning_content: Some("好的，我看到了文件".to_string()),
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -2282,24 +4410,28 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // First round: main
             ChatResponse {
                 text: Some("你好！".to_string()),
                 reasoning_content: Some("用户打招呼".to_string()),
                 tool_calls: vec![],
+                provenance: None,
             },
             // Second round: routing
             ChatResponse {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // Second round: main
             ChatResponse {
                 text: Some("再见！".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -2384,17 +4516,130 @@ mod tests {
         }
     }
 
-    #[test]
-    fn build_routing_prompt_no_tools() {
-        let skills = vec![];
-        // English version
-        let prompt = build_routing_prompt(&skills, crate::i18n::Language::English);
-        assert!(!prompt.contains("shell"));
-        assert!(!prompt.contains("file_read"));
-        assert!(prompt.contains("JSON"));
-        // Chinese version
-        let prompt_zh = build_routing_prompt(&skills, crate::i18n::Language::Chinese);
-        assert!(prompt_zh.contains("JSON"));
+    #[test]
+    fn build_routing_prompt_no_tools() {
+        let skills = vec![];
+        // English version
+        let prompt = prompts::routing_prompt(&skills, crate::i18n::Language::English);
+        assert!(!prompt.contains("shell"));
+        assert!(!prompt.contains("file_read"));
+        assert!(prompt.contains("JSON"));
+        // Chinese version
+        let prompt_zh = prompts::routing_prompt(&skills, crate::i18n::Language::Chinese);
+        assert!(prompt_zh.contains("JSON"));
+    }
+
+    #[test]
+    fn safe_mode_advertises_no_tools() {
+        let mut agent = agent_with_tools(
+            Box::new(MockProvider::new(vec![])),
+            vec![Box::new(crate::tools::shell::ShellTool)],
+        );
+        agent.skills_meta = vec![SkillMeta {
+            name: "git-commit".to_string(),
+            description: "Git commit workflow".to_string(),
+            tags: vec![],
+            source: SkillSource::BuiltIn,
+            path: None,
+        }];
+        agent.set_autonomy(AutonomyLevel::Full);
+
+        agent.enter_safe_mode();
+
+        assert!(agent.is_safe_mode());
+        assert!(agent.tool_names().is_empty());
+        assert!(agent.build_tool_specs("").is_empty());
+        assert_eq!(agent.policy().autonomy, AutonomyLevel::ReadOnly);
+        let en = agent.system_prompt_in(crate::i18n::Language::English, &[]);
+        assert!(!en.contains("following tools") && !en.contains("Available Skills"));
+        assert!(en.contains("Safe mode"));
+        let zh = agent.system_prompt_in(crate::i18n::Language::Chinese, &[]);
+        assert!(!zh.contains("可以使用以下工具") && !zh.contains("可用技能"));
+        assert!(zh.contains("安全模式"));
+
+        // 配置热更新不会放开自主级别
+        agent.apply_security_config(&crate::config::SecurityConfig::default());
+        assert_eq!(agent.policy().autonomy, AutonomyLevel::ReadOnly);
+    }
+
+    #[tokio::test]
+    async fn ask_turn_denies_write_tools_even_in_full_mode() {
+        let provider = MockProvider::new(vec![
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_write".to_string(),
+                    name: "file_write".to_string(),
+                    arguments: serde_json::json!({"path": "a.txt", "content": "x"}),
+                }],
+                provenance: None,
+            },
+            ChatResponse {
+                text: Some("无法修改".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut agent = agent_with_tools(
+            Box::new(provider),
+            vec![
+                Box::new(CountingTool {
+                    tool_name: "file_write",
+                    success: true,
+                    calls: Arc::clone(&calls),
+                }),
+                Box::new(crate::tools::file::FileReadTool),
+            ],
+        );
+        agent.set_autonomy(AutonomyLevel::Full);
+
+        agent.set_read_only(true);
+        let names: Vec<String> = agent
+            .build_tool_specs("")
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["file_read"]);
+        assert!(agent
+            .system_prompt_in(crate::i18n::Language::English, &[])
+            .contains("Ask mode"));
+        agent.set_read_only(false);
+
+        let reply = agent
+            .process_message_read_only("改一下 a.txt")
+            .await
+            .unwrap();
+        assert_eq!(reply, "无法修改");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+        let denied = agent
+            .history()
+            .iter()
+            .find_map(|m| match m {
+                ConversationMessage::ToolResult {
+                    tool_call_id,
+                    content,
+                    ..
+                } if tool_call_id == "call_write" => Some(content.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert!(
+            denied.starts_with("[失败]") && denied.contains("只读回合"),
+            "{}",
+            denied
+        );
+        // 回合结束后恢复
+        assert!(!agent.is_read_only());
+        assert_eq!(agent.policy().autonomy, AutonomyLevel::Full);
     }
 
     #[test]
@@ -2407,11 +4652,11 @@ mod tests {
             path: None,
         }];
         // English
-        let prompt = build_routing_prompt(&skills, crate::i18n::Language::English);
+        let prompt = prompts::routing_prompt(&skills, crate::i18n::Language::English);
         assert!(prompt.contains("git-commit"));
         assert!(prompt.contains("Git commit workflow"));
         // Chinese
-        let prompt_zh = build_routing_prompt(&skills, crate::i18n::Language::Chinese);
+        let prompt_zh = prompts::routing_prompt(&skills, crate::i18n::Language::Chinese);
         assert!(prompt_zh.contains("git-commit"));
     }
 
@@ -2419,10 +4664,10 @@ mod tests {
     fn build_routing_prompt_empty_skills() {
         let skills = vec![];
         // English: "No skills available"
-        let prompt = build_routing_prompt(&skills, crate::i18n::Language::English);
+        let prompt = prompts::routing_prompt(&skills, crate::i18n::Language::English);
         assert!(prompt.contains("No skills available"));
         // Chinese: "暂无可用 skill"
-        let prompt_zh = build_routing_prompt(&skills, crate::i18n::Language::Chinese);
+        let prompt_zh = prompts::routing_prompt(&skills, crate::i18n::Language::Chinese);
         assert!(prompt_zh.contains("暂无可用 skill"));
     }
 
@@ -2447,6 +4692,7 @@ mod tests {
             role: role.to_string(),
             content: content.to_string(),
             reasoning_content: None,
+            turn: 0,
         })
     }
 
@@ -2490,6 +4736,7 @@ mod tests {
             text: Some("对话摘要：用户询问了多个问题，助手逐一回答。".to_string()),
             reasoning_content: None,
             tool_calls: vec![],
+            provenance: None,
         };
         let provider = MockProvider::new(vec![summary_response]);
         let mut agent = Agent::new(
@@ -2516,6 +4763,46 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn compaction_triggers_on_tokens_for_small_context_window() {
+        let summary_response = ChatResponse {
+            text: Some("对话摘要：长消息。".to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        };
+        let provider = MockProvider::new(vec![summary_response]);
+        let mut agent = Agent::new(
+            Box::new(provider),
+            vec![],
+            Box::new(MockMemory),
+            test_policy(),
+            "test".to_string(),
+            "http://test".to_string(),
+            "test-model".to_string(),
+            0.7,
+            vec![],
+            None,
+        );
+        let long = "x".repeat(4_000); // ~1000 tokens
+        for _ in 0..4 {
+            agent.history.push(make_chat("user", &long));
+            agent.history.push(make_chat("assistant", &long));
+        }
+        // 默认窗口下 8 条长消息不触发
+        agent.compact_history_if_needed().await;
+        assert_eq!(agent.history.len(), 8);
+
+        agent.set_context_window(8_000);
+        agent.compact_history_if_needed().await;
+        assert!(agent.history.len() < 8);
+        if let ConversationMessage::Chat(cm) = &agent.history[0] {
+            assert!(cm.content.contains("对话摘要"));
+        } else {
+            panic!("第一条应该是摘要 Chat 消息");
+        }
+    }
+
     #[tokio::test]
     async fn compaction_fallback_to_trim_on_llm_failure() {
         // LLM 返回空响应 → 触发 fallback trim_history
@@ -2523,6 +4810,7 @@ mod tests {
             text: None, // 空响应触发 summarize_history 报错
             reasoning_content: None,
             tool_calls: vec![],
+            provenance: None,
         };
         let provider = MockProvider::new(vec![empty_response]);
         let mut agent = Agent::new(
@@ -2550,6 +4838,7 @@ mod tests {
             text: Some("对话摘要：早期上下文。".to_string()),
             reasoning_content: None,
             tool_calls: vec![],
+            provenance: None,
         };
         let provider = MockProvider::new(vec![summary_response]);
         let mut agent = Agent::new(
@@ -2577,104 +4866,602 @@ mod tests {
             })
             .collect();
 
-        agent.compact_history_if_needed().await;
+        agent.compact_history_if_needed().await;
+
+        // 最近 10 条应在压缩后 history 的末尾
+        let after_len = agent.history.len();
+        let recent: Vec<String> = agent.history[(after_len - 10)..]
+            .iter()
+            .map(|m| {
+                if let ConversationMessage::Chat(cm) = m {
+                    cm.content.clone()
+                } else {
+                    String::new()
+                }
+            })
+            .collect();
+        assert_eq!(last_10, recent);
+    }
+
+    // --- find_safe_window_end 测试 ---
+
+    #[test]
+    fn safe_window_end_stops_before_tool_result() {
+        let history = vec![
+            make_chat("user", "请执行命令"),
+            make_chat("assistant", "好的"),
+            ConversationMessage::AssistantToolCalls {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "1".into(),
+                    name: "shell".into(),
+                    arguments: serde_json::json!({}),
+                }],
+                turn: 0,
+            },
+            ConversationMessage::ToolResult {
+                tool_call_id: "1".into(),
+                content: "结果".into(),
+                turn: 0,
+            },
+            make_chat("user", "谢谢"),
+        ];
+        // ideal_end=3 时应退到 2（Chat 消息后），不截断 ToolCalls+ToolResult 对
+        let end = find_safe_window_end(&history, 3);
+        assert!(end <= 2);
+    }
+
+    #[test]
+    fn safe_window_end_all_chat_messages() {
+        let history: Vec<ConversationMessage> = (0..5)
+            .map(|i| make_chat("user", &format!("msg {}", i)))
+            .collect();
+        assert_eq!(find_safe_window_end(&history, 3), 3);
+    }
+
+    #[test]
+    fn safe_window_end_cuts_at_turn_boundary() {
+        let tool_call = |id: &str, turn: u64| ConversationMessage::AssistantToolCalls {
+            text: None,
+            reasoning_content: None,
+            tool_calls: vec![ToolCall {
+                id: id.into(),
+                name: "shell".into(),
+                arguments: serde_json::json!({}),
+            }],
+            turn,
+        };
+        let tool_result = |id: &str, turn: u64| ConversationMessage::ToolResult {
+            tool_call_id: id.into(),
+            content: "ok".into(),
+            turn,
+        };
+        let tagged = |role: &str, content: &str, turn: u64| {
+            let mut msg = make_chat(role, content);
+            msg.set_turn(turn);
+            msg
+        };
+        let history = vec![
+            tagged("user", "第一轮", 1),
+            tagged("assistant", "好的", 1),
+            tagged("user", "第二轮", 2),
+            tool_call("1", 2),
+            tool_result("1", 2),
+            // 同一 Turn 内 Chat 之后仍不应切割
+            tagged("assistant", "中间说明", 2),
+            tool_call("2", 2),
+            tool_result("2", 2),
+            tagged("assistant", "完成", 2),
+            tagged("user", "第三轮", 3),
+        ];
+        assert_eq!(find_safe_window_end(&history, 7), 2);
+        assert_eq!(find_safe_window_end(&history, 9), 9);
+        assert_eq!(find_safe_window_end(&history, 100), 10);
+    }
+
+    // --- format_history_for_summary 测试 ---
+
+    #[test]
+    fn format_skips_system_messages() {
+        let messages = vec![
+            make_chat("system", "系统 prompt"),
+            make_chat("user", "你好"),
+            make_chat("assistant", "你好！"),
+        ];
+        let output = format_history_for_summary(&messages);
+        assert!(!output.contains("系统 prompt"));
+        assert!(output.contains("你好"));
+        assert!(output.contains("助手"));
+    }
+
+    #[test]
+    fn format_truncates_long_content() {
+        let long_content = "X".repeat(1000);
+        let messages = vec![make_chat("user", &long_content)];
+        let output = format_history_for_summary(&messages);
+        assert!(output.len() < 600); // 500字符截断 + 标签
+    }
+
+    #[test]
+    fn format_includes_tool_call_names() {
+        let messages = vec![
+            ConversationMessage::AssistantToolCalls {
+                text: Some("我来执行".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "1".into(),
+                    name: "shell".into(),
+                    arguments: serde_json::json!({}),
+                }],
+                turn: 0,
+            },
+            ConversationMessage::ToolResult {
+                tool_call_id: "1".into(),
+                content: "output".into(),
+                turn: 0,
+            },
+        ];
+        let output = format_history_for_summary(&messages);
+        assert!(output.contains("shell"));
+        assert!(output.contains("工具调用"));
+    }
+
+    #[test]
+    fn format_includes_per_turn_tool_outcomes() {
+        let call = |id: &str| ToolCall {
+            id: id.into(),
+            name: "shell".into(),
+            arguments: serde_json::json!({}),
+        };
+        let result = |id: &str, content: &str| ConversationMessage::ToolResult {
+            tool_call_id: id.into(),
+            content: content.into(),
+            turn: 0,
+        };
+        let messages = vec![
+            make_chat("user", "跑一下测试"),
+            ConversationMessage::AssistantToolCalls {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![call("1"), call("2")],
+                turn: 0,
+            },
+            result("1", "ok"),
+            result("2", "[失败] exit code 1"),
+            ConversationMessage::AssistantToolCalls {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![call("3")],
+                turn: 0,
+            },
+            result("3", "ok"),
+            make_chat("assistant", "有一个失败"),
+            make_chat("user", "谢谢"),
+            make_chat("assistant", "不客气"),
+        ];
+        let output = format_history_for_summary(&messages);
+        assert!(output.contains("[本轮工具]: 3 次调用，1 次失败"));
+        // 没有工具调用的 Turn 不输出统计
+        assert_eq!(output.matches("[本轮工具]").count(), 1);
+    }
+
+    // --- routine_name / build_system_prompt 测试 ---
+
+    /// 记录 store 内容的 Memory（检查写入记忆的对话摘要）
+    #[derive(Default)]
+    struct CapturingMemory(std::sync::Mutex<Vec<String>>);
+
+    #[async_trait::async_trait]
+    impl Memory for CapturingMemory {
+        async fn store(&self, _key: &str, content: &str, _category: MemoryCategory) -> Result<()> {
+            self.0.lock().unwrap().push(content.to_string());
+            Ok(())
+        }
+        async fn recall(&self, _query: &str, _limit: usize) -> Result<Vec<MemoryEntry>> {
+            Ok(vec![])
+        }
+        async fn forget(&self, _key: &str) -> Result<bool> {
+            Ok(false)
+        }
+        async fn count(&self) -> Result<usize> {
+            Ok(self.0.lock().unwrap().len())
+        }
+    }
+
+    #[tokio::test]
+    async fn conversation_summary_is_scrubbed_but_reply_is_not() {
+        const KEY: &str = "sk-proj1234567890abcdef";
+        let memory = Arc::new(CapturingMemory::default());
+        let reply = format!("好的，已记下你的 key: {}", KEY);
+        let text = |content: &str| ChatResponse {
+            text: Some(content.to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        };
+        let mut agent = Agent::new(
+            Box::new(MockProvider::new(vec![routed_direct(), text(&reply)])),
+            vec![],
+            Box::new(memory.clone() as Arc<dyn Memory>),
+            test_policy(),
+            "test".into(),
+            "http://test".into(),
+            "model".into(),
+            0.7,
+            vec![],
+            None,
+        );
+
+        let output = agent
+            .process_message(&format!("帮我记一下 OPENAI_API_KEY={}", KEY))
+            .await
+            .unwrap();
+        assert_eq!(output, reply);
+        let stored = memory.0.lock().unwrap().clone();
+        assert_eq!(stored.len(), 1);
+        assert!(!stored[0].contains(KEY), "{}", stored[0]);
+        assert!(stored[0].contains("sk-p***"), "{}", stored[0]);
+
+        // scrub = false：原文保存
+        agent.set_summary_scrubber(None);
+        memory.0.lock().unwrap().clear();
+        agent.provider = Box::new(MockProvider::new(vec![routed_direct(), text("ok")]));
+        agent.process_message(KEY).await.unwrap();
+        assert!(memory.0.lock().unwrap()[0].contains(KEY));
+    }
+
+    #[tokio::test]
+    async fn only_useful_turns_produce_conversation_memories() {
+        let memory = Arc::new(CapturingMemory::default());
+        let text = |content: &str| ChatResponse {
+            text: Some(content.to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        };
+        let answer = "工作区里有 Cargo.toml、src/ 和 tests/ 三项，是一个标准的 Rust 项目布局：\
+                      src/ 放库与二进制源码，tests/ 放集成测试，Cargo.toml 声明依赖与特性。";
+        let mut agent = Agent::new(
+            Box::new(MockProvider::new(vec![
+                // 1. 寒暄 → 跳过
+                routed_direct(),
+                text("好的"),
+                // 2. 工具回合（双方都短）→ 保存，带工具与结果
+                routed_direct(),
+                tool_call("c1", "shell", serde_json::json!({"command": "ls"})),
+                text("有三项"),
+                // 3. 空回复 → 跳过
+                routed_direct(),
+                text(""),
+                // 4. 普通问答 → 保存
+                routed_direct(),
+                text(answer),
+                // 5. 与上一条只差标点 → 跳过
+                routed_direct(),
+                text(answer),
+            ])),
+            vec![Box::new(MockTool {
+                tool_name: "shell".to_string(),
+                result: "Cargo.toml\nsrc\ntests".to_string(),
+            })],
+            Box::new(memory.clone() as Arc<dyn Memory>),
+            test_policy(),
+            "test".into(),
+            "http://test".into(),
+            "model".into(),
+            0.7,
+            vec![],
+            None,
+        );
+
+        for msg in [
+            "ok",
+            "列目录",
+            "在吗？",
+            "这个项目的结构？",
+            "这个项目的结构",
+        ] {
+            agent.process_message(msg).await.unwrap();
+        }
+        let stored = memory.0.lock().unwrap().clone();
+        assert_eq!(stored.len(), 2, "{:?}", stored);
+        assert_eq!(
+            stored[0],
+            "User: 列目录\nTools: shell(ok)\nOutcome: Cargo.toml\nAssistant: 有三项"
+        );
+        assert!(stored[1].starts_with("User: 这个项目的结构？\nAssistant: "));
+
+        // store_conversations = "off"：一条都不写
+        memory.0.lock().unwrap().clear();
+        agent.set_conversation_filter(ConversationFilter::from_config(
+            &crate::config::MemoryConfig {
+                store_conversations: crate::config::StoreConversations::Off,
+                ..Default::default()
+            },
+        ));
+        agent.provider = Box::new(MockProvider::new(vec![routed_direct(), text(answer)]));
+        agent
+            .process_message("再介绍一下 tests 目录")
+            .await
+            .unwrap();
+        assert!(memory.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn pinned_provider_answers_one_turn_and_reports_provenance() {
+        let mut agent = make_agent_no_skills();
+        let pinned = crate::providers::ReliableProvider::new(
+            Box::new(MockProvider::new(vec![
+                routed_direct(),
+                ChatResponse {
+                    text: Some("来自 claude".to_string()),
+                    reasoning_content: None,
+                    tool_calls: vec![],
+                    provenance: None,
+                },
+            ])),
+            crate::providers::RetryConfig::default(),
+        )
+        .with_names(vec!["claude".to_string()])
+        .with_offline_state(Arc::new(crate::providers::OfflineState::new()));
+
+        let saved = agent.swap_provider((
+            Box::new(pinned),
+            "claude".to_string(),
+            "https://api.anthropic.com".to_string(),
+            "claude-sonnet".to_string(),
+        ));
+        assert_eq!(agent.process_message("hi").await.unwrap(), "来自 claude");
+        agent.swap_provider(saved);
+
+        assert_eq!((agent.provider_name(), agent.model()), ("test", "model"));
+        let provenance = agent.last_provenance().unwrap();
+        assert_eq!(
+            provenance.describe(true),
+            "answered by claude/claude-sonnet"
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_token_abandons_pending_provider_call() {
+        /// 永不返回的 Provider（模拟卡住的请求）
+        struct HangingProvider;
+
+        #[async_trait::async_trait]
+        impl Provider for HangingProvider {
+            async fn chat_with_tools(
+                &self,
+                _messages: &[ConversationMessage],
+                _tools: &[ToolSpec],
+                _model: &str,
+                _params: GenerationParams,
+            ) -> Result<ChatResponse> {
+                std::future::pending().await
+            }
+        }
+
+        let mut agent = make_agent_no_skills();
+        agent.swap_provider((
+            Box::new(HangingProvider),
+            "hang".to_string(),
+            String::new(),
+            "hang".to_string(),
+        ));
+        let token = CancellationToken::new();
+        agent.set_cancel_token(token.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            token.cancel();
+        });
+
+        let err = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            agent.process_message("hi"),
+        )
+        .await
+        .expect("cancellation should end the turn")
+        .unwrap_err();
+        assert_eq!(err.to_string(), CANCELLED_MESSAGE);
+    }
+
+    /// 按脚本依次返回结果（含错误）的 Provider，记录每次请求携带的消息条数
+    struct FallibleProvider {
+        results: std::sync::Mutex<Vec<Result<ChatResponse>>>,
+        message_counts: Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for FallibleProvider {
+        async fn chat_with_tools(
+            &self,
+            messages: &[ConversationMessage],
+            _tools: &[ToolSpec],
+            _model: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            self.message_counts.lock().unwrap().push(messages.len());
+            self.results.lock().unwrap().remove(0)
+        }
+    }
+
+    fn context_overflow() -> Result<ChatResponse> {
+        Err(eyre!(
+            "API 返回错误 400: This model's maximum context length is 8192 tokens"
+        ))
+    }
+
+    fn text_reply(text: &str) -> Result<ChatResponse> {
+        Ok(ChatResponse {
+            text: Some(text.to_string()),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        })
+    }
+
+    fn agent_with_results(
+        results: Vec<Result<ChatResponse>>,
+    ) -> (Agent, Arc<std::sync::Mutex<Vec<usize>>>) {
+        agent_with_provider_results(results, false)
+    }
 
-        // 最近 10 条应在压缩后 history 的末尾
-        let after_len = agent.history.len();
-        let recent: Vec<String> = agent.history[(after_len - 10)..]
-            .iter()
-            .map(|m| {
-                if let ConversationMessage::Chat(cm) = m {
-                    cm.content.clone()
-                } else {
-                    String::new()
-                }
-            })
-            .collect();
-        assert_eq!(last_10, recent);
+    /// `reliable`：像生产环境一样包在 ReliableProvider 里
+    fn agent_with_provider_results(
+        results: Vec<Result<ChatResponse>>,
+        reliable: bool,
+    ) -> (Agent, Arc<std::sync::Mutex<Vec<usize>>>) {
+        let message_counts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut agent = make_agent_no_skills();
+        let mut provider: Box<dyn Provider> = Box::new(FallibleProvider {
+            results: std::sync::Mutex::new(results),
+            message_counts: Arc::clone(&message_counts),
+        });
+        if reliable {
+            provider = Box::new(
+                crate::providers::ReliableProvider::new(
+                    provider,
+                    crate::providers::RetryConfig::default(),
+                )
+                .with_offline_state(Arc::new(crate::providers::OfflineState::new())),
+            );
+        }
+        agent.swap_provider((
+            provider,
+            "test".to_string(),
+            "http://test".to_string(),
+            "model".to_string(),
+        ));
+        fill_history(&mut agent, 3);
+        (agent, message_counts)
     }
 
-    // --- find_safe_window_end 测试 ---
+    #[tokio::test]
+    async fn context_overflow_compacts_history_and_retries_once() {
+        let (mut agent, message_counts) = agent_with_results(vec![
+            Ok(routed_direct()),
+            context_overflow(),
+            text_reply("对话摘要：用户问了三个问题。"),
+            text_reply("压缩后回答"),
+        ]);
 
-    #[test]
-    fn safe_window_end_stops_before_tool_result() {
-        let history = vec![
-            make_chat("user", "请执行命令"),
-            make_chat("assistant", "好的"),
-            ConversationMessage::AssistantToolCalls {
-                text: None,
-                reasoning_content: None,
-                tool_calls: vec![ToolCall {
-                    id: "1".into(),
-                    name: "shell".into(),
-                    arguments: serde_json::json!({}),
-                }],
-            },
-            ConversationMessage::ToolResult {
-                tool_call_id: "1".into(),
-                content: "结果".into(),
-            },
-            make_chat("user", "谢谢"),
-        ];
-        // ideal_end=3 时应退到 2（Chat 消息后），不截断 ToolCalls+ToolResult 对
-        let end = find_safe_window_end(&history, 3);
-        assert!(end <= 2);
+        let reply = agent.process_message("继续").await.unwrap();
+        assert_eq!(reply, "压缩后回答");
+        // 路由 → 超限（system + 6 条历史 + 本条）→ 摘要 → 重试（system + 摘要 + 早期剩余 + 本条）
+        let counts = message_counts.lock().unwrap().clone();
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts[1], 8);
+        assert!(counts[3] < counts[1], "{:?}", counts);
+        match &agent.history()[0] {
+            ConversationMessage::Chat(m) => {
+                assert!(
+                    m.content.starts_with("[对话摘要 - 早期上下文]"),
+                    "{}",
+                    m.content
+                )
+            }
+            other => panic!("expected summary, got {:?}", other),
+        }
     }
 
-    #[test]
-    fn safe_window_end_all_chat_messages() {
-        let history: Vec<ConversationMessage> = (0..5)
-            .map(|i| make_chat("user", &format!("msg {}", i)))
-            .collect();
-        assert_eq!(find_safe_window_end(&history, 3), 3);
+    #[tokio::test]
+    async fn context_overflow_is_recognized_through_reliable_provider() {
+        let (mut agent, message_counts) = agent_with_provider_results(
+            vec![
+                Ok(routed_direct()),
+                context_overflow(),
+                text_reply("对话摘要：用户问了三个问题。"),
+                text_reply("压缩后回答"),
+            ],
+            true,
+        );
+
+        assert_eq!(agent.process_message("继续").await.unwrap(), "压缩后回答");
+        // 超限错误不重试：路由 → 超限 → 摘要 → 重试
+        assert_eq!(message_counts.lock().unwrap().len(), 4);
     }
 
-    // --- format_history_for_summary 测试 ---
+    #[tokio::test]
+    async fn context_overflow_after_compaction_returns_friendly_error() {
+        let (mut agent, _) = agent_with_results(vec![
+            Ok(routed_direct()),
+            context_overflow(),
+            text_reply("对话摘要：用户问了三个问题。"),
+            context_overflow(),
+        ]);
 
-    #[test]
-    fn format_skips_system_messages() {
-        let messages = vec![
-            make_chat("system", "系统 prompt"),
-            make_chat("user", "你好"),
-            make_chat("assistant", "你好！"),
-        ];
-        let output = format_history_for_summary(&messages);
-        assert!(!output.contains("系统 prompt"));
-        assert!(output.contains("你好"));
-        assert!(output.contains("助手"));
+        let err = agent.process_message("继续").await.unwrap_err().to_string();
+        assert!(err.starts_with("The conversation is too long"), "{}", err);
+        assert!(!err.contains("API 返回错误"), "{}", err);
     }
 
-    #[test]
-    fn format_truncates_long_content() {
-        let long_content = "X".repeat(1000);
-        let messages = vec![make_chat("user", &long_content)];
-        let output = format_history_for_summary(&messages);
-        assert!(output.len() < 600); // 500字符截断 + 标签
+    fn branching_agent(responses: Vec<ChatResponse>) -> Agent {
+        let mut agent = make_agent_no_skills();
+        agent.switch_provider(
+            Box::new(MockProvider::new(responses)),
+            "test".into(),
+            "http://test".into(),
+            "model".into(),
+        );
+        fill_history(&mut agent, 2);
+        agent
     }
 
-    #[test]
-    fn format_includes_tool_call_names() {
-        let messages = vec![
-            ConversationMessage::AssistantToolCalls {
-                text: Some("我来执行".to_string()),
-                reasoning_content: None,
-                tool_calls: vec![ToolCall {
-                    id: "1".into(),
-                    name: "shell".into(),
-                    arguments: serde_json::json!({}),
-                }],
-            },
-            ConversationMessage::ToolResult {
-                tool_call_id: "1".into(),
-                content: "output".into(),
-            },
-        ];
-        let output = format_history_for_summary(&messages);
-        assert!(output.contains("shell"));
-        assert!(output.contains("工具调用"));
+    fn history_json(agent: &Agent) -> String {
+        serde_json::to_string(agent.history()).unwrap()
     }
 
-    // --- routine_name / build_system_prompt 测试 ---
+    #[tokio::test]
+    async fn dropped_branch_leaves_main_history_untouched() {
+        let mut agent = branching_agent(vec![
+            routed_direct(),
+            text_reply("改用 X 需要迁移数据").unwrap(),
+        ]);
+        let main = history_json(&agent);
+
+        let branch = agent.fork(Some("try-x".to_string())).unwrap();
+        assert_eq!(agent.branch_name(), Some("try-x"));
+        assert!(agent.fork(None).is_err(), "分支不支持嵌套");
+        agent.process_message("如果改用 X 会怎样？").await.unwrap();
+        assert_eq!(agent.history().len(), 6);
+
+        agent.drop_branch(branch);
+        assert_eq!(history_json(&agent), main);
+        assert_eq!(agent.branch_name(), None);
+    }
+
+    #[tokio::test]
+    async fn merged_branch_adds_exactly_one_summary_message() {
+        let mut agent = branching_agent(vec![
+            routed_direct(),
+            text_reply("改用 X 需要迁移数据").unwrap(),
+            text_reply("对话摘要：改用 X 可行，但需要先迁移数据。").unwrap(),
+        ]);
+        let main_len = agent.history().len();
+
+        let branch = agent.fork(None).unwrap();
+        assert_eq!(branch.name(), branch::DEFAULT_BRANCH_NAME);
+        agent.process_message("如果改用 X 会怎样？").await.unwrap();
+        let summary = agent.summarize_branch(&branch).await.unwrap();
+        agent.merge_branch(branch, summary);
+
+        assert_eq!(agent.history().len(), main_len + 1);
+        match agent.history().last().unwrap() {
+            ConversationMessage::Chat(m) => {
+                assert_eq!(m.role, "system");
+                assert_eq!(
+                    m.content,
+                    "[分支 branch 的结论]\n对话摘要：改用 X 可行，但需要先迁移数据。"
+                );
+            }
+            other => panic!("expected summary, got {:?}", other),
+        }
+        // 分支里没有新对话：合并不追加任何消息
+        let branch = agent.fork(None).unwrap();
+        assert!(agent.summarize_branch(&branch).await.unwrap().is_none());
+        agent.merge_branch(branch, None);
+        assert_eq!(agent.history().len(), main_len + 1);
+    }
 
     fn make_agent_no_skills() -> Agent {
         Agent::new(
@@ -2691,6 +5478,133 @@ mod tests {
         )
     }
 
+    fn tool_call_exchange(id: &str) -> [ConversationMessage; 2] {
+        [
+            ConversationMessage::AssistantToolCalls {
+                text: Some("查看一下".to_string()),
+                reasoning_content: Some("需要 ls".to_string()),
+                tool_calls: vec![ToolCall {
+                    id: id.to_string(),
+                    name: "shell".to_string(),
+                    arguments: serde_json::json!({"command": "ls"}),
+                }],
+                turn: 0,
+            },
+            ConversationMessage::ToolResult {
+                tool_call_id: id.to_string(),
+                content: "file.txt".to_string(),
+                turn: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn history_save_then_load_round_trips_tool_calls() {
+        let mut history = vec![make_chat("user", "列出文件")];
+        history.extend(tool_call_exchange("call_1"));
+        history.push(make_chat("assistant", "有 file.txt"));
+        history.push(make_chat("user", "再看一次"));
+        history.extend(tool_call_exchange("call_2"));
+        history.push(make_chat("assistant", "还是 file.txt"));
+
+        let mut agent = make_agent_no_skills();
+        agent.set_history(history);
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("history.json");
+        std::fs::write(&path, agent.export_history().unwrap()).unwrap();
+
+        let mut restored = make_agent_no_skills();
+        let dropped = restored
+            .import_history(&std::fs::read_to_string(&path).unwrap())
+            .unwrap();
+        assert_eq!(dropped, 0);
+        assert_eq!(
+            serde_json::to_value(restored.history()).unwrap(),
+            serde_json::to_value(agent.history()).unwrap()
+        );
+        // Turn 标记随 history 保存，导入后继续递增
+        assert_eq!(restored.current_turn, 2);
+    }
+
+    #[test]
+    fn history_load_sanitizes_orphan_tool_results() {
+        let [calls, result] = tool_call_exchange("call_1");
+        let json = serde_json::to_string(&vec![
+            result.clone(),
+            make_chat("user", "hi"),
+            calls,
+            result,
+            make_chat("assistant", "ok"),
+        ])
+        .unwrap();
+
+        let mut agent = make_agent_no_skills();
+        assert_eq!(agent.import_history(&json).unwrap(), 1);
+        let kinds: Vec<&str> = agent
+            .history()
+            .iter()
+            .map(|m| match m {
+                ConversationMessage::Chat(c) => c.role.as_str(),
+                ConversationMessage::AssistantToolCalls { .. } => "tool_calls",
+                ConversationMessage::ToolResult { .. } => "tool_result",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["user", "tool_calls", "tool_result", "assistant"]
+        );
+
+        assert!(agent.import_history("not json").is_err());
+    }
+
+    #[test]
+    fn history_with_skipped_messages_drops_broken_tool_blocks() {
+        let [calls_1, result_1] = tool_call_exchange("call_1");
+        // call_2 的 AssistantToolCalls 解析失败被跳过，只剩结果
+        let [_, result_2] = tool_call_exchange("call_2");
+        // call_3 / call_4 同一块，call_4 的结果被跳过
+        let [mut calls_34, result_3] = tool_call_exchange("call_3");
+        if let ConversationMessage::AssistantToolCalls { tool_calls, .. } = &mut calls_34 {
+            let mut second = tool_calls[0].clone();
+            second.id = "call_4".to_string();
+            tool_calls.push(second);
+        }
+
+        let mut agent = make_agent_no_skills();
+        agent.set_history(vec![
+            make_chat("user", "列出文件"),
+            calls_1,
+            result_1,
+            result_2,
+            make_chat("assistant", "有 file.txt"),
+            make_chat("user", "再看两次"),
+            calls_34,
+            result_3,
+            make_chat("assistant", "还是 file.txt"),
+        ]);
+
+        let kinds: Vec<String> = agent
+            .history()
+            .iter()
+            .map(|m| match m {
+                ConversationMessage::Chat(c) => c.role.clone(),
+                ConversationMessage::AssistantToolCalls { .. } => "tool_calls".to_string(),
+                ConversationMessage::ToolResult { tool_call_id, .. } => tool_call_id.clone(),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "user",
+                "tool_calls",
+                "call_1",
+                "assistant",
+                "user",
+                "assistant"
+            ]
+        );
+    }
+
     #[test]
     fn routine_system_prompt_injected_when_routine_name_set() {
         let mut agent = make_agent_no_skills();
@@ -2754,6 +5668,7 @@ mod tests {
             text: Some("对话摘要：用户询问了一些问题。".to_string()),
             reasoning_content: None,
             tool_calls: vec![],
+            provenance: None,
         }]);
         let agent = Agent::new(
             Box::new(provider),
@@ -2772,6 +5687,226 @@ mod tests {
         assert!(result.contains("摘要"));
     }
 
+    // --- 辅助模型（[agent.routing] / [agent.summary]）测试 ---
+
+    /// 记录每次调用的 (标签, 模型名)，按脚本依次返回响应（Err 表示该次调用失败）
+    struct RecordingProvider {
+        label: &'static str,
+        calls: std::sync::Arc<std::sync::Mutex<Vec<(String, String)>>>,
+        responses: std::sync::Mutex<Vec<std::result::Result<String, String>>>,
+    }
+
+    impl RecordingProvider {
+        fn new(
+            label: &'static str,
+            calls: &std::sync::Arc<std::sync::Mutex<Vec<(String, String)>>>,
+            responses: Vec<std::result::Result<&str, &str>>,
+        ) -> Self {
+            Self {
+                label,
+                calls: calls.clone(),
+                responses: std::sync::Mutex::new(
+                    responses
+                        .into_iter()
+                        .map(|r| r.map(String::from).map_err(String::from))
+                        .collect(),
+                ),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for RecordingProvider {
+        async fn chat_with_tools(
+            &self,
+            _messages: &[ConversationMessage],
+            _tools: &[ToolSpec],
+            model: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push((self.label.to_string(), model.to_string()));
+            let next = self.responses.lock().unwrap().remove(0);
+            match next {
+                Ok(text) => Ok(ChatResponse {
+                    text: Some(text),
+                    reasoning_content: None,
+                    tool_calls: vec![],
+                    provenance: None,
+                }),
+                Err(e) => Err(color_eyre::eyre::eyre!("{}", e)),
+            }
+        }
+    }
+
+    fn agent_with(primary: RecordingProvider) -> Agent {
+        Agent::new(
+            Box::new(primary),
+            vec![],
+            Box::new(MockMemory),
+            test_policy(),
+            "main".into(),
+            "h".into(),
+            "big-model".into(),
+            0.7,
+            vec![],
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn routing_uses_secondary_provider_and_phase2_uses_primary() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let primary = RecordingProvider::new("primary", &calls, vec![Ok("主模型回复")]);
+        let secondary = RecordingProvider::new(
+            "secondary",
+            &calls,
+            vec![Ok(r#"{"skills": [], "direct": true}"#)],
+        );
+        let mut agent = agent_with(primary);
+        agent.set_routing_model(Some(AuxModel::new(
+            Box::new(secondary),
+            "cheap",
+            "small-model",
+        )));
+
+        let reply = agent.process_message("你好").await.unwrap();
+        assert_eq!(reply, "主模型回复");
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                ("secondary".to_string(), "small-model".to_string()),
+                ("primary".to_string(), "big-model".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn routing_falls_back_to_primary_when_secondary_fails() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let primary = RecordingProvider::new(
+            "primary",
+            &calls,
+            vec![Ok(r#"{"skills": [], "direct": true}"#), Ok("主模型回复")],
+        );
+        let secondary = RecordingProvider::new("secondary", &calls, vec![Err("503")]);
+        let mut agent = agent_with(primary);
+        agent.set_routing_model(Some(AuxModel::new(
+            Box::new(secondary),
+            "cheap",
+            "small-model",
+        )));
+
+        let reply = agent.process_message("你好").await.unwrap();
+        assert_eq!(reply, "主模型回复");
+        let labels: Vec<String> = calls.lock().unwrap().iter().map(|c| c.0.clone()).collect();
+        assert_eq!(labels, vec!["secondary", "primary", "primary"]);
+    }
+
+    #[tokio::test]
+    async fn model_only_routing_follows_switched_provider() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let primary = RecordingProvider::new("primary", &calls, vec![]);
+        let switched = RecordingProvider::new(
+            "switched",
+            &calls,
+            vec![Ok(r#"{"skills": [], "direct": true}"#), Ok("切换后回复")],
+        );
+        let mut config = crate::config::Config::default();
+        config.agent.routing = Some(crate::config::AuxModelConfig {
+            provider: None,
+            model: Some("small-model".to_string()),
+        });
+        let mut agent = agent_with(primary);
+        agent.configure_aux_models(&config);
+
+        // `/switch` 之后，省略 provider 的路由模型也走新的主 Provider
+        agent.switch_provider(
+            Box::new(switched),
+            "other".into(),
+            "h2".into(),
+            "other-model".into(),
+        );
+        let reply = agent.process_message("你好").await.unwrap();
+        assert_eq!(reply, "切换后回复");
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                ("switched".to_string(), "small-model".to_string()),
+                ("switched".to_string(), "other-model".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn summary_uses_summary_model() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let primary = RecordingProvider::new("primary", &calls, vec![]);
+        let secondary =
+            RecordingProvider::new("secondary", &calls, vec![Ok("对话摘要：便宜模型生成")]);
+        let mut agent = agent_with(primary);
+        agent.set_summary_model(Some(AuxModel::new(
+            Box::new(secondary),
+            "cheap",
+            "small-model",
+        )));
+
+        let messages = vec![make_chat("user", "你好")];
+        let result = agent.summarize_history(&messages).await.unwrap();
+        assert!(result.contains("便宜模型"));
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![("secondary".to_string(), "small-model".to_string())]
+        );
+    }
+
+    // --- Skill 使用统计测试 ---
+
+    #[tokio::test]
+    async fn skill_usage_counts_routed_and_manual_separately() {
+        use crate::skills::usage::SqliteSkillUsage;
+        use crate::skills::SkillSource;
+
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let primary = RecordingProvider::new(
+            "primary",
+            &calls,
+            vec![
+                Ok(r#"{"skills": ["code-review"], "direct": false}"#),
+                Ok("审查完成"),
+                Ok(r#"{"skills": [], "direct": false, "question": "审查哪个文件？"}"#),
+            ],
+        );
+        let mut agent = agent_with(primary);
+        agent.skills_meta = ["code-review", "rust-dev"]
+            .iter()
+            .map(|name| SkillMeta {
+                name: name.to_string(),
+                description: String::new(),
+                tags: vec![],
+                source: SkillSource::BuiltIn,
+                path: None,
+            })
+            .collect();
+        let usage = Arc::new(SqliteSkillUsage::in_memory().unwrap());
+        agent.set_skill_usage_recorder(usage.clone());
+
+        agent.process_message("review 一下").await.unwrap();
+        agent.process_message("再看看").await.unwrap();
+        agent.inject_skill_context("rust-dev", "指令");
+        agent.inject_skill_context("not-a-skill", "指令");
+
+        let stats = usage.stats().unwrap();
+        let review = stats.iter().find(|s| s.skill == "code-review").unwrap();
+        let rust = stats.iter().find(|s| s.skill == "rust-dev").unwrap();
+        assert_eq!((review.routed, review.manual), (1, 0));
+        assert_eq!((rust.routed, rust.manual), (0, 1));
+        assert_eq!((review.routes, review.clarifications), (2, 1));
+        assert!(stats.iter().all(|s| s.skill != "not-a-skill"));
+    }
+
     // --- P7-3: 有 required 字段的 Mock 工具 ---
 
     struct StrictMockTool {
@@ -2876,6 +6011,7 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // Phase 2 iter 1: 缺少 "query"
             ChatResponse {
@@ -2886,6 +6022,7 @@ mod tests {
                     name: "strict_tool".to_string(),
                     arguments: serde_json::json!({}),
                 }],
+                provenance: None,
             },
             // Phase 2 iter 2: 提供正确参数（看到 schema 提示后）
             ChatResponse {
@@ -2896,12 +6033,14 @@ mod tests {
                     name: "strict_tool".to_string(),
                     arguments: serde_json::json!({"query": "hello"}),
                 }],
+                provenance: None,
             },
             // Phase 2 iter 3: 最终回复
             ChatResponse {
                 text: Some("搜索完成".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -2946,6 +6085,7 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // Phase 2 iter 1: 参数完整
             ChatResponse {
@@ -2956,12 +6096,14 @@ mod tests {
                     name: "strict_tool".to_string(),
                     arguments: serde_json::json!({"query": "test"}),
                 }],
+                provenance: None,
             },
             // Phase 2 iter 2: 最终回复
             ChatResponse {
                 text: Some("正常完成".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -3003,6 +6145,7 @@ mod tests {
                 text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
             // Phase 2 iter 1: 缺参数 → P7-3 触发
             ChatResponse {
@@ -3013,6 +6156,7 @@ mod tests {
                     name: "strict_tool".to_string(),
                     arguments: serde_json::json!({}),
                 }],
+                provenance: None,
             },
             // Phase 2 iter 2: 仍缺参数 → P7-3 不再触发（已在 expanded_tools），直接执行
             ChatResponse {
@@ -3023,12 +6167,14 @@ mod tests {
                     name: "strict_tool".to_string(),
                     arguments: serde_json::json!({}),
                 }],
+                provenance: None,
             },
             // Phase 2 iter 3: 最终回复
             ChatResponse {
                 text: Some("完成".to_string()),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             },
         ]);
 
@@ -3057,4 +6203,75 @@ mod tests {
         }).count();
         assert_eq!(hint_count, 1, "P7-3 每工具每轮只触发一次");
     }
+
+    #[tokio::test]
+    async fn goal_survives_restart_and_is_injected_into_prompt() {
+        let tmp = tempfile::tempdir().unwrap();
+        let memory = crate::memory::SqliteMemory::open(tmp.path()).unwrap();
+        let provider = MockProvider::new(vec![
+            ChatResponse {
+                text: Some(r#"{"skills": [], "direct": true}"#.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+            ChatResponse {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_goal".to_string(),
+                    name: goal::GOAL_TOOL_NAME.to_string(),
+                    arguments: serde_json::json!({
+                        "completed": "replaced anyhow in src/",
+                        "plan": "fix the tests"
+                    }),
+                }],
+                provenance: None,
+            },
+            ChatResponse {
+                text: Some("继续处理测试".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            },
+        ]);
+        let mut agent = agent_with_tools(Box::new(provider), vec![]);
+        assert!(!agent
+            .build_tool_specs("")
+            .iter()
+            .any(|s| s.name == goal::GOAL_TOOL_NAME));
+        agent.set_goal(Some(SessionGoal::new("migrate from anyhow to color_eyre")));
+        assert!(agent
+            .build_tool_specs("")
+            .iter()
+            .any(|s| s.name == goal::GOAL_TOOL_NAME));
+
+        agent.process_message("continue").await.unwrap();
+        memory
+            .save_conversation_history("s1", agent.history())
+            .await
+            .unwrap();
+        memory.save_session_goal("s1", agent.goal()).await.unwrap();
+        drop(agent);
+
+        // 模拟重启：新 Agent 恢复同一 session
+        let mut restored = agent_with_tools(Box::new(MockProvider::new(vec![])), vec![]);
+        restored.set_history(memory.load_conversation_history("s1").await.unwrap());
+        restored.set_goal(memory.load_session_goal("s1").await.unwrap());
+        let prompt = restored.system_prompt_in(crate::i18n::Language::English, &[]);
+        assert!(prompt.contains("[Current Goal]\nmigrate from anyhow to color_eyre"));
+        assert!(prompt.contains("- replaced anyhow in src/"));
+        assert!(prompt.contains("Plan: fix the tests"));
+        assert!(restored
+            .system_prompt_in(crate::i18n::Language::Chinese, &[])
+            .contains("[当前目标]\nmigrate from anyhow to color_eyre"));
+
+        // /goal done：清除后不再注入
+        restored.set_goal(None);
+        memory.save_session_goal("s1", None).await.unwrap();
+        assert!(memory.load_session_goal("s1").await.unwrap().is_none());
+        assert!(!restored
+            .system_prompt_in(crate::i18n::Language::English, &[])
+            .contains("[Current Goal]"));
+    }
 }
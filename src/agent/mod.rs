@@ -1,5 +1,29 @@
+pub mod approval;
+pub mod aux_model;
+pub mod branch;
+pub mod builder;
+pub mod changes;
+pub mod conversation_memory;
+pub mod dedup;
+pub mod factory;
+pub mod goal;
 pub mod identity;
 pub mod loop_;
+pub mod pin;
+pub mod project_init;
+pub mod prompts;
+pub mod suggestions;
+pub mod tokens;
 pub mod tool_groups;
+pub mod turns;
 
-pub use loop_::{Agent, ConfirmFn};
+pub use approval::{ApprovalPolicy, ConfirmFnApproval, Decision, NOTICE_ARG, PREVIEW_ARG};
+pub use aux_model::AuxModel;
+pub use branch::AgentBranch;
+pub use builder::AgentBuilder;
+pub use changes::ChangeSummary;
+pub use conversation_memory::ConversationFilter;
+pub use factory::{AgentFactory, ChangeSignal};
+pub use goal::SessionGoal;
+pub use loop_::{Agent, ConfirmFn, RichToolOutput, ToolFeedback, CANCELLED_MESSAGE};
+pub use pin::{parse_pin, ProviderPin};
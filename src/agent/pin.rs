@@ -0,0 +1,161 @@
+//! 单条消息固定 Provider：消息以 `@provider` 或 `@provider:model` 开头
+//!
+//! 例如 `@claude 解释一下这个 trait bound`：只有这一条消息改用 `[providers.claude]`，
+//! 仍经 ReliableProvider 重试，但不走 fallback 链（用户明确指定了谁来回答）。
+
+use color_eyre::eyre::{bail, Result};
+
+use crate::config::Config;
+use crate::providers::{OfflineState, Provider, ReliableProvider};
+
+/// 解析出的固定目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderPin {
+    pub provider: String,
+    /// 未写 `:model` 时使用该 Provider 配置的默认模型
+    pub model: Option<String>,
+}
+
+/// 解析消息开头的 `@provider[:model]`，返回固定目标和剩余消息
+///
+/// 名称只允许字母、数字、`-`、`_`、`.`；`@` 后紧跟空白、名称非法或没有正文时不视为固定。
+pub fn parse_pin(input: &str) -> Option<(ProviderPin, &str)> {
+    let rest = input.strip_prefix('@')?;
+    let (target, message) = rest.split_once(char::is_whitespace)?;
+    let message = message.trim();
+    if message.is_empty() {
+        return None;
+    }
+    let (provider, model) = match target.split_once(':') {
+        Some((provider, model)) => (provider, Some(model).filter(|m| !m.is_empty())),
+        None => (target, None),
+    };
+    let valid_name = !provider.is_empty()
+        && provider
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid_name {
+        return None;
+    }
+    Some((
+        ProviderPin {
+            provider: provider.to_string(),
+            model: model.map(str::to_string),
+        },
+        message,
+    ))
+}
+
+/// 已解析好的固定 Provider：`(provider, provider 名, base_url, 模型)`
+pub type PinnedProvider = (Box<dyn Provider>, String, String, String);
+
+/// 按配置构建固定的 Provider（只重试，不 fallback）；名称未配置时返回明确的错误
+pub fn resolve_pin(pin: &ProviderPin, config: &Config) -> Result<PinnedProvider> {
+    let Some(provider_config) = config.providers.get(&pin.provider) else {
+        let mut configured: Vec<&str> = config.providers.keys().map(String::as_str).collect();
+        configured.sort_unstable();
+        let configured = configured.join(", ");
+        if Config::get_language().is_english() {
+            bail!(
+                "Provider '{}' is not configured (configured: {})",
+                pin.provider,
+                configured
+            );
+        }
+        bail!(
+            "Provider '{}' 未配置（已配置: {}）",
+            pin.provider,
+            configured
+        );
+    };
+    let model = pin
+        .model
+        .clone()
+        .unwrap_or_else(|| provider_config.model.clone());
+    // 与 REPL 主 Provider 共用全局离线标记：离线时同样快速失败
+    let provider = Box::new(ReliableProvider::for_provider(
+        &pin.provider,
+        provider_config,
+        &config.reliability,
+        OfflineState::global(),
+    ));
+    Ok((
+        provider,
+        pin.provider.clone(),
+        provider_config.base_url.clone(),
+        model,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+
+    fn pin(provider: &str, model: Option<&str>) -> ProviderPin {
+        ProviderPin {
+            provider: provider.to_string(),
+            model: model.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_provider_and_optional_model() {
+        assert_eq!(
+            parse_pin("@claude explain this trait bound"),
+            Some((pin("claude", None), "explain this trait bound"))
+        );
+        assert_eq!(
+            parse_pin("@deepseek:deepseek-reasoner  想一想 "),
+            Some((pin("deepseek", Some("deepseek-reasoner")), "想一想"))
+        );
+        assert_eq!(parse_pin("@claude: hi"), Some((pin("claude", None), "hi")));
+    }
+
+    #[test]
+    fn ordinary_messages_are_not_pins() {
+        assert_eq!(parse_pin("explain @claude"), None);
+        assert_eq!(parse_pin("@claude"), None);
+        assert_eq!(parse_pin("@claude   "), None);
+        assert_eq!(parse_pin("@ claude hi"), None);
+        assert_eq!(parse_pin("@user@example.com wrote this"), None);
+        assert_eq!(parse_pin("@src/main.rs 看看这个文件"), None);
+    }
+
+    #[test]
+    fn unknown_provider_is_a_clear_error() {
+        let mut config = Config::default();
+        config.providers.insert(
+            "deepseek".to_string(),
+            ProviderConfig {
+                base_url: "https://api.deepseek.com/v1".to_string(),
+                api_key: "k".to_string(),
+                model: "deepseek-chat".to_string(),
+                auth_style: None,
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        let err = resolve_pin(&pin("claude", None), &config)
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            err,
+            "Provider 'claude' is not configured (configured: deepseek)"
+        );
+
+        let (_, name, base_url, model) = resolve_pin(&pin("deepseek", None), &config).unwrap();
+        assert_eq!(
+            (name.as_str(), base_url.as_str(), model.as_str()),
+            ("deepseek", "https://api.deepseek.com/v1", "deepseek-chat")
+        );
+        let (_, _, _, model) =
+            resolve_pin(&pin("deepseek", Some("deepseek-reasoner")), &config).unwrap();
+        assert_eq!(model, "deepseek-reasoner");
+    }
+}
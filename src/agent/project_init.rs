@@ -0,0 +1,604 @@
+//! 项目初始化（`rrclaw init-project` / `/init-project`）
+//!
+//! 检测当前 workspace 的工具链（Cargo.toml / package.json / pyproject.toml）、CI 配置和测试命令，
+//! 生成 `.rrclaw/` 脚手架：
+//! - `AGENT.md`：按检测结果预填的项目约定（格式与 `/identity edit agent` 一致）
+//! - `skills/README.md`：项目 skill 目录说明 + 示例 SKILL.md（不会被当作 skill 加载）
+//! - `config.overlay.toml`：建议加入 `[security] allowed_commands` 的命令（不会自动加载——
+//!   克隆来的仓库不应能放宽命令白名单，需用户手动合并到全局配置）
+//! - `README.md`：结合 `.gitignore` 说明 `.rrclaw/` 是否应提交
+//!
+//! 检测与生成是纯函数（输入 `WorkspaceScan`），只有 `scan` / `write_plan` 访问文件系统。
+//! 已存在的文件一律跳过，不覆盖。
+
+use color_eyre::eyre::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// 读取内容的标记文件（相对 workspace）
+const MARKER_FILES: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pnpm-lock.yaml",
+    "yarn.lock",
+    "bun.lockb",
+    "bun.lock",
+    "pyproject.toml",
+    "requirements.txt",
+    "setup.py",
+    "uv.lock",
+    "poetry.lock",
+    ".gitlab-ci.yml",
+    ".circleci/config.yml",
+    ".gitignore",
+];
+/// 只记录是否存在的目录
+const MARKER_DIRS: &[&str] = &["tests", ".git"];
+/// GitHub Actions workflow 目录
+const GITHUB_WORKFLOWS: &str = ".github/workflows";
+/// 单个标记文件最多读取的字节数
+const MAX_MARKER_BYTES: u64 = 256 * 1024;
+
+/// 生成的文件（相对 workspace）
+pub const AGENT_FILE: &str = ".rrclaw/AGENT.md";
+pub const SKILLS_README: &str = ".rrclaw/skills/README.md";
+pub const CONFIG_OVERLAY: &str = ".rrclaw/config.overlay.toml";
+pub const RRCLAW_README: &str = ".rrclaw/README.md";
+
+/// workspace 中与检测相关的文件内容和目录
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceScan {
+    /// 相对路径 → 内容
+    files: BTreeMap<String, String>,
+    dirs: BTreeSet<String>,
+}
+
+impl WorkspaceScan {
+    pub fn with_file(mut self, path: &str, content: &str) -> Self {
+        self.files.insert(path.to_string(), content.to_string());
+        self
+    }
+
+    pub fn with_dir(mut self, path: &str) -> Self {
+        self.dirs.insert(path.to_string());
+        self
+    }
+
+    fn has(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn content(&self, path: &str) -> &str {
+        self.files.get(path).map(String::as_str).unwrap_or("")
+    }
+
+    /// 是否有路径以 `prefix` 开头的文件
+    fn has_under(&self, prefix: &str) -> bool {
+        self.files.keys().any(|path| path.starts_with(prefix))
+    }
+}
+
+/// 读取 workspace 的标记文件与 GitHub workflow
+pub fn scan(root: &Path) -> WorkspaceScan {
+    let mut scan = WorkspaceScan::default();
+    let mut read = |relative: String| {
+        let path = root.join(&relative);
+        let small = std::fs::metadata(&path)
+            .map(|m| m.is_file() && m.len() <= MAX_MARKER_BYTES)
+            .unwrap_or(false);
+        if let Some(content) = small.then(|| std::fs::read_to_string(&path).ok()).flatten() {
+            scan.files.insert(relative, content);
+        }
+    };
+    for file in MARKER_FILES {
+        read(file.to_string());
+    }
+    if let Ok(entries) = std::fs::read_dir(root.join(GITHUB_WORKFLOWS)) {
+        for entry in entries.flatten() {
+            if let Some(name) = entry.file_name().to_str() {
+                read(format!("{}/{}", GITHUB_WORKFLOWS, name));
+            }
+        }
+    }
+    for dir in MARKER_DIRS {
+        if root.join(dir).is_dir() {
+            scan.dirs.insert(dir.to_string());
+        }
+    }
+    scan
+}
+
+/// 检测到的工具链
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ecosystem {
+    Rust,
+    /// 包管理器：npm / pnpm / yarn / bun
+    Node {
+        manager: &'static str,
+    },
+    /// 运行前缀：uv / poetry（None = 直接运行）
+    Python {
+        runner: Option<&'static str>,
+    },
+}
+
+impl Ecosystem {
+    pub fn label(&self) -> String {
+        match self {
+            Self::Rust => "Rust".to_string(),
+            Self::Node { manager } => format!("Node.js ({})", manager),
+            Self::Python {
+                runner: Some(runner),
+            } => format!("Python ({})", runner),
+            Self::Python { runner: None } => "Python".to_string(),
+        }
+    }
+}
+
+/// 检测结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectProfile {
+    pub ecosystems: Vec<Ecosystem>,
+    /// CI 系统名及配置位置，如 `("GitHub Actions", ".github/workflows/")`
+    pub ci: Vec<(&'static str, &'static str)>,
+    /// 测试命令（多个工具链时各一条）
+    pub test_commands: Vec<String>,
+    /// 提交前应通过的检查（格式化 / lint / 类型检查）
+    pub checks: Vec<String>,
+    /// 建议加入 allowed_commands 的命令
+    pub allowed_commands: Vec<String>,
+    /// `.gitignore` 是否忽略了 `.rrclaw/`
+    pub rrclaw_ignored: bool,
+}
+
+/// 根据扫描结果推断工具链、CI 和常用命令
+pub fn detect(scan: &WorkspaceScan) -> ProjectProfile {
+    let mut profile = ProjectProfile::default();
+    if scan.has("Cargo.toml") {
+        detect_rust(scan, &mut profile);
+    }
+    if scan.has("package.json") {
+        detect_node(scan, &mut profile);
+    }
+    if scan.has("pyproject.toml") || scan.has("requirements.txt") || scan.has("setup.py") {
+        detect_python(scan, &mut profile);
+    }
+
+    if scan.has_under(GITHUB_WORKFLOWS) {
+        profile.ci.push(("GitHub Actions", ".github/workflows/"));
+    }
+    if scan.has(".gitlab-ci.yml") {
+        profile.ci.push(("GitLab CI", ".gitlab-ci.yml"));
+    }
+    if scan.has(".circleci/config.yml") {
+        profile.ci.push(("CircleCI", ".circleci/config.yml"));
+    }
+
+    profile.rrclaw_ignored = scan.content(".gitignore").lines().any(|line| {
+        matches!(
+            line.trim(),
+            ".rrclaw" | ".rrclaw/" | "/.rrclaw" | "/.rrclaw/" | ".rrclaw/*" | "/.rrclaw/*"
+        )
+    });
+
+    let mut seen = BTreeSet::new();
+    profile
+        .allowed_commands
+        .retain(|command| seen.insert(command.clone()));
+    profile
+}
+
+fn detect_rust(scan: &WorkspaceScan, profile: &mut ProjectProfile) {
+    let workspace = scan.content("Cargo.toml").contains("[workspace]");
+    profile.ecosystems.push(Ecosystem::Rust);
+    profile.test_commands.push(if workspace {
+        "cargo test --workspace".to_string()
+    } else {
+        "cargo test".to_string()
+    });
+    profile.checks.push("cargo fmt --check".to_string());
+    profile.checks.push(if workspace {
+        "cargo clippy --workspace --all-targets -- -D warnings".to_string()
+    } else {
+        "cargo clippy -- -D warnings".to_string()
+    });
+    push_commands(profile, &["cargo", "rustc", "rustfmt"]);
+}
+
+fn detect_node(scan: &WorkspaceScan, profile: &mut ProjectProfile) {
+    let manager = if scan.has("pnpm-lock.yaml") {
+        "pnpm"
+    } else if scan.has("yarn.lock") {
+        "yarn"
+    } else if scan.has("bun.lockb") || scan.has("bun.lock") {
+        "bun"
+    } else {
+        "npm"
+    };
+    profile.ecosystems.push(Ecosystem::Node { manager });
+
+    let package: serde_json::Value =
+        serde_json::from_str(scan.content("package.json")).unwrap_or_default();
+    let script = |name: &str| {
+        package["scripts"][name]
+            .as_str()
+            .filter(|s| !s.contains("no test specified"))
+            .is_some()
+    };
+    if script("test") {
+        profile.test_commands.push(format!("{} test", manager));
+    }
+    for name in ["lint", "typecheck", "format:check"] {
+        if script(name) {
+            profile.checks.push(format!("{} run {}", manager, name));
+        }
+    }
+    push_commands(profile, &["node", manager]);
+    if manager == "npm" {
+        push_commands(profile, &["npx"]);
+    }
+}
+
+fn detect_python(scan: &WorkspaceScan, profile: &mut ProjectProfile) {
+    let pyproject = scan.content("pyproject.toml");
+    let runner = if scan.has("uv.lock") {
+        Some("uv")
+    } else if scan.has("poetry.lock") || pyproject.contains("[tool.poetry]") {
+        Some("poetry")
+    } else {
+        None
+    };
+    profile.ecosystems.push(Ecosystem::Python { runner });
+    let run = |command: &str| match runner {
+        Some(runner) => format!("{} run {}", runner, command),
+        None => command.to_string(),
+    };
+
+    let deps = format!("{}\n{}", pyproject, scan.content("requirements.txt"));
+    let mentions = |tool: &str| deps.contains(tool);
+    let pytest = mentions("pytest") || scan.dirs.contains("tests");
+    profile.test_commands.push(if pytest {
+        run("pytest")
+    } else {
+        run("python -m unittest")
+    });
+    if mentions("ruff") {
+        profile.checks.push(run("ruff check ."));
+        profile.checks.push(run("ruff format --check ."));
+    } else if mentions("black") {
+        profile.checks.push(run("black --check ."));
+    }
+    if mentions("mypy") {
+        profile.checks.push(run("mypy ."));
+    }
+
+    push_commands(profile, &["python", "python3", "pip"]);
+    if let Some(runner) = runner {
+        push_commands(profile, &[runner]);
+    }
+    for (used, tool) in [
+        (pytest, "pytest"),
+        (mentions("ruff"), "ruff"),
+        (mentions("black"), "black"),
+        (mentions("mypy"), "mypy"),
+    ] {
+        if used {
+            push_commands(profile, &[tool]);
+        }
+    }
+}
+
+fn push_commands(profile: &mut ProjectProfile, commands: &[&str]) {
+    profile
+        .allowed_commands
+        .extend(commands.iter().map(|c| c.to_string()));
+}
+
+/// 计划写入的单个文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFile {
+    /// 相对 workspace 的路径
+    pub path: &'static str,
+    pub content: String,
+    /// 已存在时跳过（不覆盖）
+    pub exists: bool,
+}
+
+/// 根据检测结果生成脚手架内容；`exists` 判断文件是否已存在
+pub fn plan(profile: &ProjectProfile, exists: impl Fn(&str) -> bool) -> Vec<PlannedFile> {
+    [
+        (AGENT_FILE, agent_md(profile)),
+        (SKILLS_README, SKILLS_README_CONTENT.to_string()),
+        (CONFIG_OVERLAY, config_overlay(profile)),
+        (RRCLAW_README, rrclaw_readme(profile)),
+    ]
+    .into_iter()
+    .map(|(path, content)| PlannedFile {
+        path,
+        content,
+        exists: exists(path),
+    })
+    .collect()
+}
+
+/// 写入所有尚不存在的文件，返回写入的路径
+pub fn write_plan(root: &Path, plan: &[PlannedFile]) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for file in plan.iter().filter(|f| !f.exists) {
+        let path = root.join(file.path);
+        if path.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("创建目录失败: {}", parent.display()))?;
+        }
+        std::fs::write(&path, &file.content)
+            .wrap_err_with(|| format!("写入失败: {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// AGENT.md：与 `/identity edit agent` 相同的 `## 代码规范` 列表格式
+fn agent_md(profile: &ProjectProfile) -> String {
+    let mut items: Vec<String> = profile
+        .checks
+        .iter()
+        .map(|check| format!("提交前运行 `{}`，确保通过", check))
+        .collect();
+    items.extend(
+        profile
+            .test_commands
+            .iter()
+            .map(|test| format!("修改代码后运行 `{}` 确认测试通过", test)),
+    );
+    for (name, location) in &profile.ci {
+        items.push(format!(
+            "CI 使用 {}（{}），本地检查应与 CI 保持一致",
+            name, location
+        ));
+    }
+    if items.is_empty() {
+        items.push("（未检测到已知工具链，请用 /identity edit agent 补充项目约定）".to_string());
+    }
+
+    let mut content = String::from("## 代码规范\n\n");
+    for item in items {
+        content.push_str(&format!("- {}\n", item));
+    }
+    content.push('\n');
+    content
+}
+
+fn config_overlay(profile: &ProjectProfile) -> String {
+    let detected: Vec<String> = profile.ecosystems.iter().map(Ecosystem::label).collect();
+    let mut commands = toml_edit::Array::new();
+    for command in &profile.allowed_commands {
+        commands.push(command.as_str());
+    }
+    commands.fmt();
+    format!(
+        "# rrclaw init-project 根据检测到的工具链（{}）建议的命令白名单。\n\
+         # RRClaw 不会自动加载本文件：克隆来的仓库不应能放宽命令白名单。\n\
+         # 确认无误后，把需要的命令合并到全局配置文件（路径见 rrclaw config）的 [security] allowed_commands。\n\
+         \n\
+         [security]\n\
+         allowed_commands = {}\n",
+        if detected.is_empty() {
+            "无".to_string()
+        } else {
+            detected.join("、")
+        },
+        commands
+    )
+}
+
+fn rrclaw_readme(profile: &ProjectProfile) -> String {
+    let note = if profile.rrclaw_ignored {
+        "当前 `.gitignore` 忽略了 `.rrclaw/`，以下文件只在本机生效。如需与团队共享项目约定和 skills，\n\
+         请从 `.gitignore` 中移除该规则，改为只忽略个人文件（如 `.rrclaw/SOUL.md`）。"
+    } else {
+        "建议提交 `AGENT.md` 和 `skills/`，让团队共享项目约定；`config.overlay.toml` 只是建议，可按需提交。\n\
+         个人人格文件 `SOUL.md` 如不想共享，可在 `.gitignore` 中加入 `.rrclaw/SOUL.md`。"
+    };
+    format!(
+        "# .rrclaw\n\n\
+         RRClaw 的项目级配置目录：\n\n\
+         - `AGENT.md`：项目行为约定，每轮注入 system prompt（`/identity edit agent` 修改）\n\
+         - `SOUL.md`：项目级人格（可选，优先于全局 `SOUL.md`）\n\
+         - `skills/<name>/SKILL.md`：项目 skill，优先于全局和内置同名 skill\n\
+         - `config.overlay.toml`：建议的命令白名单（不会自动加载）\n\n\
+         ## 是否提交到 git\n\n\
+         {}\n",
+        note
+    )
+}
+
+const SKILLS_README_CONTENT: &str = r#"# 项目 Skills
+
+每个 skill 是一个子目录，包含 `SKILL.md`（本文件不会被加载）。项目 skill 优先于全局
+（配置目录下的 `skills/`）和内置的同名 skill。示例 `release/SKILL.md`：
+
+```markdown
+---
+name: release
+description: 发布新版本。更新版本号和 CHANGELOG，打 tag。当用户要求发版时使用。
+tags: [dev, release]
+---
+
+# 发布流程
+
+1. 确认工作区干净、测试通过
+2. 更新版本号和 CHANGELOG
+3. 提交并打 tag：`git tag vX.Y.Z`
+```
+
+`description` 会出现在 system prompt 的 skill 列表中，写清楚“做什么、什么时候用”。
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_workspace_with_github_actions() {
+        let scan = WorkspaceScan::default()
+            .with_file("Cargo.toml", "[workspace]\nmembers = [\"a\"]\n")
+            .with_file(".github/workflows/ci.yml", "run: cargo test")
+            .with_file(".gitignore", "target/\n");
+        let profile = detect(&scan);
+        assert_eq!(profile.ecosystems, vec![Ecosystem::Rust]);
+        assert_eq!(profile.test_commands, vec!["cargo test --workspace"]);
+        assert_eq!(
+            profile.checks,
+            vec![
+                "cargo fmt --check",
+                "cargo clippy --workspace --all-targets -- -D warnings"
+            ]
+        );
+        assert_eq!(profile.ci, vec![("GitHub Actions", ".github/workflows/")]);
+        assert_eq!(profile.allowed_commands, vec!["cargo", "rustc", "rustfmt"]);
+        assert!(!profile.rrclaw_ignored);
+
+        let agent = agent_md(&profile);
+        assert!(agent.starts_with("## 代码规范\n"));
+        assert!(agent.contains(
+            "- 提交前运行 `cargo clippy --workspace --all-targets -- -D warnings`，确保通过"
+        ));
+        assert!(agent.contains("GitHub Actions"));
+    }
+
+    #[test]
+    fn node_project_uses_lockfile_manager_and_scripts() {
+        let scan = WorkspaceScan::default()
+            .with_file(
+                "package.json",
+                r#"{"scripts": {"test": "vitest", "lint": "eslint .", "build": "tsc"}}"#,
+            )
+            .with_file("pnpm-lock.yaml", "")
+            .with_file(".gitlab-ci.yml", "test: pnpm test");
+        let profile = detect(&scan);
+        assert_eq!(
+            profile.ecosystems,
+            vec![Ecosystem::Node { manager: "pnpm" }]
+        );
+        assert_eq!(profile.test_commands, vec!["pnpm test"]);
+        assert_eq!(profile.checks, vec!["pnpm run lint"]);
+        assert_eq!(profile.ci, vec![("GitLab CI", ".gitlab-ci.yml")]);
+        assert_eq!(profile.allowed_commands, vec!["node", "pnpm"]);
+
+        // npm init 生成的占位 test 脚本不算测试命令
+        let scan = WorkspaceScan::default().with_file(
+            "package.json",
+            r#"{"scripts": {"test": "echo \"Error: no test specified\" && exit 1"}}"#,
+        );
+        let profile = detect(&scan);
+        assert!(profile.test_commands.is_empty());
+        assert_eq!(profile.allowed_commands, vec!["node", "npm", "npx"]);
+    }
+
+    #[test]
+    fn python_project_detects_runner_and_tools() {
+        let scan = WorkspaceScan::default()
+            .with_file(
+                "pyproject.toml",
+                "[project]\nname = \"x\"\n[dependency-groups]\ndev = [\"pytest\", \"ruff\", \"mypy\"]\n",
+            )
+            .with_file("uv.lock", "");
+        let profile = detect(&scan);
+        assert_eq!(
+            profile.ecosystems,
+            vec![Ecosystem::Python { runner: Some("uv") }]
+        );
+        assert_eq!(profile.test_commands, vec!["uv run pytest"]);
+        assert_eq!(
+            profile.checks,
+            vec![
+                "uv run ruff check .",
+                "uv run ruff format --check .",
+                "uv run mypy ."
+            ]
+        );
+        assert_eq!(
+            profile.allowed_commands,
+            vec!["python", "python3", "pip", "uv", "pytest", "ruff", "mypy"]
+        );
+
+        // 只有 requirements.txt、没有 pytest：回退到 unittest；有 tests/ 目录时猜测 pytest
+        let scan = WorkspaceScan::default().with_file("requirements.txt", "requests\n");
+        let profile = detect(&scan);
+        assert_eq!(profile.ecosystems, vec![Ecosystem::Python { runner: None }]);
+        assert_eq!(profile.test_commands, vec!["python -m unittest"]);
+        assert!(profile.checks.is_empty());
+        let profile = detect(&scan.with_dir("tests"));
+        assert_eq!(profile.test_commands, vec!["pytest"]);
+    }
+
+    #[test]
+    fn unknown_project_and_gitignored_rrclaw() {
+        let scan = WorkspaceScan::default().with_file(".gitignore", "node_modules\n/.rrclaw/\n");
+        let profile = detect(&scan);
+        assert!(profile.ecosystems.is_empty());
+        assert!(profile.rrclaw_ignored);
+        assert!(agent_md(&profile).contains("未检测到已知工具链"));
+        assert!(rrclaw_readme(&profile).contains("忽略了 `.rrclaw/`"));
+        assert!(config_overlay(&profile).contains("allowed_commands = []"));
+    }
+
+    #[test]
+    fn plan_skips_existing_files_and_overlay_is_valid_toml() {
+        let scan = WorkspaceScan::default().with_file("Cargo.toml", "[package]\n");
+        let profile = detect(&scan);
+        let files = plan(&profile, |path| path == AGENT_FILE);
+        assert_eq!(
+            files.iter().map(|f| (f.path, f.exists)).collect::<Vec<_>>(),
+            vec![
+                (AGENT_FILE, true),
+                (SKILLS_README, false),
+                (CONFIG_OVERLAY, false),
+                (RRCLAW_README, false),
+            ]
+        );
+
+        let overlay: toml_edit::DocumentMut = files[2].content.parse().unwrap();
+        let commands: Vec<&str> = overlay["security"]["allowed_commands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert_eq!(commands, vec!["cargo", "rustc", "rustfmt"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".rrclaw")).unwrap();
+        std::fs::write(dir.path().join(AGENT_FILE), "keep me").unwrap();
+        let written = write_plan(dir.path(), &files).unwrap();
+        assert_eq!(written.len(), 3);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(AGENT_FILE)).unwrap(),
+            "keep me"
+        );
+        // 示例 skill 只是说明文档，不会被当作 skill 加载
+        let skills = crate::skills::scan_skills_dir(
+            &dir.path().join(".rrclaw/skills"),
+            crate::skills::SkillSource::Project,
+        );
+        assert!(skills.is_empty());
+    }
+
+    #[test]
+    fn scan_reads_markers_and_workflows_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]\n").unwrap();
+        std::fs::create_dir_all(dir.path().join(".github/workflows")).unwrap();
+        std::fs::write(dir.path().join(".github/workflows/ci.yml"), "on: push").unwrap();
+        std::fs::create_dir(dir.path().join("tests")).unwrap();
+
+        let scan = scan(dir.path());
+        assert_eq!(scan.content("Cargo.toml"), "[package]\n");
+        assert_eq!(scan.content(".github/workflows/ci.yml"), "on: push");
+        assert!(scan.dirs.contains("tests"));
+        assert!(!scan.has("package.json"));
+    }
+}
@@ -0,0 +1,599 @@
+//! Prompt 模板与构造：system prompt、Phase 1 路由、Routine 执行规范、history 摘要
+//!
+//! 发给模型的固定文案都集中在这里，按语言放在 [`Templates`] 中；构造函数都是纯函数，
+//! 输入是 [`SystemPromptContext`] 等上下文结构，不读取 Agent 状态，也不读全局配置。
+//!
+//! 改动任何文案或拼接顺序时：
+//! - [`PROMPT_VERSION`] 加一。每轮日志、故障报告、录制的 cassette / 夹具都带这个版本号，
+//!   模型行为的变化可以对应到具体的 prompt 改动
+//! - `RRCLAW_UPDATE_SNAPSHOTS=1 cargo test prompts` 重新生成 `snapshots/`，提交前检查 diff
+
+use std::path::Path;
+
+use super::goal::SessionGoal;
+use crate::i18n::Language;
+use crate::security::AutonomyLevel;
+use crate::skills::SkillMeta;
+
+/// Prompt 模板版本，任何文案改动都要加一
+pub const PROMPT_VERSION: u32 = 1;
+
+/// 某一语言的全部 system prompt / 路由模板
+#[derive(Debug)]
+pub struct Templates {
+    /// 身份文件段标题
+    pub custom_context: &'static str,
+    /// 默认身份描述（`system_prompt_override` 替换它）
+    pub identity: &'static str,
+    pub tools_header: &'static str,
+    pub mcp_tools_header: &'static str,
+    /// 受 Provider 限制未携带的工具提示，`{count}` 为工具数
+    pub omitted_tools: &'static str,
+    pub skills_header: &'static str,
+    pub safe_mode: &'static str,
+    pub ask_mode: &'static str,
+    pub read_only: &'static str,
+    pub supervised: &'static str,
+    pub full: &'static str,
+    pub memories_header: &'static str,
+    /// 路由命中的 skill L2 内容段标题
+    pub behavior_guide: &'static str,
+    /// `{name}` 为 Routine 名称
+    pub routine_rules: &'static str,
+    /// `{workspace}` / `{now}`
+    pub environment: &'static str,
+    /// 决策原则 1–7（`system_prompt_override` 时省略）
+    pub decision_principles: &'static str,
+    /// 决策原则 8：通道能显示快捷回复时追加
+    pub quick_replies_principle: &'static str,
+    pub routing: RoutingTemplates,
+}
+
+/// Phase 1 路由 prompt 模板
+#[derive(Debug)]
+pub struct RoutingTemplates {
+    pub intro: &'static str,
+    pub constraints: &'static str,
+    pub skills_header: &'static str,
+    pub no_skills: &'static str,
+    pub output_format: &'static str,
+    pub principles: &'static str,
+}
+
+pub const EN: Templates = Templates {
+    custom_context: "[Custom Context]",
+    identity: "You are RRClaw, a safety-first AI assistant.",
+    tools_header: "You can use the following tools:\n",
+    mcp_tools_header: "\n[MCP Tools] (available on demand; full parameter schema is loaded automatically on first call):\n",
+    omitted_tools: "\n[Note] {count} tool(s) are not attached to this request because of provider limits. \
+                    Use mcp_list_tools to find and call MCP tools, or the skill tool for guided workflows.\n",
+    skills_header: "[Available Skills] (use the skill tool to load detailed instructions when needed)\n",
+    safe_mode: "Safe mode: no tools are available in this session. Answer directly in text.",
+    ask_mode: "Ask mode (read-only turn): only read-only tools are available and any other tool call will be rejected. \
+               Do not try to modify files, configuration or memory; answer from what you can read.",
+    read_only: "Read-only mode: do not attempt to call any tools.",
+    supervised: "Supervised mode: call tools directly. \
+                 The system will automatically prompt the user for confirmation before execution. \
+                 Do not ask for confirmation in your text — just issue the tool call.",
+    full: "Full mode: you can execute tools autonomously within the allowed-commands list.",
+    memories_header: "[Relevant Memories]\n",
+    behavior_guide: "[Behavior Guide]",
+    routine_rules: "[Routine Execution Rules]\n\
+                    You are executing scheduled task '{name}'. This is an automated task with no user interaction.\n\
+                    - If the message starts with [Previously successful approach], try that approach first\n\
+                    - After completing the task successfully, record the effective method with memory_store:\n\
+                    \x20 - key: \"routine:{name}:approach\"\n\
+                    \x20 - category: \"custom\"\n\
+                    \x20 - content: describe the successful method (URL, headers, data extraction path, etc.)\n\
+                    - If you find a better method, overwrite the existing record\n\
+                    - Do not update the record on failure",
+    environment: "Working directory: {workspace}\nCurrent time: {now}",
+    decision_principles: concat!(
+        "[Decision Principles]\n",
+        "1. Check before acting: use self_info to query uncertain info (paths, config, capabilities) — don't guess\n",
+        "2. Ask when stuck: if you can't find or infer the answer, ask the user directly\n",
+        "3. Explain intent: briefly explain why you need a tool before calling it\n",
+        "4. Reflect on failure: analyze root cause before retrying\n",
+        "   - 1st failure: analyze cause, try a different approach\n",
+        "   - 2nd failure: explain the situation to the user and ask for guidance\n",
+        "   - Don't attempt the same goal more than 3 times\n",
+        "5. Reply in the user's language\n",
+        "6. Use memory: store user preferences with memory_store when told; use memory_recall when unsure if something was discussed before\n",
+        "7. When HTTP requests are blocked by SSRF protection: explain the situation to the user and ask if they want to add the address to the allowlist. After agreement, use the config tool to add it (e.g., set security.http_allowed_hosts to [\"localhost\"]), then retry",
+    ),
+    quick_replies_principle: "\n8. Follow-ups: when there are obvious next steps, end the final answer with a ```suggestions block listing at most 3 short follow-up requests, one per line, written as the user would type them (they become quick replies; the block itself is not shown)",
+    routing: RoutingTemplates {
+        intro: "You are RRClaw's routing assistant. Your only job is to analyze the user's message and decide which behavior guides (skills) to load.\n\n",
+        constraints: "[Constraints]\n- Do not call any tools\n- Output JSON only, no other text\n\n",
+        skills_header: "[Available Skills]\n",
+        no_skills: "No skills available.\n",
+        output_format: concat!(
+            "[Output Format]\n",
+            "Output valid JSON, one of three cases:\n\n",
+            "1. Skills needed (clear intent, matching skill found):\n",
+            "   {\"skills\": [\"skill-name\"], \"direct\": false}\n\n",
+            "2. No skill needed, intent is clear:\n",
+            "   {\"skills\": [], \"direct\": true}\n\n",
+            "3. Intent unclear, need clarification (only when truly ambiguous):\n",
+            "   {\"skills\": [], \"direct\": false, \"question\": \"your clarification question\"}\n\n",
+        ),
+        principles: concat!(
+            "[Principles]\n",
+            "- When intent is clear, choose direct: true even if no skill matches\n",
+            "- Skills are enhancements, not gates — don't ask the user when no skill matches\n",
+            "- Only return a question when the intent is genuinely ambiguous and proceeding would go wrong\n",
+            "- Use the same language as the user in the question field\n",
+        ),
+    },
+};
+
+pub const ZH: Templates = Templates {
+    custom_context: "[用户定制上下文]",
+    identity: "你是 RRClaw，一个安全优先的 AI 助手。",
+    tools_header: "你可以使用以下工具:\n",
+    mcp_tools_header: "\n[MCP 工具]（需要时可用，首次调用后自动获取完整参数说明）:\n",
+    omitted_tools: "\n[提示] 受 Provider 限制，本次请求未携带 {count} 个工具。\
+                    需要时可用 mcp_list_tools 查找并调用 MCP 工具，或用 skill 工具加载操作指南。\n",
+    skills_header: "[可用技能]（需要时用 skill 工具加载详细指令）\n",
+    safe_mode: "当前为安全模式，本会话没有任何可用工具，请直接用文字回答。",
+    ask_mode: "当前为只读回合（/ask）：只提供读取类工具，调用其他工具会被拒绝。\
+               不要尝试修改文件、配置或记忆，根据能读取到的信息直接回答。",
+    read_only: "当前为只读模式，不要尝试执行任何工具。",
+    supervised: "当前为 Supervised 模式。你应该直接调用工具，系统会自动弹出确认提示让用户决定是否执行。\
+                 不要在文本中请求用户确认，直接发起 tool call 即可。",
+    full: "你可以自主执行工具，但须遵守白名单限制。",
+    memories_header: "[相关记忆]\n",
+    behavior_guide: "[行为指南]",
+    routine_rules: "[Routine 执行规范]\n\
+                    你正在执行定时任务 '{name}'，这是一个自动化任务，不会有用户交互。\n\
+                    - 如果消息前缀有 [历史成功方法参考]，优先尝试该方法\n\
+                    - 成功完成任务后，用 memory_store 记录有效方法：\n\
+                    \x20 - key: \"routine:{name}:approach\"\n\
+                    \x20 - category: \"custom\"\n\
+                    \x20 - content: 描述成功方法（使用的 URL、headers、数据提取路径等）\n\
+                    - 如果发现更好的方法，直接覆盖旧记录\n\
+                    - 失败时不要更新记录",
+    environment: "工作目录: {workspace}\n当前时间: {now}",
+    decision_principles: concat!(
+        "[决策原则]\n",
+        "1. 先查后做: 不确定的信息（路径、配置、能力）先用 self_info 工具查询，不要猜测\n",
+        "2. 不知道就问: 如果查不到也推理不出，直接问用户，不要盲目尝试\n",
+        "3. 说明意图: 调用工具前简短说明为什么需要这个工具\n",
+        "4. 失败时反思: 工具失败后先分析原因，再决定下一步\n",
+        "   - 第 1 次失败: 分析原因，换一种方式\n",
+        "   - 第 2 次失败: 向用户说明情况，询问建议\n",
+        "   - 不要同一个目标尝试超过 3 次\n",
+        "5. 用中文回复，除非用户使用其他语言\n",
+        "6. 善用记忆: 当用户告知偏好或重要信息时，用 memory_store 保存；不确定之前是否讨论过时，用 memory_recall 检索\n",
+        "7. HTTP 请求被 SSRF 防护阻止时: 向用户说明情况，询问是否要将该地址加入白名单。用户同意后，用 config 工具添加（如 /config set security.http_allowed_hosts 添加 [\"localhost\"]），然后重新尝试请求",
+    ),
+    quick_replies_principle: "\n8. 后续建议: 有明显的下一步时，在最终回复末尾输出 ```suggestions 代码块，每行一条、最多 3 条简短的后续请求，用用户会输入的口吻书写（显示为快捷回复，代码块本身不显示）",
+    routing: RoutingTemplates {
+        intro: "你是 RRClaw 的路由助手。你的唯一任务是分析用户消息，决定需要加载哪些行为指南（skill）。\n\n",
+        constraints: "【约束】\n- 禁止调用任何工具\n- 只输出 JSON，不做其他任何操作\n\n",
+        skills_header: "【可用 Skill】\n",
+        no_skills: "暂无可用 skill。\n",
+        output_format: concat!(
+            "【输出格式】\n",
+            "必须输出合法 JSON，三种情况之一：\n\n",
+            "1. 需要加载 skill（意图明确且有匹配 skill）：\n",
+            "   {\"skills\": [\"skill-name\"], \"direct\": false}\n\n",
+            "2. 无需 skill，意图清晰可直接执行：\n",
+            "   {\"skills\": [], \"direct\": true}\n\n",
+            "3. 意图模糊，需要向用户澄清（仅在真正无法判断时使用）：\n",
+            "   {\"skills\": [], \"direct\": false, \"question\": \"你的澄清问题\"}\n\n",
+        ),
+        principles: concat!(
+            "【判断原则】\n",
+            "- 用户意图清晰时，即使没有匹配的 skill，也应选择 direct: true\n",
+            "- skill 是增强，不是门槛——没有 skill 可以匹配时不要问用户\n",
+            "- 只有用户表达含糊、继续执行会走错方向时，才返回 question\n",
+            "- question 字段使用中文，简洁明确\n",
+        ),
+    },
+};
+
+/// history 压缩摘要请求（`{max_chars}` 为摘要字数上限，`{transcript}` 为对话记录）
+pub const SUMMARY_REQUEST: &str = "请将以下对话历史压缩成简洁摘要（不超过 {max_chars} 字符）。\n\
+     保留：用户的核心需求、重要决策、已解决的问题、关键信息（路径/命令/配置）。\n\
+     忽略：闲聊、重复内容、工具执行的详细输出。\n\
+     用中文输出，以「对话摘要：」开头。\n\n\
+     ---\n{transcript}\n---";
+
+/// 压缩后替代早期 history 的 system 消息标题
+pub const SUMMARY_MESSAGE_HEADER: &str = "[对话摘要 - 早期上下文]";
+
+/// 按语言取模板
+pub fn templates(lang: Language) -> &'static Templates {
+    match lang {
+        Language::English => &EN,
+        Language::Chinese => &ZH,
+    }
+}
+
+/// 工具 / skill 目录中的一行（`- name: description`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PromptItem<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+}
+
+/// 工具段内容（Agent 已按路由结果和暴露规则筛选）
+#[derive(Debug, Clone, Default)]
+pub struct ToolsSection<'a> {
+    /// 内置工具
+    pub tools: Vec<PromptItem<'a>>,
+    /// MCP 工具（单独列出，首次调用时才加载完整 schema）
+    pub mcp_tools: Vec<PromptItem<'a>>,
+    /// 受 Provider 限制未携带的工具数
+    pub omitted: usize,
+}
+
+/// 安全规则段对应的模式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptMode {
+    /// `--safe`：本会话没有工具
+    Safe,
+    /// `/ask` 只读回合
+    Ask,
+    Autonomy(AutonomyLevel),
+}
+
+/// system prompt 的全部输入，由 `Agent` 从当前状态收集
+#[derive(Debug, Clone)]
+pub struct SystemPromptContext<'a> {
+    pub lang: Language,
+    /// `[agent] system_prompt_prefix`
+    pub prefix: Option<&'a str>,
+    /// 身份文件内容
+    pub identity: Option<&'a str>,
+    /// `[agent] system_prompt_override`：替换身份描述，并省略决策原则
+    pub override_text: Option<&'a str>,
+    /// Agent 没有任何工具时为 None（整段省略）
+    pub tools: Option<ToolsSection<'a>>,
+    /// L1 skill 目录（不含 skill 工具自身）
+    pub skills: Vec<PromptItem<'a>>,
+    pub mode: PromptMode,
+    pub goal: Option<&'a SessionGoal>,
+    /// 本轮召回的记忆内容
+    pub memories: Vec<&'a str>,
+    /// Phase 1 路由命中的 skill L2 内容
+    pub behavior_guide: Option<&'a str>,
+    /// Routine 模式下执行的 Routine 名称
+    pub routine: Option<&'a str>,
+    pub workspace: &'a Path,
+    /// 已格式化的当前时间（快照测试传固定值）
+    pub now: String,
+    /// 通道能显示快捷回复（REPL / Telegram）时要求模型附带后续建议
+    pub quick_replies: bool,
+}
+
+/// 构造 system prompt：各段按固定顺序以空行分隔
+pub fn system_prompt(ctx: &SystemPromptContext<'_>) -> String {
+    let t = templates(ctx.lang);
+    let mut parts = Vec::new();
+
+    // [-1] 配置的前缀
+    if let Some(prefix) = ctx.prefix {
+        parts.push(prefix.to_string());
+    }
+
+    // [0] 用户定制上下文（身份文件）
+    if let Some(identity) = ctx.identity {
+        parts.push(format!("{}\n{}", t.custom_context, identity));
+    }
+
+    // [1] 身份描述
+    parts.push(ctx.override_text.unwrap_or(t.identity).to_string());
+
+    // [2] 可用工具
+    if let Some(tools) = &ctx.tools {
+        parts.push(tools_section(t, tools));
+    }
+
+    // [2.5] 可用技能（L1 元数据）
+    if !ctx.skills.is_empty() {
+        let mut section = t.skills_header.to_string();
+        push_items(&mut section, &ctx.skills);
+        parts.push(section);
+    }
+
+    // [3] 安全规则
+    parts.push(
+        match &ctx.mode {
+            PromptMode::Safe => t.safe_mode,
+            PromptMode::Ask => t.ask_mode,
+            PromptMode::Autonomy(AutonomyLevel::ReadOnly) => t.read_only,
+            PromptMode::Autonomy(AutonomyLevel::Supervised) => t.supervised,
+            PromptMode::Autonomy(AutonomyLevel::Full) => t.full,
+        }
+        .to_string(),
+    );
+
+    // [3.5] 会话目标（不在 history 中，不受压缩影响）
+    if let Some(goal) = ctx.goal {
+        parts.push(goal.prompt_section(ctx.lang));
+    }
+
+    // [4] 记忆上下文
+    if !ctx.memories.is_empty() {
+        let mut section = t.memories_header.to_string();
+        for memory in &ctx.memories {
+            section.push_str(&format!("- {}\n", memory));
+        }
+        parts.push(section);
+    }
+
+    // [4.5] 已路由的 skill 行为指南
+    if let Some(guide) = ctx.behavior_guide {
+        parts.push(format!("{}\n{}", t.behavior_guide, guide));
+    }
+
+    // [4.6] Routine 执行规范
+    if let Some(name) = ctx.routine {
+        parts.push(routine_rules(ctx.lang, name));
+    }
+
+    // [5] 环境信息
+    parts.push(
+        t.environment
+            .replace("{workspace}", &ctx.workspace.display().to_string())
+            .replace("{now}", &ctx.now),
+    );
+
+    // [6] 决策原则（自定义身份时由用户文案负责）
+    if ctx.override_text.is_none() {
+        let mut principles = t.decision_principles.to_string();
+        if ctx.quick_replies {
+            principles.push_str(t.quick_replies_principle);
+        }
+        parts.push(principles);
+    }
+
+    parts.join("\n\n")
+}
+
+fn tools_section(t: &Templates, tools: &ToolsSection<'_>) -> String {
+    let mut section = t.tools_header.to_string();
+    push_items(&mut section, &tools.tools);
+    if !tools.mcp_tools.is_empty() {
+        section.push_str(t.mcp_tools_header);
+        push_items(&mut section, &tools.mcp_tools);
+    }
+    if tools.omitted > 0 {
+        section.push_str(
+            &t.omitted_tools
+                .replace("{count}", &tools.omitted.to_string()),
+        );
+    }
+    section
+}
+
+fn push_items(out: &mut String, items: &[PromptItem<'_>]) {
+    for item in items {
+        out.push_str(&format!("- {}: {}\n", item.name, item.description));
+    }
+}
+
+/// Routine 模式的执行规范段
+pub fn routine_rules(lang: Language, name: &str) -> String {
+    templates(lang).routine_rules.replace("{name}", name)
+}
+
+/// Phase 1 路由的 system prompt
+pub fn routing_prompt(skills: &[SkillMeta], lang: Language) -> String {
+    let t = &templates(lang).routing;
+    let mut prompt = String::new();
+    prompt.push_str(t.intro);
+    prompt.push_str(t.constraints);
+    prompt.push_str(t.skills_header);
+    if skills.is_empty() {
+        prompt.push_str(t.no_skills);
+    } else {
+        for skill in skills {
+            prompt.push_str(&format!("- {}: {}\n", skill.name, skill.description));
+        }
+    }
+    prompt.push('\n');
+    prompt.push_str(t.output_format);
+    prompt.push_str(t.principles);
+    prompt
+}
+
+/// history 压缩的摘要请求
+pub fn summary_request(max_chars: usize, transcript: &str) -> String {
+    // 对话记录最后代入，其中的 `{...}` 不会被当成占位符
+    SUMMARY_REQUEST
+        .replace("{max_chars}", &max_chars.to_string())
+        .replace("{transcript}", transcript)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 与 `snapshots/<name>.snap` 比较；设置 `RRCLAW_UPDATE_SNAPSHOTS=1` 时改为写入
+    ///
+    /// 快照文件头记录 [`PROMPT_VERSION`]：文案改了但版本没加一时，重新生成的快照只有正文变化，
+    /// review 时一眼能看出来。
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/agent/prompts/snapshots")
+            .join(format!("{}.snap", name));
+        let content = format!("---\nprompt_version: {}\n---\n{}\n", PROMPT_VERSION, actual);
+        if std::env::var_os("RRCLAW_UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "缺少快照 {}，用 RRCLAW_UPDATE_SNAPSHOTS=1 生成",
+                path.display()
+            )
+        });
+        assert!(
+            content == expected,
+            "快照 {} 不一致，确认改动后用 RRCLAW_UPDATE_SNAPSHOTS=1 重新生成\n--- 期望 ---\n{}\n--- 实际 ---\n{}",
+            name,
+            expected,
+            content
+        );
+    }
+
+    const TOOLS: [PromptItem<'static>; 2] = [
+        PromptItem {
+            name: "shell",
+            description: "Run a shell command",
+        },
+        PromptItem {
+            name: "file_read",
+            description: "Read a file",
+        },
+    ];
+
+    const MCP_TOOLS: [PromptItem<'static>; 1] = [PromptItem {
+        name: "mcp_github_search",
+        description: "Search GitHub issues",
+    }];
+
+    const SKILLS: [PromptItem<'static>; 1] = [PromptItem {
+        name: "code-review",
+        description: "Review a diff for bugs",
+    }];
+
+    /// 固定工作目录与时间，其余段落按开关组合
+    fn context(
+        lang: Language,
+        identity: bool,
+        skills: bool,
+        routine: bool,
+        memories: bool,
+    ) -> SystemPromptContext<'static> {
+        SystemPromptContext {
+            lang,
+            prefix: None,
+            identity: identity.then_some("I am a backend developer; prefer concise answers."),
+            override_text: None,
+            tools: Some(ToolsSection {
+                tools: TOOLS.to_vec(),
+                mcp_tools: MCP_TOOLS.to_vec(),
+                omitted: 0,
+            }),
+            skills: if skills { SKILLS.to_vec() } else { Vec::new() },
+            mode: PromptMode::Autonomy(AutonomyLevel::Supervised),
+            goal: None,
+            memories: if memories {
+                vec!["User prefers Rust", "Project uses tokio"]
+            } else {
+                Vec::new()
+            },
+            behavior_guide: None,
+            routine: routine.then_some("daily_report"),
+            workspace: Path::new("/workspace"),
+            now: "2026-01-01 09:00:00".to_string(),
+            quick_replies: false,
+        }
+    }
+
+    #[test]
+    fn system_prompt_snapshots() {
+        for (lang, code) in [(Language::English, "en"), (Language::Chinese, "zh")] {
+            for bits in 0..16u8 {
+                let flags = [
+                    (bits & 1 != 0, "identity"),
+                    (bits & 2 != 0, "skills"),
+                    (bits & 4 != 0, "routine"),
+                    (bits & 8 != 0, "memories"),
+                ];
+                let [identity, skills, routine, memories] = flags.map(|(on, _)| on);
+                let enabled: Vec<&str> = flags
+                    .iter()
+                    .filter(|(on, _)| *on)
+                    .map(|(_, name)| *name)
+                    .collect();
+                let name = if enabled.is_empty() {
+                    format!("system_{}_base", code)
+                } else {
+                    format!("system_{}_{}", code, enabled.join("_"))
+                };
+                let ctx = context(lang, identity, skills, routine, memories);
+                assert_snapshot(&name, &system_prompt(&ctx));
+            }
+        }
+    }
+
+    #[test]
+    fn routing_and_summary_snapshots() {
+        let skills = vec![SkillMeta {
+            name: "code-review".to_string(),
+            description: "Review a diff for bugs".to_string(),
+            tags: vec![],
+            source: crate::skills::SkillSource::BuiltIn,
+            path: None,
+        }];
+        for (lang, code) in [(Language::English, "en"), (Language::Chinese, "zh")] {
+            assert_snapshot(
+                &format!("routing_{}_no_skills", code),
+                &routing_prompt(&[], lang),
+            );
+            assert_snapshot(
+                &format!("routing_{}_skills", code),
+                &routing_prompt(&skills, lang),
+            );
+        }
+        assert_snapshot(
+            "summary",
+            &summary_request(500, "user: 部署到 staging\nassistant: 已完成"),
+        );
+    }
+
+    #[test]
+    fn mode_override_and_quick_replies() {
+        let mut ctx = context(Language::English, false, false, false, false);
+        assert!(!system_prompt(&ctx).contains("```suggestions"));
+        ctx.quick_replies = true;
+        assert!(system_prompt(&ctx).ends_with(EN.quick_replies_principle));
+
+        // 自定义身份：替换身份描述，省略全部决策原则（包括后续建议）
+        ctx.override_text = Some("You are OpsBot.");
+        let prompt = system_prompt(&ctx);
+        assert!(prompt.contains("You are OpsBot."));
+        assert!(!prompt.contains(EN.identity));
+        assert!(!prompt.contains("[Decision Principles]"));
+        assert!(!prompt.contains("```suggestions"));
+
+        for (mode, expected) in [
+            (PromptMode::Safe, EN.safe_mode),
+            (PromptMode::Ask, EN.ask_mode),
+            (PromptMode::Autonomy(AutonomyLevel::ReadOnly), EN.read_only),
+            (PromptMode::Autonomy(AutonomyLevel::Full), EN.full),
+        ] {
+            ctx.mode = mode;
+            assert!(system_prompt(&ctx).contains(expected));
+        }
+
+        ctx.tools = None;
+        assert!(!system_prompt(&ctx).contains(EN.tools_header));
+    }
+
+    #[test]
+    fn placeholders_are_filled_once() {
+        let rules = routine_rules(Language::Chinese, "tesla");
+        assert!(rules.contains("定时任务 'tesla'"));
+        assert!(rules.contains("\"routine:tesla:approach\""));
+        assert!(!rules.contains("{name}"));
+
+        // 对话记录里的花括号原样保留
+        let request = summary_request(800, "cat {max_chars}");
+        assert!(request.contains("不超过 800 字符"));
+        assert!(request.contains("cat {max_chars}"));
+
+        let tools = ToolsSection {
+            tools: vec![],
+            mcp_tools: vec![],
+            omitted: 3,
+        };
+        assert!(tools_section(&ZH, &tools).contains("未携带 3 个工具"));
+    }
+}
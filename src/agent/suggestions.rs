@@ -0,0 +1,240 @@
+//! 后续建议（quick replies）：工具结果与最终回复附带的下一步操作
+//!
+//! 来源有两个：
+//! - 工具在 `ToolResult::suggestions` 中给出（如 git status 有改动时建议"查看 diff"）
+//! - 模型在最终回复末尾输出 ```` ```suggestions ```` 围栏块，每行一条
+//!
+//! 围栏块由 `extract` 从回复中剥离（写入 history 与返回给调用方的都是剥离后的文本），
+//! 流式输出时由 `SuggestionFilter` 在显示前截掉。最多保留 [`MAX_SUGGESTIONS`] 条，
+//! REPL 显示为编号选项（输入 `1`/`2`/`3` 选择），Telegram 显示为回复键盘。
+
+/// 每轮最多保留的建议条数
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// 单条建议的最大字符数（过长的视为模型没遵守格式，丢弃）
+const MAX_SUGGESTION_CHARS: usize = 80;
+
+/// 建议块的开始标记
+const BLOCK_START: &str = "```suggestions";
+
+/// 围栏结束标记
+const FENCE: &str = "```";
+
+/// 从最终回复中剥离 `suggestions` 围栏块，返回 `(剥离后的文本, 建议)`
+///
+/// 块可以出现多次；缺少结束围栏时一直取到文本末尾。
+pub fn extract(text: &str) -> (String, Vec<String>) {
+    if !text.contains(BLOCK_START) {
+        return (text.to_string(), Vec::new());
+    }
+    let mut visible = String::new();
+    let mut lines = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(BLOCK_START) {
+        visible.push_str(&rest[..start]);
+        let body = &rest[start + BLOCK_START.len()..];
+        let (block, after) = match body.find(FENCE) {
+            Some(end) => (&body[..end], &body[end + FENCE.len()..]),
+            None => (body, ""),
+        };
+        // 开始标记所在行的剩余部分不算建议（```suggestions 后面通常直接换行）
+        lines.extend(block.lines().skip(1).map(str::to_string));
+        rest = after;
+    }
+    visible.push_str(rest);
+    (visible.trim_end().to_string(), normalize(lines))
+}
+
+/// 清理建议列表：去掉列表符号 / 编号 / 引号，丢弃空行、过长项和重复项，最多 [`MAX_SUGGESTIONS`] 条
+pub fn normalize<I, S>(items: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut result: Vec<String> = Vec::new();
+    for item in items {
+        let cleaned = clean_item(item.as_ref());
+        if cleaned.is_empty()
+            || cleaned.chars().count() > MAX_SUGGESTION_CHARS
+            || result.iter().any(|s| s == &cleaned)
+        {
+            continue;
+        }
+        result.push(cleaned);
+        if result.len() == MAX_SUGGESTIONS {
+            break;
+        }
+    }
+    result
+}
+
+fn clean_item(raw: &str) -> String {
+    let mut s = raw.trim();
+    for bullet in ["- ", "* ", "• "] {
+        if let Some(stripped) = s.strip_prefix(bullet) {
+            s = stripped.trim_start();
+        }
+    }
+    // "1. xxx" / "2) xxx"
+    let digits = s.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let after = &s[digits..];
+        if let Some(stripped) = after
+            .strip_prefix(". ")
+            .or_else(|| after.strip_prefix(") "))
+        {
+            s = stripped.trim_start();
+        }
+    }
+    s.trim_matches(|c| matches!(c, '"' | '“' | '”' | '`'))
+        .trim()
+        .to_string()
+}
+
+/// 把最终回复中的建议与本轮工具给出的建议合并（回复优先），去重后最多 [`MAX_SUGGESTIONS`] 条
+pub fn merge(from_answer: Vec<String>, from_tools: &[String]) -> Vec<String> {
+    normalize(from_answer.into_iter().chain(from_tools.iter().cloned()))
+}
+
+/// REPL 输入是否选择了某条建议（`1`..`n`），返回对应文本
+pub fn pick<'a>(input: &str, suggestions: &'a [String]) -> Option<&'a str> {
+    let index: usize = input.trim().parse().ok()?;
+    suggestions.get(index.checked_sub(1)?).map(String::as_str)
+}
+
+/// 流式文本过滤：截掉 `suggestions` 围栏块，其余文本原样放行
+///
+/// 可能是开始标记前缀的尾部先暂存，下一个增量到来（或 `finish`）时再决定是否输出。
+#[derive(Debug, Default)]
+pub struct SuggestionFilter {
+    pending: String,
+    in_block: bool,
+}
+
+impl SuggestionFilter {
+    /// 追加一个文本增量，返回可以立即显示的部分
+    pub fn push(&mut self, delta: &str) -> String {
+        self.pending.push_str(delta);
+        let mut out = String::new();
+        loop {
+            if self.in_block {
+                match self.pending.find(FENCE) {
+                    Some(end) => {
+                        self.pending.drain(..end + FENCE.len());
+                        self.in_block = false;
+                    }
+                    None => return out,
+                }
+            } else {
+                match self.pending.find(BLOCK_START) {
+                    Some(start) => {
+                        out.push_str(&self.pending[..start]);
+                        self.pending.drain(..start + BLOCK_START.len());
+                        self.in_block = true;
+                    }
+                    None => {
+                        let keep = partial_marker_len(&self.pending);
+                        let emit = self.pending.len() - keep;
+                        out.push_str(&self.pending[..emit]);
+                        self.pending.drain(..emit);
+                        return out;
+                    }
+                }
+            }
+        }
+    }
+
+    /// 流结束：返回暂存的非建议文本（未闭合的建议块直接丢弃）
+    pub fn finish(&mut self) -> String {
+        let pending = std::mem::take(&mut self.pending);
+        if std::mem::take(&mut self.in_block) {
+            String::new()
+        } else {
+            pending
+        }
+    }
+}
+
+/// `text` 末尾可能是开始标记前缀的字节数
+fn partial_marker_len(text: &str) -> usize {
+    (1..BLOCK_START.len().min(text.len() + 1))
+        .rev()
+        .find(|&n| {
+            let start = text.len() - n;
+            text.is_char_boundary(start) && BLOCK_START.starts_with(&text[start..])
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = "审查完成，发现 3 个问题。\n\n```suggestions\n- 修复问题 2\n2. 查看 diff\n\"提交改动\"\n- 第四条会被丢弃\n```\n";
+
+    #[test]
+    fn extract_strips_block_and_cleans_items() {
+        let (text, suggestions) = extract(ANSWER);
+        assert_eq!(text, "审查完成，发现 3 个问题。");
+        assert_eq!(suggestions, vec!["修复问题 2", "查看 diff", "提交改动"]);
+
+        // 没有建议块：原样返回
+        let (text, suggestions) = extract("普通回复\n```rust\nfn main() {}\n```");
+        assert_eq!(text, "普通回复\n```rust\nfn main() {}\n```");
+        assert!(suggestions.is_empty());
+
+        // 缺少结束围栏、重复项、空行、过长项
+        let long = "很".repeat(MAX_SUGGESTION_CHARS + 1);
+        let (text, suggestions) = extract(&format!(
+            "好的\n```suggestions\n\n运行测试\n运行测试\n{}\n",
+            long
+        ));
+        assert_eq!(text, "好的");
+        assert_eq!(suggestions, vec!["运行测试"]);
+    }
+
+    #[test]
+    fn merge_prefers_answer_and_caps_total() {
+        let merged = merge(
+            vec!["查看 diff".to_string()],
+            &[
+                "查看 diff".to_string(),
+                "提交".to_string(),
+                "推送".to_string(),
+                "开 PR".to_string(),
+            ],
+        );
+        assert_eq!(merged, vec!["查看 diff", "提交", "推送"]);
+    }
+
+    #[test]
+    fn pick_maps_numbers_to_suggestions() {
+        let suggestions = vec!["修复问题 2".to_string(), "查看 diff".to_string()];
+        assert_eq!(pick("1", &suggestions), Some("修复问题 2"));
+        assert_eq!(pick(" 2 ", &suggestions), Some("查看 diff"));
+        assert_eq!(pick("3", &suggestions), None);
+        assert_eq!(pick("0", &suggestions), None);
+        assert_eq!(pick("1.", &suggestions), None);
+        assert_eq!(pick("1", &[]), None);
+    }
+
+    #[test]
+    fn stream_filter_hides_block_split_across_chunks() {
+        let mut filter = SuggestionFilter::default();
+        let mut shown = String::new();
+        // 逐字符推送，开始标记与结束围栏都被拆开
+        for ch in ANSWER.chars() {
+            shown.push_str(&filter.push(&ch.to_string()));
+        }
+        shown.push_str(&filter.finish());
+        assert_eq!(shown, "审查完成，发现 3 个问题。\n\n\n");
+
+        // 普通代码块只暂存到能判断为止，最终完整输出
+        let mut filter = SuggestionFilter::default();
+        let mut shown = filter.push("示例：``");
+        assert_eq!(shown, "示例：");
+        shown.push_str(&filter.push("`rust\nlet x = 1;\n```"));
+        shown.push_str(&filter.finish());
+        assert_eq!(shown, "示例：```rust\nlet x = 1;\n```");
+    }
+}
@@ -0,0 +1,106 @@
+//! Token 数粗估
+//!
+//! 不依赖具体模型的 tokenizer：CJK 字符按 1 token/字，其余字符按 4 字符/token，
+//! 每条消息另加少量格式开销。误差在 ±20% 左右，只用于 `/cost` 之类的发送前估算。
+
+use crate::providers::{ConversationMessage, ToolSpec};
+
+/// 每条消息的格式开销（role、分隔符等）
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// 估算一段文本的 token 数
+pub fn estimate_tokens(text: &str) -> usize {
+    let (cjk, other) = text.chars().fold((0usize, 0usize), |(cjk, other), c| {
+        if is_cjk(c) {
+            (cjk + 1, other)
+        } else {
+            (cjk, other + 1)
+        }
+    });
+    cjk + other.div_ceil(4)
+}
+
+/// 估算一条对话消息的 token 数（含工具调用参数）
+pub fn estimate_message_tokens(msg: &ConversationMessage) -> usize {
+    let body = match msg {
+        ConversationMessage::Chat(cm) => estimate_tokens(&cm.content),
+        ConversationMessage::AssistantToolCalls {
+            text, tool_calls, ..
+        } => {
+            text.as_deref().map_or(0, estimate_tokens)
+                + tool_calls
+                    .iter()
+                    .map(|tc| {
+                        estimate_tokens(&tc.name) + estimate_tokens(&tc.arguments.to_string())
+                    })
+                    .sum::<usize>()
+        }
+        ConversationMessage::ToolResult { content, .. } => estimate_tokens(content),
+    };
+    body + MESSAGE_OVERHEAD
+}
+
+/// 估算 tools 数组的 token 数（按序列化后的 JSON 计）
+pub fn estimate_tool_spec_tokens(specs: &[ToolSpec]) -> usize {
+    specs
+        .iter()
+        .map(|spec| {
+            estimate_tokens(&spec.name)
+                + estimate_tokens(&spec.description)
+                + estimate_tokens(&spec.parameters.to_string())
+        })
+        .sum()
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303F}'   // CJK 标点
+        | '\u{3040}'..='\u{30FF}' // 日文假名
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}' // 韩文
+        | '\u{FF00}'..='\u{FFEF}' // 全角符号
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{ChatMessage, ToolCall};
+
+    #[test]
+    fn ascii_counts_four_chars_per_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("hello"), 2);
+    }
+
+    #[test]
+    fn cjk_counts_one_token_per_char() {
+        assert_eq!(estimate_tokens("你好世界"), 4);
+        assert_eq!(estimate_tokens("你好，abcd"), 4);
+    }
+
+    #[test]
+    fn messages_include_overhead_and_tool_arguments() {
+        let chat = ConversationMessage::Chat(ChatMessage {
+            role: "user".to_string(),
+            content: "abcd".to_string(),
+            reasoning_content: None,
+            turn: 0,
+        });
+        assert_eq!(estimate_message_tokens(&chat), 1 + MESSAGE_OVERHEAD);
+
+        let calls = ConversationMessage::AssistantToolCalls {
+            text: None,
+            reasoning_content: None,
+            tool_calls: vec![ToolCall {
+                id: "1".to_string(),
+                name: "shell".to_string(),
+                arguments: serde_json::json!({"command": "ls"}),
+            }],
+            turn: 0,
+        };
+        assert!(estimate_message_tokens(&calls) > MESSAGE_OVERHEAD + 2);
+    }
+}
@@ -26,15 +26,27 @@ pub static TOOL_GROUPS: &[ToolGroup] = &[
             "write",
             "edit",
             "file",
+            "grep",
+            "文档",
+            "pdf",
+            "docx",
+        ],
+        tools: &[
+            "file_read",
+            "file_write",
+            "grep",
+            "shell",
+            "git",
+            "document_read",
         ],
-        tools: &["file_read", "file_write", "shell", "git"],
     },
     ToolGroup {
         name: "web",
         keywords: &[
             "请求", "HTTP", "API", "天气", "网络", "http", "request", "fetch", "api", "url", "URL",
+            "搜索", "search", "网上", "网页", "文章", "article",
         ],
-        tools: &["http_request"],
+        tools: &["http_request", "read_url", "web_search"],
     },
     ToolGroup {
         name: "memory",
@@ -53,7 +65,12 @@ pub static TOOL_GROUPS: &[ToolGroup] = &[
         keywords: &[
             "提交", "commit", "push", "pull", "分支", "branch", "git", "版本", "stash",
         ],
-        tools: &["git", "shell"],
+        tools: &["git", "git_commit", "shell"],
+    },
+    ToolGroup {
+        name: "secrets",
+        keywords: &["密钥", "凭据", "钥匙串", "secret", "credential", "keychain"],
+        tools: &["secret", "http_request", "shell"],
     },
     ToolGroup {
         name: "routine",
@@ -147,6 +164,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn secret_keywords_route_to_secret() {
+        let result = route_tools("用钥匙串里的 GitHub 密钥查一下 issue");
+        assert!(
+            result.contains(&"secret".to_string()),
+            "secret missing: {:?}",
+            result
+        );
+        assert!(result.contains(&"http_request".to_string()));
+    }
+
     #[test]
     fn no_match_returns_empty() {
         let result = route_tools("讲一个笑话");
@@ -0,0 +1,186 @@
+//! 对话 Turn 划分
+//!
+//! 一个 Turn = 一次用户输入 + 随后的工具调用/工具结果 + 最终回复。Agent 在 `process_message`
+//! 中为每条写入 history 的消息打上 `turn` 标记；旧版持久化的历史没有该字段（反序列化为 0），
+//! 加载时由 `assign_missing_turns` 按 user 消息补齐。压缩窗口、摘要统计都以 Turn 为单位，
+//! 保证不会把同一轮的工具调用和结果拆开。
+
+use std::ops::Range;
+
+use crate::providers::ConversationMessage;
+
+/// 为未标记（turn == 0）的消息补齐 Turn，返回最大 Turn 编号
+///
+/// user/system 消息开启新 Turn，其余消息归入当前 Turn；已标记的消息保持不变。
+pub fn assign_missing_turns(history: &mut [ConversationMessage]) -> u64 {
+    let mut max_turn = history
+        .iter()
+        .map(ConversationMessage::turn)
+        .max()
+        .unwrap_or(0);
+    let mut current = 0;
+    for msg in history.iter_mut() {
+        match msg.turn() {
+            0 => {
+                if starts_turn(msg) || current == 0 {
+                    max_turn += 1;
+                    current = max_turn;
+                }
+                msg.set_turn(current);
+            }
+            turn => current = turn,
+        }
+    }
+    max_turn
+}
+
+/// 按 Turn 划分 history，返回每个 Turn 的下标范围（连续、覆盖全部消息）
+///
+/// 相邻两条消息都已标记时按 Turn 编号是否变化判断边界；否则 user/system 消息开启新 Turn。
+pub fn turn_spans(history: &[ConversationMessage]) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for i in 1..history.len() {
+        let (prev, cur) = (&history[i - 1], &history[i]);
+        let boundary = if prev.turn() != 0 && cur.turn() != 0 {
+            prev.turn() != cur.turn()
+        } else {
+            starts_turn(cur)
+        };
+        if boundary {
+            spans.push(start..i);
+            start = i;
+        }
+    }
+    if start < history.len() {
+        spans.push(start..history.len());
+    }
+    spans
+}
+
+/// 工具结果是否表示失败（预验证拒绝、执行失败、参数缺失、用户拒绝）
+pub fn is_failed_tool_result(content: &str) -> bool {
+    let content = content.trim_start();
+    ["[失败]", "[错误]", "[参数缺失]"]
+        .iter()
+        .any(|prefix| content.starts_with(prefix))
+        || content == "用户拒绝执行该工具"
+}
+
+fn starts_turn(msg: &ConversationMessage) -> bool {
+    matches!(msg, ConversationMessage::Chat(cm) if cm.role == "user" || cm.role == "system")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{ChatMessage, ToolCall};
+
+    fn chat(role: &str, content: &str, turn: u64) -> ConversationMessage {
+        ConversationMessage::Chat(ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            reasoning_content: None,
+            turn,
+        })
+    }
+
+    fn tool_round(id: &str, turn: u64) -> Vec<ConversationMessage> {
+        vec![
+            ConversationMessage::AssistantToolCalls {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![ToolCall {
+                    id: id.to_string(),
+                    name: "shell".to_string(),
+                    arguments: serde_json::json!({}),
+                }],
+                turn,
+            },
+            ConversationMessage::ToolResult {
+                tool_call_id: id.to_string(),
+                content: "ok".to_string(),
+                turn,
+            },
+        ]
+    }
+
+    #[test]
+    fn old_payload_without_turn_deserializes_to_zero() {
+        let json = r#"[
+            {"Chat":{"role":"user","content":"hi"}},
+            {"AssistantToolCalls":{"text":null,"tool_calls":[{"id":"1","name":"shell","arguments":{}}]}},
+            {"ToolResult":{"tool_call_id":"1","content":"ok"}}
+        ]"#;
+        let history: Vec<ConversationMessage> = serde_json::from_str(json).unwrap();
+        assert_eq!(history.len(), 3);
+        assert!(history.iter().all(|m| m.turn() == 0));
+    }
+
+    #[test]
+    fn zero_turn_is_not_serialized() {
+        let json = serde_json::to_string(&chat("user", "hi", 0)).unwrap();
+        assert!(!json.contains("turn"));
+
+        let tagged = chat("user", "hi", 7);
+        let json = serde_json::to_string(&tagged).unwrap();
+        let back: ConversationMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.turn(), 7);
+    }
+
+    #[test]
+    fn assign_missing_turns_groups_by_user_message() {
+        let mut history = vec![chat("user", "a", 0)];
+        history.extend(tool_round("1", 0));
+        history.push(chat("assistant", "done", 0));
+        history.push(chat("user", "b", 0));
+        history.push(chat("assistant", "ok", 0));
+
+        assert_eq!(assign_missing_turns(&mut history), 2);
+        let turns: Vec<u64> = history.iter().map(ConversationMessage::turn).collect();
+        assert_eq!(turns, vec![1, 1, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn assign_missing_turns_continues_after_tagged_history() {
+        let mut history = vec![chat("user", "a", 4), chat("assistant", "x", 4)];
+        history.push(chat("user", "b", 0));
+        history.push(chat("assistant", "y", 0));
+
+        assert_eq!(assign_missing_turns(&mut history), 5);
+        assert_eq!(history[0].turn(), 4);
+        assert_eq!(history[3].turn(), 5);
+    }
+
+    #[test]
+    fn spans_follow_turn_tags() {
+        // Turn 1 以技能注入（user）开头，同一 Turn 内的第二条 user 消息不应切开
+        let mut history = vec![chat("user", "[技能指令]", 1), chat("user", "go", 1)];
+        history.extend(tool_round("1", 1));
+        history.push(chat("assistant", "done", 1));
+        history.push(chat("user", "next", 2));
+
+        assert_eq!(turn_spans(&history), vec![0..5, 5..6]);
+    }
+
+    #[test]
+    fn spans_fall_back_to_user_messages_when_untagged() {
+        let mut history = vec![chat("system", "摘要", 0), chat("user", "a", 0)];
+        history.extend(tool_round("1", 0));
+        history.push(chat("user", "b", 0));
+
+        assert_eq!(turn_spans(&history), vec![0..1, 1..4, 4..5]);
+        assert!(turn_spans(&[]).is_empty());
+    }
+
+    #[test]
+    fn failed_tool_results_are_detected() {
+        assert!(is_failed_tool_result("[失败] 路径不在工作区内"));
+        assert!(is_failed_tool_result("[错误] 未知工具: foo"));
+        assert!(is_failed_tool_result(
+            "[参数缺失] 工具 'shell' 缺少必填参数: command"
+        ));
+        assert!(is_failed_tool_result("用户拒绝执行该工具"));
+        assert!(!is_failed_tool_result("total 0"));
+    }
+}
@@ -1,11 +1,13 @@
 use color_eyre::eyre::{eyre, Context, Result};
 use dialoguer::{Confirm, Input, Select};
-use reedline::{DefaultPrompt, DefaultPromptSegment, ExternalPrinter, Reedline, Signal};
+use reedline::{
+    DefaultPrompt, DefaultPromptSegment, EditCommand, ExternalPrinter, Reedline, Signal,
+};
 use std::collections::HashSet;
 use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tracing::info;
 
 use crate::i18n::Language;
 
@@ -29,13 +31,45 @@ mod ansi {
     pub const DIM: &str = "\x1b[2m";
 }
 
-use crate::agent::Agent;
-use crate::config::{Config, ProviderConfig, PROVIDERS};
+use crate::agent::{Agent, AgentBranch, NOTICE_ARG, PREVIEW_ARG};
+use crate::channels::input_queue::AfterTurn;
+use crate::config::{Config, ProviderConfig, ToolVerbosity, ValidationWarning, PROVIDERS};
 use crate::memory::SqliteMemory;
-use crate::providers::{StreamEvent, ToolStatusKind};
+use crate::providers::{stream_channel, StreamEvent, ToolStatusKind};
 use crate::routines::{Routine, RoutineEngine, RoutineSource};
 use crate::skills::{load_skill_content, validate_skill_name, SkillMeta, SkillSource};
 
+/// 是否显示推理模型的思考过程（`/reasoning on|off`，默认关闭，仅当前进程有效）
+static SHOW_REASONING: AtomicBool = AtomicBool::new(false);
+
+/// 醒目地打印配置警告（REPL 横幅之前 / `rrclaw doctor`）
+pub fn print_config_warnings(warnings: &[ValidationWarning]) {
+    if warnings.is_empty() {
+        return;
+    }
+    let lang = Config::get_language();
+    let header = if lang.is_english() {
+        format!("⚠ {} config warning(s):", warnings.len())
+    } else {
+        format!("⚠ 配置存在 {} 处问题:", warnings.len())
+    };
+    eprintln!("{}{}{}", ansi::YELLOW, header, ansi::RESET);
+    for warning in warnings {
+        eprintln!("{}  - {}{}", ansi::YELLOW, warning, ansi::RESET);
+    }
+    eprintln!(
+        "{}{}{}",
+        ansi::DIM,
+        t(
+            lang,
+            "修改 config.toml 后可运行 `rrclaw doctor` 重新检查",
+            "Edit config.toml and run `rrclaw doctor` to re-check"
+        ),
+        ansi::RESET
+    );
+    eprintln!();
+}
+
 /// Telegram 运行时管理器
 /// 允许在运行时动态启动/停止 Telegram Bot
 pub struct TelegramRuntime {
@@ -229,6 +263,11 @@ pub fn setup_cli_confirm(agent: &mut Agent) {
         let lang = crate::config::Config::get_language();
         let key = approval_key(name, args);
 
+        // 一次性提醒（如工作区有未提交改动）：自动批准时也要显示
+        if let Some(notice) = args.get(NOTICE_ARG).and_then(|v| v.as_str()) {
+            println!("\n{}⚠ {}{}", ansi::YELLOW, notice, ansi::RESET);
+        }
+
         // 检查是否已自动批准
         if approved.lock().unwrap().contains(&key) {
             let display = if name == "shell" {
@@ -244,22 +283,42 @@ pub fn setup_cli_confirm(agent: &mut Agent) {
             return true;
         }
 
-        let args_str = serde_json::to_string_pretty(args).unwrap_or_else(|_| args.to_string());
+        // 有预览（如覆盖文件的 diff）时，参数中不再重复整段内容
+        let preview = args.get(PREVIEW_ARG).and_then(|v| v.as_str());
+        let shown_args = match args.as_object() {
+            Some(map) => {
+                let mut map = map.clone();
+                map.remove(NOTICE_ARG);
+                if preview.is_some() {
+                    map.remove(PREVIEW_ARG);
+                    map.remove("content");
+                }
+                serde_json::Value::Object(map)
+            }
+            None => args.clone(),
+        };
+        let args_str =
+            serde_json::to_string_pretty(&shown_args).unwrap_or_else(|_| shown_args.to_string());
         if lang.is_english() {
-            print!(
-                "\n⚠ Execute tool '{}'\n  Args: {}\n  Confirm? [y/N/a(always this session)] ",
-                name, args_str
-            );
+            println!("\n⚠ Execute tool '{}'\n  Args: {}", name, args_str);
         } else {
-            print!(
-                "\n⚠ 执行工具 '{}'\n  参数: {}\n  确认执行? [y/N/a(本会话自动批准)] ",
-                name, args_str
-            );
+            println!("\n⚠ 执行工具 '{}'\n  参数: {}", name, args_str);
+        }
+        if let Some(preview) = preview {
+            render_tool_output(&crate::tools::ToolOutputKind::Diff, preview);
         }
+        print!(
+            "  {} ",
+            t(
+                lang,
+                "确认执行? [y/N/a(本会话自动批准)]",
+                "Confirm? [y/N/a(always this session)]"
+            )
+        );
         let _ = std::io::stdout().flush();
 
-        let mut input = String::new();
-        if std::io::stdin().lock().read_line(&mut input).is_ok() {
+        // 回答进行中 stdin 由排队读线程持有，确认输入经它转交
+        if let Some(input) = super::input_queue::read_confirm_line() {
             let answer = input.trim().to_lowercase();
             match answer.as_str() {
                 "a" | "always" => {
@@ -289,6 +348,8 @@ pub async fn run_repl(
     let telegram_memory = Arc::clone(memory);
     let telegram_runtime = telegram_runtime.map(|r| Arc::clone(&r));
     setup_cli_confirm(agent);
+    // REPL 把后续建议显示为编号选项
+    agent.set_quick_replies(true);
 
     // 加载今天的对话历史
     let lang = crate::config::Config::get_language();
@@ -310,6 +371,20 @@ pub async fn run_repl(
         }
         agent.set_history(history);
     }
+    match memory.load_session_goal(&session_id).await {
+        Ok(Some(goal)) => {
+            println!(
+                "{}🎯 {} {}{}",
+                ansi::DIM,
+                t(lang, "当前目标:", "Current goal:"),
+                goal.goal,
+                ansi::RESET
+            );
+            agent.set_goal(Some(goal));
+        }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("加载会话目标失败: {:#}", e),
+    }
 
     // 创建 ExternalPrinter：允许后台 routine 任务在 reedline raw mode 下安全打印
     // reedline 会在正确的终端位置插入输出，不会因 \n 缺少 \r 导致文字从当前列开始打印
@@ -327,10 +402,13 @@ pub async fn run_repl(
     }
 
     let mut line_editor = Reedline::create().with_external_printer(printer);
-    let prompt = DefaultPrompt::new(
-        DefaultPromptSegment::Basic("rrclaw".to_string()),
-        DefaultPromptSegment::Empty,
-    );
+    let mut prompt = repl_prompt(None);
+    // `/branch` 开出的分支（进行中时不保存对话历史，退出前丢弃）
+    let mut branch: Option<AgentBranch> = None;
+
+    if let Ok(config_path) = Config::config_path() {
+        print_config_warnings(&crate::config::check_config_file(config, &config_path));
+    }
 
     if lang.is_english() {
         println!(
@@ -349,10 +427,44 @@ pub async fn run_repl(
             ansi::RESET
         );
     }
+    println!(
+        "{}{} {}{}",
+        ansi::DIM,
+        t(lang, "工作目录:", "Workspace:"),
+        agent.policy().workspace_dir.display(),
+        ansi::RESET
+    );
+    if let Some(latest) = crate::update::startup_notice(&config.update, data_dir).await {
+        println!(
+            "{}{} v{} → {}{}{}",
+            ansi::DIM,
+            t(lang, "有新版本:", "New release:"),
+            crate::update::CURRENT_VERSION,
+            latest,
+            t(
+                lang,
+                "（rrclaw self-update 升级）",
+                " (run rrclaw self-update)"
+            ),
+            ansi::RESET
+        );
+    }
     println!();
 
+    let queue = crate::channels::input_queue::router();
+    // 上一轮排队、需要自动发送的消息
+    let mut queued_submit: Option<String> = None;
+    // 上一轮回复附带的后续建议（输入编号即发送对应文本）
+    let mut quick_replies: Vec<String> = Vec::new();
     loop {
-        let sig = line_editor.read_line(&prompt);
+        let sig = match queued_submit.take() {
+            Some(line) => {
+                // 回显成与手动输入相同的提示符行
+                println!("{}rrclaw〉{}{}", ansi::DIM, ansi::RESET, line);
+                Ok(Signal::Success(line))
+            }
+            None => line_editor.read_line(&prompt),
+        };
         match sig {
             Ok(Signal::Success(line)) => {
                 let input = line.trim();
@@ -361,6 +473,18 @@ pub async fn run_repl(
                     continue;
                 }
 
+                // 输入 `1`/`2`/`3` 选择上一轮的后续建议；任何输入之后建议都失效
+                let picked =
+                    crate::agent::suggestions::pick(input, &quick_replies).map(str::to_string);
+                quick_replies.clear();
+                let input = match picked.as_deref() {
+                    Some(text) => {
+                        println!("{}→ {}{}", ansi::DIM, text, ansi::RESET);
+                        text
+                    }
+                    None => input,
+                };
+
                 let lang = crate::config::Config::get_language();
                 match input {
                     "exit" | "quit" => {
@@ -378,8 +502,33 @@ pub async fn run_repl(
                     _ => {}
                 }
 
+                // `/ask <问题>`：本条消息以只读回合执行（问题里可能含路径，先于斜杠命令识别）
+                let ask = input
+                    .strip_prefix("/ask")
+                    .filter(|rest| rest.starts_with(char::is_whitespace) && !rest.trim().is_empty())
+                    .map(str::trim);
+
                 // 斜杠命令：/word 格式（不含额外斜杠，避免把 Unix 路径误识别为命令）
-                if let Some(cmd) = input.strip_prefix('/') {
+                if let Some(cmd) = input.strip_prefix('/').filter(|_| ask.is_none()) {
+                    if let Some(arg) = cmd
+                        .strip_prefix("branch")
+                        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+                    {
+                        cmd_branch(arg.trim(), agent, &mut branch).await;
+                        prompt = repl_prompt(agent.branch_name());
+                        continue;
+                    }
+                    if branch.is_some() && rewrites_session(cmd) {
+                        println!(
+                            "{}",
+                            t(
+                                lang,
+                                "分支中不能改写会话历史，请先 /branch merge 或 /branch drop",
+                                "Not available inside a branch; /branch merge or /branch drop first"
+                            )
+                        );
+                        continue;
+                    }
                     if !cmd.contains('/') {
                         let workspace_dir = agent.policy().workspace_dir.clone();
                         handle_slash_command(
@@ -400,17 +549,116 @@ pub async fn run_repl(
                     }
                 }
 
+                // `!` 前缀：本条消息一次性以 Full 模式执行，结束后恢复原自主级别
+                let (input, one_shot) = match (ask, input.strip_prefix('!')) {
+                    (Some(question), _) => (question, Some(OneShot::Ask)),
+                    (None, Some(rest)) if !rest.trim().is_empty() => {
+                        (rest.trim(), Some(OneShot::Full))
+                    }
+                    _ => (input, None),
+                };
+
+                if one_shot == Some(OneShot::Full)
+                    && crate::security::sandbox::refuses_full(config.security.require_sandbox)
+                {
+                    print_full_refused(lang);
+                    continue;
+                }
+
+                // `@provider[:model]` 前缀：本条消息固定由该 Provider 回答（不走 fallback）
+                let (input, pinned) = match crate::agent::parse_pin(input) {
+                    Some((pin, rest)) => match crate::agent::pin::resolve_pin(&pin, config) {
+                        Ok(pinned) => (rest, Some(pinned)),
+                        Err(e) => {
+                            eprintln!("{}: {:#}\n", t(lang, "错误", "Error"), e);
+                            continue;
+                        }
+                    },
+                    None => (input, None),
+                };
+
                 println!();
-                if let Err(e) = stream_message(agent, input).await {
-                    eprintln!("{}: {:#}\n", t(lang, "错误", "Error"), e);
+                let notice = match one_shot {
+                    Some(OneShot::Full) => Some(t(
+                        lang,
+                        "⚡ 本条消息以 Full 模式执行（无需确认）",
+                        "⚡ Running this message in Full mode (no confirmation)",
+                    )),
+                    Some(OneShot::Ask) => Some(t(
+                        lang,
+                        "🔍 只读回合：本条消息只允许读取类工具",
+                        "🔍 Read-only turn: only read tools are allowed for this message",
+                    )),
+                    None => None,
+                };
+                if let Some(notice) = notice {
+                    println!("{}{}{}", ansi::DIM, notice, ansi::RESET);
+                }
+                // 回答进行中键入的整行进入排队（确认提示的输入也经读线程转交）
+                let reader =
+                    crate::channels::input_queue::TurnInputReader::start(Arc::clone(queue));
+                let saved = pinned.map(|pinned| agent.swap_provider(pinned));
+                let result =
+                    stream_message(agent, input, one_shot, config.cli.tool_verbosity).await;
+                if let Some(saved) = saved {
+                    agent.swap_provider(saved);
+                }
+                drop(reader);
+                if let Err(e) = &result {
+                    eprintln!("{}: {:#}", t(lang, "错误", "Error"), e);
+                    eprintln!(
+                        "{}{}{}\n",
+                        ansi::DIM,
+                        t(
+                            lang,
+                            "输入 /report 可生成本地故障报告（不会上传）",
+                            "Type /report to write a local incident report (never uploaded)"
+                        ),
+                        ansi::RESET
+                    );
+                }
+                if result.is_ok() {
+                    quick_replies = agent.last_suggestions().to_vec();
+                    if let Some(line) = format_quick_replies(lang, &quick_replies) {
+                        println!("{}", line);
+                    }
+                }
+                crate::report::record_turn(agent, result.err().map(|e| format!("{:#}", e)));
+
+                // 每轮对话后自动保存历史（goal_update 可能改了目标，一并保存）；分支只在内存中
+                if branch.is_none() {
+                    if let Err(e) = memory
+                        .save_conversation_history(&session_id, agent.history())
+                        .await
+                    {
+                        crate::memory::report_write_error("保存对话历史", &e);
+                    }
+                    if let Err(e) = memory.save_session_goal(&session_id, agent.goal()).await {
+                        crate::memory::report_write_error("保存会话目标", &e);
+                    }
                 }
 
-                // 每轮对话后自动保存历史
-                if let Err(e) = memory
-                    .save_conversation_history(&session_id, agent.history())
-                    .await
-                {
-                    debug!("保存对话历史失败: {:#}", e);
+                match queue.finish_turn(config.cli.queue_messages) {
+                    AfterTurn::Idle => {}
+                    AfterTurn::Submit { line, remaining } => {
+                        print_queue_remaining(lang, remaining);
+                        queued_submit = Some(line);
+                    }
+                    AfterTurn::Prefill { line, remaining } => {
+                        println!(
+                            "{}{} {}{}",
+                            ansi::DIM,
+                            t(
+                                lang,
+                                "排队消息（回车发送，可先编辑）:",
+                                "queued (Enter to send, edit first if needed):"
+                            ),
+                            line,
+                            ansi::RESET
+                        );
+                        print_queue_remaining(lang, remaining);
+                        line_editor.run_edit_commands(&[EditCommand::InsertString(line)]);
+                    }
                 }
             }
             Ok(Signal::CtrlD) | Ok(Signal::CtrlC) => {
@@ -426,17 +674,178 @@ pub async fn run_repl(
         }
     }
 
-    // 退出时最终保存一次
+    // 退出时最终保存一次（未结束的分支直接丢弃）
+    if let Some(branch) = branch.take() {
+        agent.drop_branch(branch);
+    }
     if let Err(e) = memory
         .save_conversation_history(&session_id, agent.history())
         .await
     {
-        debug!("退出时保存对话历史失败: {:#}", e);
+        crate::memory::report_write_error("退出时保存对话历史", &e);
     }
 
     Ok(())
 }
 
+/// REPL 提示符：在分支中时显示分支名
+fn repl_prompt(branch: Option<&str>) -> DefaultPrompt {
+    let left = match branch {
+        Some(name) => format!("rrclaw ⎇ {}", name),
+        None => "rrclaw".to_string(),
+    };
+    DefaultPrompt::new(
+        DefaultPromptSegment::Basic(left),
+        DefaultPromptSegment::Empty,
+    )
+}
+
+/// 会改写或重新加载会话历史的斜杠命令（分支中禁用，避免分支内容写入 session）
+fn rewrites_session(cmd: &str) -> bool {
+    let mut words = cmd.split_whitespace();
+    match words.next() {
+        Some("new") => true,
+        Some("history") => words.next() == Some("load"),
+        _ => false,
+    }
+}
+
+/// /branch [name] | /branch merge | /branch drop —— 在内存分支里试探，结束后回到主对话
+async fn cmd_branch(arg: &str, agent: &mut Agent, branch: &mut Option<AgentBranch>) {
+    let lang = crate::config::Config::get_language();
+    match arg {
+        "drop" | "merge" if branch.is_none() => {
+            println!("{}", t(lang, "当前不在分支中。", "Not in a branch."));
+        }
+        "drop" => {
+            if let Some(b) = branch.take() {
+                let name = b.name().to_string();
+                agent.drop_branch(b);
+                if lang.is_english() {
+                    println!("Dropped branch '{}'; back to the main conversation.", name);
+                } else {
+                    println!("已丢弃分支 '{}'，回到主对话。", name);
+                }
+            }
+        }
+        "merge" => {
+            let Some(b) = branch.take() else { return };
+            println!(
+                "{}{}{}",
+                ansi::DIM,
+                t(lang, "正在总结分支结论…", "Summarizing the branch…"),
+                ansi::RESET
+            );
+            match agent.summarize_branch(&b).await {
+                Ok(summary) => {
+                    let name = b.name().to_string();
+                    let merged = summary.is_some();
+                    agent.merge_branch(b, summary);
+                    match (merged, lang.is_english()) {
+                        (true, true) => println!(
+                            "Merged branch '{}': its conclusion was added to the main conversation.",
+                            name
+                        ),
+                        (true, false) => println!("已合并分支 '{}'：结论摘要已加入主对话。", name),
+                        (false, true) => println!(
+                            "Branch '{}' had no new messages; back to the main conversation.",
+                            name
+                        ),
+                        (false, false) => println!("分支 '{}' 没有新对话，已回到主对话。", name),
+                    }
+                }
+                Err(e) => {
+                    if lang.is_english() {
+                        println!(
+                            "Failed to summarize the branch: {:#} (still in the branch; retry or /branch drop)",
+                            e
+                        );
+                    } else {
+                        println!("总结分支失败: {:#}（仍在分支中，可重试或 /branch drop）", e);
+                    }
+                    *branch = Some(b);
+                }
+            }
+        }
+        name => {
+            if branch.is_some() {
+                println!(
+                    "{}",
+                    t(
+                        lang,
+                        "已在分支中（不支持嵌套），先 /branch merge 或 /branch drop。",
+                        "Already in a branch (no nesting); /branch merge or /branch drop first."
+                    )
+                );
+                return;
+            }
+            match agent.fork(Some(name.to_string())) {
+                Ok(b) => {
+                    if lang.is_english() {
+                        println!(
+                            "Now in branch '{}': messages stay out of the main conversation \
+                             (/branch merge keeps a summary, /branch drop discards it).",
+                            b.name()
+                        );
+                    } else {
+                        println!(
+                            "已进入分支 '{}'：之后的对话不会写入主对话（/branch merge 保留结论摘要，/branch drop 丢弃）。",
+                            b.name()
+                        );
+                    }
+                    *branch = Some(b);
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+}
+
+/// 本轮之后还有排队消息时提示剩余条数
+/// 后续建议的显示行：`  [1] 查看 diff  [2] 提交改动  （输入编号发送）`
+fn format_quick_replies(lang: Language, suggestions: &[String]) -> Option<String> {
+    if suggestions.is_empty() {
+        return None;
+    }
+    let items: Vec<String> = suggestions
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("[{}] {}", i + 1, s))
+        .collect();
+    Some(format!(
+        "{}  {}  {}{}\n",
+        ansi::DIM,
+        items.join("  "),
+        t(lang, "（输入编号发送）", "(type a number to send)"),
+        ansi::RESET
+    ))
+}
+
+fn print_queue_remaining(lang: Language, remaining: usize) {
+    if remaining == 0 {
+        return;
+    }
+    println!(
+        "{}{}{}",
+        ansi::DIM,
+        if lang.is_english() {
+            format!("({} more queued, stop clears them)", remaining)
+        } else {
+            format!("（还有 {} 条排队，输入 stop 清空）", remaining)
+        },
+        ansi::RESET
+    );
+}
+
+/// 单条消息的一次性执行方式，结束后自动恢复
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OneShot {
+    /// `!` 前缀：以 Full 模式执行
+    Full,
+    /// `/ask` 前缀：只读回合，只允许 `ToolRisk::Read` 工具
+    Ask,
+}
+
 /// 处理斜杠命令
 #[allow(clippy::too_many_arguments)]
 async fn handle_slash_command(
@@ -463,9 +872,15 @@ async fn handle_slash_command(
                 .save_conversation_history(session_id, agent.history())
                 .await
             {
-                debug!("保存对话历史失败: {:#}", e);
+                crate::memory::report_write_error("保存对话历史", &e);
             }
             agent.clear_history();
+            if agent.goal().is_some() {
+                agent.set_goal(None);
+                if let Err(e) = memory.save_session_goal(session_id, None).await {
+                    crate::memory::report_write_error("保存会话目标", &e);
+                }
+            }
             let lang = crate::config::Config::get_language();
             println!("{}", t(lang, "已开始新对话。", "New conversation started."));
         }
@@ -476,22 +891,117 @@ async fn handle_slash_command(
         "config" => {
             cmd_config(agent);
         }
+        "diff" => {
+            let rest = cmd["diff".len()..].trim();
+            cmd_diff(rest, agent).await;
+        }
         "switch" => {
             cmd_switch(agent, config)?;
         }
         "apikey" => {
             cmd_apikey(agent, config)?;
         }
+        "maxtokens" => {
+            let rest = cmd["maxtokens".len()..].trim();
+            cmd_maxtokens(rest, agent)?;
+        }
+        "temp" => {
+            let rest = cmd["temp".len()..].trim();
+            cmd_temp(rest, agent);
+        }
+        "param" => {
+            let rest = cmd["param".len()..].trim();
+            cmd_param(rest, agent);
+        }
         "skill" => {
             // 切掉命令名，剩余部分作为参数
             let rest = cmd["skill".len()..].trim();
-            cmd_skill(rest, agent, skills)?;
+            cmd_skill(rest, agent, skills, data_dir)?;
         }
         "mcp" => {
             cmd_mcp(agent);
         }
+        "cost" => {
+            cmd_cost(agent, config);
+        }
+        "whoami" => {
+            cmd_whoami(
+                agent,
+                memory,
+                config,
+                skills,
+                data_dir,
+                routine_engine.as_deref(),
+                telegram_runtime.as_deref(),
+            )
+            .await;
+        }
+        "offline" => {
+            let rest = cmd["offline".len()..].trim();
+            cmd_offline(rest, agent).await;
+        }
+        "ps" => {
+            cmd_ps();
+        }
+        "stats" => {
+            cmd_stats();
+        }
+        "reasoning" => {
+            let rest = cmd["reasoning".len()..].trim();
+            cmd_reasoning(rest);
+        }
+        "goal" => {
+            let rest = cmd["goal".len()..].trim();
+            cmd_goal(rest, agent, session_id, memory).await;
+        }
+        "report" => {
+            cmd_report(agent);
+        }
+        "history" => {
+            let rest = cmd["history".len()..].trim();
+            cmd_history(rest, agent, session_id, memory).await;
+        }
+        "undo-file" => {
+            let rest = cmd["undo-file".len()..].trim();
+            cmd_undo_file(rest, agent);
+        }
+        "safety" => {
+            let rest = cmd["safety".len()..].trim();
+            cmd_safety(rest, agent, session_id).await;
+        }
+        "kill" => {
+            let rest = cmd["kill".len()..].trim();
+            cmd_kill(rest);
+        }
+        "good" | "bad" => {
+            let rating = if name == "good" {
+                crate::memory::FeedbackRating::Good
+            } else {
+                crate::memory::FeedbackRating::Bad
+            };
+            let reason = cmd[name.len()..].trim();
+            cmd_feedback(rating, reason, agent, memory, data_dir).await;
+        }
         "mode" => {
-            cmd_mode(agent)?;
+            cmd_mode(agent, config, cmd["mode".len()..].trim())?;
+        }
+        "ask" => {
+            let lang = crate::config::Config::get_language();
+            println!(
+                "{}",
+                t(
+                    lang,
+                    "用法: /ask <问题>（本条消息只允许读取类工具）",
+                    "Usage: /ask <question> (only read tools are allowed for this message)"
+                )
+            );
+        }
+        "readonly" => {
+            let rest = cmd["readonly".len()..].trim();
+            cmd_readonly(rest, agent);
+        }
+        "init-project" => {
+            run_init_project(&workspace_dir)?;
         }
         "identity" => {
             // 切掉命令名，剩余部分作为参数
@@ -501,7 +1011,7 @@ async fn handle_slash_command(
         "routine" => {
             // 切掉命令名，剩余部分作为参数
             let rest = cmd["routine".len()..].trim();
-            cmd_routine(rest, routine_engine).await;
+            cmd_routine(rest, routine_engine, config.cli.tool_verbosity).await;
         }
         "telegram" => {
             // 切掉命令名，剩余部分作为参数
@@ -536,17 +1046,29 @@ async fn handle_slash_command(
 }
 
 /// /skill 命令入口 —— 解析子命令后分发
-fn cmd_skill(rest: &str, agent: &mut Agent, skills: &[SkillMeta]) -> Result<()> {
+fn cmd_skill(
+    rest: &str,
+    agent: &mut Agent,
+    skills: &[SkillMeta],
+    data_dir: &std::path::Path,
+) -> Result<()> {
     let mut parts = rest.splitn(2, ' ');
     let sub = parts.next().unwrap_or("").trim();
     let arg = parts.next().map(|s| s.trim());
 
     match sub {
         "" => cmd_skill_list(skills),
-        "new" => cmd_skill_new(arg)?,
-        "edit" => cmd_skill_edit(arg, skills)?,
-        "delete" => cmd_skill_delete(arg, skills)?,
+        "new" | "edit" | "delete" => {
+            match sub {
+                "new" => cmd_skill_new(arg)?,
+                "edit" => cmd_skill_edit(arg, skills)?,
+                _ => cmd_skill_delete(arg, skills)?,
+            }
+            // 同进程的工厂（Routine、Telegram）下次创建 Agent 前重新扫描
+            crate::agent::ChangeSignal::global().skills_changed();
+        }
         "show" => cmd_skill_show(arg, skills)?,
+        "stats" => cmd_skill_stats(skills, data_dir),
         name => {
             // 默认行为：加载技能指令注入当前对话
             let lang = crate::config::Config::get_language();
@@ -573,14 +1095,20 @@ fn cmd_skill(rest: &str, agent: &mut Agent, skills: &[SkillMeta]) -> Result<()>
 fn cmd_skill_list(skills: &[SkillMeta]) {
     let lang = crate::config::Config::get_language();
     if skills.is_empty() {
+        let skills_dir = crate::config::RrclawPaths::resolve()
+            .map(|paths| paths.skills_dir().display().to_string())
+            .unwrap_or_else(|_| "skills".to_string());
         if lang.is_english() {
             println!("No skills available.");
             println!("  Use /skill new <name> to create a skill");
-            println!("  or place skill directories at ~/.rrclaw/skills/<name>/SKILL.md");
+            println!(
+                "  or place skill directories at {}/<name>/SKILL.md",
+                skills_dir
+            );
         } else {
             println!("暂无可用技能。");
             println!("  使用 /skill new <name> 创建技能");
-            println!("  或将技能目录放到 ~/.rrclaw/skills/<name>/SKILL.md");
+            println!("  或将技能目录放到 {}/<name>/SKILL.md", skills_dir);
         }
         return;
     }
@@ -600,12 +1128,110 @@ fn cmd_skill_list(skills: &[SkillMeta]) {
         println!("  /skill new <name>     Create a new skill");
         println!("  /skill edit <name>    Edit skill ($EDITOR)");
         println!("  /skill delete <name>  Delete skill");
+        println!("  /skill stats          Usage statistics per skill");
     } else {
         println!("  /skill <name>         加载技能指令到当前对话");
         println!("  /skill show <name>    查看技能完整内容");
         println!("  /skill new <name>     创建新技能");
         println!("  /skill edit <name>    编辑技能（$EDITOR）");
         println!("  /skill delete <name>  删除技能");
+        println!("  /skill stats          各技能使用统计");
+    }
+}
+
+/// /skill stats — 各技能命中次数、最近使用时间、澄清占比，标出 30 天未用的技能
+fn cmd_skill_stats(skills: &[SkillMeta], data_dir: &std::path::Path) {
+    use crate::skills::usage::{SqliteSkillUsage, STALE_AFTER_DAYS};
+
+    let lang = crate::config::Config::get_language();
+    let stats = match SqliteSkillUsage::open(data_dir).and_then(|store| store.stats()) {
+        Ok(stats) => stats,
+        Err(e) => {
+            println!(
+                "{}: {:#}",
+                t(lang, "读取技能统计失败", "Failed to read skill stats"),
+                e
+            );
+            return;
+        }
+    };
+    if skills.is_empty() {
+        println!("{}", t(lang, "暂无可用技能。", "No skills available."));
+        return;
+    }
+
+    // 只展示当前可用的技能；已删除技能的历史记录保留在库中但不显示
+    let now = chrono::Utc::now();
+    let mut rows: Vec<(&SkillMeta, Option<&crate::skills::usage::SkillStats>)> = skills
+        .iter()
+        .map(|meta| (meta, stats.iter().find(|s| s.skill == meta.name)))
+        .collect();
+    rows.sort_by_key(|(meta, s)| (std::cmp::Reverse(s.map_or(0, |s| s.hits())), &meta.name));
+
+    println!(
+        "{:<24} {:>6} {:>16} {:<12} {:>8}",
+        t(lang, "技能", "Skill"),
+        t(lang, "命中", "Hits"),
+        t(lang, "路由/手动/工具", "routed/man/tool"),
+        t(lang, "最近使用", "Last used"),
+        t(lang, "澄清率", "Clarify"),
+    );
+    let mut stale = Vec::new();
+    for (meta, s) in rows {
+        let (hits, split, last_used, rate) = match s {
+            Some(s) => (
+                s.hits(),
+                format!("{}/{}/{}", s.routed, s.manual, s.tool),
+                s.last_used
+                    .map(|t| {
+                        t.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d")
+                            .to_string()
+                    })
+                    .unwrap_or_else(|| "-".to_string()),
+                s.clarification_rate()
+                    .map(|r| format!("{:.0}%", r * 100.0))
+                    .unwrap_or_else(|| "-".to_string()),
+            ),
+            None => (0, "0/0/0".to_string(), "-".to_string(), "-".to_string()),
+        };
+        let is_stale = s.is_some_and(|s| s.is_stale(now));
+        if is_stale {
+            stale.push(meta);
+        }
+        println!(
+            "{:<24} {:>6} {:>16} {:<12} {:>8}{}",
+            meta.name,
+            hits,
+            split,
+            last_used,
+            rate,
+            if is_stale { "  ⚠" } else { "" }
+        );
+    }
+
+    if !stale.is_empty() {
+        println!();
+        if lang.is_english() {
+            println!(
+                "⚠ Not used in {} days — candidates for deletion:",
+                STALE_AFTER_DAYS
+            );
+        } else {
+            println!("⚠ 超过 {} 天未使用，可考虑删除:", STALE_AFTER_DAYS);
+        }
+        for meta in stale {
+            if meta.source == crate::skills::SkillSource::BuiltIn {
+                println!("  {} {}", meta.source.label_for(lang), meta.name);
+            } else {
+                println!(
+                    "  {} {}  (/skill delete {})",
+                    meta.source.label_for(lang),
+                    meta.name,
+                    meta.name
+                );
+            }
+        }
     }
 }
 
@@ -796,9 +1422,15 @@ fn cmd_identity(
     match sub {
         "" | "status" => cmd_identity_status(data_dir, workspace_dir),
         "show" => cmd_identity_show(arg, data_dir, workspace_dir),
-        "edit" => cmd_identity_edit(arg, data_dir, workspace_dir),
+        "edit" => {
+            cmd_identity_edit(arg, data_dir, workspace_dir)?;
+            crate::agent::ChangeSignal::global().identity_changed();
+            Ok(())
+        }
         "reload" => {
             agent.reload_identity(&workspace_dir, data_dir);
+            // 同进程的工厂（Routine、Telegram）下次创建 Agent 前重新读取
+            crate::agent::ChangeSignal::global().identity_changed();
             let lang = crate::config::Config::get_language();
             println!(
                 "{}",
@@ -1282,10 +1914,9 @@ fn cmd_identity_show(
     Ok(())
 }
 
-/// 获取用户全局 skills 目录 ~/.rrclaw/skills/
+/// 获取用户全局 skills 目录：配置根目录下的 skills/（见 `RrclawPaths`）
 fn global_skills_dir() -> Result<std::path::PathBuf> {
-    let base_dirs = directories::BaseDirs::new().ok_or_else(|| eyre!("无法获取 home 目录"))?;
-    Ok(base_dirs.home_dir().join(".rrclaw").join("skills"))
+    Ok(crate::config::RrclawPaths::resolve()?.skills_dir())
 }
 
 /// 找到可编辑的 skill 路径（全局或项目级，非内置）
@@ -1322,6 +1953,79 @@ fn find_editable_skill_path(name: &str, skills: &[SkillMeta]) -> Result<std::pat
     skill.path.clone().ok_or_else(|| eyre!("技能路径为空"))
 }
 
+/// /whoami — 汇总当前生效的身份与配置
+async fn cmd_whoami(
+    agent: &Agent,
+    memory: &Arc<SqliteMemory>,
+    config: &Config,
+    skills: &[SkillMeta],
+    data_dir: &std::path::Path,
+    routine_engine: Option<&RoutineEngine>,
+    telegram_runtime: Option<&TelegramRuntime>,
+) {
+    use super::whoami::{collect, TelegramStatus};
+    let telegram = if telegram_runtime.is_some_and(|r| r.is_running()) {
+        TelegramStatus::Running
+    } else if config.telegram.is_some() {
+        TelegramStatus::Stopped
+    } else {
+        TelegramStatus::NotConfigured
+    };
+    let info = collect(
+        agent,
+        data_dir,
+        Config::config_path().ok(),
+        skills,
+        routine_engine,
+        memory.as_ref(),
+        telegram,
+    )
+    .await;
+    let lang = crate::config::Config::get_language();
+    println!("{}", t(lang, "当前身份与配置:", "Who am I:"));
+    println!("{}", info.render(lang));
+}
+
+/// /diff [args] — 直接调用 GitTool 的 diff 显示未提交改动（不经过 LLM）
+async fn cmd_diff(rest: &str, agent: &Agent) {
+    let lang = crate::config::Config::get_language();
+    match workspace_diff(agent.policy(), rest).await {
+        Ok(diff) if diff.trim().is_empty() => {
+            println!(
+                "{}",
+                t(lang, "没有未提交的改动。", "No uncommitted changes.")
+            )
+        }
+        Ok(diff) => {
+            for line in diff.lines() {
+                println!("{}", colorize_diff_line(line));
+            }
+        }
+        Err(e) => println!("{}{}{}", ansi::RED, e, ansi::RESET),
+    }
+}
+
+/// 在工作目录执行 `git diff [args]`
+///
+/// diff 只读，因此绕过 GitTool 的 `pre_validate`（ReadOnly 模式同样可用）；
+/// `--output` 会写文件，拒绝。
+async fn workspace_diff(policy: &crate::security::SecurityPolicy, args: &str) -> Result<String> {
+    if args.split_whitespace().any(|a| a.starts_with("--output")) {
+        return Err(eyre!("/diff 不支持 --output"));
+    }
+    let result = crate::tools::Tool::execute(
+        &crate::tools::git::GitTool,
+        serde_json::json!({ "action": "diff", "args": args }),
+        policy,
+    )
+    .await?;
+    if result.success {
+        Ok(result.output)
+    } else {
+        Err(eyre!(result.error.unwrap_or_default().trim().to_string()))
+    }
+}
+
 /// /config — 显示当前配置
 fn cmd_config(agent: &Agent) {
     let lang = crate::config::Config::get_language();
@@ -1331,17 +2035,653 @@ fn cmd_config(agent: &Agent) {
         println!("  Provider:   {}", agent.provider_name());
         println!("  Base URL:   {}", agent.base_url());
         println!("  Model:      {}", agent.model());
-        println!("  Temp:       {}", agent.temperature());
+        for (name, value) in generation_param_rows(agent, lang) {
+            println!("  {:<12}{}", format!("{}:", name), value);
+        }
         println!("  Mode:       {:?}", policy.autonomy);
         println!("  Workspace:  {}", policy.workspace_dir.display());
+        if crate::memory::is_degraded() {
+            println!(
+                "  Memory:     {}degraded — this session is not being saved (see logs){}",
+                ansi::YELLOW,
+                ansi::RESET
+            );
+        }
     } else {
         println!("当前配置:");
         println!("  Provider: {}", agent.provider_name());
         println!("  Base URL: {}", agent.base_url());
         println!("  模型: {}", agent.model());
-        println!("  温度: {}", agent.temperature());
+        for (name, value) in generation_param_rows(agent, lang) {
+            println!("  {}: {}", name, value);
+        }
         println!("  安全模式: {:?}", policy.autonomy);
         println!("  工作目录: {}", policy.workspace_dir.display());
+        if crate::memory::is_degraded() {
+            println!(
+                "  记忆库: {}已降级，本次会话不持久化（详见日志）{}",
+                ansi::YELLOW,
+                ansi::RESET
+            );
+        }
+    }
+}
+
+/// /good、/bad [reason] — 对上一轮回复打标，写入 feedback 表
+async fn cmd_feedback(
+    rating: crate::memory::FeedbackRating,
+    reason: &str,
+    agent: &Agent,
+    memory: &Arc<SqliteMemory>,
+    data_dir: &std::path::Path,
+) {
+    let lang = crate::config::Config::get_language();
+    let Some(turn) =
+        crate::memory::feedback::summarize_last_turn(agent.history(), agent.routed_skills())
+    else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "没有可评价的上一轮对话。",
+                "No previous turn to rate."
+            )
+        );
+        return;
+    };
+
+    let result = match crate::memory::FeedbackStore::open(data_dir) {
+        Ok(store) => {
+            crate::memory::feedback::record_feedback(
+                &store,
+                memory.as_ref(),
+                "cli",
+                rating,
+                turn,
+                Some(reason.to_string()),
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => {
+            let msg = match (rating, reason.is_empty()) {
+                (crate::memory::FeedbackRating::Good, _) => {
+                    t(lang, "👍 已记录反馈。", "👍 Feedback recorded.")
+                }
+                (crate::memory::FeedbackRating::Bad, true) => t(
+                    lang,
+                    "👎 已记录反馈。附上原因（/bad <原因>）可让助手记住这次教训。",
+                    "👎 Feedback recorded. Add a reason (/bad <reason>) so the assistant remembers it.",
+                ),
+                (crate::memory::FeedbackRating::Bad, false) => t(
+                    lang,
+                    "👎 已记录反馈，并存入记忆供后续参考。",
+                    "👎 Feedback recorded and saved to memory.",
+                ),
+            };
+            println!("{}", msg);
+        }
+        Err(e) => println!(
+            "{}: {:#}",
+            t(lang, "记录反馈失败", "Failed to record feedback"),
+            e
+        ),
+    }
+}
+
+/// /reasoning [on|off]：切换思考过程显示，无参数时显示当前状态
+fn cmd_reasoning(rest: &str) {
+    let lang = crate::config::Config::get_language();
+    match rest {
+        "on" => SHOW_REASONING.store(true, Ordering::Relaxed),
+        "off" => SHOW_REASONING.store(false, Ordering::Relaxed),
+        "" => {}
+        _ => {
+            println!(
+                "{}",
+                t(lang, "用法: /reasoning on|off", "Usage: /reasoning on|off")
+            );
+            return;
+        }
+    }
+    let enabled = SHOW_REASONING.load(Ordering::Relaxed);
+    println!(
+        "{}",
+        match (enabled, lang.is_english()) {
+            (true, true) => "Reasoning display: on (dimmed, before the answer)",
+            (false, true) => "Reasoning display: off",
+            (true, false) => "思考过程显示: 开（暗色，显示在回答之前）",
+            (false, false) => "思考过程显示: 关",
+        }
+    );
+}
+
+/// /goal set <目标> | show | done：管理会话目标（修改后立即保存）
+async fn cmd_goal(rest: &str, agent: &mut Agent, session_id: &str, memory: &SqliteMemory) {
+    let lang = crate::config::Config::get_language();
+    let (sub, arg) = rest
+        .split_once(char::is_whitespace)
+        .map(|(sub, arg)| (sub, arg.trim()))
+        .unwrap_or((rest, ""));
+    match sub {
+        "set" if !arg.is_empty() => {
+            let goal = crate::agent::SessionGoal::new(arg);
+            println!("🎯 {} {}", t(lang, "目标已设置:", "Goal set:"), goal.goal);
+            agent.set_goal(Some(goal));
+        }
+        "" | "show" => {
+            match agent.goal() {
+                Some(goal) => {
+                    println!("🎯 {}", goal.goal);
+                    let progress = goal.progress(lang);
+                    if !progress.is_empty() {
+                        print!("{}{}{}", ansi::DIM, progress, ansi::RESET);
+                    }
+                }
+                None => println!(
+                    "{}",
+                    t(
+                        lang,
+                        "当前没有目标。用 /goal set <目标> 设置。",
+                        "No active goal. Use /goal set <goal> to set one."
+                    )
+                ),
+            }
+            return;
+        }
+        "done" => match agent.goal() {
+            Some(goal) => {
+                println!(
+                    "✓ {} {}",
+                    t(lang, "目标已完成:", "Goal completed:"),
+                    goal.goal
+                );
+                agent.set_goal(None);
+            }
+            None => {
+                println!("{}", t(lang, "当前没有目标。", "No active goal."));
+                return;
+            }
+        },
+        _ => {
+            println!(
+                "{}",
+                t(
+                    lang,
+                    "用法: /goal set <目标> | /goal show | /goal done",
+                    "Usage: /goal set <goal> | /goal show | /goal done"
+                )
+            );
+            return;
+        }
+    }
+    if let Err(e) = memory.save_session_goal(session_id, agent.goal()).await {
+        crate::memory::report_write_error("保存会话目标", &e);
+    }
+}
+
+/// /undo-file <path>：用最近一次覆盖前的快照恢复文件（可重复执行逐步回退）
+fn cmd_undo_file(rest: &str, agent: &Agent) {
+    let lang = crate::config::Config::get_language();
+    if rest.is_empty() {
+        println!(
+            "{}",
+            t(lang, "用法: /undo-file <路径>", "Usage: /undo-file <path>")
+        );
+        return;
+    }
+    let path = agent.policy().workspace_dir.join(rest);
+    let result = crate::config::RrclawPaths::resolve()
+        .and_then(|paths| crate::tools::undo::UndoStore::new(paths.undo_dir()).restore(&path));
+    match result {
+        Ok(snapshot) => {
+            if lang.is_english() {
+                println!(
+                    "{}✓ Restored {} from {}{}",
+                    ansi::GREEN,
+                    path.display(),
+                    snapshot.display(),
+                    ansi::RESET
+                );
+            } else {
+                println!(
+                    "{}✓ 已从 {} 恢复 {}{}",
+                    ansi::GREEN,
+                    snapshot.display(),
+                    path.display(),
+                    ansi::RESET
+                );
+            }
+        }
+        Err(e) => println!(
+            "{}{}: {:#}{}",
+            ansi::RED,
+            t(lang, "恢复失败", "Restore failed"),
+            e,
+            ansi::RESET
+        ),
+    }
+}
+
+/// /safety stash|restore：把工作区未提交的改动存入带会话 ID 标记的 git stash / 恢复本会话的暂存
+async fn cmd_safety(rest: &str, agent: &Agent, session_id: &str) {
+    use crate::tools::git::GitTool;
+
+    let lang = crate::config::Config::get_language();
+    let dir = &agent.policy().workspace_dir;
+    match rest {
+        "stash" => match GitTool::safety_stash(dir, session_id).await {
+            Ok(Some(message)) => {
+                if lang.is_english() {
+                    println!(
+                        "{}✓ Uncommitted changes stashed as '{}'; bring them back with /safety restore{}",
+                        ansi::GREEN,
+                        message,
+                        ansi::RESET
+                    );
+                } else {
+                    println!(
+                        "{}✓ 未提交的改动已暂存为 '{}'，用 /safety restore 恢复{}",
+                        ansi::GREEN,
+                        message,
+                        ansi::RESET
+                    );
+                }
+            }
+            Ok(None) => println!(
+                "{}",
+                t(
+                    lang,
+                    "工作区没有未提交的改动，无需暂存",
+                    "No uncommitted changes; nothing to stash"
+                )
+            ),
+            Err(e) => println!(
+                "{}{}: {:#}{}",
+                ansi::RED,
+                t(lang, "暂存失败", "Stash failed"),
+                e,
+                ansi::RESET
+            ),
+        },
+        "restore" => match GitTool::safety_restore(dir, session_id).await {
+            Ok(stash_ref) => {
+                if lang.is_english() {
+                    println!("{}✓ Restored {}{}", ansi::GREEN, stash_ref, ansi::RESET);
+                } else {
+                    println!("{}✓ 已恢复 {}{}", ansi::GREEN, stash_ref, ansi::RESET);
+                }
+            }
+            Err(e) => println!(
+                "{}{}: {:#}{}",
+                ansi::RED,
+                t(lang, "恢复失败", "Restore failed"),
+                e,
+                ansi::RESET
+            ),
+        },
+        _ => println!(
+            "{}",
+            t(
+                lang,
+                "用法: /safety stash|restore",
+                "Usage: /safety stash|restore"
+            )
+        ),
+    }
+}
+
+/// /history [save|load <文件>] — 查看 / 导出 / 导入原始对话历史 JSON（排查持久化问题用）
+///
+/// 相对路径基于 workspace；load 后立即写回当前 session，与自动保存一致。
+async fn cmd_history(rest: &str, agent: &mut Agent, session_id: &str, memory: &SqliteMemory) {
+    let lang = crate::config::Config::get_language();
+    let (sub, arg) = rest
+        .split_once(char::is_whitespace)
+        .map(|(s, a)| (s, a.trim()))
+        .unwrap_or((rest, ""));
+    let usage = t(
+        lang,
+        "用法: /history [save <文件> | load <文件>]",
+        "Usage: /history [save <file> | load <file>]",
+    );
+
+    let result = match (sub, arg) {
+        ("", _) => agent.export_history().map(|json| println!("{}", json)),
+        ("save", file) if !file.is_empty() => {
+            let path = agent.policy().workspace_dir.join(file);
+            agent.export_history().and_then(|json| {
+                std::fs::write(&path, json)
+                    .wrap_err_with(|| format!("写入失败: {}", path.display()))?;
+                if lang.is_english() {
+                    println!(
+                        "Saved {} messages to {}",
+                        agent.history().len(),
+                        path.display()
+                    );
+                } else {
+                    println!(
+                        "已保存 {} 条消息到 {}",
+                        agent.history().len(),
+                        path.display()
+                    );
+                }
+                Ok(())
+            })
+        }
+        ("load", file) if !file.is_empty() => {
+            let path = agent.policy().workspace_dir.join(file);
+            let loaded = std::fs::read_to_string(&path)
+                .wrap_err_with(|| format!("读取失败: {}", path.display()))
+                .and_then(|json| agent.import_history(&json));
+            match loaded {
+                Ok(dropped) => {
+                    if lang.is_english() {
+                        println!(
+                            "Loaded {} messages ({} orphan tool results dropped)",
+                            agent.history().len(),
+                            dropped
+                        );
+                    } else {
+                        println!(
+                            "已加载 {} 条消息（清理孤立 ToolResult {} 条）",
+                            agent.history().len(),
+                            dropped
+                        );
+                    }
+                    if let Err(e) = memory
+                        .save_conversation_history(session_id, agent.history())
+                        .await
+                    {
+                        crate::memory::report_write_error("保存对话历史", &e);
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        _ => {
+            println!("{}", usage);
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        println!("{}{:#}{}", ansi::RED, e, ansi::RESET);
+    }
+}
+
+/// /offline — 查看离线状态；`/offline probe` 立即探测 Provider 连通性
+async fn cmd_offline(rest: &str, agent: &Agent) {
+    let lang = crate::config::Config::get_language();
+    let state = crate::providers::OfflineState::global();
+
+    if rest == "probe" {
+        println!(
+            "{} {} ...",
+            t(lang, "正在探测", "Probing"),
+            agent.base_url()
+        );
+        if state.probe(agent.base_url()).await {
+            println!(
+                "{}",
+                t(
+                    lang,
+                    "✓ 网络可达，已恢复在线。",
+                    "✓ Reachable, back online."
+                )
+            );
+        } else {
+            println!(
+                "{}",
+                t(
+                    lang,
+                    "✗ 仍无法连接 Provider。",
+                    "✗ Provider still unreachable."
+                )
+            );
+        }
+        return;
+    }
+
+    let status = state.status();
+    if !status.offline {
+        println!("{}", t(lang, "在线", "Online"));
+        return;
+    }
+    println!("{}", t(lang, "离线", "Offline"));
+    if let Some(since) = &status.since {
+        println!("  {}: {}", t(lang, "开始于", "Since"), since);
+    }
+    if let Some(err) = &status.last_error {
+        println!("  {}: {}", t(lang, "最后错误", "Last error"), err);
+    }
+    println!(
+        "  {}",
+        t(
+            lang,
+            "后台每 30 秒自动探测一次，也可输入 /offline probe 立即检测。",
+            "Auto-probing every 30s in background; use /offline probe to check now."
+        )
+    );
+}
+
+/// /report — 把最近一轮的上下文写成本地故障报告（脱敏，不上传）
+fn cmd_report(agent: &Agent) {
+    let lang = crate::config::Config::get_language();
+    let result = crate::config::RrclawPaths::resolve().and_then(|paths| {
+        crate::report::record_context(agent);
+        crate::report::write_report(&paths.reports_dir(), "/report")
+    });
+    match result {
+        Ok(dir) => println!("{}", crate::report::saved_message(&dir)),
+        Err(e) => println!(
+            "{}: {:#}",
+            t(lang, "生成报告失败", "Failed to write report"),
+            e
+        ),
+    }
+}
+
+/// /stats — 流式输出的背压统计（见 providers/stream_sink.rs）
+fn cmd_stats() {
+    let lang = crate::config::Config::get_language();
+    let stats = crate::providers::stream_sink::stats();
+    println!(
+        "{}: {}",
+        t(lang, "流式通道容量", "Stream channel capacity"),
+        crate::providers::STREAM_CHANNEL_CAPACITY
+    );
+    println!(
+        "{}: {}",
+        t(
+            lang,
+            "通道已满时合并的文本增量",
+            "Text deltas coalesced while the channel was full"
+        ),
+        stats.coalesced
+    );
+    println!(
+        "{}: {}",
+        t(
+            lang,
+            "通道已满时丢弃的事件",
+            "Events dropped while the channel was full"
+        ),
+        stats.dropped
+    );
+}
+
+/// `rrclaw init-project` / `/init-project` — 检测工具链，展示计划并确认后生成 `.rrclaw/`
+pub fn run_init_project(workspace_dir: &std::path::Path) -> Result<()> {
+    use crate::agent::project_init;
+
+    let lang = crate::config::Config::get_language();
+    let profile = project_init::detect(&project_init::scan(workspace_dir));
+    let plan = project_init::plan(&profile, |p| workspace_dir.join(p).exists());
+    let none = t(lang, "（未检测到）", "(none detected)");
+    let join = |items: Vec<String>| {
+        if items.is_empty() {
+            none.to_string()
+        } else {
+            items.join(", ")
+        }
+    };
+
+    println!(
+        "{}: {}",
+        t(lang, "工作目录", "Workspace"),
+        workspace_dir.display()
+    );
+    println!(
+        "  {}: {}",
+        t(lang, "工具链", "Toolchains"),
+        join(profile.ecosystems.iter().map(|e| e.label()).collect())
+    );
+    println!(
+        "  CI: {}",
+        join(
+            profile
+                .ci
+                .iter()
+                .map(|(name, path)| format!("{} ({})", name, path))
+                .collect()
+        )
+    );
+    println!(
+        "  {}: {}",
+        t(lang, "测试命令", "Test commands"),
+        join(profile.test_commands.clone())
+    );
+    println!(
+        "  {}: {}",
+        t(lang, "提交前检查", "Pre-commit checks"),
+        join(profile.checks.clone())
+    );
+    println!();
+    println!("{}", t(lang, "计划生成:", "Planned files:"));
+    for file in &plan {
+        let status = if file.exists {
+            t(lang, "已存在，跳过", "exists, skipped")
+        } else {
+            t(lang, "新建", "new")
+        };
+        println!("  {} [{}]", file.path, status);
+    }
+    if profile.rrclaw_ignored {
+        println!(
+            "{}",
+            t(
+                lang,
+                "注意: .gitignore 忽略了 .rrclaw/，生成的文件不会被提交",
+                "Note: .gitignore ignores .rrclaw/, so these files will not be committed"
+            )
+        );
+    }
+    if lang.is_english() {
+        println!(
+            "Note: {} is not loaded automatically; merge the commands you want into config.toml",
+            project_init::CONFIG_OVERLAY
+        );
+    } else {
+        println!(
+            "注意: {} 不会自动加载，请把需要的命令手动合并到 config.toml",
+            project_init::CONFIG_OVERLAY
+        );
+    }
+
+    if plan.iter().all(|f| f.exists) {
+        println!(
+            "{}",
+            t(
+                lang,
+                "所有文件都已存在，无需生成",
+                "All files already exist, nothing to do"
+            )
+        );
+        return Ok(());
+    }
+    let proceed = Confirm::new()
+        .with_prompt(t(lang, "生成以上文件", "Create these files"))
+        .default(true)
+        .interact()
+        .wrap_err("确认输入失败")?;
+    if !proceed {
+        println!("{}", t(lang, "已取消", "Cancelled"));
+        return Ok(());
+    }
+    for path in project_init::write_plan(workspace_dir, &plan)? {
+        println!("  ✓ {}", path.display());
+    }
+    Ok(())
+}
+
+/// /ps — 列出工具启动、仍在运行的子进程（回答进行中也可用，见 `input_queue`）
+pub(crate) fn cmd_ps() {
+    let lang = crate::config::Config::get_language();
+    let processes = crate::tools::process::ProcessRegistry::global().list();
+    if processes.is_empty() {
+        println!(
+            "{}",
+            t(
+                lang,
+                "没有运行中的工具子进程。",
+                "No tool-spawned processes running."
+            )
+        );
+        return;
+    }
+    println!(
+        "{:>4}  {:>8}  {:>8}  {}",
+        "ID",
+        "PID",
+        t(lang, "耗时", "ELAPSED"),
+        t(lang, "命令", "COMMAND")
+    );
+    for p in processes {
+        let command: String = p.command.chars().take(60).collect();
+        let ellipsis = if p.command.chars().count() > 60 {
+            "…"
+        } else {
+            ""
+        };
+        println!(
+            "{:>4}  {:>8}  {:>7}s  {}{}",
+            p.id,
+            p.pid,
+            p.elapsed().as_secs(),
+            command,
+            ellipsis
+        );
+    }
+}
+
+/// /kill <id> — 终止 /ps 列出的子进程
+pub(crate) fn cmd_kill(rest: &str) {
+    let lang = crate::config::Config::get_language();
+    let Ok(id) = rest.parse::<u64>() else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "用法: /kill <id>（ID 见 /ps）",
+                "Usage: /kill <id> (see /ps for IDs)"
+            )
+        );
+        return;
+    };
+    match crate::tools::process::ProcessRegistry::global().kill(id) {
+        Some(p) => println!(
+            "{} #{} (pid {}): {}",
+            t(lang, "已终止", "Killed"),
+            p.id,
+            p.pid,
+            p.command
+        ),
+        None if lang.is_english() => println!("No process with id {}.", id),
+        None => println!("没有编号为 {} 的子进程。", id),
     }
 }
 
@@ -1435,6 +2775,10 @@ fn cmd_switch(agent: &mut Agent, config: &Config) -> Result<()> {
             pc.base_url.clone(),
             model.clone(),
         );
+        agent.set_tool_limits(crate::providers::ToolLimits::for_provider(pc));
+        agent.set_context_window(crate::providers::resolve_context_window(
+            info.name, pc, &model,
+        ));
     } else {
         // 未配置 → 引导输入
         let api_key: String = Password::new()
@@ -1453,11 +2797,21 @@ fn cmd_switch(agent: &mut Agent, config: &Config) -> Result<()> {
             api_key,
             model: model.clone(),
             auth_style: info.auth_style.map(|s| s.to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
         };
         save_provider_to_config(info.name, &pc, None)?;
 
         let new_provider = crate::providers::create_provider(&pc);
         agent.switch_provider(new_provider, info.name.to_string(), base_url, model.clone());
+        agent.set_tool_limits(crate::providers::ToolLimits::for_provider(&pc));
+        agent.set_context_window(crate::providers::resolve_context_window(
+            info.name, &pc, &model,
+        ));
     }
 
     // 持久化: 更新 config.toml 的 [default] 段
@@ -1575,6 +2929,12 @@ fn cmd_apikey(agent: &mut Agent, config: &Config) -> Result<()> {
                 pc.base_url.clone(),
                 pc.model.clone(),
             );
+            agent.set_tool_limits(crate::providers::ToolLimits::for_provider(pc));
+            agent.set_context_window(crate::providers::resolve_context_window(
+                provider_name,
+                pc,
+                &pc.model,
+            ));
             println!(
                 "{}",
                 t(lang, "当前 session 已更新。", "Current session updated.")
@@ -1585,6 +2945,194 @@ fn cmd_apikey(agent: &mut Agent, config: &Config) -> Result<()> {
     Ok(())
 }
 
+/// 生成参数展示行（/config、/param 共用）：未设置显示"默认"，与配置不同的标注为会话覆盖
+fn generation_param_rows(agent: &Agent, lang: Language) -> Vec<(&'static str, String)> {
+    let current = agent.generation_params();
+    let default = agent.default_generation_params();
+    let show = |value: Option<String>, overridden: bool| {
+        format!(
+            "{}{}",
+            value.unwrap_or_else(|| t(lang, "默认", "default").to_string()),
+            if overridden {
+                t(lang, "（本会话覆盖）", " (session override)")
+            } else {
+                ""
+            }
+        )
+    };
+    vec![
+        (
+            "temperature",
+            show(
+                Some(current.temperature.to_string()),
+                current.temperature != default.temperature,
+            ),
+        ),
+        (
+            "top_p",
+            show(
+                current.top_p.map(|v| v.to_string()),
+                current.top_p != default.top_p,
+            ),
+        ),
+        (
+            "max_tokens",
+            show(
+                current.max_tokens.map(|v| v.to_string()),
+                current.max_tokens != default.max_tokens,
+            ),
+        ),
+    ]
+}
+
+/// /temp [value] — 查看 / 修改本会话的 temperature（0.0–2.0，不写入配置）
+fn cmd_temp(rest: &str, agent: &mut Agent) {
+    let lang = crate::config::Config::get_language();
+    if rest.is_empty() {
+        println!("temperature: {}", agent.temperature());
+        println!(
+            "{}",
+            t(lang, "用法: /temp <0.0–2.0>", "Usage: /temp <0.0–2.0>")
+        );
+        return;
+    }
+    match agent.set_generation_param("temperature", rest) {
+        Ok(()) if lang.is_english() => println!(
+            "temperature set to {} for this session (/param reset restores the config value)",
+            agent.temperature()
+        ),
+        Ok(()) => println!(
+            "本会话 temperature 已设为 {}（/param reset 恢复配置值）",
+            agent.temperature()
+        ),
+        Err(e) => println!("{}{}{}", ansi::RED, e, ansi::RESET),
+    }
+}
+
+/// /param [set <name> <value> | reset] — 查看 / 覆盖本会话的生成参数（temperature / top_p / max_tokens）
+fn cmd_param(rest: &str, agent: &mut Agent) {
+    let lang = crate::config::Config::get_language();
+    let mut parts = rest.split_whitespace();
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (None, ..) | (Some("show"), None, ..) => {
+            println!("{}", t(lang, "当前生成参数:", "Generation parameters:"));
+            for (name, value) in generation_param_rows(agent, lang) {
+                println!("  {:<12} {}", name, value);
+            }
+        }
+        (Some("set"), Some(name), Some(value), None) => {
+            match agent.set_generation_param(name, value) {
+                Ok(()) if lang.is_english() => {
+                    println!("{} set to {} for this session", name, value)
+                }
+                Ok(()) => println!("本会话 {} 已设为 {}", name, value),
+                Err(e) => println!("{}{}{}", ansi::RED, e, ansi::RESET),
+            }
+        }
+        (Some("reset"), None, ..) => {
+            agent.reset_generation_params();
+            println!(
+                "{}",
+                t(
+                    lang,
+                    "已恢复配置中的生成参数。",
+                    "Generation parameters restored from config."
+                )
+            );
+        }
+        _ => println!(
+            "{}",
+            t(
+                lang,
+                "用法: /param [show] | /param set <temperature|top_p|max_tokens> <值> | /param reset",
+                "Usage: /param [show] | /param set <temperature|top_p|max_tokens> <value> | /param reset"
+            )
+        ),
+    }
+}
+
+/// /maxtokens [<n>|off] — 查看 / 修改当前 Provider 的输出 token 上限（写入 config.toml，立即生效）
+fn cmd_maxtokens(rest: &str, agent: &mut Agent) -> Result<()> {
+    let lang = crate::config::Config::get_language();
+    let provider_name = agent.provider_name().to_string();
+    let config_path = Config::config_path()?;
+    let config = Config::load_from_path(&config_path)?;
+    let Some(mut pc) = config.providers.get(&provider_name).cloned() else {
+        if lang.is_english() {
+            println!("Provider '{}' is not in config.toml", provider_name);
+        } else {
+            println!("Provider '{}' 不在 config.toml 中", provider_name);
+        }
+        return Ok(());
+    };
+
+    let new_value = match rest {
+        "" => {
+            match (pc.max_tokens, lang.is_english()) {
+                (Some(n), true) => println!("{} max_tokens: {}", provider_name, n),
+                (Some(n), false) => println!("{} 输出上限: {} tokens", provider_name, n),
+                (None, true) => println!("{} max_tokens: not set", provider_name),
+                (None, false) => println!("{} 输出上限: 未设置", provider_name),
+            }
+            println!(
+                "{}",
+                t(
+                    lang,
+                    "用法: /maxtokens <n>|off",
+                    "Usage: /maxtokens <n>|off"
+                )
+            );
+            return Ok(());
+        }
+        "off" => None,
+        n => match n.parse::<u32>() {
+            Ok(n) if n > 0 => Some(n),
+            _ => {
+                println!(
+                    "{}",
+                    t(
+                        lang,
+                        "用法: /maxtokens <正整数>|off",
+                        "Usage: /maxtokens <positive integer>|off"
+                    )
+                );
+                return Ok(());
+            }
+        },
+    };
+
+    let content = std::fs::read_to_string(&config_path)?;
+    let mut doc = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| color_eyre::eyre::eyre!("解析配置文件失败: {}", e))?;
+    match new_value {
+        Some(n) => doc["providers"][&provider_name]["max_tokens"] = toml_edit::value(n as i64),
+        None => {
+            if let Some(table) = doc["providers"][&provider_name].as_table_like_mut() {
+                table.remove("max_tokens");
+            }
+        }
+    }
+    std::fs::write(&config_path, doc.to_string())?;
+
+    // 重建 Provider 实例使之立即生效（保留当前模型）
+    pc.max_tokens = new_value;
+    let model = agent.model().to_string();
+    agent.switch_provider(
+        crate::providers::create_provider(&pc),
+        provider_name,
+        pc.base_url.clone(),
+        model,
+    );
+    match (new_value, lang.is_english()) {
+        (Some(n), true) => println!("max_tokens set to {}", n),
+        (Some(n), false) => println!("输出上限已设为 {} tokens", n),
+        (None, true) => println!("max_tokens cleared"),
+        (None, false) => println!("输出上限已取消"),
+    }
+    Ok(())
+}
+
 /// 更新 config.toml 的 [default] 段（provider + model）
 /// 如果提供了 path 则使用它，否则使用 Config::config_path()
 fn save_default_to_config(
@@ -1653,13 +3201,56 @@ fn save_provider_to_config(
     Ok(())
 }
 
-/// /mode — 切换 Agent 自主级别（ReadOnly / Supervised / Full）
-fn cmd_mode(agent: &mut Agent) -> Result<()> {
+/// /readonly [on|off] — 会话级只读回合开关（不改自主级别，不持久化）
+fn cmd_readonly(arg: &str, agent: &mut Agent) {
+    let lang = crate::config::Config::get_language();
+    let enabled = match arg {
+        "" => !agent.is_read_only(),
+        "on" => true,
+        "off" => false,
+        _ => {
+            println!(
+                "{}",
+                t(
+                    lang,
+                    "用法: /readonly [on|off]",
+                    "Usage: /readonly [on|off]"
+                )
+            );
+            return;
+        }
+    };
+    agent.set_read_only(enabled);
+    let status = match (enabled, lang.is_english()) {
+        (true, true) => "Read-only turns on: only read tools are exposed until /readonly off",
+        (true, false) => "只读回合已开启：在 /readonly off 之前只提供读取类工具",
+        (false, true) => "Read-only turns off",
+        (false, false) => "只读回合已关闭",
+    };
+    println!("{}", status);
+}
+
+/// /mode — 切换 Agent 自主级别（ReadOnly / Supervised / Full），之后可编辑命令白名单
+/// `/mode allow +make -cargo` 直接编辑白名单，不进入模式选择
+fn cmd_mode(agent: &mut Agent, config: &Config, args: &str) -> Result<()> {
     use crate::security::AutonomyLevel;
     use dialoguer::Select;
     let lang = crate::config::Config::get_language();
 
-    let current = &agent.policy().autonomy;
+    if let Some(edits) = args
+        .strip_prefix("allow")
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    {
+        if edits.trim().is_empty() {
+            print_allowlist(&agent.policy().allowed_commands, lang);
+            return Ok(());
+        }
+        let commands = apply_allowlist_edit(agent, edits, None)?;
+        print_allowlist(&commands, lang);
+        return Ok(());
+    }
+
+    let current = agent.policy().autonomy.clone();
     let modes = if lang.is_english() {
         [
             (
@@ -1705,57 +3296,164 @@ fn cmd_mode(agent: &mut Agent) -> Result<()> {
         _ => AutonomyLevel::Supervised,
     };
 
-    if new_level == *current {
-        println!("{}", t(lang, "无变化。", "No changes."));
+    if new_level == current {
+        println!("{}", t(lang, "模式无变化。", "Mode unchanged."));
+    } else {
+        if new_level == AutonomyLevel::Full
+            && crate::security::sandbox::refuses_full(config.security.require_sandbox)
+        {
+            print_full_refused(lang);
+            return Ok(());
+        }
+
+        // 运行时切换
+        agent.set_autonomy(new_level.clone());
+
+        // 持久化到 config.toml
+        let config_path = Config::config_path()?;
+        let content = std::fs::read_to_string(&config_path)?;
+        let mut doc = content
+            .parse::<toml_edit::DocumentMut>()
+            .wrap_err("解析配置文件失败")?;
+        doc["security"]["autonomy"] = toml_edit::value(key);
+        std::fs::write(&config_path, doc.to_string())?;
+
+        if lang.is_english() {
+            println!("Switched to {} mode.", key);
+        } else {
+            println!("已切换到 {} 模式。", key);
+        }
+    }
+
+    // ReadOnly 不执行任何命令，白名单无意义
+    if new_level == AutonomyLevel::ReadOnly {
+        return Ok(());
+    }
+    print_allowlist(&agent.policy().allowed_commands, lang);
+    let edits: String = Input::new()
+        .with_prompt(t(
+            lang,
+            "编辑命令白名单（+cmd 添加，-cmd 删除，回车跳过）",
+            "Edit command allowlist (+cmd to add, -cmd to remove, Enter to skip)",
+        ))
+        .allow_empty(true)
+        .interact_text()
+        .wrap_err(t(lang, "输入失败", "Input failed"))?;
+    if edits.trim().is_empty() {
         return Ok(());
     }
+    let commands = apply_allowlist_edit(agent, &edits, None)?;
+    print_allowlist(&commands, lang);
+    Ok(())
+}
 
-    // 运行时切换
-    agent.set_autonomy(new_level);
+fn print_allowlist(commands: &[String], lang: Language) {
+    let list = if commands.is_empty() {
+        t(lang, "（空）", "(empty)").to_string()
+    } else {
+        commands.join(", ")
+    };
+    println!(
+        "{}{} {}{}",
+        ansi::DIM,
+        t(lang, "命令白名单:", "Allowed commands:"),
+        list,
+        ansi::RESET
+    );
+}
 
-    // 持久化到 config.toml
-    let config_path = Config::config_path()?;
-    let content = std::fs::read_to_string(&config_path)?;
-    let mut doc = content
-        .parse::<toml_edit::DocumentMut>()
-        .wrap_err("解析配置文件失败")?;
-    doc["security"]["autonomy"] = toml_edit::value(key);
-    std::fs::write(&config_path, doc.to_string())?;
+/// 解析白名单编辑：空格分隔，`+cmd` 添加、`-cmd` 删除，不带前缀视为添加
+fn edit_allowlist(current: &[String], edits: &str) -> Result<Vec<String>> {
+    let mut commands = current.to_vec();
+    for token in edits.split_whitespace() {
+        let (remove, command) = match token.strip_prefix('-') {
+            Some(command) => (true, command),
+            None => (false, token.strip_prefix('+').unwrap_or(token)),
+        };
+        if command.is_empty() || command.contains(['"', '\'', ',', '[', ']']) {
+            return Err(eyre!("无效的命令名: {}", token));
+        }
+        if remove {
+            commands.retain(|c| c != command);
+        } else if !commands.iter().any(|c| c == command) {
+            commands.push(command.to_string());
+        }
+    }
+    Ok(commands)
+}
 
-    if lang.is_english() {
-        println!("Switched to {} mode.", key);
-    } else {
-        println!("已切换到 {} 模式。", key);
+/// 编辑白名单：先经 ConfigTool 写入 `[security] allowed_commands`，成功后更新运行中的策略
+/// 如果提供了 path 则使用它，否则使用 Config::config_path()
+fn apply_allowlist_edit(
+    agent: &mut Agent,
+    edits: &str,
+    path: Option<&std::path::Path>,
+) -> Result<Vec<String>> {
+    let commands = edit_allowlist(&agent.policy().allowed_commands, edits)?;
+    if commands == agent.policy().allowed_commands {
+        return Ok(commands);
     }
-    Ok(())
+    let value = format!(
+        "[{}]",
+        commands
+            .iter()
+            .map(|c| format!("\"{}\"", c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if !crate::tools::config::set_config_value("security.allowed_commands", &value, path)? {
+        return Err(eyre!("无法写入 security.allowed_commands"));
+    }
+    agent.set_allowed_commands(commands.clone());
+    Ok(commands)
+}
+
+/// `security.require_sandbox` 开启但不在沙箱中：拒绝切换到 Full
+fn print_full_refused(lang: Language) {
+    println!(
+        "{}{}{}",
+        ansi::YELLOW,
+        t(
+            lang,
+            "已开启 security.require_sandbox，但当前未在沙箱中运行，拒绝 Full 模式。运行 `rrclaw sandbox-profile` 生成沙箱配置。",
+            "security.require_sandbox is on but rrclaw is not running inside a sandbox; Full mode refused. Run `rrclaw sandbox-profile` to generate one."
+        ),
+        ansi::RESET
+    );
 }
 
 // ─── /routine 命令实现 ────────────────────────────────────────────────────
 
 /// /routine 命令入口 —— 解析子命令后分发
-async fn cmd_routine(rest: &str, engine: Option<Arc<RoutineEngine>>) {
+async fn cmd_routine(rest: &str, engine: Option<Arc<RoutineEngine>>, verbosity: ToolVerbosity) {
     let mut parts = rest.splitn(2, ' ');
     let sub = parts.next().unwrap_or("").trim();
     let arg = parts.next().map(|s| s.trim());
 
     match sub {
-        "" | "list" => cmd_routine_list(&engine),
+        "" | "list" => cmd_routine_list(&engine).await,
         "add" => cmd_routine_add(&engine, arg).await,
         "delete" | "rm" => cmd_routine_delete(&engine, arg).await,
         "enable" => cmd_routine_enable(&engine, arg, true).await,
         "disable" => cmd_routine_enable(&engine, arg, false).await,
         "run" => cmd_routine_run(&engine, arg).await,
+        "cancel" => cmd_routine_cancel(&engine, arg),
+        "simulate" => cmd_routine_simulate(&engine, arg, verbosity).await,
         "logs" => cmd_routine_logs(&engine, arg).await,
+        "export" => cmd_routine_export(&engine, arg),
+        "import" => cmd_routine_import(&engine, arg).await,
+        "pause" => cmd_routine_pause(&engine, true),
+        "resume" => cmd_routine_pause(&engine, false),
         _ => {
             let lang = crate::config::Config::get_language();
-            println!("{}", t(lang, "未知的 /routine 子命令。可用：list / add / delete / enable / disable / run / logs",
-                "Unknown /routine subcommand. Available: list / add / delete / enable / disable / run / logs"));
+            println!("{}", t(lang, "未知的 /routine 子命令。可用：list / add / delete / enable / disable / run / cancel / simulate / logs / export / import / pause / resume",
+                "Unknown /routine subcommand. Available: list / add / delete / enable / disable / run / cancel / simulate / logs / export / import / pause / resume"));
         }
     }
 }
 
 /// /routine list — 列出所有 Routine
-fn cmd_routine_list(engine: &Option<Arc<RoutineEngine>>) {
+async fn cmd_routine_list(engine: &Option<Arc<RoutineEngine>>) {
     let lang = crate::config::Config::get_language();
     match engine {
         None => println!(
@@ -1767,6 +3465,31 @@ fn cmd_routine_list(engine: &Option<Arc<RoutineEngine>>) {
             )
         ),
         Some(e) => {
+            if e.is_paused() {
+                println!(
+                    "{}\n",
+                    t(
+                        lang,
+                        "⏸ 调度已暂停：定时任务不会自动触发（/routine resume 恢复，/routine run 仍可手动执行）",
+                        "⏸ Scheduling paused: routines will not fire (/routine resume to resume; /routine run still works)"
+                    )
+                );
+            }
+            if let crate::routines::SchedulerRole::Standby { pid } = e.role() {
+                let holder = pid.map_or_else(|| "?".to_string(), |p| p.to_string());
+                if lang.is_english() {
+                    println!(
+                        "ℹ Routines are scheduled by another rrclaw process (pid {}); this session is read-only (list / logs)\n",
+                        holder
+                    );
+                } else {
+                    println!(
+                        "ℹ 定时任务由 pid {} 的 rrclaw 进程调度，本会话只读（list / logs 可用）\n",
+                        holder
+                    );
+                }
+                e.reload_if_standby().await;
+            }
             let routines = e.list_routines();
             if routines.is_empty() {
                 println!(
@@ -1791,23 +3514,29 @@ fn cmd_routine_list(engine: &Option<Arc<RoutineEngine>>) {
                 );
             }
             println!("{}", "-".repeat(80));
-            for r in routines {
-                let status = if r.enabled {
-                    t(lang, "✓ 启用", "✓ on")
-                } else {
-                    t(lang, "✗ 禁用", "✗ off")
+            for r in &routines {
+                let status = match e.running_elapsed(&r.name) {
+                    Some(elapsed) => format!("⏳ {}", crate::routines::format_elapsed(elapsed)),
+                    None if r.enabled => t(lang, "✓ 启用", "✓ on").to_string(),
+                    None => t(lang, "✗ 禁用", "✗ off").to_string(),
                 };
-                let preview: String = r.message.chars().take(40).collect();
+                let preview: String = r.summary().chars().take(40).collect();
                 println!(
                     "{:<20} {:<15} {:<8} {:<10} {}",
                     r.name, r.schedule, status, r.channel, preview
                 );
             }
+            println!(
+                "\n{} {}",
+                t(lang, "时区:", "Timezone:"),
+                e.timezone().name()
+            );
         }
     }
 }
 
-/// /routine add <name> "<时间描述>" "<消息>" [channel]
+/// /routine add <name> "<时间描述>" "<消息>" [channel] [--catch-up] [--chain a,b]
+///              [--if-shell "<命令>" | --if-http <URL>] [--target <URL>]
 /// 支持自然语言时间描述，如 "每天早上8点"
 async fn cmd_routine_add(engine: &Option<Arc<RoutineEngine>>, args: Option<&str>) {
     let lang = crate::config::Config::get_language();
@@ -1824,9 +3553,44 @@ async fn cmd_routine_add(engine: &Option<Arc<RoutineEngine>>, args: Option<&str>
             return;
         }
     };
-    if parts.len() < 3 {
+    // --catch-up：错过的触发在下次启动时补跑
+    let catch_up = parts.iter().any(|p| p == "--catch-up");
+    let mut parts: Vec<String> = parts.into_iter().filter(|p| p != "--catch-up").collect();
+    // --chain a,b：依次执行已有 Routine（此时可省略消息）
+    let mut chain = Vec::new();
+    if let Some(i) = parts.iter().position(|p| p == "--chain") {
+        if let Some(list) = parts.get(i + 1) {
+            chain = crate::routines::split_chain(list);
+            parts.drain(i..=i + 1);
+        }
+    }
+    // --if-shell "<命令>" / --if-http <URL>：执行前置条件（不满足时跳过本次执行）
+    let mut condition = None;
+    for flag in ["--if-shell", "--if-http"] {
+        if let Some(i) = parts.iter().position(|p| p == flag) {
+            if let Some(value) = parts.get(i + 1).cloned() {
+                condition = Some(if flag == "--if-shell" {
+                    crate::routines::RoutineCondition::Shell(value)
+                } else {
+                    crate::routines::RoutineCondition::Http(value)
+                });
+                parts.drain(i..=i + 1);
+            }
+        }
+    }
+    // --target <URL>：通道目标（channel = webhook 时的接收地址）
+    let mut channel_target = None;
+    if let Some(i) = parts.iter().position(|p| p == "--target") {
+        if let Some(url) = parts.get(i + 1).cloned() {
+            channel_target = Some(url);
+            parts.drain(i..=i + 1);
+        }
+    }
+    let required = if chain.is_empty() { 3 } else { 2 };
+    if parts.len() < required {
         if lang.is_english() {
-            println!("Usage: /routine add <name> <schedule> <message> [channel]");
+            println!("Usage: /routine add <name> <schedule> <message> [channel] [--catch-up]");
+            println!("       /routine add <name> <schedule> --chain <routine1,routine2,...>");
             println!("Example: /routine add daily_brief \"every day at 8am\" \"Generate daily report\" cli");
             println!();
             println!("Supported natural language schedules:");
@@ -1834,8 +3598,14 @@ async fn cmd_routine_add(engine: &Option<Arc<RoutineEngine>>, args: Option<&str>
             println!("  - every hour / every 2 hours");
             println!("  - every Monday at 9am / every Friday at 5pm");
             println!("  - every 15th at 10am");
+            println!();
+            println!("--catch-up: if the run is missed (machine asleep / RRClaw not running), run it once on next startup");
+            println!("--chain: run existing routines in order and send one combined result");
+            println!("--if-shell \"<cmd>\" / --if-http <url>: run only if the command exits 0 / the URL returns 200");
+            println!("--target <url>: with channel webhook, POST the result as JSON to this URL");
         } else {
-            println!("用法: /routine add <名称> <执行时间> <消息> [channel]");
+            println!("用法: /routine add <名称> <执行时间> <消息> [channel] [--catch-up]");
+            println!("      /routine add <名称> <执行时间> --chain <routine1,routine2,...>");
             println!("示例: /routine add daily_brief \"每天早上8点\" \"生成今日日报\" cli");
             println!();
             println!("支持的自然语言：");
@@ -1843,14 +3613,24 @@ async fn cmd_routine_add(engine: &Option<Arc<RoutineEngine>>, args: Option<&str>
             println!("  - 每小时 / 每2小时");
             println!("  - 每周一早上9点 / 每周五下午5点");
             println!("  - 每月15号上午10点");
+            println!();
+            println!("--catch-up：错过触发（机器休眠 / RRClaw 未运行）时，下次启动补跑一次");
+            println!("--chain：依次执行已有 Routine，汇总结果后一次发送");
+            println!("--if-shell \"<命令>\" / --if-http <URL>：命令退出码为 0 / URL 返回 200 时才执行，否则记录跳过");
+            println!("--target <URL>：channel 为 webhook 时，把结果以 JSON POST 到该地址");
         }
         return;
     }
 
     let name = parts[0].clone();
     let schedule_desc = parts[1].clone();
-    let message = parts[2].clone();
-    let channel = parts.get(3).cloned().unwrap_or_else(|| "cli".to_string());
+    // 链式 Routine 不需要消息，其余位置参数顺延
+    let (message, channel) = if chain.is_empty() {
+        (parts[2].clone(), parts.get(3).cloned())
+    } else {
+        (String::new(), parts.get(2).cloned())
+    };
+    let channel = channel.unwrap_or_else(|| "cli".to_string());
 
     // 解析时间描述为 cron（支持自然语言）
     let schedule = match crate::routines::parse_schedule_to_cron(&schedule_desc) {
@@ -1879,6 +3659,10 @@ async fn cmd_routine_add(engine: &Option<Arc<RoutineEngine>>, args: Option<&str>
         channel,
         enabled: true,
         source: RoutineSource::Dynamic,
+        catch_up,
+        chain,
+        condition,
+        channel_target,
     };
     match engine {
         None => println!(
@@ -1902,6 +3686,15 @@ async fn cmd_routine_add(engine: &Option<Arc<RoutineEngine>>, args: Option<&str>
                         name
                     );
                 }
+                let tz = e.timezone();
+                if let Ok(next) = tz.next_fire(&routine.schedule, chrono::Utc::now()) {
+                    println!(
+                        "  {} {} ({})",
+                        t(lang, "下次执行:", "Next run:"),
+                        tz.format_short(next),
+                        tz.name()
+                    );
+                }
             }
             Err(err) => println!("✗ {}: {}", t(lang, "保存失败", "Save failed"), err),
         },
@@ -1945,6 +3738,113 @@ async fn cmd_routine_delete(engine: &Option<Arc<RoutineEngine>>, name: Option<&s
     }
 }
 
+/// /routine export [file] — 导出动态 Routine（JSON），不带文件名时打印到终端
+fn cmd_routine_export(engine: &Option<Arc<RoutineEngine>>, path: Option<&str>) {
+    let lang = crate::config::Config::get_language();
+    let Some(e) = engine else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "Routine 系统未初始化",
+                "Routine system not initialized"
+            )
+        );
+        return;
+    };
+    let json = e.export_routines();
+    match path.filter(|p| !p.is_empty()) {
+        None => println!("{}", json),
+        Some(path) => match std::fs::write(path, &json) {
+            Ok(()) => {
+                if lang.is_english() {
+                    println!("✓ Dynamic routines exported to {}", path);
+                } else {
+                    println!("✓ 动态 Routine 已导出到 {}", path);
+                }
+            }
+            Err(err) => println!("✗ {}: {}", t(lang, "导出失败", "Export failed"), err),
+        },
+    }
+}
+
+/// /routine import <file> [--overwrite]
+async fn cmd_routine_import(engine: &Option<Arc<RoutineEngine>>, args: Option<&str>) {
+    let lang = crate::config::Config::get_language();
+    let parts = shell_words::split(args.unwrap_or("")).unwrap_or_default();
+    let overwrite = parts.iter().any(|p| p == "--overwrite");
+    let Some(path) = parts.iter().find(|p| !p.starts_with("--")) else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "用法: /routine import <文件> [--overwrite]",
+                "Usage: /routine import <file> [--overwrite]"
+            )
+        );
+        return;
+    };
+    let Some(e) = engine else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "Routine 系统未初始化",
+                "Routine system not initialized"
+            )
+        );
+        return;
+    };
+    let json = match std::fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(err) => {
+            println!(
+                "✗ {}: {}",
+                t(lang, "读取文件失败", "Failed to read file"),
+                err
+            );
+            return;
+        }
+    };
+    match e.clone().import_routines(&json, overwrite).await {
+        Ok(summary) => {
+            if lang.is_english() {
+                println!(
+                    "✓ Imported {}, replaced {}, skipped {}, failed {}",
+                    summary.imported.len(),
+                    summary.replaced.len(),
+                    summary.skipped.len(),
+                    summary.failed.len()
+                );
+                if !summary.skipped.is_empty() {
+                    println!(
+                        "  skipped (already exist; use --overwrite for dynamic ones): {}",
+                        summary.skipped.join(", ")
+                    );
+                }
+            } else {
+                println!(
+                    "✓ 新增 {}，覆盖 {}，跳过 {}，失败 {}",
+                    summary.imported.len(),
+                    summary.replaced.len(),
+                    summary.skipped.len(),
+                    summary.failed.len()
+                );
+                if !summary.skipped.is_empty() {
+                    println!(
+                        "  已跳过（同名已存在，动态 Routine 可加 --overwrite 覆盖）: {}",
+                        summary.skipped.join(", ")
+                    );
+                }
+            }
+            for (name, err) in &summary.failed {
+                println!("  ✗ {}: {}", name, err);
+            }
+        }
+        Err(err) => println!("✗ {}: {}", t(lang, "导入失败", "Import failed"), err),
+    }
+}
+
 /// /routine enable|disable <name>
 async fn cmd_routine_enable(
     engine: &Option<Arc<RoutineEngine>>,
@@ -1983,6 +3883,55 @@ async fn cmd_routine_enable(
     }
 }
 
+/// /routine pause|resume — 暂停 / 恢复全部定时触发（不删除、不禁用任何 Routine）
+fn cmd_routine_pause(engine: &Option<Arc<RoutineEngine>>, paused: bool) {
+    let lang = crate::config::Config::get_language();
+    let Some(e) = engine else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "Routine 系统未初始化",
+                "Routine system not initialized"
+            )
+        );
+        return;
+    };
+    if e.is_paused() == paused {
+        let msg = if paused {
+            t(
+                lang,
+                "调度已处于暂停状态。",
+                "Scheduling is already paused.",
+            )
+        } else {
+            t(lang, "调度未暂停。", "Scheduling is not paused.")
+        };
+        println!("{}", msg);
+        return;
+    }
+    e.set_paused(paused);
+    if paused {
+        println!(
+            "{}",
+            t(
+                lang,
+                "⏸ 已暂停全部定时任务（仅本次运行有效；持久暂停请在配置中设置 [routines] paused = true）",
+                "⏸ All routines paused (this run only; set [routines] paused = true in config to persist)"
+            )
+        );
+    } else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "▶ 已恢复定时任务调度。",
+                "▶ Routine scheduling resumed."
+            )
+        );
+    }
+}
+
 /// /routine run <name> — 手动触发 Routine 执行
 async fn cmd_routine_run(engine: &Option<Arc<RoutineEngine>>, name: Option<&str>) {
     let lang = crate::config::Config::get_language();
@@ -2009,24 +3958,158 @@ async fn cmd_routine_run(engine: &Option<Arc<RoutineEngine>>, name: Option<&str>
         ),
         Some(e) => {
             if lang.is_english() {
-                println!("Triggering routine manually: {} ...", name);
+                println!("Triggering routine manually: {} ...", name);
+            } else {
+                println!("正在手动触发 Routine: {} ...", name);
+            }
+            match e.execute_routine(name).await {
+                Ok(output) => {
+                    println!("\n[Routine: {}]\n{}", name, output);
+                }
+                Err(err) => {
+                    if lang.is_english() {
+                        println!("Routine '{}' failed: {}", name, err);
+                    } else {
+                        println!("Routine '{}' 执行失败: {}", name, err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// /routine cancel <name> — 取消正在执行的 Routine
+fn cmd_routine_cancel(engine: &Option<Arc<RoutineEngine>>, name: Option<&str>) {
+    let lang = crate::config::Config::get_language();
+    let name = name.unwrap_or("");
+    if name.is_empty() {
+        println!(
+            "{}",
+            t(
+                lang,
+                "用法: /routine cancel <name>",
+                "Usage: /routine cancel <name>"
+            )
+        );
+        return;
+    }
+    match engine {
+        None => println!(
+            "{}",
+            t(
+                lang,
+                "Routine 系统未初始化",
+                "Routine system not initialized"
+            )
+        ),
+        Some(e) if e.cancel_routine(name) => {
+            if lang.is_english() {
+                println!(
+                    "⏹ Cancelling routine '{}' (a running tool finishes first; no more retries)",
+                    name
+                );
+            } else {
+                println!(
+                    "⏹ 正在取消 Routine '{}'（正在运行的工具结束后停止，不再重试）",
+                    name
+                );
+            }
+        }
+        Some(_) => {
+            if lang.is_english() {
+                println!("Routine '{}' is not running", name);
+            } else {
+                println!("Routine '{}' 当前没有在执行", name);
+            }
+        }
+    }
+}
+
+/// /routine simulate <name> — 以 Supervised 模式试跑，不写日志、不发送结果
+async fn cmd_routine_simulate(
+    engine: &Option<Arc<RoutineEngine>>,
+    name: Option<&str>,
+    verbosity: ToolVerbosity,
+) {
+    let lang = crate::config::Config::get_language();
+    let name = name.unwrap_or("");
+    if name.is_empty() {
+        println!(
+            "{}",
+            t(
+                lang,
+                "用法: /routine simulate <name>",
+                "Usage: /routine simulate <name>"
+            )
+        );
+        return;
+    }
+    let Some(e) = engine else {
+        println!(
+            "{}",
+            t(
+                lang,
+                "Routine 系统未初始化",
+                "Routine system not initialized"
+            )
+        );
+        return;
+    };
+    if lang.is_english() {
+        println!(
+            "Simulating routine: {} (supervised, no log, no delivery) ...",
+            name
+        );
+    } else {
+        println!(
+            "正在模拟 Routine: {}（需确认工具调用，不记录日志、不发送结果）...",
+            name
+        );
+    }
+
+    let (tx, rx) = stream_channel();
+    let print_handle = spawn_stream_printer(rx, verbosity);
+    let result = e.simulate(name, setup_cli_confirm, tx).await;
+    let has_output = print_handle.await.unwrap_or(false);
+    if has_output {
+        println!("\n");
+    }
+
+    let report = match result {
+        Ok(report) => report,
+        Err(err) => {
+            if lang.is_english() {
+                println!("Simulation of '{}' failed: {}", name, err);
             } else {
-                println!("正在手动触发 Routine: {} ...", name);
-            }
-            match e.execute_routine(name).await {
-                Ok(output) => {
-                    println!("\n[Routine: {}]\n{}", name, output);
-                }
-                Err(err) => {
-                    if lang.is_english() {
-                        println!("Routine '{}' failed: {}", name, err);
-                    } else {
-                        println!("Routine '{}' 执行失败: {}", name, err);
-                    }
-                }
+                println!("Routine '{}' 模拟失败: {}", name, err);
             }
+            return;
+        }
+    };
+    if let Some(condition) = report.condition.filter(|c| !c.met) {
+        if lang.is_english() {
+            println!(
+                "{}Note: condition not met ({}); a scheduled run would be skipped.{}",
+                ansi::YELLOW,
+                condition.detail,
+                ansi::RESET
+            );
+        } else {
+            println!(
+                "{}注意: 条件不满足（{}），定时触发时本次会被跳过。{}",
+                ansi::YELLOW,
+                condition.detail,
+                ansi::RESET
+            );
         }
     }
+    let approach = match (report.approach_updated, lang.is_english()) {
+        (true, true) => "Approach memory updated: a scheduled run will reuse it.",
+        (true, false) => "已更新方法记忆：定时执行时将参考本次方法。",
+        (false, true) => "Approach memory not updated.",
+        (false, false) => "未更新方法记忆。",
+    };
+    println!("{}{}{}", ansi::DIM, approach, ansi::RESET);
 }
 
 /// /routine logs [limit] — 查看执行日志
@@ -2057,6 +4140,8 @@ async fn cmd_routine_logs(engine: &Option<Arc<RoutineEngine>>, args: Option<&str
             for log in &logs {
                 let status = if log.success {
                     t(lang, "✓ 成功", "✓ ok")
+                } else if log.deferred {
+                    t(lang, "⏸ 延后", "⏸ deferred")
                 } else {
                     t(lang, "✗ 失败", "✗ fail")
                 };
@@ -2074,13 +4159,75 @@ async fn cmd_routine_logs(engine: &Option<Arc<RoutineEngine>>, args: Option<&str
 }
 
 /// /mcp — 列出当前已加载的 MCP 工具
+/// /cost — 估算下一次请求的输入 token 数与费用
+fn cmd_cost(agent: &Agent, config: &Config) {
+    let lang = crate::config::Config::get_language();
+    let tokens = agent.estimate_request_tokens();
+    let pricing = config.pricing_for(agent.model());
+    println!("{}", format_cost_estimate(tokens, pricing.as_ref()));
+    let window = agent.context_window();
+    let percent = tokens * 100 / window.max(1);
+    if lang.is_english() {
+        println!("Context window: {} tokens ({}% used)", window, percent);
+    } else {
+        println!("上下文窗口: {} tokens（已用 {}%）", window, percent);
+    }
+    let note = match pricing {
+        Some(_) => t(
+            lang,
+            "（仅输入部分：system prompt + 历史 + 工具定义，不含本条消息与模型回复）",
+            "(input only: system prompt + history + tool definitions; excludes your next message and the reply)",
+        )
+        .to_string(),
+        None => {
+            if lang.is_english() {
+                format!(
+                    "No price known for model '{}'; add [pricing.\"{}\"] input/output (USD per 1M tokens) to config.toml.",
+                    agent.model(),
+                    agent.model()
+                )
+            } else {
+                format!(
+                    "未知模型 '{}' 的价格，可在 config.toml 中添加 [pricing.\"{}\"] input/output（美元 / 百万 tokens）。",
+                    agent.model(),
+                    agent.model()
+                )
+            }
+        }
+    };
+    println!("{}{}{}", ansi::DIM, note, ansi::RESET);
+}
+
+/// 格式化费用估算："~3.4k tokens ≈ $0.0102"（不足 $0.10 保留 4 位小数；无价格时只显示 token 数）
+fn format_cost_estimate(tokens: usize, pricing: Option<&crate::config::ModelPricing>) -> String {
+    let count = if tokens >= 1_000_000 {
+        format!("{:.1}M", tokens as f64 / 1_000_000.0)
+    } else if tokens >= 1_000 {
+        format!("{:.1}k", tokens as f64 / 1_000.0)
+    } else {
+        tokens.to_string()
+    };
+    match pricing {
+        Some(pricing) => {
+            let cost = pricing.cost(tokens, 0);
+            let cost = if cost > 0.0 && cost < 0.1 {
+                format!("${:.4}", cost)
+            } else {
+                format!("${:.2}", cost)
+            };
+            format!("~{} tokens ≈ {}", count, cost)
+        }
+        None => format!("~{} tokens", count),
+    }
+}
+
 fn cmd_mcp(agent: &Agent) {
     let lang = crate::config::Config::get_language();
     let all_tools = agent.tool_names();
     let mcp_tools: Vec<&str> = all_tools
         .iter()
         .copied()
-        .filter(|n| n.starts_with("mcp_"))
+        .filter(|n| n.starts_with("mcp_") && *n != crate::mcp::catalog::LIST_TOOLS_NAME)
         .collect();
 
     if mcp_tools.is_empty() {
@@ -2088,14 +4235,20 @@ fn cmd_mcp(agent: &Agent) {
             "{}",
             t(lang, "当前没有已加载的 MCP 工具。", "No MCP tools loaded.")
         );
-        println!(
-            "{}",
-            t(
-                lang,
-                "在 ~/.rrclaw/config.toml 中配置 [mcp.servers.<name>] 后重启生效。",
-                "Configure [mcp.servers.<name>] in ~/.rrclaw/config.toml and restart."
-            )
-        );
+        let config_path = crate::config::Config::config_path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| "config.toml".to_string());
+        if lang.is_english() {
+            println!(
+                "Configure [mcp.servers.<name>] in {} and restart.",
+                config_path
+            );
+        } else {
+            println!(
+                "在 {} 中配置 [mcp.servers.<name>] 后重启生效。",
+                config_path
+            );
+        }
         return;
     }
 
@@ -2129,6 +4282,16 @@ fn cmd_mcp(agent: &Agent) {
             println!("    mcp_{}_{}", server, tool);
         }
     }
+    if all_tools.contains(&crate::mcp::catalog::LIST_TOOLS_NAME) {
+        println!(
+            "{}",
+            t(
+                lang,
+                "  （部分工具未列入 prompt，模型可通过 mcp_list_tools 浏览）",
+                "  (some tools are not in the prompt; the model can browse them via mcp_list_tools)"
+            )
+        );
+    }
 }
 
 /// /telegram — 控制 Telegram Bot 启动/停止
@@ -2225,11 +4388,38 @@ fn print_help() {
         println!("  /new                   New conversation (clear history)");
         println!("  /clear                 Clear screen");
         println!("  /config                Show current config");
+        println!("  /diff [path]           Show uncommitted changes in the workspace (git diff)");
+        println!("  /whoami                Summarize provider, mode, workspace, identity files and status");
         println!("  /switch                Switch Provider + model");
         println!("  /apikey                Change API Key or Base URL");
+        println!(
+            "  /maxtokens [n|off]     Show / set the reply length cap of the current provider"
+        );
+        println!("  /temp <0.0-2.0>        Set the temperature for this session");
+        println!("  /param [set <k> <v>|reset]  Show / override temperature, top_p, max_tokens for this session");
         println!();
-        println!("  /mode                  Switch security mode (supervised/full/read-only)");
+        println!("  /mode                  Switch security mode (supervised/full/read-only) and edit allowed commands");
+        println!("  /mode allow +cmd -cmd  Add / remove shell allowlist entries (saved to config)");
+        println!("  /ask <question>        Answer one message with read-only tools (no writes)");
+        println!("  /readonly [on|off]     Keep every turn read-only for this session");
         println!("  /mcp                   List loaded MCP tools");
+        println!("  /cost                  Estimate tokens and cost of the next request");
+        println!("  /offline [probe]       Show offline state / probe provider now");
+        println!("  /ps                    List running tool-spawned processes");
+        println!("  /stats                 Show streaming back-pressure counters");
+        println!("  /reasoning [on|off]    Show / hide the model's reasoning while streaming");
+        println!("  /goal set|show|done    Set / show / complete the session goal (kept in every prompt)");
+        println!("  /branch [name]         Explore in a throwaway branch of the conversation");
+        println!("  /branch merge|drop     Leave the branch, keeping a summary / discarding it");
+        println!("  /undo-file <path>      Restore a file overwritten by file_write");
+        println!("  /safety stash|restore  Stash uncommitted changes before the agent edits / restore them");
+        println!("  /history [save|load f] Print / save / load the raw history JSON");
+        println!("  /report                Write a redacted local incident report");
+        println!("  /kill <id>             Kill a process listed by /ps");
+        println!("  /good                  Mark the previous answer as helpful");
+        println!(
+            "  /bad [reason]          Mark the previous answer as wrong (reason is remembered)"
+        );
         println!();
         println!("  /skill                 List all available skills");
         println!("  /skill <name>          Load skill instructions into current conversation");
@@ -2242,6 +4432,7 @@ fn print_help() {
         println!("  /identity show <type>  Show identity file (user/soul/agent)");
         println!("  /identity edit <type>  Edit identity file");
         println!("  /identity reload       Reload identity files (takes effect immediately)");
+        println!("  /init-project          Scaffold .rrclaw/ from the detected toolchain");
         println!();
         println!("  /routine               List all scheduled tasks");
         println!("  /routine add           Add scheduled task");
@@ -2249,8 +4440,14 @@ fn print_help() {
         println!("  /routine enable        Enable scheduled task");
         println!("  /routine disable       Disable scheduled task");
         println!("  /routine run           Manually trigger a task");
+        println!("  /routine cancel <n>    Cancel a running task");
+        println!("  /routine simulate <n>  Dry-run a task supervised (no log, no delivery)");
         println!("  /routine logs          View execution logs");
+        println!("  /routine export [file] Export dynamic routines as JSON");
+        println!("  /routine import <file> Import routines (--overwrite replaces same name)");
+        println!("  /routine pause|resume  Pause / resume all scheduled triggers");
         println!();
+        println!("  !<message>             Run one message in Full mode, then restore the mode");
         println!("  exit, quit             Quit");
         println!();
         println!("Other input is sent to the AI.");
@@ -2260,11 +4457,36 @@ fn print_help() {
         println!("  /new                   新建对话（清空历史）");
         println!("  /clear                 清屏");
         println!("  /config                显示当前配置");
+        println!("  /diff [路径]           显示工作目录中未提交的改动（git diff）");
+        println!("  /whoami                汇总 Provider、模式、工作目录、身份文件与各模块状态");
         println!("  /switch                切换 Provider + 模型");
         println!("  /apikey                修改 API Key 或 Base URL");
+        println!("  /maxtokens [n|off]     查看 / 设置当前 Provider 的回复长度上限");
+        println!("  /temp <0.0-2.0>        设置本会话的 temperature");
+        println!(
+            "  /param [set <k> <v>|reset]  查看 / 覆盖本会话的 temperature、top_p、max_tokens"
+        );
         println!();
-        println!("  /mode                  切换安全模式（supervised/full/read-only）");
+        println!("  /mode                  切换安全模式（supervised/full/read-only），并可编辑命令白名单");
+        println!("  /mode allow +cmd -cmd  添加 / 删除 shell 命令白名单（写入配置文件）");
+        println!("  /ask <问题>            本条消息只允许读取类工具（保证不写入）");
+        println!("  /readonly [on|off]     本会话所有回合只允许读取类工具");
         println!("  /mcp                   列出已加载的 MCP 工具");
+        println!("  /cost                  估算下一次请求的 token 数与费用");
+        println!("  /offline [probe]       查看离线状态 / 立即探测 Provider");
+        println!("  /ps                    列出工具启动、仍在运行的子进程");
+        println!("  /stats                 查看流式输出的背压统计");
+        println!("  /reasoning [on|off]    流式输出时显示 / 隐藏模型的思考过程");
+        println!("  /goal set|show|done    设置 / 查看 / 完成会话目标（每轮注入 prompt）");
+        println!("  /branch [名称]         在一次性对话分支中试探其他方案");
+        println!("  /branch merge|drop     离开分支：保留结论摘要 / 直接丢弃");
+        println!("  /undo-file <路径>      恢复被 file_write 覆盖的文件");
+        println!("  /safety stash|restore  Agent 修改前暂存未提交的改动 / 恢复暂存");
+        println!("  /history [save|load f] 查看 / 保存 / 加载原始对话历史 JSON");
+        println!("  /report                生成脱敏的本地故障报告");
+        println!("  /kill <id>             终止 /ps 列出的子进程");
+        println!("  /good                  标记上一轮回答有用");
+        println!("  /bad [原因]            标记上一轮回答有误（原因会存入记忆）");
         println!();
         println!("  /skill                 列出所有可用技能");
         println!("  /skill <name>          加载技能指令到当前对话");
@@ -2277,6 +4499,7 @@ fn print_help() {
         println!("  /identity show <type>  查看身份文件内容（user/soul/agent）");
         println!("  /identity edit <type>  编辑身份文件（$EDITOR）");
         println!("  /identity reload       重新加载身份文件（立即生效）");
+        println!("  /init-project          检测工具链并生成 .rrclaw/ 项目脚手架");
         println!();
         println!("  /routine               列出所有定时任务");
         println!("  /routine add           添加定时任务");
@@ -2284,23 +4507,77 @@ fn print_help() {
         println!("  /routine enable        启用定时任务");
         println!("  /routine disable       禁用定时任务");
         println!("  /routine run           手动触发定时任务");
+        println!("  /routine cancel <n>    取消正在执行的定时任务");
+        println!("  /routine simulate <n>  监督模式试跑（不记录日志、不发送结果）");
         println!("  /routine logs          查看执行日志");
+        println!("  /routine export [文件] 导出动态定时任务（JSON）");
+        println!("  /routine import <文件> 导入定时任务（--overwrite 覆盖同名）");
+        println!("  /routine pause|resume  暂停 / 恢复全部定时触发");
         println!();
+        println!("  !<消息>                本条消息以 Full 模式执行，结束后恢复原模式");
         println!("  exit, quit             退出");
         println!();
         println!("其他输入会发送给 AI 处理。");
     }
 }
 
-/// 流式处理消息并实时打印
-async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
-    let (tx, mut rx) = mpsc::channel::<StreamEvent>(64);
+/// 终端内最多显示的 diff 行数（超出部分提示省略）
+const MAX_DIFF_PREVIEW_LINES: usize = 40;
 
-    // 在后台 task 中消费 stream events 并打印
-    let print_handle = tokio::spawn(async move {
+/// 渲染工具结构化输出（diff 高亮 / 表格）
+fn render_tool_output(kind: &crate::tools::ToolOutputKind, content: &str) {
+    use crate::tools::ToolOutputKind;
+    match kind {
+        ToolOutputKind::Diff => {
+            for line in content.lines().take(MAX_DIFF_PREVIEW_LINES) {
+                println!("{}", colorize_diff_line(line));
+            }
+            let total = content.lines().count();
+            if total > MAX_DIFF_PREVIEW_LINES {
+                println!(
+                    "{}    … {} more lines{}",
+                    ansi::DIM,
+                    total - MAX_DIFF_PREVIEW_LINES,
+                    ansi::RESET
+                );
+            }
+        }
+        ToolOutputKind::Table { headers, rows } => {
+            println!("{}", crate::channels::render::render_table(headers, rows));
+        }
+        // 文件引用 / JSON：Success 摘要已足够，不额外打印
+        ToolOutputKind::FileRef { .. } | ToolOutputKind::Json { .. } => {}
+    }
+}
+
+/// 为单行 unified diff 添加 ANSI 颜色
+fn colorize_diff_line(line: &str) -> String {
+    let color = if line.starts_with("+++") || line.starts_with("---") {
+        ansi::DIM
+    } else if line.starts_with('+') {
+        ansi::GREEN
+    } else if line.starts_with('-') {
+        ansi::RED
+    } else if line.starts_with("@@") {
+        ansi::CYAN
+    } else {
+        return line.to_string();
+    };
+    format!("{}{}{}", color, line, ansi::RESET)
+}
+
+/// 在后台 task 中消费 stream events 并打印到终端，返回是否输出过正文
+fn spawn_stream_printer(
+    mut rx: tokio::sync::mpsc::Receiver<StreamEvent>,
+    verbosity: ToolVerbosity,
+) -> tokio::task::JoinHandle<bool> {
+    let mut reasoning = ReasoningDisplay::new(SHOW_REASONING.load(Ordering::Relaxed));
+    tokio::spawn(async move {
         let mut has_output = false;
         // Thinking 动画: 收到 Thinking 后启动，收到首个 Text/ToolStatus/Done 后停止
         let mut thinking_handle: Option<tokio::task::JoinHandle<()>> = None;
+        // 上一行是"准备调用"提示（由下一条状态覆盖）
+        let mut preparing_line = false;
         let thinking_flag = Arc::new(std::sync::atomic::AtomicBool::new(false));
 
         while let Some(event) = rx.recv().await {
@@ -2331,6 +4608,19 @@ async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
                         }
                     }));
                 }
+                StreamEvent::Reasoning(delta) => {
+                    // 显示关闭时保持 thinking 动画
+                    let Some(out) = reasoning.push(&delta) else {
+                        continue;
+                    };
+                    if let Some(handle) = thinking_handle.take() {
+                        thinking_flag.store(false, std::sync::atomic::Ordering::Relaxed);
+                        let _ = handle.await;
+                        print!("\r\x1b[K"); // 清除 thinking 行
+                    }
+                    print!("{}", out);
+                    let _ = std::io::stdout().flush();
+                }
                 StreamEvent::Text(text) => {
                     // 停止 thinking 动画
                     if let Some(handle) = thinking_handle.take() {
@@ -2339,6 +4629,12 @@ async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
                         print!("\r\x1b[K"); // 清除 thinking 行
                         let _ = std::io::stdout().flush();
                     }
+                    if let Some(end) = reasoning.close() {
+                        print!("{}", end);
+                    }
+                    if std::mem::take(&mut preparing_line) {
+                        println!();
+                    }
                     print!("{}", text);
                     let _ = std::io::stdout().flush();
                     has_output = true;
@@ -2351,10 +4647,38 @@ async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
                         print!("\r\x1b[K"); // 清除 thinking 行
                         let _ = std::io::stdout().flush();
                     }
+                    if let Some(end) = reasoning.close() {
+                        print!("{}", end);
+                    }
+                    // 覆盖"准备调用"提示行，否则另起一行
+                    let replacing = std::mem::take(&mut preparing_line);
+                    let lead = if replacing { "\r\x1b[K" } else { "\n" };
+                    if replacing
+                        && matches!(
+                            status,
+                            ToolStatusKind::Success(_) | ToolStatusKind::Failed(_)
+                        )
+                    {
+                        print!("{}", lead);
+                    }
                     match &status {
+                        ToolStatusKind::Preparing => {
+                            let lang = crate::config::Config::get_language();
+                            print!(
+                                "{}{}⋯ {}{}{}",
+                                lead,
+                                ansi::DIM,
+                                t(lang, "准备调用 ", "preparing "),
+                                name,
+                                ansi::RESET
+                            );
+                            let _ = std::io::stdout().flush();
+                            preparing_line = true;
+                        }
                         ToolStatusKind::Running(cmd) => {
                             print!(
-                                "\n{}⏳{} {} ...{}",
+                                "{}{}⏳{} {} ...{}",
+                                lead,
                                 ansi::YELLOW,
                                 ansi::RESET,
                                 cmd,
@@ -2387,6 +4711,43 @@ async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
                         print!("\r\x1b[K");
                         let _ = std::io::stdout().flush();
                     }
+                    if let Some(end) = reasoning.close() {
+                        print!("{}", end);
+                        let _ = std::io::stdout().flush();
+                    }
+                }
+                StreamEvent::ToolOutput { kind, content, .. } => {
+                    render_tool_output(&kind, &content);
+                }
+                StreamEvent::ToolFeedback {
+                    summary, content, ..
+                } => {
+                    // 状态行下方按 tool_verbosity 展示摘要或完整输出
+                    if let Some(text) = verbosity.select(summary.as_deref(), &content) {
+                        for line in text.lines() {
+                            println!("{}    {}{}", ansi::DIM, line, ansi::RESET);
+                        }
+                    }
+                }
+                StreamEvent::GoalUpdate(status) => {
+                    // 停止 thinking 动画
+                    if let Some(handle) = thinking_handle.take() {
+                        thinking_flag.store(false, std::sync::atomic::Ordering::Relaxed);
+                        let _ = handle.await;
+                        print!("\r\x1b[K");
+                        let _ = std::io::stdout().flush();
+                    }
+                    println!("\n{}🎯 {}{}", ansi::DIM, status, ansi::RESET);
+                }
+                StreamEvent::Notice(notice) => {
+                    // 停止 thinking 动画
+                    if let Some(handle) = thinking_handle.take() {
+                        thinking_flag.store(false, std::sync::atomic::Ordering::Relaxed);
+                        let _ = handle.await;
+                        print!("\r\x1b[K");
+                        let _ = std::io::stdout().flush();
+                    }
+                    println!("\n{}⚠ {}{}", ansi::YELLOW, notice, ansi::RESET);
                 }
                 StreamEvent::ToolCallDelta { .. } => {
                     // tool call 增量不打印给用户
@@ -2401,10 +4762,29 @@ async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
             let _ = std::io::stdout().flush();
         }
         has_output
-    });
+    })
+}
+
+/// 流式处理消息并实时打印（`one_shot` 时本条消息以 Full 模式 / 只读回合执行）
+async fn stream_message(
+    agent: &mut Agent,
+    input: &str,
+    one_shot: Option<OneShot>,
+    verbosity: ToolVerbosity,
+) -> Result<()> {
+    let (tx, rx) = stream_channel();
+    let print_handle = spawn_stream_printer(rx, verbosity);
 
     // 调用流式处理
-    let result = agent.process_message_stream(input, tx).await;
+    let result = match one_shot {
+        Some(OneShot::Full) => {
+            agent
+                .process_message_stream_as(crate::security::AutonomyLevel::Full, input, tx)
+                .await
+        }
+        Some(OneShot::Ask) => agent.process_message_stream_read_only(input, tx).await,
+        None => agent.process_message_stream(input, tx).await,
+    };
 
     // 等待打印完成
     let has_output = print_handle.await.unwrap_or(false);
@@ -2416,6 +4796,8 @@ async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
             } else {
                 println!();
             }
+            print_provenance(agent);
+            print_change_summary(agent);
         }
         Err(e) => {
             println!();
@@ -2426,42 +4808,190 @@ async fn stream_message(agent: &mut Agent, input: &str) -> Result<()> {
     Ok(())
 }
 
-/// 单次消息模式（流式输出）
-pub async fn run_single(agent: &mut Agent, message: &str, memory: &SqliteMemory) -> Result<()> {
+/// 流式思考过程的显示状态：开启时以暗色 `💭` 块打印 `StreamEvent::Reasoning`，
+/// 正文 / 工具状态到达时收起（换行结束该块）
+struct ReasoningDisplay {
+    enabled: bool,
+    /// 当前是否处于已打印、尚未结束的思考块中
+    open: bool,
+}
+
+impl ReasoningDisplay {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            open: false,
+        }
+    }
+
+    /// 收到一段思考增量，返回要打印的文本（显示关闭时为 None）
+    fn push(&mut self, delta: &str) -> Option<String> {
+        if !self.enabled || delta.is_empty() {
+            return None;
+        }
+        let prefix = if self.open { "" } else { "💭 " };
+        self.open = true;
+        Some(format!("{}{}{}{}", ansi::DIM, prefix, delta, ansi::RESET))
+    }
+
+    /// 思考块结束，返回收尾的换行（未打印过思考内容时为 None）
+    fn close(&mut self) -> Option<&'static str> {
+        std::mem::replace(&mut self.open, false).then_some("\n\n")
+    }
+}
+
+/// 暗色显示本轮回答的来源（Provider / 模型 / 重试次数）
+fn print_provenance(agent: &Agent) {
+    if let Some(provenance) = agent.last_provenance() {
+        let english = crate::config::Config::get_language().is_english();
+        println!(
+            "{}· {}{}\n",
+            ansi::DIM,
+            provenance.describe(english),
+            ansi::RESET
+        );
+    }
+}
+
+/// 暗色显示本轮改动的文件（`[cli] show_changes`）
+fn print_change_summary(agent: &mut Agent) {
+    if let Some(changes) = agent.take_change_summary() {
+        println!("{}{}{}\n", ansi::DIM, changes.format_line(), ansi::RESET);
+    }
+}
+
+/// 单次消息模式的输出格式（`rrclaw agent -m ... --format`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// 原样流式输出模型回复（默认）
+    #[default]
+    Markdown,
+    /// 去掉 Markdown 标记后输出纯文本
+    Plain,
+    /// 输出一个 JSON 对象：`{"session_id", "reply", "changes", "provenance", "error"}`
+    Json,
+}
+
+/// 单次消息模式
+///
+/// `continue_session` 时先加载最近一次会话的历史（`rrclaw agent --continue -m ...`），
+/// 结束后写回同一 session，使多次独立调用共享上下文。
+/// Markdown 格式流式输出；plain / json 等回复完成后一次性输出。
+pub async fn run_single(
+    agent: &mut Agent,
+    message: &str,
+    memory: &SqliteMemory,
+    continue_session: bool,
+    format: OutputFormat,
+) -> Result<()> {
     setup_cli_confirm(agent);
 
-    let (tx, mut rx) = mpsc::channel::<StreamEvent>(64);
+    let session_id = if continue_session {
+        let session_id = memory
+            .latest_session_id()
+            .await?
+            .unwrap_or_else(today_session_id);
+        let history = memory.load_conversation_history(&session_id).await?;
+        info!("继续会话 {}：恢复 {} 条对话历史", session_id, history.len());
+        agent.set_history(history);
+        agent.set_goal(memory.load_session_goal(&session_id).await?);
+        session_id
+    } else {
+        today_session_id()
+    };
+
+    let (tx, mut rx) = stream_channel();
 
+    let stream_text = format == OutputFormat::Markdown;
     let print_handle = tokio::spawn(async move {
         while let Some(event) = rx.recv().await {
             if let StreamEvent::Text(text) = event {
-                print!("{}", text);
-                let _ = std::io::stdout().flush();
+                if stream_text {
+                    print!("{}", text);
+                    let _ = std::io::stdout().flush();
+                }
             }
         }
     });
 
+    // `@provider[:model]` 前缀：本条消息固定由该 Provider 回答
+    let (message, saved) = match crate::agent::parse_pin(message) {
+        Some((pin, rest)) => {
+            let config = Config::load_or_init()?;
+            let pinned = crate::agent::pin::resolve_pin(&pin, &config)?;
+            (rest, Some(agent.swap_provider(pinned)))
+        }
+        None => (message, None),
+    };
     let result = agent.process_message_stream(message, tx).await;
     let _ = print_handle.await;
-    println!();
+    if let Some(saved) = saved {
+        agent.swap_provider(saved);
+    }
 
-    if let Err(e) = result {
-        let lang = crate::config::Config::get_language();
-        eprintln!("{}: {:#}", t(lang, "错误", "Error"), e);
+    match format {
+        OutputFormat::Markdown | OutputFormat::Plain => {
+            match &result {
+                Ok(reply) if format == OutputFormat::Plain => {
+                    println!("{}", crate::channels::render::strip_markdown(reply))
+                }
+                _ => println!(),
+            }
+            if result.is_ok() {
+                print_change_summary(agent);
+            }
+            if let Err(e) = &result {
+                let lang = crate::config::Config::get_language();
+                eprintln!("{}: {:#}", t(lang, "错误", "Error"), e);
+            }
+        }
+        OutputFormat::Json => {
+            let changes = agent.take_change_summary().map(|c| c.format_line());
+            println!(
+                "{}",
+                single_run_json(&session_id, &result, changes, agent.last_provenance())
+            );
+        }
     }
 
     // 单次消息也保存历史
-    let session_id = today_session_id();
     if let Err(e) = memory
         .save_conversation_history(&session_id, agent.history())
         .await
     {
-        debug!("保存对话历史失败: {:#}", e);
+        crate::memory::report_write_error("保存对话历史", &e);
+    }
+    // 只有续接的会话加载过目标，其余情况不覆盖已保存的目标
+    if continue_session {
+        if let Err(e) = memory.save_session_goal(&session_id, agent.goal()).await {
+            crate::memory::report_write_error("保存会话目标", &e);
+        }
     }
 
     Ok(())
 }
 
+/// `--format json` 的输出对象（单行）
+fn single_run_json(
+    session_id: &str,
+    result: &Result<String>,
+    changes: Option<String>,
+    provenance: Option<&crate::providers::Provenance>,
+) -> String {
+    let (reply, error) = match result {
+        Ok(reply) => (Some(reply.as_str()), None),
+        Err(e) => (None, Some(format!("{:#}", e))),
+    };
+    serde_json::json!({
+        "session_id": session_id,
+        "reply": reply,
+        "changes": changes,
+        "provenance": provenance,
+        "error": error,
+    })
+    .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2477,6 +5007,21 @@ mod tests {
         (dir, path)
     }
 
+    #[test]
+    fn quick_replies_are_numbered_in_one_dim_line() {
+        assert_eq!(format_quick_replies(Language::English, &[]), None);
+        let line = format_quick_replies(
+            Language::English,
+            &[
+                "Explain these changes".to_string(),
+                "Commit these changes".to_string(),
+            ],
+        )
+        .unwrap();
+        assert!(line.contains("[1] Explain these changes  [2] Commit these changes"));
+        assert!(line.contains("type a number"));
+    }
+
     #[test]
     fn save_default_to_config_updates_default_section() {
         let (_dir, path) = temp_config(
@@ -2506,6 +5051,107 @@ model = "deepseek-chat"
         assert!(doc["providers"]["deepseek"]["base_url"].is_str());
     }
 
+    /// 白名单为 `allowed` 的 Echo Agent
+    fn allowlist_agent(allowed: &[&str]) -> Agent {
+        let provider = crate::providers::echo::EchoProvider::new(&ProviderConfig {
+            base_url: "echo://".to_string(),
+            api_key: String::new(),
+            model: "echo".to_string(),
+            auth_style: Some("echo".to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        });
+        let policy = crate::security::SecurityPolicy {
+            allowed_commands: allowed.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+        Agent::new(
+            Box::new(provider),
+            vec![],
+            Box::new(crate::memory::NoopMemory),
+            policy,
+            "echo".to_string(),
+            "echo://".to_string(),
+            "echo".to_string(),
+            0.0,
+            vec![],
+            None,
+        )
+    }
+
+    #[test]
+    fn edit_allowlist_adds_and_removes() {
+        let current = vec!["ls".to_string(), "cargo".to_string()];
+        assert_eq!(
+            edit_allowlist(&current, "+make -cargo npm ls").unwrap(),
+            vec!["ls", "make", "npm"]
+        );
+        // 删除不存在的条目、重复添加都不报错
+        assert_eq!(
+            edit_allowlist(&current, "-rm +ls").unwrap(),
+            vec!["ls", "cargo"]
+        );
+        assert!(edit_allowlist(&current, "+").is_err());
+        assert!(edit_allowlist(&current, r#"+"ls""#).is_err());
+        assert!(edit_allowlist(&current, "+a,b").is_err());
+    }
+
+    #[test]
+    fn allowlist_edit_updates_live_policy_and_config() {
+        let (_dir, path) = temp_config(
+            r#"
+[security]
+autonomy = "supervised"
+# 保留注释
+allowed_commands = ["ls", "cargo"]
+"#,
+        );
+        let mut agent = allowlist_agent(&["ls", "cargo"]);
+
+        let commands = apply_allowlist_edit(&mut agent, "+make -cargo", Some(&path)).unwrap();
+        assert_eq!(commands, vec!["ls", "make"]);
+        assert_eq!(agent.policy().allowed_commands, vec!["ls", "make"]);
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("# 保留注释"));
+        let doc: toml_edit::DocumentMut = content.parse().unwrap();
+        let persisted: Vec<&str> = doc["security"]["allowed_commands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert_eq!(persisted, vec!["ls", "make"]);
+        assert_eq!(doc["security"]["autonomy"].as_str(), Some("supervised"));
+    }
+
+    #[test]
+    fn invalid_allowlist_edit_changes_nothing() {
+        let original = "[security]\nallowed_commands = [\"ls\"]\n";
+        let (_dir, path) = temp_config(original);
+        let mut agent = allowlist_agent(&["ls"]);
+
+        assert!(apply_allowlist_edit(&mut agent, "+make +a,b", Some(&path)).is_err());
+        assert_eq!(agent.policy().allowed_commands, vec!["ls"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        // 空白名单也能写入
+        let commands = apply_allowlist_edit(&mut agent, "-ls", Some(&path)).unwrap();
+        assert!(commands.is_empty());
+        assert!(agent.policy().allowed_commands.is_empty());
+        let doc: toml_edit::DocumentMut = fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(
+            doc["security"]["allowed_commands"]
+                .as_array()
+                .map(|a| a.len()),
+            Some(0)
+        );
+    }
+
     #[test]
     fn save_provider_to_config_adds_new_provider() {
         let (_dir, path) = temp_config(
@@ -2526,6 +5172,12 @@ model = "deepseek-chat"
             api_key: "glm-key-123".to_string(),
             model: "glm-4.7".to_string(),
             auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
         };
 
         // 执行
@@ -2593,4 +5245,121 @@ model = "deepseek-chat"
         let items = extract_section_items(content, "代码规范");
         assert!(items.is_empty());
     }
+
+    #[test]
+    fn colorize_diff_line_marks_additions_and_deletions() {
+        assert!(colorize_diff_line("+added").starts_with(ansi::GREEN));
+        assert!(colorize_diff_line("-removed").starts_with(ansi::RED));
+        assert!(colorize_diff_line("@@ -1 +1 @@").starts_with(ansi::CYAN));
+        assert!(colorize_diff_line("+++ b/file").starts_with(ansi::DIM));
+        assert_eq!(colorize_diff_line(" context"), " context");
+    }
+
+    #[tokio::test]
+    async fn workspace_diff_shows_changes_even_in_read_only_mode() {
+        let tmp = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .output()
+                .unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "t@example.com"]);
+        git(&["config", "user.name", "t"]);
+        std::fs::write(tmp.path().join("notes.md"), "draft\n").unwrap();
+        git(&["add", "notes.md"]);
+        git(&["commit", "-m", "init"]);
+
+        let policy = crate::security::SecurityPolicy {
+            autonomy: crate::security::AutonomyLevel::ReadOnly,
+            workspace_dir: tmp.path().to_path_buf(),
+            ..Default::default()
+        };
+        assert_eq!(workspace_diff(&policy, "").await.unwrap().trim(), "");
+
+        std::fs::write(tmp.path().join("notes.md"), "final\n").unwrap();
+        let diff = workspace_diff(&policy, "").await.unwrap();
+        assert!(diff.contains("-draft"), "{}", diff);
+        assert!(diff.contains("+final"), "{}", diff);
+        assert!(workspace_diff(&policy, "--output=/tmp/x").await.is_err());
+    }
+
+    #[test]
+    fn cost_estimate_formats_tokens_and_price() {
+        let pricing = crate::config::ModelPricing {
+            input: 3.0,
+            output: 15.0,
+        };
+        assert_eq!(
+            format_cost_estimate(3_400, Some(&pricing)),
+            "~3.4k tokens ≈ $0.0102"
+        );
+        assert_eq!(
+            format_cost_estimate(250_000, Some(&pricing)),
+            "~250.0k tokens ≈ $0.75"
+        );
+        assert_eq!(format_cost_estimate(850, None), "~850 tokens");
+        assert_eq!(
+            format_cost_estimate(1_200_000, Some(&pricing)),
+            "~1.2M tokens ≈ $3.60"
+        );
+    }
+
+    #[test]
+    fn reasoning_display_follows_toggle() {
+        let mut hidden = ReasoningDisplay::new(false);
+        assert_eq!(hidden.push("先想想"), None);
+        assert_eq!(hidden.close(), None);
+
+        let mut shown = ReasoningDisplay::new(true);
+        let first = shown.push("先想想").unwrap();
+        assert!(first.contains("💭 先想想"));
+        assert!(first.starts_with(ansi::DIM));
+        // 同一块内只在开头打印一次标记
+        assert!(!shown.push("，再回答").unwrap().contains("💭"));
+        assert_eq!(shown.close(), Some("\n\n"));
+        assert_eq!(shown.close(), None);
+    }
+
+    #[test]
+    fn single_run_json_reports_reply_or_error() {
+        let ok: Result<String> = Ok("**done**".to_string());
+        let provenance = crate::providers::Provenance {
+            provider: "deepseek".to_string(),
+            model: "deepseek-chat".to_string(),
+            retries: 1,
+            fallback: false,
+        };
+        let v: serde_json::Value = serde_json::from_str(&single_run_json(
+            "s1",
+            &ok,
+            Some("✎ modified: a.rs".into()),
+            Some(&provenance),
+        ))
+        .unwrap();
+        assert_eq!(v["session_id"], "s1");
+        assert_eq!(v["reply"], "**done**");
+        assert_eq!(v["changes"], "✎ modified: a.rs");
+        assert_eq!(v["provenance"]["provider"], "deepseek");
+        assert_eq!(v["provenance"]["retries"], 1);
+        assert!(v["error"].is_null());
+
+        let err: Result<String> = Err(eyre!("boom"));
+        let v: serde_json::Value =
+            serde_json::from_str(&single_run_json("s1", &err, None, None)).unwrap();
+        assert!(v["reply"].is_null());
+        assert!(v["provenance"].is_null());
+        assert_eq!(v["error"], "boom");
+    }
+
+    #[test]
+    fn session_rewriting_commands_are_blocked_in_branches() {
+        assert!(rewrites_session("new"));
+        assert!(rewrites_session("history load old.json"));
+        assert!(!rewrites_session("history"));
+        assert!(!rewrites_session("history save out.json"));
+        assert!(!rewrites_session("goal show"));
+    }
 }
@@ -0,0 +1,354 @@
+//! REPL 回答进行中的输入队列
+//!
+//! reedline 只在等待输入时读取终端，回答进行中键入的内容原先会混进输出并在下一轮丢失。
+//! 现在每轮开始时启动 `TurnInputReader`（独立线程，canonical 模式下 poll stdin，停止时最多等待
+//! `POLL_INTERVAL`），读到的整行交给 `InputRouter`：
+//! - 有工具确认在等待输入时，整行交给确认提示（`read_confirm_line`），避免两处抢读 stdin
+//! - 否则进入 `InputQueue`：`stop` 或 Esc（Esc 后回车）在队列非空时清空队列
+//! - `/ps`、`/kill <id>` 不排队、立即执行：卡住的工具子进程只能在回答进行中终止
+//!
+//! 本轮结束后 `InputQueue::finish_turn` 决定下一步：`[cli] queue_messages = true` 时自动发送最早
+//! 一条，否则把它预填到输入行供编辑；其余消息继续排队，逐轮处理。状态机与终端无关，可单独测试。
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// 清空队列的控制输入（Esc 在 canonical 模式下以 `\x1b` 随回车到达）
+const STOP_WORD: &str = "stop";
+const ESC: char = '\x1b';
+/// 读线程检查停止标记的间隔
+#[cfg_attr(not(unix), allow(dead_code))]
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 一行输入对队列的影响
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueueEvent {
+    /// 加入队列，当前共 n 条
+    Queued(usize),
+    /// 清空了 n 条排队消息
+    Cleared(usize),
+    /// 空行，或队列为空时的 Esc
+    Ignored,
+    /// 立即执行的本地命令（不入队）
+    Immediate(ImmediateCommand),
+}
+
+/// 回答进行中立即执行的命令
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImmediateCommand {
+    /// `/ps`
+    Ps,
+    /// `/kill <参数>`
+    Kill(String),
+}
+
+impl ImmediateCommand {
+    fn parse(line: &str) -> Option<Self> {
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match name {
+            "/ps" if rest.trim().is_empty() => Some(Self::Ps),
+            "/kill" => Some(Self::Kill(rest.trim().to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// 本轮结束后的下一步
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AfterTurn {
+    /// 没有排队消息，回到正常输入
+    Idle,
+    /// 自动发送该消息（其后还剩 `remaining` 条）
+    Submit { line: String, remaining: usize },
+    /// 预填到输入行供编辑（其后还剩 `remaining` 条）
+    Prefill { line: String, remaining: usize },
+}
+
+/// 排队消息（先进先出）
+#[derive(Debug, Default)]
+pub struct InputQueue {
+    lines: VecDeque<String>,
+}
+
+impl InputQueue {
+    /// 处理回答进行中读到的一行
+    pub fn push(&mut self, line: &str) -> QueueEvent {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let control = line.trim();
+        if control.contains(ESC) || (control == STOP_WORD && !self.lines.is_empty()) {
+            if self.lines.is_empty() {
+                return QueueEvent::Ignored;
+            }
+            let cleared = self.lines.len();
+            self.lines.clear();
+            return QueueEvent::Cleared(cleared);
+        }
+        if control.is_empty() {
+            return QueueEvent::Ignored;
+        }
+        if let Some(command) = ImmediateCommand::parse(control) {
+            return QueueEvent::Immediate(command);
+        }
+        self.lines.push_back(control.to_string());
+        QueueEvent::Queued(self.lines.len())
+    }
+
+    /// 本轮结束：取出最早一条，按 `auto_submit` 决定自动发送还是预填
+    pub fn finish_turn(&mut self, auto_submit: bool) -> AfterTurn {
+        let Some(line) = self.lines.pop_front() else {
+            return AfterTurn::Idle;
+        };
+        let remaining = self.lines.len();
+        if auto_submit {
+            AfterTurn::Submit { line, remaining }
+        } else {
+            AfterTurn::Prefill { line, remaining }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+/// 读线程与 REPL / 确认提示之间的分发点
+#[derive(Default)]
+pub struct InputRouter {
+    queue: Mutex<InputQueue>,
+    /// 正在等待输入的工具确认提示
+    confirm: Mutex<Option<std::sync::mpsc::Sender<String>>>,
+    /// 读线程是否在运行（未运行时确认提示直接读 stdin）
+    reading: AtomicBool,
+}
+
+impl InputRouter {
+    /// 分发读到的一行：优先交给等待中的确认提示，否则入队并提示用户
+    pub fn deliver(&self, line: String) {
+        if let Some(waiter) = self.confirm.lock().unwrap().take() {
+            let _ = waiter.send(line);
+            return;
+        }
+        let event = self.queue.lock().unwrap().push(&line);
+        match event {
+            QueueEvent::Immediate(ImmediateCommand::Ps) => super::cli::cmd_ps(),
+            QueueEvent::Immediate(ImmediateCommand::Kill(rest)) => super::cli::cmd_kill(&rest),
+            event => report(&event, &line),
+        }
+    }
+
+    /// 本轮结束后的下一步
+    pub fn finish_turn(&self, auto_submit: bool) -> AfterTurn {
+        self.queue.lock().unwrap().finish_turn(auto_submit)
+    }
+
+    /// 读线程运行中时，等待它转交下一行；否则返回 None（调用方自行读 stdin）
+    fn wait_confirm_line(&self) -> Option<String> {
+        if !self.reading.load(Ordering::Acquire) {
+            return None;
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        *self.confirm.lock().unwrap() = Some(tx);
+        // 读线程可能恰好在登记前退出（stdin EOF）
+        if !self.reading.load(Ordering::Acquire) {
+            self.confirm.lock().unwrap().take();
+        }
+        rx.recv().ok()
+    }
+}
+
+/// 队列变化时在终端给出一行暗色提示
+fn report(event: &QueueEvent, line: &str) {
+    let english = crate::config::Config::get_language().is_english();
+    let text = match event {
+        QueueEvent::Queued(n) if english => format!("⏎ queued ({}): {}", n, line.trim()),
+        QueueEvent::Queued(n) => format!("⏎ 已排队 ({}): {}", n, line.trim()),
+        QueueEvent::Cleared(n) if english => format!("⌫ cleared {} queued message(s)", n),
+        QueueEvent::Cleared(n) => format!("⌫ 已清空 {} 条排队消息", n),
+        QueueEvent::Ignored | QueueEvent::Immediate(_) => return,
+    };
+    println!("\r\x1b[K\x1b[2m{}\x1b[0m", text);
+}
+
+/// REPL 共享的分发点（工具确认回调在 Agent 内同步调用，无法直接拿到 REPL 的局部状态）
+pub fn router() -> &'static Arc<InputRouter> {
+    static ROUTER: std::sync::OnceLock<Arc<InputRouter>> = std::sync::OnceLock::new();
+    ROUTER.get_or_init(|| Arc::new(InputRouter::default()))
+}
+
+/// 工具确认提示读取一行：本轮读线程运行中时由它转交，否则直接读 stdin
+pub fn read_confirm_line() -> Option<String> {
+    if let Some(line) = router().wait_confirm_line() {
+        return Some(line);
+    }
+    let mut input = String::new();
+    std::io::BufRead::read_line(&mut std::io::stdin().lock(), &mut input)
+        .ok()
+        .map(|_| input)
+}
+
+/// 一轮回答期间的 stdin 读线程（drop 时停止并等待线程退出）
+pub struct TurnInputReader {
+    stop: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl TurnInputReader {
+    /// stdin 是终端时启动读线程；非 unix 平台或非终端时返回 None（保持原有行为）
+    pub fn start(router: Arc<InputRouter>) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            // SAFETY: isatty 只查询文件描述符属性
+            if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+                return None;
+            }
+            let stop = Arc::new(AtomicBool::new(false));
+            let flag = Arc::clone(&stop);
+            router.reading.store(true, Ordering::Release);
+            let handle = std::thread::Builder::new()
+                .name("rrclaw-turn-input".to_string())
+                .spawn(move || read_lines(&router, &flag))
+                .ok()?;
+            Some(Self {
+                stop,
+                handle: Some(handle),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = router;
+            None
+        }
+    }
+}
+
+impl Drop for TurnInputReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// canonical 模式下 stdin 每次可读时恰好是一整行；poll 带超时以便及时响应停止标记
+#[cfg(unix)]
+fn read_lines(router: &InputRouter, stop: &AtomicBool) {
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 4096];
+    while !stop.load(Ordering::Acquire) {
+        let mut fds = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: fds 指向一个有效的 pollfd
+        let ready = unsafe { libc::poll(&mut fds, 1, POLL_INTERVAL.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            continue;
+        }
+        // SAFETY: buf 可写且长度正确
+        let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if n <= 0 {
+            break;
+        }
+        pending.extend_from_slice(&buf[..n as usize]);
+        while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            router.deliver(String::from_utf8_lossy(&line).into_owned());
+        }
+    }
+    router.reading.store(false, Ordering::Release);
+    // 读线程退出时仍在等待的确认提示改为直接读 stdin
+    router.confirm.lock().unwrap().take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_lines_are_released_one_per_turn() {
+        let mut queue = InputQueue::default();
+        assert_eq!(queue.finish_turn(true), AfterTurn::Idle);
+        assert_eq!(queue.push("first\n"), QueueEvent::Queued(1));
+        assert_eq!(queue.push("   \n"), QueueEvent::Ignored);
+        assert_eq!(queue.push("second\n"), QueueEvent::Queued(2));
+
+        assert_eq!(
+            queue.finish_turn(true),
+            AfterTurn::Submit {
+                line: "first".to_string(),
+                remaining: 1
+            }
+        );
+        assert_eq!(
+            queue.finish_turn(false),
+            AfterTurn::Prefill {
+                line: "second".to_string(),
+                remaining: 0
+            }
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn stop_and_escape_clear_only_when_something_is_queued() {
+        let mut queue = InputQueue::default();
+        // 队列为空时 stop 是普通消息，Esc 被忽略
+        assert_eq!(queue.push("\x1b\n"), QueueEvent::Ignored);
+        assert_eq!(queue.push("stop\n"), QueueEvent::Queued(1));
+        assert_eq!(queue.push("run tests\n"), QueueEvent::Queued(2));
+        assert_eq!(queue.push(" stop \n"), QueueEvent::Cleared(2));
+        assert_eq!(queue.finish_turn(true), AfterTurn::Idle);
+
+        queue.push("a\n");
+        assert_eq!(queue.push("\x1b\n"), QueueEvent::Cleared(1));
+        assert_eq!(queue.len(), 0);
+    }
+
+    #[test]
+    fn ps_and_kill_run_immediately_instead_of_queueing() {
+        let mut queue = InputQueue::default();
+        assert_eq!(
+            queue.push("/ps\n"),
+            QueueEvent::Immediate(ImmediateCommand::Ps)
+        );
+        assert_eq!(
+            queue.push(" /kill 3 \n"),
+            QueueEvent::Immediate(ImmediateCommand::Kill("3".to_string()))
+        );
+        assert_eq!(
+            queue.push("/kill\n"),
+            QueueEvent::Immediate(ImmediateCommand::Kill(String::new()))
+        );
+        assert!(queue.is_empty());
+        // 其他斜杠命令与普通消息照常排队
+        assert_eq!(queue.push("/psql help\n"), QueueEvent::Queued(1));
+        assert_eq!(queue.push("/ps all\n"), QueueEvent::Queued(2));
+    }
+
+    #[test]
+    fn router_hands_lines_to_waiting_confirmation_first() {
+        let router = InputRouter::default();
+        let (tx, rx) = std::sync::mpsc::channel();
+        *router.confirm.lock().unwrap() = Some(tx);
+
+        router.deliver("y\n".to_string());
+        assert_eq!(rx.recv().unwrap(), "y\n");
+        // 确认已结束，后续输入进入队列
+        router.deliver("next question\n".to_string());
+        assert_eq!(
+            router.finish_turn(false),
+            AfterTurn::Prefill {
+                line: "next question".to_string(),
+                remaining: 0
+            }
+        );
+    }
+}
@@ -1,7 +1,11 @@
 pub mod cli;
+pub mod input_queue;
+pub mod rate_limit;
+pub mod render;
 #[cfg(feature = "telegram")]
 pub mod telegram;
 pub mod unified;
+pub mod whoami;
 
 use serde::{Deserialize, Serialize};
 
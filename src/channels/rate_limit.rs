@@ -0,0 +1,377 @@
+//! 对外通道的限流、静音与每日预算
+//!
+//! 与具体通道无关（key 为 Telegram `ChatId`、webhook 来源等），调用方持有锁并传入当前时间，
+//! 便于测试。判定顺序：静音中 → 频率（单 chat / 全局，超限计一次违规，10 分钟内违规
+//! `mute_after` 次即静音）→ 当日预算 → 并发轮次。放行后调用方须在本轮结束时调用 `finish`。
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDate;
+
+use crate::config::RateLimitConfig;
+
+/// 频率统计窗口
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// 违规计数窗口（窗口内超限 `mute_after` 次触发静音）
+const VIOLATION_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// 一条消息的准入结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Admission {
+    /// 放行（本轮结束后须调用 `finish`）
+    Allowed,
+    /// 超出频率限制，`retry_after` 后窗口内有空位
+    RateLimited { retry_after: Duration },
+    /// 处于静音期；`newly` 为 true 表示本条消息触发了静音（只需通知一次）
+    Muted { remaining: Duration, newly: bool },
+    /// 当日预算已用完（本地时间 0 点重置）
+    BudgetExceeded,
+    /// 该 chat 上一轮尚未结束，或进行中的轮次已达上限
+    Busy,
+}
+
+#[derive(Debug, Default)]
+struct ChatState {
+    recent: VecDeque<Instant>,
+    violations: VecDeque<Instant>,
+    muted_until: Option<Instant>,
+    active: bool,
+    usage_day: Option<NaiveDate>,
+    tokens: usize,
+    cost: f64,
+}
+
+impl ChatState {
+    /// 当日已用量（跨天自动清零）
+    fn usage_on(&mut self, today: NaiveDate) -> (usize, f64) {
+        if self.usage_day != Some(today) {
+            self.usage_day = Some(today);
+            self.tokens = 0;
+            self.cost = 0.0;
+        }
+        (self.tokens, self.cost)
+    }
+}
+
+/// 限流器
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    config: RateLimitConfig,
+    global: VecDeque<Instant>,
+    chats: HashMap<K, ChatState>,
+    active_turns: usize,
+}
+
+impl<K: Hash + Eq + Clone> RateLimiter<K> {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            global: VecDeque::new(),
+            chats: HashMap::new(),
+            active_turns: 0,
+        }
+    }
+
+    /// 更新配置（热重载），已有计数保留
+    pub fn set_config(&mut self, config: RateLimitConfig) {
+        self.config = config;
+    }
+
+    /// 判定一条消息能否处理
+    pub fn admit(&mut self, key: &K, now: Instant, today: NaiveDate) -> Admission {
+        prune(&mut self.global, now, RATE_WINDOW);
+        let config = &self.config;
+        let chat = self.chats.entry(key.clone()).or_default();
+
+        if let Some(until) = chat.muted_until {
+            if until > now {
+                return Admission::Muted {
+                    remaining: until - now,
+                    newly: false,
+                };
+            }
+            chat.muted_until = None;
+        }
+
+        prune(&mut chat.recent, now, RATE_WINDOW);
+        let per_chat_full = config.per_chat_per_minute > 0
+            && chat.recent.len() >= config.per_chat_per_minute as usize;
+        let global_full =
+            config.global_per_minute > 0 && self.global.len() >= config.global_per_minute as usize;
+        if per_chat_full || global_full {
+            prune(&mut chat.violations, now, VIOLATION_WINDOW);
+            chat.violations.push_back(now);
+            if config.mute_after > 0 && chat.violations.len() >= config.mute_after as usize {
+                let mute = Duration::from_secs(config.mute_minutes * 60);
+                chat.muted_until = Some(now + mute);
+                chat.violations.clear();
+                return Admission::Muted {
+                    remaining: mute,
+                    newly: true,
+                };
+            }
+            // 单 chat 超限时等本 chat 最早一条出窗口，否则等全局最早一条
+            let oldest = if per_chat_full {
+                chat.recent.front()
+            } else {
+                self.global.front()
+            };
+            let retry_after = oldest
+                .map(|t| (*t + RATE_WINDOW).saturating_duration_since(now))
+                .unwrap_or(RATE_WINDOW);
+            return Admission::RateLimited { retry_after };
+        }
+        chat.recent.push_back(now);
+        self.global.push_back(now);
+
+        let (tokens, cost) = chat.usage_on(today);
+        let over_tokens = config.daily_token_budget.is_some_and(|max| tokens >= max);
+        let over_cost = config.daily_cost_budget.is_some_and(|max| cost >= max);
+        if over_tokens || over_cost {
+            return Admission::BudgetExceeded;
+        }
+
+        if chat.active
+            || (config.max_concurrent_turns > 0 && self.active_turns >= config.max_concurrent_turns)
+        {
+            return Admission::Busy;
+        }
+        chat.active = true;
+        self.active_turns += 1;
+        Admission::Allowed
+    }
+
+    /// 放行的轮次结束（无论成功与否）
+    pub fn finish(&mut self, key: &K) {
+        if let Some(chat) = self.chats.get_mut(key) {
+            if chat.active {
+                chat.active = false;
+                self.active_turns = self.active_turns.saturating_sub(1);
+            }
+        }
+    }
+
+    /// 累加本轮用量（估算的 tokens 与费用）
+    pub fn record_usage(&mut self, key: &K, tokens: usize, cost: f64, today: NaiveDate) {
+        let chat = self.chats.entry(key.clone()).or_default();
+        chat.usage_on(today);
+        chat.tokens += tokens;
+        chat.cost += cost;
+    }
+
+    /// 当日已用量（tokens, 美元）
+    pub fn usage(&mut self, key: &K, today: NaiveDate) -> (usize, f64) {
+        self.chats
+            .get_mut(key)
+            .map(|chat| chat.usage_on(today))
+            .unwrap_or_default()
+    }
+}
+
+/// 丢弃窗口外的时间戳
+fn prune(times: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while times
+        .front()
+        .is_some_and(|t| now.saturating_duration_since(*t) >= window)
+    {
+        times.pop_front();
+    }
+}
+
+/// 未授权来源的消息计数（定期汇总通知管理员）
+#[derive(Debug)]
+pub struct BlockedTally<K> {
+    messages: u64,
+    sources: HashSet<K>,
+}
+
+impl<K> Default for BlockedTally<K> {
+    fn default() -> Self {
+        Self {
+            messages: 0,
+            sources: HashSet::new(),
+        }
+    }
+}
+
+/// 一个统计周期内的拦截汇总
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockedSummary {
+    pub messages: u64,
+    pub sources: usize,
+}
+
+impl BlockedSummary {
+    pub fn format(&self) -> String {
+        format!(
+            "🛡 过去一天拦截了来自 {} 个未授权 chat 的 {} 条消息",
+            self.sources, self.messages
+        )
+    }
+}
+
+impl<K: Hash + Eq> BlockedTally<K> {
+    pub fn record(&mut self, source: K) {
+        self.messages += 1;
+        self.sources.insert(source);
+    }
+
+    /// 取出并清空当前计数；没有拦截时返回 None
+    pub fn take(&mut self) -> Option<BlockedSummary> {
+        if self.messages == 0 {
+            return None;
+        }
+        let summary = BlockedSummary {
+            messages: self.messages,
+            sources: self.sources.len(),
+        };
+        *self = Self::default();
+        Some(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            per_chat_per_minute: 2,
+            global_per_minute: 5,
+            max_concurrent_turns: 2,
+            mute_after: 2,
+            mute_minutes: 30,
+            daily_token_budget: None,
+            daily_cost_budget: None,
+        }
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, d).unwrap()
+    }
+
+    /// 放行并立即结束一轮
+    fn pass(limiter: &mut RateLimiter<i64>, key: i64, now: Instant) -> Admission {
+        let admission = limiter.admit(&key, now, day(16));
+        limiter.finish(&key);
+        admission
+    }
+
+    #[test]
+    fn per_chat_limit_then_mute_after_repeated_violations() {
+        let mut limiter = RateLimiter::new(config());
+        let t0 = Instant::now();
+        assert_eq!(pass(&mut limiter, 1, t0), Admission::Allowed);
+        assert_eq!(
+            pass(&mut limiter, 1, t0 + Duration::from_secs(10)),
+            Admission::Allowed
+        );
+        // 第三条超限：等最早一条出窗口
+        assert_eq!(
+            pass(&mut limiter, 1, t0 + Duration::from_secs(20)),
+            Admission::RateLimited {
+                retry_after: Duration::from_secs(40)
+            }
+        );
+        // 其他 chat 不受影响
+        assert_eq!(
+            pass(&mut limiter, 2, t0 + Duration::from_secs(20)),
+            Admission::Allowed
+        );
+        // 第二次违规 → 静音
+        assert_eq!(
+            pass(&mut limiter, 1, t0 + Duration::from_secs(30)),
+            Admission::Muted {
+                remaining: Duration::from_secs(30 * 60),
+                newly: true
+            }
+        );
+        assert!(matches!(
+            pass(&mut limiter, 1, t0 + Duration::from_secs(90)),
+            Admission::Muted { newly: false, .. }
+        ));
+        // 静音结束后恢复
+        assert_eq!(
+            pass(&mut limiter, 1, t0 + Duration::from_secs(31 * 60)),
+            Admission::Allowed
+        );
+    }
+
+    #[test]
+    fn global_limit_applies_across_chats() {
+        let mut limiter = RateLimiter::new(RateLimitConfig {
+            per_chat_per_minute: 0,
+            global_per_minute: 3,
+            ..config()
+        });
+        let t0 = Instant::now();
+        for key in 1..=3 {
+            assert_eq!(pass(&mut limiter, key, t0), Admission::Allowed);
+        }
+        assert!(matches!(
+            pass(&mut limiter, 4, t0 + Duration::from_secs(1)),
+            Admission::RateLimited { .. }
+        ));
+        assert_eq!(
+            pass(&mut limiter, 4, t0 + Duration::from_secs(61)),
+            Admission::Allowed
+        );
+    }
+
+    #[test]
+    fn concurrent_turns_are_capped() {
+        let mut limiter = RateLimiter::new(config());
+        let now = Instant::now();
+        assert_eq!(limiter.admit(&1, now, day(16)), Admission::Allowed);
+        // 同一 chat 上一轮未结束
+        assert_eq!(limiter.admit(&1, now, day(16)), Admission::Busy);
+        assert_eq!(limiter.admit(&2, now, day(16)), Admission::Allowed);
+        // 全局已有 2 轮进行中
+        assert_eq!(limiter.admit(&3, now, day(16)), Admission::Busy);
+        limiter.finish(&1);
+        assert_eq!(limiter.admit(&3, now, day(16)), Admission::Allowed);
+    }
+
+    #[test]
+    fn daily_budget_resets_next_day() {
+        let mut limiter = RateLimiter::new(RateLimitConfig {
+            per_chat_per_minute: 0,
+            global_per_minute: 0,
+            daily_token_budget: Some(1_000),
+            daily_cost_budget: Some(0.5),
+            ..config()
+        });
+        let now = Instant::now();
+        assert_eq!(limiter.admit(&1, now, day(16)), Admission::Allowed);
+        limiter.finish(&1);
+        limiter.record_usage(&1, 1_200, 0.01, day(16));
+        assert_eq!(limiter.admit(&1, now, day(16)), Admission::BudgetExceeded);
+        // 费用预算同样生效
+        limiter.record_usage(&2, 10, 0.6, day(16));
+        assert_eq!(limiter.admit(&2, now, day(16)), Admission::BudgetExceeded);
+        // 次日清零
+        assert_eq!(limiter.admit(&1, now, day(17)), Admission::Allowed);
+        assert_eq!(limiter.usage(&1, day(17)), (0, 0.0));
+    }
+
+    #[test]
+    fn blocked_tally_summarizes_and_resets() {
+        let mut tally = BlockedTally::default();
+        assert_eq!(tally.take(), None);
+        for source in [7, 7, 8, 9, 7] {
+            tally.record(source);
+        }
+        let summary = tally.take().unwrap();
+        assert_eq!(
+            summary,
+            BlockedSummary {
+                messages: 5,
+                sources: 3
+            }
+        );
+        assert!(summary.format().contains("3 个未授权 chat 的 5 条消息"));
+        assert_eq!(tally.take(), None);
+    }
+}
@@ -0,0 +1,173 @@
+//! 工具结构化输出的通用渲染（CLI / Telegram 共用）
+
+use regex::Regex;
+
+/// 单元格最大显示宽度（超出截断）
+const MAX_CELL_WIDTH: usize = 40;
+
+/// 用 unicode 制表符渲染表格
+///
+/// ```text
+/// ┌──────┬──────┐
+/// │ name │ cron │
+/// ├──────┼──────┤
+/// │ a    │ * *  │
+/// └──────┴──────┘
+/// ```
+pub fn render_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let cols = headers
+        .len()
+        .max(rows.iter().map(|r| r.len()).max().unwrap_or(0));
+    if cols == 0 {
+        return String::new();
+    }
+
+    let cell = |row: &[String], i: usize| -> String {
+        let text = row.get(i).map(|s| s.replace('\n', " ")).unwrap_or_default();
+        if text.chars().count() > MAX_CELL_WIDTH {
+            let truncated: String = text.chars().take(MAX_CELL_WIDTH - 1).collect();
+            format!("{}…", truncated)
+        } else {
+            text
+        }
+    };
+
+    let mut widths = vec![0usize; cols];
+    for (i, w) in widths.iter_mut().enumerate() {
+        *w = std::iter::once(cell(headers, i))
+            .chain(rows.iter().map(|r| cell(r, i)))
+            .map(|s| s.chars().count())
+            .max()
+            .unwrap_or(0);
+    }
+
+    let border = |left: &str, mid: &str, right: &str| -> String {
+        let segs: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{}{}{}", left, segs.join(mid), right)
+    };
+    let line = |row: &[String]| -> String {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                let text = cell(row, i);
+                let pad = w - text.chars().count();
+                format!(" {}{} ", text, " ".repeat(pad))
+            })
+            .collect();
+        format!("│{}│", cells.join("│"))
+    };
+
+    let mut out = vec![border("┌", "┬", "┐")];
+    if !headers.is_empty() {
+        out.push(line(headers));
+        out.push(border("├", "┼", "┤"));
+    }
+    out.extend(rows.iter().map(|r| line(r)));
+    out.push(border("└", "┴", "┘"));
+    out.join("\n")
+}
+
+/// 去掉 Markdown 标记，输出纯文本（`rrclaw agent -m ... --format plain`）
+///
+/// - 标题 `## x` → `x`；引用 `> x` → `x`；`* x` 列表 → `- x`
+/// - `**粗体**` / `__粗体__` / `*斜体*` / `` `代码` `` → 去掉标记
+/// - `[文字](url)` → `文字 (url)`
+/// - 代码块去掉 ``` 围栏行，块内内容原样保留
+pub fn strip_markdown(text: &str) -> String {
+    let header = Regex::new(r"^\s{0,3}#{1,6}\s+").expect("valid regex");
+    let quote = Regex::new(r"^\s{0,3}>\s?").expect("valid regex");
+    let bullet = Regex::new(r"^(\s*)[*+]\s+").expect("valid regex");
+    let bold = Regex::new(r"\*\*([^*]+)\*\*|__([^_]+)__").expect("valid regex");
+    let italic = Regex::new(r"\*([^*\s][^*]*)\*").expect("valid regex");
+    let code = Regex::new(r"`([^`]+)`").expect("valid regex");
+    let link = Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").expect("valid regex");
+
+    let mut out = Vec::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            out.push(line.to_string());
+            continue;
+        }
+        let line = header.replace(line, "");
+        let line = quote.replace(&line, "");
+        let line = bullet.replace(&line, "${1}- ");
+        let line = link.replace_all(&line, "$1 ($2)");
+        let line = bold.replace_all(&line, "$1$2");
+        let line = italic.replace_all(&line, "$1");
+        let line = code.replace_all(&line, "$1");
+        out.push(line.into_owned());
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(v: &[&str]) -> Vec<String> {
+        v.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn table_renders_box_drawing() {
+        let out = render_table(&s(&["name", "n"]), &[s(&["a", "10"]), s(&["bb", "2"])]);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "┌──────┬────┐");
+        assert_eq!(lines[1], "│ name │ n  │");
+        assert_eq!(lines[2], "├──────┼────┤");
+        assert_eq!(lines[3], "│ a    │ 10 │");
+        assert_eq!(lines[5], "└──────┴────┘");
+    }
+
+    #[test]
+    fn table_pads_short_rows_and_truncates_long_cells() {
+        let long = "x".repeat(100);
+        let out = render_table(&s(&["a", "b"]), &[vec![long]]);
+        assert!(out.contains('…'));
+        // 每行显示宽度一致
+        let widths: Vec<usize> = out.lines().map(|l| l.chars().count()).collect();
+        assert!(widths.windows(2).all(|w| w[0] == w[1]));
+    }
+
+    #[test]
+    fn empty_table_renders_nothing() {
+        assert!(render_table(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn strip_markdown_removes_headers_and_quotes() {
+        let out = strip_markdown("# 标题\n### Sub *title*\n> 引用\n正文 # 不是标题");
+        assert_eq!(out, "标题\nSub title\n引用\n正文 # 不是标题");
+    }
+
+    #[test]
+    fn strip_markdown_removes_emphasis_code_and_links() {
+        let out =
+            strip_markdown("**注意**：运行 `cargo test`，见 [文档](https://x.dev) 和 __README__");
+        assert_eq!(
+            out,
+            "注意：运行 cargo test，见 文档 (https://x.dev) 和 README"
+        );
+        // 乘法表达式 / snake_case 不受影响
+        assert_eq!(
+            strip_markdown("2 * 3 = 6, my_var_name"),
+            "2 * 3 = 6, my_var_name"
+        );
+        assert_eq!(strip_markdown("* 第一项\n  + 子项"), "- 第一项\n  - 子项");
+    }
+
+    #[test]
+    fn strip_markdown_keeps_code_block_content_verbatim() {
+        let text = "示例：\n```rust\nlet x = **y**; // # 注释\n```\n结束";
+        assert_eq!(
+            strip_markdown(text),
+            "示例：\nlet x = **y**; // # 注释\n结束"
+        );
+    }
+}
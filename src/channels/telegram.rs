@@ -1,134 +1,115 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use color_eyre::eyre::Result;
 use teloxide::prelude::*;
+use teloxide::types::{
+    InputFile, KeyboardButton, KeyboardMarkup, MessageId, MessageReactionUpdated, ParseMode,
+    ReactionType,
+};
 use tokio::sync::Mutex;
 use tracing::{debug, info, warn};
 
-use crate::agent::Agent;
-use crate::config::Config;
-use crate::memory::{Memory, SqliteMemory};
-use crate::providers::{ReliableProvider, RetryConfig};
-use crate::security::SecurityPolicy;
-
-/// Agent 工厂: 为每个 chat 创建独立的 Agent
-pub struct AgentFactory {
-    config: Config,
+use crate::agent::tokens::estimate_tokens;
+use crate::agent::{Agent, AgentFactory, RichToolOutput};
+use crate::channels::rate_limit::{Admission, BlockedTally, RateLimiter};
+use crate::config::{Config, LiveConfig, ToolVerbosity};
+use crate::memory::feedback::TurnSummary;
+use crate::memory::{FeedbackRating, FeedbackStore, Memory, SqliteMemory};
+use crate::tools::ToolOutputKind;
+
+/// 结构化输出内联展示的字节上限，超出改为发送文件
+const MAX_INLINE_OUTPUT: usize = 3500;
+
+/// Telegram Bot 共享状态（Dispatcher 依赖注入）
+struct BotState {
+    config: Arc<LiveConfig>,
+    /// 为每个 chat 创建独立的 Agent（Provider / 身份文件等部件由工厂缓存）
+    factory: AgentFactory,
+    /// 每个 chat 的 Agent 及其创建/刷新时的配置版本
+    agents: Mutex<HashMap<ChatId, (Agent, u64)>>,
     memory: Arc<SqliteMemory>,
+    /// 反馈存储（打开失败时为 None，reaction 反馈不可用）
+    feedback: Option<FeedbackStore>,
+    /// 每个 chat 上一轮回复的消息 ID 与对话摘要（👍/👎 reaction 反馈用）
+    last_turns: Mutex<HashMap<ChatId, (Vec<MessageId>, TurnSummary)>>,
+    /// 限流 / 静音 / 每日预算（`[telegram.rate_limit]`）
+    limiter: std::sync::Mutex<RateLimiter<ChatId>>,
+    /// 未授权 chat 的消息计数，每天 0 点汇总发给管理员
+    blocked: std::sync::Mutex<BlockedTally<ChatId>>,
 }
 
-impl AgentFactory {
-    pub fn new(config: Config, memory: Arc<SqliteMemory>) -> Self {
-        Self { config, memory }
+impl BotState {
+    /// allowlist 每次读取当前配置，`rrclaw reload` 后立即生效
+    fn is_allowed(&self, chat_id: ChatId) -> bool {
+        let config = self.config.snapshot();
+        let allowed_ids = config
+            .telegram
+            .as_ref()
+            .map(|tg| tg.allowed_chat_ids.as_slice())
+            .unwrap_or_default();
+        allowed_ids.is_empty() || allowed_ids.contains(&chat_id.0)
     }
 
-    /// 为指定 chat 创建一个 Agent
-    fn create_agent(&self) -> Result<Agent> {
-        let provider_key = &self.config.default.provider;
-        let provider_config = self
-            .config
-            .providers
-            .get(provider_key)
-            .ok_or_else(|| color_eyre::eyre::eyre!("Provider '{}' 未配置", provider_key))?;
-
-        let raw_provider = crate::providers::create_provider(provider_config);
-        let fallback_providers: Vec<Box<dyn crate::providers::Provider>> = self
-            .config
-            .reliability
-            .fallback_providers
-            .iter()
-            .filter_map(|name| self.config.providers.get(name))
-            .map(|pc| crate::providers::create_provider(pc))
-            .collect();
-        let retry_config = RetryConfig {
-            max_retries: self.config.reliability.max_retries,
-            initial_backoff_ms: self.config.reliability.initial_backoff_ms,
-            ..Default::default()
-        };
+    /// 接收每日拦截汇总的 chat：`admin_chat_id`，否则 allowlist 第一个
+    fn admin_chat(&self) -> Option<ChatId> {
+        let config = self.config.snapshot();
+        let tg = config.telegram.as_ref()?;
+        tg.admin_chat_id
+            .or_else(|| tg.allowed_chat_ids.first().copied())
+            .map(ChatId)
+    }
 
-        // Arc<dyn Provider> 用于 HttpRequestTool 的 mini-LLM 提取
-        let raw_provider_for_arc = crate::providers::create_provider(provider_config);
-        let provider_arc: Arc<dyn crate::providers::Provider> = if fallback_providers.is_empty() {
-            Arc::new(ReliableProvider::new(
-                raw_provider_for_arc,
-                retry_config.clone(),
-            ))
-        } else {
-            let fallback_providers_arc: Vec<Box<dyn crate::providers::Provider>> = self
-                .config
-                .reliability
-                .fallback_providers
-                .iter()
-                .filter_map(|name| self.config.providers.get(name))
-                .map(|pc| crate::providers::create_provider(pc))
-                .collect();
-            Arc::new(ReliableProvider::with_fallbacks(
-                raw_provider_for_arc,
-                fallback_providers_arc,
-                retry_config.clone(),
-            ))
-        };
+    fn limiter(&self) -> std::sync::MutexGuard<'_, RateLimiter<ChatId>> {
+        self.limiter.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
-        // Box<dyn Provider> 用于 Agent
-        let provider: Box<dyn crate::providers::Provider> = if fallback_providers.is_empty() {
-            Box::new(ReliableProvider::new(raw_provider, retry_config))
-        } else {
-            Box::new(ReliableProvider::with_fallbacks(
-                raw_provider,
-                fallback_providers,
-                retry_config,
-            ))
-        };
+    /// 按当前配置判定消息能否处理（限流配置每次读取，`rrclaw reload` 后立即生效）
+    fn admit(&self, chat_id: ChatId) -> Admission {
+        let rate_limit = self
+            .config
+            .snapshot()
+            .telegram
+            .as_ref()
+            .map(|tg| tg.rate_limit.clone())
+            .unwrap_or_default();
+        let mut limiter = self.limiter();
+        limiter.set_config(rate_limit);
+        limiter.admit(&chat_id, Instant::now(), chrono::Local::now().date_naive())
+    }
 
-        let (data_dir, log_dir) = {
-            let base_dirs = directories::BaseDirs::new()
-                .ok_or_else(|| color_eyre::eyre::eyre!("无法获取 home 目录"))?;
-            let rrclaw = base_dirs.home_dir().join(".rrclaw");
-            (rrclaw.join("data"), rrclaw.join("logs"))
-        };
-        let config_path = crate::config::Config::config_path()?;
-        let tools = crate::tools::create_tools(
-            self.config.clone(),
-            provider_arc,
-            data_dir.clone(),
-            log_dir,
-            config_path,
-            vec![], // Telegram 暂不加载 skills
-            self.memory.clone() as Arc<dyn Memory>,
-            None, // Telegram channel 暂不集成 RoutineTool
+    /// 计入本轮估算用量（费用按 `[pricing]`，未知价格的模型只计 tokens）
+    fn record_usage(
+        &self,
+        chat_id: ChatId,
+        model: &str,
+        input_tokens: usize,
+        output_tokens: usize,
+    ) {
+        let cost = self
+            .config
+            .snapshot()
+            .pricing_for(model)
+            .map(|pricing| pricing.cost(input_tokens, output_tokens))
+            .unwrap_or(0.0);
+        self.limiter().record_usage(
+            &chat_id,
+            input_tokens + output_tokens,
+            cost,
+            chrono::Local::now().date_naive(),
         );
-        let policy = SecurityPolicy {
-            autonomy: self.config.security.autonomy.clone(),
-            allowed_commands: self.config.security.allowed_commands.clone(),
-            workspace_dir: std::env::current_dir()
-                .unwrap_or_else(|_| std::path::PathBuf::from(".")),
-            blocked_paths: SecurityPolicy::default().blocked_paths,
-            http_allowed_hosts: self.config.security.http_allowed_hosts.clone(),
-            injection_check: self.config.security.injection_check,
-        };
-
-        Ok(Agent::new(
-            provider,
-            tools,
-            Box::new(self.memory.clone()),
-            policy.clone(),
-            provider_key.to_string(),
-            provider_config.base_url.clone(),
-            self.config.default.model.clone(),
-            self.config.default.temperature,
-            vec![], // Telegram 暂不加载 skills
-            // identity 文件在 ~/.rrclaw/，data_dir 是 ~/.rrclaw/data/，取父目录
-            crate::agent::identity::load_identity_context(
-                &policy.workspace_dir,
-                data_dir.parent().unwrap_or(data_dir.as_path()),
-            ),
-        ))
     }
 }
 
 /// 运行 Telegram Bot
 pub async fn run_telegram(config: Config, memory: Arc<SqliteMemory>) -> Result<()> {
+    run_telegram_live(Arc::new(LiveConfig::new(config)), memory).await
+}
+
+/// 运行 Telegram Bot，配置可被 daemon 热更新（bot_token 除外）
+pub async fn run_telegram_live(live: Arc<LiveConfig>, memory: Arc<SqliteMemory>) -> Result<()> {
+    let config = live.snapshot();
     let telegram_config = config.telegram.as_ref().ok_or_else(|| {
         color_eyre::eyre::eyre!("Telegram 未配置。请在 config.toml 中添加 [telegram] 配置。")
     })?;
@@ -139,77 +120,369 @@ pub async fn run_telegram(config: Config, memory: Arc<SqliteMemory>) -> Result<(
         .ok_or_else(|| color_eyre::eyre::eyre!("Telegram bot_token 未配置"))?;
 
     let bot = Bot::new(bot_token);
-    let allowed_ids: Vec<i64> = telegram_config.allowed_chat_ids.clone();
 
-    let factory = Arc::new(AgentFactory::new(config, memory));
-    let agents: Arc<Mutex<HashMap<ChatId, Agent>>> = Arc::new(Mutex::new(HashMap::new()));
+    let feedback_store = crate::config::RrclawPaths::resolve()
+        .and_then(|paths| FeedbackStore::open(&paths.data_dir()));
+    let feedback = match feedback_store {
+        Ok(store) => Some(store),
+        Err(e) => {
+            warn!("打开反馈数据库失败，reaction 反馈不可用: {:#}", e);
+            None
+        }
+    };
+
+    let state = Arc::new(BotState {
+        config: live.clone(),
+        factory: AgentFactory::new(
+            live,
+            memory.clone() as Arc<dyn Memory>,
+            crate::config::RrclawPaths::resolve()?,
+        )
+        .with_identity(),
+        agents: Mutex::new(HashMap::new()),
+        memory,
+        feedback,
+        last_turns: Mutex::new(HashMap::new()),
+        limiter: std::sync::Mutex::new(RateLimiter::new(telegram_config.rate_limit.clone())),
+        blocked: std::sync::Mutex::new(BlockedTally::default()),
+    });
 
     info!("Telegram Bot 启动中...");
+    tokio::spawn(report_blocked_daily(bot.clone(), state.clone()));
+
+    // reaction 更新需要显式订阅，Dispatcher 会根据 handler 自动设置 allowed_updates
+    let handler = dptree::entry()
+        .branch(Update::filter_message().endpoint(handle_message))
+        .branch(Update::filter_message_reaction_updated().endpoint(handle_reaction));
+
+    Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![state])
+        .build()
+        .dispatch()
+        .await;
+
+    Ok(())
+}
+
+/// 处理普通文本消息
+async fn handle_message(bot: Bot, msg: Message, state: Arc<BotState>) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+
+    // 检查访问权限
+    if !state.is_allowed(chat_id) {
+        debug!("拒绝未授权 chat: {}", chat_id);
+        state
+            .blocked
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(chat_id);
+        bot.send_message(chat_id, "⛔ 未授权的 Chat ID").await?;
+        return Ok(());
+    }
 
-    teloxide::repl(bot, move |bot: Bot, msg: Message| {
-        let factory = factory.clone();
-        let agents = agents.clone();
-        let allowed_ids = allowed_ids.clone();
+    let text = match msg.text() {
+        Some(t) if !t.is_empty() => t.to_string(),
+        _ => return Ok(()),
+    };
 
-        async move {
-            let chat_id = msg.chat.id;
+    info!("收到消息 [chat={}]: {}", chat_id, text);
 
-            // 检查访问权限
-            if !allowed_ids.is_empty() && !allowed_ids.contains(&chat_id.0) {
-                debug!("拒绝未授权 chat: {}", chat_id);
-                bot.send_message(chat_id, "⛔ 未授权的 Chat ID").await?;
+    // 限流 / 静音 / 每日预算
+    let admission = state.admit(chat_id);
+    if admission != Admission::Allowed {
+        info!("消息未处理 [chat={}]: {:?}", chat_id, admission);
+        if let Some(notice) = admission_notice(&admission) {
+            bot.send_message(chat_id, notice).await?;
+        }
+        return Ok(());
+    }
+
+    let result = run_turn(&bot, chat_id, &text, &state).await;
+    state.limiter().finish(&chat_id);
+    result
+}
+
+/// 限流结果对应的回复（静音期间除首次外不回复）
+fn admission_notice(admission: &Admission) -> Option<String> {
+    match admission {
+        Admission::Allowed | Admission::Muted { newly: false, .. } => None,
+        Admission::RateLimited { retry_after } => Some(format!(
+            "⏳ 消息太频繁，请稍后再试（{} 秒后）",
+            retry_after.as_secs().max(1)
+        )),
+        Admission::Muted {
+            remaining,
+            newly: true,
+        } => Some(format!(
+            "🔇 消息过于频繁，已暂停响应 {} 分钟",
+            remaining.as_secs().div_ceil(60)
+        )),
+        Admission::BudgetExceeded => Some("💸 今日额度已用完，请明天（0 点后）再试".to_string()),
+        Admission::Busy => Some("⏳ 正在处理其他消息，请稍后再试".to_string()),
+    }
+}
+
+/// 处理一条已放行的消息
+async fn run_turn(bot: &Bot, chat_id: ChatId, text: &str, state: &BotState) -> ResponseResult<()> {
+    // 获取或创建该 chat 的 Agent
+    let generation = state.config.generation();
+    let mut agents_map = state.agents.lock().await;
+    if let std::collections::hash_map::Entry::Vacant(e) = agents_map.entry(chat_id) {
+        match state.factory.create_agent() {
+            Ok(mut agent) => {
+                agent.set_track_changes(state.config.snapshot().cli.show_changes);
+                agent.set_quick_replies(true);
+                e.insert((agent, generation));
+                crate::metrics::set_telegram_chats(agents_map.len());
+            }
+            Err(err) => {
+                warn!("创建 Agent 失败: {:#}", err);
+                bot.send_message(chat_id, format!("Agent 创建失败: {}", err))
+                    .await?;
                 return Ok(());
             }
+        }
+    }
 
-            let text = match msg.text() {
-                Some(t) if !t.is_empty() => t.to_string(),
-                _ => return Ok(()),
-            };
-
-            info!("收到消息 [chat={}]: {}", chat_id, text);
-
-            // 获取或创建该 chat 的 Agent
-            let mut agents_map = agents.lock().await;
-            if let std::collections::hash_map::Entry::Vacant(e) = agents_map.entry(chat_id) {
-                match factory.create_agent() {
-                    Ok(agent) => {
-                        e.insert(agent);
-                    }
-                    Err(err) => {
-                        warn!("创建 Agent 失败: {:#}", err);
-                        bot.send_message(chat_id, format!("Agent 创建失败: {}", err))
-                            .await?;
-                        return Ok(());
-                    }
+    let (agent, agent_generation) = agents_map.get_mut(&chat_id).unwrap();
+    // 配置已重新加载：刷新安全策略和身份文件，保留对话历史
+    if *agent_generation != generation {
+        state.factory.refresh_agent(agent);
+        *agent_generation = generation;
+    }
+
+    // 处理消息（用量为估算：请求前的上下文 + 本条消息 + 回复）
+    let input_tokens = agent.estimate_request_tokens() + estimate_tokens(text);
+    let result = agent.process_message(text).await;
+    let output_tokens = result.as_ref().map_or(0, |reply| estimate_tokens(reply));
+    state.record_usage(chat_id, agent.model(), input_tokens, output_tokens);
+    match result {
+        Ok(mut reply) => {
+            // 工具结果的用户视图（`[telegram] tool_verbosity`，默认 quiet 不发送）
+            let verbosity = state
+                .config
+                .snapshot()
+                .telegram
+                .as_ref()
+                .map(|tg| tg.tool_verbosity)
+                .unwrap_or(ToolVerbosity::Quiet);
+            for feedback in agent.take_tool_feedback() {
+                let Some(text) = verbosity.select(feedback.summary.as_deref(), &feedback.content)
+                else {
+                    continue;
+                };
+                let message = format!("🔧 {}\n{}", feedback.tool, text);
+                for chunk in split_message(&message, 4000) {
+                    bot.send_message(chat_id, chunk).await?;
                 }
             }
-
-            let agent = agents_map.get_mut(&chat_id).unwrap();
-
-            // 处理消息
-            match agent.process_message(&text).await {
-                Ok(reply) => {
-                    if !reply.is_empty() {
-                        // 分段发送（Telegram 消息限制 4096 字符）
-                        for chunk in split_message(&reply, 4000) {
-                            bot.send_message(chat_id, chunk).await?;
-                        }
-                    }
+            // 本轮文件变更作为回复末尾的 footer
+            if let Some(changes) = agent.take_change_summary() {
+                if !reply.is_empty() {
+                    reply.push_str("\n\n");
+                }
+                reply.push_str(&changes.format_line());
+            }
+            let mut sent_ids = Vec::new();
+            if !reply.is_empty() {
+                // 分段发送（Telegram 消息限制 4096 字符）；后续建议作为回复键盘挂在最后一段上
+                let chunks = split_message(&reply, 4000);
+                let last = chunks.len().saturating_sub(1);
+                for (i, chunk) in chunks.into_iter().enumerate() {
+                    let request = bot.send_message(chat_id, chunk);
+                    let sent = match quick_reply_keyboard(agent.last_suggestions()) {
+                        Some(keyboard) if i == last => request.reply_markup(keyboard).await?,
+                        _ => request.await?,
+                    };
+                    sent_ids.push(sent.id);
                 }
-                Err(e) => {
-                    warn!("处理消息失败 [chat={}]: {:#}", chat_id, e);
-                    bot.send_message(chat_id, format!("❌ 错误: {}", e)).await?;
+            }
+            // 工具结构化输出：短内容用代码块，长内容作为文件发送
+            for output in agent.take_rich_outputs() {
+                if let Err(e) = send_rich_output(bot, chat_id, &output).await {
+                    warn!("发送工具输出失败 [chat={}]: {}", chat_id, e);
                 }
             }
+            // 记录本轮回复的消息，供后续 👍/👎 reaction 反馈定位
+            if let Some(turn) =
+                crate::memory::feedback::summarize_last_turn(agent.history(), agent.routed_skills())
+            {
+                state
+                    .last_turns
+                    .lock()
+                    .await
+                    .insert(chat_id, (sent_ids, turn));
+            }
+        }
+        Err(e) => {
+            warn!("处理消息失败 [chat={}]: {:#}", chat_id, e);
+            bot.send_message(chat_id, format!("❌ 错误: {}", e)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 后续建议的回复键盘：每条建议一行，点击即作为下一条消息发送，用后收起
+fn quick_reply_keyboard(suggestions: &[String]) -> Option<KeyboardMarkup> {
+    if suggestions.is_empty() {
+        return None;
+    }
+    let rows = suggestions
+        .iter()
+        .map(|s| vec![KeyboardButton::new(s.clone())]);
+    Some(
+        KeyboardMarkup::new(rows)
+            .resize_keyboard()
+            .one_time_keyboard(),
+    )
+}
 
-            Ok(())
+/// 每天 0 点把前一天未授权 chat 的消息汇总发给管理员 chat
+async fn report_blocked_daily(bot: Bot, state: Arc<BotState>) {
+    loop {
+        tokio::time::sleep(until_next_midnight()).await;
+        let summary = state
+            .blocked
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let Some(summary) = summary else {
+            continue;
+        };
+        info!("{}", summary.format());
+        let Some(admin) = state.admin_chat() else {
+            continue;
+        };
+        if let Err(e) = bot.send_message(admin, summary.format()).await {
+            warn!("发送拦截汇总失败 [chat={}]: {}", admin, e);
+        }
+    }
+}
+
+/// 距本地时间下一个 0 点的时长
+fn until_next_midnight() -> Duration {
+    let now = chrono::Local::now().naive_local();
+    now.date()
+        .succ_opt()
+        .and_then(|day| day.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| (midnight - now).to_std().ok())
+        .unwrap_or(Duration::from_secs(3600))
+}
+
+/// 处理 👍/👎 reaction：只对上一轮回复的消息生效
+async fn handle_reaction(
+    reaction: MessageReactionUpdated,
+    state: Arc<BotState>,
+) -> ResponseResult<()> {
+    let chat_id = reaction.chat.id;
+    if !state.is_allowed(chat_id) {
+        return Ok(());
+    }
+    let Some(rating) = added_reaction_rating(&reaction.old_reaction, &reaction.new_reaction) else {
+        return Ok(());
+    };
+    let Some(store) = &state.feedback else {
+        return Ok(());
+    };
+
+    let turn = {
+        let last_turns = state.last_turns.lock().await;
+        match last_turns.get(&chat_id) {
+            Some((ids, turn)) if ids.contains(&reaction.message_id) => turn.clone(),
+            _ => {
+                debug!("reaction 不在上一轮回复上，忽略 [chat={}]", chat_id);
+                return Ok(());
+            }
         }
-    })
-    .await;
+    };
+
+    if let Err(e) = crate::memory::feedback::record_feedback(
+        store,
+        state.memory.as_ref(),
+        "telegram",
+        rating,
+        turn,
+        None,
+    )
+    .await
+    {
+        warn!("记录反馈失败 [chat={}]: {:#}", chat_id, e);
+    } else {
+        info!("记录反馈 [chat={}]: {}", chat_id, rating.as_str());
+    }
+    Ok(())
+}
 
+/// 本次新增的 👍/👎 reaction（已存在的 reaction 不重复记录）
+fn added_reaction_rating(old: &[ReactionType], new: &[ReactionType]) -> Option<FeedbackRating> {
+    new.iter()
+        .filter(|r| !old.contains(r))
+        .filter_map(|r| r.emoji())
+        .find_map(|emoji| FeedbackRating::from_reaction(emoji))
+}
+
+/// 发送一条工具结构化输出
+async fn send_rich_output(
+    bot: &Bot,
+    chat_id: ChatId,
+    output: &RichToolOutput,
+) -> ResponseResult<()> {
+    let Some((body, lang, file_name)) = rich_output_body(&output.kind, &output.content) else {
+        return Ok(());
+    };
+    if body.len() > MAX_INLINE_OUTPUT {
+        bot.send_document(
+            chat_id,
+            InputFile::memory(body.into_bytes()).file_name(file_name),
+        )
+        .await?;
+    } else {
+        bot.send_message(chat_id, code_block_html(&body, lang))
+            .parse_mode(ParseMode::Html)
+            .await?;
+    }
     Ok(())
 }
 
+/// 结构化输出 → (展示文本, 代码语言, 文件名)；不需要额外展示时返回 None
+fn rich_output_body(
+    kind: &ToolOutputKind,
+    content: &str,
+) -> Option<(String, &'static str, &'static str)> {
+    match kind {
+        ToolOutputKind::Diff => Some((content.to_string(), "diff", "output.diff")),
+        ToolOutputKind::Json { value } => Some((
+            serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string()),
+            "json",
+            "output.json",
+        )),
+        ToolOutputKind::Table { headers, rows } => Some((
+            crate::channels::render::render_table(headers, rows),
+            "",
+            "output.txt",
+        )),
+        // 文件引用：回复文本已说明写入位置，无需额外发送
+        ToolOutputKind::FileRef { .. } => None,
+    }
+}
+
+/// 构造 HTML 代码块（Telegram ParseMode::Html）
+fn code_block_html(body: &str, lang: &str) -> String {
+    let escaped = body
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    if lang.is_empty() {
+        format!("<pre>{}</pre>", escaped)
+    } else {
+        format!(
+            "<pre><code class=\"language-{}\">{}</code></pre>",
+            lang, escaped
+        )
+    }
+}
+
 /// 将长消息分段（Telegram 限制 4096 字符）
 fn split_message(text: &str, max_len: usize) -> Vec<&str> {
     if text.len() <= max_len {
@@ -236,6 +509,20 @@ fn split_message(text: &str, max_len: usize) -> Vec<&str> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn quick_reply_keyboard_puts_one_suggestion_per_row() {
+        assert!(quick_reply_keyboard(&[]).is_none());
+        let keyboard =
+            quick_reply_keyboard(&["查看 diff".to_string(), "提交改动".to_string()]).unwrap();
+        let rows: Vec<Vec<&str>> = keyboard
+            .keyboard
+            .iter()
+            .map(|row| row.iter().map(|b| b.text.as_str()).collect())
+            .collect();
+        assert_eq!(rows, vec![vec!["查看 diff"], vec!["提交改动"]]);
+        assert!(keyboard.resize_keyboard && keyboard.one_time_keyboard);
+    }
+
     #[test]
     fn split_short_message() {
         let chunks = split_message("hello", 4000);
@@ -263,4 +550,86 @@ mod tests {
             let _ = chunk.to_string(); // 不应 panic
         }
     }
+
+    #[test]
+    fn rich_output_diff_uses_diff_block() {
+        let (body, lang, _) = rich_output_body(&ToolOutputKind::Diff, "+a\n-b").unwrap();
+        assert_eq!(body, "+a\n-b");
+        assert_eq!(lang, "diff");
+    }
+
+    #[test]
+    fn rich_output_file_ref_is_skipped() {
+        let kind = ToolOutputKind::FileRef {
+            path: "/tmp/a".to_string(),
+            bytes: 1,
+        };
+        assert!(rich_output_body(&kind, "Wrote 1 bytes").is_none());
+    }
+
+    #[test]
+    fn code_block_escapes_html() {
+        let html = code_block_html("<b>&</b>", "json");
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-json\">&lt;b&gt;&amp;&lt;/b&gt;</code></pre>"
+        );
+        assert_eq!(code_block_html("x", ""), "<pre>x</pre>");
+    }
+
+    fn emoji(e: &str) -> ReactionType {
+        ReactionType::Emoji {
+            emoji: e.to_string(),
+        }
+    }
+
+    #[test]
+    fn reaction_added_thumbs_maps_to_rating() {
+        assert_eq!(
+            added_reaction_rating(&[], &[emoji("👍")]),
+            Some(FeedbackRating::Good)
+        );
+        assert_eq!(
+            added_reaction_rating(&[emoji("👍")], &[emoji("👍"), emoji("👎")]),
+            Some(FeedbackRating::Bad)
+        );
+    }
+
+    #[test]
+    fn admission_notices() {
+        assert_eq!(admission_notice(&Admission::Allowed), None);
+        assert_eq!(
+            admission_notice(&Admission::RateLimited {
+                retry_after: Duration::from_millis(12_400)
+            })
+            .unwrap(),
+            "⏳ 消息太频繁，请稍后再试（12 秒后）"
+        );
+        assert_eq!(
+            admission_notice(&Admission::Muted {
+                remaining: Duration::from_secs(30 * 60),
+                newly: true
+            })
+            .unwrap(),
+            "🔇 消息过于频繁，已暂停响应 30 分钟"
+        );
+        // 静音期间后续消息不再回复
+        assert_eq!(
+            admission_notice(&Admission::Muted {
+                remaining: Duration::from_secs(60),
+                newly: false
+            }),
+            None
+        );
+        assert!(admission_notice(&Admission::BudgetExceeded)
+            .unwrap()
+            .contains("今日额度已用完"));
+    }
+
+    #[test]
+    fn reaction_removed_or_other_emoji_ignored() {
+        assert_eq!(added_reaction_rating(&[emoji("👍")], &[]), None);
+        assert_eq!(added_reaction_rating(&[emoji("👍")], &[emoji("👍")]), None);
+        assert_eq!(added_reaction_rating(&[], &[emoji("🔥")]), None);
+    }
 }
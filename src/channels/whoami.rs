@@ -0,0 +1,307 @@
+//! `/whoami`：一次性汇总当前生效的身份与配置
+//!
+//! 多套配置 / 多个 workspace 之间切换时，用户需要快速确认"现在是谁在用什么跑"：
+//! Provider / 模型 / 安全模式 / workspace 取自运行中的 Agent（含 `/switch`、`/mode` 的运行时修改），
+//! 配置文件与数据目录路径同 `self_info` 工具，身份文件、Skills、Routines、记忆条数、Telegram
+//! 状态分别来自各自的模块。
+
+use std::path::{Path, PathBuf};
+
+use crate::agent::identity::loaded_identity_files;
+use crate::agent::Agent;
+use crate::i18n::Language;
+use crate::memory::Memory;
+use crate::routines::RoutineEngine;
+use crate::security::AutonomyLevel;
+use crate::skills::SkillMeta;
+
+/// Telegram 通道状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelegramStatus {
+    /// 配置中没有 `[telegram]`
+    NotConfigured,
+    /// 已配置但 Bot 未运行（`/telegram start` 启动）
+    Stopped,
+    Running,
+}
+
+/// `/whoami` 汇总结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Whoami {
+    pub provider: String,
+    pub model: String,
+    pub autonomy: AutonomyLevel,
+    pub read_only: bool,
+    pub safe_mode: bool,
+    pub workspace: PathBuf,
+    pub data_dir: PathBuf,
+    /// 配置文件路径（无法确定时为 None）
+    pub config_path: Option<PathBuf>,
+    /// 实际注入 system prompt 的身份文件
+    pub identity_files: Vec<PathBuf>,
+    pub skills: usize,
+    /// `(总数, 已启用)`；Routine 引擎未启动时为 None
+    pub routines: Option<(usize, usize)>,
+    /// 记忆条数；查询失败时为 None
+    pub memory_entries: Option<usize>,
+    /// 记忆库是否降级（本次会话不持久化）
+    pub memory_degraded: bool,
+    pub telegram: TelegramStatus,
+}
+
+/// 汇总 Agent、配置、Routine 引擎与记忆库的当前状态
+pub async fn collect(
+    agent: &Agent,
+    data_dir: &Path,
+    config_path: Option<PathBuf>,
+    skills: &[SkillMeta],
+    routine_engine: Option<&RoutineEngine>,
+    memory: &dyn Memory,
+    telegram: TelegramStatus,
+) -> Whoami {
+    let policy = agent.policy();
+    let routines = routine_engine.map(|engine| {
+        let routines = engine.list_routines();
+        let enabled = routines.iter().filter(|r| r.enabled).count();
+        (routines.len(), enabled)
+    });
+    Whoami {
+        provider: agent.provider_name().to_string(),
+        model: agent.model().to_string(),
+        autonomy: policy.autonomy.clone(),
+        read_only: agent.is_read_only(),
+        safe_mode: agent.is_safe_mode(),
+        workspace: policy.workspace_dir.clone(),
+        data_dir: data_dir.to_path_buf(),
+        config_path,
+        identity_files: loaded_identity_files(&policy.workspace_dir, data_dir),
+        skills: skills.len(),
+        routines,
+        memory_entries: memory.count().await.ok(),
+        memory_degraded: crate::memory::is_degraded(),
+        telegram,
+    }
+}
+
+impl Whoami {
+    /// 渲染为多行文本（标签按界面语言）
+    pub fn render(&self, lang: Language) -> String {
+        let en = lang.is_english();
+        let label = |zh: &'static str, en_label: &'static str| if en { en_label } else { zh };
+        let mut lines = Vec::new();
+        let mut row = |name: &str, value: String| lines.push(format!("  {:<12} {}", name, value));
+
+        row(
+            label("Provider", "Provider"),
+            format!("{} / {}", self.provider, self.model),
+        );
+        let mut mode = format!("{:?}", self.autonomy);
+        if self.read_only {
+            mode.push_str(label("（只读）", " (read-only)"));
+        }
+        if self.safe_mode {
+            mode.push_str(label("（安全模式）", " (safe mode)"));
+        }
+        row(label("安全模式", "Mode"), mode);
+        row(
+            label("工作目录", "Workspace"),
+            self.workspace.display().to_string(),
+        );
+        row(
+            label("配置文件", "Config"),
+            self.config_path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| label("（未知）", "(unknown)").to_string()),
+        );
+        row(
+            label("数据目录", "Data dir"),
+            self.data_dir.display().to_string(),
+        );
+        let identity = if self.identity_files.is_empty() {
+            label("无", "none").to_string()
+        } else {
+            self.identity_files
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        row(label("身份文件", "Identity"), identity);
+        row("Skills", self.skills.to_string());
+        let routines = match self.routines {
+            Some((total, enabled)) if en => format!("{} ({} enabled)", total, enabled),
+            Some((total, enabled)) => format!("{}（{} 个启用）", total, enabled),
+            None => label("未启动", "not running").to_string(),
+        };
+        row("Routines", routines);
+        let mut memory = match self.memory_entries {
+            Some(n) if en => format!("{} entries", n),
+            Some(n) => format!("{} 条", n),
+            None => label("不可用", "unavailable").to_string(),
+        };
+        if self.memory_degraded {
+            memory.push_str(label(
+                "（已降级，本次会话不持久化）",
+                " (degraded — this session is not being saved)",
+            ));
+        }
+        row(label("记忆", "Memory"), memory);
+        let telegram = match self.telegram {
+            TelegramStatus::NotConfigured => label("未配置", "not configured"),
+            TelegramStatus::Stopped => label("已配置，未运行", "configured, stopped"),
+            TelegramStatus::Running => label("运行中", "running"),
+        };
+        row("Telegram", telegram.to_string());
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ProviderConfig};
+    use crate::memory::{MemoryCategory, SqliteMemory};
+    use crate::routines::{Routine, RoutineSource};
+    use crate::skills::SkillSource;
+    use std::sync::Arc;
+
+    fn echo_agent(workspace: &Path) -> Agent {
+        let provider = crate::providers::echo::EchoProvider::new(&ProviderConfig {
+            base_url: "echo://".to_string(),
+            api_key: String::new(),
+            model: "echo-model".to_string(),
+            auth_style: Some("echo".to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        });
+        let policy = crate::security::SecurityPolicy {
+            autonomy: AutonomyLevel::Full,
+            workspace_dir: workspace.to_path_buf(),
+            ..Default::default()
+        };
+        Agent::new(
+            Box::new(provider),
+            vec![],
+            Box::new(crate::memory::NoopMemory),
+            policy,
+            "echo".to_string(),
+            "echo://".to_string(),
+            "echo-model".to_string(),
+            0.0,
+            vec![],
+            None,
+        )
+    }
+
+    fn routine(name: &str, enabled: bool) -> Routine {
+        Routine {
+            name: name.to_string(),
+            schedule: "0 8 * * *".to_string(),
+            message: "ping".to_string(),
+            channel: "cli".to_string(),
+            enabled,
+            source: RoutineSource::Config,
+            catch_up: false,
+            chain: vec![],
+            condition: None,
+            channel_target: None,
+        }
+    }
+
+    fn skill(name: &str) -> SkillMeta {
+        SkillMeta {
+            name: name.to_string(),
+            description: String::new(),
+            tags: vec![],
+            source: SkillSource::BuiltIn,
+            path: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn collects_fields_from_agent_engine_and_memory() {
+        let workspace = tempfile::tempdir().unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        std::fs::write(data_dir.path().join("USER.md"), "偏好中文").unwrap();
+
+        let engine = RoutineEngine::new(
+            vec![routine("daily", true), routine("weekly", false)],
+            Arc::new(Config::default()),
+            Arc::new(crate::memory::NoopMemory),
+            &data_dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap();
+        let memory = SqliteMemory::open(data_dir.path()).unwrap();
+        memory
+            .store("lang", "用户偏好中文", MemoryCategory::Core)
+            .await
+            .unwrap();
+        memory
+            .store("editor", "helix", MemoryCategory::Core)
+            .await
+            .unwrap();
+
+        let agent = echo_agent(workspace.path());
+        let info = collect(
+            &agent,
+            data_dir.path(),
+            Some(data_dir.path().join("config.toml")),
+            &[skill("code-review"), skill("rust-dev")],
+            Some(&engine),
+            &memory,
+            TelegramStatus::Stopped,
+        )
+        .await;
+
+        assert_eq!(info.provider, "echo");
+        assert_eq!(info.model, "echo-model");
+        assert_eq!(info.autonomy, AutonomyLevel::Full);
+        assert!(!info.read_only && !info.safe_mode);
+        assert_eq!(info.workspace, workspace.path());
+        assert_eq!(info.identity_files, vec![data_dir.path().join("USER.md")]);
+        assert_eq!(info.skills, 2);
+        assert_eq!(info.routines, Some((2, 1)));
+        assert_eq!(info.memory_entries, Some(2));
+        assert_eq!(info.telegram, TelegramStatus::Stopped);
+
+        let text = info.render(Language::English);
+        assert!(text.contains("echo / echo-model"));
+        assert!(text.contains("Full"));
+        assert!(text.contains("2 (1 enabled)"));
+        assert!(text.contains("2 entries"));
+        assert!(text.contains("configured, stopped"));
+    }
+
+    #[tokio::test]
+    async fn missing_engine_and_identity_render_placeholders() {
+        let workspace = tempfile::tempdir().unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        let agent = echo_agent(workspace.path());
+        let info = collect(
+            &agent,
+            data_dir.path(),
+            None,
+            &[],
+            None,
+            &crate::memory::NoopMemory,
+            TelegramStatus::NotConfigured,
+        )
+        .await;
+
+        assert!(info.identity_files.is_empty());
+        assert_eq!(info.routines, None);
+        let text = info.render(Language::Chinese);
+        assert!(text
+            .lines()
+            .any(|l| l.trim_start().starts_with("身份文件") && l.ends_with(" 无")));
+        assert!(text.contains("未启动"));
+        assert!(text.contains("未配置"));
+    }
+}
@@ -0,0 +1,674 @@
+//! 完整示例配置（`rrclaw config --example`）
+//!
+//! 以各配置结构的 `Default` 为基础，为默认关闭的可选段（Provider、Telegram、MCP、Routine 等）
+//! 填入示例值，经 serde 序列化后按 `KNOWN_KEYS` 的顺序输出 TOML，并为每个键附上 `FIELD_DOCS`
+//! 中的说明。测试保证示例覆盖全部已知键、每个键都有说明且能解析回 `Config`。
+
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use super::schema::{
+    AuxModelConfig, Config, McpConfig, McpServerConfig, McpTransport, ModelPricing, ProviderConfig,
+    RoutineJobConfig, SearchBackendKind, SearchConfig, TelegramConfig,
+};
+use super::validate::{KNOWN_KEYS, NAMED_SECTIONS};
+use crate::routines::RoutineCondition;
+
+/// 配置项说明；key 为 `KNOWN_KEYS` 中的段名 + 键名（自定义名称段用 `*`）
+const FIELD_DOCS: &[(&str, &str)] = &[
+    ("default", "默认使用的 Provider 与模型"),
+    ("default.provider", "Provider 名（对应 [providers.<名称>]）"),
+    ("default.model", "模型名"),
+    ("default.temperature", "采样温度"),
+    (
+        "default.language",
+        "界面语言：en / zh（不影响模型回复语言）",
+    ),
+    ("providers", "Provider 连接配置，名称自定义"),
+    (
+        "providers.*.base_url",
+        "API 地址（echo:// 为离线回显 Provider）",
+    ),
+    ("providers.*.api_key", "API Key"),
+    ("providers.*.model", "该 Provider 的默认模型"),
+    (
+        "providers.*.auth_style",
+        "鉴权方式：不设置 = Bearer；Claude 用 \"x-api-key\"；离线回显用 \"echo\"",
+    ),
+    (
+        "providers.*.headers",
+        "附加到每个请求的自定义请求头（API 网关用）",
+    ),
+    (
+        "providers.*.endpoint_path",
+        "请求路径（相对 base_url 或完整 URL），默认 /chat/completions",
+    ),
+    (
+        "providers.*.max_tools",
+        "单次请求最多携带的工具数，默认按 Provider 取值",
+    ),
+    (
+        "providers.*.max_tool_schema_bytes",
+        "tools 数组序列化后的最大字节数",
+    ),
+    (
+        "providers.*.context_window",
+        "上下文窗口（tokens），默认按模型名查表，用于历史压缩时机",
+    ),
+    (
+        "providers.*.max_tokens",
+        "单次回复的输出 token 上限，未设置时不限制（Claude 协议默认 8192）",
+    ),
+    ("memory", "记忆系统"),
+    ("memory.backend", "存储后端（目前只有 sqlite）"),
+    ("memory.auto_save", "自动保存对话摘要到记忆"),
+    (
+        "memory.fallback_to_noop",
+        "记忆库打不开时降级为不持久化而不是中止启动（数据目录不可写时总是降级）",
+    ),
+    (
+        "memory.scrub",
+        "保存对话摘要前脱敏密钥（配置中的密钥值与 sk-... / Bearer ... 等形态）",
+    ),
+    ("memory.scrub_emails", "脱敏时同时遮盖邮箱地址"),
+    (
+        "memory.scrub_patterns",
+        "额外的脱敏正则，匹配内容替换为 [已脱敏]",
+    ),
+    (
+        "memory.store_conversations",
+        "对话摘要保存策略：all / filtered（跳过寒暄、失败回合与连续重复）/ off",
+    ),
+    (
+        "memory.store_min_user_chars",
+        "filtered 时用户消息短于此字符数、回复也较短且未调用工具的回合不保存",
+    ),
+    (
+        "memory.store_min_reply_chars",
+        "filtered 时回复短于此字符数、用户消息也较短且未调用工具的回合不保存",
+    ),
+    ("security", "安全策略"),
+    (
+        "security.autonomy",
+        "自主级别：readonly / supervised / full",
+    ),
+    ("security.allowed_commands", "shell 工具允许执行的命令"),
+    ("security.workspace_only", "文件操作限制在工作目录内"),
+    (
+        "security.http_allowed_hosts",
+        "HTTP 请求额外允许的内网 host / IP",
+    ),
+    (
+        "security.injection_check",
+        "检测工具输出中的 Prompt Injection",
+    ),
+    (
+        "security.http_strip_threshold_kb",
+        "HTML 去标签后的大小上限（KB），超出时提取摘要；0 = 不处理",
+    ),
+    (
+        "security.require_sandbox",
+        "未在 OS 沙箱中运行时拒绝 Full 模式（见 rrclaw sandbox-profile）",
+    ),
+    (
+        "security.safe_mode",
+        "不加载任何工具，纯对话（也可 rrclaw agent --safe）",
+    ),
+    (
+        "security.warn_dirty_worktree",
+        "git 工作区有未提交改动时，首次写入前提醒先提交或 /safety stash",
+    ),
+    (
+        "telegram",
+        "Telegram Bot（rrclaw telegram / daemon），整段省略即不启用",
+    ),
+    ("telegram.bot_token", "Bot Token（从 @BotFather 获取）"),
+    (
+        "telegram.allowed_chat_ids",
+        "允许的 chat ID（空 = 允许所有）",
+    ),
+    (
+        "telegram.tool_verbosity",
+        "回复前附带的工具结果：quiet / summary / full",
+    ),
+    (
+        "telegram.admin_chat_id",
+        "每日拦截汇总发送到此 chat（默认 allowed_chat_ids 第一个）",
+    ),
+    ("telegram.rate_limit", "限流与每日预算（计数项 0 = 不限制）"),
+    (
+        "telegram.rate_limit.per_chat_per_minute",
+        "单个 chat 每分钟最多处理的消息数",
+    ),
+    (
+        "telegram.rate_limit.global_per_minute",
+        "所有 chat 合计每分钟最多处理的消息数",
+    ),
+    (
+        "telegram.rate_limit.max_concurrent_turns",
+        "同时进行中的对话轮次上限",
+    ),
+    (
+        "telegram.rate_limit.mute_after",
+        "10 分钟内超限几次后临时静音该 chat",
+    ),
+    ("telegram.rate_limit.mute_minutes", "静音时长（分钟）"),
+    (
+        "telegram.rate_limit.daily_token_budget",
+        "单个 chat 每日 token 上限（估算，本地 0 点重置；省略 = 不限制）",
+    ),
+    (
+        "telegram.rate_limit.daily_cost_budget",
+        "单个 chat 每日费用上限（美元，按 [pricing] 估算；省略 = 不限制）",
+    ),
+    ("reliability", "重试与故障切换"),
+    ("reliability.max_retries", "单个 Provider 的最大重试次数"),
+    (
+        "reliability.initial_backoff_ms",
+        "首次重试前的等待（毫秒），之后指数退避",
+    ),
+    (
+        "reliability.fallback_providers",
+        "主 Provider 失败时按顺序切换的 Provider",
+    ),
+    ("mcp", "MCP Server"),
+    ("mcp.servers", "MCP Server 列表，名称用作工具前缀"),
+    ("mcp.servers.*.transport", "连接方式：stdio / sse"),
+    ("mcp.servers.*.command", "stdio：启动命令"),
+    ("mcp.servers.*.args", "stdio：命令参数"),
+    ("mcp.servers.*.env", "stdio：附加环境变量"),
+    ("mcp.servers.*.url", "sse：服务地址"),
+    ("mcp.servers.*.headers", "sse：附加请求头"),
+    ("mcp.servers.*.allowed_tools", "只暴露这些工具（空 = 全部）"),
+    (
+        "mcp.servers.*.max_tools",
+        "system prompt 中最多列出的工具数（省略 = 不限制）",
+    ),
+    (
+        "mcp.servers.*.tool_description_max_chars",
+        "工具简介的最大字符数（默认 80）",
+    ),
+    (
+        "mcp.prompt_budget_chars",
+        "system prompt 中 MCP 工具段的字符预算（省略 = 不限制）",
+    ),
+    ("routines", "定时任务"),
+    ("routines.jobs", "静态任务（也可用 /routine add 动态创建）"),
+    ("routines.jobs.name", "任务名（唯一）"),
+    ("routines.jobs.schedule", "cron 表达式（分 时 日 月 周）"),
+    (
+        "routines.jobs.message",
+        "触发时发给 Agent 的消息（{date} / {time} / {weekday} / {env:VAR} 在触发时展开）",
+    ),
+    ("routines.jobs.channel", "结果发送到：cli / telegram / webhook"),
+    (
+        "routines.jobs.channel_target",
+        "channel = \"webhook\" 时接收 POST 的 URL（JSON: routine / output / timestamp，受 http_allowed_hosts SSRF 检查）",
+    ),
+    ("routines.jobs.enabled", "是否启用"),
+    (
+        "routines.jobs.catch_up",
+        "启动时补跑停机期间错过的最近一次触发",
+    ),
+    (
+        "routines.jobs.chain",
+        "依次执行的其他 Routine 名称，结果汇总后发送（设置后可省略 message）",
+    ),
+    (
+        "routines.jobs.condition",
+        "执行前置条件：shell = \"命令\"（退出码 0）或 http = \"URL\"（返回 200），不满足时只记录跳过",
+    ),
+    (
+        "routines.timezone",
+        "cron 使用的 IANA 时区（省略 = 系统本地时区）",
+    ),
+    (
+        "routines.approach_max_failures",
+        "记住的成功方法连续失败几次后改为探索新方法（0 = 始终沿用）",
+    ),
+    (
+        "routines.paused",
+        "暂停全部定时触发（/routine run 仍可手动执行）",
+    ),
+    ("cli", "交互界面"),
+    ("cli.show_changes", "每轮结束后显示改动的文件"),
+    (
+        "cli.tool_verbosity",
+        "工具状态行下方显示：quiet / summary / full",
+    ),
+    (
+        "cli.queue_messages",
+        "回答进行中输入的消息在本轮结束后自动发送（false = 预填到输入行）",
+    ),
+    ("agent", "辅助调用使用的模型（失败时回退到主模型）与 system prompt 定制"),
+    (
+        "agent.system_prompt_prefix",
+        "插入在 system prompt 最前面的文本",
+    ),
+    (
+        "agent.system_prompt_override",
+        "替换内置身份描述与决策原则（工具 / 记忆 / 环境信息保留）",
+    ),
+    ("agent.routing", "Phase 1 技能路由"),
+    (
+        "agent.routing.provider",
+        "Provider 名（省略 = 主 Provider）",
+    ),
+    (
+        "agent.routing.model",
+        "模型名（省略 = 该 Provider 的默认模型）",
+    ),
+    ("agent.summary", "长对话历史压缩摘要"),
+    (
+        "agent.summary.provider",
+        "Provider 名（省略 = 主 Provider）",
+    ),
+    (
+        "agent.summary.model",
+        "模型名（省略 = 该 Provider 的默认模型）",
+    ),
+    ("daemon", "后台 daemon（rrclaw start）"),
+    (
+        "daemon.metrics_port",
+        "设置后在 /metrics 暴露 Prometheus 指标（省略 = 不开启）",
+    ),
+    ("daemon.metrics_bind", "metrics 端点监听地址"),
+    (
+        "daemon.confirm_timeout_secs",
+        "Supervised 模式等待 rrclaw chat 确认工具调用的秒数，超时视为拒绝",
+    ),
+    ("dev", "开发调试：录制 / 回放 Provider 交互"),
+    (
+        "dev.mode",
+        "off / record（追加到 cassette）/ replay（按顺序回放，不访问网络）",
+    ),
+    ("dev.cassette", "cassette 文件（JSONL）"),
+    ("update", "新版本检查"),
+    (
+        "update.check",
+        "启动 REPL 时检查 GitHub 新版本（每天最多一次，false 关闭）",
+    ),
+    ("search", "网页搜索（web_search 工具）"),
+    ("search.backend", "brave（Brave Search API）/ searxng（自建实例）"),
+    ("search.api_key", "Brave Search API Key"),
+    (
+        "search.url",
+        "SearXNG 实例地址；Brave 时可覆盖 API 地址。内网地址需加入 security.http_allowed_hosts",
+    ),
+    ("search.max_results", "默认返回条数（单次最多 20）"),
+    (
+        "pricing",
+        "模型价格（美元 / 百万 tokens，/cost 估算用），key 为模型名",
+    ),
+    ("pricing.*.input", "输入价格"),
+    ("pricing.*.output", "输出价格"),
+];
+
+/// 所有可选段都填了示例值的配置（其余字段为默认值）
+pub fn example_config() -> Config {
+    let mut config = Config::default();
+
+    let provider = |base_url: &str, model: &str| ProviderConfig {
+        base_url: base_url.to_string(),
+        api_key: "your-key".to_string(),
+        model: model.to_string(),
+        auth_style: None,
+        headers: HashMap::new(),
+        endpoint_path: None,
+        max_tools: None,
+        max_tool_schema_bytes: None,
+        context_window: None,
+        max_tokens: None,
+    };
+    config.providers.insert(
+        "deepseek".to_string(),
+        provider("https://api.deepseek.com/v1", "deepseek-chat"),
+    );
+    config.providers.insert(
+        "claude".to_string(),
+        ProviderConfig {
+            auth_style: Some("x-api-key".to_string()),
+            ..provider("https://api.anthropic.com", "claude-sonnet-4-5-20250929")
+        },
+    );
+    config.providers.insert(
+        "gateway".to_string(),
+        ProviderConfig {
+            headers: HashMap::from([("X-Org-Id".to_string(), "your-org".to_string())]),
+            endpoint_path: Some("/deployments/gpt-4o/chat".to_string()),
+            max_tools: Some(40),
+            max_tool_schema_bytes: Some(32_000),
+            context_window: Some(128_000),
+            max_tokens: Some(4096),
+            ..provider("https://gw.example.com/openai", "gpt-4o")
+        },
+    );
+
+    let mut telegram = TelegramConfig {
+        bot_token: Some("123456:ABC-your-bot-token".to_string()),
+        allowed_chat_ids: vec![123456789],
+        admin_chat_id: Some(123456789),
+        ..Default::default()
+    };
+    telegram.rate_limit.daily_token_budget = Some(200_000);
+    telegram.rate_limit.daily_cost_budget = Some(1.0);
+    config.telegram = Some(telegram);
+
+    config.reliability.fallback_providers = vec!["claude".to_string()];
+    config.memory.scrub_patterns = vec![r"\b\d{11}\b".to_string()];
+    config.search = SearchConfig {
+        backend: Some(SearchBackendKind::Searxng),
+        url: Some("http://127.0.0.1:8888".to_string()),
+        ..Default::default()
+    };
+
+    config.mcp = Some(McpConfig {
+        servers: HashMap::from([
+            (
+                "filesystem".to_string(),
+                McpServerConfig {
+                    transport: McpTransport::Stdio {
+                        command: "npx".to_string(),
+                        args: vec![
+                            "-y".to_string(),
+                            "@modelcontextprotocol/server-filesystem".to_string(),
+                            "/tmp".to_string(),
+                        ],
+                        env: HashMap::from([("NODE_ENV".to_string(), "production".to_string())]),
+                    },
+                    allowed_tools: vec!["read_file".to_string(), "list_directory".to_string()],
+                    max_tools: Some(20),
+                    tool_description_max_chars: Some(80),
+                },
+            ),
+            (
+                "remote".to_string(),
+                McpServerConfig {
+                    transport: McpTransport::Sse {
+                        url: "https://mcp.example.com/sse".to_string(),
+                        headers: HashMap::from([(
+                            "Authorization".to_string(),
+                            "Bearer your-token".to_string(),
+                        )]),
+                    },
+                    allowed_tools: Vec::new(),
+                    max_tools: None,
+                    tool_description_max_chars: None,
+                },
+            ),
+        ]),
+        prompt_budget_chars: Some(4000),
+    });
+
+    config.routines.timezone = Some("Asia/Shanghai".to_string());
+    config.routines.jobs = vec![
+        RoutineJobConfig {
+            name: "morning_brief".to_string(),
+            schedule: "0 8 * * *".to_string(),
+            message: "总结 {date}（{weekday}）的待办事项".to_string(),
+            channel: "cli".to_string(),
+            channel_target: None,
+            enabled: true,
+            catch_up: false,
+            chain: vec![],
+            condition: None,
+        },
+        RoutineJobConfig {
+            name: "nightly".to_string(),
+            schedule: "0 23 * * *".to_string(),
+            message: String::new(),
+            channel: "webhook".to_string(),
+            channel_target: Some("https://hooks.example.com/rrclaw".to_string()),
+            enabled: true,
+            catch_up: false,
+            chain: vec!["morning_brief".to_string()],
+            condition: Some(RoutineCondition::Shell(
+                "test -f ~/.rrclaw/nightly.enabled".to_string(),
+            )),
+        },
+    ];
+
+    config.agent.routing = Some(AuxModelConfig {
+        provider: Some("deepseek".to_string()),
+        model: Some("deepseek-chat".to_string()),
+    });
+    config.agent.summary = Some(AuxModelConfig {
+        provider: Some("deepseek".to_string()),
+        model: Some("deepseek-chat".to_string()),
+    });
+
+    config.agent.system_prompt_prefix = Some("你服务于 ACME 公司的运维团队。".to_string());
+    config.agent.system_prompt_override =
+        Some("你是 Ops 助手：回答简短，先给可执行的命令，再解释原因。".to_string());
+
+    config.daemon.metrics_port = Some(9187);
+
+    config.pricing.insert(
+        "glm-4-flash".to_string(),
+        ModelPricing {
+            input: 0.1,
+            output: 0.1,
+        },
+    );
+    config
+}
+
+/// 带注释的完整示例 TOML
+pub fn example_toml() -> String {
+    render(&example_config()).0
+}
+
+/// 渲染 TOML，同时返回输出过的 `段.键`（测试检查覆盖）
+fn render(config: &Config) -> (String, Vec<String>) {
+    let value = serde_json::to_value(config).expect("Config 可序列化为 JSON");
+    let mut out = String::from(
+        "# RRClaw 完整示例配置（rrclaw config --example 生成）\n\
+         # 除 [default] / [memory] / [security] 外各段均可省略；复制需要的段到配置文件（路径见 rrclaw config）\n",
+    );
+    let mut emitted = Vec::new();
+    if let Value::Object(map) = &value {
+        render_table(&mut out, &mut emitted, "", "", map);
+    }
+    (out, emitted)
+}
+
+/// 输出一个表：先标量键，再子表（TOML 要求子表在父表的键之后）
+fn render_table(
+    out: &mut String,
+    emitted: &mut Vec<String>,
+    header: &str,
+    schema: &str,
+    map: &Map<String, Value>,
+) {
+    let keys = ordered_keys(schema, map);
+    for key in &keys {
+        let value = &map[key.as_str()];
+        if is_table(value) || is_table_array(value) {
+            continue;
+        }
+        let Some(literal) = literal(value) else {
+            continue;
+        };
+        let path = join(schema, key);
+        if let Some(doc) = doc(&path) {
+            out.push_str(&format!("# {}\n", doc));
+            emitted.push(path);
+        }
+        out.push_str(&format!("{} = {}\n", format_key(key), literal));
+    }
+
+    for key in &keys {
+        let value = &map[key.as_str()];
+        // 空的自由映射（未设置的 headers / env）不输出空表头
+        if matches!(value, Value::Object(child) if child.is_empty()) {
+            continue;
+        }
+        let path = join(schema, key);
+        let child_header = join(header, &format_key(key));
+        let section_doc = doc(&path);
+        if section_doc.is_some() {
+            emitted.push(path.clone());
+        }
+        match value {
+            Value::Object(child) if NAMED_SECTIONS.contains(&path.as_str()) => {
+                if let Some(doc) = section_doc {
+                    out.push_str(&format!("\n# {}", doc));
+                }
+                let named_schema = format!("{}.*", path);
+                for (name, entry) in child {
+                    if let Value::Object(entry) = entry {
+                        out.push_str(&format!("\n[{}.{}]\n", child_header, format_key(name)));
+                        render_table(
+                            out,
+                            emitted,
+                            &join(&child_header, &format_key(name)),
+                            &named_schema,
+                            entry,
+                        );
+                    }
+                }
+            }
+            Value::Object(child) => {
+                out.push('\n');
+                if let Some(doc) = section_doc {
+                    out.push_str(&format!("# {}\n", doc));
+                }
+                out.push_str(&format!("[{}]\n", child_header));
+                render_table(out, emitted, &child_header, &path, child);
+            }
+            Value::Array(items) if is_table_array(value) => {
+                out.push('\n');
+                if let Some(doc) = section_doc {
+                    out.push_str(&format!("# {}\n", doc));
+                }
+                for item in items {
+                    if let Value::Object(item) = item {
+                        out.push_str(&format!("[[{}]]\n", child_header));
+                        render_table(out, emitted, &child_header, &path, item);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 已知段按 `KNOWN_KEYS` 顺序，其余（headers / env 等自由映射）按字母序
+fn ordered_keys(schema: &str, map: &Map<String, Value>) -> Vec<String> {
+    let mut keys: Vec<String> = KNOWN_KEYS
+        .iter()
+        .find(|(section, _)| *section == schema)
+        .map(|(_, known)| {
+            known
+                .iter()
+                .filter(|k| map.contains_key(**k))
+                .map(|k| k.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut rest: Vec<String> = map.keys().filter(|k| !keys.contains(k)).cloned().collect();
+    rest.sort();
+    keys.extend(rest);
+    keys
+}
+
+fn is_table(value: &Value) -> bool {
+    value.is_object()
+}
+
+fn is_table_array(value: &Value) -> bool {
+    matches!(value, Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object))
+}
+
+/// 标量或标量数组的 TOML 字面量（JSON 字符串转义与 TOML 基本字符串兼容）；null 返回 None
+fn literal(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Array(items) => Some(format!(
+            "[{}]",
+            items
+                .iter()
+                .filter_map(literal)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        other => Some(other.to_string()),
+    }
+}
+
+/// 裸键（字母、数字、`_`、`-`）原样输出，否则加引号
+fn format_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        Value::String(key.to_string()).to_string()
+    }
+}
+
+fn doc(path: &str) -> Option<&'static str> {
+    FIELD_DOCS
+        .iter()
+        .find(|(key, _)| *key == path)
+        .map(|(_, doc)| *doc)
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_parses_back_into_valid_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        let text = example_toml();
+        std::fs::write(&path, &text).unwrap();
+
+        let config = Config::load_from_path(&path).unwrap();
+        assert_eq!(config.providers.len(), 3);
+        assert_eq!(
+            config.providers["claude"].auth_style.as_deref(),
+            Some("x-api-key")
+        );
+        assert_eq!(config.providers["gateway"].headers["X-Org-Id"], "your-org");
+        let telegram = config.telegram.as_ref().unwrap();
+        assert_eq!(telegram.rate_limit.per_chat_per_minute, 10);
+        assert_eq!(telegram.rate_limit.daily_cost_budget, Some(1.0));
+        assert_eq!(config.mcp.as_ref().unwrap().servers.len(), 2);
+        assert_eq!(config.routines.jobs[0].name, "morning_brief");
+        assert_eq!(config.pricing["glm-4-flash"].input, 0.1);
+        assert!(config.validate().is_empty(), "{:?}", config.validate());
+        assert!(super::super::validate::unknown_keys(&text).is_empty());
+    }
+
+    #[test]
+    fn example_covers_every_known_key_with_a_doc() {
+        let (_, emitted) = render(&example_config());
+        for (section, keys) in KNOWN_KEYS {
+            for key in *keys {
+                let path = join(section, key);
+                assert!(doc(&path).is_some(), "缺少说明: {}", path);
+                assert!(emitted.contains(&path), "示例未包含: {}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn keys_needing_quotes_are_quoted() {
+        assert_eq!(format_key("deepseek"), "deepseek");
+        assert_eq!(format_key("glm-4.5"), "\"glm-4.5\"");
+        assert_eq!(literal(&serde_json::json!(["a", 1])).unwrap(), "[\"a\", 1]");
+    }
+}
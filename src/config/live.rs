@@ -0,0 +1,61 @@
+//! 运行中可替换的配置
+//!
+//! daemon 持有一份 `LiveConfig`，`rrclaw reload` 重新解析 config.toml 后整体替换。
+//! 每次新建 Agent 时取快照；已缓存的 Agent（如 Telegram 会话）通过 `generation` 判断是否需要刷新。
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use super::Config;
+
+/// 可热更新的配置
+pub struct LiveConfig {
+    current: RwLock<Arc<Config>>,
+    generation: AtomicU64,
+}
+
+impl LiveConfig {
+    pub fn new(config: Config) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(config)),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// 当前配置快照
+    pub fn snapshot(&self) -> Arc<Config> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// 配置版本号（每次 `replace` 加 1）
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// 替换为新配置，返回新的版本号
+    pub fn replace(&self, config: Config) -> u64 {
+        *self.current.write().unwrap() = Arc::new(config);
+        self.generation.fetch_add(1, Ordering::AcqRel) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_swaps_snapshot_and_bumps_generation() {
+        let live = LiveConfig::new(Config::default());
+        let before = live.snapshot();
+        assert_eq!(live.generation(), 0);
+
+        let mut next = Config::default();
+        next.security.allowed_commands = vec!["ls".to_string()];
+        assert_eq!(live.replace(next), 1);
+
+        assert_eq!(live.generation(), 1);
+        assert_eq!(live.snapshot().security.allowed_commands, vec!["ls"]);
+        // 旧快照不受影响（正在处理的请求继续使用旧配置）
+        assert_ne!(before.security.allowed_commands, vec!["ls"]);
+    }
+}
@@ -1,8 +1,19 @@
+pub mod example;
+pub mod live;
+pub mod paths;
 pub mod schema;
 pub mod setup;
+pub mod setup_mcp;
+pub mod validate;
 
+pub use live::LiveConfig;
+pub use paths::RrclawPaths;
 pub use schema::{
-    Config, DefaultConfig, McpConfig, McpServerConfig, McpTransport, MemoryConfig, ProviderConfig,
-    ReliabilityConfig, RoutineJobConfig, RoutinesConfig, SecurityConfig, TelegramConfig,
+    AgentConfig, AuxModelConfig, CassetteMode, Config, DefaultConfig, DevConfig, McpConfig,
+    McpServerConfig, McpTransport, MemoryConfig, ModelPricing, ProviderConfig, RateLimitConfig,
+    ReliabilityConfig, RoutineJobConfig, RoutinesConfig, SearchBackendKind, SearchConfig,
+    SecurityConfig, StoreConversations, TelegramConfig, ToolVerbosity, UpdateConfig,
 };
 pub use setup::{find_provider_info, run_setup, select_model, ProviderInfo, PROVIDERS};
+pub use setup_mcp::run_mcp_setup;
+pub use validate::{check_config_file, ValidationWarning};
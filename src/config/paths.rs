@@ -0,0 +1,476 @@
+//! RRClaw 目录布局
+//!
+//! 解析顺序：
+//! 1. `rrclaw --config <path>`：data / logs / skills / daemon.pid / daemon.sock 等全部放在该配置文件
+//!    所在目录，与默认安装互不干扰（可用于测试多套配置，或并行运行第二个隔离的 daemon）；
+//! 2. `RRCLAW_HOME`：同样的单目录布局，根目录为该环境变量；
+//! 3. 旧版 `~/.rrclaw/` 存在且尚未迁移：继续使用旧目录（启动时询问一次是否迁移，见 [`LegacyMigration`]）；
+//! 4. XDG 目录：配置、身份文件、Skills 在 `$XDG_CONFIG_HOME/rrclaw`，数据库在 `$XDG_DATA_HOME/rrclaw`，
+//!    日志、故障报告、daemon.pid / daemon.sock 在 `$XDG_STATE_HOME/rrclaw`。
+//!
+//! daemon、Routine、身份文件、Skills、setup 都经由 [`RrclawPaths::resolve`]，保证看到同一套目录。
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use color_eyre::eyre::{bail, eyre, Context, Result};
+
+/// 指定 RRClaw 根目录的环境变量（单目录布局，优先于 XDG）
+pub const HOME_ENV: &str = "RRCLAW_HOME";
+
+/// XDG 目录下的应用子目录名
+const APP_DIR: &str = "rrclaw";
+
+/// 旧版根目录（相对 home）
+const LEGACY_DIR: &str = ".rrclaw";
+
+/// 用户拒绝迁移后写入旧目录的标记文件，之后不再询问
+const KEEP_LEGACY_MARKER: &str = ".keep-legacy-layout";
+
+/// 迁移时不复制的运行时文件
+const RUNTIME_FILES: &[&str] = &["daemon.pid", "daemon.sock", KEEP_LEGACY_MARKER];
+
+/// `--config` 指定的配置文件（进程启动时设置一次）
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// 设置全局配置文件路径（main 解析命令行后调用，只能设置一次）
+///
+/// 相对路径按当前目录解析为绝对路径，保证 daemon 等子进程看到同一文件。
+pub fn set_config_override(path: &Path) -> Result<()> {
+    let path = absolute(path)?;
+    if path.file_name().is_none() {
+        bail!("--config 需要指向配置文件，而不是目录: {}", path.display());
+    }
+    CONFIG_OVERRIDE
+        .set(path)
+        .map_err(|_| eyre!("配置文件路径已设置，不能重复设置"))
+}
+
+/// `--config` 指定的配置文件（未指定时为 None）
+pub fn config_override() -> Option<&'static Path> {
+    CONFIG_OVERRIDE.get().map(PathBuf::as_path)
+}
+
+/// 相对路径按当前目录解析
+fn absolute(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    Ok(std::env::current_dir()
+        .wrap_err("获取当前目录失败")?
+        .join(path))
+}
+
+/// 环境变量查询（测试中替换为固定映射）
+type Env<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+fn process_env(key: &str) -> Option<OsString> {
+    std::env::var_os(key)
+}
+
+fn user_home() -> Result<PathBuf> {
+    let base_dirs = directories::BaseDirs::new().ok_or_else(|| eyre!("无法获取 home 目录"))?;
+    Ok(base_dirs.home_dir().to_path_buf())
+}
+
+/// XDG 基础目录：变量未设置、为空或是相对路径时使用规范默认值
+fn xdg_dir(env: Env, key: &str, home_dir: &Path, default: &str) -> PathBuf {
+    env(key)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .unwrap_or_else(|| home_dir.join(default))
+        .join(APP_DIR)
+}
+
+/// 一套 RRClaw 目录：配置文件 + 身份文件 / Skills 所在的根目录 + 数据目录 + 状态目录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RrclawPaths {
+    config_file: PathBuf,
+    home: PathBuf,
+    data_dir: PathBuf,
+    state_dir: PathBuf,
+}
+
+impl RrclawPaths {
+    /// 当前进程使用的目录布局（`--config` → `RRCLAW_HOME` → 未迁移的 `~/.rrclaw/` → XDG）
+    pub fn resolve() -> Result<Self> {
+        match config_override() {
+            Some(path) => Ok(Self::from_config_file(path)),
+            None => Self::resolve_with(&user_home()?, &process_env),
+        }
+    }
+
+    /// 不含 `--config` 的解析（`home_dir` 为用户主目录）
+    fn resolve_with(home_dir: &Path, env: Env) -> Result<Self> {
+        if let Some(home) = env(HOME_ENV).filter(|v| !v.is_empty()) {
+            return Ok(Self::in_home(&absolute(Path::new(&home))?));
+        }
+        let xdg = Self::xdg(home_dir, env);
+        let legacy = home_dir.join(LEGACY_DIR);
+        if legacy.is_dir() && !xdg.home.exists() {
+            Ok(Self::in_home(&legacy))
+        } else {
+            Ok(xdg)
+        }
+    }
+
+    /// XDG 布局：配置在 config home，数据库在 data home，日志等在 state home
+    fn xdg(home_dir: &Path, env: Env) -> Self {
+        let home = xdg_dir(env, "XDG_CONFIG_HOME", home_dir, ".config");
+        Self {
+            config_file: home.join("config.toml"),
+            home,
+            data_dir: xdg_dir(env, "XDG_DATA_HOME", home_dir, ".local/share"),
+            state_dir: xdg_dir(env, "XDG_STATE_HOME", home_dir, ".local/state"),
+        }
+    }
+
+    /// 以配置文件所在目录为根
+    pub fn from_config_file(config_file: &Path) -> Self {
+        let home = config_file
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        Self {
+            config_file: config_file.to_path_buf(),
+            ..Self::in_home(home)
+        }
+    }
+
+    /// 单目录布局：以 `home` 为根、配置文件为 `home/config.toml`
+    pub fn in_home(home: &Path) -> Self {
+        Self {
+            config_file: home.join("config.toml"),
+            home: home.to_path_buf(),
+            data_dir: home.join("data"),
+            state_dir: home.to_path_buf(),
+        }
+    }
+
+    /// 配置文件
+    pub fn config_file(&self) -> &Path {
+        &self.config_file
+    }
+
+    /// 根目录（配置文件所在目录），存放身份文件、Skills、Recipes
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    /// SQLite 数据库与搜索索引
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone()
+    }
+
+    /// 运行时状态：日志、故障报告、daemon.pid / daemon.sock（单目录布局下即根目录）
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    /// 日志目录
+    pub fn log_dir(&self) -> PathBuf {
+        self.state_dir.join("logs")
+    }
+
+    /// 全局 Skills 目录
+    pub fn skills_dir(&self) -> PathBuf {
+        self.home.join("skills")
+    }
+
+    /// HTTP Recipe 目录（`http_request` 的多步请求定义）
+    pub fn recipes_dir(&self) -> PathBuf {
+        self.home.join("recipes")
+    }
+
+    /// FileWriteTool 覆盖前的文件快照（`/undo-file` 恢复）
+    pub fn undo_dir(&self) -> PathBuf {
+        self.data_dir().join("undo")
+    }
+
+    /// 本地故障报告目录（panic / `/report`）
+    pub fn reports_dir(&self) -> PathBuf {
+        self.state_dir.join("reports")
+    }
+
+    /// RRClaw 需要写入的顶层目录（去掉嵌套在其他目录下的），沙箱配置据此放行
+    pub fn writable_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in [&self.home, &self.data_dir, &self.state_dir] {
+            if !dirs.iter().any(|d| dir.starts_with(d)) {
+                dirs.retain(|d| !d.starts_with(dir));
+                dirs.push(dir.clone());
+            }
+        }
+        dirs
+    }
+}
+
+/// 旧版 `~/.rrclaw/` 到 XDG 目录的一次性迁移
+///
+/// 复制而不是移动：旧目录原样保留，确认新目录可用后由用户自行删除。配置目录最后写入，
+/// 且先写到临时目录再 rename，中途失败时下次启动仍使用旧目录。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyMigration {
+    legacy: PathBuf,
+    target: RrclawPaths,
+}
+
+impl LegacyMigration {
+    /// 需要询问用户是否迁移时返回 Some（使用 `--config` / `RRCLAW_HOME` 时不迁移）
+    pub fn pending() -> Result<Option<Self>> {
+        if config_override().is_some() {
+            return Ok(None);
+        }
+        Ok(Self::plan(&user_home()?, &process_env))
+    }
+
+    /// 迁移判定：旧目录存在、XDG 配置目录不存在、用户没有拒绝过
+    fn plan(home_dir: &Path, env: Env) -> Option<Self> {
+        if env(HOME_ENV).is_some_and(|v| !v.is_empty()) {
+            return None;
+        }
+        let legacy = home_dir.join(LEGACY_DIR);
+        let target = RrclawPaths::xdg(home_dir, env);
+        let needed =
+            legacy.is_dir() && !target.home.exists() && !legacy.join(KEEP_LEGACY_MARKER).exists();
+        needed.then_some(Self { legacy, target })
+    }
+
+    /// 旧目录
+    pub fn legacy(&self) -> &Path {
+        &self.legacy
+    }
+
+    /// 迁移后的目录布局
+    pub fn target(&self) -> &RrclawPaths {
+        &self.target
+    }
+
+    /// 复制旧目录到 XDG 目录，返回已复制的顶层条目（相对旧目录）
+    pub fn migrate(&self) -> Result<Vec<String>> {
+        let mut copied = Vec::new();
+        let mut config_entries = Vec::new();
+        for entry in std::fs::read_dir(&self.legacy)
+            .wrap_err_with(|| format!("读取旧目录失败: {}", self.legacy.display()))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if RUNTIME_FILES.contains(&name.as_str()) {
+                continue;
+            }
+            let target = match name.as_str() {
+                "data" => self.target.data_dir(),
+                "logs" => self.target.log_dir(),
+                "reports" => self.target.reports_dir(),
+                _ => {
+                    config_entries.push((entry.path(), name));
+                    continue;
+                }
+            };
+            copy_recursive(&entry.path(), &target)?;
+            copied.push(name);
+        }
+
+        // 配置目录出现即视为迁移完成，所以最后整体 rename 到位
+        let staging = self
+            .target
+            .home
+            .with_file_name(format!(".{}-migrating", APP_DIR));
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)
+                .wrap_err_with(|| format!("清理临时目录失败: {}", staging.display()))?;
+        }
+        std::fs::create_dir_all(&staging)
+            .wrap_err_with(|| format!("创建目录失败: {}", staging.display()))?;
+        for (path, name) in config_entries {
+            copy_recursive(&path, &staging.join(&name))?;
+            copied.push(name);
+        }
+        std::fs::rename(&staging, &self.target.home)
+            .wrap_err_with(|| format!("写入配置目录失败: {}", self.target.home.display()))?;
+        copied.sort();
+        Ok(copied)
+    }
+
+    /// 用户拒绝迁移：写入标记，之后继续使用旧目录且不再询问
+    pub fn decline(&self) -> Result<()> {
+        std::fs::write(
+            self.legacy.join(KEEP_LEGACY_MARKER),
+            "RRClaw: keep using this directory instead of the XDG layout.\n\
+             Delete this file to be asked about migrating again.\n",
+        )
+        .wrap_err("写入标记文件失败")
+    }
+}
+
+/// 递归复制文件或目录（跳过 socket 等特殊文件）
+fn copy_recursive(src: &Path, dst: &Path) -> Result<()> {
+    let meta = std::fs::metadata(src).wrap_err_with(|| format!("读取失败: {}", src.display()))?;
+    if meta.is_dir() {
+        std::fs::create_dir_all(dst)
+            .wrap_err_with(|| format!("创建目录失败: {}", dst.display()))?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+    } else if meta.is_file() {
+        if let Some(parent) = dst.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("创建目录失败: {}", parent.display()))?;
+        }
+        std::fs::copy(src, dst)
+            .wrap_err_with(|| format!("复制失败: {} → {}", src.display(), dst.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_config_isolates_all_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = tmp.path().join("second").join("config.toml");
+        let paths = RrclawPaths::from_config_file(&config);
+
+        assert_eq!(paths.config_file(), config);
+        assert_eq!(paths.home(), tmp.path().join("second"));
+        for dir in [paths.data_dir(), paths.log_dir(), paths.skills_dir()] {
+            assert!(dir.starts_with(tmp.path().join("second")));
+        }
+        assert_ne!(paths.data_dir(), RrclawPaths::resolve().unwrap().data_dir());
+        assert_eq!(paths.writable_dirs(), vec![tmp.path().join("second")]);
+    }
+
+    /// 固定的环境变量映射
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: Vec<(String, OsString)> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), OsString::from(v)))
+            .collect();
+        move |key: &str| {
+            vars.iter()
+                .find(|(k, _)| k.as_str() == key)
+                .map(|(_, v)| v.clone())
+        }
+    }
+
+    #[test]
+    fn rrclaw_home_env_overrides_everything() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join(".rrclaw")).unwrap();
+        let custom = tmp.path().join("custom");
+        let vars = env(&[
+            (HOME_ENV, custom.to_str().unwrap()),
+            ("XDG_CONFIG_HOME", "/xdg/config"),
+        ]);
+
+        let paths = RrclawPaths::resolve_with(tmp.path(), &vars).unwrap();
+        assert_eq!(paths, RrclawPaths::in_home(&custom));
+        assert_eq!(paths.data_dir(), custom.join("data"));
+        assert_eq!(paths.log_dir(), custom.join("logs"));
+        assert_eq!(LegacyMigration::plan(tmp.path(), &vars), None);
+    }
+
+    #[test]
+    fn xdg_layout_splits_config_data_and_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths = RrclawPaths::resolve_with(tmp.path(), &env(&[])).unwrap();
+        assert_eq!(
+            paths.config_file(),
+            tmp.path().join(".config/rrclaw/config.toml")
+        );
+        assert_eq!(paths.skills_dir(), tmp.path().join(".config/rrclaw/skills"));
+        assert_eq!(paths.data_dir(), tmp.path().join(".local/share/rrclaw"));
+        assert_eq!(
+            paths.undo_dir(),
+            tmp.path().join(".local/share/rrclaw/undo")
+        );
+        assert_eq!(paths.log_dir(), tmp.path().join(".local/state/rrclaw/logs"));
+        assert_eq!(paths.state_dir(), tmp.path().join(".local/state/rrclaw"));
+        assert_eq!(paths.writable_dirs().len(), 3);
+
+        // 显式设置的变量生效；相对路径按规范忽略
+        let vars = env(&[
+            ("XDG_CONFIG_HOME", "/cfg"),
+            ("XDG_DATA_HOME", "relative/data"),
+            ("XDG_STATE_HOME", "/state"),
+        ]);
+        let paths = RrclawPaths::resolve_with(tmp.path(), &vars).unwrap();
+        assert_eq!(paths.config_file(), Path::new("/cfg/rrclaw/config.toml"));
+        assert_eq!(paths.data_dir(), tmp.path().join(".local/share/rrclaw"));
+        assert_eq!(paths.log_dir(), Path::new("/state/rrclaw/logs"));
+    }
+
+    #[test]
+    fn legacy_dir_is_used_until_migrated_or_declined() {
+        let tmp = tempfile::tempdir().unwrap();
+        let legacy = tmp.path().join(".rrclaw");
+        std::fs::create_dir_all(legacy.join("data")).unwrap();
+
+        let paths = RrclawPaths::resolve_with(tmp.path(), &env(&[])).unwrap();
+        assert_eq!(paths, RrclawPaths::in_home(&legacy));
+        assert_eq!(paths.writable_dirs(), vec![legacy.clone()]);
+        let plan = LegacyMigration::plan(tmp.path(), &env(&[])).unwrap();
+        assert_eq!(plan.legacy(), legacy);
+
+        // 拒绝后不再询问，继续使用旧目录
+        plan.decline().unwrap();
+        assert_eq!(LegacyMigration::plan(tmp.path(), &env(&[])), None);
+        let paths = RrclawPaths::resolve_with(tmp.path(), &env(&[])).unwrap();
+        assert_eq!(paths.home(), legacy);
+
+        // XDG 配置目录已存在（已迁移）时不再使用旧目录
+        std::fs::remove_file(legacy.join(KEEP_LEGACY_MARKER)).unwrap();
+        std::fs::create_dir_all(tmp.path().join(".config/rrclaw")).unwrap();
+        assert_eq!(LegacyMigration::plan(tmp.path(), &env(&[])), None);
+        let paths = RrclawPaths::resolve_with(tmp.path(), &env(&[])).unwrap();
+        assert_eq!(paths.home(), tmp.path().join(".config/rrclaw"));
+    }
+
+    #[test]
+    fn migration_copies_legacy_layout_into_xdg_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let legacy = tmp.path().join(".rrclaw");
+        for (file, content) in [
+            ("config.toml", "[default]\n"),
+            ("USER.md", "# me\n"),
+            ("skills/deploy/SKILL.md", "---\nname: deploy\n---\n"),
+            ("data/memory.db", "db"),
+            ("logs/rrclaw.log", "log"),
+            ("daemon.pid", "42"),
+        ] {
+            let path = legacy.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let plan = LegacyMigration::plan(tmp.path(), &env(&[])).unwrap();
+        let copied = plan.migrate().unwrap();
+        assert_eq!(
+            copied,
+            vec!["USER.md", "config.toml", "data", "logs", "skills"]
+        );
+
+        let paths = RrclawPaths::resolve_with(tmp.path(), &env(&[])).unwrap();
+        assert_eq!(paths, *plan.target());
+        assert!(paths.config_file().is_file());
+        assert!(paths.home().join("USER.md").is_file());
+        assert!(paths.skills_dir().join("deploy/SKILL.md").is_file());
+        assert!(paths.data_dir().join("memory.db").is_file());
+        assert!(paths.log_dir().join("rrclaw.log").is_file());
+        assert!(!paths.state_dir().join("daemon.pid").exists());
+        // 旧目录保留，且不再需要迁移
+        assert!(legacy.join("data/memory.db").is_file());
+        assert_eq!(LegacyMigration::plan(tmp.path(), &env(&[])), None);
+    }
+
+    #[test]
+    fn bare_file_name_uses_current_dir() {
+        let paths = RrclawPaths::from_config_file(Path::new("alt.toml"));
+        assert_eq!(paths.home(), Path::new("."));
+        assert_eq!(paths.data_dir(), Path::new("./data"));
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-use color_eyre::eyre::{Context, Result};
+use color_eyre::eyre::{bail, eyre, Context, Result};
 use figment::providers::{Env, Format, Serialized, Toml};
 use figment::Figment;
 use serde::{Deserialize, Serialize};
@@ -23,10 +23,281 @@ pub struct Config {
     pub mcp: Option<McpConfig>,
     #[serde(default)]
     pub routines: RoutinesConfig,
+    #[serde(default)]
+    pub cli: CliConfig,
+    #[serde(default)]
+    pub agent: AgentConfig,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    #[serde(default)]
+    pub dev: DevConfig,
+    #[serde(default)]
+    pub update: UpdateConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    /// 模型价格表（`/cost` 估算用），key 为模型名，覆盖内置价格
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pricing: HashMap<String, ModelPricing>,
 }
 
-/// Telegram Bot 配置
+/// 模型价格（美元 / 百万 tokens）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPricing {
+    /// 给定输入/输出 token 数的费用（美元）
+    pub fn cost(&self, input_tokens: usize, output_tokens: usize) -> f64 {
+        (input_tokens as f64 * self.input + output_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// 内置价格（官网标价，仅作估算；`[pricing]` 同名条目优先）
+const BUILTIN_PRICING: &[(&str, ModelPricing)] = &[
+    (
+        "deepseek-chat",
+        ModelPricing {
+            input: 0.27,
+            output: 1.10,
+        },
+    ),
+    (
+        "deepseek-reasoner",
+        ModelPricing {
+            input: 0.55,
+            output: 2.19,
+        },
+    ),
+    (
+        "claude-sonnet-4-5-20250929",
+        ModelPricing {
+            input: 3.0,
+            output: 15.0,
+        },
+    ),
+    (
+        "gpt-4o",
+        ModelPricing {
+            input: 2.5,
+            output: 10.0,
+        },
+    ),
+    (
+        "gpt-4o-mini",
+        ModelPricing {
+            input: 0.15,
+            output: 0.6,
+        },
+    ),
+];
+
+/// Agent 辅助调用配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentConfig {
+    /// Phase 1 路由使用的模型（未配置时与主模型相同）
+    #[serde(default)]
+    pub routing: Option<AuxModelConfig>,
+    /// 历史压缩摘要使用的模型（未配置时与主模型相同）
+    #[serde(default)]
+    pub summary: Option<AuxModelConfig>,
+    /// 插入在 system prompt 最前面的文本（位于身份文件与内置各段之前）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt_prefix: Option<String>,
+    /// 替换内置的身份描述与决策原则（工具、记忆、环境信息等段落保留）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt_override: Option<String>,
+}
+
+/// 辅助模型：`provider` 省略时沿用主 Provider，`model` 省略时用该 Provider 的默认模型
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuxModelConfig {
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// 后台 daemon 配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonConfig {
+    /// 设置后 daemon 在该端口以 Prometheus 文本格式暴露 `/metrics`，默认不开启
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u16>,
+    /// metrics 端点监听地址，默认只监听本机
+    #[serde(default = "default_metrics_bind")]
+    pub metrics_bind: std::net::IpAddr,
+    /// Supervised 模式下等待 `rrclaw chat` 客户端确认工具调用的秒数，超时视为拒绝
+    #[serde(default = "default_confirm_timeout_secs")]
+    pub confirm_timeout_secs: u64,
+}
+
+fn default_confirm_timeout_secs() -> u64 {
+    120
+}
+
+fn default_metrics_bind() -> std::net::IpAddr {
+    std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            metrics_port: None,
+            metrics_bind: default_metrics_bind(),
+            confirm_timeout_secs: default_confirm_timeout_secs(),
+        }
+    }
+}
+
+/// 开发调试配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DevConfig {
+    /// 录制 / 回放 Provider 交互（`providers::recording`），默认 off
+    #[serde(default)]
+    pub mode: CassetteMode,
+    /// cassette 文件（JSONL，相对路径按当前目录解析）
+    #[serde(default = "default_cassette")]
+    pub cassette: PathBuf,
+}
+
+fn default_cassette() -> PathBuf {
+    PathBuf::from("rrclaw-cassette.jsonl")
+}
+
+impl Default for DevConfig {
+    fn default() -> Self {
+        Self {
+            mode: CassetteMode::default(),
+            cassette: default_cassette(),
+        }
+    }
+}
+
+/// 新版本检查配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// 启动 REPL 时检查 GitHub 上是否有新版本（每天最多一次），默认 true
+    #[serde(default = "default_true")]
+    pub check: bool,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self { check: true }
+    }
+}
+
+/// 网页搜索配置（`web_search` 工具）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchConfig {
+    /// 搜索后端，未设置时 `web_search` 返回配置提示
+    #[serde(default)]
+    pub backend: Option<SearchBackendKind>,
+    /// Brave Search API Key
+    #[serde(default)]
+    pub api_key: String,
+    /// SearXNG 实例地址（必填）；Brave 时可覆盖 API 地址（代理 / 网关）
+    #[serde(default)]
+    pub url: Option<String>,
+    /// 默认返回条数，默认 5（单次调用最多 20）
+    #[serde(default = "default_search_max_results")]
+    pub max_results: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            api_key: String::new(),
+            url: None,
+            max_results: default_search_max_results(),
+        }
+    }
+}
+
+fn default_search_max_results() -> usize {
+    5
+}
+
+/// 网页搜索后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchBackendKind {
+    /// Brave Search API（需要 api_key）
+    Brave,
+    /// 自建 SearXNG（需要 url，且实例开启 JSON 输出格式）
+    Searxng,
+}
+
+/// Provider 交互的录制 / 回放模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteMode {
+    /// 正常调用 Provider
+    #[default]
+    Off,
+    /// 调用 Provider 并把每次请求与响应追加到 cassette
+    Record,
+    /// 按顺序从 cassette 返回响应，不访问网络
+    Replay,
+}
+
+/// 交互界面配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CliConfig {
+    /// 每轮结束后显示本轮改动的文件（REPL 暗色行 / Telegram 回复末尾），默认 true
+    #[serde(default = "default_show_changes")]
+    pub show_changes: bool,
+    /// 工具执行后在状态行下方显示的内容，默认 summary
+    #[serde(default)]
+    pub tool_verbosity: ToolVerbosity,
+    /// 回答进行中输入的消息在本轮结束后自动依次发送（false = 预填到输入行供编辑），默认 false
+    #[serde(default)]
+    pub queue_messages: bool,
+}
+
+fn default_show_changes() -> bool {
+    true
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        Self {
+            show_changes: default_show_changes(),
+            tool_verbosity: ToolVerbosity::default(),
+            queue_messages: false,
+        }
+    }
+}
+
+/// 工具结果向用户展示的详细程度（不影响 LLM 看到的内容）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolVerbosity {
+    /// 只显示状态行
+    Quiet,
+    /// 状态行 + 工具提供的简短摘要（`ToolResult::user_facing`）
+    #[default]
+    Summary,
+    /// 状态行 + 完整输出（与 LLM 看到的一致）
+    Full,
+}
+
+impl ToolVerbosity {
+    /// 按详细程度选出要显示的文本（摘要缺失时 summary 模式不显示）
+    pub fn select<'a>(self, summary: Option<&'a str>, content: &'a str) -> Option<&'a str> {
+        match self {
+            ToolVerbosity::Quiet => None,
+            ToolVerbosity::Summary => summary.filter(|s| !s.trim().is_empty()),
+            ToolVerbosity::Full => Some(content).filter(|s| !s.trim().is_empty()),
+        }
+    }
+}
+
+/// Telegram Bot 配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TelegramConfig {
     /// Bot Token（从 @BotFather 获取）
     #[serde(default)]
@@ -34,6 +305,91 @@ pub struct TelegramConfig {
     /// 允许的 chat ID 列表（空 = 允许所有）
     #[serde(default)]
     pub allowed_chat_ids: Vec<i64>,
+    /// 回复前附带的工具结果详细程度，默认 quiet（只发最终回答）
+    #[serde(default = "default_telegram_tool_verbosity")]
+    pub tool_verbosity: ToolVerbosity,
+    /// 每日拦截汇总的接收 chat，未设置时取 `allowed_chat_ids` 第一个
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_chat_id: Option<i64>,
+    /// 限流与每日预算
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+}
+
+fn default_telegram_tool_verbosity() -> ToolVerbosity {
+    ToolVerbosity::Quiet
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            bot_token: None,
+            allowed_chat_ids: Vec::new(),
+            tool_verbosity: default_telegram_tool_verbosity(),
+            admin_chat_id: None,
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+/// 对外通道的限流与预算（Telegram / webhook 共用，计数项为 0 表示不限制）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// 单个 chat 每分钟最多处理的消息数
+    #[serde(default = "default_per_chat_per_minute")]
+    pub per_chat_per_minute: u32,
+    /// 所有 chat 合计每分钟最多处理的消息数
+    #[serde(default = "default_global_per_minute")]
+    pub global_per_minute: u32,
+    /// 同时进行中的对话轮次上限（所有 chat 合计）
+    #[serde(default = "default_max_concurrent_turns")]
+    pub max_concurrent_turns: usize,
+    /// 10 分钟内超限几次后临时静音该 chat
+    #[serde(default = "default_mute_after")]
+    pub mute_after: u32,
+    /// 静音时长（分钟）
+    #[serde(default = "default_mute_minutes")]
+    pub mute_minutes: u64,
+    /// 单个 chat 每日 token 上限（估算值，本地时间 0 点重置）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_token_budget: Option<usize>,
+    /// 单个 chat 每日费用上限（美元，按 `[pricing]` 估算；未知价格的模型不计费）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_cost_budget: Option<f64>,
+}
+
+fn default_per_chat_per_minute() -> u32 {
+    10
+}
+
+fn default_global_per_minute() -> u32 {
+    60
+}
+
+fn default_max_concurrent_turns() -> usize {
+    4
+}
+
+fn default_mute_after() -> u32 {
+    3
+}
+
+fn default_mute_minutes() -> u64 {
+    30
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_chat_per_minute: default_per_chat_per_minute(),
+            global_per_minute: default_global_per_minute(),
+            max_concurrent_turns: default_max_concurrent_turns(),
+            mute_after: default_mute_after(),
+            mute_minutes: default_mute_minutes(),
+            daily_token_budget: None,
+            daily_cost_budget: None,
+        }
+    }
 }
 
 /// 默认 Provider 设置
@@ -61,6 +417,27 @@ pub struct ProviderConfig {
     pub model: String,
     /// Claude 使用 "x-api-key"，其他 Provider 为 None（默认 Bearer）
     pub auth_style: Option<String>,
+    /// 附加到每个请求的自定义 headers（API 网关 / 代理用，如组织 ID、路由键）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// 显式指定请求路径（仅 OpenAI 兼容协议）：相对 base_url 的路径，或完整 URL。
+    /// 未设置时自动追加 `/chat/completions`（base_url 已以此结尾则不重复追加）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_path: Option<String>,
+    /// 单次请求最多携带的工具数（未设置时按已知 Provider 取默认值，见 `ToolLimits::for_provider`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tools: Option<usize>,
+    /// tools 数组序列化后的最大字节数（部分兼容网关对请求体很敏感）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tool_schema_bytes: Option<usize>,
+    /// 模型上下文窗口（tokens）。未设置时按模型名查表（见 `providers::context_window`），
+    /// 用于决定历史压缩时机
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<usize>,
+    /// 单次回复的输出 token 上限（请求体 `max_tokens`）。OpenAI 兼容协议未设置时不发送；
+    /// Claude 协议该字段必填，未设置时为 8192
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
 }
 
 /// 记忆系统配置
@@ -68,6 +445,53 @@ pub struct ProviderConfig {
 pub struct MemoryConfig {
     pub backend: String,
     pub auto_save: bool,
+    /// SQLite 无法打开（只读 / 损坏的 home 目录）时降级为不持久化的临时 Memory 继续运行，
+    /// 而不是中止启动。默认 false
+    #[serde(default)]
+    pub fallback_to_noop: bool,
+    /// 保存对话摘要前脱敏（配置中的密钥值与 `sk-...` / `Bearer ...` 等密钥形态），默认 true
+    #[serde(default = "default_true")]
+    pub scrub: bool,
+    /// 脱敏时同时遮盖邮箱地址，默认 false
+    #[serde(default)]
+    pub scrub_emails: bool,
+    /// 额外的脱敏正则（匹配内容替换为 `[已脱敏]`）
+    #[serde(default)]
+    pub scrub_patterns: Vec<String>,
+    /// 哪些对话摘要写入记忆，默认 filtered
+    #[serde(default)]
+    pub store_conversations: StoreConversations,
+    /// filtered：用户消息少于此字符数、且回复也很短的无工具回合不保存，默认 10
+    #[serde(default = "default_store_min_user_chars")]
+    pub store_min_user_chars: usize,
+    /// filtered：回复少于此字符数、且用户消息也很短的无工具回合不保存，默认 80
+    #[serde(default = "default_store_min_reply_chars")]
+    pub store_min_reply_chars: usize,
+}
+
+/// 对话摘要的保存策略（`[memory] store_conversations`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreConversations {
+    /// 每轮都保存
+    All,
+    /// 跳过寒暄、失败回合与连续重复（见 `agent::conversation_memory`）
+    #[default]
+    Filtered,
+    /// 不保存对话摘要（core / 手动写入的记忆不受影响）
+    Off,
+}
+
+fn default_store_min_user_chars() -> usize {
+    10
+}
+
+fn default_store_min_reply_chars() -> usize {
+    80
+}
+
+fn default_true() -> bool {
+    true
 }
 
 /// 安全策略配置
@@ -87,12 +511,27 @@ pub struct SecurityConfig {
     /// 默认 200（KB）；设为 0 禁用 strip（直接走原始 1MB 截断，旧行为）
     #[serde(default = "default_http_strip_threshold_kb")]
     pub http_strip_threshold_kb: usize,
+    /// 要求在 OS 沙箱中运行（`rrclaw sandbox-profile` 生成的包装脚本），默认 false
+    /// 开启后未检测到沙箱时拒绝 Full 模式
+    #[serde(default)]
+    pub require_sandbox: bool,
+    /// 安全模式：不加载任何工具（含 MCP）与技能，纯对话，默认 false
+    /// 也可用 `rrclaw agent --safe` 临时开启
+    #[serde(default)]
+    pub safe_mode: bool,
+    /// 工作区是 git 仓库且有未提交改动时，本会话首次写入前提醒先提交或 `/safety stash`，默认 true
+    #[serde(default = "default_warn_dirty_worktree")]
+    pub warn_dirty_worktree: bool,
 }
 
 fn default_injection_check() -> bool {
     true
 }
 
+fn default_warn_dirty_worktree() -> bool {
+    true
+}
+
 fn default_http_strip_threshold_kb() -> usize {
     200
 }
@@ -135,14 +574,42 @@ pub struct McpConfig {
     /// key = server 名称（用于 tool 前缀）
     #[serde(default)]
     pub servers: HashMap<String, McpServerConfig>,
+    /// system prompt 中 [MCP Tools] 段的字符预算；超出后剩余工具折叠，
+    /// 通过内置 `mcp_list_tools` 浏览（None = 不限制）
+    #[serde(default)]
+    pub prompt_budget_chars: Option<usize>,
 }
 
 /// 定时任务配置
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoutinesConfig {
     /// 静态任务列表（从 config.toml 读取）
     #[serde(default)]
     pub jobs: Vec<RoutineJobConfig>,
+    /// cron 表达式使用的 IANA 时区（如 "Asia/Shanghai"），None = 系统本地时区
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// 注入的历史成功方法连续失败多少次后停止注入（0 = 始终注入）
+    #[serde(default = "default_approach_max_failures")]
+    pub approach_max_failures: u32,
+    /// 全局暂停：启动时不触发任何定时任务（手动 /routine run 仍可执行）
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl Default for RoutinesConfig {
+    fn default() -> Self {
+        Self {
+            jobs: Vec::new(),
+            timezone: None,
+            approach_max_failures: default_approach_max_failures(),
+            paused: false,
+        }
+    }
+}
+
+fn default_approach_max_failures() -> u32 {
+    2
 }
 
 /// 单个静态 Routine 的配置项（映射到 Routine struct）
@@ -150,11 +617,25 @@ pub struct RoutinesConfig {
 pub struct RoutineJobConfig {
     pub name: String,
     pub schedule: String,
+    /// 设置了 `chain` 时可省略
+    #[serde(default)]
     pub message: String,
     #[serde(default = "default_routine_channel")]
     pub channel: String,
+    /// 通道目标：`channel = "webhook"` 时为接收 POST 的 URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_target: Option<String>,
     #[serde(default = "default_routine_enabled")]
     pub enabled: bool,
+    /// 启动时补跑停机期间错过的最近一次触发
+    #[serde(default)]
+    pub catch_up: bool,
+    /// 依次执行的其他 Routine（非空时不发送 `message`，汇总子任务结果）
+    #[serde(default)]
+    pub chain: Vec<String>,
+    /// 执行前置条件：`{ shell = "..." }`（退出码 0）或 `{ http = "..." }`（返回 200），不满足时跳过
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<crate::routines::RoutineCondition>,
 }
 
 fn default_routine_channel() -> String {
@@ -173,6 +654,12 @@ pub struct McpServerConfig {
     /// 只暴露部分 tools（空 = 全部）
     #[serde(default)]
     pub allowed_tools: Vec<String>,
+    /// system prompt 中最多列出的工具数，多出的只能通过 `mcp_list_tools` 找到（None = 不限制）
+    #[serde(default)]
+    pub max_tools: Option<usize>,
+    /// 工具一句话简介的最大字符数（默认 80）
+    #[serde(default)]
+    pub tool_description_max_chars: Option<usize>,
 }
 
 /// MCP 传输方式
@@ -209,6 +696,13 @@ impl Default for MemoryConfig {
         Self {
             backend: "sqlite".to_string(),
             auto_save: true,
+            fallback_to_noop: false,
+            scrub: true,
+            scrub_emails: false,
+            scrub_patterns: Vec::new(),
+            store_conversations: StoreConversations::default(),
+            store_min_user_chars: default_store_min_user_chars(),
+            store_min_reply_chars: default_store_min_reply_chars(),
         }
     }
 }
@@ -228,12 +722,15 @@ impl Default for SecurityConfig {
             http_allowed_hosts: vec![],
             injection_check: true,
             http_strip_threshold_kb: 200,
+            require_sandbox: false,
+            safe_mode: false,
+            warn_dirty_worktree: true,
         }
     }
 }
 
 /// 默认配置 TOML 模板
-const DEFAULT_CONFIG_TOML: &str = r#"[default]
+pub(super) const DEFAULT_CONFIG_TOML: &str = r#"[default]
 provider = "deepseek"
 model = "deepseek-chat"
 temperature = 0.7
@@ -251,14 +748,116 @@ language = "en"     # Interface language: "en" or "zh"
 # model = "claude-sonnet-4-5-20250929"
 # auth_style = "x-api-key"
 
+# 经 API 网关访问时可附加自定义请求头（每个请求都会携带）
+# [providers.deepseek.headers]
+# X-Org-Id = "your-org"
+
+# 网关路径不是 /chat/completions 时可显式指定（相对 base_url，或完整 URL）
+# [providers.gateway]
+# base_url = "https://gw.example.com/openai"
+# endpoint_path = "/deployments/gpt-4o/chat"
+# 工具过多时网关返回 400 可收紧限制（OpenAI 兼容协议默认最多 128 个工具）
+# max_tools = 40
+# max_tool_schema_bytes = 32000
+# 自建 / 未收录的模型可显式指定上下文窗口（tokens），历史压缩按此提前触发
+# context_window = 32768
+# 限制单次回复长度（输出 tokens），防止啰嗦的模型产生高额费用；REPL 中可用 /maxtokens 调整
+# max_tokens = 4096
+
+# 交互界面
+# [cli]
+# show_changes = true   # 每轮结束后显示改动的文件（✎ modified: ... · created: ...）
+# tool_verbosity = "summary"   # 工具状态行下方显示: quiet（不显示）/ summary（简短摘要）/ full（完整输出）
+# queue_messages = true   # 回答进行中输入的消息在本轮结束后自动发送（默认预填到输入行）
+
+# 开发调试：录制 Provider 交互，之后离线按相同顺序回放（也可用 RRCLAW_DEV_MODE / RRCLAW_DEV_CASSETTE）
+# [dev]
+# mode = "record"                       # off / record / replay
+# cassette = "rrclaw-cassette.jsonl"
+
+# 新版本提示：启动 REPL 时每天最多查询一次 GitHub Releases（rrclaw self-update 升级）
+# [update]
+# check = false   # 关闭检查
+
+# 网页搜索（web_search 工具）：Brave Search API 或自建 SearXNG
+# [search]
+# backend = "brave"               # brave / searxng
+# api_key = "BSA..."              # Brave Search API Key
+# url = "http://127.0.0.1:8888"   # SearXNG 地址（内网地址需加入 security.http_allowed_hosts）
+# max_results = 5
+
+# 后台 daemon（rrclaw start）
+# [daemon]
+# metrics_port = 9187          # 在 http://127.0.0.1:9187/metrics 暴露 Prometheus 指标
+# metrics_bind = "127.0.0.1"   # 默认只监听本机
+# confirm_timeout_secs = 120   # Supervised 模式等待 rrclaw chat 确认工具调用的秒数，超时拒绝
+
+# Telegram Bot（rrclaw telegram / daemon）
+# [telegram]
+# bot_token = "123456:ABC..."
+# allowed_chat_ids = [123456789]
+# admin_chat_id = 123456789     # 每日拦截汇总发送到此 chat（默认 allowed_chat_ids 第一个）
+# [telegram.rate_limit]
+# per_chat_per_minute = 10      # 超出回复"请稍后再试"；0 = 不限制
+# global_per_minute = 60
+# max_concurrent_turns = 4
+# mute_after = 3                # 10 分钟内超限 3 次 → 静音 mute_minutes 分钟
+# mute_minutes = 30
+# daily_token_budget = 200000   # 单个 chat 每日 token 上限（估算，0 点重置）
+# daily_cost_budget = 1.0       # 单个 chat 每日费用上限（美元）
+
+# 辅助调用使用更便宜的模型（失败时自动回退到主模型）
+# [agent.routing]        # Phase 1 技能路由
+# provider = "deepseek"
+# model = "deepseek-chat"
+# [agent.summary]        # 长对话历史压缩摘要
+# model = "deepseek-chat"
+
+# 定制 system prompt（写在 [agent] 段）
+# [agent]
+# system_prompt_prefix = "你服务于 ACME 公司的运维团队。"     # 插入在最前面
+# system_prompt_override = "你是 Ops 助手，回答简短、先给命令"  # 替换内置身份描述与决策原则
+
+# 模型价格（美元 / 百万 tokens），/cost 估算用；内置常见模型，可在此覆盖或补充
+# [pricing."glm-4-flash"]
+# input = 0.1
+# output = 0.1
+
+# 离线回显 Provider（无需 API Key，用于测试/演示）
+# [providers.echo]
+# base_url = "echo://"
+# api_key = ""
+# model = "echo"
+# auth_style = "echo"
+
 [memory]
 backend = "sqlite"
 auto_save = true
+# fallback_to_noop = true   # 记忆库无法打开时不中止启动，本次会话不持久化（数据目录不可写时总是如此）
+# scrub = false             # 保存对话摘要前不再脱敏密钥（默认脱敏）
+# scrub_emails = true       # 脱敏时同时遮盖邮箱地址
+# scrub_patterns = ["\\b\\d{11}\\b"]   # 额外的脱敏正则
+# store_conversations = "all"   # 对话摘要保存策略：all / filtered（默认，跳过寒暄、失败与重复）/ off
+# store_min_user_chars = 10     # filtered：用户消息与回复都很短（且未调用工具）的回合不保存
+# store_min_reply_chars = 80
 
 [security]
 autonomy = "supervised"
 allowed_commands = ["ls", "cat", "grep", "find", "echo", "pwd", "git", "head", "tail", "wc", "cargo", "rustc"]
 workspace_only = true
+# require_sandbox = true   # 未在 OS 沙箱中运行时拒绝 Full 模式（见 `rrclaw sandbox-profile`）
+# safe_mode = true         # 不加载任何工具，纯对话（首次试用；也可 `rrclaw agent --safe`）
+# warn_dirty_worktree = false   # 不再提醒工作区有未提交改动（默认首次写入前提醒）
+
+# 定时任务（/routine add 或 [[routines.jobs]]）
+# [routines]
+# timezone = "Asia/Shanghai"   # cron 按此时区解释，默认系统本地时区
+# approach_max_failures = 2     # 记住的成功方法连续失败几次后改为探索新方法
+# paused = true                 # 维护期间暂停全部定时触发（运行时可用 /routine pause / resume 切换）
+# [[routines.jobs]]
+# name = "nightly"
+# schedule = "0 23 * * *"
+# chain = ["backup_notes", "daily_report"]   # 依次执行其他 Routine 并汇总结果（代替 message）
 
 # 可靠性配置（可选）
 # [reliability]
@@ -268,11 +867,37 @@ workspace_only = true
 "#;
 
 impl Config {
-    /// 返回配置文件路径: `~/.rrclaw/config.toml`
+    /// 模型价格：`[pricing]` 优先，其次内置价格表；未知模型返回 None
+    pub fn pricing_for(&self, model: &str) -> Option<ModelPricing> {
+        self.pricing.get(model).copied().or_else(|| {
+            BUILTIN_PRICING
+                .iter()
+                .find(|(name, _)| *name == model)
+                .map(|(_, pricing)| *pricing)
+        })
+    }
+
+    /// 可靠性链描述（`rrclaw config` 展示）：主 Provider → fallback，未配置的 fallback 会被跳过
+    pub fn describe_reliability_chain(&self) -> String {
+        let mut chain = vec![format!("{}/{}", self.default.provider, self.default.model)];
+        for name in &self.reliability.fallback_providers {
+            if self.providers.contains_key(name) {
+                chain.push(name.clone());
+            } else {
+                chain.push(format!("{}（未配置，跳过）", name));
+            }
+        }
+        format!(
+            "{}\n  每个 Provider 最多重试 {} 次，初始退避 {} ms（指数增长）",
+            chain.join(" → "),
+            self.reliability.max_retries,
+            self.reliability.initial_backoff_ms
+        )
+    }
+
+    /// 返回配置文件路径（`--config` → `RRCLAW_HOME` → 旧版 `~/.rrclaw/` → XDG，见 `RrclawPaths`）
     pub fn config_path() -> Result<PathBuf> {
-        let base_dirs = directories::BaseDirs::new()
-            .ok_or_else(|| color_eyre::eyre::eyre!("无法获取 home 目录"))?;
-        Ok(base_dirs.home_dir().join(".rrclaw").join("config.toml"))
+        Ok(super::RrclawPaths::resolve()?.config_file().to_path_buf())
     }
 
     /// 从配置文件读取 http_allowed_hosts（实时读取，无需重启）
@@ -367,6 +992,59 @@ impl Config {
 
         Ok(config)
     }
+
+    /// 校验 provider / model 可作为默认值：Provider 必须已配置；内置 Provider 的模型须在已知列表中
+    /// 或等于 `[providers.x] model`（自定义 Provider 不限制模型名）
+    pub fn check_default(&self, provider: &str, model: &str) -> Result<()> {
+        let provider_config = self.providers.get(provider).ok_or_else(|| {
+            let mut configured: Vec<&str> = self.providers.keys().map(String::as_str).collect();
+            configured.sort_unstable();
+            eyre!(
+                "Provider '{}' 未配置（已配置: {}）",
+                provider,
+                configured.join(", ")
+            )
+        })?;
+        if model.trim().is_empty() {
+            bail!("模型名不能为空");
+        }
+        let known = super::find_provider_info(provider)
+            .map(|info| info.models)
+            .unwrap_or_default();
+        if !known.is_empty() && !known.contains(&model) && provider_config.model != model {
+            bail!(
+                "Provider '{}' 没有模型 '{}'（可选: {}，或 [providers.{}] 中配置的 {}）",
+                provider,
+                model,
+                known.join(", "),
+                provider,
+                provider_config.model
+            );
+        }
+        Ok(())
+    }
+
+    /// 校验后把 provider / model 写入 `path` 的 `[default]` 段（toml_edit，保留注释与格式），
+    /// 并同步更新 `self.default`（`rrclaw agent --save`）
+    pub fn save_default(
+        &mut self,
+        path: &std::path::Path,
+        provider: &str,
+        model: &str,
+    ) -> Result<()> {
+        self.check_default(provider, model)?;
+        let content = std::fs::read_to_string(path).wrap_err("读取配置文件失败")?;
+        let mut doc = content
+            .parse::<toml_edit::DocumentMut>()
+            .wrap_err("解析配置文件失败")?;
+        doc["default"]["provider"] = toml_edit::value(provider);
+        doc["default"]["model"] = toml_edit::value(model);
+        std::fs::write(path, doc.to_string()).wrap_err("写入配置文件失败")?;
+
+        self.default.provider = provider.to_string();
+        self.default.model = model.to_string();
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -390,6 +1068,148 @@ mod tests {
             .contains(&"cargo".to_string()));
     }
 
+    #[test]
+    fn reliability_chain_lists_primary_then_fallbacks() {
+        let mut config = Config::default();
+        config.providers.insert(
+            "claude".to_string(),
+            ProviderConfig {
+                base_url: "https://api.anthropic.com".to_string(),
+                api_key: String::new(),
+                model: "claude-sonnet".to_string(),
+                auth_style: None,
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        config.reliability.fallback_providers = vec!["claude".to_string(), "gone".to_string()];
+        let chain = config.describe_reliability_chain();
+        assert!(chain.starts_with("deepseek/deepseek-chat → claude → gone（未配置，跳过）\n"));
+        assert!(chain.contains("最多重试 3 次，初始退避 500 ms"));
+    }
+
+    #[test]
+    fn save_default_validates_and_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "# 我的配置\n[default]\nprovider = \"deepseek\"\nmodel = \"deepseek-chat\"\n\n\
+             [providers.deepseek]\nbase_url = \"https://api.deepseek.com/v1\"\napi_key = \"k\"\nmodel = \"deepseek-chat\"\n\n\
+             [providers.glm]\nbase_url = \"https://open.bigmodel.cn/api/paas/v4\"\napi_key = \"k\"\nmodel = \"glm-4-flash\"\n",
+        )
+        .unwrap();
+        let mut config = Config::load_from_path(&path).unwrap();
+
+        // 未配置的 Provider / 未知模型：报错且不写文件
+        let before = std::fs::read_to_string(&path).unwrap();
+        let err = config
+            .save_default(&path, "claude", "claude-sonnet-4-5")
+            .unwrap_err();
+        assert!(err.to_string().contains("Provider 'claude' 未配置"));
+        let err = config
+            .save_default(&path, "deepseek", "gpt-4o")
+            .unwrap_err();
+        assert!(err.to_string().contains("没有模型 'gpt-4o'"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+
+        config.save_default(&path, "glm", "glm-4-flash").unwrap();
+        assert_eq!(config.default.provider, "glm");
+        assert_eq!(config.default.model, "glm-4-flash");
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# 我的配置\n"));
+        let reloaded = Config::load_from_path(&path).unwrap();
+        assert_eq!(reloaded.default.provider, "glm");
+        assert_eq!(reloaded.default.model, "glm-4-flash");
+        assert_eq!(reloaded.providers.len(), 2);
+    }
+
+    #[test]
+    fn tool_verbosity_selects_summary_or_full_output() {
+        let full = "line1\nline2";
+        assert_eq!(ToolVerbosity::Quiet.select(Some("exit 0"), full), None);
+        assert_eq!(
+            ToolVerbosity::Summary.select(Some("exit 0"), full),
+            Some("exit 0")
+        );
+        assert_eq!(ToolVerbosity::Summary.select(None, full), None);
+        assert_eq!(ToolVerbosity::Full.select(Some("exit 0"), full), Some(full));
+        assert_eq!(CliConfig::default().tool_verbosity, ToolVerbosity::Summary);
+        assert_eq!(
+            TelegramConfig::default().tool_verbosity,
+            ToolVerbosity::Quiet
+        );
+    }
+
+    #[test]
+    fn pricing_cost_is_per_million_tokens() {
+        let pricing = ModelPricing {
+            input: 0.27,
+            output: 1.10,
+        };
+        assert!((pricing.cost(1_000_000, 0) - 0.27).abs() < 1e-9);
+        assert!((pricing.cost(3_400, 0) - 0.000918).abs() < 1e-9);
+        assert!((pricing.cost(2_000, 1_000) - 0.00164).abs() < 1e-9);
+        assert_eq!(pricing.cost(0, 0), 0.0);
+    }
+
+    #[test]
+    fn pricing_table_overrides_builtin() {
+        let tmp = tempfile::tempdir().unwrap();
+        let toml_path = tmp.path().join("config.toml");
+        std::fs::write(
+            &toml_path,
+            r#"
+[default]
+provider = "deepseek"
+model = "deepseek-chat"
+temperature = 0.7
+
+[providers.deepseek]
+base_url = "https://api.deepseek.com/v1"
+api_key = "key"
+model = "deepseek-chat"
+
+[memory]
+backend = "sqlite"
+auto_save = true
+
+[security]
+autonomy = "supervised"
+allowed_commands = []
+workspace_only = true
+
+[pricing."deepseek-chat"]
+input = 1.0
+output = 2.0
+
+[pricing."my-model"]
+input = 5.0
+output = 5.0
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load_from_path(&toml_path).unwrap();
+        assert_eq!(
+            config.pricing_for("deepseek-chat"),
+            Some(ModelPricing {
+                input: 1.0,
+                output: 2.0
+            })
+        );
+        assert_eq!(config.pricing_for("my-model").unwrap().input, 5.0);
+        assert_eq!(
+            Config::default().pricing_for("gpt-4o").unwrap().output,
+            10.0
+        );
+        assert!(Config::default().pricing_for("unknown-model").is_none());
+    }
+
     #[test]
     fn load_from_toml_file() {
         let tmp = tempfile::tempdir().unwrap();
@@ -492,12 +1312,6 @@ provider = "minimax"
         assert_eq!(config.default.provider, "deepseek");
     }
 
-    #[test]
-    fn config_path_ends_with_rrclaw() {
-        let path = Config::config_path().unwrap();
-        assert!(path.ends_with(".rrclaw/config.toml"));
-    }
-
     #[test]
     fn mcp_stdio_config_parses() {
         let tmp = tempfile::tempdir().unwrap();
@@ -553,6 +1367,83 @@ Authorization = "Bearer token"
         }
     }
 
+    #[test]
+    fn provider_custom_headers_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[providers.gateway]
+base_url = "https://gw.example.com/v1"
+api_key = "k"
+model = "m"
+[providers.gateway.headers]
+X-Org-Id = "org-42"
+
+[providers.plain]
+base_url = "https://api.example.com/v1"
+api_key = "k"
+model = "m"
+"#,
+        )
+        .unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        let gateway = config.providers.get("gateway").unwrap();
+        assert_eq!(gateway.headers.get("X-Org-Id").unwrap(), "org-42");
+        assert!(config.providers.get("plain").unwrap().headers.is_empty());
+    }
+
+    #[test]
+    fn provider_endpoint_path_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[providers.gateway]
+base_url = "https://gw.example.com/openai"
+api_key = "k"
+model = "m"
+endpoint_path = "/deployments/gpt/chat"
+
+[providers.plain]
+base_url = "https://api.example.com/v1"
+api_key = "k"
+model = "m"
+"#,
+        )
+        .unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        assert_eq!(
+            config.providers["gateway"].endpoint_path.as_deref(),
+            Some("/deployments/gpt/chat")
+        );
+        assert!(config.providers["plain"].endpoint_path.is_none());
+    }
+
+    #[test]
+    fn provider_tool_limits_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[providers.gateway]
+base_url = "https://gw.example.com/v1"
+api_key = "k"
+model = "m"
+max_tools = 40
+max_tool_schema_bytes = 32000
+"#,
+        )
+        .unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        let gateway = &config.providers["gateway"];
+        assert_eq!(gateway.max_tools, Some(40));
+        assert_eq!(gateway.max_tool_schema_bytes, Some(32000));
+    }
+
     #[test]
     fn mcp_allowed_tools_filter() {
         let tmp = tempfile::tempdir().unwrap();
@@ -572,6 +1463,86 @@ allowed_tools = ["read_file", "list_dir"]
         let mcp = config.mcp.unwrap();
         let server = mcp.servers.get("fs").unwrap();
         assert_eq!(server.allowed_tools, vec!["read_file", "list_dir"]);
+        assert!(server.max_tools.is_none());
+        assert!(mcp.prompt_budget_chars.is_none());
+    }
+
+    #[test]
+    fn agent_aux_models_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[agent.routing]\nprovider = \"deepseek\"\nmodel = \"deepseek-chat\"\n\n\
+             [agent.summary]\nmodel = \"small\"\n",
+        )
+        .unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        let routing = config.agent.routing.unwrap();
+        assert_eq!(routing.provider.as_deref(), Some("deepseek"));
+        assert_eq!(routing.model.as_deref(), Some("deepseek-chat"));
+        let summary = config.agent.summary.unwrap();
+        assert!(summary.provider.is_none());
+        assert_eq!(summary.model.as_deref(), Some("small"));
+        assert!(Config::default().agent.routing.is_none());
+    }
+
+    #[test]
+    fn routines_timezone_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "[routines]\ntimezone = \"Asia/Shanghai\"\n").unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        assert_eq!(config.routines.timezone.as_deref(), Some("Asia/Shanghai"));
+        assert!(config.routines.jobs.is_empty());
+        assert!(Config::default().routines.timezone.is_none());
+        assert_eq!(config.routines.approach_max_failures, 2);
+        assert!(!config.routines.paused);
+    }
+
+    #[test]
+    fn routines_paused_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "[routines]\npaused = true\n").unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        assert!(config.routines.paused);
+    }
+
+    #[test]
+    fn cli_show_changes_defaults_to_true() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "[cli]\nshow_changes = false\n").unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        assert!(!config.cli.show_changes);
+        assert!(Config::default().cli.show_changes);
+    }
+
+    #[test]
+    fn mcp_tool_limits_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[mcp]
+prompt_budget_chars = 4000
+
+[mcp.servers.github]
+transport = "stdio"
+command = "github-mcp"
+max_tools = 15
+tool_description_max_chars = 60
+"#,
+        )
+        .unwrap();
+        let config = Config::load_from_path(&path).unwrap();
+        let mcp = config.mcp.unwrap();
+        assert_eq!(mcp.prompt_budget_chars, Some(4000));
+        let server = mcp.servers.get("github").unwrap();
+        assert_eq!(server.max_tools, Some(15));
+        assert_eq!(server.tool_description_max_chars, Some(60));
     }
 
     #[test]
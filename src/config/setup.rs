@@ -160,6 +160,12 @@ pub fn run_setup() -> Result<()> {
             api_key,
             model: model.clone(),
             auth_style: info.auth_style.map(|s| s.to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
         },
     );
 
@@ -180,6 +186,13 @@ pub fn run_setup() -> Result<()> {
         reliability: ReliabilityConfig::default(),
         mcp: None,
         routines: RoutinesConfig::default(),
+        cli: Default::default(),
+        agent: Default::default(),
+        daemon: Default::default(),
+        dev: Default::default(),
+        update: Default::default(),
+        search: Default::default(),
+        pricing: Default::default(),
     };
 
     // 写入配置文件
@@ -194,9 +207,11 @@ pub fn run_setup() -> Result<()> {
     if lang.is_english() {
         println!("✅ Config saved to: {}", config_path.display());
         println!("\nYou can edit this file at any time to add more providers or adjust settings.");
+        println!("Run `rrclaw setup mcp` to add an MCP server.");
     } else {
         println!("✅ 配置已保存到: {}", config_path.display());
         println!("\n你可以随时编辑该文件添加更多 Provider 或调整设置。");
+        println!("运行 `rrclaw setup mcp` 可添加 MCP Server。");
     }
 
     Ok(())
@@ -0,0 +1,522 @@
+//! MCP Server 配置向导（`rrclaw setup mcp`）
+//!
+//! 交互收集 stdio（命令行 + 环境变量）或 SSE（URL + 请求头）参数，用 `mcp::probe_server`
+//! 测试连接并列出工具，成功（或用户确认忽略失败）后用 toml_edit 写入 `[mcp.servers.<名称>]`，
+//! 配置文件中已有的内容和注释保持不变。
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+
+use color_eyre::eyre::{bail, eyre, Context, Report, Result};
+use dialoguer::{Confirm, Input, Select};
+
+use super::schema::{Config, McpServerConfig, McpTransport};
+use crate::i18n::Language;
+
+/// 连接测试超时（stdio server 首次 `npx` 下载可能较慢）
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+/// 连接成功后最多列出的工具名数
+const MAX_LISTED_TOOLS: usize = 10;
+
+/// 返回当前语言对应的字符串
+fn t(lang: Language, zh: &'static str, en: &'static str) -> &'static str {
+    if lang.is_english() {
+        en
+    } else {
+        zh
+    }
+}
+
+/// 向导收集的原始输入
+#[derive(Debug, Clone)]
+pub enum McpServerInput {
+    /// 完整命令行（shell 风格引号），环境变量每项 `KEY=VALUE`
+    Stdio {
+        command_line: String,
+        env: Vec<String>,
+    },
+    /// 服务地址，请求头每项 `Name: value`
+    Sse { url: String, headers: Vec<String> },
+}
+
+/// 由向导输入构造配置项；`allowed_tools` 为逗号分隔的工具名（空 = 全部）
+pub fn build_server_config(input: &McpServerInput, allowed_tools: &str) -> Result<McpServerConfig> {
+    let transport = match input {
+        McpServerInput::Stdio { command_line, env } => {
+            let mut words = shell_words::split(command_line)
+                .map_err(|e| eyre!("命令行解析失败: {}", e))?
+                .into_iter();
+            let Some(command) = words.next() else {
+                bail!("命令不能为空");
+            };
+            McpTransport::Stdio {
+                command,
+                args: words.collect(),
+                env: parse_pairs(env, '=')?,
+            }
+        }
+        McpServerInput::Sse { url, headers } => {
+            let url = url.trim();
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                bail!("URL 必须以 http:// 或 https:// 开头: {}", url);
+            }
+            McpTransport::Sse {
+                url: url.to_string(),
+                headers: parse_pairs(headers, ':')?,
+            }
+        }
+    };
+    Ok(McpServerConfig {
+        transport,
+        allowed_tools: allowed_tools
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect(),
+        max_tools: None,
+        tool_description_max_chars: None,
+    })
+}
+
+/// 解析 `KEY=VALUE` / `Name: value` 列表（空行忽略）
+fn parse_pairs(items: &[String], sep: char) -> Result<HashMap<String, String>> {
+    let mut pairs = HashMap::new();
+    for item in items.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let Some((key, value)) = item.split_once(sep) else {
+            bail!("格式应为 KEY{}VALUE: {}", sep, item);
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            bail!("键不能为空: {}", item);
+        }
+        pairs.insert(key.to_string(), value.trim().to_string());
+    }
+    Ok(pairs)
+}
+
+/// Server 名用作工具前缀（`mcp_<名称>_<工具>`），只允许字母、数字、`_`、`-`
+pub fn validate_server_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!("名称只能包含字母、数字、_ 和 -");
+    }
+    Ok(())
+}
+
+/// 写入（或覆盖）`[mcp.servers.<name>]`，其余内容保持不变
+pub fn save_server_to_config(path: &Path, name: &str, server: &McpServerConfig) -> Result<()> {
+    let content = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("读取配置文件失败: {}", path.display()))?;
+    let mut doc = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| eyre!("解析配置文件失败: {}", e))?;
+
+    let servers = implicit_table(doc.as_table_mut(), "mcp")?;
+    let servers = implicit_table(servers, "servers")?;
+
+    let mut table = toml_edit::Table::new();
+    match &server.transport {
+        McpTransport::Stdio { command, args, env } => {
+            table["transport"] = toml_edit::value("stdio");
+            table["command"] = toml_edit::value(command);
+            if !args.is_empty() {
+                table["args"] = toml_edit::value(string_array(args));
+            }
+            if !env.is_empty() {
+                table["env"] = toml_edit::value(inline_map(env));
+            }
+        }
+        McpTransport::Sse { url, headers } => {
+            table["transport"] = toml_edit::value("sse");
+            table["url"] = toml_edit::value(url);
+            if !headers.is_empty() {
+                table["headers"] = toml_edit::value(inline_map(headers));
+            }
+        }
+    }
+    if !server.allowed_tools.is_empty() {
+        table["allowed_tools"] = toml_edit::value(string_array(&server.allowed_tools));
+    }
+    servers.insert(name, toml_edit::Item::Table(table));
+
+    std::fs::write(path, doc.to_string())
+        .wrap_err_with(|| format!("写入配置文件失败: {}", path.display()))
+}
+
+/// 取子表，不存在时创建隐式表（不单独输出 `[mcp]` 表头）
+fn implicit_table<'a>(
+    parent: &'a mut toml_edit::Table,
+    key: &str,
+) -> Result<&'a mut toml_edit::Table> {
+    parent
+        .entry(key)
+        .or_insert_with(|| {
+            let mut table = toml_edit::Table::new();
+            table.set_implicit(true);
+            toml_edit::Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| eyre!("配置中的 `{}` 不是表", key))
+}
+
+fn string_array(items: &[String]) -> toml_edit::Array {
+    items.iter().map(String::as_str).collect()
+}
+
+/// 按键排序输出，结果稳定
+fn inline_map(map: &HashMap<String, String>) -> toml_edit::InlineTable {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+    let mut table = toml_edit::InlineTable::new();
+    for key in keys {
+        table.insert(key.as_str(), map[key].as_str().into());
+    }
+    table
+}
+
+/// 测试连接后写入配置，返回是否已写入
+///
+/// 连接失败时由 `keep_on_failure` 决定是否仍然写入（如 server 需要稍后才能启动的服务）。
+pub async fn probe_and_save<P, Fut>(
+    path: &Path,
+    name: &str,
+    server: &McpServerConfig,
+    probe: P,
+    keep_on_failure: impl FnOnce(&Report) -> bool,
+) -> Result<bool>
+where
+    P: FnOnce(String, McpServerConfig) -> Fut,
+    Fut: Future<Output = Result<Vec<String>>>,
+{
+    let lang = Config::get_language();
+    println!("{}", t(lang, "正在测试连接...", "Testing connection..."));
+    match probe(name.to_string(), server.clone()).await {
+        Ok(tools) => print_tools(lang, &tools),
+        Err(e) => {
+            println!("✗ {}: {:#}", t(lang, "连接失败", "Connection failed"), e);
+            if !keep_on_failure(&e) {
+                return Ok(false);
+            }
+        }
+    }
+    save_server_to_config(path, name, server)?;
+    Ok(true)
+}
+
+fn print_tools(lang: Language, tools: &[String]) {
+    let mut listed = tools
+        .iter()
+        .take(MAX_LISTED_TOOLS)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if tools.len() > MAX_LISTED_TOOLS {
+        listed.push_str(", ...");
+    }
+    if lang.is_english() {
+        println!("✓ Connected, {} tool(s): {}", tools.len(), listed);
+    } else {
+        println!("✓ 连接成功，共 {} 个工具: {}", tools.len(), listed);
+    }
+}
+
+/// 运行 MCP Server 配置向导
+pub async fn run_mcp_setup() -> Result<()> {
+    let lang = Config::get_language();
+    let config_path = Config::config_path()?;
+    if !config_path.exists() {
+        bail!(
+            "{}",
+            t(
+                lang,
+                "配置文件不存在，请先运行 `rrclaw setup` 或 `rrclaw init`",
+                "Config file not found, run `rrclaw setup` or `rrclaw init` first"
+            )
+        );
+    }
+    let existing = Config::load_from_path(&config_path)
+        .ok()
+        .and_then(|c| c.mcp)
+        .map(|m| m.servers)
+        .unwrap_or_default();
+
+    println!(
+        "{}\n",
+        t(lang, "🔌 添加 MCP Server", "🔌 Add an MCP server")
+    );
+
+    let name: String = Input::new()
+        .with_prompt(t(
+            lang,
+            "名称（用作工具前缀）",
+            "Name (used as tool prefix)",
+        ))
+        .validate_with(|s: &String| validate_server_name(s.trim()).map_err(|e| e.to_string()))
+        .interact_text()?;
+    let name = name.trim().to_string();
+    if existing.contains_key(&name)
+        && !Confirm::new()
+            .with_prompt(t(
+                lang,
+                "同名 Server 已存在，是否覆盖？",
+                "A server with this name exists. Overwrite?",
+            ))
+            .default(false)
+            .interact()?
+    {
+        return Ok(());
+    }
+
+    let transport = Select::new()
+        .with_prompt(t(lang, "连接方式", "Transport"))
+        .items([
+            t(lang, "stdio（本地命令）", "stdio (local command)"),
+            t(lang, "sse（远程 HTTP）", "sse (remote HTTP)"),
+        ])
+        .default(0)
+        .interact()?;
+
+    let input = if transport == 0 {
+        let command_line: String = Input::new()
+            .with_prompt(t(
+                lang,
+                "命令行（如 npx -y @modelcontextprotocol/server-filesystem /tmp）",
+                "Command line (e.g. npx -y @modelcontextprotocol/server-filesystem /tmp)",
+            ))
+            .interact_text()?;
+        let env = read_list(t(
+            lang,
+            "环境变量 KEY=VALUE（回车结束）",
+            "Env var KEY=VALUE (empty to finish)",
+        ))?;
+        McpServerInput::Stdio { command_line, env }
+    } else {
+        let url: String = Input::new()
+            .with_prompt(t(lang, "服务地址", "Server URL"))
+            .interact_text()?;
+        let headers = read_list(t(
+            lang,
+            "请求头 Name: value（回车结束）",
+            "Header Name: value (empty to finish)",
+        ))?;
+        McpServerInput::Sse { url, headers }
+    };
+    let allowed_tools: String = Input::new()
+        .with_prompt(t(
+            lang,
+            "只暴露的工具（逗号分隔，留空 = 全部）",
+            "Only expose these tools (comma separated, empty = all)",
+        ))
+        .allow_empty(true)
+        .interact_text()?;
+
+    let server = build_server_config(&input, &allowed_tools)?;
+    let saved = probe_and_save(
+        &config_path,
+        &name,
+        &server,
+        |name, server| async move {
+            match tokio::time::timeout(PROBE_TIMEOUT, crate::mcp::probe_server(&name, &server))
+                .await
+            {
+                Ok(result) => result,
+                Err(_) => Err(eyre!("{} 秒内未完成握手", PROBE_TIMEOUT.as_secs())),
+            }
+        },
+        |_| {
+            Confirm::new()
+                .with_prompt(t(lang, "仍然写入配置？", "Save the config anyway?"))
+                .default(false)
+                .interact()
+                .unwrap_or(false)
+        },
+    )
+    .await?;
+
+    if saved {
+        if lang.is_english() {
+            println!(
+                "✅ Saved [mcp.servers.{}] to {} (restart rrclaw or run `rrclaw restart` to load it)",
+                name,
+                config_path.display()
+            );
+        } else {
+            println!(
+                "✅ 已写入 [mcp.servers.{}] 到 {}（重启 rrclaw 或运行 `rrclaw restart` 后生效）",
+                name,
+                config_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 逐行读取列表，空行结束
+fn read_list(prompt: &str) -> Result<Vec<String>> {
+    let mut items = Vec::new();
+    loop {
+        let line: String = Input::new()
+            .with_prompt(prompt)
+            .allow_empty(true)
+            .interact_text()?;
+        if line.trim().is_empty() {
+            return Ok(items);
+        }
+        items.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config() -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "# my config\n[default]\nprovider = \"deepseek\"\nmodel = \"deepseek-chat\"\n",
+        )
+        .unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn stdio_input_splits_command_line_and_env() {
+        let input = McpServerInput::Stdio {
+            command_line: "npx -y @modelcontextprotocol/server-filesystem \"/tmp/my dir\""
+                .to_string(),
+            env: vec!["NODE_ENV=production".to_string(), "".to_string()],
+        };
+        let server = build_server_config(&input, "read_file, list_directory").unwrap();
+        match &server.transport {
+            McpTransport::Stdio { command, args, env } => {
+                assert_eq!(command, "npx");
+                assert_eq!(
+                    args,
+                    &[
+                        "-y",
+                        "@modelcontextprotocol/server-filesystem",
+                        "/tmp/my dir"
+                    ]
+                );
+                assert_eq!(env["NODE_ENV"], "production");
+            }
+            other => panic!("unexpected transport: {:?}", other),
+        }
+        assert_eq!(server.allowed_tools, vec!["read_file", "list_directory"]);
+    }
+
+    #[test]
+    fn sse_input_parses_headers_and_rejects_bad_input() {
+        let input = McpServerInput::Sse {
+            url: " https://mcp.example.com/sse ".to_string(),
+            headers: vec!["Authorization: Bearer abc".to_string()],
+        };
+        let server = build_server_config(&input, "").unwrap();
+        match &server.transport {
+            McpTransport::Sse { url, headers } => {
+                assert_eq!(url, "https://mcp.example.com/sse");
+                assert_eq!(headers["Authorization"], "Bearer abc");
+            }
+            other => panic!("unexpected transport: {:?}", other),
+        }
+        assert!(server.allowed_tools.is_empty());
+
+        let bad_url = McpServerInput::Sse {
+            url: "mcp.example.com".to_string(),
+            headers: vec![],
+        };
+        assert!(build_server_config(&bad_url, "").is_err());
+        let bad_header = McpServerInput::Sse {
+            url: "https://x".to_string(),
+            headers: vec!["no separator".to_string()],
+        };
+        assert!(build_server_config(&bad_header, "").is_err());
+        let empty = McpServerInput::Stdio {
+            command_line: "  ".to_string(),
+            env: vec![],
+        };
+        assert!(build_server_config(&empty, "").is_err());
+        assert!(validate_server_name("fs").is_ok());
+        assert!(validate_server_name("my server").is_err());
+    }
+
+    #[tokio::test]
+    async fn probe_success_writes_entry_that_loads_back() {
+        let (_dir, path) = temp_config();
+        let server = build_server_config(
+            &McpServerInput::Stdio {
+                command_line: "npx -y server-fs /tmp".to_string(),
+                env: vec!["TOKEN=abc".to_string()],
+            },
+            "",
+        )
+        .unwrap();
+        let saved = probe_and_save(
+            &path,
+            "fs",
+            &server,
+            |_, _| async { Ok::<_, Report>(vec!["read_file".to_string()]) },
+            |_| panic!("should not ask"),
+        )
+        .await
+        .unwrap();
+        assert!(saved);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# my config\n"));
+        assert!(crate::config::validate::unknown_keys(&content).is_empty());
+        let config = Config::load_from_path(&path).unwrap();
+        let fs = &config.mcp.unwrap().servers["fs"];
+        match &fs.transport {
+            McpTransport::Stdio { command, args, env } => {
+                assert_eq!(command, "npx");
+                assert_eq!(args, &["-y", "server-fs", "/tmp"]);
+                assert_eq!(env["TOKEN"], "abc");
+            }
+            other => panic!("unexpected transport: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn probe_failure_saves_only_when_confirmed() {
+        let (_dir, path) = temp_config();
+        let server = build_server_config(
+            &McpServerInput::Sse {
+                url: "https://mcp.example.com/sse".to_string(),
+                headers: vec![],
+            },
+            "search",
+        )
+        .unwrap();
+        let failing = |_: String, _: McpServerConfig| async {
+            Err::<Vec<String>, _>(eyre!("connection refused"))
+        };
+
+        let saved = probe_and_save(&path, "remote", &server, failing, |_| false)
+            .await
+            .unwrap();
+        assert!(!saved);
+        assert!(Config::load_from_path(&path).unwrap().mcp.is_none());
+
+        let saved = probe_and_save(&path, "remote", &server, failing, |e| {
+            format!("{}", e).contains("refused")
+        })
+        .await
+        .unwrap();
+        assert!(saved);
+        let config = Config::load_from_path(&path).unwrap();
+        let remote = &config.mcp.unwrap().servers["remote"];
+        assert_eq!(remote.allowed_tools, vec!["search"]);
+        assert!(
+            matches!(&remote.transport, McpTransport::Sse { url, .. } if url == "https://mcp.example.com/sse")
+        );
+    }
+}
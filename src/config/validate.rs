@@ -0,0 +1,631 @@
+//! 配置校验
+//!
+//! 解析成功不代表配置有效：引用了不存在的 Provider、写错的 cron、拼错的键名都会被静默忽略。
+//! `Config::validate` 在启动时（REPL 横幅之前）和 `rrclaw doctor` 中运行，只产出警告，不阻止启动。
+//!
+//! 未知键用软检查（`unknown_keys`）：serde `deny_unknown_fields` 会让旧版本遗留的键直接导致启动失败，
+//! 这里只提示，不报错。
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use super::{Config, SearchBackendKind};
+use crate::routines::RoutineCondition;
+
+/// 单条配置警告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationWarning {
+    /// 出问题的配置项（如 `reliability.fallback_providers`）
+    pub key: String,
+    pub message: String,
+}
+
+impl ValidationWarning {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+impl Config {
+    /// 检查语义层面的配置问题（不含未知键，见 `unknown_keys`）
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        let mut warnings = Vec::new();
+
+        if !self.providers.contains_key(&self.default.provider) {
+            warnings.push(ValidationWarning::new(
+                "default.provider",
+                format!(
+                    "Provider '{}' 未在 [providers] 中配置",
+                    self.default.provider
+                ),
+            ));
+        }
+
+        for name in &self.reliability.fallback_providers {
+            if !self.providers.contains_key(name) {
+                warnings.push(ValidationWarning::new(
+                    "reliability.fallback_providers",
+                    format!("Provider '{}' 未在 [providers] 中配置，将被忽略", name),
+                ));
+            }
+        }
+
+        for (key, aux) in [
+            ("agent.routing.provider", &self.agent.routing),
+            ("agent.summary.provider", &self.agent.summary),
+        ] {
+            if let Some(name) = aux.as_ref().and_then(|a| a.provider.as_ref()) {
+                if !self.providers.contains_key(name) {
+                    warnings.push(ValidationWarning::new(
+                        key,
+                        format!("Provider '{}' 未在 [providers] 中配置，将使用主模型", name),
+                    ));
+                }
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for job in &self.routines.jobs {
+            let key = format!("routines.jobs.{}", job.name);
+            if !seen.insert(job.name.as_str()) {
+                warnings.push(ValidationWarning::new(
+                    key.clone(),
+                    "同名 Routine 重复定义，仅最后一个生效",
+                ));
+            }
+            if let Err(e) = croner::Cron::new(&job.schedule).with_dom_and_dow().parse() {
+                warnings.push(ValidationWarning::new(
+                    format!("{}.schedule", key),
+                    format!("cron 表达式无效 ({}): {}", job.schedule, e),
+                ));
+            }
+            if job.chain.contains(&job.name) {
+                warnings.push(ValidationWarning::new(
+                    format!("{}.chain", key),
+                    "chain 引用了自身，执行时会被拒绝",
+                ));
+            } else if job.chain.is_empty() && job.message.trim().is_empty() {
+                warnings.push(ValidationWarning::new(
+                    format!("{}.message", key),
+                    "message 为空且未设置 chain，触发时无事可做",
+                ));
+            }
+            match &job.condition {
+                Some(RoutineCondition::Shell(command)) if command.trim().is_empty() => {
+                    warnings.push(ValidationWarning::new(
+                        format!("{}.condition", key),
+                        "shell 条件为空，执行时总是被视为满足",
+                    ));
+                }
+                Some(RoutineCondition::Http(url))
+                    if !url.starts_with("http://") && !url.starts_with("https://") =>
+                {
+                    warnings.push(ValidationWarning::new(
+                        format!("{}.condition", key),
+                        format!(
+                            "http 条件应为 http(s):// URL，当前为 '{}'，条件永远不满足",
+                            url
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+            if job.channel == "webhook" {
+                match job.channel_target.as_deref() {
+                    None | Some("") => warnings.push(ValidationWarning::new(
+                        format!("{}.channel_target", key),
+                        "channel = \"webhook\" 需要 channel_target（接收 POST 的 URL），否则结果降级为 cli",
+                    )),
+                    Some(url) if !url.starts_with("http://") && !url.starts_with("https://") => {
+                        warnings.push(ValidationWarning::new(
+                            format!("{}.channel_target", key),
+                            format!("webhook 地址应为 http(s):// URL，当前为 '{}'", url),
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        for pattern in &self.memory.scrub_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                warnings.push(ValidationWarning::new(
+                    "memory.scrub_patterns",
+                    format!("正则 '{}' 无效，脱敏时跳过: {}", pattern, e),
+                ));
+            }
+        }
+
+        match self.search.backend {
+            Some(SearchBackendKind::Brave) if self.search.api_key.is_empty() => {
+                warnings.push(ValidationWarning::new(
+                    "search.api_key",
+                    "backend = \"brave\" 需要 Brave Search API Key，web_search 调用会失败",
+                ))
+            }
+            Some(SearchBackendKind::Searxng)
+                if self.search.url.as_deref().unwrap_or("").is_empty() =>
+            {
+                warnings.push(ValidationWarning::new(
+                    "search.url",
+                    "backend = \"searxng\" 需要 SearXNG 实例地址，web_search 调用会失败",
+                ))
+            }
+            _ => {}
+        }
+
+        let telegram_jobs: Vec<&str> = self
+            .routines
+            .jobs
+            .iter()
+            .filter(|job| job.enabled && job.channel == "telegram")
+            .map(|job| job.name.as_str())
+            .collect();
+        if !telegram_jobs.is_empty() {
+            match &self.telegram {
+                None => warnings.push(ValidationWarning::new(
+                    "telegram",
+                    format!(
+                        "Routine {} 发送到 Telegram，但未配置 [telegram]",
+                        telegram_jobs.join(", ")
+                    ),
+                )),
+                Some(tg) if tg.allowed_chat_ids.is_empty() => {
+                    warnings.push(ValidationWarning::new(
+                        "telegram.allowed_chat_ids",
+                        format!("为空，Routine {} 的结果无处发送", telegram_jobs.join(", ")),
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+
+        warnings
+    }
+}
+
+/// 校验配置文件：语义检查 + 未知键（文件读取失败时只做语义检查）
+pub fn check_config_file(config: &Config, path: &Path) -> Vec<ValidationWarning> {
+    let mut warnings = config.validate();
+    if let Ok(content) = std::fs::read_to_string(path) {
+        warnings.extend(unknown_keys(&content));
+    }
+    warnings
+}
+
+/// 各配置段已知的键；`*` 段为用户自定义名称（Provider 名、模型名、MCP server 名）
+pub(super) const KNOWN_KEYS: &[(&str, &[&str])] = &[
+    (
+        "",
+        &[
+            "default",
+            "providers",
+            "memory",
+            "security",
+            "telegram",
+            "reliability",
+            "mcp",
+            "routines",
+            "cli",
+            "agent",
+            "daemon",
+            "dev",
+            "update",
+            "search",
+            "pricing",
+        ],
+    ),
+    ("default", &["provider", "model", "temperature", "language"]),
+    (
+        "providers.*",
+        &[
+            "base_url",
+            "api_key",
+            "model",
+            "auth_style",
+            "headers",
+            "endpoint_path",
+            "max_tools",
+            "max_tool_schema_bytes",
+            "context_window",
+            "max_tokens",
+        ],
+    ),
+    (
+        "memory",
+        &[
+            "backend",
+            "auto_save",
+            "fallback_to_noop",
+            "scrub",
+            "scrub_emails",
+            "scrub_patterns",
+            "store_conversations",
+            "store_min_user_chars",
+            "store_min_reply_chars",
+        ],
+    ),
+    (
+        "security",
+        &[
+            "autonomy",
+            "allowed_commands",
+            "workspace_only",
+            "http_allowed_hosts",
+            "injection_check",
+            "http_strip_threshold_kb",
+            "require_sandbox",
+            "safe_mode",
+            "warn_dirty_worktree",
+        ],
+    ),
+    (
+        "telegram",
+        &[
+            "bot_token",
+            "allowed_chat_ids",
+            "tool_verbosity",
+            "admin_chat_id",
+            "rate_limit",
+        ],
+    ),
+    (
+        "telegram.rate_limit",
+        &[
+            "per_chat_per_minute",
+            "global_per_minute",
+            "max_concurrent_turns",
+            "mute_after",
+            "mute_minutes",
+            "daily_token_budget",
+            "daily_cost_budget",
+        ],
+    ),
+    (
+        "reliability",
+        &["max_retries", "initial_backoff_ms", "fallback_providers"],
+    ),
+    ("mcp", &["servers", "prompt_budget_chars"]),
+    (
+        "mcp.servers.*",
+        &[
+            "transport",
+            "command",
+            "args",
+            "env",
+            "url",
+            "headers",
+            "allowed_tools",
+            "max_tools",
+            "tool_description_max_chars",
+        ],
+    ),
+    (
+        "routines",
+        &["jobs", "timezone", "approach_max_failures", "paused"],
+    ),
+    (
+        "routines.jobs",
+        &[
+            "name",
+            "schedule",
+            "message",
+            "channel",
+            "enabled",
+            "catch_up",
+            "chain",
+            "condition",
+            "channel_target",
+        ],
+    ),
+    ("cli", &["show_changes", "tool_verbosity", "queue_messages"]),
+    (
+        "daemon",
+        &["metrics_port", "metrics_bind", "confirm_timeout_secs"],
+    ),
+    ("dev", &["mode", "cassette"]),
+    ("update", &["check"]),
+    ("search", &["backend", "api_key", "url", "max_results"]),
+    (
+        "agent",
+        &[
+            "routing",
+            "summary",
+            "system_prompt_prefix",
+            "system_prompt_override",
+        ],
+    ),
+    ("agent.routing", &["provider", "model"]),
+    ("agent.summary", &["provider", "model"]),
+    ("pricing.*", &["input", "output"]),
+];
+
+/// 子表的键是用户自定义名称的配置段
+pub(super) const NAMED_SECTIONS: &[&str] = &["providers", "pricing", "mcp.servers"];
+
+/// 找出 TOML 中不属于任何已知配置项的键（解析失败返回空，由加载流程报错）
+pub fn unknown_keys(toml: &str) -> Vec<ValidationWarning> {
+    let mut warnings = Vec::new();
+    if let Ok(doc) = toml.parse::<DocumentMut>() {
+        walk(doc.as_table(), "", "", &mut warnings);
+    }
+    warnings
+}
+
+fn walk(table: &dyn TableLike, schema: &str, path: &str, out: &mut Vec<ValidationWarning>) {
+    // 未列出的段（headers / env 等自由映射）不检查
+    let Some((_, known)) = KNOWN_KEYS.iter().find(|(section, _)| *section == schema) else {
+        return;
+    };
+    for (key, item) in table.iter() {
+        let full = join(path, key);
+        if !known.contains(&key) {
+            out.push(ValidationWarning::new(full, "未知配置项，将被忽略"));
+            continue;
+        }
+        let child_schema = join(schema, key);
+        if NAMED_SECTIONS.contains(&child_schema.as_str()) {
+            if let Some(named) = item.as_table_like() {
+                for (name, entry) in named.iter() {
+                    if let Some(entry) = entry.as_table_like() {
+                        walk(
+                            entry,
+                            &format!("{}.*", child_schema),
+                            &join(&full, name),
+                            out,
+                        );
+                    }
+                }
+            }
+        } else {
+            for child in child_tables(item) {
+                walk(child, &child_schema, &full, out);
+            }
+        }
+    }
+}
+
+/// 表、内联表、表数组（`[[routines.jobs]]`）及内联表数组的每个元素
+fn child_tables(item: &Item) -> Vec<&dyn TableLike> {
+    if let Some(table) = item.as_table_like() {
+        return vec![table];
+    }
+    if let Some(array) = item.as_array_of_tables() {
+        return array.iter().map(|t| t as &dyn TableLike).collect();
+    }
+    if let Some(array) = item.as_array() {
+        return array
+            .iter()
+            .filter_map(|v| v.as_inline_table())
+            .map(|t| t as &dyn TableLike)
+            .collect();
+    }
+    Vec::new()
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ProviderConfig, RoutineJobConfig, TelegramConfig};
+
+    fn provider() -> ProviderConfig {
+        ProviderConfig {
+            base_url: "https://api.deepseek.com/v1".to_string(),
+            api_key: "key".to_string(),
+            model: "deepseek-chat".to_string(),
+            auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        }
+    }
+
+    fn valid_config() -> Config {
+        let mut config = Config::default();
+        config.providers.insert("deepseek".to_string(), provider());
+        config
+    }
+
+    fn job(name: &str, schedule: &str, channel: &str) -> RoutineJobConfig {
+        RoutineJobConfig {
+            name: name.to_string(),
+            schedule: schedule.to_string(),
+            message: "hi".to_string(),
+            channel: channel.to_string(),
+            enabled: true,
+            catch_up: false,
+            chain: vec![],
+            condition: None,
+            channel_target: None,
+        }
+    }
+
+    fn keys(warnings: &[ValidationWarning]) -> Vec<&str> {
+        warnings.iter().map(|w| w.key.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_warnings() {
+        assert!(valid_config().validate().is_empty());
+    }
+
+    #[test]
+    fn missing_default_provider_is_reported() {
+        let warnings = Config::default().validate();
+        assert_eq!(keys(&warnings), vec!["default.provider"]);
+        assert!(warnings[0].message.contains("deepseek"));
+    }
+
+    #[test]
+    fn unknown_fallback_provider_is_reported() {
+        let mut config = valid_config();
+        config.reliability.fallback_providers = vec!["deepseek".to_string(), "glm".to_string()];
+        let warnings = config.validate();
+        assert_eq!(keys(&warnings), vec!["reliability.fallback_providers"]);
+        assert!(warnings[0].message.contains("'glm'"));
+    }
+
+    #[test]
+    fn duplicate_routine_names_are_reported() {
+        let mut config = valid_config();
+        config.routines.jobs = vec![
+            job("daily", "0 8 * * *", "cli"),
+            job("daily", "0 9 * * *", "cli"),
+        ];
+        assert_eq!(keys(&config.validate()), vec!["routines.jobs.daily"]);
+    }
+
+    #[test]
+    fn invalid_cron_is_reported() {
+        let mut config = valid_config();
+        config.routines.jobs = vec![job("broken", "every morning", "cli")];
+        assert_eq!(
+            keys(&config.validate()),
+            vec!["routines.jobs.broken.schedule"]
+        );
+    }
+
+    #[test]
+    fn invalid_scrub_pattern_is_reported() {
+        let mut config = valid_config();
+        config.memory.scrub_patterns = vec![r"\d{11}".to_string(), "(unclosed".to_string()];
+        assert_eq!(keys(&config.validate()), vec!["memory.scrub_patterns"]);
+    }
+
+    #[test]
+    fn self_referencing_chain_is_reported() {
+        let mut config = valid_config();
+        let mut nightly = job("nightly", "0 23 * * *", "cli");
+        nightly.message = String::new();
+        nightly.chain = vec!["daily".to_string(), "nightly".to_string()];
+        config.routines.jobs = vec![job("daily", "0 8 * * *", "cli"), nightly];
+        assert_eq!(
+            keys(&config.validate()),
+            vec!["routines.jobs.nightly.chain"]
+        );
+
+        config.routines.jobs[1].chain = vec![];
+        assert_eq!(
+            keys(&config.validate()),
+            vec!["routines.jobs.nightly.message"]
+        );
+    }
+
+    #[test]
+    fn malformed_routine_condition_is_reported() {
+        let mut config = valid_config();
+        let mut deploy = job("deploy", "0 9 * * *", "cli");
+        deploy.condition = Some(RoutineCondition::Http("ci.example.com/status".to_string()));
+        config.routines.jobs = vec![deploy];
+        assert_eq!(
+            keys(&config.validate()),
+            vec!["routines.jobs.deploy.condition"]
+        );
+
+        config.routines.jobs[0].condition = Some(RoutineCondition::Http(
+            "https://ci.example.com/status".to_string(),
+        ));
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn webhook_routine_needs_an_http_target() {
+        let mut config = valid_config();
+        config.routines.jobs = vec![job("digest", "0 18 * * *", "webhook")];
+        assert_eq!(
+            keys(&config.validate()),
+            vec!["routines.jobs.digest.channel_target"]
+        );
+
+        config.routines.jobs[0].channel_target = Some("hooks.example.com".to_string());
+        assert_eq!(
+            keys(&config.validate()),
+            vec!["routines.jobs.digest.channel_target"]
+        );
+
+        config.routines.jobs[0].channel_target = Some("https://hooks.example.com/x".to_string());
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn telegram_routine_without_chat_ids_is_reported() {
+        let mut config = valid_config();
+        config.routines.jobs = vec![job("news", "0 8 * * *", "telegram")];
+        assert_eq!(keys(&config.validate()), vec!["telegram"]);
+
+        config.telegram = Some(TelegramConfig {
+            bot_token: Some("token".to_string()),
+            allowed_chat_ids: vec![],
+            ..Default::default()
+        });
+        assert_eq!(keys(&config.validate()), vec!["telegram.allowed_chat_ids"]);
+
+        config.telegram.as_mut().unwrap().allowed_chat_ids = vec![42];
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_softly() {
+        let toml = r#"
+[default]
+provider = "deepseek"
+modle = "typo"
+
+[providers.deepseek]
+base_url = "https://api.deepseek.com/v1"
+api_key = "key"
+model = "deepseek-chat"
+timeout = 30
+
+[providers.deepseek.headers]
+X-Anything = "free-form"
+
+[[routines.jobs]]
+name = "daily"
+schedule = "0 8 * * *"
+message = "hi"
+chanel = "cli"
+
+[mystery]
+enabled = true
+"#;
+        let warnings = unknown_keys(toml);
+        assert_eq!(
+            keys(&warnings),
+            vec![
+                "default.modle",
+                "providers.deepseek.timeout",
+                "routines.jobs.chanel",
+                "mystery",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_template_has_no_unknown_keys() {
+        assert!(unknown_keys(crate::config::schema::DEFAULT_CONFIG_TOML).is_empty());
+    }
+}
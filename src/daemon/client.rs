@@ -7,6 +7,7 @@
 use color_eyre::eyre::{eyre, Context, Result};
 use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline, Signal};
 use std::io::Write;
+use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
@@ -14,7 +15,9 @@ use std::sync::{
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
 
-use super::protocol::{ClientMessage, DaemonMessage};
+use super::protocol::{ClientMessage, ConfirmResponse, DaemonMessage};
+use super::reload::ReloadReport;
+use crate::tools::traits::ToolResult;
 
 // ANSI colour helpers
 const RESET: &str = "\x1b[0m";
@@ -22,7 +25,16 @@ const YELLOW: &str = "\x1b[33m";
 const CYAN: &str = "\x1b[36m";
 
 /// `rrclaw chat` — connect to daemon and start interactive REPL.
-pub async fn run_chat() -> Result<()> {
+///
+/// `workspace` is resolved to an absolute path here (the daemon has its own CWD)
+/// and sent with every message.
+pub async fn run_chat(workspace: Option<PathBuf>) -> Result<()> {
+    let workspace = workspace
+        .map(|dir| {
+            dir.canonicalize()
+                .wrap_err_with(|| format!("Invalid --workspace: {}", dir.display()))
+        })
+        .transpose()?;
     let sock_path = super::sock_path()?;
 
     if !sock_path.exists() {
@@ -83,6 +95,7 @@ pub async fn run_chat() -> Result<()> {
                 let msg = ClientMessage::Message {
                     session_id: session_id.clone(),
                     content: input.clone(),
+                    workspace: workspace.clone(),
                 };
                 {
                     let mut w = writer.lock().await;
@@ -162,34 +175,37 @@ pub async fn run_chat() -> Result<()> {
                                     eprintln!("\n[error] {}\n", message);
                                     break;
                                 }
-                                DaemonMessage::Confirm {
-                                    request_id,
-                                    tool,
-                                    args,
-                                } => {
+                                DaemonMessage::ConfirmRequest(request) => {
                                     thinking_flag.store(false, Ordering::Relaxed);
                                     if let Some(h) = thinking_handle.take() {
                                         let _ = h.await;
                                     }
                                     print!("\r\x1b[K");
 
-                                    let args_str = serde_json::to_string_pretty(&args)
-                                        .unwrap_or_else(|_| format!("{:?}", args));
+                                    let args_str = serde_json::to_string_pretty(&request.args)
+                                        .unwrap_or_else(|_| format!("{:?}", request.args));
                                     println!(
                                         "\n{}[confirm]{} Tool '{}' wants to execute:\n{}",
-                                        YELLOW, RESET, tool, args_str
+                                        YELLOW, RESET, request.tool, args_str
+                                    );
+                                    if let Some(preview) = &request.preview {
+                                        println!("{}", preview);
+                                    }
+                                    print!(
+                                        "Allow? [y/N] (denied automatically after {}s) ",
+                                        request.timeout_secs
                                     );
-                                    print!("Allow? [y/N] ");
                                     std::io::stdout().flush()?;
 
                                     let mut response = String::new();
                                     std::io::stdin().read_line(&mut response)?;
                                     let approved = response.trim().eq_ignore_ascii_case("y");
 
-                                    let confirm_msg = ClientMessage::ConfirmResponse {
-                                        request_id,
-                                        approved,
-                                    };
+                                    let confirm_msg =
+                                        ClientMessage::ConfirmResponse(ConfirmResponse {
+                                            request_id: request.request_id,
+                                            approved,
+                                        });
                                     let mut w = writer.lock().await;
                                     let mut json = serde_json::to_string(&confirm_msg)?;
                                     json.push('\n');
@@ -197,6 +213,10 @@ pub async fn run_chat() -> Result<()> {
                                     w.flush().await?;
                                     first_token = true; // reset for next response
                                 }
+                                DaemonMessage::Reloaded { .. }
+                                | DaemonMessage::RoutineResult { .. } => {
+                                    // Only sent in reply to `Reload` / `Routine`, never during chat
+                                }
                             }
                         }
                         Ok(None) => {
@@ -229,3 +249,57 @@ pub async fn run_chat() -> Result<()> {
 
     Ok(())
 }
+
+/// `rrclaw reload` — send a `Reload` request on an open connection and wait for the report.
+pub async fn request_reload(stream: UnixStream) -> Result<ReloadReport> {
+    let (reader, mut writer) = stream.into_split();
+    let mut json = serde_json::to_string(&ClientMessage::Reload)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await?;
+    writer.flush().await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    let line = lines
+        .next_line()
+        .await
+        .wrap_err("Error reading from daemon")?
+        .ok_or_else(|| eyre!("Daemon disconnected unexpectedly"))?;
+    match serde_json::from_str(&line).wrap_err("Failed to parse daemon message")? {
+        DaemonMessage::Reloaded { applied, deferred } => Ok(ReloadReport { applied, deferred }),
+        DaemonMessage::Error { message } => Err(eyre!("Reload failed: {}", message)),
+        other => Err(eyre!("Unexpected daemon response: {:?}", other)),
+    }
+}
+
+/// Send a `Routine` request (a `routine` tool call) and wait for the daemon's result.
+pub async fn request_routine(stream: UnixStream, args: &serde_json::Value) -> Result<ToolResult> {
+    let (reader, mut writer) = stream.into_split();
+    let msg = ClientMessage::Routine { args: args.clone() };
+    let mut json = serde_json::to_string(&msg)?;
+    json.push('\n');
+    writer.write_all(json.as_bytes()).await?;
+    writer.flush().await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    let line = lines
+        .next_line()
+        .await
+        .wrap_err("Error reading from daemon")?
+        .ok_or_else(|| eyre!("Daemon disconnected unexpectedly"))?;
+    match serde_json::from_str(&line).wrap_err("Failed to parse daemon message")? {
+        DaemonMessage::RoutineResult {
+            success,
+            output,
+            error,
+        } => Ok(ToolResult {
+            success,
+            output,
+            error,
+            ..Default::default()
+        }),
+        DaemonMessage::Error { message } => {
+            Err(eyre!("Daemon routine request failed: {}", message))
+        }
+        other => Err(eyre!("Unexpected daemon response: {:?}", other)),
+    }
+}
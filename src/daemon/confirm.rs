@@ -0,0 +1,274 @@
+//! Supervised-mode confirmations over IPC.
+//!
+//! A daemon agent has no terminal, so its approval policy is a [`ConfirmBroker`]:
+//! each tool call that needs confirmation becomes a [`ConfirmRequest`] frame sent
+//! to the connected `rrclaw chat` client, and the turn waits for the matching
+//! [`ConfirmResponse`]. No answer within the timeout, or no client to ask, denies
+//! the call; the agent records the reason as the tool result and carries on.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+use super::protocol::{ConfirmRequest, ConfirmResponse, DaemonMessage};
+use crate::agent::{ApprovalPolicy, Decision, NOTICE_ARG, PREVIEW_ARG};
+use crate::providers::ToolCall;
+use crate::security::SecurityPolicy;
+
+/// Denial reason when nobody can answer the request.
+pub const NO_CLIENT_MESSAGE: &str = "Supervised mode needs an attached `rrclaw chat` client \
+     to confirm tool calls, and none is connected. The tool was not executed; reconnect and \
+     retry, or switch to Full mode.";
+
+/// Routes confirmation requests to one client connection and its answers back.
+///
+/// Cloned into the agent as its approval policy; `handle_client` keeps a handle to
+/// deliver responses and to mark the client gone on disconnect.
+#[derive(Clone)]
+pub struct ConfirmBroker {
+    outbound: mpsc::UnboundedSender<DaemonMessage>,
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>,
+    closed: Arc<AtomicBool>,
+    timeout: Duration,
+}
+
+impl ConfirmBroker {
+    /// `outbound` feeds the connection's writer task.
+    pub fn new(outbound: mpsc::UnboundedSender<DaemonMessage>, timeout: Duration) -> Self {
+        Self {
+            outbound,
+            pending: Arc::default(),
+            closed: Arc::default(),
+            timeout,
+        }
+    }
+
+    /// Send a request to the client and wait for its answer.
+    pub async fn request(&self, tool: &str, args: &serde_json::Value) -> Decision {
+        if self.closed.load(Ordering::SeqCst) {
+            return Decision::Deny(Some(NO_CLIENT_MESSAGE.to_string()));
+        }
+
+        // The preview travels in its own field; the client shows the real arguments.
+        // A one-off notice (e.g. dirty worktree) is shown above the preview.
+        let mut args = args.clone();
+        let mut take = |key: &str| {
+            args.as_object_mut()
+                .and_then(|obj| obj.remove(key))
+                .and_then(|v| v.as_str().map(str::to_string))
+        };
+        let notice = take(NOTICE_ARG);
+        let preview = match (notice, take(PREVIEW_ARG)) {
+            (Some(notice), Some(preview)) => Some(format!("{}\n\n{}", notice, preview)),
+            (notice, preview) => notice.or(preview),
+        };
+
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        self.lock().insert(request_id.clone(), tx);
+
+        let frame = DaemonMessage::ConfirmRequest(ConfirmRequest {
+            request_id: request_id.clone(),
+            tool: tool.to_string(),
+            args,
+            preview,
+            timeout_secs: self.timeout.as_secs(),
+        });
+        if self.outbound.send(frame).is_err() {
+            self.lock().remove(&request_id);
+            return Decision::Deny(Some(NO_CLIENT_MESSAGE.to_string()));
+        }
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(true)) => Decision::Approve,
+            Ok(Ok(false)) => Decision::Deny(None),
+            // Sender dropped by `close`: the client disconnected mid-request
+            Ok(Err(_)) => Decision::Deny(Some(NO_CLIENT_MESSAGE.to_string())),
+            Err(_) => {
+                self.lock().remove(&request_id);
+                Decision::Deny(Some(format!(
+                    "no confirmation from the chat client within {}s; the tool was not executed",
+                    self.timeout.as_secs()
+                )))
+            }
+        }
+    }
+
+    /// Deliver a client's answer. Returns false for unknown or expired requests.
+    pub fn resolve(&self, response: ConfirmResponse) -> bool {
+        match self.lock().remove(&response.request_id) {
+            Some(tx) => tx.send(response.approved).is_ok(),
+            None => {
+                debug!(
+                    "Ignoring confirm response for unknown request {}",
+                    response.request_id
+                );
+                false
+            }
+        }
+    }
+
+    /// The client disconnected: deny everything pending and anything asked later.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<bool>>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl ApprovalPolicy for ConfirmBroker {
+    async fn approve(&self, call: &ToolCall, policy: &SecurityPolicy) -> Decision {
+        if !policy.requires_confirmation() {
+            return Decision::Approve;
+        }
+        self.request(&call.name, &call.arguments).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_broker(timeout: Duration) -> (ConfirmBroker, mpsc::UnboundedReceiver<DaemonMessage>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (ConfirmBroker::new(tx, timeout), rx)
+    }
+
+    async fn next_request(rx: &mut mpsc::UnboundedReceiver<DaemonMessage>) -> ConfirmRequest {
+        match rx.recv().await {
+            Some(DaemonMessage::ConfirmRequest(request)) => request,
+            other => panic!("expected ConfirmRequest, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn approved_and_denied_responses_reach_the_waiting_call() {
+        let (broker, mut rx) = make_broker(Duration::from_secs(5));
+        let args = serde_json::json!({"path": "a.txt", PREVIEW_ARG: "-old\n+new"});
+
+        for approved in [true, false] {
+            let waiting = tokio::spawn({
+                let broker = broker.clone();
+                let args = args.clone();
+                async move { broker.request("file_write", &args).await }
+            });
+            let request = next_request(&mut rx).await;
+            assert_eq!(request.tool, "file_write");
+            assert_eq!(request.args, serde_json::json!({"path": "a.txt"}));
+            assert_eq!(request.preview.as_deref(), Some("-old\n+new"));
+            assert_eq!(request.timeout_secs, 5);
+
+            assert!(broker.resolve(ConfirmResponse {
+                request_id: request.request_id.clone(),
+                approved,
+            }));
+            let expected = if approved {
+                Decision::Approve
+            } else {
+                Decision::Deny(None)
+            };
+            assert_eq!(waiting.await.unwrap(), expected);
+            // Answered requests are forgotten
+            assert!(!broker.resolve(ConfirmResponse {
+                request_id: request.request_id,
+                approved: true,
+            }));
+        }
+    }
+
+    #[tokio::test]
+    async fn notice_is_shown_above_the_preview() {
+        let (broker, mut rx) = make_broker(Duration::from_secs(5));
+        let args = serde_json::json!({
+            "path": "a.txt",
+            PREVIEW_ARG: "+new",
+            NOTICE_ARG: "worktree is dirty",
+        });
+        let waiting = tokio::spawn({
+            let broker = broker.clone();
+            async move { broker.request("file_write", &args).await }
+        });
+        let request = next_request(&mut rx).await;
+        assert_eq!(request.args, serde_json::json!({"path": "a.txt"}));
+        assert_eq!(
+            request.preview.as_deref(),
+            Some("worktree is dirty\n\n+new")
+        );
+        broker.resolve(ConfirmResponse {
+            request_id: request.request_id,
+            approved: true,
+        });
+        assert_eq!(waiting.await.unwrap(), Decision::Approve);
+    }
+
+    #[tokio::test]
+    async fn timeout_denies_and_late_answer_is_ignored() {
+        let (broker, mut rx) = make_broker(Duration::from_millis(50));
+        let decision = broker.request("shell", &serde_json::json!({})).await;
+        let Decision::Deny(Some(reason)) = decision else {
+            panic!("expected a denial with a reason, got {:?}", decision);
+        };
+        assert!(reason.contains("within 0s"), "{}", reason);
+
+        let request = next_request(&mut rx).await;
+        assert!(!broker.resolve(ConfirmResponse {
+            request_id: request.request_id,
+            approved: true,
+        }));
+    }
+
+    #[tokio::test]
+    async fn no_client_denies_immediately() {
+        // Writer gone before the request
+        let (broker, rx) = make_broker(Duration::from_secs(60));
+        drop(rx);
+        assert_eq!(
+            broker.request("shell", &serde_json::json!({})).await,
+            Decision::Deny(Some(NO_CLIENT_MESSAGE.to_string()))
+        );
+
+        // Client disconnects while the request is pending
+        let (broker, mut rx) = make_broker(Duration::from_secs(60));
+        let waiting = tokio::spawn({
+            let broker = broker.clone();
+            async move { broker.request("shell", &serde_json::json!({})).await }
+        });
+        next_request(&mut rx).await;
+        broker.close();
+        assert_eq!(
+            waiting.await.unwrap(),
+            Decision::Deny(Some(NO_CLIENT_MESSAGE.to_string()))
+        );
+        // ...and stays closed
+        assert_eq!(
+            broker.request("shell", &serde_json::json!({})).await,
+            Decision::Deny(Some(NO_CLIENT_MESSAGE.to_string()))
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn only_supervised_mode_asks() {
+        let (broker, mut rx) = make_broker(Duration::from_secs(60));
+        let call = ToolCall {
+            id: "1".to_string(),
+            name: "shell".to_string(),
+            arguments: serde_json::json!({"command": "ls"}),
+        };
+        let policy = SecurityPolicy {
+            autonomy: crate::security::AutonomyLevel::Full,
+            ..Default::default()
+        };
+        assert_eq!(broker.approve(&call, &policy).await, Decision::Approve);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Runtime log level switching (`kill -USR1 <daemon pid>`).
+//!
+//! `init_tracing` wraps the stderr and rolling-file `EnvFilter`s in
+//! `tracing_subscriber::reload` layers and installs a [`LogLevelSwitch`] that
+//! owns their handles. Each SIGUSR1 received by the daemon cycles both sinks
+//! warn → info → debug → warn; the change lasts until the process restarts.
+
+use std::sync::{Mutex, OnceLock};
+
+use color_eyre::eyre::Result;
+use tracing_subscriber::{reload, EnvFilter};
+
+/// Levels the switch cycles through (dependencies always stay at warn).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// Level matching the stderr filter chosen by `-v` at startup.
+    pub fn from_verbosity(verbose: u8) -> Self {
+        match verbose {
+            0 => Self::Warn,
+            1 => Self::Info,
+            _ => Self::Debug,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Warn => Self::Info,
+            Self::Info => Self::Debug,
+            Self::Debug => Self::Warn,
+        }
+    }
+
+    /// `EnvFilter` directive for this level.
+    pub fn directive(self) -> &'static str {
+        match self {
+            Self::Warn => "warn",
+            Self::Info => "warn,rrclaw=info",
+            Self::Debug => "warn,rrclaw=debug",
+        }
+    }
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        })
+    }
+}
+
+type ApplyFn = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+/// Owns the reload handles of every filter that follows the cycled level.
+pub struct LogLevelSwitch {
+    current: Mutex<LogLevel>,
+    apply: ApplyFn,
+}
+
+impl LogLevelSwitch {
+    /// `apply` receives the new directive and swaps it into each reload handle.
+    pub fn new(
+        initial: LogLevel,
+        apply: impl Fn(&str) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            current: Mutex::new(initial),
+            apply: Box::new(apply),
+        }
+    }
+
+    /// Switch covering a single reload handle.
+    pub fn for_handle<S: 'static>(initial: LogLevel, handle: reload::Handle<EnvFilter, S>) -> Self {
+        Self::new(initial, move |directive| {
+            handle.reload(EnvFilter::new(directive))?;
+            Ok(())
+        })
+    }
+
+    pub fn current(&self) -> LogLevel {
+        *self.current.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move to the next level and apply it; the level is unchanged on error.
+    pub fn cycle(&self) -> Result<LogLevel> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let next = current.next();
+        (self.apply)(next.directive())?;
+        *current = next;
+        Ok(next)
+    }
+}
+
+static SWITCH: OnceLock<LogLevelSwitch> = OnceLock::new();
+
+/// Install the process-wide switch (called once by `init_tracing`).
+pub fn install(switch: LogLevelSwitch) {
+    let _ = SWITCH.set(switch);
+}
+
+/// The installed switch, if tracing was initialised with one.
+pub fn global() -> Option<&'static LogLevelSwitch> {
+    SWITCH.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    struct CountEvents(Arc<AtomicUsize>);
+
+    impl<S: Subscriber> Layer<S> for CountEvents {
+        fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn levels_cycle_warn_info_debug() {
+        assert_eq!(LogLevel::from_verbosity(0), LogLevel::Warn);
+        assert_eq!(LogLevel::from_verbosity(3), LogLevel::Debug);
+        assert_eq!(LogLevel::Warn.next(), LogLevel::Info);
+        assert_eq!(LogLevel::Info.next(), LogLevel::Debug);
+        assert_eq!(LogLevel::Debug.next(), LogLevel::Warn);
+    }
+
+    #[test]
+    fn cycle_changes_the_effective_filter() {
+        let (filter, handle) = reload::Layer::new(EnvFilter::new(LogLevel::Warn.directive()));
+        let seen = Arc::new(AtomicUsize::new(0));
+        let subscriber =
+            tracing_subscriber::registry().with(CountEvents(seen.clone()).with_filter(filter));
+        let switch = LogLevelSwitch::for_handle(LogLevel::Warn, handle.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let emit = || {
+                tracing::info!(target: "rrclaw::daemon", "info");
+                tracing::debug!(target: "rrclaw::daemon", "debug");
+            };
+            emit();
+            assert_eq!(seen.load(Ordering::SeqCst), 0);
+
+            assert_eq!(switch.cycle().unwrap(), LogLevel::Info);
+            let directive = handle.with_current(|f| f.to_string()).unwrap();
+            assert!(directive.contains("rrclaw=info"), "{directive}");
+            emit();
+            assert_eq!(seen.load(Ordering::SeqCst), 1);
+
+            assert_eq!(switch.cycle().unwrap(), LogLevel::Debug);
+            emit();
+            assert_eq!(seen.load(Ordering::SeqCst), 3);
+
+            assert_eq!(switch.cycle().unwrap(), LogLevel::Warn);
+            emit();
+            assert_eq!(seen.load(Ordering::SeqCst), 3);
+        });
+        assert_eq!(switch.current(), LogLevel::Warn);
+    }
+}
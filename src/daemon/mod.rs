@@ -3,7 +3,10 @@
 //! Provides background process management so Telegram and other channels
 //! continue running after the terminal is closed.
 
+pub mod confirm;
+pub mod log_level;
 pub mod protocol;
+pub mod reload;
 
 #[cfg(unix)]
 pub mod client;
@@ -13,7 +16,7 @@ pub mod server;
 /// Stub: daemon IPC client (Unix only).
 #[cfg(not(unix))]
 pub mod client {
-    pub async fn run_chat() -> color_eyre::eyre::Result<()> {
+    pub async fn run_chat(_workspace: Option<std::path::PathBuf>) -> color_eyre::eyre::Result<()> {
         color_eyre::eyre::bail!("Daemon mode is only supported on Unix (macOS/Linux)")
     }
 }
@@ -26,30 +29,46 @@ pub mod server {
     }
 }
 
-use color_eyre::eyre::{eyre, Result};
-use std::path::PathBuf;
+use color_eyre::eyre::Result;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use tracing::info;
 
-/// Returns `~/.rrclaw/daemon.pid`.
+/// Returns `<state dir>/daemon.pid`.
 pub fn pid_path() -> Result<PathBuf> {
-    Ok(rrclaw_home()?.join("daemon.pid"))
+    Ok(state_dir()?.join("daemon.pid"))
 }
 
-/// Returns `~/.rrclaw/daemon.sock`.
+/// Returns `<state dir>/daemon.sock`.
 pub fn sock_path() -> Result<PathBuf> {
-    Ok(rrclaw_home()?.join("daemon.sock"))
+    Ok(state_dir()?.join("daemon.sock"))
 }
 
-/// Returns `~/.rrclaw/logs/daemon.log`.
+/// Returns `<state dir>/logs/daemon.log`.
 pub fn log_path() -> Result<PathBuf> {
-    Ok(rrclaw_home()?.join("logs").join("daemon.log"))
+    Ok(crate::config::RrclawPaths::resolve()?
+        .log_dir()
+        .join("daemon.log"))
 }
 
-/// `~/.rrclaw/`
-fn rrclaw_home() -> Result<PathBuf> {
-    let base =
-        directories::BaseDirs::new().ok_or_else(|| eyre!("Cannot determine home directory"))?;
-    Ok(base.home_dir().join(".rrclaw"))
+/// Runtime state directory: `$XDG_STATE_HOME/rrclaw`, or the single-directory
+/// root (`~/.rrclaw/`, `RRCLAW_HOME`, or the `--config` file's directory).
+fn state_dir() -> Result<PathBuf> {
+    Ok(crate::config::RrclawPaths::resolve()?
+        .state_dir()
+        .to_path_buf())
+}
+
+/// Arguments for the re-exec'd worker; forwards `--config` so the worker
+/// uses the same config file and data directory as the launching command.
+fn worker_args(config_override: Option<&Path>) -> Vec<OsString> {
+    let mut args = Vec::new();
+    if let Some(path) = config_override {
+        args.push(OsString::from("--config"));
+        args.push(path.as_os_str().to_os_string());
+    }
+    args.push(OsString::from("daemon-worker"));
+    args
 }
 
 // ─── Process helpers ──────────────────────────────────────────────────────────
@@ -74,6 +93,45 @@ fn cleanup_files(pid_file: &std::path::Path, sock_file: &std::path::Path) {
     let _ = std::fs::remove_file(sock_file);
 }
 
+/// PID of the running daemon, if any (stale pid files are ignored).
+#[cfg(unix)]
+pub fn running_pid() -> Option<u32> {
+    let pid = read_pid(&pid_path().ok()?)?;
+    is_process_alive(pid).then_some(pid)
+}
+
+#[cfg(not(unix))]
+pub fn running_pid() -> Option<u32> {
+    None
+}
+
+/// Forward a `routine` tool call to the daemon when it is the routine scheduler.
+///
+/// `holder` is the pid in the scheduler lock. Returns `None` when that pid is not
+/// a running daemon (another `rrclaw agent`, or this process) or the socket is
+/// unreachable, so the caller falls back to its own standby handling.
+#[cfg(unix)]
+pub async fn forward_routine(
+    holder: u32,
+    args: &serde_json::Value,
+) -> Option<Result<crate::tools::traits::ToolResult>> {
+    if holder == std::process::id() || running_pid()? != holder {
+        return None;
+    }
+    let stream = tokio::net::UnixStream::connect(sock_path().ok()?)
+        .await
+        .ok()?;
+    Some(client::request_routine(stream, args).await)
+}
+
+#[cfg(not(unix))]
+pub async fn forward_routine(
+    _holder: u32,
+    _args: &serde_json::Value,
+) -> Option<Result<crate::tools::traits::ToolResult>> {
+    None
+}
+
 // ─── Public commands ──────────────────────────────────────────────────────────
 
 /// `rrclaw start` — launch daemon in background via re-exec.
@@ -108,7 +166,7 @@ pub fn start() -> Result<()> {
     // Re-exec self with internal `--daemon-worker` flag
     let exe = std::env::current_exe()?;
     let child = std::process::Command::new(exe)
-        .arg("daemon-worker")
+        .args(worker_args(crate::config::paths::config_override()))
         .stdin(std::process::Stdio::null())
         .stdout(std::process::Stdio::from(log))
         .stderr(std::process::Stdio::from(log_err))
@@ -181,6 +239,59 @@ pub fn restart() -> Result<()> {
     start()
 }
 
+/// `rrclaw reload` — ask the running daemon to re-read config.toml.
+///
+/// Uses the IPC socket so the report can be printed; falls back to SIGHUP
+/// (report goes to the daemon log) when the socket is unavailable.
+#[cfg(unix)]
+pub async fn reload() -> Result<()> {
+    let Some(pid) = running_pid() else {
+        println!("Daemon not running");
+        return Ok(());
+    };
+
+    let sock_file = sock_path()?;
+    match tokio::net::UnixStream::connect(&sock_file).await {
+        Ok(stream) => {
+            let report = client::request_reload(stream).await?;
+            print!("{}", format_reload_report(&report));
+        }
+        Err(e) => {
+            // SAFETY: sending SIGHUP to the daemon pid read from our own pid file
+            unsafe {
+                libc::kill(pid as i32, libc::SIGHUP);
+            }
+            println!(
+                "Daemon socket unavailable ({}), sent SIGHUP (pid {})",
+                e, pid
+            );
+            println!("See {} for the reload result.", log_path()?.display());
+        }
+    }
+    Ok(())
+}
+
+/// Human-readable `rrclaw reload` output.
+fn format_reload_report(report: &reload::ReloadReport) -> String {
+    if report.is_empty() {
+        return "Config reloaded: no changes\n".to_string();
+    }
+    let mut out = String::from("Config reloaded\n");
+    if !report.applied.is_empty() {
+        out.push_str("  Applied:\n");
+        for key in &report.applied {
+            out.push_str(&format!("    ✓ {}\n", key));
+        }
+    }
+    if !report.deferred.is_empty() {
+        out.push_str("  Needs `rrclaw restart`:\n");
+        for key in &report.deferred {
+            out.push_str(&format!("    • {}\n", key));
+        }
+    }
+    out
+}
+
 /// `rrclaw status` — check if daemon is running.
 #[cfg(unix)]
 pub fn status() -> Result<()> {
@@ -198,6 +309,9 @@ pub fn status() -> Result<()> {
                 } else {
                     println!("  Telegram: not configured");
                 }
+                if config.routines.paused {
+                    println!("  Routines: paused ([routines] paused = true)");
+                }
             }
 
             if sock_file.exists() {
@@ -233,6 +347,11 @@ pub fn restart() -> Result<()> {
     color_eyre::eyre::bail!("Daemon mode is only supported on Unix (macOS/Linux)")
 }
 
+#[cfg(not(unix))]
+pub async fn reload() -> Result<()> {
+    color_eyre::eyre::bail!("Daemon mode is only supported on Unix (macOS/Linux)")
+}
+
 #[cfg(not(unix))]
 pub fn status() -> Result<()> {
     color_eyre::eyre::bail!("Daemon mode is only supported on Unix (macOS/Linux)")
@@ -260,6 +379,43 @@ mod tests {
         assert!(p.ends_with("daemon.log"));
     }
 
+    #[test]
+    fn worker_args_default_is_just_the_subcommand() {
+        assert_eq!(worker_args(None), vec![OsString::from("daemon-worker")]);
+    }
+
+    #[test]
+    fn worker_args_forward_custom_config() {
+        let config = Path::new("/tmp/rrclaw-alt/config.toml");
+        let args = worker_args(Some(config));
+        assert_eq!(
+            args,
+            vec![
+                OsString::from("--config"),
+                OsString::from("/tmp/rrclaw-alt/config.toml"),
+                OsString::from("daemon-worker"),
+            ]
+        );
+        // worker 从该配置推导出的 pid/log 均在配置目录下
+        let paths = crate::config::RrclawPaths::from_config_file(config);
+        assert!(paths.log_dir().starts_with("/tmp/rrclaw-alt"));
+    }
+
+    #[test]
+    fn reload_report_lists_applied_and_deferred() {
+        let report = reload::ReloadReport {
+            applied: vec!["security.autonomy".to_string()],
+            deferred: vec!["memory.backend（数据库在启动时打开）".to_string()],
+        };
+        let out = format_reload_report(&report);
+        assert!(out.contains("✓ security.autonomy"));
+        assert!(out.contains("Needs `rrclaw restart`:\n    • memory.backend"));
+        assert_eq!(
+            format_reload_report(&reload::ReloadReport::default()),
+            "Config reloaded: no changes\n"
+        );
+    }
+
     #[test]
     fn read_pid_nonexistent_returns_none() {
         let p = std::path::Path::new("/tmp/rrclaw-test-nonexistent.pid");
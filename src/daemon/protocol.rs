@@ -11,10 +11,24 @@ use serde::{Deserialize, Serialize};
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
     /// Start a new conversation or continue chatting.
-    Message { session_id: String, content: String },
+    Message {
+        session_id: String,
+        content: String,
+        /// Absolute workspace directory for this message (`rrclaw chat --workspace`);
+        /// omitted = the daemon's own working directory.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        workspace: Option<std::path::PathBuf>,
+    },
+
+    /// Answer to a [`DaemonMessage::ConfirmRequest`] (Supervised mode).
+    ConfirmResponse(ConfirmResponse),
+
+    /// Re-read config.toml and apply what can change without a restart (`rrclaw reload`).
+    Reload,
 
-    /// Response to a tool confirmation request (Supervised mode).
-    ConfirmResponse { request_id: String, approved: bool },
+    /// Run a `routine` tool call on the daemon's RoutineEngine; sent by a process
+    /// in routine standby while the daemon holds the scheduler lock.
+    Routine { args: serde_json::Value },
 }
 
 // ─── Daemon → Client ─────────────────────────────────────────────────────────
@@ -29,15 +43,51 @@ pub enum DaemonMessage {
     /// Agent finished its response.
     Done,
 
-    /// Request tool execution confirmation from the user (Supervised mode).
-    Confirm {
-        request_id: String,
-        tool: String,
-        args: serde_json::Value,
-    },
+    /// Ask the user to confirm a tool call (Supervised mode); answered by
+    /// [`ClientMessage::ConfirmResponse`] with the same `request_id`.
+    ConfirmRequest(ConfirmRequest),
 
     /// An error occurred while processing the request.
     Error { message: String },
+
+    /// Result of a `Reload` request: config keys applied live vs needing `rrclaw restart`.
+    Reloaded {
+        applied: Vec<String>,
+        deferred: Vec<String>,
+    },
+
+    /// Result of a `Routine` request (the tool's own result fields).
+    RoutineResult {
+        success: bool,
+        output: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+// ─── Confirmation frames ─────────────────────────────────────────────────────
+//
+// Transport-neutral: the daemon sends them over the socket as JSON lines, and a
+// Telegram inline keyboard can carry the same `request_id` in its callback data.
+
+/// A tool call waiting for the user's approval.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfirmRequest {
+    pub request_id: String,
+    pub tool: String,
+    pub args: serde_json::Value,
+    /// Dry-run text from the tool (e.g. the diff `file_write` would apply).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+    /// Seconds until the request is denied automatically.
+    pub timeout_secs: u64,
+}
+
+/// The user's answer to a [`ConfirmRequest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfirmResponse {
+    pub request_id: String,
+    pub approved: bool,
 }
 
 #[cfg(test)]
@@ -49,18 +99,34 @@ mod tests {
         let msg = ClientMessage::Message {
             session_id: "cli-abc".to_string(),
             content: "hello".to_string(),
+            workspace: None,
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"type\":\"message\""));
         assert!(json.contains("\"session_id\":\"cli-abc\""));
+        assert!(!json.contains("workspace"));
+    }
+
+    #[test]
+    fn client_message_without_workspace_still_parses() {
+        // Clients predating `rrclaw chat --workspace` omit the field.
+        let json = r#"{"type":"message","session_id":"s","content":"hi"}"#;
+        let parsed: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            parsed,
+            ClientMessage::Message {
+                workspace: None,
+                ..
+            }
+        ));
     }
 
     #[test]
     fn client_confirm_response_serialize() {
-        let msg = ClientMessage::ConfirmResponse {
+        let msg = ClientMessage::ConfirmResponse(ConfirmResponse {
             request_id: "req-1".to_string(),
             approved: true,
-        };
+        });
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"type\":\"confirm_response\""));
         assert!(json.contains("\"approved\":true"));
@@ -84,14 +150,44 @@ mod tests {
 
     #[test]
     fn daemon_confirm_serialize() {
-        let msg = DaemonMessage::Confirm {
+        let msg = DaemonMessage::ConfirmRequest(ConfirmRequest {
             request_id: "r1".to_string(),
             tool: "shell".to_string(),
             args: serde_json::json!({"command": "ls"}),
-        };
+            preview: None,
+            timeout_secs: 120,
+        });
         let json = serde_json::to_string(&msg).unwrap();
-        assert!(json.contains("\"type\":\"confirm\""));
+        assert!(json.contains("\"type\":\"confirm_request\""));
         assert!(json.contains("\"tool\":\"shell\""));
+        assert!(json.contains("\"timeout_secs\":120"));
+        assert!(!json.contains("preview"));
+    }
+
+    #[test]
+    fn confirm_frames_roundtrip() {
+        let request = ConfirmRequest {
+            request_id: "r2".to_string(),
+            tool: "file_write".to_string(),
+            args: serde_json::json!({"path": "a.txt"}),
+            preview: Some("-old\n+new".to_string()),
+            timeout_secs: 30,
+        };
+        let json = serde_json::to_string(&DaemonMessage::ConfirmRequest(request.clone())).unwrap();
+        match serde_json::from_str(&json).unwrap() {
+            DaemonMessage::ConfirmRequest(parsed) => assert_eq!(parsed, request),
+            other => panic!("wrong variant: {:?}", other),
+        }
+
+        // Wire format is unchanged from the original struct variant
+        let json = r#"{"type":"confirm_response","request_id":"r2","approved":false}"#;
+        match serde_json::from_str(json).unwrap() {
+            ClientMessage::ConfirmResponse(response) => {
+                assert_eq!(response.request_id, "r2");
+                assert!(!response.approved);
+            }
+            other => panic!("wrong variant: {:?}", other),
+        }
     }
 
     #[test]
@@ -103,11 +199,53 @@ mod tests {
         assert!(json.contains("\"type\":\"error\""));
     }
 
+    #[test]
+    fn reload_serialize() {
+        let json = serde_json::to_string(&ClientMessage::Reload).unwrap();
+        assert_eq!(json, r#"{"type":"reload"}"#);
+
+        let msg = DaemonMessage::Reloaded {
+            applied: vec!["security.autonomy".to_string()],
+            deferred: vec![],
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains("\"type\":\"reloaded\""));
+        assert!(json.contains("\"applied\":[\"security.autonomy\"]"));
+    }
+
+    #[test]
+    fn routine_roundtrip() {
+        let msg = ClientMessage::Routine {
+            args: serde_json::json!({"action": "run", "name": "standup"}),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains("\"type\":\"routine\""));
+        match serde_json::from_str(&json).unwrap() {
+            ClientMessage::Routine { args } => assert_eq!(args["name"], "standup"),
+            other => panic!("wrong variant: {:?}", other),
+        }
+
+        let json = r#"{"type":"routine_result","success":true,"output":"done"}"#;
+        match serde_json::from_str(json).unwrap() {
+            DaemonMessage::RoutineResult {
+                success,
+                output,
+                error,
+            } => {
+                assert!(success);
+                assert_eq!(output, "done");
+                assert_eq!(error, None);
+            }
+            other => panic!("wrong variant: {:?}", other),
+        }
+    }
+
     #[test]
     fn client_message_roundtrip() {
         let msg = ClientMessage::Message {
             session_id: "s1".to_string(),
             content: "你好".to_string(),
+            workspace: Some("/home/me/other-project".into()),
         };
         let json = serde_json::to_string(&msg).unwrap();
         let parsed: ClientMessage = serde_json::from_str(&json).unwrap();
@@ -115,9 +253,11 @@ mod tests {
             ClientMessage::Message {
                 session_id,
                 content,
+                workspace,
             } => {
                 assert_eq!(session_id, "s1");
                 assert_eq!(content, "你好");
+                assert_eq!(workspace, Some("/home/me/other-project".into()));
             }
             _ => panic!("wrong variant"),
         }
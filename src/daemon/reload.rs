@@ -0,0 +1,430 @@
+//! Config reload planning for `rrclaw reload`.
+//!
+//! The daemon re-parses config.toml, diffs it against the running config and
+//! applies everything that new or refreshed Agents pick up on their own
+//! (security policy, reliability, default model, telegram allowlist, ...).
+//! Changes bound at startup (memory backend, MCP connections, the Telegram bot
+//! token, the base_url of a provider in use) are reverted in the effective
+//! config and reported as needing `rrclaw restart`.
+
+use std::collections::BTreeSet;
+
+use color_eyre::eyre::{bail, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::Config;
+
+/// Outcome of a reload: config keys applied live vs deferred until restart.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    pub applied: Vec<String>,
+    /// Keys that need `rrclaw restart`, each with the reason in parentheses.
+    pub deferred: Vec<String>,
+}
+
+impl ReloadReport {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.deferred.is_empty()
+    }
+}
+
+/// Config to install plus the report describing how it differs.
+#[derive(Debug)]
+pub struct ReloadPlan {
+    /// The new config with deferred changes reverted to the running values.
+    pub effective: Config,
+    pub report: ReloadReport,
+}
+
+/// Diff `new` against the running `old` config and classify every change.
+///
+/// Fails (keeping the running config) when the new config is unusable.
+pub fn plan_reload(old: &Config, new: Config) -> Result<ReloadPlan> {
+    if !new.providers.contains_key(&new.default.provider) {
+        bail!(
+            "default.provider '{}' 未在 [providers] 中配置，保留当前配置",
+            new.default.provider
+        );
+    }
+
+    let mut effective = new;
+    let mut report = ReloadReport::default();
+
+    // 新建 / 刷新 Agent 时读取，立即生效
+    report
+        .applied
+        .extend(changed_keys("default", &old.default, &effective.default));
+    report
+        .applied
+        .extend(changed_keys("security", &old.security, &effective.security));
+    report.applied.extend(changed_keys(
+        "reliability",
+        &old.reliability,
+        &effective.reliability,
+    ));
+    report
+        .applied
+        .extend(changed_keys("agent", &old.agent, &effective.agent));
+    report
+        .applied
+        .extend(changed_keys("cli", &old.cli, &effective.cli));
+    report
+        .applied
+        .extend(changed_keys("pricing", &old.pricing, &effective.pricing));
+    report.applied.extend(routine_changes(old, &effective));
+
+    provider_changes(old, &mut effective, &mut report);
+    telegram_changes(old, &mut effective, &mut report);
+
+    // 启动时建立，需要重启
+    let memory = changed_keys("memory", &old.memory, &effective.memory);
+    if !memory.is_empty() {
+        report.deferred.extend(
+            memory
+                .into_iter()
+                .map(|key| format!("{}（数据库在启动时打开）", key)),
+        );
+        effective.memory = old.memory.clone();
+    }
+    let mcp = changed_keys("mcp", &old.mcp, &effective.mcp);
+    if !mcp.is_empty() {
+        report.deferred.extend(
+            mcp.into_iter()
+                .map(|key| format!("{}（MCP 连接在启动时建立）", key)),
+        );
+        effective.mcp = old.mcp.clone();
+    }
+    let daemon = changed_keys("daemon", &old.daemon, &effective.daemon);
+    if !daemon.is_empty() {
+        report.deferred.extend(
+            daemon
+                .into_iter()
+                .map(|key| format!("{}（metrics 端点在启动时绑定）", key)),
+        );
+        effective.daemon = old.daemon.clone();
+    }
+
+    Ok(ReloadPlan { effective, report })
+}
+
+/// Providers referenced by the running config (main, fallbacks, aux models).
+fn providers_in_use(config: &Config) -> BTreeSet<String> {
+    let mut names = BTreeSet::from([config.default.provider.clone()]);
+    names.extend(config.reliability.fallback_providers.iter().cloned());
+    for aux in [&config.agent.routing, &config.agent.summary]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(provider) = &aux.provider {
+            names.insert(provider.clone());
+        }
+    }
+    names
+}
+
+fn provider_changes(old: &Config, effective: &mut Config, report: &mut ReloadReport) {
+    let in_use = providers_in_use(old);
+    let names: BTreeSet<String> = old
+        .providers
+        .keys()
+        .chain(effective.providers.keys())
+        .cloned()
+        .collect();
+    for name in names {
+        match (old.providers.get(&name), effective.providers.get_mut(&name)) {
+            (None, Some(_)) => report.applied.push(format!("providers.{}（新增）", name)),
+            (Some(_), None) => report.applied.push(format!("providers.{}（移除）", name)),
+            (Some(old_pc), Some(new_pc)) => {
+                if old_pc.base_url != new_pc.base_url && in_use.contains(&name) {
+                    report
+                        .deferred
+                        .push(format!("providers.{}.base_url（Provider 使用中）", name));
+                    new_pc.base_url = old_pc.base_url.clone();
+                }
+                report.applied.extend(changed_keys(
+                    &format!("providers.{}", name),
+                    old_pc,
+                    &*new_pc,
+                ));
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn telegram_changes(old: &Config, effective: &mut Config, report: &mut ReloadReport) {
+    match (&old.telegram, &mut effective.telegram) {
+        (Some(old_tg), Some(new_tg)) => {
+            if old_tg.allowed_chat_ids != new_tg.allowed_chat_ids {
+                report.applied.push("telegram.allowed_chat_ids".to_string());
+            }
+            if old_tg.tool_verbosity != new_tg.tool_verbosity {
+                report.applied.push("telegram.tool_verbosity".to_string());
+            }
+            if old_tg.admin_chat_id != new_tg.admin_chat_id {
+                report.applied.push("telegram.admin_chat_id".to_string());
+            }
+            if old_tg.rate_limit != new_tg.rate_limit {
+                report.applied.push("telegram.rate_limit".to_string());
+            }
+            if old_tg.bot_token != new_tg.bot_token {
+                report
+                    .deferred
+                    .push("telegram.bot_token（Bot 连接在启动时建立）".to_string());
+                new_tg.bot_token = old_tg.bot_token.clone();
+            }
+        }
+        (None, None) => {}
+        (None, Some(_)) => {
+            report
+                .deferred
+                .push("telegram（启用 Telegram Bot）".to_string());
+            effective.telegram = None;
+        }
+        (Some(_), None) => {
+            report
+                .deferred
+                .push("telegram（停用 Telegram Bot）".to_string());
+            effective.telegram = old.telegram.clone();
+        }
+    }
+}
+
+/// `[routines]` changes: settings by key, jobs by name (`+` added, `-` removed, `~` updated).
+fn routine_changes(old: &Config, new: &Config) -> Vec<String> {
+    let strip_jobs = |config: &Config| {
+        let mut value = to_value(&config.routines);
+        if let Value::Object(map) = &mut value {
+            map.remove("jobs");
+        }
+        value
+    };
+    let mut changes = changed_value_keys("routines", &strip_jobs(old), &strip_jobs(new));
+
+    let old_jobs = &old.routines.jobs;
+    let new_jobs = &new.routines.jobs;
+    for job in new_jobs {
+        match old_jobs.iter().find(|j| j.name == job.name) {
+            None => changes.push(format!("routines.jobs: +{}", job.name)),
+            Some(prev) if to_value(prev) != to_value(job) => {
+                changes.push(format!("routines.jobs: ~{}", job.name))
+            }
+            Some(_) => {}
+        }
+    }
+    for job in old_jobs {
+        if !new_jobs.iter().any(|j| j.name == job.name) {
+            changes.push(format!("routines.jobs: -{}", job.name));
+        }
+    }
+    changes
+}
+
+/// Dotted keys that differ between two serializable values (values are never reported).
+fn changed_keys<T: Serialize>(prefix: &str, old: &T, new: &T) -> Vec<String> {
+    changed_value_keys(prefix, &to_value(old), &to_value(new))
+}
+
+fn changed_value_keys(prefix: &str, old: &Value, new: &Value) -> Vec<String> {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let keys: BTreeSet<&String> = old_map.keys().chain(new_map.keys()).collect();
+            keys.into_iter()
+                .filter(|key| old_map.get(*key) != new_map.get(*key))
+                .map(|key| format!("{}.{}", prefix, key))
+                .collect()
+        }
+        _ if old != new => vec![prefix.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ProviderConfig, RoutineJobConfig, TelegramConfig};
+    use crate::security::AutonomyLevel;
+
+    fn provider(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            base_url: base_url.to_string(),
+            api_key: "sk-old".to_string(),
+            model: "deepseek-chat".to_string(),
+            auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        }
+    }
+
+    fn job(name: &str, schedule: &str) -> RoutineJobConfig {
+        RoutineJobConfig {
+            name: name.to_string(),
+            schedule: schedule.to_string(),
+            message: "hi".to_string(),
+            channel: "cli".to_string(),
+            enabled: true,
+            catch_up: false,
+            chain: vec![],
+            condition: None,
+            channel_target: None,
+        }
+    }
+
+    fn base_config() -> Config {
+        let mut config = Config::default();
+        config
+            .providers
+            .insert("deepseek".to_string(), provider("https://api.deepseek.com"));
+        config
+            .providers
+            .insert("spare".to_string(), provider("https://spare.example"));
+        config.telegram = Some(TelegramConfig {
+            bot_token: Some("token-a".to_string()),
+            allowed_chat_ids: vec![1],
+            ..Default::default()
+        });
+        config.routines.jobs = vec![job("morning", "0 8 * * *"), job("backup", "0 2 * * *")];
+        config
+    }
+
+    #[test]
+    fn unchanged_config_reports_nothing() {
+        let old = base_config();
+        let plan = plan_reload(&old, base_config()).unwrap();
+        assert!(plan.report.is_empty());
+    }
+
+    #[test]
+    fn policy_reliability_and_allowlist_apply_live() {
+        let old = base_config();
+        let mut new = base_config();
+        new.security.autonomy = AutonomyLevel::ReadOnly;
+        new.security.allowed_commands.push("make".to_string());
+        new.reliability.max_retries = 5;
+        new.telegram.as_mut().unwrap().allowed_chat_ids = vec![1, 2];
+
+        let plan = plan_reload(&old, new).unwrap();
+        assert_eq!(
+            plan.report.applied,
+            vec![
+                "security.allowed_commands",
+                "security.autonomy",
+                "reliability.max_retries",
+                "telegram.allowed_chat_ids",
+            ]
+        );
+        assert!(plan.report.deferred.is_empty());
+        assert_eq!(plan.effective.security.autonomy, AutonomyLevel::ReadOnly);
+    }
+
+    #[test]
+    fn in_use_provider_base_url_is_deferred_but_key_applies() {
+        let old = base_config();
+        let mut new = base_config();
+        let main = new.providers.get_mut("deepseek").unwrap();
+        main.base_url = "https://proxy.example".to_string();
+        main.api_key = "sk-new".to_string();
+        new.providers.get_mut("spare").unwrap().base_url = "https://spare2.example".to_string();
+
+        let plan = plan_reload(&old, new).unwrap();
+        assert_eq!(
+            plan.report.deferred,
+            vec!["providers.deepseek.base_url（Provider 使用中）"]
+        );
+        assert_eq!(
+            plan.report.applied,
+            vec!["providers.deepseek.api_key", "providers.spare.base_url"]
+        );
+        // 使用中的 Provider 保留原 base_url，新 api_key 生效；api_key 的值不出现在报告中
+        let main = &plan.effective.providers["deepseek"];
+        assert_eq!(main.base_url, "https://api.deepseek.com");
+        assert_eq!(main.api_key, "sk-new");
+        assert!(!format!("{:?}", plan.report).contains("sk-new"));
+    }
+
+    #[test]
+    fn fallback_providers_count_as_in_use() {
+        let mut old = base_config();
+        old.reliability.fallback_providers = vec!["spare".to_string()];
+        let mut new = old.clone();
+        new.providers.get_mut("spare").unwrap().base_url = "https://spare2.example".to_string();
+
+        let plan = plan_reload(&old, new).unwrap();
+        assert_eq!(
+            plan.report.deferred,
+            vec!["providers.spare.base_url（Provider 使用中）"]
+        );
+    }
+
+    #[test]
+    fn routine_jobs_are_diffed_by_name() {
+        let old = base_config();
+        let mut new = base_config();
+        new.routines.jobs = vec![job("morning", "0 9 * * *"), job("weekly", "0 9 * * 1")];
+        new.routines.paused = true;
+
+        let plan = plan_reload(&old, new).unwrap();
+        assert_eq!(
+            plan.report.applied,
+            vec![
+                "routines.paused",
+                "routines.jobs: ~morning",
+                "routines.jobs: +weekly",
+                "routines.jobs: -backup",
+            ]
+        );
+    }
+
+    #[test]
+    fn startup_bound_sections_are_deferred_and_reverted() {
+        let old = base_config();
+        let mut new = base_config();
+        new.memory.backend = "none".to_string();
+        new.telegram.as_mut().unwrap().bot_token = Some("token-b".to_string());
+        new.mcp = Some(Default::default());
+
+        let plan = plan_reload(&old, new).unwrap();
+        assert_eq!(
+            plan.report.deferred,
+            vec![
+                "telegram.bot_token（Bot 连接在启动时建立）",
+                "memory.backend（数据库在启动时打开）",
+                "mcp（MCP 连接在启动时建立）",
+            ]
+        );
+        assert!(plan.report.applied.is_empty());
+        assert_eq!(plan.effective.memory.backend, old.memory.backend);
+        assert!(plan.effective.mcp.is_none());
+        assert_eq!(
+            plan.effective.telegram.unwrap().bot_token.as_deref(),
+            Some("token-a")
+        );
+    }
+
+    #[test]
+    fn enabling_telegram_needs_restart() {
+        let mut old = base_config();
+        old.telegram = None;
+        let plan = plan_reload(&old, base_config()).unwrap();
+        assert_eq!(plan.report.deferred, vec!["telegram（启用 Telegram Bot）"]);
+        assert!(plan.effective.telegram.is_none());
+    }
+
+    #[test]
+    fn missing_default_provider_is_rejected() {
+        let old = base_config();
+        let mut new = base_config();
+        new.default.provider = "nope".to_string();
+        let err = plan_reload(&old, new).unwrap_err();
+        assert!(err.to_string().contains("nope"));
+    }
+}
@@ -4,22 +4,45 @@
 //! and optionally starts the Telegram Bot channel.
 
 use color_eyre::eyre::{Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixListener;
+use tokio::sync::mpsc;
 use tracing::{error, info, warn};
 
-use crate::config::Config;
-use crate::memory::SqliteMemory;
+use crate::agent::AgentFactory;
+use crate::config::{Config, LiveConfig};
+use crate::providers::OfflineState;
+use crate::routines::{Routine, RoutineEngine};
+use crate::security::SecurityPolicy;
+use crate::skills::usage::SkillUsageRecorder;
+use crate::tools::routine::RoutineTool;
+use crate::tools::Tool;
 
+use super::confirm::ConfirmBroker;
 use super::protocol::{ClientMessage, DaemonMessage};
+use super::reload::{plan_reload, ReloadReport};
 
 /// Entry point for the daemon worker process (`rrclaw daemon-worker`).
 ///
 /// This function does not return until the daemon is shut down.
 pub async fn run_daemon_worker() -> Result<()> {
     let config = Config::load_or_init().wrap_err("Failed to load config")?;
+    // No terminal to print to: surface config warnings in the daemon log
+    if let Ok(config_path) = Config::config_path() {
+        for warning in crate::config::check_config_file(&config, &config_path) {
+            tracing::warn!("config: {}", warning);
+        }
+    }
+    match crate::security::sandbox::current() {
+        Some(kind) => tracing::info!("running inside OS sandbox: {}", kind),
+        None if config.security.require_sandbox => {
+            tracing::warn!("{}", crate::security::sandbox::FULL_REFUSED_MESSAGE)
+        }
+        None => {}
+    }
     let data_dir = data_dir()?;
     let sock_path = super::sock_path()?;
 
@@ -27,7 +50,12 @@ pub async fn run_daemon_worker() -> Result<()> {
     let _ = std::fs::remove_file(&sock_path);
 
     // Initialize shared memory
-    let memory = Arc::new(SqliteMemory::open(&data_dir).wrap_err("Failed to initialize memory")?);
+    let (memory, degraded) =
+        crate::memory::open_with_fallback(&data_dir, config.memory.fallback_to_noop)?;
+    if degraded {
+        tracing::warn!("{}", crate::memory::degraded_memory_warning(&data_dir));
+    }
+    let memory = Arc::new(memory);
 
     // Seed core knowledge
     let log_dir = log_dir()?;
@@ -37,19 +65,113 @@ pub async fn run_daemon_worker() -> Result<()> {
         .await
         .wrap_err("Failed to seed core knowledge")?;
 
+    // Prometheus metrics endpoint (`[daemon] metrics_port`), localhost-only by default
+    if let Some(port) = config.daemon.metrics_port {
+        let addr = std::net::SocketAddr::new(config.daemon.metrics_bind, port);
+        let listener = crate::metrics::server::bind(addr).await?;
+        let metrics_memory = memory.clone() as Arc<dyn crate::memory::Memory>;
+        tokio::spawn(crate::metrics::server::serve(
+            listener,
+            crate::metrics::Registry::global(),
+            Some(metrics_memory),
+        ));
+    }
+
+    // Shared, reloadable config (`rrclaw reload` / SIGHUP)
+    let live = Arc::new(LiveConfig::new(config));
+
+    // Offline mode: agents, Telegram and routines share the global flag; only a
+    // probe can clear it, since ReliableProvider fails fast while offline.
+    spawn_offline_probe(
+        live.clone(),
+        OfflineState::global(),
+        crate::providers::offline::PROBE_INTERVAL,
+    );
+
+    // Agents for CLI clients: provider, skills and identity are built once and
+    // rebuilt automatically after a config reload.
+    let factory = Arc::new(
+        AgentFactory::new(
+            live.clone(),
+            memory.clone() as Arc<dyn crate::memory::Memory>,
+            crate::config::RrclawPaths::resolve()?,
+        )
+        .with_skills()
+        .with_identity(),
+    );
+    let skill_usage: Option<Arc<dyn SkillUsageRecorder>> =
+        match crate::skills::usage::SqliteSkillUsage::open(&data_dir) {
+            Ok(usage) => Some(Arc::new(usage)),
+            Err(e) => {
+                warn!("Skill 使用统计不可用: {:#}", e);
+                None
+            }
+        };
+
+    // Routines: the daemon competes for the scheduler lock like `rrclaw agent`;
+    // standby processes forward routine changes here over the socket.
+    let routines = start_routines(
+        live.snapshot(),
+        memory.clone() as Arc<dyn crate::memory::Memory>,
+        &data_dir,
+    )
+    .await;
+
     // Start Telegram bot if configured
     #[cfg(feature = "telegram")]
-    if config.telegram.is_some() {
-        let tg_config = config.clone();
+    if live.snapshot().telegram.is_some() {
+        let tg_live = live.clone();
         let tg_memory = memory.clone();
         tokio::spawn(async move {
             info!("Starting Telegram Bot channel");
-            if let Err(e) = crate::channels::telegram::run_telegram(tg_config, tg_memory).await {
+            if let Err(e) = crate::channels::telegram::run_telegram_live(tg_live, tg_memory).await {
                 error!("Telegram Bot error: {:#}", e);
             }
         });
     }
 
+    // SIGHUP: reload config (fallback when the socket is unavailable)
+    let hup_live = live.clone();
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Failed to install SIGHUP handler: {}", e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            info!("Received SIGHUP, reloading config");
+            if let Err(e) = reload_config(&hup_live) {
+                warn!("Config reload failed: {:#}", e);
+            }
+        }
+    });
+
+    // SIGUSR1: cycle the log level (warn → info → debug → warn) without a restart
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut usr1 = match signal(SignalKind::user_defined1()) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Failed to install SIGUSR1 handler: {}", e);
+                return;
+            }
+        };
+        while usr1.recv().await.is_some() {
+            let Some(switch) = crate::daemon::log_level::global() else {
+                warn!("Received SIGUSR1, but log level switching is not available");
+                continue;
+            };
+            match switch.cycle() {
+                // Logged at warn so the change is visible at every level
+                Ok(level) => warn!("Received SIGUSR1, log level is now {}", level),
+                Err(e) => warn!("Log level switch failed: {:#}", e),
+            }
+        }
+    });
+
     // Start Unix socket listener
     let listener = UnixListener::bind(&sock_path)
         .wrap_err_with(|| format!("Failed to bind socket: {}", sock_path.display()))?;
@@ -69,10 +191,11 @@ pub async fn run_daemon_worker() -> Result<()> {
     loop {
         match listener.accept().await {
             Ok((stream, _addr)) => {
-                let config = config.clone();
-                let memory = memory.clone();
+                let factory = factory.clone();
+                let skill_usage = skill_usage.clone();
+                let routines = routines.clone();
                 tokio::spawn(async move {
-                    if let Err(e) = handle_client(stream, config, memory).await {
+                    if let Err(e) = handle_client(stream, factory, skill_usage, routines).await {
                         warn!("Client session error: {:#}", e);
                     }
                 });
@@ -84,38 +207,162 @@ pub async fn run_daemon_worker() -> Result<()> {
     }
 }
 
+/// Create the RoutineEngine, claim the scheduler lock and start scheduling.
+///
+/// Returns `None` when the engine cannot be created; routines are then skipped.
+async fn start_routines(
+    config: Arc<Config>,
+    memory: Arc<dyn crate::memory::Memory>,
+    data_dir: &Path,
+) -> Option<Arc<RoutineEngine>> {
+    let routines = config
+        .routines
+        .jobs
+        .iter()
+        .map(Routine::from_config)
+        .collect();
+    // Degraded data directory: keep routines running without persistence
+    let db_path = if crate::memory::is_degraded() {
+        PathBuf::from(":memory:")
+    } else {
+        data_dir.join("routines.db")
+    };
+    let engine = match RoutineEngine::new(routines, config, memory, &db_path).await {
+        Ok(engine) => Arc::new(engine),
+        Err(e) => {
+            warn!("RoutineEngine unavailable, routines are skipped: {:#}", e);
+            return None;
+        }
+    };
+    if let Some(notice) = engine.claim_scheduler().standby_notice() {
+        info!("{}", notice);
+    }
+    let scheduler = engine.clone();
+    tokio::spawn(async move {
+        if let Err(e) = scheduler.start().await {
+            error!("RoutineEngine failed to start: {:#}", e);
+        }
+    });
+    Some(engine)
+}
+
+/// Run a `routine` tool call forwarded by a standby process on the daemon's engine.
+async fn routine_reply(
+    routines: Option<&Arc<RoutineEngine>>,
+    args: serde_json::Value,
+) -> DaemonMessage {
+    let Some(engine) = routines else {
+        return DaemonMessage::Error {
+            message: "Routines are not running in the daemon".to_string(),
+        };
+    };
+    // Natural-language schedules are converted to cron by the sender
+    let tool = RoutineTool::new(engine.clone(), None, String::new());
+    match tool.execute(args, &SecurityPolicy::default()).await {
+        Ok(result) => DaemonMessage::RoutineResult {
+            success: result.success,
+            output: result.output,
+            error: result.error,
+        },
+        Err(e) => DaemonMessage::Error {
+            message: format!("{:#}", e),
+        },
+    }
+}
+
+/// Probe the default provider while offline, re-reading it after each reload.
+fn spawn_offline_probe(
+    live: Arc<LiveConfig>,
+    state: Arc<OfflineState>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    crate::providers::offline::spawn_probe_task_with(
+        state,
+        move || {
+            let config = live.snapshot();
+            config
+                .providers
+                .get(&config.default.provider)
+                .map(|provider| provider.base_url.clone())
+        },
+        interval,
+    )
+}
+
 /// Handle a single CLI client connection.
 ///
-/// Each client gets its own Agent instance (channel isolation).
+/// Each message gets a fresh Agent from the shared factory (channel isolation).
+/// The factory tracks the config generation, so `rrclaw reload` applies to the
+/// next message.
+///
+/// Messages are processed in order by a worker task so the reader keeps draining
+/// the socket: a `ConfirmResponse` has to reach the [`ConfirmBroker`] while the
+/// turn that asked for it is still running.
 async fn handle_client(
     stream: tokio::net::UnixStream,
-    config: Config,
-    memory: Arc<SqliteMemory>,
+    factory: Arc<AgentFactory>,
+    skill_usage: Option<Arc<dyn SkillUsageRecorder>>,
+    routines: Option<Arc<RoutineEngine>>,
 ) -> Result<()> {
+    let live = factory.live_config().clone();
     let (reader, mut writer) = stream.into_split();
     let mut lines = BufReader::new(reader).lines();
 
     info!("New CLI client connected");
 
-    // Create a dedicated Agent for this session
-    let provider_key = config.default.provider.clone();
-    let provider_config = config
-        .providers
-        .get(&provider_key)
-        .ok_or_else(|| color_eyre::eyre::eyre!("Provider '{}' not found in config", provider_key))?
-        .clone();
+    // Single writer: replies, errors and confirmation requests share one ordered stream
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<DaemonMessage>();
+    let writer_task = tokio::spawn(async move {
+        while let Some(msg) = out_rx.recv().await {
+            if let Err(e) = send_message(&mut writer, &msg).await {
+                warn!("Failed to write to client: {:#}", e);
+                break;
+            }
+        }
+    });
+
+    let timeout = Duration::from_secs(live.snapshot().daemon.confirm_timeout_secs);
+    let broker = ConfirmBroker::new(out_tx.clone(), timeout);
 
-    let model = config.default.model.clone();
-    let temperature = config.default.temperature;
+    let (msg_tx, mut msg_rx) = mpsc::unbounded_channel::<(String, Option<PathBuf>)>();
+    let worker = tokio::spawn({
+        let out_tx = out_tx.clone();
+        let broker = broker.clone();
+        async move {
+            while let Some((content, workspace)) = msg_rx.recv().await {
+                let response = process_message(
+                    &content,
+                    workspace.as_deref(),
+                    &factory,
+                    skill_usage.clone(),
+                    broker.clone(),
+                )
+                .await;
+                let replies = match response {
+                    Ok(text) => vec![DaemonMessage::Token { content: text }, DaemonMessage::Done],
+                    Err(e) => vec![DaemonMessage::Error {
+                        message: format!("{:#}", e),
+                    }],
+                };
+                for reply in replies {
+                    let _ = out_tx.send(reply);
+                }
+            }
+        }
+    });
 
-    while let Some(line) = lines.next_line().await? {
+    let read_result = loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break Ok(()),
+            Err(e) => break Err(e),
+        };
         let msg: ClientMessage = match serde_json::from_str(&line) {
             Ok(m) => m,
             Err(e) => {
-                let err = DaemonMessage::Error {
+                let _ = out_tx.send(DaemonMessage::Error {
                     message: format!("Invalid message: {}", e),
-                };
-                send_message(&mut writer, &err).await?;
+                });
                 continue;
             }
         };
@@ -124,141 +371,82 @@ async fn handle_client(
             ClientMessage::Message {
                 session_id: _,
                 content,
+                workspace,
             } => {
-                // Build a one-shot agent and process the message
-                let response = process_message(
-                    &content,
-                    &config,
-                    &provider_config,
-                    &provider_key,
-                    &model,
-                    temperature,
-                    &memory,
-                )
-                .await;
-
-                match response {
-                    Ok(text) => {
-                        send_message(&mut writer, &DaemonMessage::Token { content: text }).await?;
-                        send_message(&mut writer, &DaemonMessage::Done).await?;
-                    }
-                    Err(e) => {
-                        send_message(
-                            &mut writer,
-                            &DaemonMessage::Error {
-                                message: format!("{:#}", e),
-                            },
-                        )
-                        .await?;
-                    }
-                }
+                let _ = msg_tx.send((content, workspace));
             }
-            ClientMessage::ConfirmResponse { .. } => {
-                // TODO: forward to pending confirm request in Agent
-                send_message(
-                    &mut writer,
-                    &DaemonMessage::Error {
-                        message: "Confirm not yet implemented in daemon mode".to_string(),
+            ClientMessage::Reload => {
+                let reply = match reload_config(&live) {
+                    Ok(report) => DaemonMessage::Reloaded {
+                        applied: report.applied,
+                        deferred: report.deferred,
                     },
-                )
-                .await?;
+                    Err(e) => DaemonMessage::Error {
+                        message: format!("{:#}", e),
+                    },
+                };
+                let _ = out_tx.send(reply);
+            }
+            ClientMessage::ConfirmResponse(response) => {
+                broker.resolve(response);
+            }
+            ClientMessage::Routine { args } => {
+                // `run` waits for the routine agent; keep reading meanwhile
+                let out_tx = out_tx.clone();
+                let routines = routines.clone();
+                tokio::spawn(async move {
+                    let _ = out_tx.send(routine_reply(routines.as_ref(), args).await);
+                });
             }
         }
-    }
+    };
 
     info!("CLI client disconnected");
-    Ok(())
+    // Pending and later confirmations are denied; the running turn finishes on its own
+    broker.close();
+    drop(msg_tx);
+    drop(out_tx);
+    let _ = worker.await;
+    drop(broker);
+    let _ = writer_task.await;
+    read_result.wrap_err("Failed to read from client")
+}
+
+/// Re-read config.toml and install it, keeping startup-bound settings unchanged.
+fn reload_config(live: &LiveConfig) -> Result<ReloadReport> {
+    let config_path = Config::config_path()?;
+    let new_config = Config::load_from_path(&config_path)?;
+    let plan = plan_reload(&live.snapshot(), new_config)?;
+    // Always install (bumps the generation) so cached Telegram agents re-read identity files
+    live.replace(plan.effective);
+    info!(
+        "Config reloaded: applied [{}], needs restart [{}]",
+        plan.report.applied.join(", "),
+        plan.report.deferred.join(", ")
+    );
+    Ok(plan.report)
 }
 
 /// Process a single user message through the Agent and return the text response.
+///
+/// `workspace` selects a per-workspace agent: security policy, shell cwd, project
+/// skills and identity files all resolve against it. The factory caches these per
+/// directory, so several `rrclaw chat --workspace` clients share one daemon.
 async fn process_message(
     content: &str,
-    config: &Config,
-    provider_config: &crate::config::ProviderConfig,
-    provider_key: &str,
-    model: &str,
-    temperature: f64,
-    memory: &Arc<SqliteMemory>,
+    workspace: Option<&std::path::Path>,
+    factory: &AgentFactory,
+    skill_usage: Option<Arc<dyn SkillUsageRecorder>>,
+    broker: ConfirmBroker,
 ) -> Result<String> {
-    let data_dir = data_dir()?;
-    let log_dir = log_dir()?;
-    let config_path = Config::config_path()?;
-    let workspace_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-
-    // Create provider
-    let provider = crate::providers::create_provider(provider_config);
-    let retry_config = crate::providers::RetryConfig {
-        max_retries: config.reliability.max_retries,
-        initial_backoff_ms: config.reliability.initial_backoff_ms,
-        ..Default::default()
-    };
-    let provider: Box<dyn crate::providers::Provider> = Box::new(
-        crate::providers::ReliableProvider::new(provider, retry_config),
-    );
-
-    // Load skills
-    let global_skills_dir = {
-        let base_dirs = directories::BaseDirs::new()
-            .ok_or_else(|| color_eyre::eyre::eyre!("Cannot determine home directory"))?;
-        base_dirs.home_dir().join(".rrclaw").join("skills")
+    let mut agent = match workspace {
+        Some(dir) => factory.create_agent_in(dir)?,
+        None => factory.create_agent()?,
     };
-    let builtin = crate::skills::builtin_skills(Config::get_language());
-    let skills = crate::skills::load_skills(&workspace_dir, &global_skills_dir, builtin);
-
-    // Create provider Arc for HttpRequestTool
-    let provider_arc: Arc<dyn crate::providers::Provider> =
-        Arc::new(crate::providers::ReliableProvider::new(
-            crate::providers::create_provider(provider_config),
-            crate::providers::RetryConfig {
-                max_retries: config.reliability.max_retries,
-                initial_backoff_ms: config.reliability.initial_backoff_ms,
-                ..Default::default()
-            },
-        ));
-
-    // Create tools (no routine engine in daemon for now)
-    let tools = crate::tools::create_tools(
-        config.clone(),
-        provider_arc,
-        data_dir.clone(),
-        log_dir.clone(),
-        config_path.clone(),
-        skills.clone(),
-        memory.clone() as Arc<dyn crate::memory::Memory>,
-        None,
-    );
-
-    // Security policy
-    let policy = crate::security::SecurityPolicy {
-        autonomy: config.security.autonomy.clone(),
-        allowed_commands: config.security.allowed_commands.clone(),
-        workspace_dir,
-        blocked_paths: crate::security::SecurityPolicy::default().blocked_paths,
-        http_allowed_hosts: config.security.http_allowed_hosts.clone(),
-        injection_check: config.security.injection_check,
-    };
-
-    // Identity
-    let rrclaw_home = data_dir
-        .parent()
-        .unwrap_or(data_dir.as_path())
-        .to_path_buf();
-    let identity_context =
-        crate::agent::identity::load_identity_context(&policy.workspace_dir, &rrclaw_home);
-
-    // Create agent
-    let mut agent = crate::agent::Agent::new(
-        provider,
-        tools,
-        Box::new(memory.clone()),
-        policy,
-        provider_key.to_string(),
-        provider_config.base_url.clone(),
-        model.to_string(),
-        temperature,
-        skills,
-        identity_context,
-    );
+    agent.set_approval_policy(Box::new(broker));
+    if let Some(usage) = skill_usage {
+        agent.set_skill_usage_recorder(usage);
+    }
 
     // Process message (non-streaming for now)
     let response = agent.process_message(content).await?;
@@ -278,13 +466,117 @@ async fn send_message(
 }
 
 fn data_dir() -> Result<PathBuf> {
-    let base_dirs = directories::BaseDirs::new()
-        .ok_or_else(|| color_eyre::eyre::eyre!("Cannot determine home directory"))?;
-    Ok(base_dirs.home_dir().join(".rrclaw").join("data"))
+    Ok(crate::config::RrclawPaths::resolve()?.data_dir())
 }
 
 fn log_dir() -> Result<PathBuf> {
-    let base_dirs = directories::BaseDirs::new()
-        .ok_or_else(|| color_eyre::eyre::eyre!("Cannot determine home directory"))?;
-    Ok(base_dirs.home_dir().join(".rrclaw").join("logs"))
+    Ok(crate::config::RrclawPaths::resolve()?.log_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+
+    fn config_with_provider(base_url: &str) -> Config {
+        let mut config = Config::default();
+        config.default.provider = "main".to_string();
+        config.providers.insert(
+            "main".to_string(),
+            ProviderConfig {
+                base_url: base_url.to_string(),
+                api_key: String::new(),
+                model: "m".to_string(),
+                auth_style: None,
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        config
+    }
+
+    #[tokio::test]
+    async fn offline_probe_recovers_after_provider_becomes_reachable() {
+        // Port 1: connection refused, so the daemon stays offline
+        let live = Arc::new(LiveConfig::new(config_with_provider("http://127.0.0.1:1")));
+        let state = Arc::new(OfflineState::new());
+        state.mark_offline("connection refused");
+        let probe = spawn_offline_probe(live.clone(), state.clone(), Duration::from_millis(20));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(state.is_offline());
+
+        // Reload points the default provider at a listening port
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        live.replace(config_with_provider(&format!("http://{}", addr)));
+
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while state.is_offline() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the probe should bring the daemon back online");
+        probe.abort();
+    }
+
+    #[tokio::test]
+    async fn routine_request_runs_on_the_daemon_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = crate::config::RrclawPaths::in_home(dir.path());
+        let memory: Arc<dyn crate::memory::Memory> = Arc::new(crate::memory::NoopMemory);
+        let engine = |memory: Arc<dyn crate::memory::Memory>| {
+            let paths = paths.clone();
+            let db_path = dir.path().join("routines.db");
+            async move {
+                let engine = RoutineEngine::new(vec![], Arc::default(), memory, &db_path)
+                    .await
+                    .unwrap()
+                    .with_paths(paths);
+                Arc::new(engine)
+            }
+        };
+        // The daemon holds the scheduler lock; the second engine is a standby process
+        let scheduler = engine(memory.clone()).await;
+        assert_eq!(
+            scheduler.claim_scheduler(),
+            crate::routines::SchedulerRole::Scheduler
+        );
+        let standby = engine(memory.clone()).await;
+        assert!(standby.claim_scheduler().standby_notice().is_some());
+
+        let sock = dir.path().join("daemon.sock");
+        let listener = UnixListener::bind(&sock).unwrap();
+        let factory = Arc::new(AgentFactory::new(
+            Arc::new(LiveConfig::new(Config::default())),
+            memory,
+            paths.clone(),
+        ));
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle_client(stream, factory, None, Some(scheduler)).await
+        });
+
+        let stream = tokio::net::UnixStream::connect(&sock).await.unwrap();
+        let args = serde_json::json!({
+            "action": "create",
+            "name": "standup",
+            "schedule": "0 9 * * 1-5",
+            "message": "提醒我开站会"
+        });
+        let result = super::super::client::request_routine(stream, &args)
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        server.await.unwrap().unwrap();
+
+        // Written by the scheduler, so the standby sees it after a refresh
+        standby.reload_if_standby().await;
+        assert!(standby.get_routine("standup").is_some());
+    }
 }
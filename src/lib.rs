@@ -5,8 +5,11 @@ pub mod daemon;
 pub mod i18n;
 pub mod mcp;
 pub mod memory;
+pub mod metrics;
 pub mod providers;
+pub mod report;
 pub mod routines;
 pub mod security;
 pub mod skills;
 pub mod tools;
+pub mod update;
@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use color_eyre::eyre::{Context, Result};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -9,6 +9,14 @@ use tracing_subscriber::prelude::*;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// 提高终端日志级别（-v: info，-vv: debug，-vvv: trace），不影响日志文件
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// 使用指定的配置文件（data / logs / skills 等放在该文件所在目录，与默认目录隔离）
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -26,6 +34,42 @@ enum Commands {
         /// 指定模型（覆盖配置文件中的 default）
         #[arg(long)]
         model: Option<String>,
+
+        /// 把 --provider / --model 写入 config.toml，作为之后的默认值（写入前校验已配置）
+        #[arg(long)]
+        save: bool,
+
+        /// 单次消息模式下继续最近一次会话（加载其历史，结束后写回）
+        #[arg(long = "continue", requires = "message")]
+        continue_session: bool,
+
+        /// 单次消息模式的输出格式
+        #[arg(long, value_enum, default_value_t, requires = "message")]
+        format: rrclaw::channels::cli::OutputFormat,
+
+        /// 安全模式：不加载任何工具，纯对话（等同 security.safe_mode = true）
+        #[arg(long)]
+        safe: bool,
+
+        /// 临时模式：不读写记忆库与对话历史（NoopMemory），退出后不留任何状态
+        #[arg(long, visible_alias = "no-memory")]
+        ephemeral: bool,
+
+        /// 工作目录（默认当前目录）：文件工具的相对路径、项目级 Skills / 身份文件、Routine 均以此为准
+        #[arg(long, value_name = "PATH")]
+        workspace: Option<PathBuf>,
+
+        /// 把每次 Provider 请求 / 响应按请求哈希录制为夹具文件
+        #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+
+        /// 从夹具目录回放 Provider 响应（未命中即报错，不访问网络）
+        #[arg(long, value_name = "DIR")]
+        replay: Option<PathBuf>,
+
+        /// 跳过启动预检（交互模式进入 REPL 前向默认 Provider 发一次极小的请求，检查 API Key 与网络）
+        #[arg(long)]
+        no_preflight: bool,
     },
     /// 启动 Telegram Bot（需要 --features telegram 编译）
     #[cfg(feature = "telegram")]
@@ -33,76 +77,276 @@ enum Commands {
     /// Start daemon (background process with Telegram + IPC socket)
     Start,
     /// Connect to running daemon for interactive chat
-    Chat,
+    Chat {
+        /// Workspace directory for this chat (default: the daemon's working directory)
+        #[arg(long, value_name = "PATH")]
+        workspace: Option<PathBuf>,
+    },
     /// Stop the running daemon
     Stop,
     /// Restart the daemon (stop + start)
     Restart,
+    /// Reload config.toml in the running daemon without dropping sessions
+    Reload,
     /// Show daemon status
     Status,
     /// Internal: daemon worker process (do not call directly)
     #[command(hide = true)]
     DaemonWorker,
-    /// 交互式配置向导
-    Setup,
+    /// 交互式配置向导（`rrclaw setup mcp` 添加 MCP Server）
+    Setup {
+        #[command(subcommand)]
+        target: Option<SetupTarget>,
+    },
     /// 初始化配置文件
     Init,
+    /// 初始化当前项目的 .rrclaw/（检测工具链，生成 AGENT.md、skills 说明、命令白名单建议）
+    InitProject,
     /// 显示当前配置
-    Config,
+    Config {
+        /// 输出覆盖全部配置项的带注释示例（默认值 + 可选段示例），不读取配置文件
+        #[arg(long, visible_alias = "schema")]
+        example: bool,
+    },
+    /// 检查配置文件（不存在的 Provider、无效 cron、未知配置项等）
+    Doctor,
+    /// 生成 OS 沙箱配置（Linux: bubblewrap / systemd-run 包装脚本，macOS: sandbox-exec profile）
+    SandboxProfile {
+        /// 目标平台（默认当前平台）
+        #[arg(long, value_enum)]
+        platform: Option<rrclaw::security::sandbox::Platform>,
+        /// 输出文件路径（默认输出到 stdout）
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 用户反馈（/good、/bad、Telegram 👍/👎）
+    Feedback {
+        #[command(subcommand)]
+        action: FeedbackCommands,
+    },
+    /// 数据目录维护：备份 / 恢复 / VACUUM / 统计
+    Memory {
+        #[command(subcommand)]
+        action: MemoryCommands,
+    },
+    /// 系统钥匙串中的具名密钥（Agent 通过 secret 工具读取，默认不向模型返回明文）
+    Secret {
+        #[command(subcommand)]
+        action: SecretCommands,
+    },
+    /// 升级到 GitHub 上的最新发布（校验 SHA256SUMS 后替换当前可执行文件）
+    SelfUpdate,
+    /// 输出 shell 补全脚本（如 `rrclaw completions zsh > _rrclaw`）
+    Completions {
+        /// 目标 shell：bash / zsh / fish / elvish / powershell
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum SetupTarget {
+    /// 添加 MCP Server：测试连接后写入 config.toml 的 [mcp.servers.<名称>]
+    Mcp,
+}
+
+#[derive(Subcommand)]
+enum FeedbackCommands {
+    /// 以 JSONL 导出全部反馈（默认输出到 stdout）
+    Export {
+        /// 输出文件路径
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemoryCommands {
+    /// 备份数据库（SQLite online backup）、身份文件和全局 Skills 到 .tar.gz
+    Backup {
+        /// 输出文件路径（如 rrclaw-backup.tar.gz）
+        path: PathBuf,
+    },
+    /// 从备份恢复（daemon 运行中时拒绝执行）
+    Restore {
+        /// 备份文件路径
+        path: PathBuf,
+    },
+    /// 执行 VACUUM + ANALYZE 并报告回收空间
+    Vacuum,
+    /// 显示各数据库文件大小和每表行数
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum SecretCommands {
+    /// 写入密钥（终端中隐藏输入；非终端时从 stdin 读取一行）
+    Set {
+        /// 密钥名称（字母、数字、_ - .）
+        name: String,
+    },
+    /// 列出已保存的密钥名称
+    List,
+    /// 删除密钥
+    Rm {
+        /// 密钥名称
+        name: String,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
-    init_tracing()?;
-
     let cli = Cli::parse();
+    if let Some(path) = &cli.config {
+        rrclaw::config::paths::set_config_override(path)?;
+    }
+    // 在日志初始化之前：迁移后日志应写到新目录
+    if !matches!(
+        cli.command,
+        Commands::DaemonWorker | Commands::Completions { .. }
+    ) {
+        offer_legacy_migration()?;
+    }
+    init_tracing(cli.verbose)?;
+    rrclaw::report::install_panic_hook(rrclaw::config::RrclawPaths::resolve()?.reports_dir());
 
     match cli.command {
         Commands::Agent {
             message,
             provider,
             model,
-        } => run_agent(message, provider, model).await?,
+            save,
+            continue_session,
+            format,
+            safe,
+            ephemeral,
+            workspace,
+            record,
+            replay,
+            no_preflight,
+        } => {
+            let fixtures = record
+                .map(rrclaw::providers::recording::FixtureMode::Record)
+                .or(replay.map(rrclaw::providers::recording::FixtureMode::Replay));
+            run_agent(
+                message,
+                provider,
+                model,
+                save,
+                continue_session,
+                format,
+                safe,
+                ephemeral,
+                workspace,
+                fixtures,
+                no_preflight,
+            )
+            .await?
+        }
         #[cfg(feature = "telegram")]
         Commands::Telegram => run_telegram().await?,
         Commands::Start => rrclaw::daemon::start()?,
-        Commands::Chat => rrclaw::daemon::client::run_chat().await?,
+        Commands::Chat { workspace } => rrclaw::daemon::client::run_chat(workspace).await?,
         Commands::Stop => rrclaw::daemon::stop()?,
         Commands::Restart => rrclaw::daemon::restart()?,
+        Commands::Reload => rrclaw::daemon::reload().await?,
         Commands::Status => rrclaw::daemon::status()?,
         Commands::DaemonWorker => rrclaw::daemon::server::run_daemon_worker().await?,
-        Commands::Setup => rrclaw::config::run_setup()?,
+        Commands::Setup { target: None } => rrclaw::config::run_setup()?,
+        Commands::Setup {
+            target: Some(SetupTarget::Mcp),
+        } => rrclaw::config::run_mcp_setup().await?,
         Commands::Init => run_init()?,
-        Commands::Config => run_config()?,
+        Commands::InitProject => {
+            rrclaw::channels::cli::run_init_project(&std::env::current_dir()?)?
+        }
+        Commands::Config { example } => run_config(example)?,
+        Commands::Doctor => run_doctor()?,
+        Commands::SandboxProfile { platform, output } => run_sandbox_profile(platform, output)?,
+        Commands::Feedback { action } => match action {
+            FeedbackCommands::Export { output } => run_feedback_export(output).await?,
+        },
+        Commands::Memory { action } => run_memory(action).await?,
+        Commands::Secret { action } => run_secret(action)?,
+        Commands::SelfUpdate => run_self_update().await?,
+        Commands::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "rrclaw", &mut std::io::stdout());
+        }
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_agent(
     message: Option<String>,
     provider_name: Option<String>,
     model_override: Option<String>,
+    save: bool,
+    continue_session: bool,
+    format: rrclaw::channels::cli::OutputFormat,
+    safe: bool,
+    ephemeral: bool,
+    workspace: Option<PathBuf>,
+    fixtures: Option<rrclaw::providers::recording::FixtureMode>,
+    no_preflight: bool,
 ) -> Result<()> {
-    let config = rrclaw::config::Config::load_or_init().wrap_err("加载配置失败")?;
+    let mut config = rrclaw::config::Config::load_or_init().wrap_err("加载配置失败")?;
+    // --safe 写入本进程的配置副本，Routine / Telegram 创建的 Agent 同样不带工具
+    config.security.safe_mode |= safe;
+
+    // --workspace：校验（存在、是目录、不在 blocked_paths 下）后代替当前目录
+    let mut workspace_policy = rrclaw::security::SecurityPolicy::default();
+    if let Some(dir) = &workspace {
+        workspace_policy
+            .set_workspace(dir)
+            .wrap_err("--workspace 无效")?;
+    }
+    let workspace_dir = workspace_policy.workspace_dir;
+
+    // --save：校验后把 --provider / --model 写回 config.toml 的 [default]
+    if save {
+        if provider_name.is_none() && model_override.is_none() {
+            color_eyre::eyre::bail!("--save 需要同时给出 --provider 和/或 --model");
+        }
+        let provider = provider_name
+            .clone()
+            .unwrap_or_else(|| config.default.provider.clone());
+        let model = model_override
+            .clone()
+            .unwrap_or_else(|| config.default.model.clone());
+        let config_path = rrclaw::config::Config::config_path()?;
+        config.save_default(&config_path, &provider, &model)?;
+        let english = rrclaw::config::Config::get_language().is_english();
+        eprintln!(
+            "{} {}/{}",
+            if english {
+                "Saved as the new default:"
+            } else {
+                "已保存为默认:"
+            },
+            provider,
+            model
+        );
+    }
 
     // 确定使用的 provider
     let provider_key = provider_name.as_deref().unwrap_or(&config.default.provider);
 
-    let provider_config = config
-        .providers
-        .get(provider_key)
-        .ok_or_else(|| {
-            color_eyre::eyre::eyre!(
-                "Provider '{}' 未在配置文件中配置。请编辑 ~/.rrclaw/config.toml 添加 [providers.{}] 配置。",
-                provider_key,
-                provider_key
-            )
-        })?;
+    let provider_config = config.providers.get(provider_key).ok_or_else(|| {
+        color_eyre::eyre::eyre!(
+            "Provider '{}' 未在配置文件中配置。请编辑 {} 添加 [providers.{}] 配置。",
+            provider_key,
+            rrclaw::config::Config::config_path()
+                .unwrap_or_default()
+                .display(),
+            provider_key
+        )
+    })?;
 
     // 确定模型
     let model = model_override.unwrap_or_else(|| config.default.model.clone());
+    rrclaw::report::set_session(&config, provider_key, &model);
 
     // 创建 Provider
     let main_provider = rrclaw::providers::create_provider(provider_config);
@@ -116,25 +360,36 @@ async fn run_agent(
         .map(|pc| rrclaw::providers::create_provider(pc))
         .collect();
 
+    // metrics 标签：主 Provider + 已配置的 fallback（顺序与上面一致）
+    let provider_names: Vec<String> = std::iter::once(provider_key.to_string())
+        .chain(
+            config
+                .reliability
+                .fallback_providers
+                .iter()
+                .filter(|name| config.providers.contains_key(*name))
+                .cloned(),
+        )
+        .collect();
+
     // 包装为 ReliableProvider
-    let retry_config = rrclaw::providers::RetryConfig {
-        max_retries: config.reliability.max_retries,
-        initial_backoff_ms: config.reliability.initial_backoff_ms,
-        ..Default::default()
-    };
+    let retry_config = rrclaw::providers::RetryConfig::from_reliability(&config.reliability);
 
     // Arc<dyn Provider> 用于 HttpRequestTool 的 mini-LLM 提取
     let provider_arc: Arc<dyn rrclaw::providers::Provider> = if fallback_providers.is_empty() {
-        Arc::new(rrclaw::providers::ReliableProvider::new(
-            main_provider,
-            retry_config.clone(),
-        ))
+        Arc::new(
+            rrclaw::providers::ReliableProvider::new(main_provider, retry_config.clone())
+                .with_names(provider_names.clone()),
+        )
     } else {
-        Arc::new(rrclaw::providers::ReliableProvider::with_fallbacks(
-            main_provider,
-            fallback_providers,
-            retry_config.clone(),
-        ))
+        Arc::new(
+            rrclaw::providers::ReliableProvider::with_fallbacks(
+                main_provider,
+                fallback_providers,
+                retry_config.clone(),
+            )
+            .with_names(provider_names.clone()),
+        )
     };
 
     // Box<dyn Provider> 用于 Agent（重新创建，因为上面的 main_provider 和 fallback_providers 已移动）
@@ -147,17 +402,55 @@ async fn run_agent(
         .collect();
     let main_provider_for_box = rrclaw::providers::create_provider(provider_config);
     let provider: Box<dyn rrclaw::providers::Provider> = if fallback_providers_for_box.is_empty() {
-        Box::new(rrclaw::providers::ReliableProvider::new(
-            main_provider_for_box,
-            retry_config,
-        ))
+        Box::new(
+            rrclaw::providers::ReliableProvider::new(main_provider_for_box, retry_config)
+                .with_names(provider_names),
+        )
     } else {
-        Box::new(rrclaw::providers::ReliableProvider::with_fallbacks(
-            main_provider_for_box,
-            fallback_providers_for_box,
-            retry_config,
-        ))
+        Box::new(
+            rrclaw::providers::ReliableProvider::with_fallbacks(
+                main_provider_for_box,
+                fallback_providers_for_box,
+                retry_config,
+            )
+            .with_names(provider_names),
+        )
+    };
+
+    // --record / --replay 夹具目录优先于 [dev] mode 的 cassette
+    if let Some(mode) = &fixtures {
+        use rrclaw::providers::recording::FixtureMode;
+        let english = rrclaw::config::Config::get_language().is_english();
+        let (action, dir) = match mode {
+            FixtureMode::Record(dir) if english => ("Recording provider fixtures to", dir),
+            FixtureMode::Record(dir) => ("正在录制 Provider 夹具到", dir),
+            FixtureMode::Replay(dir) if english => ("Replaying provider fixtures from", dir),
+            FixtureMode::Replay(dir) => ("正在回放 Provider 夹具:", dir),
+        };
+        eprintln!("{} {}", action, dir.display());
+    }
+    // [dev] mode：录制 / 回放 Agent 的 Provider 交互（包在 ReliableProvider 外层）
+    let provider = match &fixtures {
+        Some(mode) => rrclaw::providers::recording::with_fixtures(provider, mode)?,
+        None => rrclaw::providers::recording::with_cassette(provider, &config.dev)?,
     };
+    if fixtures.is_none() && config.dev.mode != rrclaw::config::CassetteMode::Off {
+        let english = rrclaw::config::Config::get_language().is_english();
+        let action = match (config.dev.mode, english) {
+            (rrclaw::config::CassetteMode::Record, true) => "Recording provider interactions to",
+            (rrclaw::config::CassetteMode::Record, false) => "正在录制 Provider 交互到",
+            (_, true) => "Replaying provider interactions from",
+            (_, false) => "正在回放 Provider 交互:",
+        };
+        eprintln!("{} {}", action, config.dev.cassette.display());
+    }
+
+    // 离线模式：后台定期探测 Provider，网络恢复后自动清除离线标记
+    rrclaw::providers::offline::spawn_probe_task(
+        rrclaw::providers::OfflineState::global(),
+        provider_config.base_url.clone(),
+        rrclaw::providers::offline::PROBE_INTERVAL,
+    );
 
     // 创建 Memory（Arc 共享给 Agent 和 CLI）
     let data_dir = data_dir()?;
@@ -165,18 +458,30 @@ async fn run_agent(
     let config_path = rrclaw::config::Config::config_path()?;
 
     // 加载 Skills（内置 > 全局 > 项目级）
-    let workspace_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-    let global_skills_dir = {
-        let base_dirs = directories::BaseDirs::new()
-            .ok_or_else(|| color_eyre::eyre::eyre!("无法获取 home 目录"))?;
-        base_dirs.home_dir().join(".rrclaw").join("skills")
-    };
+    let global_skills_dir = rrclaw::config::RrclawPaths::resolve()?.skills_dir();
     let builtin = rrclaw::skills::builtin_skills(rrclaw::config::Config::get_language());
     let skills = rrclaw::skills::load_skills(&workspace_dir, &global_skills_dir, builtin);
 
-    // 创建 Memory（Arc 共享给 Tools）
-    let memory =
-        Arc::new(rrclaw::memory::SqliteMemory::open(&data_dir).wrap_err("初始化 Memory 失败")?);
+    // 创建 Memory（Arc 共享给 Tools）；--ephemeral 不打开数据目录，会话历史只存于内存
+    let memory = if ephemeral {
+        Arc::new(rrclaw::memory::SqliteMemory::in_memory().wrap_err("创建临时 Memory 失败")?)
+    } else {
+        Arc::new(open_memory_or_degrade(&config, &data_dir)?)
+    };
+    // Agent / 工具 / Routine 使用的记忆：--ephemeral 时为 NoopMemory（不存也不召回）
+    let agent_memory: Arc<dyn rrclaw::memory::Memory> = if ephemeral {
+        eprintln!(
+            "{}",
+            if rrclaw::config::Config::get_language().is_english() {
+                "Ephemeral mode: memories and conversation history will not be saved."
+            } else {
+                "临时模式：不保存记忆与对话历史。"
+            }
+        );
+        Arc::new(rrclaw::memory::NoopMemory)
+    } else {
+        memory.clone()
+    };
 
     // ─── RoutineEngine 初始化 ────────────────────────────────────────────
     // 构建 Routine 列表（从 config 的静态配置转换）
@@ -184,28 +489,41 @@ async fn run_agent(
         .routines
         .jobs
         .iter()
-        .map(|job| rrclaw::routines::Routine {
-            name: job.name.clone(),
-            schedule: job.schedule.clone(),
-            message: job.message.clone(),
-            channel: job.channel.clone(),
-            enabled: job.enabled,
-            source: rrclaw::routines::RoutineSource::Config,
-        })
+        .map(rrclaw::routines::Routine::from_config)
         .collect();
 
-    // 初始化 RoutineEngine
-    let routines_db_path = data_dir.join("routines.db");
+    // 初始化 RoutineEngine（数据目录不可用或临时模式时用内存库，定时任务照常运行但不持久化）
+    let routines_db_path = if ephemeral || rrclaw::memory::is_degraded() {
+        PathBuf::from(":memory:")
+    } else {
+        data_dir.join("routines.db")
+    };
     let routine_engine = match rrclaw::routines::RoutineEngine::new(
         static_routines,
         Arc::new(config.clone()),
-        memory.clone() as Arc<dyn rrclaw::memory::Memory>,
+        agent_memory.clone(),
         &routines_db_path,
     )
     .await
     {
         Ok(engine) => {
-            let engine = Arc::new(engine);
+            let engine = Arc::new(engine.with_workspace_dir(workspace_dir.clone()));
+            // 同一数据目录只允许一个进程调度：锁被占用时本进程只读，避免重复执行。
+            // 内存库（--ephemeral / 数据目录降级）不碰数据目录的锁，始终自行调度
+            if let rrclaw::routines::SchedulerRole::Standby { pid } = engine.claim_scheduler() {
+                let holder = pid.map_or_else(|| "?".to_string(), |p| p.to_string());
+                if rrclaw::config::Config::get_language().is_english() {
+                    eprintln!(
+                        "Routines are handled by another rrclaw process (pid {}); scheduling is skipped here",
+                        holder
+                    );
+                } else {
+                    eprintln!(
+                        "定时任务由 pid {} 的 rrclaw 进程负责调度，本进程不再调度",
+                        holder
+                    );
+                }
+            }
             // 后台启动调度器（不阻塞 REPL）
             let engine_clone = Arc::clone(&engine);
             tokio::spawn(async move {
@@ -223,22 +541,32 @@ async fn run_agent(
     // ─── RoutineEngine 初始化结束 ────────────────────────────────────────
 
     // 创建 Tools（SelfInfoTool 需要 config 和路径信息，SkillTool 需要 skills，MemoryTools 需要 memory，HttpRequestTool 需要 provider）
-    let mut tools = rrclaw::tools::create_tools(
-        config.clone(),
-        provider_arc,
-        data_dir.clone(),
-        log_dir.clone(),
-        config_path.clone(),
-        skills.clone(),
-        memory.clone() as Arc<dyn rrclaw::memory::Memory>,
-        routine_engine.clone(),
-    );
+    // 安全模式不创建任何工具，也不启动 MCP Server
+    let safe_mode = config.security.safe_mode;
+    let mut tools = if safe_mode {
+        Vec::new()
+    } else {
+        rrclaw::tools::create_tools(
+            config.clone(),
+            provider_arc,
+            data_dir.clone(),
+            log_dir.clone(),
+            config_path.clone(),
+            skills.clone(),
+            agent_memory.clone(),
+            routine_engine.clone(),
+        )
+    };
 
     // MCP 工具加载（可选，配置了才加载）
-    let mcp_manager = if let Some(mcp_config) = &config.mcp {
+    let mcp_manager = if safe_mode {
+        None
+    } else if let Some(mcp_config) = &config.mcp {
         if !mcp_config.servers.is_empty() {
-            let mgr = rrclaw::mcp::McpManager::connect_all(&mcp_config.servers).await;
-            let mcp_tools = mgr.tools_l1().await;
+            let mgr = rrclaw::mcp::McpManager::connect_all(&mcp_config.servers)
+                .await
+                .with_prompt_budget(mcp_config.prompt_budget_chars);
+            let mcp_tools = mgr.tools_l1().await.into_tools();
             if !mcp_tools.is_empty() {
                 tracing::info!("已加载 {} 个 MCP 工具", mcp_tools.len());
                 tools.extend(mcp_tools);
@@ -257,22 +585,23 @@ async fn run_agent(
         .await
         .wrap_err("种入核心知识失败")?;
 
-    // 创建 SecurityPolicy
+    // 创建 SecurityPolicy（`security.require_sandbox` 开启且不在沙箱中时 Full 降为 Supervised）
+    warn_if_full_refused(&config);
     let policy = rrclaw::security::SecurityPolicy {
-        autonomy: config.security.autonomy.clone(),
+        autonomy: rrclaw::security::sandbox::clamp_autonomy(
+            config.security.autonomy.clone(),
+            config.security.require_sandbox,
+        ),
         allowed_commands: config.security.allowed_commands.clone(),
-        workspace_dir: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        workspace_dir: workspace_dir.clone(),
         blocked_paths: rrclaw::security::SecurityPolicy::default().blocked_paths,
         http_allowed_hosts: config.security.http_allowed_hosts.clone(),
         injection_check: config.security.injection_check,
     };
 
     // ─── 身份文件加载（P5-2）────────────────────────────────────────────
-    // identity 文件在 ~/.rrclaw/，而 data_dir 是 ~/.rrclaw/data/，取父目录
-    let rrclaw_home = data_dir
-        .parent()
-        .unwrap_or(data_dir.as_path())
-        .to_path_buf();
+    // identity 文件在配置根目录（~/.rrclaw/ 或 $XDG_CONFIG_HOME/rrclaw/），与 data_dir 分开
+    let rrclaw_home = rrclaw::config::RrclawPaths::resolve()?.home().to_path_buf();
     let identity_context =
         rrclaw::agent::identity::load_identity_context(&policy.workspace_dir, &rrclaw_home);
     if identity_context.is_some() {
@@ -284,7 +613,7 @@ async fn run_agent(
     let mut agent = rrclaw::agent::Agent::new(
         provider,
         tools,
-        Box::new(memory.clone()),
+        Box::new(agent_memory),
         policy,
         provider_key.to_string(),
         provider_config.base_url.clone(),
@@ -293,6 +622,37 @@ async fn run_agent(
         skills.clone(),
         identity_context,
     );
+    agent.set_track_changes(config.cli.show_changes);
+    agent.set_warn_dirty_worktree(config.security.warn_dirty_worktree);
+    agent.set_tool_limits(rrclaw::providers::ToolLimits::for_provider(provider_config));
+    agent.set_context_window(rrclaw::providers::resolve_context_window(
+        provider_key,
+        provider_config,
+        agent.model(),
+    ));
+    agent.configure_aux_models(&config);
+    agent.configure_system_prompt(&config.agent);
+    agent.set_summary_scrubber(rrclaw::security::redact::Redactor::for_memory(&config));
+    agent.set_conversation_filter(rrclaw::agent::ConversationFilter::from_config(
+        &config.memory,
+    ));
+    if safe_mode {
+        agent.enter_safe_mode();
+        eprintln!(
+            "{}",
+            if rrclaw::config::Config::get_language().is_english() {
+                "Safe mode: no tools loaded; the agent can only chat."
+            } else {
+                "安全模式：未加载任何工具，仅对话。"
+            }
+        );
+    }
+    if !ephemeral {
+        match rrclaw::skills::usage::SqliteSkillUsage::open(&data_dir) {
+            Ok(usage) => agent.set_skill_usage_recorder(Arc::new(usage)),
+            Err(e) => tracing::warn!("Skill 使用统计不可用: {:#}", e),
+        }
+    }
 
     // 创建 Telegram 运行时管理器
     let telegram_runtime = Arc::new(rrclaw::channels::cli::TelegramRuntime::new());
@@ -308,13 +668,43 @@ async fn run_agent(
         cfg
     };
 
+    // 启动预检：只在交互模式；夹具 / cassette 回放时不访问网络
+    if message.is_none()
+        && !no_preflight
+        && fixtures.is_none()
+        && config.dev.mode == rrclaw::config::CassetteMode::Off
+    {
+        let ping = rrclaw::providers::create_provider(provider_config);
+        let outcome = rrclaw::providers::preflight::check(ping.as_ref(), agent.model()).await;
+        if let Some(text) = rrclaw::providers::preflight::message(
+            provider_key,
+            &outcome,
+            rrclaw::config::Config::get_language(),
+        ) {
+            eprintln!("{}", text);
+        }
+    }
+
     // 运行
     match message {
-        Some(msg) => rrclaw::channels::cli::run_single(&mut agent, &msg, &memory).await?,
+        Some(msg) => {
+            rrclaw::channels::cli::run_single(&mut agent, &msg, &memory, continue_session, format)
+                .await?
+        }
         None => {
             #[cfg(feature = "telegram")]
             {
-                if telegram_config.is_some() {
+                // daemon 已在轮询同一个 Bot 时不再并行启动 Telegram，提示改用 `rrclaw chat` 连接
+                let daemon_pid = telegram_config
+                    .as_ref()
+                    .and_then(|_| rrclaw::daemon::running_pid());
+                let attach = match daemon_pid {
+                    Some(pid) => offer_daemon_attach(pid)?,
+                    None => false,
+                };
+                if attach {
+                    rrclaw::daemon::client::run_chat(workspace.clone()).await?;
+                } else if telegram_config.is_some() && daemon_pid.is_none() {
                     // 同时启动 CLI 和 Telegram
                     run_cli_with_telegram(
                         &mut agent,
@@ -362,6 +752,27 @@ async fn run_agent(
     Ok(())
 }
 
+/// daemon 已在运行（并轮询 Telegram）时询问是否改用 `rrclaw chat` 连接
+///
+/// 选择否则启动不带 Telegram 的本地会话；非交互终端不询问，直接走本地会话。
+#[cfg(feature = "telegram")]
+fn offer_daemon_attach(pid: u32) -> Result<bool> {
+    use std::io::IsTerminal;
+
+    eprintln!(
+        "daemon 正在运行 (pid {})，Telegram Bot 由它负责；本地会话不再启动 Telegram",
+        pid
+    );
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Ok(false);
+    }
+    dialoguer::Confirm::new()
+        .with_prompt("改用 `rrclaw chat` 连接 daemon？（选择否则启动本地会话）")
+        .default(true)
+        .interact()
+        .wrap_err("读取确认失败")
+}
+
 /// 同时运行 CLI REPL 和 Telegram Bot
 #[cfg(feature = "telegram")]
 #[allow(clippy::too_many_arguments)]
@@ -427,8 +838,16 @@ async fn run_telegram() -> Result<()> {
     let config = rrclaw::config::Config::load_or_init().wrap_err("加载配置失败")?;
 
     let data_dir = data_dir()?;
-    let memory =
-        Arc::new(rrclaw::memory::SqliteMemory::open(&data_dir).wrap_err("初始化 Memory 失败")?);
+    let memory = Arc::new(open_memory_or_degrade(&config, &data_dir)?);
+
+    // 离线后由探测任务恢复（否则 ReliableProvider 一直快速失败）
+    if let Some(provider_config) = config.providers.get(&config.default.provider) {
+        rrclaw::providers::offline::spawn_probe_task(
+            rrclaw::providers::OfflineState::global(),
+            provider_config.base_url.clone(),
+            rrclaw::providers::offline::PROBE_INTERVAL,
+        );
+    }
 
     rrclaw::channels::telegram::run_telegram(config, memory).await
 }
@@ -448,7 +867,12 @@ fn run_init() -> Result<()> {
     Ok(())
 }
 
-fn run_config() -> Result<()> {
+fn run_config(example: bool) -> Result<()> {
+    if example {
+        print!("{}", rrclaw::config::example::example_toml());
+        return Ok(());
+    }
+
     let config_path = rrclaw::config::Config::config_path()?;
 
     if !config_path.exists() {
@@ -459,49 +883,463 @@ fn run_config() -> Result<()> {
     let content = std::fs::read_to_string(&config_path).wrap_err("读取配置文件失败")?;
     println!("配置文件: {}\n", config_path.display());
     println!("{}", content);
+    if let Ok(config) = rrclaw::config::Config::load_from_path(&config_path) {
+        println!("可靠性链: {}", config.describe_reliability_chain());
+    }
+
+    Ok(())
+}
+
+/// 校验配置文件，有警告时以非零状态退出
+fn run_doctor() -> Result<()> {
+    let config_path = rrclaw::config::Config::config_path()?;
+    if !config_path.exists() {
+        println!("配置文件不存在。运行 `rrclaw init` 创建。");
+        return Ok(());
+    }
+
+    let config = rrclaw::config::Config::load_from_path(&config_path).wrap_err("加载配置失败")?;
+    let warnings = rrclaw::config::check_config_file(&config, &config_path);
+    if warnings.is_empty() {
+        println!("配置检查通过: {}", config_path.display());
+        return Ok(());
+    }
+    rrclaw::channels::cli::print_config_warnings(&warnings);
+    color_eyre::eyre::bail!(
+        "配置存在 {} 处问题: {}",
+        warnings.len(),
+        config_path.display()
+    )
+}
+
+/// 按当前配置生成沙箱配置：workspace 为当前目录，放行 RRClaw 根目录与各 Provider 端点
+fn run_sandbox_profile(
+    platform: Option<rrclaw::security::sandbox::Platform>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    use rrclaw::security::sandbox;
 
+    let platform = platform
+        .or_else(sandbox::Platform::current)
+        .ok_or_else(|| {
+            color_eyre::eyre::eyre!("当前平台不支持，请用 --platform 指定 linux 或 macos")
+        })?;
+    let paths = rrclaw::config::RrclawPaths::resolve()?;
+    let config = if paths.config_file().exists() {
+        rrclaw::config::Config::load_from_path(paths.config_file()).wrap_err("加载配置失败")?
+    } else {
+        rrclaw::config::Config::default()
+    };
+    // bubblewrap 的 --bind 要求源目录存在
+    let rrclaw_dirs = paths.writable_dirs();
+    for dir in &rrclaw_dirs {
+        std::fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("创建目录失败: {}", dir.display()))?;
+    }
+    let params = sandbox::ProfileParams {
+        workspace_dir: std::env::current_dir().wrap_err("获取当前目录失败")?,
+        rrclaw_dirs,
+        binary: std::env::current_exe().wrap_err("获取 rrclaw 可执行文件路径失败")?,
+        provider_urls: config
+            .providers
+            .values()
+            .map(|p| p.base_url.clone())
+            .collect(),
+    };
+    let profile = sandbox::generate_profile(platform, &params);
+    match output {
+        Some(path) => {
+            std::fs::write(&path, profile)
+                .wrap_err_with(|| format!("写入失败: {}", path.display()))?;
+            eprintln!("已生成沙箱配置: {}", path.display());
+        }
+        None => print!("{}", profile),
+    }
     Ok(())
 }
 
-/// 获取数据目录: ~/.rrclaw/data/
+/// 导出反馈记录为 JSONL
+async fn run_feedback_export(output: Option<PathBuf>) -> Result<()> {
+    let store = rrclaw::memory::FeedbackStore::open(&data_dir()?)?;
+    let count = match &output {
+        Some(path) => {
+            let mut file = std::fs::File::create(path)
+                .wrap_err_with(|| format!("创建输出文件失败: {}", path.display()))?;
+            store.export_jsonl(&mut file).await?
+        }
+        None => store.export_jsonl(&mut std::io::stdout().lock()).await?,
+    };
+    // 提示写到 stderr，避免污染 stdout 的 JSONL
+    eprintln!("已导出 {} 条反馈", count);
+    Ok(())
+}
+
+/// 管理系统钥匙串中的密钥
+fn run_secret(action: SecretCommands) -> Result<()> {
+    use std::io::IsTerminal;
+
+    let store = rrclaw::tools::secret::SecretStore::system(&data_dir()?);
+    match action {
+        SecretCommands::Set { name } => {
+            rrclaw::tools::secret::validate_name(&name)?;
+            let value = if std::io::stdin().is_terminal() {
+                dialoguer::Password::new()
+                    .with_prompt(format!("密钥 {} 的值", name))
+                    .interact()
+                    .wrap_err("输入密钥失败")?
+            } else {
+                let mut line = String::new();
+                std::io::stdin()
+                    .read_line(&mut line)
+                    .wrap_err("从 stdin 读取密钥失败")?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            };
+            store.set(&name, &value)?;
+            println!("已保存密钥 {}（Agent 可通过 secret 工具读取）", name);
+        }
+        SecretCommands::List => {
+            let names = store.names();
+            if names.is_empty() {
+                println!("尚未保存任何密钥（rrclaw secret set <name> 添加）");
+            }
+            for name in names {
+                println!("{}", name);
+            }
+        }
+        SecretCommands::Rm { name } => {
+            if store.remove(&name)? {
+                println!("已删除密钥 {}", name);
+            } else {
+                println!("密钥 {} 不存在", name);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_memory(action: MemoryCommands) -> Result<()> {
+    use rrclaw::memory::backup;
+    use rrclaw::tools::self_info::format_bytes;
+
+    let paths = rrclaw::config::RrclawPaths::resolve()?;
+    let data_dir = paths.data_dir();
+    // 身份文件和 skills 在配置根目录
+    let home = paths.home().to_path_buf();
+
+    match action {
+        MemoryCommands::Backup { path } => {
+            let report = backup::backup(&home, &data_dir, &path)?;
+            for entry in &report.entries {
+                println!("  + {}", entry);
+            }
+            println!(
+                "已备份到 {}（{}）",
+                path.display(),
+                format_bytes(report.archive_bytes)
+            );
+        }
+        MemoryCommands::Restore { path } => {
+            if let Some(pid) = rrclaw::daemon::running_pid() {
+                color_eyre::eyre::bail!(
+                    "daemon 正在运行 (pid {})，请先执行 `rrclaw stop` 再恢复",
+                    pid
+                );
+            }
+            let report = backup::restore(&home, &data_dir, &path).await?;
+            for entry in &report.restored {
+                println!("  ← {}", entry);
+            }
+            println!(
+                "已从 {} 恢复，重建索引 {} 条记忆",
+                path.display(),
+                report.reindexed
+            );
+        }
+        MemoryCommands::Vacuum => {
+            let reports = backup::vacuum(&data_dir)?;
+            let mut total = 0;
+            for r in &reports {
+                total += r.reclaimed_bytes();
+                println!(
+                    "  {}: {} → {}（回收 {}）",
+                    r.file,
+                    format_bytes(r.before_bytes),
+                    format_bytes(r.after_bytes),
+                    format_bytes(r.reclaimed_bytes())
+                );
+            }
+            println!("共回收 {}", format_bytes(total));
+        }
+        MemoryCommands::Stats => {
+            for db in backup::stats(&data_dir)? {
+                println!("{}  {}", db.file, format_bytes(db.size_bytes));
+                for table in &db.tables {
+                    match table.size_bytes {
+                        Some(size) => println!(
+                            "  {:<24} {:>8} 行  {}",
+                            table.name,
+                            table.rows,
+                            format_bytes(size)
+                        ),
+                        None => println!("  {:<24} {:>8} 行", table.name, table.rows),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_self_update() -> Result<()> {
+    use rrclaw::update::{self, GithubReleases, SelfUpdateOutcome};
+
+    let exe = std::env::current_exe()?;
+    // 通过符号链接（如 Homebrew）安装时替换真实文件
+    let exe = exe.canonicalize().unwrap_or(exe);
+    println!("当前版本 v{}，正在检查最新发布...", update::CURRENT_VERSION);
+
+    let source = GithubReleases::new()?;
+    let outcome = update::self_update(
+        &source,
+        &exe,
+        update::CURRENT_VERSION,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    )
+    .await?;
+    match outcome {
+        SelfUpdateOutcome::UpToDate { latest } => {
+            println!("已是最新版本（最新发布 {}）", latest);
+        }
+        SelfUpdateOutcome::Updated { to } => {
+            println!("已升级到 {}: {}", to, exe.display());
+        }
+        SelfUpdateOutcome::Staged { path, to } => {
+            println!("{} 已下载并通过校验: {}", to, path.display());
+            println!(
+                "运行中的程序无法被替换：请退出 rrclaw，解压后用其中的 exe 覆盖 {}",
+                exe.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 记录沙箱检测结果；配置为 Full 但被 `require_sandbox` 拒绝时醒目提示
+fn warn_if_full_refused(config: &rrclaw::config::Config) {
+    use rrclaw::security::{sandbox, AutonomyLevel};
+    match sandbox::current() {
+        Some(kind) => tracing::info!("运行在 OS 沙箱中: {}", kind),
+        None => tracing::debug!("未检测到 OS 沙箱（{} 未设置）", sandbox::SANDBOX_ENV),
+    }
+    if config.security.autonomy == AutonomyLevel::Full
+        && sandbox::refuses_full(config.security.require_sandbox)
+    {
+        eprintln!(
+            "\x1b[1;33m{}，本次以 Supervised 模式运行\x1b[0m",
+            sandbox::FULL_REFUSED_MESSAGE
+        );
+    }
+}
+
+/// 旧版 `~/.rrclaw/` 尚未迁移到 XDG 目录时询问一次（仅交互终端）
+fn offer_legacy_migration() -> Result<()> {
+    use std::io::IsTerminal;
+
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Ok(());
+    }
+    let Some(migration) = rrclaw::config::paths::LegacyMigration::pending()? else {
+        return Ok(());
+    };
+    if let Some(pid) = rrclaw::daemon::running_pid() {
+        eprintln!(
+            "检测到旧版目录 {}，daemon 正在运行 (pid {})，执行 `rrclaw stop` 后可迁移到 XDG 目录",
+            migration.legacy().display(),
+            pid
+        );
+        return Ok(());
+    }
+    let target = migration.target();
+    println!(
+        "检测到旧版目录 {}。RRClaw 现在使用 XDG 目录:",
+        migration.legacy().display()
+    );
+    println!("  配置 / 身份文件 / Skills: {}", target.home().display());
+    println!("  数据库: {}", target.data_dir().display());
+    println!("  日志: {}", target.log_dir().display());
+    let confirmed = dialoguer::Confirm::new()
+        .with_prompt("复制到新目录？（旧目录保留；选择否则继续使用旧目录且不再询问）")
+        .default(true)
+        .interact()
+        .wrap_err("读取确认失败")?;
+    if confirmed {
+        let copied = migration.migrate()?;
+        println!(
+            "已迁移 {} 项（{}）。确认无误后可删除 {}",
+            copied.len(),
+            copied.join(", "),
+            migration.legacy().display()
+        );
+    } else {
+        migration.decline()?;
+        println!("继续使用 {}", migration.legacy().display());
+    }
+    Ok(())
+}
+
+/// 获取数据目录: `$XDG_DATA_HOME/rrclaw/`，单目录布局下为 `<根目录>/data/`（见 `RrclawPaths`）
 fn data_dir() -> Result<PathBuf> {
-    let base_dirs = directories::BaseDirs::new()
-        .ok_or_else(|| color_eyre::eyre::eyre!("无法获取 home 目录"))?;
-    Ok(base_dirs.home_dir().join(".rrclaw").join("data"))
+    Ok(rrclaw::config::RrclawPaths::resolve()?.data_dir())
+}
+
+/// 打开 Memory；数据目录不可写（或 `memory.fallback_to_noop` 开启时打开失败）降级并醒目提示一次
+fn open_memory_or_degrade(
+    config: &rrclaw::config::Config,
+    data_dir: &std::path::Path,
+) -> Result<rrclaw::memory::SqliteMemory> {
+    let (memory, degraded) =
+        rrclaw::memory::open_with_fallback(data_dir, config.memory.fallback_to_noop)?;
+    if degraded {
+        eprintln!(
+            "\x1b[1;33m{}\x1b[0m",
+            rrclaw::memory::degraded_memory_warning(data_dir)
+        );
+    }
+    Ok(memory)
 }
 
-/// 获取日志目录: ~/.rrclaw/logs/
+/// 获取日志目录: `$XDG_STATE_HOME/rrclaw/logs/`，单目录布局下为 `<根目录>/logs/`
 fn log_dir() -> Result<PathBuf> {
-    let base_dirs = directories::BaseDirs::new()
-        .ok_or_else(|| color_eyre::eyre::eyre!("无法获取 home 目录"))?;
-    Ok(base_dirs.home_dir().join(".rrclaw").join("logs"))
+    Ok(rrclaw::config::RrclawPaths::resolve()?.log_dir())
 }
 
-/// 初始化 tracing: stderr 只输出 warn+，日志文件输出 debug+
-fn init_tracing() -> Result<()> {
+/// stderr 日志过滤指令：默认 warn+，`-v` 逐级放开 rrclaw 自身日志（依赖库保持 warn）
+fn stderr_filter_directive(verbose: u8) -> &'static str {
+    match verbose {
+        0 => "warn",
+        1 => "warn,rrclaw=info",
+        2 => "warn,rrclaw=debug",
+        _ => "warn,rrclaw=trace",
+    }
+}
+
+/// 初始化 tracing: stderr 默认只输出 warn+（`-v` 提高），日志文件输出 debug+
+///
+/// stderr / 文件两个过滤器可热替换，daemon 收到 SIGUSR1 时经 `daemon::log_level` 循环切换级别
+fn init_tracing(verbose: u8) -> Result<()> {
     let log_dir = log_dir()?;
     std::fs::create_dir_all(&log_dir)
         .wrap_err_with(|| format!("创建日志目录失败: {}", log_dir.display()))?;
 
     // 文件日志: 按天滚动，debug 级别
     let file_appender = tracing_appender::rolling::daily(&log_dir, "rrclaw.log");
+    let (file_filter, file_handle) = tracing_subscriber::reload::Layer::new(
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("rrclaw=debug")),
+    );
     let file_layer = tracing_subscriber::fmt::layer()
         .with_writer(file_appender)
         .with_ansi(false)
-        .with_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("rrclaw=debug")),
-        );
+        .with_filter(file_filter);
 
-    // stderr: 只输出 warn+（不干扰 REPL 交互）
+    // stderr: 默认只输出 warn+（不干扰 REPL 交互），-v/-vv 调试时临时放开
+    let (stderr_filter, stderr_handle) = tracing_subscriber::reload::Layer::new(
+        tracing_subscriber::EnvFilter::new(stderr_filter_directive(verbose)),
+    );
     let stderr_layer = tracing_subscriber::fmt::layer()
         .with_writer(std::io::stderr)
-        .with_filter(tracing_subscriber::EnvFilter::new("warn"));
+        .with_filter(stderr_filter);
+
+    // SIGUSR1 切换级别时两个过滤器同步替换
+    use rrclaw::daemon::log_level::{self, LogLevel, LogLevelSwitch};
+    log_level::install(LogLevelSwitch::new(
+        LogLevel::from_verbosity(verbose),
+        move |directive| {
+            stderr_handle.reload(tracing_subscriber::EnvFilter::new(directive))?;
+            file_handle.reload(tracing_subscriber::EnvFilter::new(directive))?;
+            Ok(())
+        },
+    ));
+
+    // 内存环形缓冲：最近 50 行日志供故障报告使用
+    let ring_layer = rrclaw::report::RingBufferLayer::new(rrclaw::report::LogRingBuffer::global())
+        .with_filter(tracing_subscriber::EnvFilter::new("rrclaw=debug"));
 
     tracing_subscriber::registry()
         .with(file_layer)
         .with(stderr_layer)
+        .with(ring_layer)
         .init();
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_maps_to_stderr_filter() {
+        assert_eq!(stderr_filter_directive(0), "warn");
+        assert_eq!(stderr_filter_directive(1), "warn,rrclaw=info");
+        assert_eq!(stderr_filter_directive(2), "warn,rrclaw=debug");
+        assert_eq!(stderr_filter_directive(3), "warn,rrclaw=trace");
+        assert_eq!(stderr_filter_directive(9), "warn,rrclaw=trace");
+    }
+
+    #[test]
+    fn verbose_flag_counts_and_is_global() {
+        let cli = Cli::try_parse_from(["rrclaw", "-vv", "agent"]).unwrap();
+        assert_eq!(cli.verbose, 2);
+        let cli = Cli::try_parse_from(["rrclaw", "agent", "-v"]).unwrap();
+        assert_eq!(cli.verbose, 1);
+        let cli = Cli::try_parse_from(["rrclaw", "agent"]).unwrap();
+        assert_eq!(cli.verbose, 0);
+    }
+
+    #[test]
+    fn record_and_replay_are_exclusive() {
+        let cli = Cli::try_parse_from(["rrclaw", "agent", "--replay", "fixtures"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Agent { replay: Some(ref dir), record: None, .. } if dir.as_os_str() == "fixtures"
+        ));
+        assert!(
+            Cli::try_parse_from(["rrclaw", "agent", "--record", "a", "--replay", "b"]).is_err()
+        );
+    }
+
+    #[test]
+    fn save_flag_parses_with_provider_and_model() {
+        let cli = Cli::try_parse_from([
+            "rrclaw",
+            "agent",
+            "--provider",
+            "glm",
+            "--model",
+            "glm-4-flash",
+            "--save",
+        ])
+        .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Agent { save: true, provider: Some(ref p), model: Some(ref m), .. }
+                if p == "glm" && m == "glm-4-flash"
+        ));
+    }
+
+    #[test]
+    fn no_memory_is_an_alias_of_ephemeral() {
+        for flag in ["--ephemeral", "--no-memory"] {
+            let cli = Cli::try_parse_from(["rrclaw", "agent", flag]).unwrap();
+            assert!(matches!(
+                cli.command,
+                Commands::Agent {
+                    ephemeral: true,
+                    ..
+                }
+            ));
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use std::sync::Arc;
+
+use crate::security::SecurityPolicy;
+use crate::tools::traits::{Tool, ToolResult};
+
+use super::tool::McpTool;
+
+/// 浏览工具名称
+pub const LIST_TOOLS_NAME: &str = "mcp_list_tools";
+
+/// 每页默认条目数 / 上限
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 50;
+
+/// 目录中单条描述的最大字符数
+const CATALOG_DESCRIPTION_CHARS: usize = 200;
+
+/// MCP 工具目录中的一项
+pub struct McpCatalogEntry {
+    pub server: String,
+    /// L2 完整版本（浏览时展示完整描述，`call` 时直接执行）
+    pub tool: McpTool,
+    /// 是否已在 system prompt 的 [MCP Tools] 段列出
+    pub listed: bool,
+}
+
+/// 全部 MCP 工具目录（含未在 prompt 中列出的工具）
+#[derive(Default)]
+pub struct McpCatalog {
+    entries: Vec<McpCatalogEntry>,
+}
+
+/// 一页搜索结果
+pub struct CatalogPage<'a> {
+    pub items: Vec<&'a McpCatalogEntry>,
+    /// 匹配总数
+    pub total: usize,
+    /// 当前页（从 1 开始，已夹到有效范围）
+    pub page: usize,
+    pub pages: usize,
+}
+
+impl McpCatalog {
+    pub fn new(entries: Vec<McpCatalogEntry>) -> Self {
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 各 server 未在 prompt 中列出的工具数（按 server 首次出现顺序，跳过 0）
+    pub fn hidden_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for entry in self.entries.iter().filter(|e| !e.listed) {
+            match counts.iter_mut().find(|(s, _)| s == &entry.server) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((entry.server.clone(), 1)),
+            }
+        }
+        counts
+    }
+
+    /// 按工具名查找（支持带前缀的 `mcp_{server}_{tool}`）
+    pub fn get(&self, name: &str) -> Option<&McpCatalogEntry> {
+        self.entries.iter().find(|e| e.tool.name() == name)
+    }
+
+    /// 子串过滤（名称或描述，忽略大小写）+ 分页
+    pub fn search(
+        &self,
+        query: Option<&str>,
+        server: Option<&str>,
+        page: usize,
+        page_size: usize,
+    ) -> CatalogPage<'_> {
+        let query = query.map(|q| q.to_lowercase()).filter(|q| !q.is_empty());
+        let matched: Vec<&McpCatalogEntry> = self
+            .entries
+            .iter()
+            .filter(|e| server.is_none_or(|s| e.server == s))
+            .filter(|e| {
+                query.as_deref().is_none_or(|q| {
+                    e.tool.name().to_lowercase().contains(q)
+                        || e.tool.description().to_lowercase().contains(q)
+                })
+            })
+            .collect();
+
+        let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+        let total = matched.len();
+        let pages = total.div_ceil(page_size).max(1);
+        let page = page.clamp(1, pages);
+        let items = matched
+            .into_iter()
+            .skip((page - 1) * page_size)
+            .take(page_size)
+            .collect();
+
+        CatalogPage {
+            items,
+            total,
+            page,
+            pages,
+        }
+    }
+}
+
+/// 内置工具：按需搜索 / 浏览完整 MCP 工具目录
+///
+/// 工具数量超过 `max_tools` 或 `prompt_budget_chars` 时，多出的 MCP 工具不再注册进 Agent，
+/// 模型通过本工具查找，并用 `call` 参数调用未列出的工具。
+pub struct McpListToolsTool {
+    catalog: Arc<McpCatalog>,
+    description: String,
+}
+
+impl McpListToolsTool {
+    pub fn new(catalog: Arc<McpCatalog>) -> Self {
+        let mut description =
+            "Search and browse the full MCP tool catalog (substring filter + pagination). \
+             Set `call` to invoke a catalog tool that is not listed above."
+                .to_string();
+        for (server, count) in catalog.hidden_counts() {
+            description.push_str(&format!(
+                "\n  server {}: {} more tools, call {} to browse",
+                server, count, LIST_TOOLS_NAME
+            ));
+        }
+        Self {
+            catalog,
+            description,
+        }
+    }
+
+    fn browse(&self, args: &serde_json::Value) -> String {
+        let query = args.get("query").and_then(|v| v.as_str());
+        let server = args.get("server").and_then(|v| v.as_str());
+        let page = args.get("page").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+        let page_size = args
+            .get("page_size")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_PAGE_SIZE as u64) as usize;
+
+        let result = self.catalog.search(query, server, page, page_size);
+        if result.total == 0 {
+            return "No MCP tools match.".to_string();
+        }
+
+        let mut lines = vec![format!(
+            "MCP tool catalog: {} match(es), page {}/{}",
+            result.total, result.page, result.pages
+        )];
+        for entry in &result.items {
+            let desc: String = entry
+                .tool
+                .description()
+                .chars()
+                .take(CATALOG_DESCRIPTION_CHARS)
+                .collect();
+            let status = if entry.listed { "listed" } else { "via call" };
+            lines.push(format!(
+                "- {} [{}]: {}",
+                entry.tool.name(),
+                status,
+                desc.replace('\n', " ")
+            ));
+            let params = param_summary(&entry.tool.parameters_schema());
+            if !params.is_empty() {
+                lines.push(format!("  params: {}", params));
+            }
+        }
+        if result.page < result.pages {
+            lines.push(format!("(more: page={})", result.page + 1));
+        }
+        lines.join("\n")
+    }
+}
+
+/// 参数摘要：`name*`（* 表示必填）
+fn param_summary(schema: &serde_json::Value) -> String {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+    schema["properties"]
+        .as_object()
+        .map(|props| {
+            props
+                .keys()
+                .map(|k| {
+                    if required.contains(&k.as_str()) {
+                        format!("{}*", k)
+                    } else {
+                        k.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl Tool for McpListToolsTool {
+    fn name(&self) -> &str {
+        LIST_TOOLS_NAME
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "Case-insensitive substring matched against tool name and description"
+                },
+                "server": {
+                    "type": "string",
+                    "description": "Only list tools from this MCP server"
+                },
+                "page": {
+                    "type": "integer",
+                    "description": "Page number, starting at 1"
+                },
+                "page_size": {
+                    "type": "integer",
+                    "description": "Results per page (default 20, max 50)"
+                },
+                "call": {
+                    "type": "string",
+                    "description": "Full tool name (mcp_{server}_{tool}) to invoke instead of browsing"
+                },
+                "arguments": {
+                    "type": "object",
+                    "description": "Arguments for the tool given in `call`"
+                }
+            }
+        })
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        if let Some(name) = args.get("call").and_then(|v| v.as_str()) {
+            let Some(entry) = self.catalog.get(name) else {
+                return Ok(ToolResult {
+                    success: false,
+                    output: String::new(),
+                    error: Some(format!(
+                        "Unknown MCP tool '{}'. Browse with {} first.",
+                        name, LIST_TOOLS_NAME
+                    )),
+                    ..Default::default()
+                });
+            };
+            let call_args = args
+                .get("arguments")
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!({}));
+            return entry.tool.execute(call_args, policy).await;
+        }
+
+        Ok(ToolResult {
+            success: true,
+            output: self.browse(&args),
+            error: None,
+            ..Default::default()
+        })
+    }
+}
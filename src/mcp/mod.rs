@@ -1,3 +1,4 @@
+pub mod catalog;
 pub mod tool;
 
 use color_eyre::eyre::{Context, Result};
@@ -11,7 +12,8 @@ use rmcp::ServiceExt;
 
 use crate::config::{McpServerConfig, McpTransport};
 use crate::tools::traits::Tool;
-use tool::McpTool;
+use catalog::{McpCatalog, McpCatalogEntry, McpListToolsTool};
+use tool::{McpTool, DEFAULT_DESCRIPTION_CHARS};
 
 /// 已连接的单个 MCP Server
 struct McpServer {
@@ -19,11 +21,42 @@ struct McpServer {
     service: RunningService<RoleClient, ()>,
     peer: Arc<Peer<RoleClient>>,
     allowed_tools: Vec<String>,
+    /// prompt 中最多列出的工具数（None = 不限制）
+    max_tools: Option<usize>,
+    /// L1 简介最大字符数
+    description_max_chars: usize,
+}
+
+impl McpServer {
+    fn is_allowed(&self, tool_name: &str) -> bool {
+        self.allowed_tools.is_empty() || self.allowed_tools.iter().any(|a| a == tool_name)
+    }
 }
 
 /// 管理所有 MCP Server 连接
 pub struct McpManager {
     servers: Vec<McpServer>,
+    /// [MCP Tools] 段的字符预算（None = 不限制）
+    prompt_budget_chars: Option<usize>,
+}
+
+/// `tools_l1()` 的结果：prompt 中列出的 L1 工具 + 完整目录
+pub struct McpToolset {
+    /// 裁剪后注册进 Agent 的工具
+    pub tools: Vec<Box<dyn Tool>>,
+    /// 全部工具目录（供 `mcp_list_tools` 浏览与调用）
+    pub catalog: Arc<McpCatalog>,
+}
+
+impl McpToolset {
+    /// 合并为 Agent 工具列表：有未列出的工具时追加 `mcp_list_tools`
+    pub fn into_tools(self) -> Vec<Box<dyn Tool>> {
+        let mut tools = self.tools;
+        if !self.catalog.hidden_counts().is_empty() {
+            tools.push(Box::new(McpListToolsTool::new(self.catalog)));
+        }
+        tools
+    }
 }
 
 impl McpManager {
@@ -41,6 +74,10 @@ impl McpManager {
                         service,
                         peer,
                         allowed_tools: config.allowed_tools.clone(),
+                        max_tools: config.max_tools,
+                        description_max_chars: config
+                            .tool_description_max_chars
+                            .unwrap_or(DEFAULT_DESCRIPTION_CHARS),
                     });
                 }
                 Err(e) => {
@@ -49,7 +86,19 @@ impl McpManager {
             }
         }
 
-        Self { servers }
+        // HashMap 遍历顺序不固定，按名称排序保证 prompt 预算分配稳定
+        servers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            servers,
+            prompt_budget_chars: None,
+        }
+    }
+
+    /// 设置 [MCP Tools] 段的字符预算（来自 `[mcp] prompt_budget_chars`）
+    pub fn with_prompt_budget(mut self, budget: Option<usize>) -> Self {
+        self.prompt_budget_chars = budget;
+        self
     }
 
     /// 获取所有 MCP tools（L2 完整模式），转换为 RRClaw Tool trait 对象
@@ -59,15 +108,76 @@ impl McpManager {
         self.tools_inner(false).await
     }
 
-    /// 获取所有 MCP tools（L1 懒加载模式），转换为 RRClaw Tool trait 对象
+    /// 获取 MCP tools（L1 懒加载模式），按 `max_tools` / `prompt_budget_chars` 裁剪
     ///
     /// 返回只含一句话简介 + 极简 schema 的工具。LLM 首次调用某工具后，
     /// Agent 自动调用 `load_full_schema()` 将其升级为 L2 完整 schema。
-    pub async fn tools_l1(&self) -> Vec<Box<dyn Tool>> {
-        self.tools_inner(true).await
+    ///
+    /// 超出单 server `max_tools` 的工具不列出；累计 prompt 行长度超出预算后，
+    /// 剩余工具（含后续所有 server）全部折叠，只出现在目录里，由 `mcp_list_tools` 浏览。
+    pub async fn tools_l1(&self) -> McpToolset {
+        let mut listed: Vec<Box<dyn Tool>> = Vec::new();
+        let mut entries: Vec<McpCatalogEntry> = Vec::new();
+        let mut used_chars = 0usize;
+        let mut budget_exceeded = false;
+
+        for server in &self.servers {
+            let tool_defs = match server.peer.list_all_tools().await {
+                Ok(tools) => tools,
+                Err(e) => {
+                    warn!("获取 MCP Server '{}' 工具列表失败: {:#}", server.name, e);
+                    continue;
+                }
+            };
+
+            let mut shown = 0usize;
+            let mut hidden = 0usize;
+            for tool_def in tool_defs {
+                if !server.is_allowed(tool_def.name.as_ref()) {
+                    continue;
+                }
+                let l1 = McpTool::new_l1(&server.name, tool_def.clone(), server.peer.clone())
+                    .with_description_limit(server.description_max_chars);
+
+                let under_cap = server.max_tools.is_none_or(|max| shown < max);
+                if under_cap && !budget_exceeded {
+                    let line = prompt_line_chars(&l1);
+                    if self
+                        .prompt_budget_chars
+                        .is_some_and(|budget| used_chars + line > budget)
+                    {
+                        budget_exceeded = true;
+                    } else {
+                        used_chars += line;
+                    }
+                }
+                let is_listed = under_cap && !budget_exceeded;
+
+                if is_listed {
+                    listed.push(Box::new(l1));
+                    shown += 1;
+                } else {
+                    hidden += 1;
+                }
+                entries.push(McpCatalogEntry {
+                    server: server.name.clone(),
+                    tool: McpTool::new(&server.name, tool_def, server.peer.clone()),
+                    listed: is_listed,
+                });
+            }
+            info!(
+                "MCP Server '{}' 加载了 {} 个工具（L1 懒加载），{} 个仅在目录中",
+                server.name, shown, hidden
+            );
+        }
+
+        McpToolset {
+            tools: listed,
+            catalog: Arc::new(McpCatalog::new(entries)),
+        }
     }
 
-    /// 内部实现：根据 lazy 标志创建 L1 或 L2 工具
+    /// 内部实现：根据 lazy 标志创建 L1 或 L2 工具（不裁剪）
     async fn tools_inner(&self, lazy: bool) -> Vec<Box<dyn Tool>> {
         let mut result: Vec<Box<dyn Tool>> = Vec::new();
 
@@ -76,11 +186,8 @@ impl McpManager {
                 Ok(tools) => {
                     let mut count = 0;
                     for tool_def in tools {
-                        let tool_name = tool_def.name.as_ref();
                         // 过滤：如果 allowed_tools 非空，只保留白名单内的工具
-                        if !server.allowed_tools.is_empty()
-                            && !server.allowed_tools.iter().any(|a| a == tool_name)
-                        {
+                        if !server.is_allowed(tool_def.name.as_ref()) {
                             continue;
                         }
                         let mcp_tool = if lazy {
@@ -119,6 +226,26 @@ impl McpManager {
     }
 }
 
+/// 连接单个 Server 并列出其工具名后断开（`rrclaw setup mcp` 测试连接用）
+pub async fn probe_server(name: &str, config: &McpServerConfig) -> Result<Vec<String>> {
+    let service = connect_server(name, config).await?;
+    let tools = service
+        .peer()
+        .list_all_tools()
+        .await
+        .map_err(|e| color_eyre::eyre::eyre!("{}", e))
+        .wrap_err_with(|| format!("获取 MCP Server '{}' 工具列表失败", name));
+    if let Err(e) = service.cancel().await {
+        warn!("MCP Server '{}' 关闭失败: {:#}", name, e);
+    }
+    Ok(tools?.into_iter().map(|t| t.name.to_string()).collect())
+}
+
+/// 工具在 [MCP Tools] 段中的一行长度（`- name: description\n`）
+fn prompt_line_chars(tool: &McpTool) -> usize {
+    tool.name().chars().count() + tool.description().chars().count() + 4
+}
+
 /// 连接单个 MCP Server
 async fn connect_server(
     name: &str,
@@ -180,3 +307,209 @@ async fn connect_server(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::SecurityPolicy;
+    use rmcp::model::{
+        CallToolRequestParams, CallToolResult, Content, ListToolsResult, PaginatedRequestParams,
+        ServerCapabilities, ServerInfo, Tool as McpToolDef,
+    };
+    use rmcp::service::{RequestContext, RoleServer};
+    use rmcp::{ErrorData, ServerHandler};
+
+    /// 暴露大量工具的假 MCP Server
+    #[derive(Clone)]
+    struct FakeServer {
+        tool_count: usize,
+    }
+
+    impl ServerHandler for FakeServer {
+        fn get_info(&self) -> ServerInfo {
+            ServerInfo {
+                capabilities: ServerCapabilities::builder().enable_tools().build(),
+                ..Default::default()
+            }
+        }
+
+        fn list_tools(
+            &self,
+            _request: Option<PaginatedRequestParams>,
+            _context: RequestContext<RoleServer>,
+        ) -> impl std::future::Future<Output = Result<ListToolsResult, ErrorData>> + Send + '_
+        {
+            let schema: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_value(serde_json::json!({
+                    "type": "object",
+                    "properties": {"path": {"type": "string"}},
+                    "required": ["path"]
+                }))
+                .unwrap();
+            let schema = Arc::new(schema);
+            let tools = (0..self.tool_count)
+                .map(|i| {
+                    McpToolDef::new(
+                        format!("tool_{:03}", i),
+                        format!("Does thing number {}. Extra details here.", i),
+                        schema.clone(),
+                    )
+                })
+                .collect();
+            std::future::ready(Ok(ListToolsResult::with_all_items(tools)))
+        }
+
+        fn call_tool(
+            &self,
+            request: CallToolRequestParams,
+            _context: RequestContext<RoleServer>,
+        ) -> impl std::future::Future<Output = Result<CallToolResult, ErrorData>> + Send + '_
+        {
+            std::future::ready(Ok(CallToolResult::success(vec![Content::text(format!(
+                "called {}",
+                request.name
+            ))])))
+        }
+    }
+
+    async fn fake_server(name: &str, tool_count: usize, max_tools: Option<usize>) -> McpServer {
+        let (server_transport, client_transport) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            if let Ok(running) = (FakeServer { tool_count }).serve(server_transport).await {
+                let _ = running.waiting().await;
+            }
+        });
+        let service = ().serve(client_transport).await.unwrap();
+        let peer = Arc::new(service.peer().clone());
+        McpServer {
+            name: name.to_string(),
+            service,
+            peer,
+            allowed_tools: vec![],
+            max_tools,
+            description_max_chars: DEFAULT_DESCRIPTION_CHARS,
+        }
+    }
+
+    #[tokio::test]
+    async fn max_tools_caps_listed_tools_per_server() {
+        let mgr = McpManager {
+            servers: vec![fake_server("big", 120, Some(10)).await],
+            prompt_budget_chars: None,
+        };
+        let toolset = mgr.tools_l1().await;
+        assert_eq!(toolset.tools.len(), 10);
+        assert_eq!(toolset.catalog.len(), 120);
+        assert_eq!(
+            toolset.catalog.hidden_counts(),
+            vec![("big".to_string(), 110)]
+        );
+
+        let tools = toolset.into_tools();
+        assert_eq!(tools.len(), 11);
+        let browse = tools.last().unwrap();
+        assert_eq!(browse.name(), catalog::LIST_TOOLS_NAME);
+        assert!(browse
+            .description()
+            .contains("server big: 110 more tools, call mcp_list_tools to browse"));
+    }
+
+    #[tokio::test]
+    async fn prompt_budget_collapses_remaining_servers() {
+        let mgr = McpManager {
+            servers: vec![
+                fake_server("alpha", 20, None).await,
+                fake_server("beta", 120, None).await,
+            ],
+            // 每行约 40 字符，alpha 的 20 个工具放不下，预算在 alpha 中途耗尽
+            prompt_budget_chars: Some(400),
+        };
+        let toolset = mgr.tools_l1().await;
+
+        let used: usize = toolset
+            .tools
+            .iter()
+            .map(|t| t.name().chars().count() + t.description().chars().count() + 4)
+            .sum();
+        assert!(used <= 400, "列出的工具超出预算: {}", used);
+        assert!(!toolset.tools.is_empty());
+        // 预算耗尽后 alpha 的剩余工具与 beta 全部折叠
+        assert!(toolset
+            .tools
+            .iter()
+            .all(|t| t.name().starts_with("mcp_alpha_")));
+        let hidden = toolset.catalog.hidden_counts();
+        assert_eq!(
+            hidden,
+            vec![
+                ("alpha".to_string(), 20 - toolset.tools.len()),
+                ("beta".to_string(), 120),
+            ]
+        );
+        assert!(toolset.tools.len() < 20);
+        assert_eq!(toolset.catalog.len(), 140);
+    }
+
+    #[tokio::test]
+    async fn no_limits_lists_everything_without_browse_tool() {
+        let mgr = McpManager {
+            servers: vec![fake_server("small", 5, None).await],
+            prompt_budget_chars: None,
+        };
+        let tools = mgr.tools_l1().await.into_tools();
+        assert_eq!(tools.len(), 5);
+        assert!(tools.iter().all(|t| t.name() != catalog::LIST_TOOLS_NAME));
+    }
+
+    #[tokio::test]
+    async fn list_tools_browses_pages_and_calls_hidden_tool() {
+        let mgr = McpManager {
+            servers: vec![fake_server("big", 120, Some(5)).await],
+            prompt_budget_chars: None,
+        };
+        let catalog = mgr.tools_l1().await.catalog;
+
+        let page = catalog.search(None, None, 2, 50);
+        assert_eq!((page.total, page.page, page.pages), (120, 2, 3));
+        assert_eq!(page.items.len(), 50);
+        assert_eq!(page.items[0].tool.name(), "mcp_big_tool_050");
+        // 页码越界夹到最后一页
+        let last = catalog.search(None, None, 99, 50);
+        assert_eq!((last.page, last.items.len()), (3, 20));
+        // 子串过滤（忽略大小写）
+        let filtered = catalog.search(Some("TOOL_11"), Some("big"), 1, 20);
+        assert_eq!(filtered.total, 10);
+        assert!(catalog.search(None, Some("other"), 1, 20).items.is_empty());
+
+        let browse = McpListToolsTool::new(catalog);
+        let policy = SecurityPolicy::default();
+        let out = browse
+            .execute(
+                serde_json::json!({"query": "tool_00", "page_size": 3}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(out.success);
+        assert!(out.output.contains("page 1/4"));
+        assert!(out.output.contains("mcp_big_tool_000 [listed]"));
+        assert!(out.output.contains("params: path*"));
+        assert!(out.output.contains("(more: page=2)"));
+
+        let called = browse
+            .execute(
+                serde_json::json!({"call": "mcp_big_tool_099", "arguments": {"path": "x"}}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(called.success);
+        assert_eq!(called.output, "called tool_099");
+
+        let unknown = browse
+            .execute(serde_json::json!({"call": "mcp_big_nope"}), &policy)
+            .await
+            .unwrap();
+        assert!(!unknown.success);
+    }
+}
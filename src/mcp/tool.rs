@@ -6,7 +6,7 @@ use rmcp::model::{CallToolRequestParams, RawContent, ResourceContents, Tool as M
 use rmcp::service::{Peer, RoleClient};
 
 use crate::security::SecurityPolicy;
-use crate::tools::traits::{Tool, ToolResult};
+use crate::tools::traits::{Tool, ToolResult, ToolRisk};
 
 /// MCP Tool 的 RRClaw 适配器：将一个 MCP server 工具桥接为 RRClaw Tool trait
 ///
@@ -44,18 +44,10 @@ impl McpTool {
         let original_name = def.name.to_string();
         let prefixed_name = format!("mcp_{}_{}", server_name, original_name);
 
-        // 生成一句话简介：取完整 description 的首句（按 '.' 或 '\n' 断句），最多 80 字符
-        let full_desc = def.description.as_deref().unwrap_or("MCP tool");
-        let first_sentence = full_desc
-            .split(['.', '\n'])
-            .next()
-            .unwrap_or(full_desc)
-            .trim();
-        let short_description = if first_sentence.len() > 80 {
-            format!("{}...", &first_sentence[..80])
-        } else {
-            first_sentence.to_string()
-        };
+        let short_description = short_description(
+            def.description.as_deref().unwrap_or("MCP tool"),
+            DEFAULT_DESCRIPTION_CHARS,
+        );
 
         Self {
             prefixed_name,
@@ -66,6 +58,33 @@ impl McpTool {
             loaded: false,
         }
     }
+
+    /// 按 server 配置的 `tool_description_max_chars` 重新截断 L1 简介
+    pub fn with_description_limit(mut self, max_chars: usize) -> Self {
+        self.short_description = short_description(
+            self.def.description.as_deref().unwrap_or("MCP tool"),
+            max_chars,
+        );
+        self
+    }
+}
+
+/// L1 简介默认最大字符数
+pub const DEFAULT_DESCRIPTION_CHARS: usize = 80;
+
+/// 生成一句话简介：取完整 description 的首句（按 '.' 或 '\n' 断句），最多 `max_chars` 字符
+pub fn short_description(full_desc: &str, max_chars: usize) -> String {
+    let first_sentence = full_desc
+        .split(['.', '\n'])
+        .next()
+        .unwrap_or(full_desc)
+        .trim();
+    if first_sentence.chars().count() > max_chars {
+        let truncated: String = first_sentence.chars().take(max_chars).collect();
+        format!("{}...", truncated)
+    } else {
+        first_sentence.to_string()
+    }
 }
 
 #[async_trait]
@@ -98,6 +117,14 @@ impl Tool for McpTool {
         }
     }
 
+    /// 只有 server 显式标注 `readOnlyHint: true` 的工具才算只读
+    fn risk(&self) -> ToolRisk {
+        match self.def.annotations.as_ref().and_then(|a| a.read_only_hint) {
+            Some(true) => ToolRisk::Read,
+            _ => ToolRisk::Write,
+        }
+    }
+
     /// 懒加载升级：将 schema 从 L1 升级为 L2（完整 description + parameters）
     fn load_full_schema(&mut self) {
         self.loaded = true;
@@ -171,6 +198,8 @@ impl Tool for McpTool {
 
 #[cfg(test)]
 mod tests {
+    use super::short_description;
+
     #[test]
     fn mcp_tool_name_has_prefix() {
         let prefixed = format!("mcp_{}_{}", "filesystem", "read_file");
@@ -209,4 +238,15 @@ mod tests {
         let loaded = false;
         assert!(!loaded);
     }
+
+    #[test]
+    fn short_description_respects_limit_and_char_boundaries() {
+        assert_eq!(
+            short_description("Read a file. More text.", 80),
+            "Read a file"
+        );
+        assert_eq!(short_description("abcdefghij", 4), "abcd...");
+        // 多字节字符不会在字节中间截断
+        assert_eq!(short_description("读取文件内容并返回", 4), "读取文件...");
+    }
 }
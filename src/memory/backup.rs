@@ -0,0 +1,601 @@
+//! 数据目录备份 / 恢复 / 维护（`rrclaw memory ...`）
+//!
+//! SQLite 数据库通过 online backup API 拷贝，daemon 正在写入时也能得到一致快照；
+//! 直接复制打开中的 .db 文件（尤其 WAL 模式下）可能得到损坏的副本。
+//! tantivy 索引不进备份，恢复后从 memories 表重建。
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{bail, eyre, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{Connection, DatabaseName};
+
+use super::sqlite::configure_connection;
+use super::SqliteMemory;
+
+/// data 目录下纳入备份的 SQLite 数据库
+pub const DATABASES: &[&str] = &["memory.db", "routines.db", "feedback.db", "skill_usage.db"];
+
+/// 配置根目录（`RrclawPaths::home()`）下纳入备份的全局身份文件
+const IDENTITY_FILES: &[&str] = &["USER.md", "SOUL.md"];
+
+/// 全局 Skills 目录（相对配置根目录）
+const SKILLS_DIR: &str = "skills";
+
+/// 归档内的数据库目录（与数据目录的实际位置无关，XDG 与单目录布局的备份可互相恢复）
+const DATA_DIR: &str = "data";
+
+/// 备份结果
+#[derive(Debug)]
+pub struct BackupReport {
+    /// 归档内条目（相对路径）
+    pub entries: Vec<String>,
+    /// 归档大小（字节）
+    pub archive_bytes: u64,
+}
+
+/// 恢复结果
+#[derive(Debug)]
+pub struct RestoreReport {
+    /// 已恢复的条目（归档内的相对路径）
+    pub restored: Vec<String>,
+    /// 重建索引的记忆条数
+    pub reindexed: usize,
+}
+
+/// 单个数据库的 VACUUM 结果
+#[derive(Debug)]
+pub struct VacuumReport {
+    pub file: String,
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+}
+
+impl VacuumReport {
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.before_bytes.saturating_sub(self.after_bytes)
+    }
+}
+
+/// 单个数据库的统计
+#[derive(Debug)]
+pub struct DbStats {
+    pub file: String,
+    /// 数据库文件 + WAL 大小
+    pub size_bytes: u64,
+    pub tables: Vec<TableStats>,
+}
+
+#[derive(Debug)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: i64,
+    /// 表占用字节（需 SQLite 启用 dbstat，否则为 None）
+    pub size_bytes: Option<u64>,
+}
+
+/// 备份 `data_dir` 下的数据库与 `home`（配置根目录）下的身份文件、全局 Skills 到 `.tar.gz`
+pub fn backup(home: &Path, data_dir: &Path, archive: &Path) -> Result<BackupReport> {
+    let staging = staging_dir(home, "backup")?;
+    let result = write_archive(home, data_dir, &staging, archive);
+    let _ = std::fs::remove_dir_all(&staging);
+    let entries = result?;
+
+    let archive_bytes = std::fs::metadata(archive).map(|m| m.len()).unwrap_or(0);
+    Ok(BackupReport {
+        entries,
+        archive_bytes,
+    })
+}
+
+fn write_archive(
+    home: &Path,
+    data_dir: &Path,
+    staging: &Path,
+    archive: &Path,
+) -> Result<Vec<String>> {
+    if let Some(parent) = archive.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .wrap_err_with(|| format!("创建备份目录失败: {}", parent.display()))?;
+    }
+    let file = File::create(archive)
+        .wrap_err_with(|| format!("创建备份文件失败: {}", archive.display()))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut entries = Vec::new();
+
+    for name in DATABASES {
+        let src = data_dir.join(name);
+        if !src.exists() {
+            continue;
+        }
+        // online backup：拷贝的是某一时刻的一致快照，期间其他连接仍可写入
+        let snapshot = staging.join(name);
+        let conn = Connection::open(&src)
+            .wrap_err_with(|| format!("打开数据库失败: {}", src.display()))?;
+        conn.busy_timeout(std::time::Duration::from_millis(5000))
+            .wrap_err("设置 SQLite busy_timeout 失败")?;
+        conn.backup(DatabaseName::Main, &snapshot, None)
+            .wrap_err_with(|| format!("备份数据库失败: {}", name))?;
+
+        let entry = format!("{}/{}", DATA_DIR, name);
+        tar.append_path_with_name(&snapshot, &entry)
+            .wrap_err_with(|| format!("写入归档失败: {}", entry))?;
+        entries.push(entry);
+    }
+
+    for name in IDENTITY_FILES {
+        let src = home.join(name);
+        if src.is_file() {
+            tar.append_path_with_name(&src, name)
+                .wrap_err_with(|| format!("写入归档失败: {}", name))?;
+            entries.push(name.to_string());
+        }
+    }
+
+    let skills = home.join(SKILLS_DIR);
+    if skills.is_dir() {
+        tar.append_dir_all(SKILLS_DIR, &skills)
+            .wrap_err("写入 Skills 目录失败")?;
+        entries.push(format!("{}/", SKILLS_DIR));
+    }
+
+    tar.into_inner()
+        .and_then(|gz| gz.finish())
+        .wrap_err("写入备份文件失败")?;
+    Ok(entries)
+}
+
+/// 从 `.tar.gz` 恢复到 `data_dir` / `home`，覆盖现有数据库 / 身份文件 / 全局 Skills
+///
+/// 先完整解包到临时目录并校验数据库完整性，校验通过后才替换原文件；
+/// 要替换的数据库仍被其他进程（daemon / REPL / Telegram）打开时拒绝恢复，不改动任何文件。
+pub async fn restore(home: &Path, data_dir: &Path, archive: &Path) -> Result<RestoreReport> {
+    let staging = staging_dir(home, "restore")?;
+    let result = unpack_and_replace(home, data_dir, &staging, archive);
+    let _ = std::fs::remove_dir_all(&staging);
+    let restored = result?;
+
+    // 索引不在备份中：从 memories 表重建
+    let memory = SqliteMemory::open(data_dir)?;
+    let reindexed = memory.rebuild_index().await?;
+
+    Ok(RestoreReport {
+        restored,
+        reindexed,
+    })
+}
+
+fn unpack_and_replace(
+    home: &Path,
+    data_dir: &Path,
+    staging: &Path,
+    archive: &Path,
+) -> Result<Vec<String>> {
+    let file =
+        File::open(archive).wrap_err_with(|| format!("打开备份文件失败: {}", archive.display()))?;
+    tar::Archive::new(GzDecoder::new(file))
+        .unpack(staging)
+        .wrap_err("解包备份失败")?;
+
+    let staged_data = staging.join(DATA_DIR);
+    if !staged_data.join("memory.db").is_file() {
+        bail!("备份中缺少 {}/memory.db，不是有效的 RRClaw 备份", DATA_DIR);
+    }
+    for name in DATABASES {
+        let path = staged_data.join(name);
+        if path.is_file() {
+            check_integrity(&path).wrap_err_with(|| format!("备份中的 {} 已损坏", name))?;
+        }
+    }
+
+    std::fs::create_dir_all(data_dir).wrap_err("创建数据目录失败")?;
+    // 替换期间一直持有，其他进程此时也打不开这些库
+    let locks = lock_databases(data_dir, &staged_data)?;
+    let mut restored = Vec::new();
+
+    for name in DATABASES {
+        let staged = staged_data.join(name);
+        if !staged.is_file() {
+            continue;
+        }
+        let target = data_dir.join(name);
+        // 旧的 -wal / -shm 必须一起删除，否则会被当作新库的日志回放
+        for suffix in ["", "-wal", "-shm"] {
+            let path = PathBuf::from(format!("{}{}", target.display(), suffix));
+            if path.exists() {
+                std::fs::remove_file(&path)
+                    .wrap_err_with(|| format!("删除旧文件失败: {}", path.display()))?;
+            }
+        }
+        move_file(&staged, &target).wrap_err_with(|| format!("恢复 {} 失败", name))?;
+        restored.push(format!("{}/{}", DATA_DIR, name));
+    }
+    drop(locks);
+
+    for name in IDENTITY_FILES {
+        let staged = staging.join(name);
+        if staged.is_file() {
+            std::fs::rename(&staged, home.join(name))
+                .wrap_err_with(|| format!("恢复 {} 失败", name))?;
+            restored.push(name.to_string());
+        }
+    }
+
+    let staged_skills = staging.join(SKILLS_DIR);
+    if staged_skills.is_dir() {
+        let target = home.join(SKILLS_DIR);
+        if target.exists() {
+            std::fs::remove_dir_all(&target).wrap_err("删除旧 Skills 目录失败")?;
+        }
+        std::fs::rename(&staged_skills, &target).wrap_err("恢复 Skills 目录失败")?;
+        restored.push(format!("{}/", SKILLS_DIR));
+    }
+
+    let index_dir = data_dir.join("search_index");
+    if index_dir.exists() {
+        std::fs::remove_dir_all(&index_dir).wrap_err("删除旧搜索索引失败")?;
+    }
+
+    Ok(restored)
+}
+
+/// 独占锁住 `data_dir` 中将被备份替换的数据库；任一库仍被其他连接打开时报错
+///
+/// WAL 模式下单独的 `BEGIN EXCLUSIVE` 看不到空闲的连接，先切到 `locking_mode=EXCLUSIVE`
+/// 再开事务：只要还有连接打开着该库，加锁立即失败（busy_timeout 为 0，不等待）。
+fn lock_databases(data_dir: &Path, staged_data: &Path) -> Result<Vec<Connection>> {
+    let mut locks = Vec::new();
+    for name in DATABASES {
+        let target = data_dir.join(name);
+        if !staged_data.join(name).is_file() || !target.is_file() {
+            continue;
+        }
+        let conn = Connection::open(&target)
+            .wrap_err_with(|| format!("打开数据库失败: {}", target.display()))?;
+        conn.busy_timeout(std::time::Duration::ZERO)?;
+        let locked = conn
+            .query_row("PRAGMA locking_mode=EXCLUSIVE", [], |_| Ok(()))
+            .and_then(|_| conn.execute_batch("BEGIN EXCLUSIVE"));
+        match locked {
+            Ok(()) => {}
+            Err(e) if is_busy(&e) => bail!(
+                "{} 正被其他进程使用（rrclaw agent / telegram / daemon），请先退出再恢复",
+                name
+            ),
+            // 已损坏的旧库不可能在用，直接替换
+            Err(_) => continue,
+        }
+        locks.push(conn);
+    }
+    Ok(locks)
+}
+
+fn is_busy(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+/// 对 data 目录下的数据库执行 VACUUM + ANALYZE，返回每个库的前后大小
+pub fn vacuum(data_dir: &Path) -> Result<Vec<VacuumReport>> {
+    let mut reports = Vec::new();
+    for name in DATABASES {
+        let path = data_dir.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let before_bytes = db_size(&path);
+        let conn = Connection::open(&path)
+            .wrap_err_with(|| format!("打开数据库失败: {}", path.display()))?;
+        configure_connection(&conn)?;
+        conn.execute_batch("VACUUM; ANALYZE;")
+            .wrap_err_with(|| format!("VACUUM {} 失败", name))?;
+        // 把 WAL 合并回主库并截断，大小才反映真实占用
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .wrap_err("WAL checkpoint 失败")?;
+        drop(conn);
+
+        reports.push(VacuumReport {
+            file: name.to_string(),
+            before_bytes,
+            after_bytes: db_size(&path),
+        });
+    }
+    Ok(reports)
+}
+
+/// data 目录下各数据库的文件大小与每表行数
+pub fn stats(data_dir: &Path) -> Result<Vec<DbStats>> {
+    let mut all = Vec::new();
+    for name in DATABASES {
+        let path = data_dir.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let conn = Connection::open(&path)
+            .wrap_err_with(|| format!("打开数据库失败: {}", path.display()))?;
+
+        let table_names: Vec<String> = conn
+            .prepare(
+                "SELECT name FROM sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .wrap_err_with(|| format!("读取 {} 表结构失败", name))?;
+
+        let mut tables = Vec::new();
+        for table in table_names {
+            let rows: i64 = conn
+                .query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table), [], |row| {
+                    row.get(0)
+                })
+                .wrap_err_with(|| format!("统计 {}.{} 失败", name, table))?;
+            let size_bytes = conn
+                .query_row(
+                    "SELECT SUM(pgsize) FROM dbstat WHERE name = ?1",
+                    [&table],
+                    |row| row.get::<_, Option<i64>>(0),
+                )
+                .ok()
+                .flatten()
+                .map(|n| n as u64);
+            tables.push(TableStats {
+                name: table,
+                rows,
+                size_bytes,
+            });
+        }
+
+        all.push(DbStats {
+            file: name.to_string(),
+            size_bytes: db_size(&path),
+            tables,
+        });
+    }
+    Ok(all)
+}
+
+/// 数据库文件 + WAL 文件大小
+fn db_size(path: &Path) -> u64 {
+    let wal = PathBuf::from(format!("{}-wal", path.display()));
+    [path, wal.as_path()]
+        .iter()
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn check_integrity(path: &Path) -> Result<()> {
+    let conn = Connection::open(path).wrap_err("打开数据库失败")?;
+    let result: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .wrap_err("完整性检查失败")?;
+    if result != "ok" {
+        return Err(eyre!("integrity_check: {}", result));
+    }
+    Ok(())
+}
+
+/// 移动文件；XDG 布局下数据目录可能与临时目录不在同一文件系统，rename 失败时改为复制
+fn move_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if std::fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(src, dst)?;
+    std::fs::remove_file(src)
+}
+
+/// `home` 下的临时工作目录（与身份文件、Skills 同一文件系统，恢复时可直接 rename）
+fn staging_dir(home: &Path, kind: &str) -> Result<PathBuf> {
+    let dir = home.join(format!(".{}-{}", kind, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("创建临时目录失败: {}", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{Memory, MemoryCategory};
+
+    async fn populate(home: &Path) {
+        let data_dir = home.join(DATA_DIR);
+        let memory = SqliteMemory::open(&data_dir).unwrap();
+        memory
+            .store("deploy", "deploy with cargo release", MemoryCategory::Core)
+            .await
+            .unwrap();
+        memory
+            .store("editor", "user prefers helix editor", MemoryCategory::Core)
+            .await
+            .unwrap();
+        drop(memory);
+
+        let routines = Connection::open(data_dir.join("routines.db")).unwrap();
+        routines
+            .execute_batch(
+                "CREATE TABLE routines (name TEXT PRIMARY KEY);
+                 INSERT INTO routines VALUES ('daily_brief');",
+            )
+            .unwrap();
+
+        std::fs::write(home.join("USER.md"), "# me\n").unwrap();
+        let skill = home.join(SKILLS_DIR).join("deploy");
+        std::fs::create_dir_all(&skill).unwrap();
+        std::fs::write(skill.join("SKILL.md"), "---\nname: deploy\n---\n").unwrap();
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_while_database_is_open() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join(".rrclaw");
+        let data_dir = home.join(DATA_DIR);
+        populate(&home).await;
+        let archive = tmp.path().join("backup.tar.gz");
+        backup(&home, &data_dir, &archive).unwrap();
+        std::fs::write(home.join("USER.md"), "# changed\n").unwrap();
+
+        // 另一个进程（如 REPL）打开着 memory.db，即使当前没有事务
+        let open = SqliteMemory::open(&data_dir).unwrap();
+        let err = restore(&home, &data_dir, &archive).await.unwrap_err();
+        assert!(err.to_string().contains("memory.db"), "{:#}", err);
+        // 没有替换任何文件
+        assert_eq!(
+            std::fs::read_to_string(home.join("USER.md")).unwrap(),
+            "# changed\n"
+        );
+        assert_eq!(open.count().await.unwrap(), 2);
+
+        drop(open);
+        restore(&home, &data_dir, &archive).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(home.join("USER.md")).unwrap(),
+            "# me\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn backup_corrupt_restore_roundtrip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join(".rrclaw");
+        populate(&home).await;
+
+        let archive = tmp.path().join("out").join("backup.tar.gz");
+        let report = backup(&home, &home.join(DATA_DIR), &archive).unwrap();
+        assert!(report.archive_bytes > 0);
+        assert!(report.entries.contains(&"data/memory.db".to_string()));
+        assert!(report.entries.contains(&"data/routines.db".to_string()));
+        assert!(report.entries.contains(&"USER.md".to_string()));
+        assert!(report.entries.contains(&"skills/".to_string()));
+
+        // 破坏原数据
+        let data_dir = home.join(DATA_DIR);
+        std::fs::write(data_dir.join("memory.db"), b"garbage").unwrap();
+        std::fs::remove_file(data_dir.join("routines.db")).unwrap();
+        std::fs::remove_file(home.join("USER.md")).unwrap();
+        std::fs::remove_dir_all(home.join(SKILLS_DIR)).unwrap();
+
+        let restored = restore(&home, &home.join(DATA_DIR), &archive)
+            .await
+            .unwrap();
+        assert_eq!(restored.reindexed, 2);
+
+        let memory = SqliteMemory::open(&data_dir).unwrap();
+        assert_eq!(memory.count().await.unwrap(), 2);
+        let hits = memory.recall("helix", 5).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].key, "editor");
+
+        let routines = Connection::open(data_dir.join("routines.db")).unwrap();
+        let name: String = routines
+            .query_row("SELECT name FROM routines", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "daily_brief");
+
+        assert_eq!(
+            std::fs::read_to_string(home.join("USER.md")).unwrap(),
+            "# me\n"
+        );
+        assert!(home
+            .join(SKILLS_DIR)
+            .join("deploy")
+            .join("SKILL.md")
+            .is_file());
+        // 临时目录已清理
+        let leftovers: Vec<_> = std::fs::read_dir(&home)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with('.'))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn backup_is_consistent_while_another_connection_writes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join(".rrclaw");
+        populate(&home).await;
+        let data_dir = home.join(DATA_DIR);
+
+        // 写入方持有未提交事务：备份只应看到已提交的数据
+        let writer = Connection::open(data_dir.join("memory.db")).unwrap();
+        configure_connection(&writer).unwrap();
+        writer
+            .execute_batch("BEGIN; DELETE FROM memories;")
+            .unwrap();
+
+        let archive = tmp.path().join("mid-write.tar.gz");
+        backup(&home, &home.join(DATA_DIR), &archive).unwrap();
+        writer.execute_batch("ROLLBACK;").unwrap();
+        drop(writer);
+
+        // 恢复到配置与数据分开的 XDG 布局
+        let other = tmp.path().join("restored");
+        let other_data = tmp.path().join("restored-data");
+        restore(&other, &other_data, &archive).await.unwrap();
+        assert!(other.join("USER.md").is_file());
+        let memory = SqliteMemory::open(&other_data).unwrap();
+        assert_eq!(memory.count().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn restore_rejects_archive_without_memory_db() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join(".rrclaw");
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(home.join("USER.md"), "# me\n").unwrap();
+
+        // 只有身份文件，没有数据库
+        let archive = tmp.path().join("bad.tar.gz");
+        backup(&home, &home.join(DATA_DIR), &archive).unwrap();
+        let err = restore(&home, &home.join(DATA_DIR), &archive)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("memory.db"));
+        assert_eq!(
+            std::fs::read_to_string(home.join("USER.md")).unwrap(),
+            "# me\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn vacuum_and_stats_report_sizes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let home = tmp.path().join(".rrclaw");
+        populate(&home).await;
+        let data_dir = home.join(DATA_DIR);
+
+        {
+            let conn = Connection::open(data_dir.join("routines.db")).unwrap();
+            conn.execute_batch("CREATE TABLE filler (blob TEXT);")
+                .unwrap();
+            let big = "x".repeat(4096);
+            for _ in 0..200 {
+                conn.execute("INSERT INTO filler VALUES (?1)", [&big])
+                    .unwrap();
+            }
+            conn.execute_batch("DELETE FROM filler;").unwrap();
+        }
+
+        let stats = stats(&data_dir).unwrap();
+        let memory_db = stats.iter().find(|s| s.file == "memory.db").unwrap();
+        let memories = memory_db
+            .tables
+            .iter()
+            .find(|t| t.name == "memories")
+            .unwrap();
+        assert_eq!(memories.rows, 2);
+        assert!(memory_db.size_bytes > 0);
+
+        let reports = vacuum(&data_dir).unwrap();
+        let routines = reports.iter().find(|r| r.file == "routines.db").unwrap();
+        assert!(routines.reclaimed_bytes() > 0);
+    }
+}
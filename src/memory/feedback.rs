@@ -0,0 +1,491 @@
+//! 用户反馈（/good、/bad、Telegram 👍/👎）
+//!
+//! 反馈对象是“上一轮对话”：从最后一条真实 user 消息开始到 history 末尾。
+//! 记录写入 `<data_dir>/feedback.db` 的 `feedback` 表；`/bad` 附带原因时，
+//! 额外存一条 Custom("feedback") 记忆，让后续 recall 提醒模型避开失败做法。
+
+use std::ops::Range;
+use std::path::Path;
+
+use color_eyre::eyre::{Context, Result};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use super::migrations::{migrate, Migration, Step};
+use super::traits::{Memory, MemoryCategory};
+use crate::providers::ConversationMessage;
+
+/// feedback.db schema 版本（只追加，不修改已发布的版本）
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "feedback",
+    steps: &[Step::Sql(
+        "CREATE TABLE IF NOT EXISTS feedback (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at   TEXT NOT NULL,
+            channel      TEXT NOT NULL,
+            rating       TEXT NOT NULL,
+            reason       TEXT,
+            user_message TEXT NOT NULL,
+            answer       TEXT NOT NULL,
+            skills       TEXT NOT NULL DEFAULT '[]',
+            tools        TEXT NOT NULL DEFAULT '[]'
+        );",
+    )],
+}];
+
+/// 反馈评价
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackRating {
+    Good,
+    Bad,
+}
+
+impl FeedbackRating {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Good => "good",
+            Self::Bad => "bad",
+        }
+    }
+
+    /// Telegram reaction emoji → 评价（其他 emoji 忽略）
+    pub fn from_reaction(emoji: &str) -> Option<Self> {
+        match emoji {
+            "👍" => Some(Self::Good),
+            "👎" => Some(Self::Bad),
+            _ => None,
+        }
+    }
+}
+
+/// 一轮对话的摘要（反馈记录的主体）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TurnSummary {
+    pub user_message: String,
+    pub answer: String,
+    /// Phase 1 路由加载的 skill
+    pub skills: Vec<String>,
+    /// 本轮调用过的工具（按首次调用顺序去重）
+    pub tools: Vec<String>,
+}
+
+/// 一条反馈记录（feedback 表一行，export 时每行一个 JSON）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    pub created_at: String,
+    /// 来源 channel："cli" | "telegram"
+    pub channel: String,
+    pub rating: FeedbackRating,
+    pub reason: Option<String>,
+    #[serde(flatten)]
+    pub turn: TurnSummary,
+}
+
+/// `/skill` 手动注入的技能指令也以 user 角色进入 history，不算一轮对话的起点
+fn is_turn_start(msg: &ConversationMessage) -> bool {
+    matches!(
+        msg,
+        ConversationMessage::Chat(c) if c.role == "user" && !c.content.starts_with("[技能指令:")
+    )
+}
+
+/// 上一轮对话在 history 中的下标范围（最后一条真实 user 消息 → 末尾）
+///
+/// 没有 user 消息，或该轮尚无任何回复时返回 None。
+pub fn last_turn_range(history: &[ConversationMessage]) -> Option<Range<usize>> {
+    let start = history.iter().rposition(is_turn_start)?;
+    if start + 1 >= history.len() {
+        return None;
+    }
+    Some(start..history.len())
+}
+
+/// 提取上一轮对话摘要
+pub fn summarize_last_turn(
+    history: &[ConversationMessage],
+    skills: &[String],
+) -> Option<TurnSummary> {
+    let range = last_turn_range(history)?;
+    let turn = &history[range];
+
+    let user_message = match &turn[0] {
+        ConversationMessage::Chat(c) => c.content.clone(),
+        _ => return None,
+    };
+
+    let mut tools: Vec<String> = Vec::new();
+    let mut answer = String::new();
+    for msg in &turn[1..] {
+        match msg {
+            ConversationMessage::AssistantToolCalls { tool_calls, .. } => {
+                for tc in tool_calls {
+                    if !tools.contains(&tc.name) {
+                        tools.push(tc.name.clone());
+                    }
+                }
+            }
+            ConversationMessage::Chat(c) if c.role == "assistant" => {
+                answer = c.content.clone();
+            }
+            _ => {}
+        }
+    }
+
+    Some(TurnSummary {
+        user_message,
+        answer,
+        skills: skills.to_vec(),
+        tools,
+    })
+}
+
+/// `/bad <reason>` 对应的记忆条目 (key, content)
+///
+/// 内容刻意包含原问题文本，使相似问题 recall 时能命中这条教训。
+pub fn failure_memory(turn: &TurnSummary, reason: &str) -> (String, String) {
+    let question: String = turn.user_message.chars().take(200).collect();
+    let mut approach = String::new();
+    if !turn.skills.is_empty() {
+        approach.push_str(&format!("skills: {}; ", turn.skills.join(", ")));
+    }
+    if !turn.tools.is_empty() {
+        approach.push_str(&format!("tools: {}; ", turn.tools.join(", ")));
+    }
+    let content = format!(
+        "用户反馈：之前对「{}」的做法失败了（{}原因：{}）。再遇到类似问题时换一种方式。",
+        question, approach, reason
+    );
+    let key = format!("feedback_{}", chrono::Utc::now().timestamp_millis());
+    (key, content)
+}
+
+/// 反馈存储（`<data_dir>/feedback.db`）
+pub struct FeedbackStore {
+    db: Mutex<Connection>,
+}
+
+impl FeedbackStore {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(data_dir).wrap_err("创建数据目录失败")?;
+        let conn = Connection::open(data_dir.join("feedback.db")).wrap_err("打开反馈数据库失败")?;
+        super::sqlite::configure_connection(&conn)?;
+        Self::init(conn)
+    }
+
+    /// 内存数据库（测试用）
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().wrap_err("打开内存数据库失败")?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        migrate(&conn, "feedback.db", MIGRATIONS)?;
+        Ok(Self {
+            db: Mutex::new(conn),
+        })
+    }
+
+    pub async fn insert(&self, record: &FeedbackRecord) -> Result<()> {
+        let db = self.db.lock().await;
+        db.execute(
+            "INSERT INTO feedback
+             (created_at, channel, rating, reason, user_message, answer, skills, tools)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.created_at,
+                record.channel,
+                record.rating.as_str(),
+                record.reason,
+                record.turn.user_message,
+                record.turn.answer,
+                serde_json::to_string(&record.turn.skills)?,
+                serde_json::to_string(&record.turn.tools)?,
+            ],
+        )
+        .wrap_err("写入反馈失败")?;
+        Ok(())
+    }
+
+    /// 全部反馈，按时间正序
+    pub async fn list(&self) -> Result<Vec<FeedbackRecord>> {
+        let db = self.db.lock().await;
+        let mut stmt = db
+            .prepare(
+                "SELECT created_at, channel, rating, reason, user_message, answer, skills, tools
+                 FROM feedback ORDER BY id ASC",
+            )
+            .wrap_err("准备查询反馈失败")?;
+        let records = stmt
+            .query_map([], |row| {
+                let rating: String = row.get(2)?;
+                let skills: String = row.get(6)?;
+                let tools: String = row.get(7)?;
+                Ok(FeedbackRecord {
+                    created_at: row.get(0)?,
+                    channel: row.get(1)?,
+                    rating: if rating == "good" {
+                        FeedbackRating::Good
+                    } else {
+                        FeedbackRating::Bad
+                    },
+                    reason: row.get(3)?,
+                    turn: TurnSummary {
+                        user_message: row.get(4)?,
+                        answer: row.get(5)?,
+                        skills: serde_json::from_str(&skills).unwrap_or_default(),
+                        tools: serde_json::from_str(&tools).unwrap_or_default(),
+                    },
+                })
+            })
+            .wrap_err("查询反馈失败")?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(records)
+    }
+
+    /// 导出为 JSONL（每行一条记录）
+    pub async fn export_jsonl(&self, out: &mut dyn std::io::Write) -> Result<usize> {
+        let records = self.list().await?;
+        for record in &records {
+            writeln!(out, "{}", serde_json::to_string(record)?).wrap_err("写出反馈失败")?;
+        }
+        Ok(records.len())
+    }
+}
+
+/// 记录一条反馈；`Bad` 且有原因时同时写入一条 Custom("feedback") 记忆
+pub async fn record_feedback(
+    store: &FeedbackStore,
+    memory: &dyn Memory,
+    channel: &str,
+    rating: FeedbackRating,
+    turn: TurnSummary,
+    reason: Option<String>,
+) -> Result<()> {
+    let reason = reason.filter(|r| !r.trim().is_empty());
+    if let (FeedbackRating::Bad, Some(reason)) = (rating, &reason) {
+        let (key, content) = failure_memory(&turn, reason);
+        memory
+            .store(
+                &key,
+                &content,
+                MemoryCategory::Custom("feedback".to_string()),
+            )
+            .await?;
+    }
+    store
+        .insert(&FeedbackRecord {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            channel: channel.to_string(),
+            rating,
+            reason,
+            turn,
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryEntry;
+    use crate::providers::{ChatMessage, ToolCall};
+
+    fn chat(role: &str, content: &str) -> ConversationMessage {
+        ConversationMessage::Chat(ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            reasoning_content: None,
+            turn: 0,
+        })
+    }
+
+    fn tool_calls(names: &[&str]) -> ConversationMessage {
+        ConversationMessage::AssistantToolCalls {
+            text: None,
+            reasoning_content: None,
+            tool_calls: names
+                .iter()
+                .enumerate()
+                .map(|(i, n)| ToolCall {
+                    id: format!("c{}", i),
+                    name: n.to_string(),
+                    arguments: serde_json::json!({}),
+                })
+                .collect(),
+            turn: 0,
+        }
+    }
+
+    fn tool_result(id: &str) -> ConversationMessage {
+        ConversationMessage::ToolResult {
+            tool_call_id: id.to_string(),
+            content: "ok".to_string(),
+            turn: 0,
+        }
+    }
+
+    /// 记录 store 调用的 Memory
+    #[derive(Default)]
+    struct RecordingMemory {
+        stored: std::sync::Mutex<Vec<(String, String, MemoryCategory)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Memory for RecordingMemory {
+        async fn store(&self, key: &str, content: &str, category: MemoryCategory) -> Result<()> {
+            self.stored
+                .lock()
+                .unwrap()
+                .push((key.to_string(), content.to_string(), category));
+            Ok(())
+        }
+        async fn recall(&self, _query: &str, _limit: usize) -> Result<Vec<MemoryEntry>> {
+            Ok(vec![])
+        }
+        async fn forget(&self, _key: &str) -> Result<bool> {
+            Ok(false)
+        }
+        async fn count(&self) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn last_turn_covers_tool_calls_and_answer() {
+        let history = vec![
+            chat("user", "first"),
+            chat("assistant", "a1"),
+            chat("user", "list files"),
+            tool_calls(&["shell"]),
+            tool_result("c0"),
+            tool_calls(&["file_read", "shell"]),
+            tool_result("c0"),
+            tool_result("c1"),
+            chat("assistant", "here they are"),
+        ];
+        assert_eq!(last_turn_range(&history), Some(2..9));
+
+        let turn = summarize_last_turn(&history, &["git-helper".to_string()]).unwrap();
+        assert_eq!(turn.user_message, "list files");
+        assert_eq!(turn.answer, "here they are");
+        assert_eq!(turn.tools, vec!["shell", "file_read"]);
+        assert_eq!(turn.skills, vec!["git-helper"]);
+    }
+
+    #[test]
+    fn skill_injection_is_not_a_turn_start() {
+        let history = vec![
+            chat("user", "question"),
+            chat("assistant", "answer"),
+            chat("user", "[技能指令: code-review]\n..."),
+        ];
+        assert_eq!(last_turn_range(&history), Some(0..3));
+        let turn = summarize_last_turn(&history, &[]).unwrap();
+        assert_eq!(turn.user_message, "question");
+        assert_eq!(turn.answer, "answer");
+    }
+
+    #[test]
+    fn no_turn_without_reply() {
+        assert!(last_turn_range(&[]).is_none());
+        assert!(last_turn_range(&[chat("user", "pending")]).is_none());
+        assert!(summarize_last_turn(&[chat("assistant", "hi")], &[]).is_none());
+    }
+
+    #[test]
+    fn reaction_mapping() {
+        assert_eq!(
+            FeedbackRating::from_reaction("👍"),
+            Some(FeedbackRating::Good)
+        );
+        assert_eq!(
+            FeedbackRating::from_reaction("👎"),
+            Some(FeedbackRating::Bad)
+        );
+        assert_eq!(FeedbackRating::from_reaction("🔥"), None);
+    }
+
+    #[tokio::test]
+    async fn bad_with_reason_stores_memory_entry() {
+        let store = FeedbackStore::in_memory().unwrap();
+        let memory = RecordingMemory::default();
+        let turn = TurnSummary {
+            user_message: "deploy the app".to_string(),
+            answer: "done".to_string(),
+            skills: vec![],
+            tools: vec!["shell".to_string()],
+        };
+        record_feedback(
+            &store,
+            &memory,
+            "cli",
+            FeedbackRating::Bad,
+            turn,
+            Some("used the wrong branch".to_string()),
+        )
+        .await
+        .unwrap();
+
+        {
+            let stored = memory.stored.lock().unwrap();
+            assert_eq!(stored.len(), 1);
+            let (key, content, category) = &stored[0];
+            assert!(key.starts_with("feedback_"));
+            assert!(content.contains("deploy the app"));
+            assert!(content.contains("used the wrong branch"));
+            assert!(content.contains("shell"));
+            assert_eq!(category, &MemoryCategory::Custom("feedback".to_string()));
+        }
+
+        let records = store.list().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].rating, FeedbackRating::Bad);
+        assert_eq!(records[0].turn.tools, vec!["shell"]);
+    }
+
+    #[tokio::test]
+    async fn good_or_reasonless_bad_skips_memory() {
+        let store = FeedbackStore::in_memory().unwrap();
+        let memory = RecordingMemory::default();
+        for (rating, reason) in [
+            (FeedbackRating::Good, Some("nice".to_string())),
+            (FeedbackRating::Bad, None),
+            (FeedbackRating::Bad, Some("  ".to_string())),
+        ] {
+            record_feedback(
+                &store,
+                &memory,
+                "telegram",
+                rating,
+                TurnSummary::default(),
+                reason,
+            )
+            .await
+            .unwrap();
+        }
+        assert!(memory.stored.lock().unwrap().is_empty());
+        assert_eq!(store.list().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn export_writes_one_json_per_line() {
+        let store = FeedbackStore::in_memory().unwrap();
+        let memory = RecordingMemory::default();
+        for rating in [FeedbackRating::Good, FeedbackRating::Bad] {
+            record_feedback(&store, &memory, "cli", rating, TurnSummary::default(), None)
+                .await
+                .unwrap();
+        }
+        let mut out = Vec::new();
+        assert_eq!(store.export_jsonl(&mut out).await.unwrap(), 2);
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["rating"], "good");
+        assert_eq!(first["channel"], "cli");
+        assert!(first["tools"].is_array());
+    }
+}
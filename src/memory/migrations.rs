@@ -0,0 +1,217 @@
+//! 基于 `PRAGMA user_version` 的轻量 SQLite schema 迁移
+//!
+//! 每个数据库维护一张有序的 `Migration` 列表，打开时把 `user_version` 之后的
+//! 迁移逐个在事务中执行并推进版本号。已发布的迁移不可修改，只能追加新版本。
+//!
+//! 引入本机制之前创建的库 `user_version` 为 0：v1 基线使用 `CREATE TABLE IF NOT EXISTS`，
+//! 补列使用幂等的 `Step::AddColumn`，因此旧库同样可以安全升级。
+
+use color_eyre::eyre::{bail, Context, Result};
+use rusqlite::Connection;
+
+/// 迁移中的一步
+pub enum Step {
+    /// 任意 SQL（可含多条语句）
+    Sql(&'static str),
+    /// `ALTER TABLE .. ADD COLUMN`，列已存在时跳过
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    },
+}
+
+/// 一个 schema 版本
+pub struct Migration {
+    /// 目标版本号，从 1 开始连续递增
+    pub version: u32,
+    pub description: &'static str,
+    pub steps: &'static [Step],
+}
+
+/// 当前 schema 版本（`PRAGMA user_version`）
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .wrap_err("读取 schema 版本失败")
+}
+
+/// 将数据库迁移到 `migrations` 中的最新版本，返回最终版本号
+///
+/// 已是最新版本时不做任何修改；数据库版本高于程序已知版本（被新版 RRClaw 升级过）时报错，
+/// 避免旧程序误写新 schema。
+pub fn migrate(conn: &Connection, db_name: &str, migrations: &[Migration]) -> Result<u32> {
+    debug_assert!(
+        migrations
+            .iter()
+            .enumerate()
+            .all(|(i, m)| m.version == i as u32 + 1),
+        "迁移版本号必须从 1 开始连续递增"
+    );
+
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    let current = schema_version(conn)?;
+    if current > latest {
+        bail!(
+            "{} 的 schema 版本 ({}) 高于当前程序支持的版本 ({})，请升级 RRClaw",
+            db_name,
+            current,
+            latest
+        );
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.unchecked_transaction().wrap_err("开启迁移事务失败")?;
+        for step in migration.steps {
+            apply_step(&tx, step).wrap_err_with(|| {
+                format!(
+                    "{} 迁移到 v{} 失败（{}）",
+                    db_name, migration.version, migration.description
+                )
+            })?;
+        }
+        // user_version 不支持参数绑定，版本号为 u32，直接拼接安全
+        tx.execute_batch(&format!("PRAGMA user_version = {}", migration.version))
+            .wrap_err("写入 schema 版本失败")?;
+        tx.commit().wrap_err("提交迁移事务失败")?;
+        tracing::info!(
+            "{} schema 迁移到 v{}: {}",
+            db_name,
+            migration.version,
+            migration.description
+        );
+    }
+
+    Ok(latest)
+}
+
+fn apply_step(conn: &Connection, step: &Step) -> Result<()> {
+    match step {
+        Step::Sql(sql) => conn.execute_batch(sql).wrap_err("执行迁移 SQL 失败"),
+        Step::AddColumn {
+            table,
+            column,
+            definition,
+        } => {
+            if has_column(conn, table, column)? {
+                return Ok(());
+            }
+            conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))
+            .wrap_err_with(|| format!("为 {} 添加列 {} 失败", table, column))
+        }
+    }
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .wrap_err("读取表结构失败")?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .wrap_err("读取表结构失败")?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .wrap_err("读取表结构失败")?;
+    Ok(names.iter().any(|n| n == column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            description: "baseline",
+            steps: &[Step::Sql(
+                "CREATE TABLE IF NOT EXISTS items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);",
+            )],
+        },
+        Migration {
+            version: 2,
+            description: "items.expires_at",
+            steps: &[Step::AddColumn {
+                table: "items",
+                column: "expires_at",
+                definition: "TEXT",
+            }],
+        },
+    ];
+
+    #[test]
+    fn fresh_db_migrates_to_latest() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(migrate(&conn, "test.db", MIGRATIONS).unwrap(), 2);
+        assert_eq!(schema_version(&conn).unwrap(), 2);
+        assert!(has_column(&conn, "items", "expires_at").unwrap());
+    }
+
+    #[test]
+    fn unversioned_old_schema_is_adopted_idempotently() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+             INSERT INTO items (name) VALUES ('kept');",
+        )
+        .unwrap();
+
+        migrate(&conn, "test.db", MIGRATIONS).unwrap();
+        migrate(&conn, "test.db", MIGRATIONS).unwrap();
+
+        assert_eq!(schema_version(&conn).unwrap(), 2);
+        let name: String = conn
+            .query_row("SELECT name FROM items", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(name, "kept");
+    }
+
+    #[test]
+    fn column_added_before_versioning_is_skipped() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL, expires_at TEXT);",
+        )
+        .unwrap();
+        assert_eq!(migrate(&conn, "test.db", MIGRATIONS).unwrap(), 2);
+    }
+
+    #[test]
+    fn newer_db_version_is_rejected() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA user_version = 9").unwrap();
+        let err = migrate(&conn, "test.db", MIGRATIONS).unwrap_err();
+        assert!(err.to_string().contains("请升级 RRClaw"));
+    }
+
+    #[test]
+    fn failed_migration_rolls_back_version() {
+        const BROKEN: &[Migration] = &[
+            Migration {
+                version: 1,
+                description: "baseline",
+                steps: &[Step::Sql("CREATE TABLE t (a TEXT);")],
+            },
+            Migration {
+                version: 2,
+                description: "broken",
+                steps: &[
+                    Step::Sql("CREATE TABLE u (b TEXT);"),
+                    Step::Sql("NOT VALID SQL"),
+                ],
+            },
+        ];
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(migrate(&conn, "test.db", BROKEN).is_err());
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        // v2 的第一步也随事务回滚
+        let exists: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'u'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(exists, 0);
+    }
+}
@@ -1,12 +1,119 @@
+pub mod backup;
+pub mod feedback;
+pub mod migrations;
 pub mod sqlite;
 pub mod traits;
 
+pub use feedback::{FeedbackRating, FeedbackStore};
 pub use sqlite::SqliteMemory;
 pub use traits::{Memory, MemoryCategory, MemoryEntry};
 
+use std::sync::atomic::{AtomicBool, Ordering};
+
 /// 空操作 Memory 实现，用于不需要持久化记忆的临时 Agent（如 Routine 执行）
 pub struct NoopMemory;
 
+/// 本进程的持久化是否已降级（Memory 以临时库运行，或写入过失败）
+static PERSISTENCE_DEGRADED: AtomicBool = AtomicBool::new(false);
+
+/// 持久化是否已降级（`/config` 与 self_info 展示）
+pub fn is_degraded() -> bool {
+    PERSISTENCE_DEGRADED.load(Ordering::Relaxed)
+}
+
+/// 记录一次持久化写入失败：首次 warn 并标记降级，之后只记 debug，避免每轮刷屏
+pub fn report_write_error(what: &str, err: &color_eyre::eyre::Report) {
+    if PERSISTENCE_DEGRADED.swap(true, Ordering::Relaxed) {
+        tracing::debug!("{}失败: {:#}", what, err);
+    } else {
+        tracing::warn!(
+            "{}失败，后续同类错误不再提示，本次会话可能不会持久化: {:#}",
+            what,
+            err
+        );
+    }
+}
+
+/// 打开持久化 Memory，必要时降级为临时 Memory
+///
+/// - 数据目录不可写（权限不足 / 只读文件系统）→ 总是降级
+/// - 其他打开失败 → `fallback_to_noop` 开启时降级，否则报错
+///
+/// 降级实例不写磁盘（进程退出即丢失），与 NoopMemory 一样不持久化，但保留
+/// `SqliteMemory` 类型，对话历史 / 核心知识等接口在本次会话内照常工作。
+/// 返回 `(memory, degraded)`，degraded 为 true 时调用方应醒目提示用户（只提示一次）。
+pub fn open_with_fallback(
+    data_dir: &std::path::Path,
+    fallback_to_noop: bool,
+) -> color_eyre::eyre::Result<(SqliteMemory, bool)> {
+    open_with_probe(data_dir, fallback_to_noop, probe_writable)
+}
+
+/// `open_with_fallback` 的实现，可替换可写探测（测试中 root 不受目录权限限制，需注入失败）
+fn open_with_probe(
+    data_dir: &std::path::Path,
+    fallback_to_noop: bool,
+    probe: fn(&std::path::Path) -> std::io::Result<()>,
+) -> color_eyre::eyre::Result<(SqliteMemory, bool)> {
+    use color_eyre::eyre::WrapErr;
+
+    let result = match probe(data_dir) {
+        Err(e) if is_unwritable(&e) => Err(color_eyre::eyre::Report::new(e)
+            .wrap_err(format!("数据目录不可写: {}", data_dir.display()))),
+        _ => SqliteMemory::open(data_dir),
+    };
+    match result {
+        Ok(memory) => Ok((memory, false)),
+        Err(e) if fallback_to_noop || e.chain().any(is_unwritable_error) => {
+            tracing::warn!(
+                "打开 Memory 失败（{}），降级为临时 Memory，本次会话不持久化: {:#}",
+                data_dir.display(),
+                e
+            );
+            let memory = SqliteMemory::in_memory().wrap_err("创建临时 Memory 失败")?;
+            PERSISTENCE_DEGRADED.store(true, Ordering::Relaxed);
+            Ok((memory, true))
+        }
+        Err(e) => Err(e).wrap_err("初始化 Memory 失败"),
+    }
+}
+
+/// 确认数据目录可创建且可写入（写入并删除一个探测文件）
+fn probe_writable(data_dir: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let probe = data_dir.join(".write-probe");
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+fn is_unwritable(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+fn is_unwritable_error(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .is_some_and(is_unwritable)
+}
+
+/// Memory 降级时输出到 stderr 的醒目提示
+pub fn degraded_memory_warning(data_dir: &std::path::Path) -> String {
+    if crate::config::Config::get_language().is_english() {
+        format!(
+            "⚠️  Memory store at {} could not be opened. Running WITHOUT persistence: \
+             memories and conversation history will be lost on exit.",
+            data_dir.display()
+        )
+    } else {
+        format!(
+            "⚠️  无法打开记忆库 {}，当前以无持久化模式运行：记忆与对话历史在退出后丢失。",
+            data_dir.display()
+        )
+    }
+}
+
 /// 允许将 Arc<dyn Memory> 直接装箱传给 Agent（Routine 共享 Memory 场景）
 #[async_trait::async_trait]
 impl Memory for std::sync::Arc<dyn Memory> {
@@ -63,3 +170,74 @@ impl Memory for NoopMemory {
         Ok(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unwritable_data_dir_falls_back_when_enabled() {
+        let tmp = tempfile::tempdir().unwrap();
+        // 父路径是普通文件，create_dir_all 必然失败（root 下也成立）
+        let blocker = tmp.path().join("not-a-dir");
+        std::fs::write(&blocker, "x").unwrap();
+        let data_dir = blocker.join("data");
+
+        let err = open_with_fallback(&data_dir, false)
+            .err()
+            .expect("未开启降级时应报错");
+        assert!(format!("{:#}", err).contains("初始化 Memory 失败"));
+
+        let (memory, degraded) = open_with_fallback(&data_dir, true).unwrap();
+        assert!(degraded);
+        assert!(!data_dir.exists());
+        // 降级实例在本次会话内可正常使用
+        memory
+            .store("k", "hello", MemoryCategory::Core)
+            .await
+            .unwrap();
+        assert_eq!(memory.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_only_data_dir_always_falls_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let data_dir = tmp.path().join("data");
+        // 注入只读文件系统错误：真实权限在 root 下不生效
+        let read_only: fn(&std::path::Path) -> std::io::Result<()> =
+            |_| Err(std::io::Error::from(std::io::ErrorKind::ReadOnlyFilesystem));
+
+        // 未开启 fallback_to_noop 也降级，而不是中止启动
+        let (memory, degraded) = open_with_probe(&data_dir, false, read_only).unwrap();
+        assert!(degraded);
+        assert!(is_degraded());
+        assert!(!data_dir.join("memory.db").exists());
+        memory.save_conversation_history("s1", &[]).await.unwrap();
+
+        // 其他探测错误不算只读：未开启 fallback_to_noop 时照常打开
+        let other: fn(&std::path::Path) -> std::io::Result<()> =
+            |_| Err(std::io::Error::other("probe failed"));
+        let (_memory, degraded) = open_with_probe(&data_dir, false, other).unwrap();
+        assert!(!degraded);
+        assert!(data_dir.join("memory.db").exists());
+    }
+
+    #[test]
+    fn unwritable_errors_are_detected_through_report_chain() {
+        let err = color_eyre::eyre::Report::new(std::io::Error::from(
+            std::io::ErrorKind::PermissionDenied,
+        ))
+        .wrap_err("打开数据库失败");
+        assert!(err.chain().any(is_unwritable_error));
+        let err = color_eyre::eyre::eyre!("database disk image is malformed");
+        assert!(!err.chain().any(is_unwritable_error));
+    }
+
+    #[test]
+    fn writable_data_dir_is_not_degraded() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (_memory, degraded) = open_with_fallback(tmp.path(), true).unwrap();
+        assert!(!degraded);
+        assert!(tmp.path().join("memory.db").exists());
+    }
+}
@@ -1,18 +1,98 @@
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use color_eyre::eyre::{Context, Result};
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use tantivy::collector::TopDocs;
 use tantivy::query::QueryParser;
 use tantivy::schema::*;
 use tantivy::{doc, Index, IndexWriter, ReloadPolicy, TantivyDocument, Term};
 use tokio::sync::Mutex;
 
+use super::migrations::{migrate, Migration, Step};
 use super::traits::{Memory, MemoryCategory, MemoryEntry};
+use crate::agent::SessionGoal;
 use crate::providers::ConversationMessage;
 
+/// 写锁等待上限：并发写入方（REPL / Routine / Telegram）在此时间内重试而非立即报 "database is locked"
+const BUSY_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// 为文件型 SQLite 连接启用 WAL 与 busy_timeout
+///
+/// WAL 模式下读写互不阻塞；busy_timeout 让写入方在锁被占用时等待而不是直接失败。
+/// memory.db / routines.db / feedback.db 打开后均需调用。
+pub(crate) fn configure_connection(conn: &Connection) -> Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .wrap_err("设置 SQLite busy_timeout 失败")?;
+    let _mode: String = conn
+        .query_row("PRAGMA journal_mode=WAL", [], |row| row.get(0))
+        .wrap_err("启用 SQLite WAL 模式失败")?;
+    Ok(())
+}
+
+/// memory.db schema 版本（只追加，不修改已发布的版本）
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "memories + conversation_history + search_meta",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS memories (
+            key TEXT PRIMARY KEY,
+            content TEXT NOT NULL,
+            category TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS conversation_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            seq INTEGER NOT NULL,
+            payload TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_conv_session ON conversation_history(session_id);
+        CREATE TABLE IF NOT EXISTS search_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+        )],
+    },
+    Migration {
+        version: 2,
+        description: "session_goals",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS session_goals (
+            session_id TEXT PRIMARY KEY,
+            payload TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );",
+        )],
+    },
+    Migration {
+        version: 3,
+        description: "conversation_history.format_version",
+        steps: &[Step::AddColumn {
+            table: "conversation_history",
+            column: "format_version",
+            definition: "INTEGER NOT NULL DEFAULT 0",
+        }],
+    },
+];
+
+/// 对话历史消息的持久化格式版本（`conversation_history.format_version`）
+///
+/// `ConversationMessage` 增删字段 / 变体导致旧数据无法解析时递增；0 为加入版本号之前写入的数据。
+pub const HISTORY_FORMAT_VERSION: i64 = 1;
+
+/// 加载结果：可解析的消息 + 因格式不兼容（其他版本写入 / 已损坏）跳过的条数
+#[derive(Debug, Default)]
+pub struct LoadedHistory {
+    pub messages: Vec<ConversationMessage>,
+    pub skipped: usize,
+}
+
 /// SQLite + tantivy 记忆实现
 pub struct SqliteMemory {
     db: Arc<Mutex<Connection>>,
@@ -46,6 +126,7 @@ impl SqliteMemory {
 
         let db_path = data_dir.join("memory.db");
         let db = Connection::open(&db_path).wrap_err("打开 SQLite 失败")?;
+        configure_connection(&db)?;
 
         // 提前建 search_meta 表，用于读取上次使用的分词器
         db.execute_batch(
@@ -151,29 +232,8 @@ impl SqliteMemory {
             .writer(50_000_000) // 50MB heap
             .wrap_err("创建 IndexWriter 失败")?;
 
-        // 初始化 SQLite 表
-        db.execute_batch(
-            "CREATE TABLE IF NOT EXISTS memories (
-                key TEXT PRIMARY KEY,
-                content TEXT NOT NULL,
-                category TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            );
-            CREATE TABLE IF NOT EXISTS conversation_history (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                session_id TEXT NOT NULL,
-                seq INTEGER NOT NULL,
-                payload TEXT NOT NULL,
-                created_at TEXT NOT NULL
-            );
-            CREATE INDEX IF NOT EXISTS idx_conv_session ON conversation_history(session_id);
-            CREATE TABLE IF NOT EXISTS search_meta (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL
-            );",
-        )
-        .wrap_err("创建数据库表失败")?;
+        // 初始化 / 迁移 SQLite 表
+        migrate(&db, "memory.db", MIGRATIONS)?;
 
         // 记录当前分词器名称，供下次启动对比
         db.execute(
@@ -212,9 +272,9 @@ impl SqliteMemory {
         for (i, msg) in history.iter().enumerate() {
             let payload = serde_json::to_string(msg).wrap_err("序列化对话消息失败")?;
             db.execute(
-                "INSERT INTO conversation_history (session_id, seq, payload, created_at)
-                 VALUES (?1, ?2, ?3, ?4)",
-                params![session_id, i as i64, payload, now],
+                "INSERT INTO conversation_history (session_id, seq, payload, created_at, format_version)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![session_id, i as i64, payload, now, HISTORY_FORMAT_VERSION],
             )
             .wrap_err("写入对话历史失败")?;
         }
@@ -222,29 +282,112 @@ impl SqliteMemory {
         Ok(())
     }
 
-    /// 加载指定 session 的对话历史
+    /// 加载指定 session 的对话历史；无法解析的消息跳过，并汇总记一条 warn
+    ///
+    /// 跳过消息可能留下孤立的 ToolResult / 不完整的 tool call，由 `Agent::set_history` 清理。
     pub async fn load_conversation_history(
         &self,
         session_id: &str,
     ) -> Result<Vec<ConversationMessage>> {
+        let loaded = self.load_conversation_history_checked(session_id).await?;
+        if loaded.skipped > 0 {
+            tracing::warn!(
+                "跳过 {} 条无法解析的对话历史消息（来自其他版本的 RRClaw 或已损坏, session: {}）",
+                loaded.skipped,
+                session_id
+            );
+        }
+        Ok(loaded.messages)
+    }
+
+    /// 逐条解析指定 session 的对话历史，返回可解析的消息与跳过的条数
+    pub async fn load_conversation_history_checked(
+        &self,
+        session_id: &str,
+    ) -> Result<LoadedHistory> {
         let db = self.db.lock().await;
         let mut stmt = db
             .prepare(
-                "SELECT payload FROM conversation_history WHERE session_id = ?1 ORDER BY seq ASC",
+                "SELECT payload, format_version FROM conversation_history
+                 WHERE session_id = ?1 ORDER BY seq ASC",
             )
             .wrap_err("准备查询对话历史失败")?;
 
-        let messages: Vec<ConversationMessage> = stmt
+        let rows = stmt
             .query_map(params![session_id], |row| {
-                let payload: String = row.get(0)?;
-                Ok(payload)
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
             })
-            .wrap_err("查询对话历史失败")?
-            .filter_map(|r| r.ok())
-            .filter_map(|payload| serde_json::from_str(&payload).ok())
-            .collect();
+            .wrap_err("查询对话历史失败")?;
+
+        let mut loaded = LoadedHistory::default();
+        for row in rows {
+            let parsed = row.ok().and_then(|(payload, version)| {
+                serde_json::from_str::<ConversationMessage>(&payload)
+                    .map_err(|e| tracing::debug!("跳过对话历史消息（格式版本 {}）: {}", version, e))
+                    .ok()
+            });
+            match parsed {
+                Some(message) => loaded.messages.push(message),
+                None => loaded.skipped += 1,
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// 保存 session 的目标（None 删除）
+    pub async fn save_session_goal(
+        &self,
+        session_id: &str,
+        goal: Option<&SessionGoal>,
+    ) -> Result<()> {
+        let db = self.db.lock().await;
+        match goal {
+            Some(goal) => {
+                let payload = serde_json::to_string(goal).wrap_err("序列化会话目标失败")?;
+                db.execute(
+                    "INSERT INTO session_goals (session_id, payload, updated_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(session_id) DO UPDATE SET payload = ?2, updated_at = ?3",
+                    params![session_id, payload, chrono::Utc::now().to_rfc3339()],
+                )
+                .wrap_err("写入会话目标失败")?;
+            }
+            None => {
+                db.execute(
+                    "DELETE FROM session_goals WHERE session_id = ?1",
+                    params![session_id],
+                )
+                .wrap_err("删除会话目标失败")?;
+            }
+        }
+        Ok(())
+    }
 
-        Ok(messages)
+    /// 加载 session 的目标（不存在或无法解析时返回 None）
+    pub async fn load_session_goal(&self, session_id: &str) -> Result<Option<SessionGoal>> {
+        let db = self.db.lock().await;
+        let payload: Option<String> = db
+            .query_row(
+                "SELECT payload FROM session_goals WHERE session_id = ?1",
+                params![session_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("查询会话目标失败")?;
+        Ok(payload.and_then(|p| serde_json::from_str(&p).ok()))
+    }
+
+    /// 最近一次保存过对话历史的 session（每次保存都会整体重写，id 最大者即最新）
+    pub async fn latest_session_id(&self) -> Result<Option<String>> {
+        let db = self.db.lock().await;
+        let session = db
+            .query_row(
+                "SELECT session_id FROM conversation_history ORDER BY id DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .wrap_err("查询最近 session 失败")?;
+        Ok(session)
     }
 
     /// 种入核心知识条目（启动时调用，upsert 语义）
@@ -291,6 +434,38 @@ impl SqliteMemory {
         Ok(())
     }
 
+    /// 清空 tantivy 索引并从 memories 表全量重建，返回写入条数
+    ///
+    /// 用于索引丢失或损坏后恢复搜索（如 `rrclaw memory restore` 之后）。
+    pub async fn rebuild_index(&self) -> Result<usize> {
+        let entries: Vec<(String, String, String)> = {
+            let db = self.db.lock().await;
+            let mut stmt = db
+                .prepare("SELECT key, content, category FROM memories")
+                .wrap_err("准备查询语句失败")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .wrap_err("读取记忆失败")?;
+            rows.collect::<rusqlite::Result<_>>()
+                .wrap_err("读取记忆失败")?
+        };
+
+        let mut writer = self.index_writer.lock().await;
+        writer
+            .delete_all_documents()
+            .wrap_err("清空 tantivy 索引失败")?;
+        for (key, content, category) in &entries {
+            writer.add_document(doc!(
+                self.key_field => key.as_str(),
+                self.content_field => content.as_str(),
+                self.category_field => category.as_str(),
+            ))?;
+        }
+        writer.commit().wrap_err("tantivy commit 失败")?;
+
+        Ok(entries.len())
+    }
+
     /// 从 SQLite 根据 key 查询完整条目
     async fn get_from_sqlite(&self, key: &str) -> Result<Option<MemoryEntry>> {
         let db = self.db.lock().await;
@@ -562,6 +737,7 @@ mod tests {
                 role: "user".to_string(),
                 content: "你好".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
             ConversationMessage::AssistantToolCalls {
                 text: Some("让我查看".to_string()),
@@ -571,15 +747,18 @@ mod tests {
                     name: "shell".to_string(),
                     arguments: serde_json::json!({"command": "ls"}),
                 }],
+                turn: 0,
             },
             ConversationMessage::ToolResult {
                 tool_call_id: "call_1".to_string(),
                 content: "file.txt".to_string(),
+                turn: 0,
             },
             ConversationMessage::Chat(ChatMessage {
                 role: "assistant".to_string(),
                 content: "目录中有 file.txt".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
         ];
 
@@ -600,6 +779,33 @@ mod tests {
         assert!(payload.contains("file.txt"));
     }
 
+    #[tokio::test]
+    async fn latest_session_is_the_last_saved() {
+        use crate::providers::{ChatMessage, ConversationMessage};
+
+        let mem = create_test_memory().await;
+        assert!(mem.latest_session_id().await.unwrap().is_none());
+
+        let msg = |content: &str| {
+            vec![ConversationMessage::Chat(ChatMessage {
+                role: "user".to_string(),
+                content: content.to_string(),
+                reasoning_content: None,
+                turn: 0,
+            })]
+        };
+        mem.save_conversation_history("2026-01-02", &msg("b"))
+            .await
+            .unwrap();
+        mem.save_conversation_history("2026-01-01", &msg("a"))
+            .await
+            .unwrap();
+        assert_eq!(
+            mem.latest_session_id().await.unwrap().as_deref(),
+            Some("2026-01-01")
+        );
+    }
+
     #[tokio::test]
     async fn load_nonexistent_session_returns_empty() {
         let mem = create_test_memory().await;
@@ -618,6 +824,7 @@ mod tests {
             role: "user".to_string(),
             content: "first".to_string(),
             reasoning_content: None,
+            turn: 0,
         })];
         mem.save_conversation_history(session_id, &history1)
             .await
@@ -628,11 +835,13 @@ mod tests {
                 role: "user".to_string(),
                 content: "second".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
             ConversationMessage::Chat(ChatMessage {
                 role: "assistant".to_string(),
                 content: "reply".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
         ];
         mem.save_conversation_history(session_id, &history2)
@@ -657,6 +866,7 @@ mod tests {
                 role: "user".to_string(),
                 content: "查看文件".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
             ConversationMessage::AssistantToolCalls {
                 text: Some("让我查看".to_string()),
@@ -666,15 +876,18 @@ mod tests {
                     name: "shell".to_string(),
                     arguments: serde_json::json!({"command": "ls"}),
                 }],
+                turn: 0,
             },
             ConversationMessage::ToolResult {
                 tool_call_id: "call_1".to_string(),
                 content: "file.txt".to_string(),
+                turn: 0,
             },
             ConversationMessage::Chat(ChatMessage {
                 role: "assistant".to_string(),
                 content: "目录中有 file.txt".to_string(),
                 reasoning_content: Some("工具返回了文件列表".to_string()),
+                turn: 0,
             }),
         ];
 
@@ -727,6 +940,90 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn conversation_history_without_turns_is_migrated_on_load() {
+        // 旧版本保存的历史没有 turn 字段：加载后按 user 消息补齐，再保存可保留标记
+        let mem = create_test_memory().await;
+        let session_id = "pre-turns";
+        let old_payloads = [
+            r#"{"Chat":{"role":"user","content":"列出文件"}}"#,
+            r#"{"AssistantToolCalls":{"text":null,"tool_calls":[{"id":"1","name":"shell","arguments":{}}]}}"#,
+            r#"{"ToolResult":{"tool_call_id":"1","content":"a.txt"}}"#,
+            r#"{"Chat":{"role":"assistant","content":"只有 a.txt"}}"#,
+            r#"{"Chat":{"role":"user","content":"谢谢"}}"#,
+        ];
+        let db = mem.db.lock().await;
+        for (seq, payload) in old_payloads.iter().enumerate() {
+            db.execute(
+                "INSERT INTO conversation_history (session_id, seq, payload, created_at) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![session_id, seq as i64, payload, "2024-01-01T00:00:00Z"],
+            ).unwrap();
+        }
+        drop(db);
+
+        let mut loaded = mem.load_conversation_history(session_id).await.unwrap();
+        assert!(loaded.iter().all(|m| m.turn() == 0));
+        assert_eq!(crate::agent::turns::assign_missing_turns(&mut loaded), 2);
+
+        mem.save_conversation_history(session_id, &loaded)
+            .await
+            .unwrap();
+        let reloaded = mem.load_conversation_history(session_id).await.unwrap();
+        let turns: Vec<u64> = reloaded.iter().map(|m| m.turn()).collect();
+        assert_eq!(turns, vec![1, 1, 1, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn incompatible_history_messages_are_skipped() {
+        let mem = create_test_memory().await;
+        let session_id = "mixed-versions";
+        let payloads = [
+            r#"{"Chat":{"role":"user","content":"列出文件"}}"#,
+            // 未来版本新增的变体
+            r#"{"ImageAttachment":{"path":"a.png","turn":1}}"#,
+            r#"{"ToolResult":{"tool_call_id":"1","content":"a.txt"}}"#,
+            // 损坏的 JSON
+            r#"{"Chat":{"role":"assistant","#,
+            // 未知字段忽略，消息照常加载
+            r#"{"Chat":{"role":"assistant","content":"只有 a.txt","sentiment":"ok"}}"#,
+        ];
+        let db = mem.db.lock().await;
+        for (seq, payload) in payloads.iter().enumerate() {
+            db.execute(
+                "INSERT INTO conversation_history (session_id, seq, payload, created_at, format_version)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![session_id, seq as i64, payload, "2024-01-01T00:00:00Z", 9],
+            )
+            .unwrap();
+        }
+        drop(db);
+
+        let loaded = mem
+            .load_conversation_history_checked(session_id)
+            .await
+            .unwrap();
+        assert_eq!(loaded.skipped, 2);
+        assert_eq!(loaded.messages.len(), 3);
+        assert!(matches!(
+            &loaded.messages[2],
+            ConversationMessage::Chat(cm) if cm.content == "只有 a.txt"
+        ));
+
+        // 新写入的消息带当前格式版本
+        mem.save_conversation_history(session_id, &loaded.messages)
+            .await
+            .unwrap();
+        let db = mem.db.lock().await;
+        let versions: Vec<i64> = db
+            .prepare("SELECT DISTINCT format_version FROM conversation_history")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|v| v.unwrap())
+            .collect();
+        assert_eq!(versions, vec![HISTORY_FORMAT_VERSION]);
+    }
+
     #[tokio::test]
     async fn seed_core_knowledge_stores_and_recalls() {
         let mem = create_test_memory().await;
@@ -887,4 +1184,90 @@ mod tests {
             assert_eq!(mem.count().await.unwrap(), 1);
         }
     }
+
+    #[tokio::test]
+    async fn open_enables_wal_mode() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mem = SqliteMemory::open_with_tokenizer(tmp.path(), "en_stem").unwrap();
+        let db = mem.db.lock().await;
+        let mode: String = db
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        let timeout: i64 = db
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(timeout, 5000);
+    }
+
+    #[test]
+    fn concurrent_writers_do_not_hit_lock_errors() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db_path = tmp.path().join("memory.db");
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            configure_connection(&conn).unwrap();
+            conn.execute_batch("CREATE TABLE t (writer INTEGER, n INTEGER);")
+                .unwrap();
+        }
+
+        // 两个独立连接（模拟 REPL 与 Routine）同时反复写事务
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+        let handles: Vec<_> = (0..2)
+            .map(|writer| {
+                let db_path = db_path.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || -> rusqlite::Result<()> {
+                    let mut conn = Connection::open(&db_path)?;
+                    configure_connection(&conn).unwrap();
+                    barrier.wait();
+                    for n in 0..200 {
+                        let tx = conn.transaction()?;
+                        tx.execute("INSERT INTO t VALUES (?1, ?2)", params![writer, n])?;
+                        tx.commit()?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        for h in handles {
+            h.join()
+                .unwrap()
+                .expect("并发写入不应出现 database is locked");
+        }
+
+        let conn = Connection::open(&db_path).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 400);
+    }
+
+    #[tokio::test]
+    async fn open_migrates_unversioned_db_and_keeps_data() {
+        let tmp = tempfile::tempdir().unwrap();
+        {
+            // 引入 schema 版本之前的库：只有 memories 表，user_version = 0
+            let conn = Connection::open(tmp.path().join("memory.db")).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE memories (
+                    key TEXT PRIMARY KEY, content TEXT NOT NULL, category TEXT NOT NULL,
+                    created_at TEXT NOT NULL, updated_at TEXT NOT NULL
+                );
+                INSERT INTO memories VALUES ('k', 'legacy content', 'core', 'x', 'x');",
+            )
+            .unwrap();
+        }
+
+        for _ in 0..2 {
+            let mem = SqliteMemory::open_with_tokenizer(tmp.path(), "en_stem").unwrap();
+            assert_eq!(mem.count().await.unwrap(), 1);
+            let db = mem.db.lock().await;
+            assert_eq!(
+                crate::memory::migrations::schema_version(&db).unwrap(),
+                MIGRATIONS.last().unwrap().version
+            );
+        }
+    }
 }
@@ -0,0 +1,367 @@
+//! 运行指标（Prometheus 文本格式）
+//!
+//! 进程级全局 `Registry`，埋点分布在 ReliableProvider（每次请求尝试）、Agent 工具执行、
+//! RoutineEngine（每次执行）和 Telegram 会话表。daemon 配置了 `[daemon] metrics_port` 时由
+//! `server::serve` 在 `/metrics` 暴露；未配置时只在内存中累加，开销可忽略。
+//!
+//! 指标种类只有 counter / gauge / histogram 三种，标签按插入顺序输出，
+//! 不依赖外部 crate。
+
+pub mod server;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 耗时 histogram 的桶上界（秒）
+const DURATION_BUCKETS: &[f64] = &[
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// 指标定义：名称 + 说明
+#[derive(Debug, Clone, Copy)]
+pub struct Metric {
+    pub name: &'static str,
+    pub help: &'static str,
+}
+
+pub const ROUTINE_EXECUTIONS: Metric = Metric {
+    name: "rrclaw_routine_executions_total",
+    help: "Routine executions by routine name and outcome",
+};
+pub const ROUTINE_DURATION: Metric = Metric {
+    name: "rrclaw_routine_duration_seconds",
+    help: "Routine execution duration including retries",
+};
+pub const PROVIDER_REQUESTS: Metric = Metric {
+    name: "rrclaw_provider_requests_total",
+    help: "Provider request attempts by provider, model and outcome",
+};
+pub const PROVIDER_ERRORS: Metric = Metric {
+    name: "rrclaw_provider_errors_total",
+    help: "Failed provider request attempts by provider and model",
+};
+pub const PROVIDER_LATENCY: Metric = Metric {
+    name: "rrclaw_provider_request_duration_seconds",
+    help: "Provider request latency by provider and model",
+};
+pub const TOOL_EXECUTIONS: Metric = Metric {
+    name: "rrclaw_tool_executions_total",
+    help: "Tool executions by tool name and outcome",
+};
+pub const TELEGRAM_ACTIVE_CHATS: Metric = Metric {
+    name: "rrclaw_telegram_active_chats",
+    help: "Telegram chats with a live agent session",
+};
+pub const MEMORY_ROWS: Metric = Metric {
+    name: "rrclaw_memory_rows",
+    help: "Rows in the memory store",
+};
+const UPTIME: Metric = Metric {
+    name: "rrclaw_uptime_seconds",
+    help: "Seconds since the process started",
+};
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Clone)]
+enum Series {
+    Counter(u64),
+    Gauge(f64),
+    Histogram {
+        /// 各桶累计计数（与 `DURATION_BUCKETS` 对应，不含 +Inf）
+        buckets: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+impl Series {
+    fn kind(&self) -> &'static str {
+        match self {
+            Series::Counter(_) => "counter",
+            Series::Gauge(_) => "gauge",
+            Series::Histogram { .. } => "histogram",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Family {
+    help: &'static str,
+    series: BTreeMap<Labels, Series>,
+}
+
+/// 指标注册表
+#[derive(Debug)]
+pub struct Registry {
+    started: Instant,
+    families: Mutex<BTreeMap<&'static str, Family>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            families: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Registry {
+    /// 进程级全局实例（埋点统一写这里）
+    pub fn global() -> &'static Registry {
+        static GLOBAL: OnceLock<Registry> = OnceLock::new();
+        GLOBAL.get_or_init(Registry::default)
+    }
+
+    fn update_or_insert(
+        &self,
+        metric: Metric,
+        labels: &[(&'static str, &str)],
+        initial: Series,
+        f: impl FnOnce(&mut Series),
+    ) {
+        let labels: Labels = labels.iter().map(|(k, v)| (*k, v.to_string())).collect();
+        let mut families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+        let family = families.entry(metric.name).or_insert_with(|| Family {
+            help: metric.help,
+            series: BTreeMap::new(),
+        });
+        f(family.series.entry(labels).or_insert(initial));
+    }
+
+    /// counter +1
+    pub fn inc(&self, metric: Metric, labels: &[(&'static str, &str)]) {
+        self.update_or_insert(metric, labels, Series::Counter(0), |series| {
+            if let Series::Counter(n) = series {
+                *n += 1;
+            }
+        });
+    }
+
+    /// 设置 gauge
+    pub fn set(&self, metric: Metric, labels: &[(&'static str, &str)], value: f64) {
+        self.update_or_insert(metric, labels, Series::Gauge(0.0), |series| {
+            if let Series::Gauge(v) = series {
+                *v = value;
+            }
+        });
+    }
+
+    /// 记录一次耗时
+    pub fn observe(&self, metric: Metric, labels: &[(&'static str, &str)], duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let initial = Series::Histogram {
+            buckets: vec![0; DURATION_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        };
+        self.update_or_insert(metric, labels, initial, |series| {
+            if let Series::Histogram {
+                buckets,
+                sum,
+                count,
+            } = series
+            {
+                for (bucket, bound) in buckets.iter_mut().zip(DURATION_BUCKETS) {
+                    if secs <= *bound {
+                        *bucket += 1;
+                    }
+                }
+                *sum += secs;
+                *count += 1;
+            }
+        });
+    }
+
+    /// 渲染为 Prometheus 文本格式（0.0.4），附带进程运行时长
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, family) in families.iter() {
+            let Some(kind) = family.series.values().next().map(Series::kind) else {
+                continue;
+            };
+            let _ = writeln!(out, "# HELP {} {}", name, family.help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, series) in &family.series {
+                match series {
+                    Series::Counter(n) => {
+                        let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), n);
+                    }
+                    Series::Gauge(v) => {
+                        let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), v);
+                    }
+                    Series::Histogram {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        for (bound, n) in DURATION_BUCKETS.iter().zip(buckets) {
+                            let le = bound.to_string();
+                            let _ = writeln!(
+                                out,
+                                "{}_bucket{} {}",
+                                name,
+                                format_labels(labels, Some(&le)),
+                                n
+                            );
+                        }
+                        let _ = writeln!(
+                            out,
+                            "{}_bucket{} {}",
+                            name,
+                            format_labels(labels, Some("+Inf")),
+                            count
+                        );
+                        let _ =
+                            writeln!(out, "{}_sum{} {}", name, format_labels(labels, None), sum);
+                        let _ = writeln!(
+                            out,
+                            "{}_count{} {}",
+                            name,
+                            format_labels(labels, None),
+                            count
+                        );
+                    }
+                }
+            }
+        }
+        let _ = writeln!(out, "# HELP {} {}", UPTIME.name, UPTIME.help);
+        let _ = writeln!(out, "# TYPE {} gauge", UPTIME.name);
+        let _ = writeln!(
+            out,
+            "{} {}",
+            UPTIME.name,
+            self.started.elapsed().as_secs_f64()
+        );
+        out
+    }
+}
+
+/// `{k="v",...}`，无标签时为空串；`le` 为 histogram 桶上界
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// ─── 埋点入口 ────────────────────────────────────────────────────────────
+
+/// RoutineEngine：一次执行结束（含重试），outcome 为 success / failure / deferred
+pub fn record_routine(name: &str, outcome: &str, duration: Duration) {
+    let registry = Registry::global();
+    registry.inc(
+        ROUTINE_EXECUTIONS,
+        &[("routine", name), ("outcome", outcome)],
+    );
+    registry.observe(ROUTINE_DURATION, &[("routine", name)], duration);
+}
+
+/// ReliableProvider：一次请求尝试结束（重试的每次尝试分别计数）
+pub fn record_provider_request(provider: &str, model: &str, ok: bool, duration: Duration) {
+    let registry = Registry::global();
+    let outcome = if ok { "success" } else { "error" };
+    registry.inc(
+        PROVIDER_REQUESTS,
+        &[
+            ("provider", provider),
+            ("model", model),
+            ("outcome", outcome),
+        ],
+    );
+    if !ok {
+        registry.inc(PROVIDER_ERRORS, &[("provider", provider), ("model", model)]);
+    }
+    registry.observe(
+        PROVIDER_LATENCY,
+        &[("provider", provider), ("model", model)],
+        duration,
+    );
+}
+
+/// Agent 工具执行：outcome 为 success / failure（ToolResult 失败）/ error（执行出错）/ unknown
+pub fn record_tool(tool: &str, outcome: &str) {
+    Registry::global().inc(TOOL_EXECUTIONS, &[("tool", tool), ("outcome", outcome)]);
+}
+
+/// Telegram：当前持有 Agent 的 chat 数
+pub fn set_telegram_chats(count: usize) {
+    Registry::global().set(TELEGRAM_ACTIVE_CHATS, &[], count as f64);
+}
+
+/// Memory 行数（抓取时刷新）
+pub fn set_memory_rows(count: usize) {
+    Registry::global().set(MEMORY_ROWS, &[], count as f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_counters_gauges_and_histograms() {
+        let registry = Registry::default();
+        registry.inc(
+            TOOL_EXECUTIONS,
+            &[("tool", "shell"), ("outcome", "success")],
+        );
+        registry.inc(
+            TOOL_EXECUTIONS,
+            &[("tool", "shell"), ("outcome", "success")],
+        );
+        registry.set(MEMORY_ROWS, &[], 7.0);
+        registry.observe(
+            PROVIDER_LATENCY,
+            &[("provider", "deepseek"), ("model", "deepseek-chat")],
+            Duration::from_millis(300),
+        );
+
+        let text = registry.render();
+        assert!(text.contains("# TYPE rrclaw_tool_executions_total counter"));
+        assert!(text.contains("rrclaw_tool_executions_total{tool=\"shell\",outcome=\"success\"} 2"));
+        assert!(text.contains("rrclaw_memory_rows 7"));
+        assert!(text.contains("# TYPE rrclaw_provider_request_duration_seconds histogram"));
+        // 0.3s 落在 0.5 桶而不在 0.25 桶
+        assert!(text.contains(
+            "rrclaw_provider_request_duration_seconds_bucket{provider=\"deepseek\",model=\"deepseek-chat\",le=\"0.25\"} 0"
+        ));
+        assert!(text.contains(
+            "rrclaw_provider_request_duration_seconds_bucket{provider=\"deepseek\",model=\"deepseek-chat\",le=\"0.5\"} 1"
+        ));
+        assert!(text.contains(
+            "rrclaw_provider_request_duration_seconds_count{provider=\"deepseek\",model=\"deepseek-chat\"} 1"
+        ));
+        assert!(text.contains("# TYPE rrclaw_uptime_seconds gauge"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let registry = Registry::default();
+        registry.inc(
+            ROUTINE_EXECUTIONS,
+            &[("routine", "a\"b"), ("outcome", "success")],
+        );
+        assert!(registry
+            .render()
+            .contains("rrclaw_routine_executions_total{routine=\"a\\\"b\",outcome=\"success\"} 1"));
+    }
+}
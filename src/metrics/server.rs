@@ -0,0 +1,124 @@
+//! `/metrics` HTTP 端点
+//!
+//! 只处理 `GET /metrics`，其余路径返回 404。请求头读完即响应并关闭连接，
+//! 不支持 keep-alive（Prometheus 抓取足够）。
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use color_eyre::eyre::{Context, Result};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+
+use super::Registry;
+use crate::memory::Memory;
+
+/// 请求头最大字节数（超出直接断开）
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// 绑定监听地址
+pub async fn bind(addr: SocketAddr) -> Result<TcpListener> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .wrap_err_with(|| format!("Failed to bind metrics endpoint {}", addr))?;
+    info!("Metrics endpoint listening on http://{}/metrics", addr);
+    Ok(listener)
+}
+
+/// 持续处理抓取请求；`memory` 存在时每次抓取前刷新行数
+pub async fn serve(
+    listener: TcpListener,
+    registry: &'static Registry,
+    memory: Option<Arc<dyn Memory>>,
+) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                debug!("metrics accept failed: {}", e);
+                continue;
+            }
+        };
+        let memory = memory.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, registry, memory).await {
+                debug!("metrics request from {} failed: {:#}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    registry: &Registry,
+    memory: Option<Arc<dyn Memory>>,
+) -> Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 || buf.len() + n > MAX_REQUEST_BYTES {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let request_line = String::from_utf8_lossy(&buf);
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    let path = path.map(|p| p.split('?').next().unwrap_or(p));
+
+    let (status, body) = if method == Some("GET") && path == Some("/metrics") {
+        if let Some(memory) = &memory {
+            if let Ok(rows) = memory.count().await {
+                super::set_memory_rows(rows);
+            }
+        }
+        ("200 OK", registry.render())
+    } else {
+        ("404 Not Found", "not found\n".to_string())
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// 测试辅助：向 `addr` 发送 GET 请求，返回完整响应文本
+#[cfg(test)]
+pub(crate) async fn scrape(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_metrics_and_404_for_other_paths() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let memory: Arc<dyn Memory> = Arc::new(crate::memory::NoopMemory);
+        tokio::spawn(serve(listener, Registry::global(), Some(memory)));
+
+        let response = scrape(addr, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("rrclaw_uptime_seconds"));
+        assert!(response.contains("rrclaw_memory_rows 0"));
+
+        let response = scrape(addr, "/").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+    }
+}
@@ -7,15 +7,26 @@ use tracing::{debug, trace, warn};
 
 use crate::config::ProviderConfig;
 
+use super::params::GenerationParams;
+use super::stream_sink::EventSink;
+use super::tool_stream::ToolCallAssembler;
 use super::traits::{
-    ChatMessage, ChatResponse, ConversationMessage, Provider, StreamEvent, ToolCall, ToolSpec,
+    ChatMessage, ChatResponse, ConversationMessage, Provider, StreamEvent, StreamInterrupted,
+    ToolCall, ToolSpec, ToolStatusKind,
 };
 
+/// 未配置 `max_tokens` 时的输出上限（Messages API 要求必须携带该字段）
+const DEFAULT_MAX_TOKENS: u32 = 8192;
+
 /// Anthropic Messages API Provider
 pub struct ClaudeProvider {
     client: reqwest::Client,
     base_url: String,
     api_key: String,
+    /// 配置的自定义 headers（在内置 headers 之后设置，同名时覆盖）
+    headers: reqwest::header::HeaderMap,
+    /// 输出 token 上限
+    max_tokens: u32,
 }
 
 impl ClaudeProvider {
@@ -28,6 +39,8 @@ impl ClaudeProvider {
             client,
             base_url: config.base_url.trim_end_matches('/').to_string(),
             api_key: config.api_key.clone(),
+            headers: super::custom_header_map(&config.headers),
+            max_tokens: config.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
         }
     }
 
@@ -81,6 +94,7 @@ impl ClaudeProvider {
                 ConversationMessage::ToolResult {
                     tool_call_id,
                     content,
+                    ..
                 } => {
                     claude_messages.push(serde_json::json!({
                         "role": "user",
@@ -119,21 +133,27 @@ impl ClaudeProvider {
 
     /// 构造请求体
     fn build_request_body(
+        &self,
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
         stream: bool,
     ) -> serde_json::Value {
         let (system, claude_messages) = Self::extract_system(messages);
 
         let mut body = serde_json::json!({
             "model": model,
-            "max_tokens": 8192,
+            // 会话覆盖（/param set max_tokens）优先于 Provider 配置
+            "max_tokens": params.max_tokens.unwrap_or(self.max_tokens),
             "messages": claude_messages,
-            "temperature": temperature,
+            "temperature": params.temperature,
         });
 
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+
         if let Some(system_text) = system {
             body["system"] = serde_json::Value::String(system_text);
         }
@@ -187,6 +207,7 @@ impl ClaudeProvider {
             text,
             reasoning_content: None,
             tool_calls,
+            provenance: None,
         }
     }
 }
@@ -198,9 +219,9 @@ impl Provider for ClaudeProvider {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
     ) -> Result<ChatResponse> {
-        let body = Self::build_request_body(messages, tools, model, temperature, false);
+        let body = self.build_request_body(messages, tools, model, params, false);
 
         debug!("Claude API 请求: {} model={}", self.endpoint(), model);
         trace!(
@@ -214,6 +235,7 @@ impl Provider for ClaudeProvider {
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("Content-Type", "application/json")
+            .headers(self.headers.clone())
             .json(&body)
             .send()
             .await
@@ -241,10 +263,10 @@ impl Provider for ClaudeProvider {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
         tx: mpsc::Sender<StreamEvent>,
     ) -> Result<ChatResponse> {
-        let body = Self::build_request_body(messages, tools, model, temperature, true);
+        let body = self.build_request_body(messages, tools, model, params, true);
 
         debug!("Claude API 流式请求: {} model={}", self.endpoint(), model);
         trace!(
@@ -258,6 +280,7 @@ impl Provider for ClaudeProvider {
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("Content-Type", "application/json")
+            .headers(self.headers.clone())
             .json(&body)
             .send()
             .await
@@ -274,16 +297,29 @@ impl Provider for ClaudeProvider {
         }
 
         debug!("Claude API 流式响应状态: {}", status);
+        let mut sink = EventSink::new(tx);
 
         // 累积状态
         let mut text_parts = Vec::new();
-        let mut tool_calls: Vec<ToolCall> = Vec::new();
-        let mut current_tool_input = String::new();
+        let mut tool_calls = ToolCallAssembler::new();
         let mut line_buf = String::new();
+        // 收到 message_stop 才算完整响应
+        let mut finished = false;
 
         let mut byte_stream = resp.bytes_stream();
         while let Some(chunk) = byte_stream.next().await {
-            let chunk = chunk.wrap_err("读取 SSE 数据块失败")?;
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    sink.flush().await;
+                    return Err(StreamInterrupted {
+                        partial_text: text_parts.concat(),
+                        has_tool_calls: !tool_calls.is_empty(),
+                        reason: format!("读取 SSE 数据块失败: {}", e),
+                    }
+                    .into());
+                }
+            };
             let chunk_str = String::from_utf8_lossy(&chunk);
             line_buf.push_str(&chunk_str);
 
@@ -313,15 +349,20 @@ impl Provider for ClaudeProvider {
                     "content_block_start" => {
                         let block = &event["content_block"];
                         if block["type"].as_str() == Some("tool_use") {
-                            // 新 tool_use block 开始
-                            let id = block["id"].as_str().unwrap_or("").to_string();
-                            let name = block["name"].as_str().unwrap_or("").to_string();
-                            tool_calls.push(ToolCall {
-                                id,
-                                name,
-                                arguments: serde_json::Value::Object(serde_json::Map::new()),
-                            });
-                            current_tool_input.clear();
+                            // 新 tool_use block 开始：名称在这里就已知，参数随后分片到达
+                            let idx = tool_calls.len();
+                            if let Some(name) = tool_calls.push(
+                                idx,
+                                block["id"].as_str(),
+                                block["name"].as_str(),
+                                "",
+                            ) {
+                                sink.send(StreamEvent::ToolStatus {
+                                    name,
+                                    status: ToolStatusKind::Preparing,
+                                })
+                                .await;
+                            }
                         }
                     }
                     "content_block_delta" => {
@@ -331,42 +372,28 @@ impl Provider for ClaudeProvider {
                                 if let Some(text) = delta["text"].as_str() {
                                     if !text.is_empty() {
                                         text_parts.push(text.to_string());
-                                        let _ = tx.send(StreamEvent::Text(text.to_string())).await;
+                                        sink.send(StreamEvent::Text(text.to_string())).await;
                                     }
                                 }
                             }
                             Some("input_json_delta") => {
                                 if let Some(partial) = delta["partial_json"].as_str() {
-                                    current_tool_input.push_str(partial);
-                                    let idx = if tool_calls.is_empty() {
-                                        0
-                                    } else {
-                                        tool_calls.len() - 1
-                                    };
-                                    let _ = tx
-                                        .send(StreamEvent::ToolCallDelta {
-                                            index: idx,
-                                            id: None,
-                                            name: None,
-                                            arguments_delta: partial.to_string(),
-                                        })
-                                        .await;
+                                    let idx = tool_calls.len().saturating_sub(1);
+                                    tool_calls.push(idx, None, None, partial);
+                                    sink.send(StreamEvent::ToolCallDelta {
+                                        index: idx,
+                                        id: None,
+                                        name: None,
+                                        arguments_delta: partial.to_string(),
+                                    })
+                                    .await;
                                 }
                             }
                             _ => {}
                         }
                     }
-                    "content_block_stop" => {
-                        // 当前 block 结束，如果是 tool_use，解析累积的 input
-                        if !current_tool_input.is_empty() {
-                            if let Some(tc) = tool_calls.last_mut() {
-                                tc.arguments = serde_json::from_str(&current_tool_input)
-                                    .unwrap_or(serde_json::Value::Object(serde_json::Map::new()));
-                            }
-                            current_tool_input.clear();
-                        }
-                    }
                     "message_stop" => {
+                        finished = true;
                         break;
                     }
                     _ => {}
@@ -374,6 +401,17 @@ impl Provider for ClaudeProvider {
             }
         }
 
+        if !finished {
+            warn!("Claude SSE 流在 message_stop 前关闭");
+            sink.flush().await;
+            return Err(StreamInterrupted {
+                partial_text: text_parts.concat(),
+                has_tool_calls: !tool_calls.is_empty(),
+                reason: "连接在结束标记前关闭".to_string(),
+            }
+            .into());
+        }
+
         let text = if text_parts.is_empty() {
             None
         } else {
@@ -383,9 +421,10 @@ impl Provider for ClaudeProvider {
         let response = ChatResponse {
             text,
             reasoning_content: None,
-            tool_calls,
+            tool_calls: tool_calls.finish(),
+            provenance: None,
         };
-        let _ = tx.send(StreamEvent::Done(response.clone())).await;
+        sink.send(StreamEvent::Done(response.clone())).await;
 
         debug!(
             "Claude 流式响应完成: text_len={}, tool_calls={}",
@@ -424,11 +463,52 @@ mod tests {
             api_key: "test".to_string(),
             model: "claude-sonnet-4-5-20250929".to_string(),
             auth_style: Some("x-api-key".to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
         };
         let provider = ClaudeProvider::new(&config);
         assert_eq!(provider.endpoint(), "https://api.anthropic.com/v1/messages");
     }
 
+    #[test]
+    fn max_tokens_defaults_and_follows_config() {
+        let mut config = ProviderConfig {
+            base_url: "https://api.anthropic.com".to_string(),
+            api_key: "test".to_string(),
+            model: "claude-sonnet-4-5-20250929".to_string(),
+            auth_style: Some("x-api-key".to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        };
+        // Messages API 要求 max_tokens，未配置时使用默认值
+        let body =
+            ClaudeProvider::new(&config).build_request_body(&[], &[], "m", 0.7.into(), false);
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+
+        config.max_tokens = Some(512);
+        let body = ClaudeProvider::new(&config).build_request_body(&[], &[], "m", 0.7.into(), true);
+        assert_eq!(body["max_tokens"], 512);
+
+        // 会话覆盖优先于配置
+        let params = GenerationParams {
+            temperature: 0.0,
+            top_p: Some(0.5),
+            max_tokens: Some(128),
+        };
+        let body = ClaudeProvider::new(&config).build_request_body(&[], &[], "m", params, false);
+        assert_eq!(body["max_tokens"], 128);
+        assert_eq!(body["top_p"], 0.5);
+        assert_eq!(body["temperature"], 0.0);
+    }
+
     #[test]
     fn extract_system_separates_correctly() {
         let msgs = vec![
@@ -436,11 +516,13 @@ mod tests {
                 role: "system".to_string(),
                 content: "You are RRClaw.".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
             ConversationMessage::Chat(ChatMessage {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
         ];
         let (system, claude_msgs) = ClaudeProvider::extract_system(&msgs);
@@ -456,11 +538,13 @@ mod tests {
                 role: "system".to_string(),
                 content: "Part 1".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
             ConversationMessage::Chat(ChatMessage {
                 role: "system".to_string(),
                 content: "Part 2".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
         ];
         let (system, _) = ClaudeProvider::extract_system(&msgs);
@@ -478,10 +562,12 @@ mod tests {
                     name: "shell".to_string(),
                     arguments: serde_json::json!({"command": "ls"}),
                 }],
+                turn: 0,
             },
             ConversationMessage::ToolResult {
                 tool_call_id: "toolu_1".to_string(),
                 content: "file.txt".to_string(),
+                turn: 0,
             },
         ];
         let (_, claude_msgs) = ClaudeProvider::extract_system(&msgs);
@@ -555,4 +641,50 @@ mod tests {
         assert_eq!(parsed.tool_calls[0].id, "toolu_abc");
         assert_eq!(parsed.tool_calls[0].name, "shell");
     }
+
+    #[tokio::test]
+    async fn custom_headers_sent_with_request() {
+        let (base_url, server) = crate::providers::test_server::capture_one(
+            r#"{"content":[{"type":"text","text":"ok"}]}"#,
+        )
+        .await;
+        let config = ProviderConfig {
+            base_url,
+            api_key: "sk-test".to_string(),
+            model: "m".to_string(),
+            auth_style: Some("x-api-key".to_string()),
+            headers: [
+                ("X-Org-Id".to_string(), "org-42".to_string()),
+                ("X-Route-Key".to_string(), "blue".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        };
+        let provider = ClaudeProvider::new(&config);
+        let resp = provider
+            .chat_with_tools(
+                &[ConversationMessage::Chat(ChatMessage {
+                    role: "user".to_string(),
+                    content: "hi".to_string(),
+                    reasoning_content: None,
+                    turn: 0,
+                })],
+                &[],
+                "m",
+                0.7.into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.text.as_deref(), Some("ok"));
+
+        let head = server.await.unwrap().to_lowercase();
+        assert!(head.starts_with("post /v1/messages"), "{}", head);
+        assert!(head.contains("x-org-id: org-42"), "{}", head);
+        assert!(head.contains("x-route-key: blue"), "{}", head);
+    }
 }
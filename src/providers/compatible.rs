@@ -7,15 +7,28 @@ use tracing::{debug, trace, warn};
 
 use crate::config::ProviderConfig;
 
+use super::params::GenerationParams;
+use super::stream_sink::EventSink;
+use super::tool_stream::ToolCallAssembler;
 use super::traits::{
-    ChatMessage, ChatResponse, ConversationMessage, Provider, StreamEvent, ToolCall, ToolSpec,
+    ChatMessage, ChatResponse, ConversationMessage, Provider, StreamEvent, StreamInterrupted,
+    ToolCall, ToolSpec, ToolStatusKind,
 };
 
+/// 默认请求路径
+const CHAT_COMPLETIONS_PATH: &str = "/chat/completions";
+
 /// OpenAI 兼容协议 Provider（GLM/MiniMax/DeepSeek/GPT）
 pub struct CompatibleProvider {
     client: reqwest::Client,
     base_url: String,
     api_key: String,
+    /// 配置的自定义 headers（在内置 headers 之后设置，同名时覆盖）
+    headers: reqwest::header::HeaderMap,
+    /// 配置的 endpoint_path（覆盖默认 `/chat/completions`）
+    endpoint_path: Option<String>,
+    /// 输出 token 上限（None = 不发送，由服务端决定）
+    max_tokens: Option<u32>,
 }
 
 impl CompatibleProvider {
@@ -28,12 +41,31 @@ impl CompatibleProvider {
             client,
             base_url: config.base_url.trim_end_matches('/').to_string(),
             api_key: config.api_key.clone(),
+            headers: super::custom_header_map(&config.headers),
+            endpoint_path: config.endpoint_path.clone(),
+            max_tokens: config.max_tokens,
         }
     }
 
     /// 构造请求 URL
+    ///
+    /// - 配置了 `endpoint_path`：完整 URL 直接使用，否则拼接到 base_url 后
+    /// - base_url 已以 `/chat/completions` 结尾（部分网关要求填完整地址）：原样使用
+    /// - 其余情况追加 `/chat/completions`
     fn endpoint(&self) -> String {
-        format!("{}/chat/completions", self.base_url)
+        if let Some(path) = self.endpoint_path.as_deref().map(str::trim) {
+            if path.starts_with("http://") || path.starts_with("https://") {
+                return path.to_string();
+            }
+            if !path.is_empty() {
+                return format!("{}/{}", self.base_url, path.trim_start_matches('/'));
+            }
+        }
+        if self.base_url.ends_with(CHAT_COMPLETIONS_PATH) {
+            self.base_url.clone()
+        } else {
+            format!("{}{}", self.base_url, CHAT_COMPLETIONS_PATH)
+        }
     }
 
     /// 将 ConversationMessage 转换为 OpenAI messages 格式
@@ -46,6 +78,7 @@ impl CompatibleProvider {
                     role,
                     content,
                     reasoning_content,
+                    ..
                 }) => {
                     let mut obj = serde_json::json!({
                         "role": role,
@@ -63,6 +96,7 @@ impl CompatibleProvider {
                     text,
                     reasoning_content,
                     tool_calls,
+                    ..
                 } => {
                     let mut obj = serde_json::json!({
                         "role": "assistant",
@@ -94,6 +128,7 @@ impl CompatibleProvider {
                 ConversationMessage::ToolResult {
                     tool_call_id,
                     content,
+                    ..
                 } => {
                     result.push(serde_json::json!({
                         "role": "tool",
@@ -126,18 +161,27 @@ impl CompatibleProvider {
 
     /// 构造请求体（stream/非stream 共用）
     fn build_request_body(
+        &self,
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
         stream: bool,
     ) -> serde_json::Value {
         let mut body = serde_json::json!({
             "model": model,
             "messages": Self::build_messages(messages),
-            "temperature": temperature,
+            "temperature": params.temperature,
         });
 
+        // 会话覆盖（/param set max_tokens）优先于 Provider 配置
+        if let Some(max_tokens) = params.max_tokens.or(self.max_tokens) {
+            body["max_tokens"] = serde_json::json!(max_tokens);
+        }
+        if let Some(top_p) = params.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+
         let built_tools = Self::build_tools(tools);
         if !built_tools.is_empty() {
             body["tools"] = serde_json::Value::Array(built_tools);
@@ -159,6 +203,7 @@ impl CompatibleProvider {
                     text: None,
                     reasoning_content: None,
                     tool_calls: vec![],
+                    provenance: None,
                 }
             }
         };
@@ -190,6 +235,7 @@ impl CompatibleProvider {
             text,
             reasoning_content,
             tool_calls,
+            provenance: None,
         }
     }
 }
@@ -201,9 +247,9 @@ impl Provider for CompatibleProvider {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
     ) -> Result<ChatResponse> {
-        let body = Self::build_request_body(messages, tools, model, temperature, false);
+        let body = self.build_request_body(messages, tools, model, params, false);
 
         debug!("API 请求: {} model={}", self.endpoint(), model);
         trace!(
@@ -216,6 +262,7 @@ impl Provider for CompatibleProvider {
             .post(self.endpoint())
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
+            .headers(self.headers.clone())
             .json(&body)
             .send()
             .await
@@ -246,10 +293,10 @@ impl Provider for CompatibleProvider {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
         tx: mpsc::Sender<StreamEvent>,
     ) -> Result<ChatResponse> {
-        let body = Self::build_request_body(messages, tools, model, temperature, true);
+        let body = self.build_request_body(messages, tools, model, params, true);
 
         debug!("API 流式请求: {} model={}", self.endpoint(), model);
         trace!(
@@ -262,6 +309,7 @@ impl Provider for CompatibleProvider {
             .post(self.endpoint())
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
+            .headers(self.headers.clone())
             .json(&body)
             .send()
             .await
@@ -278,17 +326,30 @@ impl Provider for CompatibleProvider {
         }
 
         debug!("API 流式响应状态: {}", status);
+        let mut sink = EventSink::new(tx);
 
         // 累积状态
         let mut full_text = String::new();
         let mut full_reasoning = String::new(); // reasoning_content 单独累积
-                                                // tool_calls 累积: index → (id, name, arguments_buffer)
-        let mut tool_calls_acc: Vec<(String, String, String)> = Vec::new();
+        let mut tool_calls_acc = ToolCallAssembler::new();
         let mut line_buf = String::new();
+        // 收到 [DONE] 或 finish_reason 才算完整响应
+        let mut finished = false;
 
         let mut byte_stream = resp.bytes_stream();
         while let Some(chunk) = byte_stream.next().await {
-            let chunk = chunk.wrap_err("读取 SSE 数据块失败")?;
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    sink.flush().await;
+                    return Err(StreamInterrupted {
+                        partial_text: full_text,
+                        has_tool_calls: !tool_calls_acc.is_empty(),
+                        reason: format!("读取 SSE 数据块失败: {}", e),
+                    }
+                    .into());
+                }
+            };
             let chunk_str = String::from_utf8_lossy(&chunk);
 
             // SSE 协议: 每行 "data: {...}\n\n"，可能一个 chunk 包含多行
@@ -304,6 +365,7 @@ impl Provider for CompatibleProvider {
                 }
 
                 if line == "data: [DONE]" {
+                    finished = true;
                     break;
                 }
 
@@ -321,11 +383,14 @@ impl Provider for CompatibleProvider {
                 };
 
                 if let Some(choice) = parsed.choices.first() {
+                    if choice.finish_reason.is_some() {
+                        finished = true;
+                    }
                     // 文本增量: content 和 reasoning_content 分别累积
                     if let Some(content) = choice.delta.content.as_deref().filter(|s| !s.is_empty())
                     {
                         full_text.push_str(content);
-                        let _ = tx.send(StreamEvent::Text(content.to_string())).await;
+                        sink.send(StreamEvent::Text(content.to_string())).await;
                     }
                     if let Some(rc) = choice
                         .delta
@@ -334,36 +399,38 @@ impl Provider for CompatibleProvider {
                         .filter(|s| !s.is_empty())
                     {
                         full_reasoning.push_str(rc);
-                        let _ = tx.send(StreamEvent::Thinking).await;
+                        sink.send(StreamEvent::Reasoning(rc.to_string())).await;
                     }
 
                     // tool call 增量
                     if let Some(tc_deltas) = &choice.delta.tool_calls {
                         for tc in tc_deltas {
                             let idx = tc.index.unwrap_or(0);
-
-                            // 扩展 tool_calls_acc 到足够大小
-                            while tool_calls_acc.len() <= idx {
-                                tool_calls_acc.push((String::new(), String::new(), String::new()));
-                            }
-
-                            if let Some(id) = &tc.id {
-                                tool_calls_acc[idx].0 = id.clone();
+                            let name = tc.function.as_ref().and_then(|f| f.name.as_deref());
+                            let args = tc
+                                .function
+                                .as_ref()
+                                .and_then(|f| f.arguments.as_deref())
+                                .unwrap_or("");
+                            // 名称一到就提示，不等参数流完
+                            if let Some(name) =
+                                tool_calls_acc.push(idx, tc.id.as_deref(), name, args)
+                            {
+                                sink.send(StreamEvent::ToolStatus {
+                                    name,
+                                    status: ToolStatusKind::Preparing,
+                                })
+                                .await;
                             }
                             if let Some(func) = &tc.function {
-                                if let Some(name) = &func.name {
-                                    tool_calls_acc[idx].1 = name.clone();
-                                }
                                 if let Some(args) = &func.arguments {
-                                    tool_calls_acc[idx].2.push_str(args);
-                                    let _ = tx
-                                        .send(StreamEvent::ToolCallDelta {
-                                            index: idx,
-                                            id: tc.id.clone(),
-                                            name: tc.function.as_ref().and_then(|f| f.name.clone()),
-                                            arguments_delta: args.clone(),
-                                        })
-                                        .await;
+                                    sink.send(StreamEvent::ToolCallDelta {
+                                        index: idx,
+                                        id: tc.id.clone(),
+                                        name: tc.function.as_ref().and_then(|f| f.name.clone()),
+                                        arguments_delta: args.clone(),
+                                    })
+                                    .await;
                                 }
                             }
                         }
@@ -372,17 +439,19 @@ impl Provider for CompatibleProvider {
             }
         }
 
+        if !finished {
+            warn!("SSE 流在结束标记前关闭: text_len={}", full_text.len());
+            sink.flush().await;
+            return Err(StreamInterrupted {
+                partial_text: full_text,
+                has_tool_calls: !tool_calls_acc.is_empty(),
+                reason: "连接在结束标记前关闭".to_string(),
+            }
+            .into());
+        }
+
         // 组装最终 ChatResponse
-        let tool_calls: Vec<ToolCall> = tool_calls_acc
-            .into_iter()
-            .filter(|(id, name, _)| !id.is_empty() || !name.is_empty())
-            .map(|(id, name, args)| ToolCall {
-                id,
-                name,
-                arguments: serde_json::from_str(&args)
-                    .unwrap_or(serde_json::Value::Object(serde_json::Map::new())),
-            })
-            .collect();
+        let tool_calls = tool_calls_acc.finish();
 
         let response = ChatResponse {
             text: if full_text.is_empty() {
@@ -396,9 +465,10 @@ impl Provider for CompatibleProvider {
                 Some(full_reasoning)
             },
             tool_calls,
+            provenance: None,
         };
 
-        let _ = tx.send(StreamEvent::Done(response.clone())).await;
+        sink.send(StreamEvent::Done(response.clone())).await;
 
         debug!(
             "流式响应完成: text_len={}, tool_calls={}",
@@ -452,6 +522,9 @@ struct SSEStreamResponse {
 #[derive(Debug, Deserialize)]
 struct SSEStreamChoice {
     delta: SSEDelta,
+    /// 最后一个数据块携带（stop / tool_calls / length），其余为 null
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -486,6 +559,12 @@ mod tests {
             api_key: "test".to_string(),
             model: "deepseek-chat".to_string(),
             auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
         };
         let provider = CompatibleProvider::new(&config);
         assert_eq!(
@@ -501,6 +580,12 @@ mod tests {
             api_key: "test".to_string(),
             model: "gpt-4o".to_string(),
             auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
         };
         let provider = CompatibleProvider::new(&config);
         assert_eq!(
@@ -509,6 +594,116 @@ mod tests {
         );
     }
 
+    fn endpoint_for(base_url: &str, endpoint_path: Option<&str>) -> String {
+        let config = ProviderConfig {
+            base_url: base_url.to_string(),
+            api_key: "test".to_string(),
+            model: "m".to_string(),
+            auth_style: None,
+            headers: Default::default(),
+            endpoint_path: endpoint_path.map(str::to_string),
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        };
+        CompatibleProvider::new(&config).endpoint()
+    }
+
+    fn provider_with_max_tokens(max_tokens: Option<u32>) -> CompatibleProvider {
+        CompatibleProvider::new(&ProviderConfig {
+            base_url: "https://api.deepseek.com/v1".to_string(),
+            api_key: "test".to_string(),
+            model: "deepseek-chat".to_string(),
+            auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens,
+        })
+    }
+
+    #[test]
+    fn max_tokens_sent_only_when_configured() {
+        let body = provider_with_max_tokens(None).build_request_body(
+            &[],
+            &[],
+            "deepseek-chat",
+            0.7.into(),
+            false,
+        );
+        assert!(body.get("max_tokens").is_none(), "{}", body);
+
+        let body = provider_with_max_tokens(Some(1024)).build_request_body(
+            &[],
+            &[],
+            "deepseek-chat",
+            0.7.into(),
+            true,
+        );
+        assert_eq!(body["max_tokens"], 1024);
+    }
+
+    #[test]
+    fn session_params_override_config_in_body() {
+        let params = GenerationParams {
+            temperature: 1.3,
+            top_p: Some(0.8),
+            max_tokens: Some(256),
+        };
+        let body =
+            provider_with_max_tokens(Some(1024)).build_request_body(&[], &[], "m", params, false);
+        assert_eq!(body["temperature"], 1.3);
+        assert_eq!(body["top_p"], 0.8);
+        assert_eq!(body["max_tokens"], 256);
+
+        // 未覆盖 top_p 时不发送
+        let body =
+            provider_with_max_tokens(None).build_request_body(&[], &[], "m", 0.2.into(), false);
+        assert!(body.get("top_p").is_none(), "{}", body);
+    }
+
+    #[test]
+    fn endpoint_not_appended_twice() {
+        assert_eq!(
+            endpoint_for("https://gw.example.com/v1/chat/completions", None),
+            "https://gw.example.com/v1/chat/completions"
+        );
+        assert_eq!(
+            endpoint_for("https://gw.example.com/v1/chat/completions/", None),
+            "https://gw.example.com/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn endpoint_path_override() {
+        assert_eq!(
+            endpoint_for(
+                "https://gw.example.com/openai/",
+                Some("/deployments/gpt/chat")
+            ),
+            "https://gw.example.com/openai/deployments/gpt/chat"
+        );
+        assert_eq!(
+            endpoint_for("https://gw.example.com/v1", Some("responses")),
+            "https://gw.example.com/v1/responses"
+        );
+        assert_eq!(
+            endpoint_for(
+                "https://ignored.example.com",
+                Some("https://other.example.com/api/chat")
+            ),
+            "https://other.example.com/api/chat"
+        );
+        // 空字符串视为未设置
+        assert_eq!(
+            endpoint_for("https://api.example.com/v1", Some("")),
+            "https://api.example.com/v1/chat/completions"
+        );
+    }
+
     #[test]
     fn build_messages_chat() {
         let msgs = vec![
@@ -516,11 +711,13 @@ mod tests {
                 role: "system".to_string(),
                 content: "You are helpful.".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
             ConversationMessage::Chat(ChatMessage {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
         ];
         let built = CompatibleProvider::build_messages(&msgs);
@@ -540,10 +737,12 @@ mod tests {
                     name: "shell".to_string(),
                     arguments: serde_json::json!({"command": "ls"}),
                 }],
+                turn: 0,
             },
             ConversationMessage::ToolResult {
                 tool_call_id: "call_1".to_string(),
                 content: "file1.txt\nfile2.txt".to_string(),
+                turn: 0,
             },
         ];
         let built = CompatibleProvider::build_messages(&msgs);
@@ -668,6 +867,7 @@ mod tests {
             role: "assistant".to_string(),
             content: "回答".to_string(),
             reasoning_content: Some("我的思考过程".to_string()),
+            turn: 0,
         })];
         let built = CompatibleProvider::build_messages(&msgs);
         assert_eq!(built[0]["reasoning_content"], "我的思考过程");
@@ -679,6 +879,7 @@ mod tests {
             role: "assistant".to_string(),
             content: "回答".to_string(),
             reasoning_content: None,
+            turn: 0,
         })];
         let built = CompatibleProvider::build_messages(&msgs);
         // reasoning_content 为 None 时不应出现在 JSON 中
@@ -692,6 +893,7 @@ mod tests {
             role: "user".to_string(),
             content: "你好".to_string(),
             reasoning_content: None,
+            turn: 0,
         })];
         let built = CompatibleProvider::build_messages(&msgs);
         assert!(built[0].get("reasoning_content").is_none());
@@ -707,6 +909,7 @@ mod tests {
                 name: "shell".to_string(),
                 arguments: serde_json::json!({"command": "date"}),
             }],
+            turn: 0,
         }];
         let built = CompatibleProvider::build_messages(&msgs);
         assert_eq!(
@@ -726,9 +929,122 @@ mod tests {
                 name: "shell".to_string(),
                 arguments: serde_json::json!({"command": "ls"}),
             }],
+            turn: 0,
         }];
         let built = CompatibleProvider::build_messages(&msgs);
         // 无 reasoning_content 时不应包含该字段
         assert!(built[0].get("reasoning_content").is_none());
     }
+
+    #[tokio::test]
+    async fn custom_headers_sent_with_request() {
+        let (base_url, server) = crate::providers::test_server::capture_one(
+            r#"{"choices":[{"message":{"content":"ok"}}]}"#,
+        )
+        .await;
+        let config = ProviderConfig {
+            base_url,
+            api_key: "sk-test".to_string(),
+            model: "m".to_string(),
+            auth_style: None,
+            headers: [
+                ("X-Org-Id".to_string(), "org-42".to_string()),
+                ("X-Route-Key".to_string(), "blue".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        };
+        let provider = CompatibleProvider::new(&config);
+        let resp = provider
+            .chat_with_tools(
+                &[ConversationMessage::Chat(ChatMessage {
+                    role: "user".to_string(),
+                    content: "hi".to_string(),
+                    reasoning_content: None,
+                    turn: 0,
+                })],
+                &[],
+                "m",
+                0.7.into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.text.as_deref(), Some("ok"));
+
+        let head = server.await.unwrap().to_lowercase();
+        assert!(head.starts_with("post /chat/completions"), "{}", head);
+        assert!(head.contains("x-org-id: org-42"), "{}", head);
+        assert!(head.contains("x-route-key: blue"), "{}", head);
+    }
+
+    #[tokio::test]
+    async fn stream_announces_tool_before_arguments_finish() {
+        const SSE: &str = concat!(
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"file_write\",\"arguments\":\"\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"path\\\": \\\"a.md\\\", \"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"content\\\": \\\"hel\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"lo\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let (base_url, server) = crate::providers::test_server::capture_one(SSE).await;
+        let config = ProviderConfig {
+            base_url,
+            api_key: "sk-test".to_string(),
+            model: "m".to_string(),
+            auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        };
+        let (tx, mut rx) = mpsc::channel(64);
+        let resp = CompatibleProvider::new(&config)
+            .chat_stream(&[], &[], "m", 0.7.into(), tx)
+            .await
+            .unwrap();
+        server.await.unwrap();
+
+        assert_eq!(resp.tool_calls.len(), 1);
+        assert_eq!(
+            resp.tool_calls[0].arguments,
+            serde_json::json!({ "path": "a.md", "content": "hello" })
+        );
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        let preparing = events.iter().position(|e| {
+            matches!(e, StreamEvent::ToolStatus { name, status: ToolStatusKind::Preparing } if name == "file_write")
+        });
+        let first_delta = events
+            .iter()
+            .position(|e| matches!(e, StreamEvent::ToolCallDelta { .. }));
+        assert!(preparing.unwrap() < first_delta.unwrap(), "{:?}", events);
+        let preparing_count = events
+            .iter()
+            .filter(|e| matches!(e, StreamEvent::ToolStatus { .. }))
+            .count();
+        assert_eq!(preparing_count, 1);
+    }
+
+    #[test]
+    fn invalid_custom_headers_are_skipped() {
+        let headers = [
+            ("bad header".to_string(), "v".to_string()),
+            ("X-Ok".to_string(), "1".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let map = crate::providers::custom_header_map(&headers);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get("x-ok").unwrap(), "1");
+    }
 }
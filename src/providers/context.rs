@@ -0,0 +1,146 @@
+//! 模型上下文窗口查表
+//!
+//! 各家 API 都不提供查询上下文长度的接口，这里按模型名前缀维护一张表，
+//! 查不到时再按 Provider 名取该家族的保守默认值。配置中的 `context_window` 优先。
+
+use crate::config::ProviderConfig;
+
+/// 查表与配置都没有结果时使用的窗口（按较小的常见值保守估计）
+pub const DEFAULT_CONTEXT_WINDOW: usize = 32_768;
+
+/// 模型名前缀 → 上下文窗口（tokens）。按顺序匹配，更具体的前缀在前
+const MODEL_WINDOWS: &[(&str, usize)] = &[
+    // Anthropic
+    ("claude-", 200_000),
+    // OpenAI
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("gpt-5", 400_000),
+    ("o1-mini", 128_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    // DeepSeek
+    ("deepseek-", 128_000),
+    // 智谱
+    ("glm-4-long", 1_000_000),
+    ("glm-4-flash", 128_000),
+    ("glm-4.5", 128_000),
+    ("glm-4.6", 200_000),
+    ("glm-4.7", 200_000),
+    ("glm-4", 128_000),
+    // MiniMax
+    ("minimax-", 1_000_000),
+    // Moonshot / Kimi
+    ("moonshot-v1-8k", 8_192),
+    ("moonshot-v1-32k", 32_768),
+    ("moonshot-v1-128k", 131_072),
+    ("kimi-", 131_072),
+    // 通义千问
+    ("qwen-long", 10_000_000),
+    ("qwen-max", 32_768),
+    ("qwen-plus", 131_072),
+    ("qwen-turbo", 1_000_000),
+    // Google
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-", 1_048_576),
+];
+
+/// Provider 名（config.toml 中的 key）→ 该家族默认窗口，模型名查不到时使用
+const PROVIDER_WINDOWS: &[(&str, usize)] = &[
+    ("claude", 200_000),
+    ("anthropic", 200_000),
+    ("gpt", 128_000),
+    ("openai", 128_000),
+    ("deepseek", 128_000),
+    ("glm", 128_000),
+    ("zhipu", 128_000),
+    ("minimax", 1_000_000),
+    ("moonshot", 131_072),
+    ("kimi", 131_072),
+    ("gemini", 1_048_576),
+];
+
+/// 按 Provider 名与模型名估计上下文窗口（tokens），未收录时返回 None
+///
+/// 模型名匹配不区分大小写，并忽略 `org/` 前缀（如 `deepseek-ai/deepseek-chat`）。
+pub fn context_window(provider: &str, model: &str) -> Option<usize> {
+    let model = model.to_lowercase();
+    let model = model.rsplit('/').next().unwrap_or(&model);
+    MODEL_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .or_else(|| {
+            let provider = provider.to_lowercase();
+            PROVIDER_WINDOWS.iter().find(|(name, _)| provider == *name)
+        })
+        .map(|(_, window)| *window)
+}
+
+/// 配置优先，其次查表，最后取 `DEFAULT_CONTEXT_WINDOW`
+pub fn resolve_context_window(provider: &str, config: &ProviderConfig, model: &str) -> usize {
+    config
+        .context_window
+        .or_else(|| context_window(provider, model))
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_models_return_their_windows() {
+        assert_eq!(
+            context_window("claude", "claude-sonnet-4-5-20250929"),
+            Some(200_000)
+        );
+        assert_eq!(context_window("gpt", "gpt-4o-mini"), Some(128_000));
+        assert_eq!(context_window("gpt", "gpt-4"), Some(8_192));
+        assert_eq!(context_window("gpt", "o1-mini"), Some(128_000));
+        assert_eq!(
+            context_window("deepseek", "deepseek-reasoner"),
+            Some(128_000)
+        );
+        assert_eq!(context_window("glm", "glm-4-long"), Some(1_000_000));
+        assert_eq!(context_window("minimax", "MiniMax-M2.5"), Some(1_000_000));
+        // 网关常见的 org/model 写法
+        assert_eq!(
+            context_window("gateway", "deepseek-ai/DeepSeek-Chat"),
+            Some(128_000)
+        );
+    }
+
+    #[test]
+    fn unknown_models_fall_back_to_provider_family_then_none() {
+        assert_eq!(context_window("deepseek", "ds-v4-preview"), Some(128_000));
+        assert_eq!(context_window("claude", "some-proxy-alias"), Some(200_000));
+        assert_eq!(context_window("local", "llama3:8b"), None);
+    }
+
+    #[test]
+    fn config_override_wins() {
+        let mut config = ProviderConfig {
+            base_url: "http://localhost:11434/v1".to_string(),
+            api_key: "k".to_string(),
+            model: "llama3:8b".to_string(),
+            auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        };
+        assert_eq!(
+            resolve_context_window("local", &config, "llama3:8b"),
+            DEFAULT_CONTEXT_WINDOW
+        );
+        config.context_window = Some(8_192);
+        assert_eq!(resolve_context_window("local", &config, "llama3:8b"), 8_192);
+        assert_eq!(resolve_context_window("gpt", &config, "gpt-4o"), 8_192);
+    }
+}
@@ -0,0 +1,243 @@
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+
+use crate::config::ProviderConfig;
+
+use super::params::GenerationParams;
+use super::traits::{ChatResponse, ConversationMessage, Provider, ToolCall, ToolSpec};
+
+/// 触发工具调用的消息前缀：`tool:<name> [json 参数]`
+const TOOL_PREFIX: &str = "tool:";
+
+/// 离线 Echo Provider（无需网络与 API Key）
+///
+/// 行为完全确定，便于在无网络环境下演示 CLI / Routine / 工具：
+/// - 回显最后一条 user 消息：`[echo] <内容>`
+/// - 最后一条 user 消息形如 `tool:<name> {json}` 且该工具在本轮可用时，发起一次 tool call
+/// - 对话末尾是工具结果时，回显工具输出并结束本轮
+///
+/// `base_url` 可写成 `echo://<tool>`，表示每条消息都以 `{"input": <消息>}` 调用该工具。
+pub struct EchoProvider {
+    /// 配置的默认工具（来自 `base_url = "echo://<tool>"`）
+    tool: Option<String>,
+}
+
+impl EchoProvider {
+    pub fn new(config: &ProviderConfig) -> Self {
+        let tool = config
+            .base_url
+            .strip_prefix("echo://")
+            .map(|s| s.trim_matches('/').to_string())
+            .filter(|s| !s.is_empty());
+        Self { tool }
+    }
+
+    /// 最后一条 user 消息内容
+    fn last_user_message(messages: &[ConversationMessage]) -> Option<&str> {
+        messages.iter().rev().find_map(|m| match m {
+            ConversationMessage::Chat(c) if c.role == "user" => Some(c.content.as_str()),
+            _ => None,
+        })
+    }
+
+    /// 解析 `tool:<name> [json]` 形式的工具调用请求
+    fn parse_tool_request(&self, input: &str) -> Option<(String, serde_json::Value)> {
+        if let Some(rest) = input.trim().strip_prefix(TOOL_PREFIX) {
+            let rest = rest.trim();
+            let (name, args) = match rest.split_once(char::is_whitespace) {
+                Some((name, args)) => (name, args.trim()),
+                None => (rest, ""),
+            };
+            if name.is_empty() {
+                return None;
+            }
+            let args = if args.is_empty() {
+                serde_json::json!({})
+            } else {
+                serde_json::from_str(args).unwrap_or_else(|_| serde_json::json!({ "input": args }))
+            };
+            return Some((name.to_string(), args));
+        }
+        self.tool
+            .as_ref()
+            .map(|name| (name.clone(), serde_json::json!({ "input": input })))
+    }
+}
+
+#[async_trait]
+impl Provider for EchoProvider {
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        _model: &str,
+        _params: GenerationParams,
+    ) -> Result<ChatResponse> {
+        // 工具已执行完毕：回显工具结果，结束本轮
+        if let Some(ConversationMessage::ToolResult { content, .. }) = messages.last() {
+            return Ok(ChatResponse {
+                text: Some(format!("[echo] tool result:\n{}", content)),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            });
+        }
+
+        let input = Self::last_user_message(messages).unwrap_or_default();
+
+        if let Some((name, arguments)) = self.parse_tool_request(input) {
+            if tools.iter().any(|t| t.name == name) {
+                return Ok(ChatResponse {
+                    text: None,
+                    reasoning_content: None,
+                    tool_calls: vec![ToolCall {
+                        id: format!("echo_call_{}", messages.len()),
+                        name,
+                        arguments,
+                    }],
+                    provenance: None,
+                });
+            }
+        }
+
+        Ok(ChatResponse {
+            text: Some(format!("[echo] {}", input)),
+            reasoning_content: None,
+            tool_calls: vec![],
+            provenance: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::traits::ChatMessage;
+
+    fn echo_config(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            base_url: base_url.to_string(),
+            api_key: String::new(),
+            model: "echo".to_string(),
+            auth_style: Some("echo".to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        }
+    }
+
+    fn user(content: &str) -> ConversationMessage {
+        ConversationMessage::Chat(ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+            reasoning_content: None,
+            turn: 0,
+        })
+    }
+
+    fn shell_spec() -> ToolSpec {
+        ToolSpec {
+            name: "shell".to_string(),
+            description: "run".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        }
+    }
+
+    #[tokio::test]
+    async fn echoes_last_user_message() {
+        let provider: Box<dyn Provider> = Box::new(EchoProvider::new(&echo_config("")));
+        let messages = vec![
+            ConversationMessage::Chat(ChatMessage {
+                role: "system".to_string(),
+                content: "sys".to_string(),
+                reasoning_content: None,
+                turn: 0,
+            }),
+            user("first"),
+            user("hello"),
+        ];
+        let resp = provider
+            .chat_with_tools(&messages, &[], "echo", 0.7.into())
+            .await
+            .unwrap();
+        assert_eq!(resp.text.as_deref(), Some("[echo] hello"));
+        assert!(resp.tool_calls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn tool_prefix_invokes_available_tool() {
+        let provider = EchoProvider::new(&echo_config(""));
+        let resp = provider
+            .chat_with_tools(
+                &[user(r#"tool:shell {"command": "ls"}"#)],
+                &[shell_spec()],
+                "echo",
+                0.7.into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.tool_calls.len(), 1);
+        assert_eq!(resp.tool_calls[0].name, "shell");
+        assert_eq!(resp.tool_calls[0].arguments["command"], "ls");
+    }
+
+    #[tokio::test]
+    async fn tool_prefix_unknown_tool_falls_back_to_echo() {
+        let provider = EchoProvider::new(&echo_config(""));
+        let resp = provider
+            .chat_with_tools(&[user("tool:missing")], &[shell_spec()], "echo", 0.7.into())
+            .await
+            .unwrap();
+        assert!(resp.tool_calls.is_empty());
+        assert_eq!(resp.text.as_deref(), Some("[echo] tool:missing"));
+    }
+
+    #[tokio::test]
+    async fn configured_tool_from_base_url() {
+        let provider = EchoProvider::new(&echo_config("echo://shell"));
+        let resp = provider
+            .chat_with_tools(&[user("pwd")], &[shell_spec()], "echo", 0.7.into())
+            .await
+            .unwrap();
+        assert_eq!(resp.tool_calls.len(), 1);
+        assert_eq!(resp.tool_calls[0].arguments["input"], "pwd");
+    }
+
+    #[tokio::test]
+    async fn tool_result_is_echoed_and_ends_turn() {
+        let provider = EchoProvider::new(&echo_config("echo://shell"));
+        let messages = vec![
+            user("pwd"),
+            ConversationMessage::AssistantToolCalls {
+                text: None,
+                reasoning_content: None,
+                tool_calls: vec![],
+                turn: 0,
+            },
+            ConversationMessage::ToolResult {
+                tool_call_id: "echo_call_1".to_string(),
+                content: "/tmp".to_string(),
+                turn: 0,
+            },
+        ];
+        let resp = provider
+            .chat_with_tools(&messages, &[shell_spec()], "echo", 0.7.into())
+            .await
+            .unwrap();
+        assert!(resp.tool_calls.is_empty());
+        assert!(resp.text.unwrap().contains("/tmp"));
+    }
+
+    #[tokio::test]
+    async fn create_provider_selects_echo() {
+        let provider = crate::providers::create_provider(&echo_config(""));
+        let resp = provider
+            .chat_with_tools(&[user("ping")], &[], "echo", 0.7.into())
+            .await
+            .unwrap();
+        assert_eq!(resp.text.as_deref(), Some("[echo] ping"));
+    }
+}
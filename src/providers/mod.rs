@@ -1,20 +1,252 @@
 pub mod claude;
 pub mod compatible;
+pub mod context;
+pub mod echo;
+pub mod offline;
+pub mod params;
+pub mod preflight;
+pub mod recording;
 pub mod reliable;
+pub mod stream_sink;
+pub mod tool_stream;
 pub mod traits;
 
+pub use context::{context_window, resolve_context_window, DEFAULT_CONTEXT_WINDOW};
+pub use offline::OfflineState;
+pub use params::GenerationParams;
 pub use reliable::{ReliableProvider, RetryConfig};
+pub use stream_sink::{stream_channel, EventSink, StreamStats, STREAM_CHANNEL_CAPACITY};
 pub use traits::{
-    ChatMessage, ChatResponse, ConversationMessage, Provider, StreamEvent, ToolCall, ToolSpec,
-    ToolStatusKind,
+    ChatMessage, ChatResponse, ConversationMessage, Provenance, Provider, StreamEvent,
+    StreamInterrupted, ToolCall, ToolSpec, ToolStatusKind,
 };
 
+use std::collections::HashMap;
+
 use crate::config::ProviderConfig;
 
 /// 根据配置创建 Provider 实例
 pub fn create_provider(config: &ProviderConfig) -> Box<dyn Provider> {
     match config.auth_style.as_deref() {
         Some("x-api-key") => Box::new(claude::ClaudeProvider::new(config)),
+        Some("echo") => Box::new(echo::EchoProvider::new(config)),
         _ => Box::new(compatible::CompatibleProvider::new(config)),
     }
 }
+
+/// OpenAI function calling 的工具数量上限（DeepSeek 等兼容 API 相同）
+const OPENAI_MAX_TOOLS: usize = 128;
+
+/// Provider 对请求中 tools 数组的限制
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolLimits {
+    /// 最多携带的工具数（None = 不限制）
+    pub max_tools: Option<usize>,
+    /// tools 序列化后的最大字节数（None = 不限制）
+    pub max_tool_schema_bytes: Option<usize>,
+}
+
+impl ToolLimits {
+    /// 配置优先；未配置时按协议取默认值
+    ///
+    /// - OpenAI 兼容协议：最多 128 个工具（OpenAI 官方上限）
+    /// - Claude / Echo：无已知硬性上限
+    pub fn for_provider(config: &ProviderConfig) -> Self {
+        let default_max_tools = match config.auth_style.as_deref() {
+            Some("x-api-key") | Some("echo") => None,
+            _ => Some(OPENAI_MAX_TOOLS),
+        };
+        Self {
+            max_tools: config.max_tools.or(default_max_tools),
+            max_tool_schema_bytes: config.max_tool_schema_bytes,
+        }
+    }
+}
+
+/// 判断错误是否为 Provider 拒绝了过多 / 过大的 tools
+pub fn is_too_many_tools_error(err_str: &str) -> bool {
+    let lower = err_str.to_lowercase();
+    [
+        "too many functions",
+        "too many tools",
+        "maximum number of tools",
+        "maximum number of functions",
+        "array too long",
+        "tools: array",
+        "functions: array",
+    ]
+    .iter()
+    .any(|k| lower.contains(k))
+}
+
+/// 判断错误是否为请求超出了模型的上下文窗口（history + system prompt 过长）
+pub fn is_context_overflow_error(err_str: &str) -> bool {
+    let lower = err_str.to_lowercase();
+    [
+        "context_length_exceeded",
+        "maximum context length",
+        "context length",
+        "context window",
+        "prompt is too long",
+        "input is too long",
+        "reduce the length of the messages",
+        "exceeds the model's maximum",
+        "model_context_window_exceeded",
+    ]
+    .iter()
+    .any(|k| lower.contains(k))
+}
+
+/// 将配置中的自定义 headers 转为 HeaderMap
+///
+/// 与 MCP SSE 的 headers 处理一致：名称或值非法的条目跳过（记录警告），不影响请求。
+pub(crate) fn custom_header_map(headers: &HashMap<String, String>) -> reqwest::header::HeaderMap {
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+    let mut map = HeaderMap::new();
+    for (k, v) in headers {
+        match (
+            HeaderName::from_bytes(k.as_bytes()),
+            HeaderValue::from_str(v),
+        ) {
+            (Ok(name), Ok(value)) => {
+                map.insert(name, value);
+            }
+            _ => tracing::warn!("忽略非法的 Provider 自定义 header: {}", k),
+        }
+    }
+    map
+}
+
+/// 测试辅助：单次请求的本地 mock HTTP server
+#[cfg(test)]
+pub(crate) mod test_server {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// 启动只处理一个请求的 mock server，返回 (base_url, 原始请求头)
+    ///
+    /// 响应固定为 200 + `response_body`（JSON）。
+    pub async fn capture_one(
+        response_body: &'static str,
+    ) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            // 读到请求头结束，再按 Content-Length 读完请求体
+            let head_end = loop {
+                let n = stream.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+                if n == 0 {
+                    break buf.len();
+                }
+            };
+            let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+            let content_length = head
+                .lines()
+                .find_map(|l| {
+                    let (k, v) = l.split_once(':')?;
+                    k.eq_ignore_ascii_case("content-length")
+                        .then(|| v.trim().parse::<usize>().ok())
+                        .flatten()
+                })
+                .unwrap_or(0);
+            while buf.len() < head_end + content_length {
+                let n = stream.read(&mut chunk).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response_body.len(),
+                response_body
+            );
+            stream.write_all(resp.as_bytes()).await.unwrap();
+            head
+        });
+        (base_url, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider_config(auth_style: Option<&str>) -> ProviderConfig {
+        ProviderConfig {
+            base_url: "https://api.example.com/v1".to_string(),
+            api_key: "k".to_string(),
+            model: "m".to_string(),
+            auth_style: auth_style.map(str::to_string),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        }
+    }
+
+    #[test]
+    fn tool_limits_defaults_per_protocol() {
+        assert_eq!(
+            ToolLimits::for_provider(&provider_config(None)).max_tools,
+            Some(128)
+        );
+        assert_eq!(
+            ToolLimits::for_provider(&provider_config(Some("x-api-key"))),
+            ToolLimits::default()
+        );
+
+        let config = ProviderConfig {
+            max_tools: Some(40),
+            max_tool_schema_bytes: Some(32000),
+            context_window: None,
+            max_tokens: None,
+            ..provider_config(Some("x-api-key"))
+        };
+        assert_eq!(
+            ToolLimits::for_provider(&config),
+            ToolLimits {
+                max_tools: Some(40),
+                max_tool_schema_bytes: Some(32000),
+            }
+        );
+    }
+
+    #[test]
+    fn too_many_tools_errors_detected() {
+        assert!(is_too_many_tools_error(
+            "API 返回错误 400: Invalid 'tools': array too long. Expected an array with maximum length 128"
+        ));
+        assert!(is_too_many_tools_error("Too many functions provided"));
+        assert!(!is_too_many_tools_error(
+            "API 返回错误 401: invalid api key"
+        ));
+        assert!(!is_too_many_tools_error("error sending request"));
+    }
+
+    #[test]
+    fn context_overflow_errors_detected() {
+        assert!(is_context_overflow_error(
+            "API 返回错误 400: This model's maximum context length is 65536 tokens. However, you requested 70123 tokens"
+        ));
+        assert!(is_context_overflow_error(
+            r#"API 返回错误 400: {"error":{"code":"context_length_exceeded"}}"#
+        ));
+        assert!(is_context_overflow_error(
+            "API 返回错误 400: prompt is too long: 210000 tokens > 200000 maximum"
+        ));
+        assert!(!is_context_overflow_error("API 返回错误 429: rate limited"));
+        assert!(!is_too_many_tools_error(
+            "maximum context length is 8192 tokens"
+        ));
+    }
+}
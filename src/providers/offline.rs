@@ -0,0 +1,329 @@
+//! 离线状态检测
+//!
+//! ReliableProvider 在重试耗尽且错误属于网络类时标记离线，之后的请求直接快速失败，
+//! 不再走完整退避流程。后台探测任务（或 `/offline probe`）恢复连通后清除标记，
+//! 并唤醒等待联网的 Routine 重放任务。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+/// 探测单次 TCP 连接超时
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 离线状态快照（用于 `/offline` 展示）
+#[derive(Debug, Clone)]
+pub struct OfflineStatus {
+    pub offline: bool,
+    /// 进入离线状态的时间（RFC 3339）
+    pub since: Option<String>,
+    /// 触发离线的最后一个网络错误
+    pub last_error: Option<String>,
+}
+
+/// 共享离线标记
+///
+/// 默认使用进程级全局实例（`OfflineState::global()`），所有 ReliableProvider
+/// 与 RoutineEngine 共享；测试可构造独立实例避免互相干扰。
+#[derive(Default)]
+pub struct OfflineState {
+    offline: AtomicBool,
+    detail: Mutex<(Option<String>, Option<String>)>,
+    online_notify: Notify,
+}
+
+impl OfflineState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 进程级共享实例
+    pub fn global() -> Arc<OfflineState> {
+        static GLOBAL: OnceLock<Arc<OfflineState>> = OnceLock::new();
+        GLOBAL.get_or_init(|| Arc::new(OfflineState::new())).clone()
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::Relaxed)
+    }
+
+    /// 标记离线（记录触发错误）
+    pub fn mark_offline(&self, error: &str) {
+        let was_offline = self.offline.swap(true, Ordering::Relaxed);
+        let mut detail = self.detail.lock().unwrap();
+        if !was_offline {
+            warn!("网络不可达，进入离线模式: {}", error);
+            detail.0 = Some(chrono::Utc::now().to_rfc3339());
+        }
+        detail.1 = Some(error.chars().take(200).collect());
+    }
+
+    /// 清除离线标记，并唤醒所有等待联网的任务
+    pub fn mark_online(&self) {
+        if self.offline.swap(false, Ordering::Relaxed) {
+            info!("网络已恢复，退出离线模式");
+            *self.detail.lock().unwrap() = (None, None);
+            self.online_notify.notify_waiters();
+        }
+    }
+
+    pub fn status(&self) -> OfflineStatus {
+        let detail = self.detail.lock().unwrap();
+        OfflineStatus {
+            offline: self.is_offline(),
+            since: detail.0.clone(),
+            last_error: detail.1.clone(),
+        }
+    }
+
+    /// 等待下一次“离线 → 在线”切换
+    pub async fn wait_online_transition(&self) {
+        self.online_notify.notified().await;
+    }
+
+    /// 下一次“离线 → 在线”切换的通知
+    ///
+    /// 先 `enable()` 再检查状态：检查之后、开始等待之前发生的切换不会漏掉。
+    pub fn online_transition(&self) -> Notified<'_> {
+        self.online_notify.notified()
+    }
+
+    /// 主动探测 base_url 是否可达（TCP 连接），可达则清除离线标记
+    ///
+    /// 返回探测结果；无法解析出主机（如 `echo://`）视为可达。
+    pub async fn probe(&self, base_url: &str) -> bool {
+        let reachable = probe_url(base_url).await;
+        if reachable {
+            self.mark_online();
+        }
+        reachable
+    }
+}
+
+/// 后台探测间隔
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 启动后台探测任务：离线期间每隔 `interval` 探测一次
+pub fn spawn_probe_task(
+    state: Arc<OfflineState>,
+    base_url: String,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    spawn_probe_task_with(state, move || Some(base_url.clone()), interval)
+}
+
+/// 同 `spawn_probe_task`，每次探测时重新取 base_url
+///
+/// 用于配置可热重载的进程（daemon）：重载后默认 Provider 可能改变。
+/// 取不到 base_url（默认 Provider 未配置）时跳过本次探测。
+pub fn spawn_probe_task_with(
+    state: Arc<OfflineState>,
+    base_url: impl Fn() -> Option<String> + Send + 'static,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if state.is_offline() {
+                let Some(base_url) = base_url() else {
+                    continue;
+                };
+                let ok = state.probe(&base_url).await;
+                debug!(
+                    "离线探测 {}: {}",
+                    base_url,
+                    if ok { "可达" } else { "不可达" }
+                );
+            }
+        }
+    })
+}
+
+/// TCP 连接探测
+async fn probe_url(base_url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(base_url) else {
+        return true;
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return true;
+    };
+    matches!(
+        tokio::time::timeout(
+            PROBE_TIMEOUT,
+            tokio::net::TcpStream::connect((host.to_string(), port))
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
+/// 判断错误是否属于网络不可达类（DNS / 连接失败 / 连接被拒绝或重置）
+///
+/// 4xx/5xx 等服务端有响应的错误不算离线；请求已发出后的响应超时（慢但可达的 Provider）也不算，
+/// 因此不匹配泛化的 "timed out"。连接超时由 `is_network_failure` 通过 `reqwest::Error::is_connect()` 识别。
+pub fn is_network_error(err_str: &str) -> bool {
+    let lower = err_str.to_lowercase();
+    [
+        "connection refused",
+        "connection reset",
+        "failed to connect",
+        "tcp connect error",
+        "client error (connect)",
+        "dns error",
+        "failed to lookup address",
+        "network is unreachable",
+        "no route to host",
+    ]
+    .iter()
+    .any(|k| lower.contains(k))
+}
+
+/// 按错误链判断是否网络不可达（`ReliableProvider` 决定是否标记离线时使用）
+///
+/// 链上有 reqwest 错误时只认连接阶段的失败：`is_connect()`（DNS / 拒绝连接 / 连接超时）或连接被重置；
+/// 没有 reqwest 错误（如自定义 Provider 返回的文本错误）时退回 `is_network_error` 文本判断。
+pub fn is_network_failure(err: &color_eyre::Report) -> bool {
+    let mut from_reqwest = false;
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            if e.is_connect() {
+                return true;
+            }
+            from_reqwest = true;
+        }
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            use std::io::ErrorKind;
+            if matches!(
+                e.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+            ) {
+                return true;
+            }
+        }
+    }
+    !from_reqwest && is_network_error(&format!("{:#}", err))
+}
+
+/// 离线快速失败时返回给用户的提示
+pub fn offline_error_message() -> String {
+    if crate::config::Config::get_language().is_english() {
+        "Offline: the provider is unreachable. Local commands (/help, /routine, /identity ...) \
+         still work; use /offline probe to re-check connectivity."
+            .to_string()
+    } else {
+        "当前处于离线状态：Provider 无法连接。本地命令（/help、/routine、/identity 等）仍可使用，\
+         可用 /offline probe 重新检测网络。"
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_errors_detected() {
+        assert!(is_network_error(
+            "error sending request for url (https://api.x.com/v1/chat/completions): client error (Connect)"
+        ));
+        assert!(is_network_error(
+            "tcp connect error: Connection refused (os error 111)"
+        ));
+        assert!(is_network_error(
+            "dns error: failed to lookup address information"
+        ));
+        assert!(!is_network_error("API 返回错误 500: internal error"));
+        assert!(!is_network_error("401 Unauthorized"));
+        // 慢但可达的 Provider：响应超时不算离线
+        assert!(!is_network_error(
+            "error sending request for url (https://api.x.com/v1/chat/completions): operation timed out"
+        ));
+    }
+
+    #[tokio::test]
+    async fn reqwest_connect_failure_is_network_but_response_timeout_is_not() {
+        use color_eyre::eyre::WrapErr;
+
+        // 端口 1 通常无服务监听：连接阶段失败
+        let refused = reqwest::get("http://127.0.0.1:1/")
+            .await
+            .wrap_err("发送请求失败")
+            .unwrap_err();
+        assert!(is_network_failure(&refused), "{:#}", refused);
+
+        // 接受连接但从不响应：请求已发出后超时
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let timed_out = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap()
+            .get(format!("http://{}/", addr))
+            .send()
+            .await
+            .wrap_err("发送请求失败")
+            .unwrap_err();
+        assert!(!is_network_failure(&timed_out), "{:#}", timed_out);
+    }
+
+    #[test]
+    fn mark_offline_and_online() {
+        let state = OfflineState::new();
+        assert!(!state.is_offline());
+        state.mark_offline("connection refused");
+        let status = state.status();
+        assert!(status.offline);
+        assert!(status.since.is_some());
+        assert_eq!(status.last_error.as_deref(), Some("connection refused"));
+        state.mark_online();
+        assert!(!state.is_offline());
+        assert!(state.status().last_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn online_transition_wakes_waiters() {
+        let state = Arc::new(OfflineState::new());
+        state.mark_offline("timed out");
+        let waiter = {
+            let state = state.clone();
+            tokio::spawn(async move { state.wait_online_transition().await })
+        };
+        // 让 waiter 先注册
+        tokio::task::yield_now().await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        state.mark_online();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("mark_online 应唤醒等待者")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn probe_without_host_counts_as_reachable() {
+        let state = OfflineState::new();
+        state.mark_offline("timed out");
+        assert!(state.probe("echo://").await);
+        assert!(!state.is_offline());
+    }
+
+    #[tokio::test]
+    async fn probe_unreachable_port_stays_offline() {
+        let state = OfflineState::new();
+        state.mark_offline("connection refused");
+        // 端口 1 通常无服务监听，连接立即被拒绝
+        assert!(!state.probe("http://127.0.0.1:1").await);
+        assert!(state.is_offline());
+    }
+}
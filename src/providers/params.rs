@@ -0,0 +1,131 @@
+//! 生成参数：temperature / top_p / max_tokens
+//!
+//! 每次请求随消息一起传给 Provider（`Provider::chat_with_tools` / `chat_stream`）。
+//! 会话内可用 `/temp`、`/param set` 覆盖，`/param reset` 恢复配置默认值；
+//! `top_p` / `max_tokens` 为 None 时不覆盖，沿用 Provider 配置或 API 默认值。
+
+use color_eyre::eyre::{eyre, Result};
+
+/// temperature 允许的范围（OpenAI 兼容 API 与 Anthropic 的并集上限）
+pub const TEMPERATURE_RANGE: std::ops::RangeInclusive<f64> = 0.0..=2.0;
+
+/// 可通过 `/param set` 修改的参数名
+pub const PARAM_NAMES: &[&str] = &["temperature", "top_p", "max_tokens"];
+
+/// 单次请求的生成参数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationParams {
+    pub temperature: f64,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u32>,
+}
+
+impl GenerationParams {
+    /// 只指定 temperature，其余沿用 Provider 默认
+    pub fn with_temperature(temperature: f64) -> Self {
+        Self {
+            temperature,
+            top_p: None,
+            max_tokens: None,
+        }
+    }
+
+    /// 按名称设置参数（`/param set <name> <value>`），校验取值范围
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "temperature" | "temp" => self.temperature = parse_temperature(value)?,
+            "top_p" => {
+                let top_p: f64 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| eyre!("top_p 应为数字，当前为 '{}'", value))?;
+                if !(top_p > 0.0 && top_p <= 1.0) {
+                    return Err(eyre!("top_p 应在 (0, 1] 之间，当前为 {}", top_p));
+                }
+                self.top_p = Some(top_p);
+            }
+            "max_tokens" => {
+                let max_tokens: u32 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| eyre!("max_tokens 应为正整数，当前为 '{}'", value))?;
+                if max_tokens == 0 {
+                    return Err(eyre!("max_tokens 应为正整数，当前为 0"));
+                }
+                self.max_tokens = Some(max_tokens);
+            }
+            other => {
+                return Err(eyre!(
+                    "未知参数 '{}'，可用：{}",
+                    other,
+                    PARAM_NAMES.join(" / ")
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<f64> for GenerationParams {
+    fn from(temperature: f64) -> Self {
+        Self::with_temperature(temperature)
+    }
+}
+
+/// 解析 temperature（`/temp <value>`），限定在 [`TEMPERATURE_RANGE`] 内
+pub fn parse_temperature(value: &str) -> Result<f64> {
+    let temperature: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| eyre!("temperature 应为数字，当前为 '{}'", value))?;
+    if !TEMPERATURE_RANGE.contains(&temperature) {
+        return Err(eyre!(
+            "temperature 应在 {:.1}–{:.1} 之间，当前为 {}",
+            TEMPERATURE_RANGE.start(),
+            TEMPERATURE_RANGE.end(),
+            temperature
+        ));
+    }
+    Ok(temperature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_validates_each_parameter() {
+        let mut params = GenerationParams::with_temperature(0.7);
+        params.set("temperature", "1.5").unwrap();
+        params.set("top_p", "0.9").unwrap();
+        params.set("max_tokens", "2048").unwrap();
+        assert_eq!(
+            params,
+            GenerationParams {
+                temperature: 1.5,
+                top_p: Some(0.9),
+                max_tokens: Some(2048),
+            }
+        );
+
+        assert!(params.set("temperature", "2.5").is_err());
+        assert!(params.set("temperature", "-0.1").is_err());
+        assert!(params.set("top_p", "0").is_err());
+        assert!(params.set("top_p", "1.1").is_err());
+        assert!(params.set("max_tokens", "0").is_err());
+        assert!(params.set("max_tokens", "lots").is_err());
+        let err = params.set("top_k", "40").unwrap_err().to_string();
+        assert!(err.contains("temperature / top_p / max_tokens"), "{}", err);
+        // 校验失败不改动已有值
+        assert_eq!(params.temperature, 1.5);
+        assert_eq!(params.max_tokens, Some(2048));
+    }
+
+    #[test]
+    fn temperature_bounds_are_inclusive() {
+        assert_eq!(parse_temperature("0").unwrap(), 0.0);
+        assert_eq!(parse_temperature(" 2.0 ").unwrap(), 2.0);
+        assert!(parse_temperature("2.01").is_err());
+        assert!(parse_temperature("NaN").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! 启动预检：进入 REPL 前向默认 Provider 发一次极小的请求
+//!
+//! API Key 写错时，用户原本要等到发出第一条消息才看到一串难懂的报错；
+//! 预检把结果归为认证失败 / 网络不通 / 其它，打印一行明确的提示（`rrclaw agent --no-preflight` 跳过）。
+//! 直接使用未包装的 Provider：不重试、不回退，也不会把离线标记带进会话。
+
+use std::time::Duration;
+
+use super::{offline, ChatMessage, ConversationMessage, GenerationParams, Provider};
+use crate::i18n::Language;
+
+/// 预检整体超时
+pub const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 预检结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightOutcome {
+    Ok,
+    /// API Key 无效 / 无权限（401、403 等）
+    Auth(String),
+    /// 连接失败、DNS 错误或超时
+    Network(String),
+    /// 服务端有响应但返回了其它错误（模型名错误、5xx 等）
+    Other(String),
+}
+
+/// 发送一次 "ping" 并归类结果
+pub async fn check(provider: &dyn Provider, model: &str) -> PreflightOutcome {
+    let messages = [ConversationMessage::Chat(ChatMessage {
+        role: "user".to_string(),
+        content: "ping".to_string(),
+        reasoning_content: None,
+        turn: 0,
+    })];
+    match tokio::time::timeout(
+        PREFLIGHT_TIMEOUT,
+        provider.chat_with_tools(
+            &messages,
+            &[],
+            model,
+            GenerationParams::with_temperature(0.0),
+        ),
+    )
+    .await
+    {
+        Ok(Ok(_)) => PreflightOutcome::Ok,
+        Ok(Err(e)) => classify(&format!("{:#}", e)),
+        Err(_) => {
+            PreflightOutcome::Network(format!("timed out after {}s", PREFLIGHT_TIMEOUT.as_secs()))
+        }
+    }
+}
+
+/// 按错误文本归类（认证优先：网关可能把 401 包在连接错误信息里）
+pub fn classify(err: &str) -> PreflightOutcome {
+    let lower = err.to_lowercase();
+    let auth_markers = [
+        "401",
+        "403",
+        "unauthorized",
+        "forbidden",
+        "invalid_api_key",
+        "invalid api key",
+        "incorrect api key",
+        "authentication",
+    ];
+    if auth_markers.iter().any(|m| lower.contains(m)) {
+        PreflightOutcome::Auth(err.to_string())
+    } else if offline::is_network_error(err)
+        // 预检只发一条 ping：请求超时同样按"不可达"提示（不影响离线标记）
+        || ["error sending request", "timed out"]
+            .iter()
+            .any(|m| lower.contains(m))
+    {
+        PreflightOutcome::Network(err.to_string())
+    } else {
+        PreflightOutcome::Other(err.to_string())
+    }
+}
+
+/// 预检结果对应的提示（成功时为 None）
+pub fn message(provider: &str, outcome: &PreflightOutcome, lang: Language) -> Option<String> {
+    let english = lang.is_english();
+    let text = match outcome {
+        PreflightOutcome::Ok => return None,
+        PreflightOutcome::Auth(detail) if english => format!(
+            "Provider {} authentication failed — run /apikey to update the key. ({})",
+            provider, detail
+        ),
+        PreflightOutcome::Auth(detail) => format!(
+            "Provider {} 认证失败 — 请运行 /apikey 修改 API Key。（{}）",
+            provider, detail
+        ),
+        PreflightOutcome::Network(detail) if english => format!(
+            "Provider {} is unreachable — check the network or base URL (/apikey). ({})",
+            provider, detail
+        ),
+        PreflightOutcome::Network(detail) => format!(
+            "无法连接 Provider {} — 请检查网络或 Base URL（/apikey）。（{}）",
+            provider, detail
+        ),
+        PreflightOutcome::Other(detail) if english => {
+            format!("Provider {} pre-flight check failed: {}", provider, detail)
+        }
+        PreflightOutcome::Other(detail) => format!("Provider {} 预检失败: {}", provider, detail),
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::echo::EchoProvider;
+
+    #[test]
+    fn auth_and_network_errors_map_to_distinct_messages() {
+        let auth = classify("API 返回错误 401: {\"error\":{\"code\":\"invalid_api_key\"}}");
+        assert!(matches!(auth, PreflightOutcome::Auth(_)));
+        let text = message("deepseek", &auth, Language::English).unwrap();
+        assert!(text.starts_with("Provider deepseek authentication failed — run /apikey"));
+        let text = message("deepseek", &auth, Language::Chinese).unwrap();
+        assert!(text.contains("认证失败") && text.contains("/apikey"));
+        assert!(matches!(
+            classify("API 返回错误 403: Forbidden"),
+            PreflightOutcome::Auth(_)
+        ));
+
+        let network = classify("error sending request for url (https://api.x.com/v1/chat)");
+        assert!(matches!(network, PreflightOutcome::Network(_)));
+        let text = message("deepseek", &network, Language::English).unwrap();
+        assert!(text.starts_with("Provider deepseek is unreachable"));
+        assert!(!text.contains("authentication"));
+
+        let other = classify("API 返回错误 404: model not found");
+        assert!(matches!(other, PreflightOutcome::Other(_)));
+        assert!(message("deepseek", &other, Language::English)
+            .unwrap()
+            .contains("model not found"));
+
+        assert_eq!(
+            message("deepseek", &PreflightOutcome::Ok, Language::English),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn healthy_provider_passes() {
+        let provider = EchoProvider::new(&crate::config::ProviderConfig {
+            base_url: String::new(),
+            api_key: String::new(),
+            model: "echo".to_string(),
+            auth_style: Some("echo".to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        });
+        assert_eq!(check(&provider, "echo").await, PreflightOutcome::Ok);
+    }
+}
@@ -0,0 +1,681 @@
+//! 录制 / 回放 Provider 交互（开发调试用，VCR 风格）
+//!
+//! - `RecordingProvider`：包装真实 Provider，每次成功调用后把请求与响应追加到 cassette（JSONL，一行一次交互）
+//! - `ReplayProvider`：按录制顺序依次返回响应，不访问网络；同一 cassette 回放出的 Agent 输出与录制时一致
+//!
+//! 由 `[dev] mode` / `[dev] cassette` 选择（环境变量 `RRCLAW_DEV_MODE` / `RRCLAW_DEV_CASSETTE` 同样生效），
+//! `with_cassette()` 包在 ReliableProvider 外层：录制的是重试后的最终响应，回放不经过重试。
+//!
+//! 回放只按顺序对应，不校验请求；请求的最后一条消息与录制时不同说明对话已经分叉，记一条 warn。
+//! cassette 含完整对话内容，附到 bug 报告前请检查其中的敏感信息。
+//!
+//! 夹具目录（`rrclaw agent --record <dir>` / `--replay <dir>`，`tests/` 中的快照测试同样使用）：
+//! - `FixtureRecorder`：每次交互写一个 `<dir>/<key>.json`，key 为规范化请求的哈希
+//! - `FixtureReplayer`：按 key 查找响应，与调用顺序无关；未命中时报错（并记 warn），从不访问网络
+//!
+//! 规范化请求（`CanonicalRequest`）只保留模型与非 system 消息：system prompt 含当前时间、工作目录、
+//! 记忆等环境信息，工具结果只保留 tool_call_id（输出取决于本地环境）。
+//!
+//! 每次交互记录录制时的 `PROMPT_VERSION`；回放时版本不同只记 warn（模型行为可能因 prompt 改动而不同）。
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::params::GenerationParams;
+use super::traits::{ChatResponse, ConversationMessage, Provider, StreamEvent, ToolCall, ToolSpec};
+use crate::agent::prompts::PROMPT_VERSION;
+use crate::config::{CassetteMode, DevConfig};
+
+/// cassette 中的一次交互
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// 录制时的 prompt 模板版本（旧 cassette 没有此字段，为 0）
+    #[serde(default)]
+    pub prompt_version: u32,
+    pub request: RecordedRequest,
+    pub response: ChatResponse,
+}
+
+/// 录制的请求（工具只记名称，schema 不影响回放）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub model: String,
+    pub temperature: f64,
+    pub messages: Vec<ConversationMessage>,
+    pub tools: Vec<String>,
+}
+
+impl RecordedRequest {
+    fn new(
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> Self {
+        Self {
+            model: model.to_string(),
+            temperature,
+            messages: messages.to_vec(),
+            tools: tools.iter().map(|t| t.name.clone()).collect(),
+        }
+    }
+}
+
+/// 按 `[dev] mode` 包装 Provider（off 时原样返回）
+pub fn with_cassette(provider: Box<dyn Provider>, dev: &DevConfig) -> Result<Box<dyn Provider>> {
+    Ok(match dev.mode {
+        CassetteMode::Off => provider,
+        CassetteMode::Record => Box::new(RecordingProvider::create(provider, &dev.cassette)?),
+        CassetteMode::Replay => Box::new(ReplayProvider::load(&dev.cassette)?),
+    })
+}
+
+/// 录制每次交互的 Provider 包装
+pub struct RecordingProvider {
+    inner: Box<dyn Provider>,
+    path: PathBuf,
+    file: Mutex<std::fs::File>,
+}
+
+impl RecordingProvider {
+    /// 创建（覆盖）cassette 文件
+    pub fn create(inner: Box<dyn Provider>, path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("无法创建目录: {}", parent.display()))?;
+        }
+        let file = std::fs::File::create(path)
+            .wrap_err_with(|| format!("无法创建 cassette: {}", path.display()))?;
+        Ok(Self {
+            inner,
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// 追加一次交互；写入失败只记 warn，不影响本次对话
+    fn record(&self, request: RecordedRequest, response: &ChatResponse) {
+        let interaction = Interaction {
+            prompt_version: PROMPT_VERSION,
+            request,
+            response: response.clone(),
+        };
+        let result = serde_json::to_string(&interaction)
+            .map_err(Into::into)
+            .and_then(|line| {
+                let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+                writeln!(file, "{}", line)?;
+                file.flush()
+            });
+        if let Err(e) = result {
+            warn!("写入 cassette {} 失败: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for RecordingProvider {
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        params: GenerationParams,
+    ) -> Result<ChatResponse> {
+        let response = self
+            .inner
+            .chat_with_tools(messages, tools, model, params)
+            .await?;
+        self.record(
+            RecordedRequest::new(messages, tools, model, params.temperature),
+            &response,
+        );
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        params: GenerationParams,
+        tx: mpsc::Sender<StreamEvent>,
+    ) -> Result<ChatResponse> {
+        let response = self
+            .inner
+            .chat_stream(messages, tools, model, params, tx)
+            .await?;
+        self.record(
+            RecordedRequest::new(messages, tools, model, params.temperature),
+            &response,
+        );
+        Ok(response)
+    }
+}
+
+/// 按录制顺序回放的 Provider（流式调用走默认实现：整段文本一次发出）
+pub struct ReplayProvider {
+    path: PathBuf,
+    total: usize,
+    remaining: Mutex<VecDeque<Interaction>>,
+}
+
+impl ReplayProvider {
+    /// 读取 cassette；任何一行解析失败都报错（带行号）
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("无法读取 cassette: {}", path.display()))?;
+        let interactions = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str::<Interaction>(line).wrap_err_with(|| {
+                    format!("cassette {} 第 {} 行格式错误", path.display(), i + 1)
+                })
+            })
+            .collect::<Result<VecDeque<_>>>()?;
+        warn_prompt_version(path, interactions.iter().map(|i| i.prompt_version));
+        Ok(Self {
+            path: path.to_path_buf(),
+            total: interactions.len(),
+            remaining: Mutex::new(interactions),
+        })
+    }
+
+    /// 尚未回放的交互数
+    pub fn remaining(&self) -> usize {
+        self.remaining
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+#[async_trait]
+impl Provider for ReplayProvider {
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        _tools: &[ToolSpec],
+        _model: &str,
+        _params: GenerationParams,
+    ) -> Result<ChatResponse> {
+        let next = self
+            .remaining
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front();
+        let Some(interaction) = next else {
+            return Err(eyre!(
+                "cassette {} 的 {} 次交互已全部回放，本次请求超出录制范围",
+                self.path.display(),
+                self.total
+            ));
+        };
+        if !same_message(messages.last(), interaction.request.messages.last()) {
+            warn!(
+                "回放第 {} 次交互: 最后一条消息与录制时不同，对话可能已分叉",
+                self.total - self.remaining()
+            );
+        }
+        Ok(interaction.response)
+    }
+}
+
+/// 录制时的 prompt 版本与当前不同时记一条 warn（版本 0 为旧录制，不提示）
+fn warn_prompt_version(source: &Path, versions: impl IntoIterator<Item = u32>) {
+    let stale = versions
+        .into_iter()
+        .filter(|&v| v != 0 && v != PROMPT_VERSION)
+        .count();
+    if stale > 0 {
+        warn!(
+            "{} 中有 {} 次交互录制于其他 prompt 版本（当前 v{}），回放结果可能与现在的模型行为不同",
+            source.display(),
+            stale,
+            PROMPT_VERSION
+        );
+    }
+}
+
+fn same_message(a: Option<&ConversationMessage>, b: Option<&ConversationMessage>) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// 夹具目录模式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixtureMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// 按夹具目录模式包装 Provider（与 `with_cassette` 一样包在 ReliableProvider 外层）
+pub fn with_fixtures(provider: Box<dyn Provider>, mode: &FixtureMode) -> Result<Box<dyn Provider>> {
+    Ok(match mode {
+        FixtureMode::Record(dir) => Box::new(FixtureRecorder::create(provider, dir)?),
+        FixtureMode::Replay(dir) => Box::new(FixtureReplayer::load(dir)?),
+    })
+}
+
+/// 夹具文件内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    /// 录制时的 prompt 模板版本（旧夹具没有此字段，为 0）
+    #[serde(default)]
+    pub prompt_version: u32,
+    pub request: CanonicalRequest,
+    pub response: ChatResponse,
+}
+
+/// 计算夹具 key 用的规范化请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanonicalRequest {
+    pub model: String,
+    pub messages: Vec<CanonicalMessage>,
+}
+
+/// 规范化消息（turn 保留：路由请求中的用户消息 turn 为 0，借此与正式请求区分）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CanonicalMessage {
+    Chat {
+        role: String,
+        content: String,
+        turn: u64,
+    },
+    ToolCalls {
+        text: Option<String>,
+        calls: Vec<ToolCall>,
+        turn: u64,
+    },
+    ToolResult {
+        tool_call_id: String,
+        turn: u64,
+    },
+}
+
+impl CanonicalRequest {
+    pub fn new(messages: &[ConversationMessage], model: &str) -> Self {
+        let messages = messages
+            .iter()
+            .filter_map(|m| match m {
+                ConversationMessage::Chat(c) if c.role == "system" => None,
+                ConversationMessage::Chat(c) => Some(CanonicalMessage::Chat {
+                    role: c.role.clone(),
+                    content: c.content.clone(),
+                    turn: c.turn,
+                }),
+                ConversationMessage::AssistantToolCalls {
+                    text,
+                    tool_calls,
+                    turn,
+                    ..
+                } => Some(CanonicalMessage::ToolCalls {
+                    text: text.clone(),
+                    calls: tool_calls.clone(),
+                    turn: *turn,
+                }),
+                ConversationMessage::ToolResult {
+                    tool_call_id, turn, ..
+                } => Some(CanonicalMessage::ToolResult {
+                    tool_call_id: tool_call_id.clone(),
+                    turn: *turn,
+                }),
+            })
+            .collect();
+        Self {
+            model: model.to_string(),
+            messages,
+        }
+    }
+
+    /// 夹具 key：紧凑 JSON 的 FNV-1a 64 位哈希（跨平台、跨 Rust 版本稳定）
+    pub fn key(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        let hash = json.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    /// 最后一条用户消息（未命中时的错误提示用）
+    fn last_user_message(&self) -> &str {
+        self.messages
+            .iter()
+            .rev()
+            .find_map(|m| match m {
+                CanonicalMessage::Chat { role, content, .. } if role == "user" => {
+                    Some(content.as_str())
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// 把每次交互写成夹具文件的 Provider 包装
+pub struct FixtureRecorder {
+    inner: Box<dyn Provider>,
+    dir: PathBuf,
+}
+
+impl FixtureRecorder {
+    /// 创建夹具目录（已有的夹具保留，同 key 覆盖）
+    pub fn create(inner: Box<dyn Provider>, dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("无法创建夹具目录: {}", dir.display()))?;
+        Ok(Self {
+            inner,
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// 写入一个夹具；写入失败只记 warn，不影响本次对话
+    fn record(&self, request: CanonicalRequest, response: &ChatResponse) {
+        let path = self.dir.join(format!("{}.json", request.key()));
+        let fixture = Fixture {
+            prompt_version: PROMPT_VERSION,
+            request,
+            response: response.clone(),
+        };
+        let result = serde_json::to_string_pretty(&fixture)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(&path, json + "\n"));
+        if let Err(e) = result {
+            warn!("写入夹具 {} 失败: {}", path.display(), e);
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for FixtureRecorder {
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        params: GenerationParams,
+    ) -> Result<ChatResponse> {
+        let response = self
+            .inner
+            .chat_with_tools(messages, tools, model, params)
+            .await?;
+        self.record(CanonicalRequest::new(messages, model), &response);
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        params: GenerationParams,
+        tx: mpsc::Sender<StreamEvent>,
+    ) -> Result<ChatResponse> {
+        let response = self
+            .inner
+            .chat_stream(messages, tools, model, params, tx)
+            .await?;
+        self.record(CanonicalRequest::new(messages, model), &response);
+        Ok(response)
+    }
+}
+
+/// 按规范化请求查找夹具的回放 Provider（流式调用走默认实现）
+pub struct FixtureReplayer {
+    dir: PathBuf,
+    fixtures: HashMap<String, ChatResponse>,
+}
+
+impl FixtureReplayer {
+    /// 读取目录中所有 `*.json` 夹具；key 按文件内的请求重新计算，手工编辑过的夹具同样可用
+    pub fn load(dir: &Path) -> Result<Self> {
+        let entries = std::fs::read_dir(dir)
+            .wrap_err_with(|| format!("无法读取夹具目录: {}", dir.display()))?;
+        let mut fixtures = HashMap::new();
+        let mut versions = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let text = std::fs::read_to_string(&path)
+                .wrap_err_with(|| format!("无法读取夹具: {}", path.display()))?;
+            let fixture: Fixture = serde_json::from_str(&text)
+                .wrap_err_with(|| format!("夹具格式错误: {}", path.display()))?;
+            let key = fixture.request.key();
+            if path.file_stem().and_then(|s| s.to_str()) != Some(key.as_str()) {
+                warn!("夹具 {} 的文件名与请求 key {} 不一致", path.display(), key);
+            }
+            versions.push(fixture.prompt_version);
+            fixtures.insert(key, fixture.response);
+        }
+        warn_prompt_version(dir, versions);
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            fixtures,
+        })
+    }
+
+    /// 已加载的夹具数
+    pub fn len(&self) -> usize {
+        self.fixtures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixtures.is_empty()
+    }
+}
+
+#[async_trait]
+impl Provider for FixtureReplayer {
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        _tools: &[ToolSpec],
+        model: &str,
+        _params: GenerationParams,
+    ) -> Result<ChatResponse> {
+        let request = CanonicalRequest::new(messages, model);
+        let key = request.key();
+        if let Some(response) = self.fixtures.get(&key) {
+            return Ok(response.clone());
+        }
+        // 路由调用失败会被降级为 Direct，warn 保证未命中不会被悄悄吞掉
+        let message = format!(
+            "回放夹具未命中: {} 中没有 {}.json（最后一条用户消息: {}），请用 --record 重新录制",
+            self.dir.display(),
+            key,
+            request
+                .last_user_message()
+                .chars()
+                .take(80)
+                .collect::<String>()
+        );
+        warn!("{}", message);
+        Err(eyre!(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Agent, AgentFactory};
+    use crate::config::{Config, LiveConfig, ProviderConfig, RrclawPaths};
+    use crate::memory::NoopMemory;
+    use crate::providers::echo::EchoProvider;
+    use crate::security::AutonomyLevel;
+    use std::sync::Arc;
+
+    fn echo_config() -> ProviderConfig {
+        ProviderConfig {
+            base_url: "echo://".to_string(),
+            api_key: String::new(),
+            model: "echo".to_string(),
+            auth_style: Some("echo".to_string()),
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
+        }
+    }
+
+    /// Full 模式的 Agent，Provider 替换为 `provider`
+    fn agent(home: &Path, provider: Box<dyn Provider>) -> Agent {
+        let mut config = Config::default();
+        config.default.provider = "echo".to_string();
+        config.default.model = "echo".to_string();
+        config.providers.insert("echo".to_string(), echo_config());
+        let mut agent = AgentFactory::new(
+            Arc::new(LiveConfig::new(config)),
+            Arc::new(NoopMemory),
+            RrclawPaths::in_home(home),
+        )
+        .create_agent()
+        .unwrap();
+        agent.set_autonomy(AutonomyLevel::Full);
+        agent.switch_provider(provider, "echo".into(), "echo://".into(), "echo".into());
+        agent
+    }
+
+    const SCRIPT: &[&str] = &["你好", "tool:memory_recall {\"query\": \"周报\"}", "再见"];
+
+    #[tokio::test]
+    async fn replay_reproduces_recorded_agent_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let cassette = dir.path().join("cassettes/session.jsonl");
+
+        let recorder =
+            RecordingProvider::create(Box::new(EchoProvider::new(&echo_config())), &cassette)
+                .unwrap();
+        let mut recorded = agent(dir.path(), Box::new(recorder));
+        let mut outputs = Vec::new();
+        for message in SCRIPT {
+            outputs.push(recorded.process_message(message).await.unwrap());
+        }
+        assert!(outputs[1].contains("tool result"), "{}", outputs[1]);
+
+        // 每次交互都带录制时的 prompt 版本
+        let text = std::fs::read_to_string(&cassette).unwrap();
+        for line in text.lines() {
+            let interaction: Interaction = serde_json::from_str(line).unwrap();
+            assert_eq!(interaction.prompt_version, PROMPT_VERSION);
+        }
+
+        let replay = ReplayProvider::load(&cassette).unwrap();
+        // 每条消息一次路由调用；工具那条多一次 tool call 往返
+        assert_eq!(replay.total, 2 * SCRIPT.len() + 1);
+        let mut replayed = agent(dir.path(), Box::new(replay));
+        for (message, expected) in SCRIPT.iter().zip(&outputs) {
+            // 回放走流式路径，结果仍与录制一致
+            let (tx, _rx) = crate::providers::stream_channel();
+            let output = replayed.process_message_stream(message, tx).await.unwrap();
+            assert_eq!(&output, expected);
+        }
+        assert_eq!(
+            serde_json::to_value(recorded.history()).unwrap(),
+            serde_json::to_value(replayed.history()).unwrap()
+        );
+
+        // 超出录制范围的请求直接报错，不会静默访问网络
+        let err = replayed.process_message("多出来的一条").await.unwrap_err();
+        assert!(format!("{:#}", err).contains("已全部回放"), "{:#}", err);
+    }
+
+    #[tokio::test]
+    async fn fixtures_replay_by_request_not_by_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixtures = dir.path().join("fixtures");
+
+        let recorder =
+            FixtureRecorder::create(Box::new(EchoProvider::new(&echo_config())), &fixtures)
+                .unwrap();
+        let mut recorded = agent(dir.path(), Box::new(recorder));
+        let mut outputs = Vec::new();
+        for message in SCRIPT {
+            outputs.push(recorded.process_message(message).await.unwrap());
+        }
+
+        let replayer = FixtureReplayer::load(&fixtures).unwrap();
+        assert_eq!(replayer.len(), 2 * SCRIPT.len() + 1);
+        let mut replayed = agent(dir.path(), Box::new(replayer));
+        for (message, expected) in SCRIPT.iter().zip(&outputs) {
+            assert_eq!(&replayed.process_message(message).await.unwrap(), expected);
+        }
+
+        // 同一段对话在新 Agent 中从头回放：同样的请求命中同样的夹具
+        let replayer = FixtureReplayer::load(&fixtures).unwrap();
+        let mut again = agent(dir.path(), Box::new(replayer));
+        assert_eq!(again.process_message(SCRIPT[0]).await.unwrap(), outputs[0]);
+
+        let err = again.process_message("不在夹具中").await.unwrap_err();
+        assert!(format!("{:#}", err).contains("回放夹具未命中"), "{:#}", err);
+    }
+
+    #[test]
+    fn fixture_key_ignores_system_prompt_and_tool_output() {
+        let chat = |role: &str, content: &str| {
+            ConversationMessage::Chat(crate::providers::ChatMessage {
+                role: role.to_string(),
+                content: content.to_string(),
+                reasoning_content: None,
+                turn: 1,
+            })
+        };
+        let result = |content: &str| ConversationMessage::ToolResult {
+            tool_call_id: "c1".to_string(),
+            content: content.to_string(),
+            turn: 1,
+        };
+        let a = [
+            chat("system", "当前时间: 10:00"),
+            chat("user", "hi"),
+            result("a"),
+        ];
+        let b = [
+            chat("system", "当前时间: 10:05"),
+            chat("user", "hi"),
+            result("b"),
+        ];
+        let key = |m: &[ConversationMessage], model: &str| CanonicalRequest::new(m, model).key();
+        assert_eq!(key(&a, "m"), key(&b, "m"));
+        assert_ne!(key(&a, "m"), key(&a, "other"));
+        assert_ne!(key(&a, "m"), key(&[chat("user", "hello")], "m"));
+        assert_eq!(key(&a, "m").len(), 16);
+    }
+
+    #[test]
+    fn with_cassette_follows_dev_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut dev = DevConfig {
+            mode: CassetteMode::Replay,
+            cassette: dir.path().join("missing.jsonl"),
+        };
+        let err = with_cassette(Box::new(EchoProvider::new(&echo_config())), &dev)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("无法读取 cassette"), "{}", err);
+
+        dev.mode = CassetteMode::Record;
+        with_cassette(Box::new(EchoProvider::new(&echo_config())), &dev).unwrap();
+        assert!(dev.cassette.exists());
+
+        std::fs::write(&dev.cassette, "{\"request\": 1}\n").unwrap();
+        dev.mode = CassetteMode::Replay;
+        let err = with_cassette(Box::new(EchoProvider::new(&echo_config())), &dev)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("第 1 行"), "{}", err);
+    }
+}
@@ -1,9 +1,25 @@
 use async_trait::async_trait;
 use color_eyre::eyre::Result;
+use std::hash::BuildHasher;
+use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 use tracing::{debug, warn};
 
-use super::traits::{ChatResponse, ConversationMessage, Provider, StreamEvent, ToolSpec};
+use crate::config::{ProviderConfig, ReliabilityConfig};
+
+use super::offline::{is_network_failure, offline_error_message, OfflineState};
+use super::params::GenerationParams;
+use super::traits::{
+    ChatMessage, ChatResponse, ConversationMessage, Provenance, Provider, StreamEvent,
+    StreamInterrupted, ToolSpec,
+};
+
+/// 流式响应中断后的续写提示（与已收到的部分回复一起发送）
+const CONTINUE_PROMPT: &str =
+    "你的上一条回复因网络中断被截断。请从中断处直接继续输出剩余内容，不要重复已输出的部分，也不要添加任何说明。";
+
+/// 从头重试 / 换 fallback 后的回复与 UI 上已显示的内容不一致时的提示
+const REGENERATED_NOTICE: &str = "重试后的回复与上面已显示的内容不同，以下为完整的新回复";
 
 /// 重试配置
 #[derive(Debug, Clone)]
@@ -16,6 +32,9 @@ pub struct RetryConfig {
     pub backoff_multiplier: f64,
     /// 最大退避时间上限（毫秒）
     pub max_backoff_ms: u64,
+    /// 是否启用 full jitter（实际等待时间在 0 ~ 计算值之间随机），
+    /// 避免大量客户端在 Provider 故障时同步重试
+    pub jitter: bool,
 }
 
 impl Default for RetryConfig {
@@ -25,6 +44,18 @@ impl Default for RetryConfig {
             initial_backoff_ms: 500,
             backoff_multiplier: 2.0,
             max_backoff_ms: 10_000,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// 按 `[reliability]` 配置构造（退避乘数、上限与 jitter 取默认值）
+    pub fn from_reliability(reliability: &ReliabilityConfig) -> Self {
+        Self {
+            max_retries: reliability.max_retries,
+            initial_backoff_ms: reliability.initial_backoff_ms,
+            ..Default::default()
         }
     }
 }
@@ -37,6 +68,10 @@ pub struct ReliableProvider {
     fallbacks: Vec<Box<dyn Provider>>,
     /// 重试配置
     config: RetryConfig,
+    /// 共享离线标记（默认全局实例）
+    offline: Arc<OfflineState>,
+    /// Provider 名（metrics 标签），顺序为主 Provider + fallbacks
+    names: Vec<String>,
 }
 
 impl ReliableProvider {
@@ -46,6 +81,8 @@ impl ReliableProvider {
             inner,
             fallbacks: vec![],
             config,
+            offline: OfflineState::global(),
+            names: Vec::new(),
         }
     }
 
@@ -59,6 +96,70 @@ impl ReliableProvider {
             inner,
             fallbacks,
             config,
+            offline: OfflineState::global(),
+            names: Vec::new(),
+        }
+    }
+
+    /// 单个已配置 Provider 的重试包装（不 fallback）
+    ///
+    /// 重试参数取自 `[reliability]`，metrics 标签为 `name`，离线时与同一离线标记下的其他请求一起快速失败。
+    pub fn for_provider(
+        name: &str,
+        provider_config: &ProviderConfig,
+        reliability: &ReliabilityConfig,
+        offline: Arc<OfflineState>,
+    ) -> Self {
+        Self::new(
+            super::create_provider(provider_config),
+            RetryConfig::from_reliability(reliability),
+        )
+        .with_names(vec![name.to_string()])
+        .with_offline_state(offline)
+    }
+
+    /// 使用指定的离线状态（测试或隔离场景用）
+    pub fn with_offline_state(mut self, offline: Arc<OfflineState>) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// 设置 Provider 名（metrics 标签），顺序为主 Provider + fallbacks
+    pub fn with_names(mut self, names: Vec<String>) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// 第 `index` 个 Provider 的名称（0 为主 Provider），未设置时按位置命名
+    fn name(&self, index: usize) -> String {
+        match self.names.get(index) {
+            Some(name) => name.clone(),
+            None if index == 0 => "primary".to_string(),
+            None => format!("fallback-{}", index),
+        }
+    }
+
+    /// 在成功的响应上标记来源（第 `index` 个 Provider，此前共失败 `retries` 次）
+    fn stamp(
+        &self,
+        mut resp: ChatResponse,
+        index: usize,
+        model: &str,
+        retries: u32,
+    ) -> ChatResponse {
+        resp.provenance = Some(Provenance {
+            provider: self.name(index),
+            model: model.to_string(),
+            retries,
+            fallback: index > 0,
+        });
+        resp
+    }
+
+    /// 全部 Provider 失败后的收尾：网络类错误标记离线
+    fn on_all_failed(&self, last_error: &color_eyre::Report) {
+        if is_network_failure(last_error) {
+            self.offline.mark_offline(&format!("{:#}", last_error));
         }
     }
 }
@@ -70,49 +171,73 @@ impl Provider for ReliableProvider {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
     ) -> Result<ChatResponse> {
+        // 离线时快速失败，不再走完整退避流程
+        if self.offline.is_offline() {
+            color_eyre::eyre::bail!("{}", offline_error_message());
+        }
+
+        // 整条链上失败的请求数（写进响应来源）
+        let mut retries = 0;
+
         // 先重试主 Provider
-        match retry_with_backoff(
+        let mut last_error = match retry_with_backoff(
             &*self.inner,
+            &self.name(0),
             messages,
             tools,
             model,
-            temperature,
+            params,
             &self.config,
             &StreamMode::NonStream,
+            &mut retries,
         )
         .await
         {
-            Ok(resp) => return Ok(resp),
+            Ok(resp) => {
+                self.offline.mark_online();
+                return Ok(self.stamp(resp, 0, model, retries));
+            }
             Err(e) => {
                 warn!("主 Provider 全部重试失败: {:#}", e);
+                e
             }
-        }
+        };
 
         // 依次尝试 fallback
         for (i, fallback) in self.fallbacks.iter().enumerate() {
             warn!("尝试 Fallback Provider #{}", i + 1);
             match retry_with_backoff(
                 &**fallback,
+                &self.name(i + 1),
                 messages,
                 tools,
                 model,
-                temperature,
+                params,
                 &self.config,
                 &StreamMode::NonStream,
+                &mut retries,
             )
             .await
             {
-                Ok(resp) => return Ok(resp),
-                Err(e) => warn!("Fallback #{} 失败: {:#}", i + 1, e),
+                Ok(resp) => {
+                    self.offline.mark_online();
+                    return Ok(self.stamp(resp, i + 1, model, retries));
+                }
+                Err(e) => {
+                    warn!("Fallback #{} 失败: {:#}", i + 1, e);
+                    last_error = e;
+                }
             }
         }
 
-        color_eyre::eyre::bail!(
+        self.on_all_failed(&last_error);
+        // 保留最后一个错误作为 cause，调用方按错误链识别上下文超限等错误
+        Err(last_error.wrap_err(format!(
             "所有 Provider 均失败（主 Provider + {} 个 fallback）",
             self.fallbacks.len()
-        )
+        )))
     }
 
     async fn chat_stream(
@@ -120,84 +245,132 @@ impl Provider for ReliableProvider {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
         tx: tokio::sync::mpsc::Sender<StreamEvent>,
     ) -> Result<ChatResponse> {
-        let stream_mode = StreamMode::Stream(tx.clone());
+        if self.offline.is_offline() {
+            color_eyre::eyre::bail!("{}", offline_error_message());
+        }
+
+        let stream_mode = StreamMode::Stream {
+            tx: tx.clone(),
+            shown: std::sync::Mutex::new(String::new()),
+        };
+
+        let mut retries = 0;
 
         // 流式模式：先尝试主 Provider 重试
-        match retry_with_backoff(
+        let mut last_error = match retry_with_backoff(
             &*self.inner,
+            &self.name(0),
             messages,
             tools,
             model,
-            temperature,
+            params,
             &self.config,
             &stream_mode,
+            &mut retries,
         )
         .await
         {
-            Ok(resp) => return Ok(resp),
-            Err(e) => warn!("主 Provider 流式重试全部失败: {:#}", e),
-        }
+            Ok(resp) => {
+                self.offline.mark_online();
+                return Ok(self.stamp(resp, 0, model, retries));
+            }
+            Err(e) => {
+                warn!("主 Provider 流式重试全部失败: {:#}", e);
+                e
+            }
+        };
 
         // Fallback 链（流式）
         for (i, fallback) in self.fallbacks.iter().enumerate() {
             warn!("流式: 尝试 Fallback Provider #{}", i + 1);
             match retry_with_backoff(
                 &**fallback,
+                &self.name(i + 1),
                 messages,
                 tools,
                 model,
-                temperature,
+                params,
                 &self.config,
                 &stream_mode,
+                &mut retries,
             )
             .await
             {
-                Ok(resp) => return Ok(resp),
-                Err(e) => warn!("流式 Fallback #{} 失败: {:#}", i + 1, e),
+                Ok(resp) => {
+                    self.offline.mark_online();
+                    return Ok(self.stamp(resp, i + 1, model, retries));
+                }
+                Err(e) => {
+                    warn!("流式 Fallback #{} 失败: {:#}", i + 1, e);
+                    last_error = e;
+                }
             }
         }
 
-        color_eyre::eyre::bail!(
+        self.on_all_failed(&last_error);
+        // 保留最后一个错误作为 cause，调用方按错误链识别上下文超限等错误
+        Err(last_error.wrap_err(format!(
             "流式: 所有 Provider 均失败（主 Provider + {} 个 fallback）",
             self.fallbacks.len()
-        )
+        )))
     }
 }
 
 /// 流式模式选择：非流式 or 流式（带 sender）
 enum StreamMode {
     NonStream,
-    Stream(tokio::sync::mpsc::Sender<StreamEvent>),
+    Stream {
+        tx: tokio::sync::mpsc::Sender<StreamEvent>,
+        /// UI 上已显示的本次回复文本（跨重试与 fallback）
+        shown: std::sync::Mutex<String>,
+    },
 }
 
-/// 对单个 Provider 执行重试逻辑（含指数退避）
+/// 对单个 Provider 执行重试逻辑（含指数退避），每次失败的请求累加到 `failures`
+#[allow(clippy::too_many_arguments)]
 async fn retry_with_backoff(
     provider: &dyn Provider,
+    name: &str,
     messages: &[ConversationMessage],
     tools: &[ToolSpec],
     model: &str,
-    temperature: f64,
+    params: GenerationParams,
     config: &RetryConfig,
     mode: &StreamMode,
+    failures: &mut u32,
 ) -> Result<ChatResponse> {
     let mut backoff_ms = config.initial_backoff_ms;
+    // 流式中断前已发给 UI 的文本：重试时带上它让模型续写，避免 UI 上重复输出
+    let mut resumed_text = String::new();
 
     for attempt in 0..=config.max_retries {
+        let started = std::time::Instant::now();
         let result = match mode {
-            StreamMode::Stream(tx) => {
-                provider
-                    .chat_stream(messages, tools, model, temperature, tx.clone())
-                    .await
+            StreamMode::Stream { tx, shown } => {
+                let continued;
+                let request = if resumed_text.is_empty() {
+                    messages
+                } else {
+                    continued = continuation_messages(messages, &resumed_text);
+                    &continued[..]
+                };
+                let (attempt_tx, attempt_rx) = super::stream_sink::stream_channel();
+                let (result, ()) = tokio::join!(
+                    provider.chat_stream(request, tools, model, params, attempt_tx),
+                    forward_attempt(attempt_rx, tx, shown, &resumed_text),
+                );
+                result.map(|resp| prepend_text(resp, &resumed_text))
             }
             StreamMode::NonStream => {
                 provider
-                    .chat_with_tools(messages, tools, model, temperature)
+                    .chat_with_tools(messages, tools, model, params)
                     .await
             }
         };
+        crate::metrics::record_provider_request(name, model, result.is_ok(), started.elapsed());
 
         match result {
             Ok(resp) => {
@@ -207,25 +380,34 @@ async fn retry_with_backoff(
                 return Ok(resp);
             }
             Err(e) => {
+                *failures += 1;
                 if attempt == config.max_retries {
                     // 最后一次尝试也失败了
                     return Err(e);
                 }
 
-                // 判断是否是可重试的错误
+                // 判断是否是可重试的错误（流式中断总是重试）
                 let err_str = format!("{:#}", e);
-                if !is_retryable(&err_str) {
+                if let Some(interrupted) = e.downcast_ref::<StreamInterrupted>() {
+                    if interrupted.has_tool_calls {
+                        // tool call 参数不完整，无法续写，整体重试
+                        resumed_text.clear();
+                    } else {
+                        resumed_text.push_str(&interrupted.partial_text);
+                    }
+                } else if !is_retryable(&err_str) {
                     warn!("不可重试的错误，停止: {}", err_str);
                     return Err(e);
                 }
 
+                let delay_ms = backoff_delay(backoff_ms, config.jitter);
                 warn!(
                     "第 {} 次尝试失败，{} ms 后重试: {}",
                     attempt + 1,
-                    backoff_ms,
+                    delay_ms,
                     truncate_error(&err_str)
                 );
-                sleep(Duration::from_millis(backoff_ms)).await;
+                sleep(Duration::from_millis(delay_ms)).await;
 
                 // 指数退避，不超过上限
                 backoff_ms = ((backoff_ms as f64) * config.backoff_multiplier) as u64;
@@ -237,6 +419,85 @@ async fn retry_with_backoff(
     unreachable!()
 }
 
+/// 把单次流式尝试的事件转发给 UI
+///
+/// 从头重试（tool call 中断）或换 fallback 时，模型会重新输出 UI 已显示过的文本：
+/// 与已显示内容一致的前缀不再转发；内容不同时先发提示，再完整发送本次尝试的文本。
+async fn forward_attempt(
+    mut rx: tokio::sync::mpsc::Receiver<StreamEvent>,
+    tx: &tokio::sync::mpsc::Sender<StreamEvent>,
+    shown: &std::sync::Mutex<String>,
+    resumed: &str,
+) {
+    let displayed = shown.lock().unwrap().clone();
+    // 本次尝试的完整文本（续写时含已收到的前缀）
+    let mut text = resumed.to_string();
+    let mut replaying = displayed.len() > text.len();
+    while let Some(event) = rx.recv().await {
+        let StreamEvent::Text(delta) = event else {
+            let _ = tx.send(event).await;
+            continue;
+        };
+        text.push_str(&delta);
+        if !replaying {
+            let _ = tx.send(StreamEvent::Text(delta)).await;
+            continue;
+        }
+        if displayed.starts_with(&text) {
+            continue;
+        }
+        replaying = false;
+        let fresh = if text.starts_with(&displayed) {
+            text[displayed.len()..].to_string()
+        } else {
+            let _ = tx
+                .send(StreamEvent::Notice(REGENERATED_NOTICE.to_string()))
+                .await;
+            text[resumed.len()..].to_string()
+        };
+        let _ = tx.send(StreamEvent::Text(fresh)).await;
+    }
+    // 仍在重放已显示的部分时中断：UI 上保留的是更长的旧文本
+    if !replaying {
+        *shown.lock().unwrap() = text;
+    }
+}
+
+/// 续写请求：原消息 + 已收到的部分回复 + 续写提示
+fn continuation_messages(
+    messages: &[ConversationMessage],
+    partial: &str,
+) -> Vec<ConversationMessage> {
+    let mut continued = messages.to_vec();
+    for (role, content) in [("assistant", partial), ("user", CONTINUE_PROMPT)] {
+        continued.push(ConversationMessage::Chat(ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            reasoning_content: None,
+            turn: 0,
+        }));
+    }
+    continued
+}
+
+/// 把中断前的文本拼回续写结果，调用方拿到的是完整回复
+fn prepend_text(mut resp: ChatResponse, prefix: &str) -> ChatResponse {
+    if !prefix.is_empty() {
+        resp.text = Some(format!("{}{}", prefix, resp.text.unwrap_or_default()));
+    }
+    resp
+}
+
+/// 计算本次实际等待时间：启用 jitter 时在 [0, backoff_ms] 内均匀随机
+fn backoff_delay(backoff_ms: u64, jitter: bool) -> u64 {
+    if !jitter || backoff_ms == 0 {
+        return backoff_ms;
+    }
+    // RandomState 每次构造使用随机种子，足够用于退避抖动（无需引入 rand）
+    let random = std::collections::hash_map::RandomState::new().hash_one(backoff_ms);
+    random % (backoff_ms + 1)
+}
+
 /// 判断错误是否可重试
 /// 可重试：超时、网络连接失败、5xx 服务端错误、速率限制(429)
 /// 不可重试：4xx 客户端错误（除 429）、认证失败等
@@ -287,6 +548,7 @@ mod tests {
                     text: Some("成功".to_string()),
                     reasoning_content: None,
                     tool_calls: vec![],
+                    provenance: None,
                 },
             }
         }
@@ -299,7 +561,7 @@ mod tests {
             _m: &[ConversationMessage],
             _t: &[ToolSpec],
             _mo: &str,
-            _te: f64,
+            _params: GenerationParams,
         ) -> Result<ChatResponse> {
             let mut count = self.fail_count.lock().unwrap();
             if *count > 0 {
@@ -319,7 +581,7 @@ mod tests {
             _m: &[ConversationMessage],
             _t: &[ToolSpec],
             _mo: &str,
-            _te: f64,
+            _params: GenerationParams,
         ) -> Result<ChatResponse> {
             color_eyre::eyre::bail!("始终失败")
         }
@@ -336,12 +598,13 @@ mod tests {
             _m: &[ConversationMessage],
             _t: &[ToolSpec],
             _mo: &str,
-            _te: f64,
+            _params: GenerationParams,
         ) -> Result<ChatResponse> {
             Ok(ChatResponse {
                 text: Some(format!("来自 {}", self.label)),
                 reasoning_content: None,
                 tool_calls: vec![],
+                provenance: None,
             })
         }
     }
@@ -352,6 +615,7 @@ mod tests {
             initial_backoff_ms: 1, // 测试用：1ms 退避
             backoff_multiplier: 1.0,
             max_backoff_ms: 5,
+            jitter: false,
         }
     }
 
@@ -361,7 +625,7 @@ mod tests {
     async fn retries_and_succeeds() {
         // 失败 2 次后成功，max_retries=3，应该成功
         let provider = ReliableProvider::new(Box::new(FlakyProvider::new(2)), fast_retry());
-        let result = provider.chat_with_tools(&[], &[], "m", 0.7).await;
+        let result = provider.chat_with_tools(&[], &[], "m", 0.7.into()).await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap().text.as_deref(), Some("成功"));
     }
@@ -370,7 +634,7 @@ mod tests {
     async fn fails_after_max_retries() {
         // 失败 5 次，max_retries=3，应该失败
         let provider = ReliableProvider::new(Box::new(FlakyProvider::new(5)), fast_retry());
-        let result = provider.chat_with_tools(&[], &[], "m", 0.7).await;
+        let result = provider.chat_with_tools(&[], &[], "m", 0.7.into()).await;
         assert!(result.is_err());
     }
 
@@ -378,7 +642,7 @@ mod tests {
     async fn success_on_first_try_no_retry() {
         // 第一次就成功，不应重试
         let provider = ReliableProvider::new(Box::new(FlakyProvider::new(0)), fast_retry());
-        let result = provider.chat_with_tools(&[], &[], "m", 0.7).await;
+        let result = provider.chat_with_tools(&[], &[], "m", 0.7.into()).await;
         assert!(result.is_ok());
     }
 
@@ -393,7 +657,7 @@ mod tests {
             })],
             fast_retry(),
         );
-        let result = provider.chat_with_tools(&[], &[], "m", 0.7).await;
+        let result = provider.chat_with_tools(&[], &[], "m", 0.7.into()).await;
         assert!(result.is_ok());
         assert!(result.unwrap().text.unwrap().contains("fallback1"));
     }
@@ -410,7 +674,7 @@ mod tests {
             ],
             fast_retry(),
         );
-        let result = provider.chat_with_tools(&[], &[], "m", 0.7).await;
+        let result = provider.chat_with_tools(&[], &[], "m", 0.7.into()).await;
         assert!(result.is_ok());
         assert!(result.unwrap().text.unwrap().contains("fallback2"));
     }
@@ -422,12 +686,335 @@ mod tests {
             vec![Box::new(AlwaysFailProvider)],
             fast_retry(),
         );
-        let result = provider.chat_with_tools(&[], &[], "m", 0.7).await;
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("所有 Provider 均失败"));
+        let result = provider.chat_with_tools(&[], &[], "m", 0.7.into()).await;
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("所有 Provider 均失败"));
+        // 最后一个 Provider 的错误保留在错误链中
+        assert!(format!("{:#}", err).contains("始终失败"), "{:#}", err);
+    }
+
+    #[tokio::test]
+    async fn provenance_names_the_fallback_that_answered() {
+        let provider = ReliableProvider::with_fallbacks(
+            Box::new(AlwaysFailProvider),
+            vec![Box::new(AlwaysSucceedProvider {
+                label: "fallback1".to_string(),
+            })],
+            fast_retry(),
+        )
+        .with_names(vec!["deepseek".to_string(), "claude".to_string()])
+        .with_offline_state(Arc::new(OfflineState::new()));
+        let resp = provider
+            .chat_with_tools(&[], &[], "deepseek-chat", 0.7.into())
+            .await
+            .unwrap();
+        // 主 Provider：1 次初始请求 + 3 次重试全部失败
+        assert_eq!(
+            resp.provenance,
+            Some(Provenance {
+                provider: "claude".to_string(),
+                model: "deepseek-chat".to_string(),
+                retries: 4,
+                fallback: true,
+            })
+        );
+        assert_eq!(
+            resp.provenance.unwrap().describe(true),
+            "answered by claude/deepseek-chat after 4 retries (fallback)"
+        );
+
+        let provider = ReliableProvider::new(Box::new(FlakyProvider::new(1)), fast_retry())
+            .with_offline_state(Arc::new(OfflineState::new()));
+        let provenance = provider
+            .chat_with_tools(&[], &[], "m", 0.7.into())
+            .await
+            .unwrap()
+            .provenance
+            .unwrap();
+        assert_eq!(
+            provenance.describe(true),
+            "answered by primary/m after 1 retry"
+        );
+        assert!(!provenance.fallback);
+    }
+
+    // --- 流式中断续写测试 ---
+
+    /// 本地 SSE 服务：第一次请求发出半条回复后断开，之后返回完整的续写结果
+    async fn spawn_truncating_sse_server(bodies: Arc<Mutex<Vec<String>>>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let n = stream.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let content_length = text[..end]
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                            })
+                            .unwrap_or(0);
+                        if buf.len() >= end + 4 + content_length {
+                            bodies.lock().unwrap().push(text[end + 4..].to_string());
+                            break;
+                        }
+                    }
+                }
+                let first = bodies.lock().unwrap().len() == 1;
+                let events = if first {
+                    // 没有 finish_reason 和 [DONE]：连接中途断开
+                    "data: {\"choices\":[{\"delta\":{\"content\":\"Hello, \"}}]}\n\n".to_string()
+                } else {
+                    "data: {\"choices\":[{\"delta\":{\"content\":\"world\"},\"finish_reason\":\"stop\"}]}\n\ndata: [DONE]\n\n".to_string()
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+                    events
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn truncated_stream_is_retried_as_continuation() {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let base_url = spawn_truncating_sse_server(bodies.clone()).await;
+        let inner =
+            crate::providers::compatible::CompatibleProvider::new(&crate::config::ProviderConfig {
+                base_url,
+                api_key: "test".to_string(),
+                model: "m".to_string(),
+                auth_style: None,
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            });
+        let provider = ReliableProvider::new(Box::new(inner), fast_retry())
+            .with_offline_state(Arc::new(OfflineState::new()));
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+        let resp = provider
+            .chat_stream(&[], &[], "m", 0.7.into(), tx)
+            .await
+            .unwrap();
+
+        assert_eq!(resp.text.as_deref(), Some("Hello, world"));
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2, "中断后应重试一次");
+        assert!(bodies[1].contains("Hello, "));
+        assert!(bodies[1].contains(CONTINUE_PROMPT));
+
+        // UI 只看到每段文本一次
+        let mut streamed = String::new();
+        while let Ok(event) = rx.try_recv() {
+            if let StreamEvent::Text(text) = event {
+                streamed.push_str(&text);
+            }
+        }
+        assert_eq!(streamed, "Hello, world");
+    }
+
+    /// 第一次流式调用在 tool call 中途中断，之后成功；记录每次收到的消息数
+    struct InterruptedToolCallProvider {
+        calls: Arc<Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for InterruptedToolCallProvider {
+        async fn chat_with_tools(
+            &self,
+            _m: &[ConversationMessage],
+            _t: &[ToolSpec],
+            _mo: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            unreachable!("只测试流式")
+        }
+
+        async fn chat_stream(
+            &self,
+            messages: &[ConversationMessage],
+            _t: &[ToolSpec],
+            _mo: &str,
+            _params: GenerationParams,
+            tx: tokio::sync::mpsc::Sender<StreamEvent>,
+        ) -> Result<ChatResponse> {
+            let attempt = {
+                let mut calls = self.calls.lock().unwrap();
+                calls.push(messages.len());
+                calls.len()
+            };
+            // 两次都从头输出同一段开场白
+            let _ = tx.send(StreamEvent::Text("先读一下文件".to_string())).await;
+            if attempt == 1 {
+                return Err(StreamInterrupted {
+                    partial_text: "先读一下文件".to_string(),
+                    has_tool_calls: true,
+                    reason: "连接在结束标记前关闭".to_string(),
+                }
+                .into());
+            }
+            let _ = tx.send(StreamEvent::Text("，完整回复".to_string())).await;
+            Ok(ChatResponse {
+                text: Some("先读一下文件，完整回复".to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            })
+        }
+    }
+
+    /// 收集 UI 收到的文本与提示
+    fn drain_stream(rx: &mut tokio::sync::mpsc::Receiver<StreamEvent>) -> (String, Vec<String>) {
+        let mut streamed = String::new();
+        let mut notices = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                StreamEvent::Text(text) => streamed.push_str(&text),
+                StreamEvent::Notice(notice) => notices.push(notice),
+                _ => {}
+            }
+        }
+        (streamed, notices)
+    }
+
+    /// 每次调用流出固定文本：`fail` 时以 tool call 中断结束，否则成功返回
+    struct ScriptedStreamProvider {
+        text: &'static str,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for ScriptedStreamProvider {
+        async fn chat_with_tools(
+            &self,
+            _m: &[ConversationMessage],
+            _t: &[ToolSpec],
+            _mo: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            unreachable!("只测试流式")
+        }
+
+        async fn chat_stream(
+            &self,
+            _m: &[ConversationMessage],
+            _t: &[ToolSpec],
+            _mo: &str,
+            _params: GenerationParams,
+            tx: tokio::sync::mpsc::Sender<StreamEvent>,
+        ) -> Result<ChatResponse> {
+            let _ = tx.send(StreamEvent::Text(self.text.to_string())).await;
+            if self.fail {
+                return Err(StreamInterrupted {
+                    partial_text: self.text.to_string(),
+                    has_tool_calls: true,
+                    reason: "连接在结束标记前关闭".to_string(),
+                }
+                .into());
+            }
+            Ok(ChatResponse {
+                text: Some(self.text.to_string()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fallback_after_partial_stream_skips_text_already_shown() {
+        let provider = ReliableProvider::with_fallbacks(
+            Box::new(ScriptedStreamProvider {
+                text: "我来看看",
+                fail: true,
+            }),
+            vec![Box::new(ScriptedStreamProvider {
+                text: "我来看看这个问题",
+                fail: false,
+            })],
+            fast_retry(),
+        )
+        .with_offline_state(Arc::new(OfflineState::new()));
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+
+        let resp = provider
+            .chat_stream(&[], &[], "m", 0.7.into(), tx)
+            .await
+            .unwrap();
+
+        assert_eq!(resp.text.as_deref(), Some("我来看看这个问题"));
+        let (streamed, notices) = drain_stream(&mut rx);
+        assert_eq!(streamed, "我来看看这个问题");
+        assert!(notices.is_empty(), "{:?}", notices);
+    }
+
+    #[tokio::test]
+    async fn diverging_fallback_is_announced_and_streamed_in_full() {
+        let provider = ReliableProvider::with_fallbacks(
+            Box::new(ScriptedStreamProvider {
+                text: "我来看看",
+                fail: true,
+            }),
+            vec![Box::new(ScriptedStreamProvider {
+                text: "好的，答案是 42",
+                fail: false,
+            })],
+            fast_retry(),
+        )
+        .with_offline_state(Arc::new(OfflineState::new()));
+        let (tx, mut rx) = tokio::sync::mpsc::channel(32);
+
+        provider
+            .chat_stream(&[], &[], "m", 0.7.into(), tx)
+            .await
+            .unwrap();
+
+        let (streamed, notices) = drain_stream(&mut rx);
+        assert_eq!(streamed, "我来看看好的，答案是 42");
+        assert_eq!(notices, vec![REGENERATED_NOTICE.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn interrupted_tool_call_is_retried_from_scratch() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let provider = ReliableProvider::new(
+            Box::new(InterruptedToolCallProvider {
+                calls: calls.clone(),
+            }),
+            fast_retry(),
+        )
+        .with_offline_state(Arc::new(OfflineState::new()));
+        let (tx, mut rx) = tokio::sync::mpsc::channel(8);
+
+        let resp = provider
+            .chat_stream(&[], &[], "m", 0.7.into(), tx)
+            .await
+            .unwrap();
+
+        assert_eq!(resp.text.as_deref(), Some("先读一下文件，完整回复"));
+        assert_eq!(*calls.lock().unwrap(), vec![0, 0]);
+        // 重试重新输出的开场白不会在 UI 上出现第二次
+        let (streamed, notices) = drain_stream(&mut rx);
+        assert_eq!(streamed, "先读一下文件，完整回复");
+        assert!(notices.is_empty(), "{:?}", notices);
     }
 
     // --- is_retryable 测试 ---
@@ -455,5 +1042,172 @@ mod tests {
         assert_eq!(config.max_retries, 3);
         assert_eq!(config.initial_backoff_ms, 500);
         assert!((config.backoff_multiplier - 2.0).abs() < f64::EPSILON);
+        assert!(config.jitter);
+    }
+
+    // --- jitter 测试 ---
+
+    #[test]
+    fn jitter_delays_vary_within_bounds() {
+        let delays: Vec<u64> = (0..50).map(|_| backoff_delay(1000, true)).collect();
+        assert!(delays.iter().all(|d| *d <= 1000));
+        let first = delays[0];
+        assert!(
+            delays.iter().any(|d| *d != first),
+            "jitter 应产生不同的等待时间: {:?}",
+            delays
+        );
+    }
+
+    #[test]
+    fn no_jitter_uses_exact_backoff() {
+        assert_eq!(backoff_delay(1000, false), 1000);
+        assert_eq!(backoff_delay(0, true), 0);
+    }
+
+    // --- 离线模式测试 ---
+
+    /// 模拟网络不可达，记录调用次数
+    struct UnreachableProvider {
+        calls: Arc<Mutex<usize>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for UnreachableProvider {
+        async fn chat_with_tools(
+            &self,
+            _m: &[ConversationMessage],
+            _t: &[ToolSpec],
+            _mo: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            *self.calls.lock().unwrap() += 1;
+            color_eyre::eyre::bail!("error sending request: Connection refused (os error 111)")
+        }
+    }
+
+    #[tokio::test]
+    async fn network_failure_marks_offline_then_fails_fast() {
+        let calls = Arc::new(Mutex::new(0));
+        let offline = Arc::new(OfflineState::new());
+        let provider = ReliableProvider::new(
+            Box::new(UnreachableProvider {
+                calls: calls.clone(),
+            }),
+            fast_retry(),
+        )
+        .with_offline_state(offline.clone());
+
+        assert!(provider
+            .chat_with_tools(&[], &[], "m", 0.7.into())
+            .await
+            .is_err());
+        assert!(offline.is_offline());
+        let calls_after_first = *calls.lock().unwrap();
+        // 1 次初始请求 + 3 次重试
+        assert_eq!(calls_after_first, 4);
+
+        // 离线后不再请求底层 Provider，直接返回友好提示
+        let err = provider
+            .chat_with_tools(&[], &[], "m", 0.7.into())
+            .await
+            .unwrap_err()
+            .to_string();
+        assert_eq!(*calls.lock().unwrap(), calls_after_first);
+        assert!(err.contains("/offline probe"));
+
+        // 恢复后重新请求
+        offline.mark_online();
+        let _ = provider.chat_with_tools(&[], &[], "m", 0.7.into()).await;
+        assert!(*calls.lock().unwrap() > calls_after_first);
+    }
+
+    /// 连接成功、请求发出后等不到响应（慢但可达的 Provider）
+    struct SlowProvider {
+        url: String,
+    }
+
+    #[async_trait]
+    impl Provider for SlowProvider {
+        async fn chat_with_tools(
+            &self,
+            _m: &[ConversationMessage],
+            _t: &[ToolSpec],
+            _mo: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            use color_eyre::eyre::WrapErr;
+            reqwest::Client::builder()
+                .timeout(Duration::from_millis(100))
+                .build()?
+                .get(&self.url)
+                .send()
+                .await
+                .wrap_err("发送请求失败")?;
+            color_eyre::eyre::bail!("unreachable")
+        }
+    }
+
+    #[tokio::test]
+    async fn response_timeout_does_not_mark_offline() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let offline = Arc::new(OfflineState::new());
+        let provider = ReliableProvider::new(
+            Box::new(SlowProvider {
+                url: format!("http://{}/", addr),
+            }),
+            fast_retry(),
+        )
+        .with_offline_state(offline.clone());
+
+        let err = provider
+            .chat_with_tools(&[], &[], "m", 0.7.into())
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("timed out"), "{:#}", err);
+        assert!(!offline.is_offline());
+    }
+
+    #[tokio::test]
+    async fn non_network_failure_does_not_mark_offline() {
+        let offline = Arc::new(OfflineState::new());
+        let provider = ReliableProvider::new(Box::new(AlwaysFailProvider), fast_retry())
+            .with_offline_state(offline.clone());
+        assert!(provider
+            .chat_with_tools(&[], &[], "m", 0.7.into())
+            .await
+            .is_err());
+        assert!(!offline.is_offline());
+    }
+
+    #[tokio::test]
+    async fn success_clears_offline_flag() {
+        let offline = Arc::new(OfflineState::new());
+        offline.mark_offline("connection refused");
+        let provider = ReliableProvider::new(
+            Box::new(AlwaysSucceedProvider {
+                label: "a".to_string(),
+            }),
+            fast_retry(),
+        )
+        .with_offline_state(offline.clone());
+        // 离线时快速失败；探测恢复后请求成功
+        assert!(provider
+            .chat_with_tools(&[], &[], "m", 0.7.into())
+            .await
+            .is_err());
+        offline.mark_online();
+        assert!(provider
+            .chat_with_tools(&[], &[], "m", 0.7.into())
+            .await
+            .is_ok());
+        assert!(!offline.is_offline());
     }
 }
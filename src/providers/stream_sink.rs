@@ -0,0 +1,261 @@
+//! StreamEvent 通道的背压处理
+//!
+//! 通道容量统一为 `STREAM_CHANNEL_CAPACITY`（`stream_channel()` 创建）。Provider 与 Agent Loop
+//! 通过 `EventSink` 发送事件，UI 消费变慢时不再让 SSE 读取循环卡在 `send().await` 上：
+//! - `Text` / `Reasoning`：`try_send`，通道满时合并进待发缓冲（相邻同类增量拼成一条），
+//!   下次有空位时按原顺序补发
+//! - `ToolCallDelta`：UI 不展示，通道满时直接丢弃（完整参数在 `Done` 中）
+//! - 其余事件（`ToolStatus`、`Done` 等）：先补发缓冲，再阻塞等待空位，从不丢弃
+//!
+//! 缓冲最多是本次回复已生成的文本，内存随回复长度而非事件数增长。合并 / 丢弃次数计入进程级
+//! 计数器，`/stats` 展示。
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::warn;
+
+use super::traits::StreamEvent;
+
+/// 所有 StreamEvent 通道的容量
+pub const STREAM_CHANNEL_CAPACITY: usize = 64;
+
+static COALESCED: AtomicU64 = AtomicU64::new(0);
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// 创建 StreamEvent 通道（容量 `STREAM_CHANNEL_CAPACITY`）
+pub fn stream_channel() -> (mpsc::Sender<StreamEvent>, mpsc::Receiver<StreamEvent>) {
+    mpsc::channel(STREAM_CHANNEL_CAPACITY)
+}
+
+/// 背压统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// 因通道已满而合并发送的文本增量数
+    pub coalesced: u64,
+    /// 因通道已满而丢弃的事件数
+    pub dropped: u64,
+}
+
+/// 进程启动以来的累计统计
+pub fn stats() -> StreamStats {
+    StreamStats {
+        coalesced: COALESCED.load(Ordering::Relaxed),
+        dropped: DROPPED.load(Ordering::Relaxed),
+    }
+}
+
+/// 事件在通道满时的处理方式
+enum Delivery {
+    /// 合并进待发缓冲
+    Coalesce,
+    /// 丢弃
+    Droppable,
+    /// 阻塞等待
+    Required,
+}
+
+fn delivery(event: &StreamEvent) -> Delivery {
+    match event {
+        StreamEvent::Text(_) | StreamEvent::Reasoning(_) => Delivery::Coalesce,
+        StreamEvent::ToolCallDelta { .. } => Delivery::Droppable,
+        _ => Delivery::Required,
+    }
+}
+
+/// 带背压处理的 StreamEvent 发送端（每次流式调用各建一个）
+pub struct EventSink {
+    tx: mpsc::Sender<StreamEvent>,
+    /// 通道满时暂存的文本增量（按到达顺序，相邻同类已合并）
+    pending: VecDeque<StreamEvent>,
+    stats: StreamStats,
+}
+
+impl EventSink {
+    pub fn new(tx: mpsc::Sender<StreamEvent>) -> Self {
+        Self {
+            tx,
+            pending: VecDeque::new(),
+            stats: StreamStats::default(),
+        }
+    }
+
+    /// 发送一个事件；接收端已关闭时静默忽略（与原先 `let _ = tx.send(..)` 一致）
+    pub async fn send(&mut self, event: StreamEvent) {
+        match delivery(&event) {
+            Delivery::Required => {
+                self.flush().await;
+                let _ = self.tx.send(event).await;
+            }
+            Delivery::Coalesce => {
+                if !self.try_flush() {
+                    self.coalesce(event);
+                    return;
+                }
+                if let Err(TrySendError::Full(event)) = self.tx.try_send(event) {
+                    self.coalesce(event);
+                }
+            }
+            Delivery::Droppable => {
+                if !self.try_flush()
+                    || matches!(self.tx.try_send(event), Err(TrySendError::Full(_)))
+                {
+                    self.stats.dropped += 1;
+                    DROPPED.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// 等待空位发出所有暂存的文本（流程提前结束、返回错误前调用）
+    pub async fn flush(&mut self) {
+        while let Some(event) = self.pending.pop_front() {
+            if self.tx.send(event).await.is_err() {
+                self.pending.clear();
+            }
+        }
+    }
+
+    /// 本发送端的统计
+    pub fn stats(&self) -> StreamStats {
+        self.stats
+    }
+
+    /// 不等待地补发暂存文本；全部发出（或接收端已关闭）时返回 true
+    fn try_flush(&mut self) -> bool {
+        while let Some(event) = self.pending.pop_front() {
+            match self.tx.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(event)) => {
+                    self.pending.push_front(event);
+                    return false;
+                }
+                Err(TrySendError::Closed(_)) => self.pending.clear(),
+            }
+        }
+        true
+    }
+
+    fn coalesce(&mut self, event: StreamEvent) {
+        self.stats.coalesced += 1;
+        COALESCED.fetch_add(1, Ordering::Relaxed);
+        match (self.pending.back_mut(), event) {
+            (Some(StreamEvent::Text(buf)), StreamEvent::Text(delta))
+            | (Some(StreamEvent::Reasoning(buf)), StreamEvent::Reasoning(delta)) => {
+                buf.push_str(&delta)
+            }
+            (_, event) => self.pending.push_back(event),
+        }
+    }
+}
+
+impl Drop for EventSink {
+    fn drop(&mut self) {
+        if !self.try_flush() {
+            let lost = self.pending.len() as u64;
+            warn!("StreamEvent 通道已满，{} 段暂存文本未能发出", lost);
+            DROPPED.fetch_add(lost, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ToolStatusKind;
+    use std::time::Duration;
+
+    fn status() -> StreamEvent {
+        StreamEvent::ToolStatus {
+            name: "shell".to_string(),
+            status: ToolStatusKind::Running("ls".to_string()),
+        }
+    }
+
+    fn describe(event: &StreamEvent) -> String {
+        match event {
+            StreamEvent::Text(t) => format!("text:{}", t),
+            StreamEvent::Reasoning(r) => format!("reasoning:{}", r),
+            StreamEvent::ToolStatus { .. } => "status".to_string(),
+            StreamEvent::ToolCallDelta { .. } => "delta".to_string(),
+            other => format!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_consumer_gets_coalesced_text_in_order() {
+        let (tx, mut rx) = mpsc::channel(2);
+        let mut sink = EventSink::new(tx);
+        for i in 0..6 {
+            sink.send(StreamEvent::Text(i.to_string())).await;
+        }
+        sink.send(StreamEvent::Reasoning("r".to_string())).await;
+        sink.send(StreamEvent::Text("6".to_string())).await;
+        sink.send(StreamEvent::ToolCallDelta {
+            index: 0,
+            id: None,
+            name: None,
+            arguments_delta: "{".to_string(),
+        })
+        .await;
+        assert_eq!(
+            sink.stats(),
+            StreamStats {
+                coalesced: 6,
+                dropped: 1
+            }
+        );
+
+        // 消费者稍后才开始读取：ToolStatus 需要等待空位，但不会丢失
+        let consumer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut seen = Vec::new();
+            while let Some(event) = rx.recv().await {
+                seen.push(describe(&event));
+            }
+            seen
+        });
+        sink.send(status()).await;
+        drop(sink);
+
+        assert_eq!(
+            consumer.await.unwrap(),
+            vec![
+                "text:0",
+                "text:1",
+                "text:2345",
+                "reasoning:r",
+                "text:6",
+                "status"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pending_text_goes_out_before_the_next_delta() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut sink = EventSink::new(tx);
+        sink.send(StreamEvent::Text("a".to_string())).await;
+        sink.send(StreamEvent::Text("b".to_string())).await;
+        sink.send(StreamEvent::Text("c".to_string())).await;
+
+        // 消费者读走一条后，新增量排在暂存文本之后
+        assert_eq!(describe(&rx.recv().await.unwrap()), "text:a");
+        sink.send(StreamEvent::Text("d".to_string())).await;
+        assert_eq!(describe(&rx.recv().await.unwrap()), "text:bc");
+        sink.flush().await;
+        assert_eq!(describe(&rx.recv().await.unwrap()), "text:d");
+        assert_eq!(sink.stats().dropped, 0);
+    }
+
+    #[tokio::test]
+    async fn closed_receiver_is_ignored() {
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        let mut sink = EventSink::new(tx);
+        sink.send(StreamEvent::Text("x".to_string())).await;
+        sink.send(status()).await;
+        assert_eq!(sink.stats(), StreamStats::default());
+    }
+}
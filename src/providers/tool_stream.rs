@@ -0,0 +1,131 @@
+//! 流式 tool call 组装：按 index 累积 id / 名称 / 参数片段
+//!
+//! 部分 Provider 逐 token 推送 tool call 参数，大参数（如整份 `file_write`）要等很久才结束。
+//! 名称一到就返回给调用方，由其发送 `ToolStatus { Preparing }`，UI 可以立即显示"正在准备"；
+//! 参数片段只拼接，流结束时统一解析（中途的 JSON 本来就不完整）。
+
+use super::traits::ToolCall;
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+    /// 已返回过名称（每个 tool call 只提示一次）
+    announced: bool,
+}
+
+/// 流式 tool call 累积器（compatible / claude 共用）
+#[derive(Debug, Default)]
+pub struct ToolCallAssembler {
+    calls: Vec<PartialToolCall>,
+}
+
+impl ToolCallAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加第 `index` 个 tool call 的增量；名称首次已知时返回名称
+    pub fn push(
+        &mut self,
+        index: usize,
+        id: Option<&str>,
+        name: Option<&str>,
+        arguments_delta: &str,
+    ) -> Option<String> {
+        if self.calls.len() <= index {
+            self.calls.resize_with(index + 1, PartialToolCall::default);
+        }
+        let call = &mut self.calls[index];
+        if let Some(id) = id.filter(|s| !s.is_empty()) {
+            call.id = id.to_string();
+        }
+        if let Some(name) = name.filter(|s| !s.is_empty()) {
+            call.name = name.to_string();
+        }
+        call.arguments.push_str(arguments_delta);
+
+        if call.announced || call.name.is_empty() {
+            return None;
+        }
+        call.announced = true;
+        Some(call.name.clone())
+    }
+
+    /// 已开始的 tool call 数量
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// 结束：解析参数（空串或非法 JSON 视为 `{}`），跳过既无 id 也无名称的空位
+    pub fn finish(self) -> Vec<ToolCall> {
+        self.calls
+            .into_iter()
+            .filter(|c| !c.id.is_empty() || !c.name.is_empty())
+            .map(|c| ToolCall {
+                id: c.id,
+                name: c.name,
+                arguments: serde_json::from_str(&c.arguments)
+                    .unwrap_or(serde_json::Value::Object(serde_json::Map::new())),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunked_deltas_assemble_arguments_and_announce_name_once() {
+        let mut asm = ToolCallAssembler::new();
+        // OpenAI 风格：第一个增量带 id + 名称，之后只有参数片段；两个 tool call 交错
+        assert_eq!(
+            asm.push(0, Some("call_1"), Some("file_write"), ""),
+            Some("file_write".to_string())
+        );
+        let content = "line one\\nline \\\"two\\\"";
+        let args = format!(r#"{{"path": "notes.md", "content": "{}"}}"#, content);
+        let (head, tail) = args.split_at(args.len() / 2);
+        for piece in head.as_bytes().chunks(3) {
+            let piece = std::str::from_utf8(piece).unwrap();
+            assert_eq!(asm.push(0, None, None, piece), None);
+        }
+        assert_eq!(
+            asm.push(1, Some("call_2"), None, r#"{"comm"#),
+            None,
+            "名称未到时不提示"
+        );
+        assert_eq!(
+            asm.push(1, None, Some("shell"), r#"and": "ls"}"#),
+            Some("shell".to_string())
+        );
+        assert_eq!(asm.push(0, None, Some("file_write"), tail), None);
+
+        let calls = asm.finish();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].name, "file_write");
+        assert_eq!(calls[0].arguments["path"], "notes.md");
+        assert_eq!(calls[0].arguments["content"], "line one\nline \"two\"");
+        assert_eq!(calls[1].name, "shell");
+        assert_eq!(calls[1].arguments, serde_json::json!({ "command": "ls" }));
+    }
+
+    #[test]
+    fn gaps_are_skipped_and_bad_json_becomes_empty_object() {
+        let mut asm = ToolCallAssembler::new();
+        asm.push(2, Some("call_x"), Some("grep"), r#"{"pattern": "#);
+        assert_eq!(asm.len(), 3);
+
+        let calls = asm.finish();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "grep");
+        assert_eq!(calls[0].arguments, serde_json::json!({}));
+    }
+}
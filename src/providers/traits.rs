@@ -3,6 +3,8 @@ use color_eyre::eyre::Result;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 
+pub use super::params::GenerationParams;
+
 /// 聊天消息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
@@ -11,6 +13,13 @@ pub struct ChatMessage {
     /// DeepSeek/MiniMax 思考模式的推理内容（同一 Turn 内多轮 tool call 需回传）
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reasoning_content: Option<String>,
+    /// 所属 Turn（一次用户输入到最终回复为一个 Turn，由 Agent 分配；0 表示未标记，如旧版历史）
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub turn: u64,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
 }
 
 /// 模型请求的工具调用
@@ -22,12 +31,52 @@ pub struct ToolCall {
 }
 
 /// 模型响应
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatResponse {
     pub text: Option<String>,
     /// DeepSeek/MiniMax 思考模式的推理内容
     pub reasoning_content: Option<String>,
     pub tool_calls: Vec<ToolCall>,
+    /// 实际给出这条响应的 Provider / 模型（由 ReliableProvider 填写，其它 Provider 为 None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+/// 响应来源：重试 / fallback 之后最终是谁回答的
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Provider 名（与 `[providers.xxx]` 的 key 一致）
+    pub provider: String,
+    pub model: String,
+    /// 得到这条响应前失败的请求次数（含已放弃的 Provider）
+    pub retries: u32,
+    /// 是否由 fallback Provider 给出
+    pub fallback: bool,
+}
+
+impl Provenance {
+    /// REPL 回复后的提示行，如 "answered by deepseek/deepseek-chat after 1 retry"
+    pub fn describe(&self, english: bool) -> String {
+        let mut text = if english {
+            format!("answered by {}/{}", self.provider, self.model)
+        } else {
+            format!("由 {}/{} 回答", self.provider, self.model)
+        };
+        match (english, self.retries) {
+            (_, 0) => {}
+            (true, 1) => text.push_str(" after 1 retry"),
+            (true, n) => text.push_str(&format!(" after {} retries", n)),
+            (false, n) => text.push_str(&format!("（重试 {} 次）", n)),
+        }
+        if self.fallback {
+            text.push_str(if english {
+                " (fallback)"
+            } else {
+                "（fallback）"
+            });
+        }
+        text
+    }
 }
 
 /// 对话消息（支持多轮 tool call 交互）
@@ -42,14 +91,38 @@ pub enum ConversationMessage {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         reasoning_content: Option<String>,
         tool_calls: Vec<ToolCall>,
+        /// 所属 Turn（同 `ChatMessage::turn`）
+        #[serde(default, skip_serializing_if = "is_zero")]
+        turn: u64,
     },
     /// 工具执行结果
     ToolResult {
         tool_call_id: String,
         content: String,
+        /// 所属 Turn（同 `ChatMessage::turn`）
+        #[serde(default, skip_serializing_if = "is_zero")]
+        turn: u64,
     },
 }
 
+impl ConversationMessage {
+    /// 所属 Turn（0 表示未标记）
+    pub fn turn(&self) -> u64 {
+        match self {
+            Self::Chat(msg) => msg.turn,
+            Self::AssistantToolCalls { turn, .. } | Self::ToolResult { turn, .. } => *turn,
+        }
+    }
+
+    /// 设置所属 Turn
+    pub fn set_turn(&mut self, value: u64) {
+        match self {
+            Self::Chat(msg) => msg.turn = value,
+            Self::AssistantToolCalls { turn, .. } | Self::ToolResult { turn, .. } => *turn = value,
+        }
+    }
+}
+
 /// 工具规格描述（传递给 LLM）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolSpec {
@@ -75,15 +148,64 @@ pub enum StreamEvent {
         name: String,
         status: ToolStatusKind,
     },
+    /// 工具结构化输出（紧随 Success 状态发送，用于 diff 高亮 / 表格渲染）
+    ToolOutput {
+        name: String,
+        kind: crate::tools::ToolOutputKind,
+        /// 工具原始文本输出（与 LLM 看到的一致）
+        content: String,
+    },
+    /// 工具结果的用户视图（紧随 Success / Failed 状态发送；由 UI 按 tool_verbosity 选择展示）
+    ToolFeedback {
+        name: String,
+        /// 工具给出的简短摘要（`ToolResult::user_facing`）
+        summary: Option<String>,
+        /// 完整输出（与 LLM 看到的一致）
+        content: String,
+    },
+    /// 会话目标进度（`goal_update` 记录的子目标 / 计划），UI 以暗色状态行显示
+    GoalUpdate(String),
+    /// 给用户的一次性提醒（如工作区有未提交改动），UI 以警告行显示，不进入 history
+    Notice(String),
+    /// 推理模型的思考过程增量（`reasoning_content`），UI 可选择显示
+    Reasoning(String),
     /// LLM 思考中（等待首个 token）
     Thinking,
     /// 流结束，返回完整响应
     Done(ChatResponse),
 }
 
+/// 流式响应在结束标记（`[DONE]` / finish_reason / `message_stop`）之前中断
+///
+/// 已收到的文本已经通过 `StreamEvent::Text` 发给 UI，`ReliableProvider` 据此续写而不是从头重来。
+#[derive(Debug, Clone)]
+pub struct StreamInterrupted {
+    /// 中断前收到的文本
+    pub partial_text: String,
+    /// 中断前是否已收到 tool call 片段（参数不完整，无法续写，只能整体重试）
+    pub has_tool_calls: bool,
+    /// 中断原因（连接错误或"连接在结束标记前关闭"）
+    pub reason: String,
+}
+
+impl std::fmt::Display for StreamInterrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "流式响应中断（已接收 {} 字符）: {}",
+            self.partial_text.chars().count(),
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for StreamInterrupted {}
+
 /// 工具执行状态类型
 #[derive(Debug, Clone)]
 pub enum ToolStatusKind {
+    /// 模型正在流式生成该工具的参数（名称已知，参数未完整）
+    Preparing,
     /// 开始执行
     Running(String),
     /// 执行成功（摘要）
@@ -101,7 +223,7 @@ pub trait Provider: Send + Sync {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
     ) -> Result<ChatResponse>;
 
     /// 流式调用（逐步发送 StreamEvent，最终返回完整响应）
@@ -111,17 +233,45 @@ pub trait Provider: Send + Sync {
         messages: &[ConversationMessage],
         tools: &[ToolSpec],
         model: &str,
-        temperature: f64,
+        params: GenerationParams,
         tx: mpsc::Sender<StreamEvent>,
     ) -> Result<ChatResponse> {
-        let resp = self
-            .chat_with_tools(messages, tools, model, temperature)
-            .await?;
+        let resp = self.chat_with_tools(messages, tools, model, params).await?;
         // 将完整文本作为一次性 Text 事件发送
+        let mut sink = super::EventSink::new(tx);
         if let Some(text) = &resp.text {
-            let _ = tx.send(StreamEvent::Text(text.clone())).await;
+            sink.send(StreamEvent::Text(text.clone())).await;
         }
-        let _ = tx.send(StreamEvent::Done(resp.clone())).await;
+        sink.send(StreamEvent::Done(resp.clone())).await;
         Ok(resp)
     }
 }
+
+/// 允许将共享的 Arc<dyn Provider> 直接装箱传给 Agent（AgentFactory 缓存 Provider 场景）
+#[async_trait]
+impl Provider for std::sync::Arc<dyn Provider> {
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        params: GenerationParams,
+    ) -> Result<ChatResponse> {
+        (**self)
+            .chat_with_tools(messages, tools, model, params)
+            .await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        params: GenerationParams,
+        tx: mpsc::Sender<StreamEvent>,
+    ) -> Result<ChatResponse> {
+        (**self)
+            .chat_stream(messages, tools, model, params, tx)
+            .await
+    }
+}
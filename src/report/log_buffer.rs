@@ -0,0 +1,142 @@
+//! 内存日志环形缓冲
+//!
+//! `RingBufferLayer` 作为 tracing Layer 挂在全局 subscriber 上，只保留最近 N 行
+//! 格式化后的日志，供故障报告读取（无需解析按天滚动的日志文件）。
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// 故障报告保留的日志行数
+pub const LOG_BUFFER_CAPACITY: usize = 50;
+
+/// 最近日志行的环形缓冲
+#[derive(Debug)]
+pub struct LogRingBuffer {
+    capacity: usize,
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl LogRingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// 进程级共享实例（容量 `LOG_BUFFER_CAPACITY`）
+    pub fn global() -> Arc<LogRingBuffer> {
+        static GLOBAL: OnceLock<Arc<LogRingBuffer>> = OnceLock::new();
+        GLOBAL
+            .get_or_init(|| Arc::new(LogRingBuffer::new(LOG_BUFFER_CAPACITY)))
+            .clone()
+    }
+
+    /// 追加一行，超出容量时丢弃最旧的一行
+    pub fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// 按时间顺序返回当前缓冲内容
+    ///
+    /// panic hook 中调用：锁被占用时返回空，避免在持锁线程 panic 时死锁。
+    pub fn snapshot(&self) -> Vec<String> {
+        match self.lines.try_lock() {
+            Ok(lines) => lines.iter().cloned().collect(),
+            Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner().iter().cloned().collect(),
+            Err(std::sync::TryLockError::WouldBlock) => Vec::new(),
+        }
+    }
+}
+
+/// 把事件写入 `LogRingBuffer` 的 tracing Layer
+pub struct RingBufferLayer {
+    buffer: Arc<LogRingBuffer>,
+}
+
+impl RingBufferLayer {
+    pub fn new(buffer: Arc<LogRingBuffer>) -> Self {
+        Self { buffer }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for RingBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let meta = event.metadata();
+        self.buffer.push(format!(
+            "{} {:>5} {}: {}{}",
+            chrono::Local::now().format("%H:%M:%S%.3f"),
+            meta.level(),
+            meta.target(),
+            visitor.message,
+            visitor.fields
+        ));
+    }
+}
+
+/// 与 fmt Layer 相同的单行格式：message 在前，其余字段以 ` key=value` 追加
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn keeps_only_latest_lines() {
+        let buffer = LogRingBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(format!("line {}", i));
+        }
+        assert_eq!(buffer.snapshot(), vec!["line 2", "line 3", "line 4"]);
+    }
+
+    #[test]
+    fn layer_formats_message_and_fields() {
+        let buffer = Arc::new(LogRingBuffer::new(10));
+        let subscriber =
+            tracing_subscriber::registry().with(RingBufferLayer::new(Arc::clone(&buffer)));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(provider = "deepseek", "请求失败 {}", 503);
+        });
+
+        let lines = buffer.snapshot();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains(" WARN "));
+        assert!(lines[0].contains("请求失败 503"));
+        assert!(lines[0].ends_with(" provider=deepseek"));
+    }
+}
@@ -0,0 +1,306 @@
+//! 本地故障报告
+//!
+//! panic 或用户执行 `/report` 时，在 `~/.rrclaw/reports/<时间戳>/` 下写一份脱敏的报告包，
+//! 方便提 issue 时附上完整上下文。报告只写本地磁盘，从不上传。
+//!
+//! 报告包内容：
+//! - `summary.txt` — 版本、prompt 模板版本、系统、Provider/模型、触发原因（panic 信息或最近一次错误）
+//! - `config.json` — 密钥字段已脱敏的配置
+//! - `logs.txt` — 内存环形缓冲中最近 50 行日志（见 `log_buffer`）
+//! - `history.json` — 最后一个 Turn 的对话历史
+//!
+//! 所有文本都经过 `security::redact::Redactor`：配置中的已知密钥值和常见密钥形态都会被替换。
+
+pub mod log_buffer;
+
+pub use log_buffer::{LogRingBuffer, RingBufferLayer};
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use color_eyre::eyre::{Context, Result};
+
+use crate::agent::Agent;
+use crate::config::Config;
+use crate::providers::ConversationMessage;
+use crate::security::redact::Redactor;
+
+/// 生成报告所需的运行上下文（随对话推进更新）
+#[derive(Debug, Clone, Default)]
+pub struct IncidentContext {
+    pub config: Option<Config>,
+    pub provider: String,
+    pub model: String,
+    /// 最后一个 Turn 的消息
+    pub last_turn: Vec<ConversationMessage>,
+    /// 最近一次 Turn 级错误
+    pub last_error: Option<String>,
+}
+
+impl IncidentContext {
+    /// 从 Agent 当前状态刷新 Provider/模型与最后一个 Turn
+    pub fn update_from_agent(&mut self, agent: &Agent) {
+        self.provider = agent.provider_name().to_string();
+        self.model = agent.model().to_string();
+        self.last_turn = last_turn(agent.history()).to_vec();
+    }
+}
+
+fn context() -> &'static Mutex<IncidentContext> {
+    static CONTEXT: OnceLock<Mutex<IncidentContext>> = OnceLock::new();
+    CONTEXT.get_or_init(Default::default)
+}
+
+fn with_context<R>(f: impl FnOnce(&mut IncidentContext) -> R) -> R {
+    let mut ctx = context().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut ctx)
+}
+
+/// 记录启动时的配置与 Provider/模型
+pub fn set_session(config: &Config, provider: &str, model: &str) {
+    with_context(|ctx| {
+        ctx.config = Some(config.clone());
+        ctx.provider = provider.to_string();
+        ctx.model = model.to_string();
+    });
+}
+
+/// 每轮对话结束后记录 Agent 状态；`error` 为本轮失败原因（成功时清除上次的错误）
+pub fn record_turn(agent: &Agent, error: Option<String>) {
+    with_context(|ctx| {
+        ctx.update_from_agent(agent);
+        ctx.last_error = error;
+    });
+}
+
+/// 刷新 Agent 状态（保留最近一次错误），用于 `/report` 生成前
+pub fn record_context(agent: &Agent) {
+    with_context(|ctx| ctx.update_from_agent(agent));
+}
+
+/// 用全局上下文和日志缓冲写一份报告
+pub fn write_report(reports_dir: &Path, reason: &str) -> Result<PathBuf> {
+    let ctx = with_context(|ctx| ctx.clone());
+    write_report_with(
+        reports_dir,
+        reason,
+        &ctx,
+        &LogRingBuffer::global().snapshot(),
+    )
+}
+
+/// 写报告包，返回报告目录
+pub fn write_report_with(
+    reports_dir: &Path,
+    reason: &str,
+    ctx: &IncidentContext,
+    logs: &[String],
+) -> Result<PathBuf> {
+    let redactor = ctx
+        .config
+        .as_ref()
+        .map(Redactor::from_config)
+        .unwrap_or_default();
+
+    let dir = unique_report_dir(reports_dir);
+    std::fs::create_dir_all(&dir)
+        .wrap_err_with(|| format!("创建报告目录失败: {}", dir.display()))?;
+
+    let mut summary = format!(
+        "rrclaw {}\nprompt: v{}\nos: {} {}\ntime: {}\nprovider: {}\nmodel: {}\n\n[reason]\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        crate::agent::prompts::PROMPT_VERSION,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        chrono::Local::now().to_rfc3339(),
+        ctx.provider,
+        ctx.model,
+        reason.trim_end()
+    );
+    if let Some(error) = &ctx.last_error {
+        summary.push_str(&format!("\n[last error]\n{}\n", error));
+    }
+    write_file(&dir.join("summary.txt"), &redactor.redact(&summary))?;
+
+    if let Some(config) = &ctx.config {
+        let json = serde_json::to_string_pretty(&redactor.redact_config(config))
+            .wrap_err("序列化配置失败")?;
+        write_file(&dir.join("config.json"), &redactor.redact(&json))?;
+    }
+
+    write_file(&dir.join("logs.txt"), &redactor.redact(&logs.join("\n")))?;
+
+    let history = serde_json::to_string_pretty(&ctx.last_turn).wrap_err("序列化对话历史失败")?;
+    write_file(&dir.join("history.json"), &redactor.redact(&history))?;
+
+    Ok(dir)
+}
+
+/// 安装 panic hook：先写报告并提示路径，再交给原 hook（color-eyre）打印 panic 信息
+pub fn install_panic_hook(reports_dir: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let reason = format!(
+            "panic: {}\n\n{}",
+            info,
+            std::backtrace::Backtrace::force_capture()
+        );
+        // panic 可能发生在持有上下文锁的线程上：拿不到锁就只写日志和原因
+        let ctx = context()
+            .try_lock()
+            .map(|ctx| ctx.clone())
+            .unwrap_or_default();
+        match write_report_with(
+            &reports_dir,
+            &reason,
+            &ctx,
+            &LogRingBuffer::global().snapshot(),
+        ) {
+            Ok(dir) => eprintln!("{}", saved_message(&dir)),
+            Err(e) => eprintln!("rrclaw: failed to write crash report: {:#}", e),
+        }
+        previous(info);
+    }));
+}
+
+/// 报告已写入的提示（含分享前检查提醒）
+pub fn saved_message(dir: &Path) -> String {
+    if Config::get_language().is_english() {
+        format!(
+            "Report saved to {}\nNothing was sent anywhere. Please review the files before attaching them to an issue.",
+            dir.display()
+        )
+    } else {
+        format!(
+            "报告已保存到 {}\n报告未发送到任何地方。附到 issue 前请先检查文件内容。",
+            dir.display()
+        )
+    }
+}
+
+/// 最后一个 Turn 的消息（history 为空时返回空切片）
+fn last_turn(history: &[ConversationMessage]) -> &[ConversationMessage] {
+    match crate::agent::turns::turn_spans(history).pop() {
+        Some(span) => &history[span],
+        None => &[],
+    }
+}
+
+/// `<reports_dir>/<YYYYMMDD-HHMMSS>`，同一秒内多次生成时追加序号
+fn unique_report_dir(reports_dir: &Path) -> PathBuf {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut dir = reports_dir.join(&stamp);
+    let mut n = 1;
+    while dir.exists() {
+        n += 1;
+        dir = reports_dir.join(format!("{}-{}", stamp, n));
+    }
+    dir
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    std::fs::write(path, content).wrap_err_with(|| format!("写入失败: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderConfig;
+    use crate::providers::ChatMessage;
+
+    const API_KEY: &str = "sk-live-0123456789abcdef";
+    const PASTED_TOKEN: &str = "ghp_pasted_secret_value";
+
+    fn chat(role: &str, content: &str, turn: u64) -> ConversationMessage {
+        ConversationMessage::Chat(ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            reasoning_content: None,
+            turn,
+        })
+    }
+
+    fn incident() -> IncidentContext {
+        let mut config = Config::default();
+        config.providers.insert(
+            "deepseek".to_string(),
+            ProviderConfig {
+                base_url: "https://api.deepseek.com/v1".to_string(),
+                api_key: API_KEY.to_string(),
+                model: "deepseek-chat".to_string(),
+                auth_style: None,
+                headers: [("X-Token".to_string(), PASTED_TOKEN.to_string())].into(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        let history = vec![
+            chat("user", "第一轮", 1),
+            chat("assistant", "ok", 1),
+            chat(
+                "user",
+                &format!("我的 key 是 {}，token 是 {}", API_KEY, PASTED_TOKEN),
+                2,
+            ),
+            chat("assistant", "收到", 2),
+        ];
+        IncidentContext {
+            config: Some(config),
+            provider: "deepseek".to_string(),
+            model: "deepseek-chat".to_string(),
+            last_turn: last_turn(&history).to_vec(),
+            last_error: Some(format!("401 Unauthorized: key {}", API_KEY)),
+        }
+    }
+
+    fn read_all(dir: &Path) -> String {
+        let mut all = String::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            all.push_str(&std::fs::read_to_string(entry.unwrap().path()).unwrap());
+        }
+        all
+    }
+
+    #[test]
+    fn report_never_contains_secrets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let logs = vec![format!("DEBUG rrclaw: Authorization: Bearer {}", API_KEY)];
+
+        let dir = write_report_with(tmp.path(), "turn failed", &incident(), &logs).unwrap();
+
+        for file in ["summary.txt", "config.json", "logs.txt", "history.json"] {
+            assert!(dir.join(file).exists(), "{} missing", file);
+        }
+        let all = read_all(&dir);
+        assert!(!all.contains(API_KEY));
+        assert!(!all.contains(PASTED_TOKEN));
+        assert!(all.contains("deepseek-chat"));
+        assert!(all.contains(env!("CARGO_PKG_VERSION")));
+        let summary = std::fs::read_to_string(dir.join("summary.txt")).unwrap();
+        assert!(summary.contains(&format!(
+            "prompt: v{}",
+            crate::agent::prompts::PROMPT_VERSION
+        )));
+    }
+
+    #[test]
+    fn report_includes_only_last_turn() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = write_report_with(tmp.path(), "manual", &incident(), &[]).unwrap();
+        let history = std::fs::read_to_string(dir.join("history.json")).unwrap();
+        assert!(!history.contains("第一轮"));
+        assert!(history.contains("收到"));
+    }
+
+    #[test]
+    fn reports_in_same_second_get_distinct_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ctx = IncidentContext::default();
+        let a = write_report_with(tmp.path(), "a", &ctx, &[]).unwrap();
+        let b = write_report_with(tmp.path(), "b", &ctx, &[]).unwrap();
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,170 @@
+//! Routine 执行前置条件（`condition`）
+//!
+//! 例如“CI 通过才部署”：触发时先检查条件，不满足则记录一条 `skipped: condition not met`
+//! 执行日志，不创建 Agent。条件本身出错（命令无法启动、请求失败、超时）也视为不满足。
+//! shell 条件与 Routine 同样以非交互方式执行，不经过 `allowed_commands` 白名单——
+//! 条件只能由 config.toml 或 `/routine add` 设置，不向 LLM 的 routine 工具开放。
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 条件检查的超时（shell 命令与 HTTP 请求共用）
+const CONDITION_TIMEOUT: Duration = Duration::from_secs(60);
+/// 日志中保留的命令输出 / 错误字符数
+const DETAIL_CHARS: usize = 200;
+
+/// 前置条件：TOML 中写作 `condition = { shell = "..." }` 或 `condition = { http = "https://..." }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutineCondition {
+    /// `sh -c` 执行，退出码为 0 时满足
+    Shell(String),
+    /// GET 请求，返回 200 时满足
+    Http(String),
+}
+
+/// 条件检查结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionOutcome {
+    pub met: bool,
+    /// 退出码 / HTTP 状态 / 错误原因，写入跳过记录
+    pub detail: String,
+}
+
+impl RoutineCondition {
+    /// 列表展示用的简短描述
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Shell(command) => format!("shell: {}", command),
+            Self::Http(url) => format!("http: {}", url),
+        }
+    }
+
+    /// 存入 routines.db 的文本（JSON）；`None` 存空字符串
+    pub(crate) fn to_column(condition: Option<&Self>) -> String {
+        condition
+            .and_then(|c| serde_json::to_string(c).ok())
+            .unwrap_or_default()
+    }
+
+    /// 从 routines.db 文本还原；空字符串或无法解析时为 None
+    pub(crate) fn from_column(text: &str) -> Option<Self> {
+        (!text.is_empty())
+            .then(|| serde_json::from_str(text).ok())
+            .flatten()
+    }
+
+    /// 执行检查
+    pub async fn evaluate(&self) -> ConditionOutcome {
+        match self {
+            Self::Shell(command) => check_shell(command).await,
+            Self::Http(url) => check_http(url).await,
+        }
+    }
+}
+
+async fn check_shell(command: &str) -> ConditionOutcome {
+    let output = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(CONDITION_TIMEOUT, output).await {
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let mut detail = match output.status.code() {
+                Some(code) => format!("exit code {}", code),
+                None => "terminated by signal".to_string(),
+            };
+            if !output.status.success() && !stderr.trim().is_empty() {
+                detail.push_str(": ");
+                detail.extend(stderr.trim().chars().take(DETAIL_CHARS));
+            }
+            ConditionOutcome {
+                met: output.status.success(),
+                detail,
+            }
+        }
+        Ok(Err(e)) => ConditionOutcome {
+            met: false,
+            detail: format!("failed to run: {}", e),
+        },
+        Err(_) => ConditionOutcome {
+            met: false,
+            detail: format!("timed out after {}s", CONDITION_TIMEOUT.as_secs()),
+        },
+    }
+}
+
+async fn check_http(url: &str) -> ConditionOutcome {
+    let client = match reqwest::Client::builder()
+        .timeout(CONDITION_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            return ConditionOutcome {
+                met: false,
+                detail: format!("http client error: {}", e),
+            }
+        }
+    };
+    match client.get(url).send().await {
+        Ok(resp) => ConditionOutcome {
+            met: resp.status() == reqwest::StatusCode::OK,
+            detail: format!("HTTP {}", resp.status().as_u16()),
+        },
+        Err(e) => ConditionOutcome {
+            met: false,
+            detail: format!("request failed: {}", e)
+                .chars()
+                .take(DETAIL_CHARS)
+                .collect(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn condition_toml_and_column_roundtrip() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            condition: RoutineCondition,
+        }
+        // 与 Config::load_from_path 相同，经 figment 解析
+        use figment::providers::Format;
+        let parsed: Wrapper = figment::Figment::from(figment::providers::Toml::string(
+            r#"condition = { http = "https://ci.example.com/status" }"#,
+        ))
+        .extract()
+        .unwrap();
+        assert_eq!(
+            parsed.condition,
+            RoutineCondition::Http("https://ci.example.com/status".to_string())
+        );
+
+        let shell = RoutineCondition::Shell("test -f ready".to_string());
+        let column = RoutineCondition::to_column(Some(&shell));
+        assert_eq!(RoutineCondition::from_column(&column), Some(shell));
+        assert_eq!(RoutineCondition::from_column(""), None);
+        assert_eq!(RoutineCondition::to_column(None), "");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn shell_condition_uses_exit_code() {
+        let ok = RoutineCondition::Shell("true".to_string()).evaluate().await;
+        assert!(ok.met);
+        assert_eq!(ok.detail, "exit code 0");
+
+        let failed = RoutineCondition::Shell("echo red >&2; exit 3".to_string())
+            .evaluate()
+            .await;
+        assert!(!failed.met);
+        assert_eq!(failed.detail, "exit code 3: red");
+    }
+}
@@ -0,0 +1,171 @@
+//! 调度锁：同一数据目录只允许一个进程调度 Routine
+//!
+//! 终端里的 `rrclaw agent` 与另一个 `rrclaw agent`（或其他共用数据目录的进程）同时运行时，
+//! 两边都打开同一个 routines.db，各自调度会让每个 Routine 重复执行。
+//! 启动调度前先对 `<data_dir>/routines.lock` 加 `flock(LOCK_EX | LOCK_NB)`：
+//! 拿到锁的进程负责调度，并把自己的 pid 写进锁文件；没拿到的进程进入旁观模式（只读）。
+//!
+//! 锁随文件描述符释放，进程崩溃后内核自动解锁；残留的锁文件只剩一个过期 pid，
+//! 下一个进程加锁成功后直接覆盖（"过期锁清理"）。锁文件本身不删除，
+//! 避免"删除后他人重新创建"导致两个进程各锁一个 inode。
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Result};
+
+/// 锁文件名（位于数据目录下）
+pub const LOCK_FILE: &str = "routines.lock";
+
+/// 已持有的调度锁；drop 时清空 pid 并释放
+#[derive(Debug)]
+pub struct SchedulerLock {
+    file: File,
+    path: PathBuf,
+    /// 加锁前锁文件里残留的 pid（上一个持有者未正常退出）
+    stale_pid: Option<u32>,
+}
+
+/// 加锁结果
+#[derive(Debug)]
+pub enum LockOutcome {
+    Acquired(SchedulerLock),
+    /// 锁被其他进程持有；pid 取自锁文件（读不到时为 None）
+    Held {
+        pid: Option<u32>,
+    },
+}
+
+impl SchedulerLock {
+    /// 尝试获取 `dir` 下的调度锁（不阻塞）
+    pub fn try_acquire(dir: &Path) -> Result<LockOutcome> {
+        std::fs::create_dir_all(dir).map_err(|e| eyre!("创建数据目录失败: {}", e))?;
+        let path = dir.join(LOCK_FILE);
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| eyre!("打开调度锁 {} 失败: {}", path.display(), e))?;
+
+        if !try_lock_exclusive(&file)? {
+            return Ok(LockOutcome::Held {
+                pid: read_pid(&path),
+            });
+        }
+
+        let stale_pid = read_pid(&path).filter(|&pid| pid != std::process::id());
+        let mut lock = SchedulerLock {
+            file,
+            path,
+            stale_pid,
+        };
+        lock.write_pid()?;
+        Ok(LockOutcome::Acquired(lock))
+    }
+
+    /// 锁文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 被清理的过期 pid（上一个持有者崩溃后留下）
+    pub fn stale_pid(&self) -> Option<u32> {
+        self.stale_pid
+    }
+
+    fn write_pid(&mut self) -> Result<()> {
+        use std::io::{Seek, Write};
+        self.file
+            .set_len(0)
+            .and_then(|_| self.file.rewind())
+            .and_then(|_| write!(self.file, "{}", std::process::id()))
+            .and_then(|_| self.file.flush())
+            .map_err(|e| eyre!("写入调度锁失败: {}", e))
+    }
+}
+
+impl Drop for SchedulerLock {
+    fn drop(&mut self) {
+        // 先清空 pid 再随 fd 关闭释放锁，旁观进程不会读到已退出的 pid
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// 锁文件中记录的 pid
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+}
+
+/// 非阻塞加排他锁；已被持有时返回 false
+#[cfg(unix)]
+fn try_lock_exclusive(file: &File) -> Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: fd 在 `file` 存活期间有效；flock 不涉及内存访问
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if ret == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(eyre!("加调度锁失败: {}", err))
+    }
+}
+
+/// 非 Unix 平台不做跨进程协调（daemon 同样只支持 Unix）
+#[cfg(not(unix))]
+fn try_lock_exclusive(_file: &File) -> Result<bool> {
+    Ok(true)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_acquire_sees_holder_pid_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let LockOutcome::Acquired(first) = SchedulerLock::try_acquire(dir.path()).unwrap() else {
+            panic!("空目录应能加锁");
+        };
+        assert_eq!(first.stale_pid(), None);
+
+        // flock 按打开的文件描述区分，同一进程内第二次打开同样拿不到锁
+        match SchedulerLock::try_acquire(dir.path()).unwrap() {
+            LockOutcome::Held { pid } => assert_eq!(pid, Some(std::process::id())),
+            LockOutcome::Acquired(_) => panic!("锁已被持有"),
+        }
+
+        drop(first);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(LOCK_FILE)).unwrap(),
+            "",
+            "释放时清空 pid"
+        );
+        assert!(matches!(
+            SchedulerLock::try_acquire(dir.path()).unwrap(),
+            LockOutcome::Acquired(_)
+        ));
+    }
+
+    #[test]
+    fn stale_lock_file_is_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        // 上一个持有者崩溃：锁文件留着 pid，但没有任何进程持有 flock
+        std::fs::write(dir.path().join(LOCK_FILE), "4194000").unwrap();
+
+        let LockOutcome::Acquired(lock) = SchedulerLock::try_acquire(dir.path()).unwrap() else {
+            panic!("过期锁应被接管");
+        };
+        assert_eq!(lock.stale_pid(), Some(4194000));
+        assert_eq!(
+            std::fs::read_to_string(lock.path()).unwrap(),
+            std::process::id().to_string()
+        );
+    }
+}
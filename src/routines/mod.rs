@@ -15,7 +15,44 @@
 //! message = "用中文总结今天的工作计划"
 //! channel = "cli"
 //! enabled = true
+//! catch_up = true   # 错过的最近一次触发在启动时补跑
+//!
+//! [[routines.jobs]]
+//! name = "nightly"
+//! schedule = "0 23 * * *"
+//! chain = ["backup_notes", "morning_brief"]   # 依次执行其他 Routine，汇总结果后发送
 //! ```
+//!
+//! 链式 Routine 执行前展开为叶子任务列表，检测循环引用并限制嵌套深度（`MAX_CHAIN_DEPTH`）。
+//!
+//! cron 按 `[routines] timezone`（IANA 时区名，默认系统本地时区）解释，见 `timezone.rs`。
+//!
+//! 设置了 `condition`（shell 退出码 / HTTP 200）的 Routine 触发时先检查条件，不满足则只记录跳过，
+//! 见 `condition.rs`。消息中的 `{date}` / `{time}` / `{weekday}` / `{env:VAR}` 在触发时展开（`{env:VAR}` 仅限
+//! config.toml 中的 Routine），见 `template.rs`。
+//!
+//! `channel = "webhook"` 的结果 POST 到 `channel_target`（SSRF 检查、失败重试一次后降级为 cli），
+//! 见 `webhook.rs`。
+//!
+//! `/routine simulate <name>` 以 Supervised 模式试跑一次（不写日志、不发送结果），见 `simulate.rs`。
+//!
+//! 正在执行的 Routine 在 `running` 中登记取消令牌：同名 Routine 不会重复执行，
+//! `/routine cancel <name>` 触发令牌后，Agent 放弃等待中的 Provider 调用、跳过未执行的工具，
+//! 执行日志记为 `cancelled by user`。
+//!
+//! 同一数据目录只有一个进程调度：`start` 先获取 `routines.lock`（见 `lock.rs`），
+//! 锁被其他进程持有时进入旁观模式（`SchedulerRole::Standby`），不注册 cron job，
+//! 手动执行与增删改返回错误，列表从数据库刷新。
+
+pub mod condition;
+pub mod lock;
+pub mod simulate;
+pub mod template;
+pub mod timezone;
+pub mod webhook;
+
+pub use condition::RoutineCondition;
+pub use lock::{LockOutcome, SchedulerLock};
 
 use std::sync::Arc;
 
@@ -24,10 +61,101 @@ use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use tokio_cron_scheduler::{Job, JobScheduler};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
-use crate::config::Config;
+use crate::agent::{AgentFactory, CANCELLED_MESSAGE};
+use crate::config::{Config, LiveConfig, RrclawPaths};
+use crate::memory::migrations::{migrate, Migration, Step};
 use crate::memory::Memory;
+use crate::providers::offline::OfflineState;
+use timezone::RoutineTimezone;
+
+/// routines.db schema 版本（只追加，不修改已发布的版本）
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "routines + routines_log",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS routines (
+                name        TEXT PRIMARY KEY,
+                schedule    TEXT NOT NULL,
+                message     TEXT NOT NULL,
+                channel     TEXT NOT NULL DEFAULT 'cli',
+                enabled     INTEGER NOT NULL DEFAULT 1,
+                created_at  TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS routines_log (
+                id           INTEGER PRIMARY KEY AUTOINCREMENT,
+                routine_name TEXT NOT NULL,
+                started_at   TEXT NOT NULL,
+                finished_at  TEXT NOT NULL,
+                success      INTEGER NOT NULL,
+                output       TEXT NOT NULL DEFAULT '',
+                error        TEXT
+            );",
+        )],
+    },
+    Migration {
+        version: 2,
+        description: "routines_log.deferred（离线推迟标记）",
+        steps: &[Step::AddColumn {
+            table: "routines_log",
+            column: "deferred",
+            definition: "INTEGER NOT NULL DEFAULT 0",
+        }],
+    },
+    Migration {
+        version: 3,
+        description: "routine_state（历史方法连续失败计数）",
+        steps: &[Step::Sql(
+            "CREATE TABLE IF NOT EXISTS routine_state (
+                name              TEXT PRIMARY KEY,
+                approach_failures INTEGER NOT NULL DEFAULT 0,
+                updated_at        TEXT NOT NULL
+            );",
+        )],
+    },
+    Migration {
+        version: 4,
+        description: "routines.catch_up（启动时补跑错过的触发）",
+        steps: &[Step::AddColumn {
+            table: "routines",
+            column: "catch_up",
+            definition: "INTEGER NOT NULL DEFAULT 0",
+        }],
+    },
+    Migration {
+        version: 5,
+        description: "routines.chain（链式执行的子任务，逗号分隔）",
+        steps: &[Step::AddColumn {
+            table: "routines",
+            column: "chain",
+            definition: "TEXT NOT NULL DEFAULT ''",
+        }],
+    },
+    Migration {
+        version: 6,
+        description: "routines.condition（执行前置条件，JSON）",
+        steps: &[Step::AddColumn {
+            table: "routines",
+            column: "condition",
+            definition: "TEXT NOT NULL DEFAULT ''",
+        }],
+    },
+    Migration {
+        version: 7,
+        description: "routines.channel_target（webhook URL 等通道目标）",
+        steps: &[Step::AddColumn {
+            table: "routines",
+            column: "channel_target",
+            definition: "TEXT NOT NULL DEFAULT ''",
+        }],
+    },
+];
+
+/// 链式 Routine 的最大嵌套层数（含自身）
+pub const MAX_CHAIN_DEPTH: usize = 4;
 
 // ─── 辅助函数 ─────────────────────────────────────────────────────────────────
 
@@ -66,20 +194,75 @@ pub struct Routine {
     /// 执行结果发送到哪个通道：
     ///   "cli"       → 打印到 stdout（带 [Routine] 前缀）
     ///   "telegram"  → 通过 Telegram Bot 发送（需配置 bot_token）
+    ///   "webhook"   → POST JSON 到 `channel_target`
     #[serde(default = "default_channel")]
     pub channel: String,
+    /// 通道目标：webhook 的 URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_target: Option<String>,
     /// 是否启用（false 时跳过调度）
     #[serde(default = "default_enabled")]
     pub enabled: bool,
     /// 来源：config.toml 配置 还是 /routine add 动态创建
     #[serde(default)]
     pub source: RoutineSource,
+    /// 启动时若错过了最近一次触发（机器休眠 / 未运行），补跑一次
+    #[serde(default)]
+    pub catch_up: bool,
+    /// 依次执行的其他 Routine 名称；非空时不发送 `message`，汇总子任务结果后发送
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<String>,
+    /// 执行前置条件：不满足时跳过本次执行（记录 `skipped: condition not met`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<RoutineCondition>,
+}
+
+impl Routine {
+    /// 由 config.toml 的 `[[routines.jobs]]` 构造静态 Routine
+    pub fn from_config(job: &crate::config::RoutineJobConfig) -> Self {
+        Self {
+            name: job.name.clone(),
+            schedule: job.schedule.clone(),
+            message: job.message.clone(),
+            channel: job.channel.clone(),
+            enabled: job.enabled,
+            source: RoutineSource::Config,
+            catch_up: job.catch_up,
+            chain: job.chain.clone(),
+            condition: job.condition.clone(),
+            channel_target: job.channel_target.clone(),
+        }
+    }
+
+    /// 列表展示用的任务内容：链式 Routine 显示子任务顺序，否则为 message；有前置条件时附在末尾
+    pub fn summary(&self) -> String {
+        let body = if self.chain.is_empty() {
+            self.message.clone()
+        } else {
+            format!("chain: {}", self.chain.join(" → "))
+        };
+        match &self.condition {
+            Some(condition) => format!("{} [if {}]", body, condition.describe()),
+            None => body,
+        }
+    }
 }
 
 fn default_channel() -> String {
     "cli".to_string()
 }
 
+/// 当前配置下 Routine 可用的结果通道：cli 总是可用，telegram 需配置 `[telegram]`，
+/// webhook 需同时提供 `channel_target`
+pub fn available_channels(config: &Config) -> Vec<&'static str> {
+    let mut channels = vec!["cli"];
+    if config.telegram.is_some() {
+        channels.push("telegram");
+    }
+    channels.push("webhook");
+    channels
+}
+
 fn default_enabled() -> bool {
     true
 }
@@ -93,6 +276,30 @@ pub enum RoutineSource {
     Dynamic, // 来自 /routine add 命令（持久化到 SQLite）
 }
 
+/// `/routine export` 导出格式版本
+const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// 导出文件（JSON）：只包含动态 Routine，config.toml 里的静态 Routine 随配置文件迁移
+#[derive(Debug, Serialize, Deserialize)]
+struct RoutineExport {
+    version: u32,
+    exported_at: String,
+    routines: Vec<Routine>,
+}
+
+/// `import_routines` 结果
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// 新增的 Routine
+    pub imported: Vec<String>,
+    /// overwrite 模式下覆盖的同名 Routine
+    pub replaced: Vec<String>,
+    /// 同名已存在（未开启 overwrite，或来自 config.toml）而跳过的 Routine
+    pub skipped: Vec<String>,
+    /// 校验或保存失败的 Routine 及原因
+    pub failed: Vec<(String, String)>,
+}
+
 /// 单次执行记录
 #[derive(Debug, Clone)]
 pub struct RoutineExecution {
@@ -102,12 +309,147 @@ pub struct RoutineExecution {
     pub success: bool,
     pub output_preview: String, // 前 200 字符
     pub error: Option<String>,
+    /// 因离线延后执行（不算失败，联网后自动重放）
+    pub deferred: bool,
+}
+
+/// 正在执行的 Routine：取消令牌 + 开始时间（`/routine list` 显示已运行时长）
+struct RunningRoutine {
+    token: CancellationToken,
+    started: std::time::Instant,
+}
+
+/// 执行期间占用 `running` 中的登记，结束（含提前返回）时释放
+struct RunGuard<'a> {
+    engine: &'a RoutineEngine,
+    name: String,
+    token: CancellationToken,
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        self.engine.running.lock().unwrap().remove(&self.name);
+    }
+}
+
+/// 已运行时长的简短表示：`42s` / `3m05s` / `1h02m`
+pub fn format_elapsed(elapsed: std::time::Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// 合并静态与动态 Routine：同名时动态（SQLite）版本替换静态版本并记录警告
+fn merge_routines(mut routines: Vec<Routine>, dynamic: Vec<Routine>) -> Vec<Routine> {
+    for routine in dynamic {
+        match routines.iter().position(|r| r.name == routine.name) {
+            Some(index) => {
+                warn!(
+                    "Routine '{}' 同时定义在 config.toml 和数据库中，使用数据库中的版本",
+                    routine.name
+                );
+                routines[index] = routine;
+            }
+            None => routines.push(routine),
+        }
+    }
+    routines
+}
+
+/// 解析逗号分隔的 chain（SQLite 列与 `/routine add --chain`）
+pub(crate) fn split_chain(chain: &str) -> Vec<String> {
+    chain
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 将链式 Routine 展开为按执行顺序排列的叶子任务（嵌套的链递归展开）
+///
+/// 拒绝：引用不存在的 Routine、循环引用（含引用自身）、嵌套超过 `MAX_CHAIN_DEPTH` 层。
+pub fn resolve_chain(routines: &[Routine], name: &str) -> Result<Vec<Routine>> {
+    fn visit(
+        routines: &[Routine],
+        name: &str,
+        path: &mut Vec<String>,
+        leaves: &mut Vec<Routine>,
+    ) -> Result<()> {
+        if path.iter().any(|n| n == name) {
+            return Err(eyre!(
+                "Routine 链存在循环引用: {} → {}",
+                path.join(" → "),
+                name
+            ));
+        }
+        if path.len() >= MAX_CHAIN_DEPTH {
+            return Err(eyre!(
+                "Routine 链嵌套超过 {} 层: {} → {}",
+                MAX_CHAIN_DEPTH,
+                path.join(" → "),
+                name
+            ));
+        }
+        let routine =
+            routines
+                .iter()
+                .find(|r| r.name == name)
+                .ok_or_else(|| match path.last() {
+                    Some(parent) => {
+                        eyre!("Routine '{}' 的 chain 引用了不存在的 '{}'", parent, name)
+                    }
+                    None => eyre!("Routine '{}' 不存在", name),
+                })?;
+        if routine.chain.is_empty() {
+            leaves.push(routine.clone());
+            return Ok(());
+        }
+        path.push(name.to_string());
+        for child in &routine.chain {
+            visit(routines, child, path, leaves)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
+    let mut leaves = Vec::new();
+    visit(routines, name, &mut Vec::new(), &mut leaves)?;
+    Ok(leaves)
 }
 
 // ─── RoutineEngine ───────────────────────────────────────────────────────────
 
 /// 定时任务引擎
 ///
+/// 本进程在数据目录中的调度角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulerRole {
+    /// 持有调度锁，负责定时触发
+    Scheduler,
+    /// 锁由其他进程持有（pid 读不到时为 None），本进程只读
+    Standby { pid: Option<u32> },
+}
+
+impl SchedulerRole {
+    /// 旁观模式的提示；调度者返回 None
+    pub fn standby_notice(&self) -> Option<String> {
+        match self {
+            SchedulerRole::Scheduler => None,
+            SchedulerRole::Standby { pid: Some(pid) } => Some(format!(
+                "定时任务由 pid {} 的 rrclaw 进程调度，本进程不执行 Routine",
+                pid
+            )),
+            SchedulerRole::Standby { pid: None } => {
+                Some("定时任务由另一个 rrclaw 进程调度，本进程不执行 Routine".to_string())
+            }
+        }
+    }
+}
+
 /// 持有调度器和所有 Routine 配置，负责启动调度和执行任务。
 ///
 /// # 线程安全
@@ -128,6 +470,30 @@ pub struct RoutineEngine {
     pub trigger_count: Arc<std::sync::atomic::AtomicUsize>,
     /// routine name → scheduler job UUID（用于 delete/disable 时精确注销 cron job）
     job_uuids: std::sync::RwLock<std::collections::HashMap<String, uuid::Uuid>>,
+    /// 共享离线标记（默认全局实例，与交互式 Provider 共享）
+    offline: Arc<OfflineState>,
+    /// 因离线延后、等待联网后重放的 Routine 名称
+    deferred: std::sync::Mutex<Vec<String>>,
+    /// cron 表达式与执行日志时间戳使用的时区
+    timezone: RoutineTimezone,
+    /// Routine Agent 的 data / logs / 配置文件位置（跟随 `--config`）
+    paths: RrclawPaths,
+    /// Routine Agent 的工作目录（默认当前目录，`rrclaw agent --workspace` 时为指定目录）
+    workspace_dir: Option<std::path::PathBuf>,
+    /// 全局暂停：定时触发、启动补跑和离线重放都跳过，已注册的 job 保留
+    paused: std::sync::atomic::AtomicBool,
+    /// 取消暂停时唤醒离线重放任务（暂停期间联网留下的队列不必等下一次联网）
+    resumed: tokio::sync::Notify,
+    /// Routine Agent 工厂（延迟创建，见 `agent_factory`）
+    agent_factory: std::sync::OnceLock<AgentFactory>,
+    /// 正在执行的 Routine（name → 取消令牌），由 `RunGuard` 登记与释放
+    running: std::sync::Mutex<std::collections::HashMap<String, RunningRoutine>>,
+    /// 调度角色（`claim_scheduler` 后确定；未声明时按调度者处理）
+    role: std::sync::OnceLock<SchedulerRole>,
+    /// Routine 库是否为进程私有的内存库（临时会话 / 数据目录不可用），此时不参与数据目录的调度协调
+    private_db: bool,
+    /// 调度者持有的锁，随引擎一起释放
+    scheduler_lock: std::sync::Mutex<Option<SchedulerLock>>,
 }
 
 impl RoutineEngine {
@@ -139,7 +505,7 @@ impl RoutineEngine {
     /// - `memory`: 共享 Memory（Routine Agent 和主 Agent 共享记忆）
     /// - `db_path`: SQLite 数据库路径（存储动态 Routine + 执行日志）
     pub async fn new(
-        mut routines: Vec<Routine>,
+        routines: Vec<Routine>,
         config: Arc<Config>,
         memory: Arc<dyn Memory>,
         db_path: &std::path::Path,
@@ -147,16 +513,27 @@ impl RoutineEngine {
         // 初始化数据库
         let conn =
             Connection::open(db_path).map_err(|e| eyre!("打开 Routines 数据库失败: {}", e))?;
+        crate::memory::sqlite::configure_connection(&conn)?;
         Self::init_db(&conn)?;
 
-        // 从 SQLite 加载动态创建的 Routine（合并到 config 来的列表）
+        // 从 SQLite 加载动态创建的 Routine（合并到 config 来的列表，同名时动态版本优先）
         let dynamic_routines = Self::load_dynamic_routines(&conn)?;
-        routines.extend(dynamic_routines);
+        let routines = merge_routines(routines, dynamic_routines);
 
         let scheduler = JobScheduler::new()
             .await
             .map_err(|e| eyre!("创建 JobScheduler 失败: {}", e))?;
 
+        // 时区写错时不放弃全部定时任务，退回系统本地时区
+        let timezone = RoutineTimezone::from_config(config.routines.timezone.as_deref())
+            .unwrap_or_else(|e| {
+                warn!("{}，改用系统本地时区", e);
+                RoutineTimezone::Local
+            });
+
+        let paths = RrclawPaths::resolve()?;
+        let paused = std::sync::atomic::AtomicBool::new(config.routines.paused);
+
         Ok(Self {
             routines: std::sync::RwLock::new(routines),
             scheduler,
@@ -167,41 +544,203 @@ impl RoutineEngine {
             cli_notifier: std::sync::OnceLock::new(),
             trigger_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             job_uuids: std::sync::RwLock::new(std::collections::HashMap::new()),
+            offline: OfflineState::global(),
+            deferred: std::sync::Mutex::new(Vec::new()),
+            timezone,
+            paths,
+            workspace_dir: None,
+            paused,
+            resumed: tokio::sync::Notify::new(),
+            agent_factory: std::sync::OnceLock::new(),
+            running: std::sync::Mutex::new(std::collections::HashMap::new()),
+            role: std::sync::OnceLock::new(),
+            private_db: db_path == std::path::Path::new(":memory:"),
+            scheduler_lock: std::sync::Mutex::new(None),
+        })
+    }
+
+    /// 使用指定的目录布局（测试或隔离场景用）
+    pub fn with_paths(mut self, paths: RrclawPaths) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// Routine Agent 的工作目录（默认当前目录）
+    pub fn with_workspace_dir(mut self, workspace_dir: std::path::PathBuf) -> Self {
+        self.workspace_dir = Some(workspace_dir);
+        self
+    }
+
+    /// Routine Agent 使用的目录布局
+    pub fn paths(&self) -> &RrclawPaths {
+        &self.paths
+    }
+
+    /// 获取数据目录的调度锁，确定本进程的调度角色（重复调用返回首次结果）
+    ///
+    /// 加锁出错（如数据目录不可写）时不协调，照常调度。
+    /// 内存库的引擎只调度自己的 Routine，与数据目录中的 `routines.db` 无关：不加锁、不创建数据目录。
+    pub fn claim_scheduler(&self) -> SchedulerRole {
+        if let Some(role) = self.role.get() {
+            return *role;
+        }
+        if self.private_db {
+            return *self.role.get_or_init(|| SchedulerRole::Scheduler);
+        }
+        let role = match SchedulerLock::try_acquire(&self.paths.data_dir()) {
+            Ok(LockOutcome::Acquired(lock)) => {
+                if let Some(pid) = lock.stale_pid() {
+                    info!("清理过期调度锁（pid {} 已退出）", pid);
+                }
+                *self.scheduler_lock.lock().unwrap() = Some(lock);
+                SchedulerRole::Scheduler
+            }
+            Ok(LockOutcome::Held { pid }) => SchedulerRole::Standby { pid },
+            Err(e) => {
+                warn!("{}，不做多进程协调", e);
+                SchedulerRole::Scheduler
+            }
+        };
+        *self.role.get_or_init(|| role)
+    }
+
+    /// 当前调度角色
+    pub fn role(&self) -> SchedulerRole {
+        self.role.get().copied().unwrap_or(SchedulerRole::Scheduler)
+    }
+
+    /// 旁观模式下拒绝执行与增删改
+    fn ensure_scheduler(&self) -> Result<()> {
+        match self.role().standby_notice() {
+            Some(notice) => Err(eyre!("{}。请在该进程中操作（list / logs 仍可用）", notice)),
+            None => Ok(()),
+        }
+    }
+
+    /// 旁观模式下从数据库重新加载动态 Routine（调度进程可能已增删）
+    pub async fn reload_if_standby(&self) {
+        if self.role() == SchedulerRole::Scheduler {
+            return;
+        }
+        let dynamic = {
+            let db = self.db.lock().await;
+            match Self::load_dynamic_routines(&db) {
+                Ok(dynamic) => dynamic,
+                Err(e) => {
+                    warn!("重新加载 Routine 失败: {}", e);
+                    return;
+                }
+            }
+        };
+        let mut routines = self.routines.write().unwrap();
+        let from_config = routines
+            .iter()
+            .filter(|r| r.source == RoutineSource::Config)
+            .cloned()
+            .collect();
+        *routines = merge_routines(from_config, dynamic);
+    }
+
+    /// 是否处于全局暂停状态
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// 暂停 / 恢复全部定时触发（不注销 job，只在触发时跳过；仅影响本次运行，
+    /// 需要重启后仍保持暂停时设置 `[routines] paused = true`）
+    pub fn set_paused(&self, paused: bool) {
+        self.paused
+            .store(paused, std::sync::atomic::Ordering::Relaxed);
+        info!("Routine 调度已{}", if paused { "暂停" } else { "恢复" });
+        if !paused {
+            self.resumed.notify_waiters();
+        }
+    }
+
+    /// 请求取消正在执行的 Routine；未在执行时返回 false
+    ///
+    /// 协作式取消：等待中的 Provider 调用立即放弃，已在执行的工具运行完后停止，不再重试。
+    pub fn cancel_routine(&self, name: &str) -> bool {
+        match self.running.lock().unwrap().get(name) {
+            Some(run) => {
+                info!("请求取消 Routine '{}'", name);
+                run.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 正在执行的 Routine 已运行的时长（未在执行时为 None）
+    pub fn running_elapsed(&self, name: &str) -> Option<std::time::Duration> {
+        self.running
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|run| run.started.elapsed())
+    }
+
+    /// 登记一次执行；同名 Routine 已在执行时拒绝
+    fn begin_run(&self, name: &str) -> Result<RunGuard<'_>> {
+        let mut running = self.running.lock().unwrap();
+        if let Some(run) = running.get(name) {
+            return Err(eyre!(
+                "Routine '{}' 正在执行（已运行 {}），可用 /routine cancel {} 取消",
+                name,
+                format_elapsed(run.started.elapsed()),
+                name
+            ));
+        }
+        let token = CancellationToken::new();
+        running.insert(
+            name.to_string(),
+            RunningRoutine {
+                token: token.clone(),
+                started: std::time::Instant::now(),
+            },
+        );
+        Ok(RunGuard {
+            engine: self,
+            name: name.to_string(),
+            token,
         })
     }
 
+    /// 当前配置下可用的结果通道（见 [`available_channels`]）
+    pub fn available_channels(&self) -> Vec<&'static str> {
+        available_channels(&self.config)
+    }
+
+    /// 调度使用的时区
+    pub fn timezone(&self) -> RoutineTimezone {
+        self.timezone
+    }
+
+    /// 使用指定的离线状态（测试或隔离场景用）
+    pub fn with_offline_state(mut self, offline: Arc<OfflineState>) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// 当前因离线而等待重放的 Routine
+    pub fn deferred_routines(&self) -> Vec<String> {
+        self.deferred.lock().unwrap().clone()
+    }
+
     /// 初始化 SQLite 表
     fn init_db(conn: &Connection) -> Result<()> {
-        conn.execute_batch(
-            r#"
-            CREATE TABLE IF NOT EXISTS routines (
-                name        TEXT PRIMARY KEY,
-                schedule    TEXT NOT NULL,
-                message     TEXT NOT NULL,
-                channel     TEXT NOT NULL DEFAULT 'cli',
-                enabled     INTEGER NOT NULL DEFAULT 1,
-                created_at  TEXT NOT NULL
-            );
-
-            CREATE TABLE IF NOT EXISTS routines_log (
-                id           INTEGER PRIMARY KEY AUTOINCREMENT,
-                routine_name TEXT NOT NULL,
-                started_at   TEXT NOT NULL,
-                finished_at  TEXT NOT NULL,
-                success      INTEGER NOT NULL,
-                output       TEXT NOT NULL DEFAULT '',
-                error        TEXT
-            );
-            "#,
-        )
-        .map_err(|e| eyre!("初始化 Routines 数据库失败: {}", e))?;
+        migrate(conn, "routines.db", MIGRATIONS)
+            .map_err(|e| eyre!("初始化 Routines 数据库失败: {:#}", e))?;
         Ok(())
     }
 
     /// 从 SQLite 加载动态 Routine（/routine add 创建的）
     fn load_dynamic_routines(conn: &Connection) -> Result<Vec<Routine>> {
         let mut stmt = conn
-            .prepare("SELECT name, schedule, message, channel, enabled FROM routines")
+            .prepare(
+                "SELECT name, schedule, message, channel, enabled, catch_up, chain, condition, \
+                 channel_target FROM routines",
+            )
             .map_err(|e| eyre!("查询动态 Routines 失败: {}", e))?;
 
         let routines = stmt
@@ -213,6 +752,10 @@ impl RoutineEngine {
                     channel: row.get(3)?,
                     enabled: row.get::<_, i32>(4)? != 0,
                     source: RoutineSource::Dynamic,
+                    catch_up: row.get::<_, i32>(5)? != 0,
+                    chain: split_chain(&row.get::<_, String>(6)?),
+                    condition: RoutineCondition::from_column(&row.get::<_, String>(7)?),
+                    channel_target: Some(row.get::<_, String>(8)?).filter(|t| !t.is_empty()),
                 })
             })
             .map_err(|e| eyre!("解析动态 Routines 失败: {}", e))?
@@ -240,25 +783,49 @@ impl RoutineEngine {
     /// job handler 会在每次触发时：
     /// 1. 递增 trigger_count（供集成测试验证 scheduler 真实触发）
     /// 2. 调用 execute_routine 运行任务
+    ///
+    /// cron 按 `self.timezone` 解释。注意 tokio-cron-scheduler 在创建 job 时固定 UTC 偏移，
+    /// 夏令时切换后需重启才会按新偏移触发。
     fn make_job(engine: Arc<Self>, name: String, schedule: &str) -> Result<Job> {
+        match engine.timezone {
+            RoutineTimezone::Local => Self::make_job_tz(engine, name, schedule, chrono::Local),
+            RoutineTimezone::Named(tz) => Self::make_job_tz(engine, name, schedule, tz),
+        }
+    }
+
+    fn make_job_tz<TZ: chrono::TimeZone>(
+        engine: Arc<Self>,
+        name: String,
+        schedule: &str,
+        timezone: TZ,
+    ) -> Result<Job> {
         let schedule_6field = convert_5field_to_6field(schedule);
         let name_for_err = name.clone(); // 保留一份用于错误信息（name 会被 move 进闭包）
-        Job::new_async(&schedule_6field, move |_uuid, _lock| {
+        Job::new_async_tz(&schedule_6field, timezone, move |_uuid, _lock| {
             let engine = Arc::clone(&engine);
             let name = name.clone();
             Box::pin(async move {
-                engine
-                    .trigger_count
-                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                info!("Routine 触发: {}", name);
-                if let Err(e) = engine.execute_routine(&name).await {
-                    error!("Routine 执行失败: {} - {}", name, e);
-                }
+                engine.on_scheduled_trigger(&name).await;
             })
         })
         .map_err(|e| eyre!("创建 cron job 失败 ({}): {}", name_for_err, e))
     }
 
+    /// cron job 触发时调用；全局暂停时跳过执行，返回是否真正执行
+    async fn on_scheduled_trigger(&self, name: &str) -> bool {
+        self.trigger_count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if self.is_paused() {
+            info!("Routine 调度已暂停，跳过触发: {}", name);
+            return false;
+        }
+        info!("Routine 触发: {}", name);
+        if let Err(e) = self.execute_routine(name).await {
+            error!("Routine 执行失败: {} - {}", name, e);
+        }
+        true
+    }
+
     /// 将单个 Routine 添加到调度器（供 persist_add_routine 调用）
     async fn schedule_job(self: Arc<Self>, routine: &Routine) -> Result<()> {
         if !routine.enabled {
@@ -300,13 +867,22 @@ impl RoutineEngine {
         }
 
         info!(
-            "已调度 Routine: {} (schedule={})",
-            routine.name, routine.schedule
+            "已调度 Routine: {} (schedule={}, tz={})",
+            routine.name,
+            routine.schedule,
+            self.timezone.name()
         );
         Ok(())
     }
 
     pub async fn start(self: Arc<Self>) -> Result<()> {
+        if let Some(notice) = self.claim_scheduler().standby_notice() {
+            info!("{}，跳过调度", notice);
+            return Ok(());
+        }
+        Self::spawn_deferred_replay(Arc::clone(&self));
+        Self::spawn_catch_up(Arc::clone(&self));
+
         let enabled_routines: Vec<Routine> = self
             .routines
             .read()
@@ -354,10 +930,124 @@ impl RoutineEngine {
         Ok(())
     }
 
+    /// 后台任务：每次网络恢复时重放离线期间延后的 Routine
+    ///
+    /// 重放中途再次断网时 Routine 会重新入队，此时的联网通知可能在本任务执行 Routine 期间发出；
+    /// 因此每轮先登记通知、再按当前状态判断是否需要重放，而不是只依赖通知本身。
+    fn spawn_deferred_replay(engine: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                let online = engine.offline.online_transition();
+                let resumed = engine.resumed.notified();
+                tokio::pin!(online, resumed);
+                online.as_mut().enable();
+                resumed.as_mut().enable();
+
+                // 离线或暂停时保留队列，等联网或取消暂停后再重放
+                if engine.offline.is_offline()
+                    || engine.is_paused()
+                    || engine.deferred.lock().unwrap().is_empty()
+                {
+                    tokio::select! {
+                        _ = online => {}
+                        _ = resumed => {}
+                    }
+                    continue;
+                }
+                let names: Vec<String> = std::mem::take(&mut *engine.deferred.lock().unwrap());
+                for name in names {
+                    info!("网络已恢复，重放延后的 Routine: {}", name);
+                    if let Err(e) = engine.execute_routine(&name).await {
+                        error!("重放 Routine 失败: {} - {}", name, e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// 后台任务：补跑 `catch_up = true` 且在停机期间错过触发的 Routine
+    ///
+    /// 以最近一次成功执行为基准，只补跑错过的最近一次（错过多次也只跑一次）；
+    /// 从未成功执行过的 Routine 没有基准，不补跑。
+    fn spawn_catch_up(engine: Arc<Self>) {
+        let candidates: Vec<Routine> = engine
+            .routines
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|r| r.enabled && r.catch_up)
+            .cloned()
+            .collect();
+        if candidates.is_empty() || engine.is_paused() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let now = chrono::Utc::now();
+            for routine in candidates {
+                let Some(last_success) = engine.last_success_at(&routine.name).await else {
+                    continue;
+                };
+                let Some(missed) =
+                    missed_occurrence(&routine.schedule, engine.timezone, last_success, now)
+                else {
+                    continue;
+                };
+                info!(
+                    "Routine '{}' 错过了 {} 的触发，启动时补跑",
+                    routine.name,
+                    engine.timezone.format_short(missed)
+                );
+                if let Err(e) = engine.execute_routine(&routine.name).await {
+                    error!("补跑 Routine 失败: {} - {}", routine.name, e);
+                }
+            }
+        });
+    }
+
+    /// 最近一次成功执行的开始时间
+    async fn last_success_at(&self, name: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        let db = self.db.lock().await;
+        let started_at: String = db
+            .query_row(
+                "SELECT started_at FROM routines_log \
+                 WHERE routine_name = ?1 AND success = 1 ORDER BY id DESC LIMIT 1",
+                params![name],
+                |row| row.get(0),
+            )
+            .ok()?;
+        chrono::DateTime::parse_from_rfc3339(&started_at)
+            .ok()
+            .map(|t| t.with_timezone(&chrono::Utc))
+    }
+
+    /// 离线时延后执行：记录 deferred 日志并加入重放队列
+    async fn defer_routine(&self, name: &str, started_at: String) -> String {
+        {
+            let mut deferred = self.deferred.lock().unwrap();
+            if !deferred.iter().any(|n| n == name) {
+                deferred.push(name.to_string());
+            }
+        }
+        warn!("离线中，Routine '{}' 延后到网络恢复后执行", name);
+        self.log_execution(RoutineExecution {
+            routine_name: name.to_string(),
+            started_at,
+            finished_at: self.timezone.now_rfc3339(),
+            success: false,
+            output_preview: String::new(),
+            error: Some("deferred: offline".to_string()),
+            deferred: true,
+        })
+        .await;
+        format!("Routine '{}' 已延后：当前离线，网络恢复后自动执行。", name)
+    }
+
     /// 执行单个 Routine（含超时保护 + 失败重试）
     ///
     /// 对外暴露，供 `/routine run <name>` 命令手动触发。
     pub async fn execute_routine(&self, name: &str) -> Result<String> {
+        self.ensure_scheduler()?;
         let routine = self
             .routines
             .read()
@@ -370,6 +1060,21 @@ impl RoutineEngine {
         if !routine.enabled {
             return Ok(format!("Routine '{}' 已禁用，跳过执行。", name));
         }
+        let run = self.begin_run(name)?;
+        if !routine.chain.is_empty() {
+            return self.execute_chain(&routine, &run.token).await;
+        }
+        self.execute_single(&routine, true, &run.token).await
+    }
+
+    /// 执行单个（非链式）Routine；`deliver` 为 false 时不发送结果（由链式父任务汇总发送）
+    async fn execute_single(
+        &self,
+        routine: &Routine,
+        deliver: bool,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        let name = routine.name.as_str();
 
         // Routine 级别最大重试次数：来自 reliability 配置（默认 3，每次间隔 5 分钟）
         // 测试时可将 config.reliability.max_retries 设为 1 以跳过重试等待
@@ -377,27 +1082,64 @@ impl RoutineEngine {
         const RETRY_DELAY_SECS: u64 = 300; // 5 分钟
         const TIMEOUT_SECS: u64 = 300; // 5 分钟超时
 
-        let started_at = chrono::Utc::now().to_rfc3339();
+        let started_at = self.timezone.now_rfc3339();
+        let timer = std::time::Instant::now();
         let mut last_error = String::new();
 
+        // 离线时不执行，直接延后（避免无意义的重试与失败记录）
+        if self.offline.is_offline() {
+            crate::metrics::record_routine(name, "deferred", timer.elapsed());
+            return Ok(self.defer_routine(name, started_at).await);
+        }
+
+        // 前置条件不满足：记录跳过，不创建 Agent、不发送结果
+        if let Some(condition) = &routine.condition {
+            let outcome = condition.evaluate().await;
+            if !outcome.met {
+                let output = format!("skipped: condition not met ({})", outcome.detail);
+                info!("Routine '{}' {}", name, output);
+                self.log_execution(RoutineExecution {
+                    routine_name: name.to_string(),
+                    started_at,
+                    finished_at: self.timezone.now_rfc3339(),
+                    success: true,
+                    output_preview: output.chars().take(200).collect(),
+                    error: None,
+                    deferred: false,
+                })
+                .await;
+                crate::metrics::record_routine(name, "skipped", timer.elapsed());
+                return Ok(output);
+            }
+        }
+
         for attempt in 0..max_retries {
             if attempt > 0 {
                 info!(
                     "Routine '{}' 第 {} 次重试，等待 {}s...",
                     name, attempt, RETRY_DELAY_SECS
                 );
-                tokio::time::sleep(std::time::Duration::from_secs(RETRY_DELAY_SECS)).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(RETRY_DELAY_SECS)) => {}
+                    _ = cancel.cancelled() => {}
+                }
+            }
+            if cancel.is_cancelled() {
+                return Err(self.record_cancelled(name, started_at, timer).await);
             }
 
+            // 每次尝试重新读取历史方法：重试期间连续失败达到阈值后即改为探索新方法
+            let (message, approach_injected) = self.prepare_message(routine).await;
             match tokio::time::timeout(
                 std::time::Duration::from_secs(TIMEOUT_SECS),
-                self.run_once(&routine),
+                self.run_once(routine, &message, cancel),
             )
             .await
             {
                 Ok(Ok(output)) => {
-                    let finished_at = chrono::Utc::now().to_rfc3339();
+                    let finished_at = self.timezone.now_rfc3339();
                     info!("Routine '{}' 执行成功", name);
+                    self.reset_approach_failures(name).await;
                     self.log_execution(RoutineExecution {
                         routine_name: name.to_string(),
                         started_at,
@@ -405,11 +1147,18 @@ impl RoutineEngine {
                         success: true,
                         output_preview: output.chars().take(200).collect(),
                         error: None,
+                        deferred: false,
                     })
                     .await;
-                    self.send_result(&routine, &output).await;
+                    crate::metrics::record_routine(name, "success", timer.elapsed());
+                    if deliver {
+                        self.send_result(routine, &output).await;
+                    }
                     return Ok(output);
                 }
+                Ok(Err(_)) if cancel.is_cancelled() => {
+                    return Err(self.record_cancelled(name, started_at, timer).await);
+                }
                 Ok(Err(e)) => {
                     warn!(
                         "Routine '{}' 执行出错（第 {} 次）: {}",
@@ -418,6 +1167,14 @@ impl RoutineEngine {
                         e
                     );
                     last_error = e.to_string();
+                    // 执行中途断网：转为延后，不再等待重试
+                    if self.offline.is_offline() {
+                        crate::metrics::record_routine(name, "deferred", timer.elapsed());
+                        return Ok(self.defer_routine(name, started_at).await);
+                    }
+                    if approach_injected {
+                        self.record_approach_failure(name).await;
+                    }
                 }
                 Err(_) => {
                     warn!(
@@ -427,12 +1184,15 @@ impl RoutineEngine {
                         TIMEOUT_SECS
                     );
                     last_error = format!("执行超时（超过 {} 秒）", TIMEOUT_SECS);
+                    if approach_injected {
+                        self.record_approach_failure(name).await;
+                    }
                 }
             }
         }
 
         // 全部重试失败
-        let finished_at = chrono::Utc::now().to_rfc3339();
+        let finished_at = self.timezone.now_rfc3339();
         error!(
             "Routine '{}' 全部 {} 次重试均失败，最后错误: {}",
             name, max_retries, last_error
@@ -444,108 +1204,218 @@ impl RoutineEngine {
             success: false,
             output_preview: String::new(),
             error: Some(last_error.clone()),
+            deferred: false,
         })
         .await;
+        crate::metrics::record_routine(name, "failure", timer.elapsed());
         let error_msg = format!(
             "[Routine: {}] 执行失败（{} 次重试后）: {}",
             name, max_retries, last_error
         );
-        self.send_result(&routine, &error_msg).await;
+        if deliver {
+            self.send_result(routine, &error_msg).await;
+        }
         Err(eyre!("{}", error_msg))
     }
 
-    /// 创建独立 Agent 并执行一次任务消息
-    async fn run_once(&self, routine: &Routine) -> Result<String> {
-        use crate::agent::Agent;
-        use crate::providers::{create_provider, ReliableProvider, RetryConfig};
-        use crate::security::SecurityPolicy;
-        use crate::tools::create_tools;
-        use std::sync::Arc;
-
-        let provider_key = &self.config.default.provider;
-        let provider_config = self
-            .config
-            .providers
-            .get(provider_key)
-            .ok_or_else(|| eyre!("Provider '{}' 未配置", provider_key))?;
-
-        let retry_config = RetryConfig {
-            max_retries: self.config.reliability.max_retries,
-            initial_backoff_ms: self.config.reliability.initial_backoff_ms,
-            ..Default::default()
+    /// 被用户取消：记录日志（error = `cancelled by user`），不发送结果
+    async fn record_cancelled(
+        &self,
+        name: &str,
+        started_at: String,
+        timer: std::time::Instant,
+    ) -> color_eyre::Report {
+        info!("Routine '{}' 已被用户取消", name);
+        self.log_execution(RoutineExecution {
+            routine_name: name.to_string(),
+            started_at,
+            finished_at: self.timezone.now_rfc3339(),
+            success: false,
+            output_preview: String::new(),
+            error: Some(CANCELLED_MESSAGE.to_string()),
+            deferred: false,
+        })
+        .await;
+        crate::metrics::record_routine(name, "cancelled", timer.elapsed());
+        eyre!("Routine '{}' 已取消", name)
+    }
+
+    /// 依次执行链中的叶子任务并汇总结果；任一子任务失败不中断后续任务，整体记为失败
+    async fn execute_chain(&self, routine: &Routine, cancel: &CancellationToken) -> Result<String> {
+        let steps = {
+            let routines = self.routines.read().unwrap();
+            resolve_chain(&routines, &routine.name)?
         };
+        let started_at = self.timezone.now_rfc3339();
+        let timer = std::time::Instant::now();
+        info!(
+            "链式 Routine '{}' 开始执行 {} 个子任务",
+            routine.name,
+            steps.len()
+        );
 
-        // Arc<dyn Provider> 用于 HttpRequestTool 的 mini-LLM 提取
-        let raw_provider_for_arc = create_provider(provider_config);
-        let provider_arc: Arc<dyn crate::providers::Provider> = Arc::new(ReliableProvider::new(
-            raw_provider_for_arc,
-            retry_config.clone(),
-        ));
+        let mut sections = Vec::with_capacity(steps.len());
+        let mut failed = Vec::new();
+        for (index, step) in steps.iter().enumerate() {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let header = format!("[{}/{}] {}", index + 1, steps.len(), step.name);
+            if !step.enabled {
+                sections.push(format!("{} — 已禁用，跳过", header));
+                continue;
+            }
+            match self.execute_single(step, false, cancel).await {
+                Ok(output) => sections.push(format!("{} ✓\n{}", header, output)),
+                Err(e) => {
+                    failed.push(step.name.clone());
+                    sections.push(format!("{} ✗\n{}", header, e));
+                }
+            }
+        }
+        if cancel.is_cancelled() {
+            return Err(self
+                .record_cancelled(&routine.name, started_at, timer)
+                .await);
+        }
+        let summary = sections.join("\n\n");
 
-        // Box<dyn Provider> 用于 Agent（从 Arc 克隆一份）
-        let provider: Box<dyn crate::providers::Provider> = Box::new(ReliableProvider::new(
-            create_provider(provider_config),
-            retry_config,
-        ));
+        let success = failed.is_empty();
+        self.log_execution(RoutineExecution {
+            routine_name: routine.name.clone(),
+            started_at,
+            finished_at: self.timezone.now_rfc3339(),
+            success,
+            output_preview: summary.chars().take(200).collect(),
+            error: (!success).then(|| format!("子任务失败: {}", failed.join(", "))),
+            deferred: false,
+        })
+        .await;
+        crate::metrics::record_routine(
+            &routine.name,
+            if success { "success" } else { "failure" },
+            timer.elapsed(),
+        );
+        self.send_result(routine, &summary).await;
+        if success {
+            Ok(summary)
+        } else {
+            Err(eyre!(
+                "[Routine: {}] {} 个子任务失败（{}）\n\n{}",
+                routine.name,
+                failed.len(),
+                failed.join(", "),
+                summary
+            ))
+        }
+    }
 
-        let base_dirs = directories::BaseDirs::new().ok_or_else(|| eyre!("无法获取 home 目录"))?;
-        let rrclaw_dir = base_dirs.home_dir().join(".rrclaw");
-        let data_dir = rrclaw_dir.join("data");
-        let log_dir = rrclaw_dir.join("logs");
-        let config_path = crate::config::Config::config_path()?;
-
-        let policy = SecurityPolicy {
-            autonomy: self.config.security.autonomy.clone(),
-            allowed_commands: self.config.security.allowed_commands.clone(),
-            workspace_dir: std::env::current_dir()
-                .unwrap_or_else(|_| std::path::PathBuf::from(".")),
-            blocked_paths: SecurityPolicy::default().blocked_paths,
-            http_allowed_hosts: self.config.security.http_allowed_hosts.clone(),
-            injection_check: self.config.security.injection_check,
-        };
+    /// 展开模板变量、召回上次成功的方法并构造本次发给 Agent 的消息
+    ///
+    /// 返回 (消息, 是否注入了历史方法)。注入的方法连续失败达到
+    /// `routines.approach_max_failures` 次后不再注入，改为提示 LLM 探索新方法。
+    async fn prepare_message(&self, routine: &Routine) -> (String, bool) {
+        let memory_key = approach_key(&routine.name);
+        let recalled = self.memory.recall(&memory_key, 1).await.unwrap_or_default();
 
-        let tools = create_tools(
-            (*self.config).clone(),
-            provider_arc,
-            data_dir.clone(),
-            log_dir,
-            config_path,
-            vec![], // Routine 不加载 skills（保持执行简洁）
-            Arc::clone(&self.memory),
-            None, // Routine 内部 Agent 不注册 RoutineTool（避免循环调度）
+        let max_failures = self.config.routines.approach_max_failures;
+        let approach_stale =
+            max_failures > 0 && self.approach_failures(&routine.name).await >= max_failures;
+        if approach_stale && !recalled.is_empty() {
+            info!(
+                "Routine '{}' 的历史方法已连续失败 {} 次，本次不再注入",
+                routine.name, max_failures
+            );
+        }
+
+        let injected = !recalled.is_empty() && !approach_stale;
+        // 动态 Routine 的消息可能由 LLM 写入（routine 工具），展开环境变量会泄露密钥，
+        // 只有 config.toml 中的 Routine 展开 {env:VAR}
+        let expand_env = routine.source == RoutineSource::Config;
+        let message = template::expand(
+            &routine.message,
+            self.timezone.now_naive(),
+            Config::get_language(),
+            |name| expand_env.then(|| std::env::var(name).ok()).flatten(),
         );
+        (
+            build_enhanced_message(&recalled, &message, approach_stale),
+            injected,
+        )
+    }
 
-        let provider_name = provider_key.clone();
-        let model = self.config.default.model.clone();
-        let temperature = self.config.default.temperature;
+    /// 注入历史方法后连续失败的次数
+    async fn approach_failures(&self, name: &str) -> u32 {
+        let db = self.db.lock().await;
+        db.query_row(
+            "SELECT approach_failures FROM routine_state WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        )
+        .unwrap_or(0)
+    }
 
-        // ★ Step 0: 从共享 Memory 召回上次成功的方法描述
-        let memory_key = format!("routine:{}:approach", routine.name);
-        let recalled = self.memory.recall(&memory_key, 1).await.unwrap_or_default();
+    /// 注入历史方法的一次执行失败：连续失败计数 +1
+    async fn record_approach_failure(&self, name: &str) {
+        let db = self.db.lock().await;
+        if let Err(e) = db.execute(
+            "INSERT INTO routine_state (name, approach_failures, updated_at) VALUES (?1, 1, ?2)
+             ON CONFLICT(name) DO UPDATE SET
+                approach_failures = approach_failures + 1,
+                updated_at = excluded.updated_at",
+            params![name, self.timezone.now_rfc3339()],
+        ) {
+            warn!("记录 Routine '{}' 方法失败次数失败: {}", name, e);
+        }
+    }
 
-        // ★ Step 1: 构造增强版 message（有历史方法时注入前缀）
-        let enhanced_message = build_enhanced_message(&recalled, &routine.message);
-
-        // ★ Step 2: 传入共享 Memory（LLM 可通过 memory_store 保存有效方法）
-        let mut agent = Agent::new(
-            provider,
-            tools,
-            Box::new(Arc::clone(&self.memory)), // 共享 Memory，读写均生效
-            policy,
-            provider_name,
-            provider_config.base_url.clone(),
-            model,
-            temperature,
-            vec![], // 无 skills
-            None,   // 无身份文件上下文（Routine 是系统任务，不需要用户偏好）
-        );
+    /// 执行成功：清零连续失败计数（新方法由 Agent 通过 memory_store 覆盖写入）
+    async fn reset_approach_failures(&self, name: &str) {
+        let db = self.db.lock().await;
+        if let Err(e) = db.execute("DELETE FROM routine_state WHERE name = ?1", params![name]) {
+            warn!("重置 Routine '{}' 方法失败次数失败: {}", name, e);
+        }
+    }
 
+    /// Routine Agent 工厂（首次执行时按最终的 paths / 离线状态创建，之后复用缓存部件）
+    fn agent_factory(&self) -> &AgentFactory {
+        self.agent_factory.get_or_init(|| {
+            let factory = AgentFactory::new(
+                Arc::new(LiveConfig::new((*self.config).clone())),
+                Arc::clone(&self.memory),
+                self.paths.clone(),
+            )
+            .with_offline_state(Arc::clone(&self.offline));
+            match &self.workspace_dir {
+                Some(dir) => factory.with_workspace_dir(dir.clone()),
+                None => factory,
+            }
+        })
+    }
+
+    /// 创建独立 Agent 并执行一次任务消息
+    ///
+    /// Provider、安全策略等由工厂缓存，每次执行只创建工具列表和 Agent 本身。
+    /// 不加载 skills（保持执行简洁）和身份文件（Routine 是系统任务，不需要用户偏好）；
+    /// 共享 Memory（LLM 可通过 memory_store 保存有效方法），历史方法已由 prepare_message 注入。
+    async fn run_once(
+        &self,
+        routine: &Routine,
+        message: &str,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        // Routine 只能以 Full 模式执行；沙箱要求未满足时整体拒绝
+        if crate::security::sandbox::refuses_full(self.config.security.require_sandbox) {
+            return Err(eyre!(crate::security::sandbox::FULL_REFUSED_MESSAGE));
+        }
+        let mut agent = self.agent_factory().create_agent()?;
         // Routine 在 Full 模式下执行（不需要用户逐一确认，无交互界面）
         agent.set_autonomy(crate::security::AutonomyLevel::Full);
         // 注入 Routine 专属 system prompt 段
         agent.set_routine_name(routine.name.clone());
+        agent.set_cancel_token(cancel.clone());
 
-        let output = agent.process_message(&enhanced_message).await?;
+        let output = agent.process_message(message).await?;
         Ok(output)
     }
 
@@ -584,6 +1454,31 @@ impl RoutineEngine {
                     }
                 }
             }
+            "webhook" => {
+                let result = match routine.channel_target.as_deref() {
+                    Some(url) if !url.is_empty() => {
+                        let payload = webhook::WebhookPayload {
+                            routine: &routine.name,
+                            output,
+                            timestamp: self.timezone.now_rfc3339(),
+                        };
+                        webhook::deliver(url, &self.config.security.http_allowed_hosts, &payload)
+                            .await
+                    }
+                    _ => Err(eyre!("未设置 channel_target")),
+                };
+                if let Err(e) = result {
+                    warn!(
+                        "Routine '{}' webhook 发送失败，降级为 cli: {}",
+                        routine.name, e
+                    );
+                    if let Some(tx) = self.cli_notifier.get() {
+                        let _ = tx.send(message).await;
+                    } else {
+                        eprintln!("{}", message);
+                    }
+                }
+            }
             other => {
                 warn!(
                     "Routine '{}' 使用了未知 channel: {}，降级为 cli",
@@ -647,8 +1542,8 @@ impl RoutineEngine {
         let db = self.db.lock().await;
         let _ = db.execute(
             "INSERT INTO routines_log \
-             (routine_name, started_at, finished_at, success, output, error) \
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+             (routine_name, started_at, finished_at, success, output, error, deferred) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 exec.routine_name,
                 exec.started_at,
@@ -656,12 +1551,23 @@ impl RoutineEngine {
                 exec.success as i32,
                 exec.output_preview,
                 exec.error,
+                exec.deferred as i32,
             ],
         );
     }
 
     // ─── 动态管理 API（供 /routine 斜杠命令使用）───────────────────────────
 
+    /// 新增链式 Routine 前检查：子任务须已存在，且不构成循环、不超过嵌套深度
+    fn check_new_chain(&self, routine: &Routine) -> Result<()> {
+        if routine.chain.is_empty() {
+            return Ok(());
+        }
+        let mut routines = self.routines.read().unwrap().clone();
+        routines.push(routine.clone());
+        resolve_chain(&routines, &routine.name).map(|_| ())
+    }
+
     /// 列出所有 Routine（包括 disabled），返回当前快照
     pub fn list_routines(&self) -> Vec<Routine> {
         self.routines.read().unwrap().clone()
@@ -681,7 +1587,7 @@ impl RoutineEngine {
     pub async fn get_recent_logs(&self, limit: usize) -> Vec<RoutineExecution> {
         let db = self.db.lock().await;
         let mut stmt = match db.prepare(
-            "SELECT routine_name, started_at, finished_at, success, output, error \
+            "SELECT routine_name, started_at, finished_at, success, output, error, deferred \
              FROM routines_log ORDER BY id DESC LIMIT ?1",
         ) {
             Ok(s) => s,
@@ -696,6 +1602,7 @@ impl RoutineEngine {
                 success: row.get::<_, i32>(3)? != 0,
                 output_preview: row.get(4)?,
                 error: row.get(5)?,
+                deferred: row.get::<_, i32>(6)? != 0,
             })
         })
         .map(|rows| rows.filter_map(|r| r.ok()).collect())
@@ -717,6 +1624,7 @@ impl RoutineEngine {
         {
             return Err(eyre!("Routine '{}' 已存在，请先删除再添加", routine.name));
         }
+        self.check_new_chain(&routine)?;
 
         // 验证 cron 表达式（尝试用 tokio-cron-scheduler 解析）
         // 最简单的验证：字段数量检查（5 字段 cron）
@@ -733,8 +1641,9 @@ impl RoutineEngine {
             let db = self.db.lock().await;
             db.execute(
                 "INSERT OR REPLACE INTO routines \
-                 (name, schedule, message, channel, enabled, created_at) \
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                 (name, schedule, message, channel, enabled, created_at, catch_up, chain, condition, \
+                 channel_target) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
                 params![
                     routine.name,
                     routine.schedule,
@@ -742,6 +1651,10 @@ impl RoutineEngine {
                     routine.channel,
                     routine.enabled as i32,
                     chrono::Utc::now().to_rfc3339(),
+                    routine.catch_up as i32,
+                    routine.chain.join(","),
+                    RoutineCondition::to_column(routine.condition.as_ref()),
+                    routine.channel_target.as_deref().unwrap_or_default(),
                 ],
             )
             .map_err(|e| eyre!("保存 Routine 失败: {}", e))?;
@@ -819,6 +1732,7 @@ impl RoutineEngine {
 
     /// 持久化新增 Routine 到 SQLite 并同步更新内存 Vec 和调度器
     pub async fn persist_add_routine(self: Arc<Self>, routine: &Routine) -> Result<()> {
+        self.ensure_scheduler()?;
         // 重复检查（先持有 read lock，检查完立即释放）
         {
             if self
@@ -831,6 +1745,7 @@ impl RoutineEngine {
                 return Err(eyre!("Routine '{}' 已存在，请先删除再添加", routine.name));
             }
         }
+        self.check_new_chain(routine)?;
         let field_count = routine.schedule.split_whitespace().count();
         if field_count != 5 && field_count != 6 {
             return Err(eyre!(
@@ -844,8 +1759,9 @@ impl RoutineEngine {
             let db = self.db.lock().await;
             db.execute(
                 "INSERT OR REPLACE INTO routines \
-                 (name, schedule, message, channel, enabled, created_at) \
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                 (name, schedule, message, channel, enabled, created_at, catch_up, chain, condition, \
+                 channel_target) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
                 params![
                     routine.name,
                     routine.schedule,
@@ -853,6 +1769,10 @@ impl RoutineEngine {
                     routine.channel,
                     routine.enabled as i32,
                     chrono::Utc::now().to_rfc3339(),
+                    routine.catch_up as i32,
+                    routine.chain.join(","),
+                    RoutineCondition::to_column(routine.condition.as_ref()),
+                    routine.channel_target.as_deref().unwrap_or_default(),
                 ],
             )
             .map_err(|e| eyre!("保存 Routine 失败: {}", e))?;
@@ -864,8 +1784,73 @@ impl RoutineEngine {
         Ok(())
     }
 
+    /// 导出全部动态 Routine 为 JSON（跳过 config.toml 来源）
+    pub fn export_routines(&self) -> String {
+        let routines = self
+            .routines
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|r| r.source == RoutineSource::Dynamic)
+            .cloned()
+            .collect();
+        let export = RoutineExport {
+            version: EXPORT_FORMAT_VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            routines,
+        };
+        serde_json::to_string_pretty(&export).expect("Routine 序列化不会失败")
+    }
+
+    /// 导入 `export_routines` 生成的 JSON，逐条持久化并注册到调度器
+    ///
+    /// 导入的 Routine 一律视为动态来源。同名 Routine 已存在时：来自 config.toml 的始终跳过；
+    /// 动态来源在 `overwrite` 为 true 时先删除再导入，否则跳过。单条失败不影响其余条目。
+    pub async fn import_routines(
+        self: Arc<Self>,
+        json: &str,
+        overwrite: bool,
+    ) -> Result<ImportSummary> {
+        let export: RoutineExport =
+            serde_json::from_str(json).map_err(|e| eyre!("解析 Routine 导出文件失败: {}", e))?;
+        if export.version > EXPORT_FORMAT_VERSION {
+            return Err(eyre!(
+                "导出文件版本 {} 高于当前支持的版本 {}，请升级 RRClaw",
+                export.version,
+                EXPORT_FORMAT_VERSION
+            ));
+        }
+
+        let mut summary = ImportSummary::default();
+        for mut routine in export.routines {
+            routine.source = RoutineSource::Dynamic;
+            let name = routine.name.clone();
+
+            let mut replacing = false;
+            if let Some(existing) = self.get_routine(&name) {
+                if existing.source == RoutineSource::Config || !overwrite {
+                    summary.skipped.push(name);
+                    continue;
+                }
+                if let Err(e) = self.persist_delete_routine(&name).await {
+                    summary.failed.push((name, e.to_string()));
+                    continue;
+                }
+                replacing = true;
+            }
+
+            match self.clone().persist_add_routine(&routine).await {
+                Ok(()) if replacing => summary.replaced.push(name),
+                Ok(()) => summary.imported.push(name),
+                Err(e) => summary.failed.push((name, e.to_string())),
+            }
+        }
+        Ok(summary)
+    }
+
     /// 从 SQLite 删除 Routine 并同步更新内存 Vec
     pub async fn persist_delete_routine(&self, name: &str) -> Result<()> {
+        self.ensure_scheduler()?;
         {
             let guard = self.routines.read().unwrap();
             let routine = guard
@@ -883,6 +1868,7 @@ impl RoutineEngine {
             let db = self.db.lock().await;
             db.execute("DELETE FROM routines WHERE name = ?1", params![name])
                 .map_err(|e| eyre!("删除 Routine 失败: {}", e))?;
+            let _ = db.execute("DELETE FROM routine_state WHERE name = ?1", params![name]);
         }
         // 从调度器精确注销 cron job，防止已删除的 routine 继续触发
         // 注意：必须先取出 UUID 并 drop 锁（RwLockWriteGuard 不是 Send），再跨 .await
@@ -906,6 +1892,7 @@ impl RoutineEngine {
     /// enable(true) 只更新 DB/内存状态；若需立即生效需重启 RoutineEngine
     /// （或调用 persist_add_routine 重新注册）。
     pub async fn persist_set_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        self.ensure_scheduler()?;
         {
             if !self.routines.read().unwrap().iter().any(|r| r.name == name) {
                 return Err(eyre!("Routine '{}' 不存在", name));
@@ -947,21 +1934,57 @@ impl RoutineEngine {
 
 use regex::Regex;
 
+/// Routine 成功方法在 Memory 中的 key
+pub(crate) fn approach_key(name: &str) -> String {
+    format!("routine:{}:approach", name)
+}
+
 /// 根据 Memory recall 结果构造增强版 message
 ///
 /// 若召回到上次成功方法，注入 `[历史成功方法参考]` 前缀供 LLM 优先参考。
+/// `approach_stale` 为 true（该方法已连续失败）时不注入方法内容，改为提示 LLM 探索新方法。
 /// 未找到历史记录时返回原始 message，行为与之前一致。
 pub(crate) fn build_enhanced_message(
     recalled: &[crate::memory::MemoryEntry],
     message: &str,
+    approach_stale: bool,
 ) -> String {
-    if let Some(entry) = recalled.first() {
-        format!("[历史成功方法参考]\n{}\n\n---\n{}", entry.content, message)
-    } else {
-        message.to_string()
+    match recalled.first() {
+        None => message.to_string(),
+        Some(_) if approach_stale => format!(
+            "[注意] 上次记录的方法已连续失败，请探索新方法\n\n---\n{}",
+            message
+        ),
+        Some(entry) => format!("[历史成功方法参考]\n{}\n\n---\n{}", entry.content, message),
     }
 }
 
+/// 上次执行 `last_run` 之后、`now` 之前（含）错过的最近一次触发时间
+///
+/// 没有错过任何触发时返回 None。错过多次时只返回最近一次（补跑只执行一次）；
+/// 高频 schedule 长时间停机时最多向后推算 `MAX_CATCH_UP_STEPS` 次。
+pub(crate) fn missed_occurrence(
+    schedule: &str,
+    timezone: RoutineTimezone,
+    last_run: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    const MAX_CATCH_UP_STEPS: usize = 10_000;
+
+    let mut missed = None;
+    let mut cursor = last_run;
+    for _ in 0..MAX_CATCH_UP_STEPS {
+        match timezone.next_fire(schedule, cursor) {
+            Ok(next) if next <= now => {
+                missed = Some(next);
+                cursor = next;
+            }
+            _ => break,
+        }
+    }
+    missed
+}
+
 /// 将自然语言时间描述或 cron 表达式转换为标准 5 字段 cron 表达式
 ///
 /// - 若输入已是 5 字段 cron 格式，直接原样返回
@@ -1223,6 +2246,10 @@ mod tests {
             channel: "cli".to_string(),
             enabled: true,
             source: RoutineSource::Dynamic,
+            catch_up: false,
+            chain: vec![],
+            condition: None,
+            channel_target: None,
         }
     }
 
@@ -1256,6 +2283,23 @@ mod tests {
         assert!(routines.is_empty());
     }
 
+    #[test]
+    fn merge_routines_prefers_dynamic_on_duplicate_name() {
+        let mut daily = make_routine("daily", "0 8 * * *");
+        daily.source = RoutineSource::Config;
+        let weekly = make_routine("weekly", "0 9 * * 1");
+        let mut dynamic_daily = make_routine("daily", "30 7 * * *");
+        dynamic_daily.message = "动态版本".to_string();
+
+        let merged = merge_routines(vec![daily, weekly], vec![dynamic_daily]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].name, "daily");
+        assert_eq!(merged[0].schedule, "30 7 * * *");
+        assert_eq!(merged[0].source, RoutineSource::Dynamic);
+        assert_eq!(merged[1].name, "weekly");
+    }
+
     #[test]
     fn routine_serialization() {
         let r = make_routine("test", "0 8 * * *");
@@ -1320,6 +2364,1055 @@ mod tests {
         assert_eq!(count, 0);
     }
 
+    #[tokio::test]
+    async fn offline_routine_is_deferred_not_failed() {
+        let dir = tempdir().unwrap();
+        let offline = Arc::new(OfflineState::new());
+        offline.mark_offline("connection refused");
+        let engine = RoutineEngine::new(
+            vec![make_routine("daily", "0 8 * * *")],
+            Arc::new(Config::default()),
+            Arc::new(NoopMemory),
+            &dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap()
+        .with_offline_state(offline);
+
+        let msg = engine.execute_routine("daily").await.unwrap();
+        assert!(msg.contains("延后"));
+        assert_eq!(engine.deferred_routines(), vec!["daily".to_string()]);
+
+        // 重复触发不会重复入队
+        engine.execute_routine("daily").await.unwrap();
+        assert_eq!(engine.deferred_routines().len(), 1);
+
+        let logs = engine.get_recent_logs(10).await;
+        assert_eq!(logs.len(), 2);
+        assert!(logs.iter().all(|l| l.deferred && !l.success));
+    }
+
+    #[tokio::test]
+    async fn replay_requeues_when_network_drops_again() {
+        let dir = tempdir().unwrap();
+        // 端口 1 拒绝连接：每次重放都会再次断网并重新入队
+        let engine = Arc::new(
+            mock_engine(
+                dir.path(),
+                vec![make_routine("daily", "0 8 * * *")],
+                "http://127.0.0.1:1/v1",
+                None,
+            )
+            .await,
+        );
+        let offline = Arc::clone(&engine.offline);
+        offline.mark_offline("connection refused");
+        engine.execute_routine("daily").await.unwrap();
+        RoutineEngine::spawn_deferred_replay(Arc::clone(&engine));
+
+        // 每次联网都应重放一次，即使联网发生在上一轮重放尚未回到等待时
+        for expected in 2..=3 {
+            offline.mark_online();
+            let mut logs = 0;
+            for _ in 0..500 {
+                logs = engine.get_recent_logs(10).await.len();
+                if logs == expected
+                    && offline.is_offline()
+                    && !engine.deferred_routines().is_empty()
+                {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            assert_eq!(logs, expected, "联网后应重放延后的 Routine");
+            assert_eq!(engine.deferred_routines(), vec!["daily".to_string()]);
+        }
+        let logs = engine.get_recent_logs(10).await;
+        assert!(logs.iter().all(|l| l.deferred && !l.success));
+    }
+
+    #[tokio::test]
+    async fn paused_engine_suppresses_scheduled_triggers_but_allows_manual_run() {
+        let dir = tempdir().unwrap();
+        // 离线让手动执行走延后路径，无需真实 Provider 也能观察到"确实执行了"
+        let offline = Arc::new(OfflineState::new());
+        offline.mark_offline("connection refused");
+        let mut config = Config::default();
+        config.routines.paused = true;
+        let engine = RoutineEngine::new(
+            vec![make_routine("daily", "0 8 * * *")],
+            Arc::new(config),
+            Arc::new(NoopMemory),
+            &dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap()
+        .with_offline_state(offline);
+        assert!(engine.is_paused());
+
+        // 定时触发被跳过：计数增加，但没有执行记录
+        assert!(!engine.on_scheduled_trigger("daily").await);
+        assert_eq!(
+            engine
+                .trigger_count
+                .load(std::sync::atomic::Ordering::Relaxed),
+            1
+        );
+        assert!(engine.get_recent_logs(10).await.is_empty());
+
+        // 手动执行不受暂停影响
+        let msg = engine.execute_routine("daily").await.unwrap();
+        assert!(msg.contains("延后"));
+        assert_eq!(engine.get_recent_logs(10).await.len(), 1);
+
+        // 恢复后定时触发重新生效
+        engine.set_paused(false);
+        assert!(engine.on_scheduled_trigger("daily").await);
+        assert_eq!(engine.get_recent_logs(10).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn engine_paths_follow_custom_config() {
+        let dir = tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        let engine = RoutineEngine::new(
+            vec![],
+            Arc::new(Config::default()),
+            Arc::new(NoopMemory),
+            &dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap()
+        .with_paths(RrclawPaths::from_config_file(&config));
+
+        assert_eq!(engine.paths().config_file(), config);
+        assert!(engine.paths().data_dir().starts_with(dir.path()));
+        assert!(engine.paths().log_dir().starts_with(dir.path()));
+    }
+
+    #[tokio::test]
+    async fn repeated_runs_reuse_factory_pieces() {
+        let dir = tempdir().unwrap();
+        let mut config = Config::default();
+        config.default.provider = "echo".to_string();
+        config.default.model = "echo".to_string();
+        config.providers.insert(
+            "echo".to_string(),
+            crate::config::ProviderConfig {
+                base_url: "echo://".to_string(),
+                api_key: String::new(),
+                model: "echo".to_string(),
+                auth_style: Some("echo".to_string()),
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        let engine = RoutineEngine::new(
+            vec![],
+            Arc::new(config),
+            Arc::new(NoopMemory),
+            &dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap()
+        .with_paths(RrclawPaths::in_home(dir.path()))
+        .with_offline_state(Arc::new(OfflineState::new()));
+        let routine = make_routine("echo", "0 9 * * *");
+
+        for _ in 0..3 {
+            engine
+                .run_once(&routine, "ping", &CancellationToken::new())
+                .await
+                .unwrap();
+        }
+
+        // 每次执行只创建 Agent，Provider 只在第一次构建
+        let stats = engine.agent_factory().stats();
+        assert_eq!(stats.provider_builds, 1);
+        assert_eq!(stats.agents_created, 3);
+    }
+
+    #[tokio::test]
+    async fn execution_metrics_are_scraped() {
+        let dir = tempdir().unwrap();
+        let mut config = Config::default();
+        config.default.provider = "echo".to_string();
+        config.default.model = "echo".to_string();
+        config.reliability.max_retries = 1;
+        config.providers.insert(
+            "echo".to_string(),
+            crate::config::ProviderConfig {
+                base_url: "echo://".to_string(),
+                api_key: String::new(),
+                model: "echo".to_string(),
+                auth_style: Some("echo".to_string()),
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        let mut routine = make_routine("metrics-probe", "0 9 * * *");
+        routine.message = r#"tool:memory_recall {"query": "metrics"}"#.to_string();
+        let engine = RoutineEngine::new(
+            vec![routine],
+            Arc::new(config),
+            Arc::new(NoopMemory),
+            &dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap()
+        .with_paths(RrclawPaths::in_home(dir.path()))
+        .with_offline_state(Arc::new(OfflineState::new()));
+
+        engine.execute_routine("metrics-probe").await.unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(crate::metrics::server::serve(
+            listener,
+            crate::metrics::Registry::global(),
+            None,
+        ));
+        let body = crate::metrics::server::scrape(addr, "/metrics").await;
+        for series in [
+            r#"rrclaw_routine_executions_total{routine="metrics-probe",outcome="success"}"#,
+            r#"rrclaw_routine_duration_seconds_count{routine="metrics-probe"}"#,
+            r#"rrclaw_provider_requests_total{provider="echo",model="echo",outcome="success"}"#,
+            r#"rrclaw_provider_request_duration_seconds_bucket{provider="echo",model="echo",le="+Inf"}"#,
+            r#"rrclaw_tool_executions_total{tool="memory_recall",outcome="success"}"#,
+            "rrclaw_uptime_seconds",
+        ] {
+            assert!(body.contains(series), "missing {}:\n{}", series, body);
+        }
+    }
+
+    fn chained(name: &str, chain: &[&str]) -> Routine {
+        let mut routine = make_routine(name, "0 23 * * *");
+        routine.message = String::new();
+        routine.chain = chain.iter().map(|c| c.to_string()).collect();
+        routine
+    }
+
+    /// 使用 echo Provider（回复 `[echo] <消息>`）、不重试的引擎
+    async fn echo_engine(dir: &std::path::Path, routines: Vec<Routine>) -> RoutineEngine {
+        mock_engine(dir, routines, "echo://", Some("echo")).await
+    }
+
+    /// 默认 Provider 指向 `base_url` 的引擎（不重试）
+    async fn mock_engine(
+        dir: &std::path::Path,
+        routines: Vec<Routine>,
+        base_url: &str,
+        auth_style: Option<&str>,
+    ) -> RoutineEngine {
+        let mut config = Config::default();
+        config.default.provider = "mock".to_string();
+        config.default.model = "mock".to_string();
+        config.reliability.max_retries = 1;
+        config.providers.insert(
+            "mock".to_string(),
+            crate::config::ProviderConfig {
+                base_url: base_url.to_string(),
+                api_key: String::new(),
+                model: "mock".to_string(),
+                auth_style: auth_style.map(str::to_string),
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        RoutineEngine::new(
+            routines,
+            Arc::new(config),
+            Arc::new(NoopMemory),
+            &dir.join("routines.db"),
+        )
+        .await
+        .unwrap()
+        .with_paths(RrclawPaths::in_home(dir))
+        .with_offline_state(Arc::new(OfflineState::new()))
+    }
+
+    #[tokio::test]
+    async fn chain_runs_children_in_order_and_aggregates() {
+        let dir = tempdir().unwrap();
+        let mut first = make_routine("first", "0 1 * * *");
+        first.message = "step one".to_string();
+        let mut second = make_routine("second", "0 2 * * *");
+        second.message = "step two".to_string();
+        let engine = echo_engine(
+            dir.path(),
+            vec![
+                second,
+                first,
+                chained("inner", &["second"]),
+                chained("nightly", &["first", "inner"]),
+            ],
+        )
+        .await;
+
+        let output = engine.execute_routine("nightly").await.unwrap();
+        let one = output.find("[1/2] first ✓").unwrap();
+        let two = output.find("[2/2] second ✓").unwrap();
+        assert!(one < two, "{}", output);
+        assert!(output[one..two].contains("step one"));
+        assert!(output[two..].contains("step two"));
+
+        // 子任务各自记录日志，父任务最后记录
+        let names: Vec<String> = engine
+            .get_recent_logs(10)
+            .await
+            .into_iter()
+            .map(|l| l.routine_name)
+            .collect();
+        assert_eq!(names, vec!["nightly", "second", "first"]);
+    }
+
+    #[tokio::test]
+    async fn message_placeholders_expand_at_fire_time() {
+        let dir = tempdir().unwrap();
+        let mut report = make_routine("report", "0 9 * * *");
+        report.message = "总结 {date} 的邮件 {unknown}".to_string();
+        let engine = echo_engine(dir.path(), vec![report]).await;
+
+        let output = engine.execute_routine("report").await.unwrap();
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert!(
+            output.contains(&format!("总结 {} 的邮件 {{unknown}}", today)),
+            "{}",
+            output
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn shell_condition_gates_the_agent_run() {
+        let dir = tempdir().unwrap();
+        let mut green = make_routine("deploy_green", "0 9 * * *");
+        green.message = "deploy".to_string();
+        green.condition = Some(RoutineCondition::Shell("exit 0".to_string()));
+        let mut red = make_routine("deploy_red", "0 9 * * *");
+        red.message = "deploy".to_string();
+        red.condition = Some(RoutineCondition::Shell("exit 1".to_string()));
+        let engine = Arc::new(echo_engine(dir.path(), vec![green, red]).await);
+
+        // 条件满足：run_once 执行，得到 echo 回复
+        let output = engine.execute_routine("deploy_green").await.unwrap();
+        assert!(output.contains("[echo]"), "{}", output);
+
+        // 条件不满足：不调用 Agent，只记录跳过
+        let output = engine.execute_routine("deploy_red").await.unwrap();
+        assert_eq!(output, "skipped: condition not met (exit code 1)");
+
+        let logs = engine.get_recent_logs(10).await;
+        assert_eq!(logs[0].routine_name, "deploy_red");
+        assert!(logs[0].success);
+        assert!(logs[0]
+            .output_preview
+            .starts_with("skipped: condition not met"));
+        assert!(logs[1].output_preview.contains("[echo]"));
+
+        // 条件随动态 Routine 持久化
+        let mut gated = make_routine("gated", "0 9 * * *");
+        gated.condition = Some(RoutineCondition::Http("https://ci.example.com".to_string()));
+        engine.clone().persist_add_routine(&gated).await.unwrap();
+        let conn = Connection::open(dir.path().join("routines.db")).unwrap();
+        let loaded = RoutineEngine::load_dynamic_routines(&conn).unwrap();
+        assert_eq!(loaded[0].condition, gated.condition);
+    }
+
+    #[tokio::test]
+    async fn rejected_webhook_falls_back_to_cli() {
+        let dir = tempdir().unwrap();
+        let mut hook = make_routine("hook", "0 9 * * *");
+        hook.message = "report".to_string();
+        hook.channel = "webhook".to_string();
+        // 未加入 http_allowed_hosts 的内网地址：SSRF 检查拒绝，结果改为 cli 输出
+        hook.channel_target = Some("http://127.0.0.1:9/hook".to_string());
+        let engine = Arc::new(echo_engine(dir.path(), vec![hook.clone()]).await);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        engine.set_cli_notifier(tx);
+
+        engine.execute_routine("hook").await.unwrap();
+        let printed = rx.try_recv().unwrap();
+        assert!(printed.starts_with("[Routine: hook]"), "{}", printed);
+        assert!(printed.contains("[echo]"), "{}", printed);
+
+        // channel_target 随动态 Routine 持久化
+        let mut saved = hook.clone();
+        saved.name = "hook_saved".to_string();
+        engine.clone().persist_add_routine(&saved).await.unwrap();
+        let conn = Connection::open(dir.path().join("routines.db")).unwrap();
+        let loaded = RoutineEngine::load_dynamic_routines(&conn).unwrap();
+        assert_eq!(loaded[0].channel_target, hook.channel_target);
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_running_routine_and_releases_guard() {
+        // 接受连接但从不响应：模拟卡住的 Provider 请求
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+        let dir = tempdir().unwrap();
+        let engine = Arc::new(
+            mock_engine(
+                dir.path(),
+                vec![make_routine("slow", "0 8 * * *")],
+                &format!("http://{}/v1", addr),
+                None,
+            )
+            .await,
+        );
+
+        let runner = {
+            let engine = Arc::clone(&engine);
+            tokio::spawn(async move { engine.execute_routine("slow").await })
+        };
+        for _ in 0..200 {
+            if engine.running_elapsed("slow").is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(engine.running_elapsed("slow").is_some());
+
+        // 执行中再次触发同名 Routine：拒绝，不叠加
+        let err = engine.execute_routine("slow").await.unwrap_err();
+        assert!(err.to_string().contains("正在执行"), "{}", err);
+
+        assert!(engine.cancel_routine("slow"));
+        let err = tokio::time::timeout(std::time::Duration::from_secs(5), runner)
+            .await
+            .expect("cancelled run should finish promptly")
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().contains("已取消"), "{}", err);
+
+        // 运行登记已释放：不能再取消，也可以重新执行
+        assert!(engine.running_elapsed("slow").is_none());
+        assert!(!engine.cancel_routine("slow"));
+        let logs = engine.get_recent_logs(5).await;
+        assert_eq!(logs.len(), 1);
+        assert!(!logs[0].success);
+        assert_eq!(logs[0].error.as_deref(), Some(CANCELLED_MESSAGE));
+    }
+
+    #[test]
+    fn format_elapsed_is_compact() {
+        use std::time::Duration;
+        assert_eq!(format_elapsed(Duration::from_secs(42)), "42s");
+        assert_eq!(format_elapsed(Duration::from_secs(185)), "3m05s");
+        assert_eq!(format_elapsed(Duration::from_secs(3720)), "1h02m");
+    }
+
+    /// 按脚本依次回复的 Provider（模拟测试用）
+    struct ScriptedProvider(std::sync::Mutex<Vec<crate::providers::ChatResponse>>);
+
+    #[async_trait::async_trait]
+    impl crate::providers::Provider for ScriptedProvider {
+        async fn chat_with_tools(
+            &self,
+            _messages: &[crate::providers::ConversationMessage],
+            _tools: &[crate::providers::ToolSpec],
+            _model: &str,
+            _params: crate::providers::GenerationParams,
+        ) -> Result<crate::providers::ChatResponse> {
+            Ok(self.0.lock().unwrap().remove(0))
+        }
+    }
+
+    fn reply(
+        text: &str,
+        tool_calls: Vec<crate::providers::ToolCall>,
+    ) -> crate::providers::ChatResponse {
+        crate::providers::ChatResponse {
+            text: Some(text.to_string()),
+            reasoning_content: None,
+            tool_calls,
+            provenance: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn simulate_runs_supervised_without_log_or_delivery() {
+        let dir = tempdir().unwrap();
+        let mut stock = make_routine("stock", "0 9 * * *");
+        stock.message = "查询今日股价".to_string();
+        let engine = echo_engine(dir.path(), vec![stock]).await;
+        let (notify_tx, mut notify_rx) = tokio::sync::mpsc::channel(4);
+        engine.set_cli_notifier(notify_tx);
+
+        let confirmed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&confirmed);
+        let script = vec![
+            reply(r#"{"skills": [], "direct": true}"#, vec![]),
+            reply(
+                "记下方法",
+                vec![crate::providers::ToolCall {
+                    id: "c1".to_string(),
+                    name: "memory_store".to_string(),
+                    arguments: serde_json::json!({
+                        "key": approach_key("stock"),
+                        "content": "GET /api/quote",
+                    }),
+                }],
+            ),
+            reply("今日收盘 42.0", vec![]),
+        ];
+        let (tx, mut rx) = crate::providers::stream_channel();
+        let report = engine
+            .simulate(
+                "stock",
+                move |agent| {
+                    agent.switch_provider(
+                        Box::new(ScriptedProvider(std::sync::Mutex::new(script))),
+                        "scripted".to_string(),
+                        String::new(),
+                        "scripted".to_string(),
+                    );
+                    agent.set_confirm_fn(Box::new(move |name, _| {
+                        seen.lock().unwrap().push(name.to_string());
+                        true
+                    }));
+                },
+                tx,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(report.output, "今日收盘 42.0");
+        assert!(report.approach_updated);
+        assert!(!report.approach_injected);
+        assert!(report.condition.is_none());
+        // Supervised：写入 Memory 前经过确认回调
+        assert_eq!(*confirmed.lock().unwrap(), vec!["memory_store".to_string()]);
+        let mut streamed = String::new();
+        while let Ok(event) = rx.try_recv() {
+            if let crate::providers::StreamEvent::Text(t) = event {
+                streamed.push_str(&t);
+            }
+        }
+        assert!(streamed.contains("今日收盘 42.0"), "{}", streamed);
+
+        // 不写执行日志、不发送到 channel
+        assert!(engine.get_recent_logs(10).await.is_empty());
+        assert!(notify_rx.try_recv().is_err());
+
+        let err = engine.simulate("missing", |_| {}, crate::providers::stream_channel().0);
+        assert!(err.await.unwrap_err().to_string().contains("不存在"));
+    }
+
+    #[tokio::test]
+    async fn chain_cycles_and_deep_nesting_are_rejected() {
+        let dir = tempdir().unwrap();
+        let engine = engine_at(
+            dir.path(),
+            vec![
+                make_routine("leaf", "0 8 * * *"),
+                chained("ping", &["pong"]),
+                chained("pong", &["leaf", "ping"]),
+            ],
+        )
+        .await;
+
+        // 引用自身：创建时拒绝
+        let err = engine
+            .clone()
+            .persist_add_routine(&chained("selfish", &["selfish"]))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("循环引用"), "{}", err);
+        assert!(engine.get_routine("selfish").is_none());
+
+        // 引用不存在的 Routine：创建时拒绝
+        let err = engine
+            .clone()
+            .persist_add_routine(&chained("broken", &["missing"]))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("'missing'"), "{}", err);
+
+        // 配置中的循环：执行前拒绝，子任务不会运行
+        let err = engine.execute_routine("ping").await.unwrap_err();
+        assert!(err.to_string().contains("ping → pong → ping"), "{}", err);
+        assert!(engine.get_recent_logs(10).await.is_empty());
+
+        // 合法的链持久化后能读回
+        engine
+            .clone()
+            .persist_add_routine(&chained("daily_chain", &["leaf", "leaf"]))
+            .await
+            .unwrap();
+        let conn = Connection::open(dir.path().join("routines.db")).unwrap();
+        let loaded = RoutineEngine::load_dynamic_routines(&conn).unwrap();
+        assert_eq!(loaded[0].chain, vec!["leaf", "leaf"]);
+
+        // 嵌套深度
+        let routines = vec![
+            make_routine("leaf", "0 8 * * *"),
+            chained("l1", &["leaf"]),
+            chained("l2", &["l1"]),
+            chained("l3", &["l2"]),
+            chained("l4", &["l3"]),
+        ];
+        assert_eq!(resolve_chain(&routines, "l3").unwrap().len(), 1);
+        let err = resolve_chain(&routines, "l4").unwrap_err();
+        assert!(err.to_string().contains("嵌套超过"), "{}", err);
+    }
+
+    async fn engine_at(dir: &std::path::Path, routines: Vec<Routine>) -> Arc<RoutineEngine> {
+        Arc::new(
+            RoutineEngine::new(
+                routines,
+                Arc::new(Config::default()),
+                Arc::new(NoopMemory),
+                &dir.join("routines.db"),
+            )
+            .await
+            .unwrap(),
+        )
+    }
+
+    // --- 调度锁（同一数据目录多进程）测试 ---
+
+    async fn engine_in_home(dir: &std::path::Path) -> Arc<RoutineEngine> {
+        let mut daily = make_routine("daily", "0 8 * * *");
+        daily.source = RoutineSource::Config;
+        Arc::new(
+            RoutineEngine::new(
+                vec![daily],
+                Arc::new(Config::default()),
+                Arc::new(NoopMemory),
+                &dir.join("routines.db"),
+            )
+            .await
+            .unwrap()
+            .with_paths(RrclawPaths::in_home(dir)),
+        )
+    }
+
+    #[tokio::test]
+    async fn second_engine_on_same_data_dir_stands_by() {
+        let dir = tempdir().unwrap();
+        let first = engine_in_home(dir.path()).await;
+        let second = engine_in_home(dir.path()).await;
+        first.clone().start().await.unwrap();
+        second.clone().start().await.unwrap();
+
+        assert_eq!(first.role(), SchedulerRole::Scheduler);
+        assert_eq!(
+            second.role(),
+            SchedulerRole::Standby {
+                pid: Some(std::process::id())
+            }
+        );
+        assert_eq!(first.job_uuids.read().unwrap().len(), 1);
+        assert!(
+            second.job_uuids.read().unwrap().is_empty(),
+            "旁观者不注册 cron job"
+        );
+
+        // 旁观者拒绝手动执行与增删改
+        let err = second
+            .execute_routine("daily")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(&format!("pid {}", std::process::id())),
+            "{}",
+            err
+        );
+        assert!(second
+            .clone()
+            .persist_add_routine(&make_routine("other", "0 9 * * *"))
+            .await
+            .is_err());
+        assert!(second.persist_set_enabled("daily", false).await.is_err());
+        assert!(second.persist_delete_routine("daily").await.is_err());
+
+        // 调度者新增的 Routine，旁观者刷新后可见，静态 Routine 保留
+        first
+            .clone()
+            .persist_add_routine(&make_routine("weekly", "0 9 * * 1"))
+            .await
+            .unwrap();
+        assert!(second.get_routine("weekly").is_none());
+        second.reload_if_standby().await;
+        let names: Vec<String> = second.list_routines().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["daily", "weekly"]);
+    }
+
+    #[tokio::test]
+    async fn scheduler_lock_is_released_with_engine_and_stale_file_is_ignored() {
+        let dir = tempdir().unwrap();
+        // 上一个进程崩溃后留下的锁文件
+        let data_dir = RrclawPaths::in_home(dir.path()).data_dir();
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::write(data_dir.join(lock::LOCK_FILE), "4194000").unwrap();
+
+        let first = engine_in_home(dir.path()).await;
+        assert_eq!(first.claim_scheduler(), SchedulerRole::Scheduler);
+        assert_eq!(
+            first.claim_scheduler(),
+            SchedulerRole::Scheduler,
+            "重复声明返回首次结果"
+        );
+        let second = engine_in_home(dir.path()).await;
+        assert!(matches!(
+            second.claim_scheduler(),
+            SchedulerRole::Standby { .. }
+        ));
+
+        // 调度进程退出（引擎释放）后，新进程接管调度
+        drop(first);
+        let third = engine_in_home(dir.path()).await;
+        assert_eq!(third.claim_scheduler(), SchedulerRole::Scheduler);
+        assert!(third
+            .execute_routine("missing")
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("不存在"));
+    }
+
+    #[tokio::test]
+    async fn in_memory_engine_does_not_take_the_scheduler_lock() {
+        let dir = tempdir().unwrap();
+        // 临时会话 / 数据目录不可用时的引擎：私有内存库，目录布局仍指向共享数据目录
+        let private = RoutineEngine::new(
+            vec![make_routine("daily", "0 8 * * *")],
+            Arc::new(Config::default()),
+            Arc::new(NoopMemory),
+            std::path::Path::new(":memory:"),
+        )
+        .await
+        .unwrap()
+        .with_paths(RrclawPaths::in_home(dir.path()));
+        assert_eq!(private.claim_scheduler(), SchedulerRole::Scheduler);
+        let data_dir = RrclawPaths::in_home(dir.path()).data_dir();
+        assert!(!data_dir.join(lock::LOCK_FILE).exists());
+
+        // 之后启动的正常进程仍是调度者
+        let healthy = engine_in_home(dir.path()).await;
+        assert_eq!(healthy.claim_scheduler(), SchedulerRole::Scheduler);
+        assert_eq!(private.role(), SchedulerRole::Scheduler);
+    }
+
+    // --- missed_occurrence（启动补跑）测试 ---
+
+    fn utc(s: &str) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .unwrap()
+            .with_timezone(&chrono::Utc)
+    }
+
+    fn shanghai() -> RoutineTimezone {
+        RoutineTimezone::Named(chrono_tz::Asia::Shanghai)
+    }
+
+    #[test]
+    fn missed_occurrence_detects_skipped_morning_run() {
+        // 上次成功：03-09 08:00 (+08)；现在 03-10 10:00 (+08)，错过了 03-10 08:00
+        let missed = missed_occurrence(
+            "0 8 * * *",
+            shanghai(),
+            utc("2026-03-09T08:00:00+08:00"),
+            utc("2026-03-10T10:00:00+08:00"),
+        );
+        assert_eq!(missed, Some(utc("2026-03-10T08:00:00+08:00")));
+    }
+
+    #[test]
+    fn missed_occurrence_none_before_next_fire() {
+        // 现在 03-10 07:00，今天 8 点还没到
+        let missed = missed_occurrence(
+            "0 8 * * *",
+            shanghai(),
+            utc("2026-03-09T08:00:00+08:00"),
+            utc("2026-03-10T07:00:00+08:00"),
+        );
+        assert_eq!(missed, None);
+    }
+
+    #[test]
+    fn missed_occurrence_returns_only_most_recent() {
+        // 停机三天，只补最近一次
+        let missed = missed_occurrence(
+            "0 8 * * *",
+            shanghai(),
+            utc("2026-03-06T08:00:00+08:00"),
+            utc("2026-03-09T20:00:00+08:00"),
+        );
+        assert_eq!(missed, Some(utc("2026-03-09T08:00:00+08:00")));
+    }
+
+    #[test]
+    fn missed_occurrence_respects_weekday_schedule() {
+        // 每周一 9 点；上次 03-02（周一），现在 03-08（周日）→ 没有错过
+        let last = utc("2026-03-02T09:00:00+08:00");
+        assert_eq!(
+            missed_occurrence(
+                "0 9 * * 1",
+                shanghai(),
+                last,
+                utc("2026-03-08T12:00:00+08:00")
+            ),
+            None
+        );
+        // 现在 03-09（周一）10 点 → 错过当天 9 点
+        assert_eq!(
+            missed_occurrence(
+                "0 9 * * 1",
+                shanghai(),
+                last,
+                utc("2026-03-09T10:00:00+08:00")
+            ),
+            Some(utc("2026-03-09T09:00:00+08:00"))
+        );
+    }
+
+    #[tokio::test]
+    async fn catch_up_flag_persists_and_last_success_is_read() {
+        let dir = tempdir().unwrap();
+        let engine = engine_at(dir.path(), vec![]).await;
+        let mut routine = make_routine("brief", "0 8 * * *");
+        routine.catch_up = true;
+        engine.clone().persist_add_routine(&routine).await.unwrap();
+        assert!(engine.last_success_at("brief").await.is_none());
+
+        engine
+            .log_execution(RoutineExecution {
+                routine_name: "brief".to_string(),
+                started_at: "2026-03-10T08:00:00+08:00".to_string(),
+                finished_at: "2026-03-10T08:01:00+08:00".to_string(),
+                success: true,
+                output_preview: String::new(),
+                error: None,
+                deferred: false,
+            })
+            .await;
+        assert_eq!(
+            engine.last_success_at("brief").await,
+            Some(utc("2026-03-10T00:00:00Z"))
+        );
+
+        let conn = Connection::open(dir.path().join("routines.db")).unwrap();
+        let loaded = RoutineEngine::load_dynamic_routines(&conn).unwrap();
+        assert!(loaded[0].catch_up);
+    }
+
+    #[tokio::test]
+    async fn failing_approach_is_suppressed_until_success() {
+        use crate::memory::{Memory, MemoryCategory, MemoryEntry};
+
+        /// recall 总是返回同一条历史方法
+        struct ApproachMemory;
+
+        #[async_trait::async_trait]
+        impl Memory for ApproachMemory {
+            async fn store(&self, _: &str, _: &str, _: MemoryCategory) -> Result<()> {
+                Ok(())
+            }
+            async fn recall(&self, _: &str, _: usize) -> Result<Vec<MemoryEntry>> {
+                Ok(vec![make_memory_entry("GET https://old.example.com/api")])
+            }
+            async fn forget(&self, _: &str) -> Result<bool> {
+                Ok(false)
+            }
+            async fn count(&self) -> Result<usize> {
+                Ok(1)
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let memory: Arc<dyn Memory> = Arc::new(ApproachMemory);
+        let engine = RoutineEngine::new(
+            vec![],
+            Arc::new(Config::default()),
+            memory,
+            &dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap();
+        let routine = make_routine("stock", "0 9 * * *");
+
+        let (msg, injected) = engine.prepare_message(&routine).await;
+        assert!(injected);
+        assert!(msg.contains("old.example.com"));
+
+        // 默认阈值 2：第一次失败仍注入，第二次失败后改为提示
+        engine.record_approach_failure("stock").await;
+        assert!(engine.prepare_message(&routine).await.1);
+        engine.record_approach_failure("stock").await;
+        let (msg, injected) = engine.prepare_message(&routine).await;
+        assert!(!injected);
+        assert!(msg.contains("[注意]"));
+        assert!(!msg.contains("old.example.com"));
+
+        // 新方法执行成功后重置
+        engine.reset_approach_failures("stock").await;
+        assert_eq!(engine.approach_failures("stock").await, 0);
+        assert!(engine.prepare_message(&routine).await.1);
+    }
+
+    #[tokio::test]
+    async fn env_placeholders_expand_only_for_config_routines() {
+        let dir = tempdir().unwrap();
+        let engine = RoutineEngine::new(
+            vec![],
+            Arc::new(Config::default()),
+            Arc::new(NoopMemory),
+            &dir.path().join("routines.db"),
+        )
+        .await
+        .unwrap();
+        let path = std::env::var("PATH").unwrap();
+
+        let mut routine = make_routine("env", "0 9 * * *");
+        routine.message = "路径 {env:PATH}".to_string();
+        let (msg, _) = engine.prepare_message(&routine).await;
+        assert!(msg.contains("路径 {env:PATH}"), "{}", msg);
+
+        routine.source = RoutineSource::Config;
+        let (msg, _) = engine.prepare_message(&routine).await;
+        assert!(msg.contains(&format!("路径 {}", path)), "{}", msg);
+    }
+
+    #[tokio::test]
+    async fn export_import_roundtrip_skips_config_routines() {
+        let src_dir = tempdir().unwrap();
+        let mut from_config = make_routine("from_config", "0 9 * * *");
+        from_config.source = RoutineSource::Config;
+        let source = engine_at(src_dir.path(), vec![from_config]).await;
+        let mut weekly = make_routine("weekly", "0 9 * * 1");
+        weekly.enabled = false;
+        weekly.channel = "telegram".to_string();
+        source
+            .clone()
+            .persist_add_routine(&make_routine("daily", "0 8 * * *"))
+            .await
+            .unwrap();
+        source.clone().persist_add_routine(&weekly).await.unwrap();
+
+        let json = source.export_routines();
+        assert!(!json.contains("from_config"));
+
+        let dst_dir = tempdir().unwrap();
+        let target = engine_at(dst_dir.path(), vec![]).await;
+        let summary = target.clone().import_routines(&json, false).await.unwrap();
+        assert_eq!(summary.imported, vec!["daily", "weekly"]);
+        assert!(summary.skipped.is_empty() && summary.failed.is_empty());
+
+        // 已持久化：新引擎从同一数据库加载
+        let reloaded = engine_at(dst_dir.path(), vec![]).await;
+        let weekly = reloaded.get_routine("weekly").unwrap();
+        assert_eq!(weekly.schedule, "0 9 * * 1");
+        assert_eq!(weekly.channel, "telegram");
+        assert!(!weekly.enabled);
+        assert_eq!(weekly.source, RoutineSource::Dynamic);
+        assert!(reloaded.get_routine("daily").is_some());
+
+        // 再次导入：默认跳过，overwrite 时覆盖
+        let again = target.clone().import_routines(&json, false).await.unwrap();
+        assert_eq!(again.skipped.len(), 2);
+        let replaced = target.clone().import_routines(&json, true).await.unwrap();
+        assert_eq!(replaced.replaced, vec!["daily", "weekly"]);
+        assert_eq!(target.list_routines().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn import_never_overwrites_config_routines() {
+        let dir = tempdir().unwrap();
+        let mut from_config = make_routine("daily", "0 9 * * *");
+        from_config.source = RoutineSource::Config;
+        let engine = engine_at(dir.path(), vec![from_config]).await;
+
+        let json = r#"{"version":1,"exported_at":"x","routines":[
+            {"name":"daily","schedule":"0 8 * * *","message":"m"},
+            {"name":"bad","schedule":"0 8 *","message":"m"}]}"#;
+        let summary = engine.clone().import_routines(json, true).await.unwrap();
+        assert_eq!(summary.skipped, vec!["daily"]);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(engine.get_routine("daily").unwrap().schedule, "0 9 * * *");
+    }
+
+    #[test]
+    fn init_db_adds_deferred_column_to_old_log_table() {
+        let dir = tempdir().unwrap();
+        let conn = Connection::open(dir.path().join("old.db")).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE routines_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                routine_name TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                finished_at TEXT NOT NULL,
+                success INTEGER NOT NULL,
+                output TEXT NOT NULL DEFAULT '',
+                error TEXT
+            );",
+        )
+        .unwrap();
+        RoutineEngine::init_db(&conn).unwrap();
+        // 再次初始化不报错（列已存在）
+        RoutineEngine::init_db(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO routines_log (routine_name, started_at, finished_at, success, deferred) \
+             VALUES ('a', 'x', 'y', 0, 1)",
+            [],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn init_db_versions_pre_migration_db_with_deferred_column() {
+        // 引入 schema 版本之前、已由旧逻辑补过 deferred 列的库（user_version = 0）
+        let dir = tempdir().unwrap();
+        let conn = Connection::open(dir.path().join("old.db")).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE routines (
+                name TEXT PRIMARY KEY, schedule TEXT NOT NULL, message TEXT NOT NULL,
+                channel TEXT NOT NULL DEFAULT 'cli', enabled INTEGER NOT NULL DEFAULT 1,
+                created_at TEXT NOT NULL
+            );
+            INSERT INTO routines VALUES ('old', '0 8 * * *', 'hi', 'cli', 1, 'x');
+            CREATE TABLE routines_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, routine_name TEXT NOT NULL,
+                started_at TEXT NOT NULL, finished_at TEXT NOT NULL, success INTEGER NOT NULL,
+                output TEXT NOT NULL DEFAULT '', error TEXT,
+                deferred INTEGER NOT NULL DEFAULT 0
+            );",
+        )
+        .unwrap();
+
+        RoutineEngine::init_db(&conn).unwrap();
+        RoutineEngine::init_db(&conn).unwrap();
+
+        let latest = MIGRATIONS.last().unwrap().version;
+        assert_eq!(
+            crate::memory::migrations::schema_version(&conn).unwrap(),
+            latest
+        );
+        let routines = RoutineEngine::load_dynamic_routines(&conn).unwrap();
+        assert_eq!(routines.len(), 1);
+        assert_eq!(routines[0].name, "old");
+    }
+
     #[test]
     fn routine_source_default_is_config() {
         let source = RoutineSource::default();
@@ -1415,7 +3508,7 @@ mod tests {
         let entry =
             make_memory_entry("GET https://api.example.com/price, headers: User-Agent: Mozilla");
         let recalled = vec![entry];
-        let msg = build_enhanced_message(&recalled, "查询股价");
+        let msg = build_enhanced_message(&recalled, "查询股价", false);
         assert!(
             msg.starts_with("[历史成功方法参考]"),
             "应以历史参考前缀开头"
@@ -1429,7 +3522,7 @@ mod tests {
 
     #[test]
     fn enhanced_message_without_recalled_returns_original() {
-        let msg = build_enhanced_message(&[], "查询股价");
+        let msg = build_enhanced_message(&[], "查询股价", false);
         assert_eq!(msg, "查询股价", "无召回时应原样返回任务消息");
     }
 
@@ -1438,8 +3531,25 @@ mod tests {
         let entry1 = make_memory_entry("方法一");
         let entry2 = make_memory_entry("方法二");
         let recalled = vec![entry1, entry2];
-        let msg = build_enhanced_message(&recalled, "任务");
+        let msg = build_enhanced_message(&recalled, "任务", false);
         assert!(msg.contains("方法一"), "应包含第一条记录");
         assert!(!msg.contains("方法二"), "不应包含第二条记录");
     }
+
+    #[test]
+    fn enhanced_message_stale_approach_is_suppressed_with_hint() {
+        let recalled = vec![make_memory_entry("GET https://old.example.com/api")];
+        let msg = build_enhanced_message(&recalled, "查询股价", true);
+        assert!(msg.starts_with("[注意] 上次记录的方法已连续失败"));
+        assert!(!msg.contains("[历史成功方法参考]"));
+        assert!(!msg.contains("old.example.com"), "失效方法不应再注入");
+        assert!(msg.ends_with("查询股价"));
+    }
+
+    #[test]
+    fn enhanced_message_stale_without_recalled_returns_original() {
+        // 没有历史方法时无需提示
+        let msg = build_enhanced_message(&[], "查询股价", true);
+        assert_eq!(msg, "查询股价");
+    }
 }
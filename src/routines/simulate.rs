@@ -0,0 +1,133 @@
+//! Routine 模拟执行（`/routine simulate <name>`）
+//!
+//! 在无人值守地以 Full 模式定时运行之前，先在用户监督下试跑一次：
+//! - Agent 以 Supervised 模式运行，调用方通过 `configure` 接入确认回调（CLI 为 REPL 的确认提示）
+//! - 事件流式发送给调用方，像普通对话一样显示
+//! - 不写 routines_log、不发送到 Routine 的 channel、不改历史方法的失败计数
+//!
+//! 消息构造（模板展开 + 历史方法注入）与 system prompt 段（`set_routine_name`）与正式执行一致；
+//! Agent 共享 Memory，LLM 通过 memory_store 保存的方法照常生效。链式 Routine 需分别模拟子任务。
+
+use color_eyre::eyre::{eyre, Result};
+use tokio::sync::mpsc;
+
+use super::condition::ConditionOutcome;
+use super::{approach_key, RoutineEngine};
+use crate::agent::turns::is_failed_tool_result;
+use crate::agent::Agent;
+use crate::providers::{ConversationMessage, StreamEvent};
+use crate::security::AutonomyLevel;
+
+/// 模拟执行的结果
+#[derive(Debug, Clone)]
+pub struct SimulationReport {
+    pub output: String,
+    /// 正式触发时的条件检查结果（模拟不因条件不满足而跳过）
+    pub condition: Option<ConditionOutcome>,
+    /// 消息中是否注入了历史方法
+    pub approach_injected: bool,
+    /// 本次是否通过 memory_store 更新了 `routine:<name>:approach`
+    pub approach_updated: bool,
+}
+
+impl RoutineEngine {
+    /// 以 Supervised 模式试跑一次 Routine，事件发送到 `tx`
+    pub async fn simulate(
+        &self,
+        name: &str,
+        configure: impl FnOnce(&mut Agent),
+        tx: mpsc::Sender<StreamEvent>,
+    ) -> Result<SimulationReport> {
+        let routine = self
+            .get_routine(name)
+            .ok_or_else(|| eyre!("Routine '{}' 不存在", name))?;
+        if !routine.chain.is_empty() {
+            return Err(eyre!(
+                "链式 Routine '{}' 不支持模拟，请分别模拟子任务: {}",
+                name,
+                routine.chain.join(", ")
+            ));
+        }
+
+        let condition = match &routine.condition {
+            Some(condition) => Some(condition.evaluate().await),
+            None => None,
+        };
+        let (message, approach_injected) = self.prepare_message(&routine).await;
+
+        let mut agent = self.agent_factory().create_agent()?;
+        agent.set_autonomy(AutonomyLevel::Supervised);
+        agent.set_routine_name(routine.name.clone());
+        configure(&mut agent);
+
+        let output = agent.process_message_stream(&message, tx).await?;
+        Ok(SimulationReport {
+            output,
+            condition,
+            approach_injected,
+            approach_updated: approach_stored(agent.history(), &approach_key(&routine.name)),
+        })
+    }
+}
+
+/// history 中是否有成功执行、写入 `key` 的 memory_store 调用
+fn approach_stored(history: &[ConversationMessage], key: &str) -> bool {
+    let call_ids: Vec<&str> = history
+        .iter()
+        .filter_map(|m| match m {
+            ConversationMessage::AssistantToolCalls { tool_calls, .. } => Some(tool_calls),
+            _ => None,
+        })
+        .flatten()
+        .filter(|tc| tc.name == "memory_store" && tc.arguments["key"].as_str() == Some(key))
+        .map(|tc| tc.id.as_str())
+        .collect();
+    history.iter().any(|m| {
+        matches!(m, ConversationMessage::ToolResult { tool_call_id, content, .. }
+            if call_ids.contains(&tool_call_id.as_str()) && !is_failed_tool_result(content))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::approval::USER_DENIED_MESSAGE;
+    use crate::providers::ToolCall;
+
+    fn store_call(id: &str, key: &str) -> ConversationMessage {
+        ConversationMessage::AssistantToolCalls {
+            text: None,
+            reasoning_content: None,
+            tool_calls: vec![ToolCall {
+                id: id.to_string(),
+                name: "memory_store".to_string(),
+                arguments: serde_json::json!({"key": key, "content": "GET /api/quote"}),
+            }],
+            turn: 1,
+        }
+    }
+
+    fn result(id: &str, content: &str) -> ConversationMessage {
+        ConversationMessage::ToolResult {
+            tool_call_id: id.to_string(),
+            content: content.to_string(),
+            turn: 1,
+        }
+    }
+
+    #[test]
+    fn approach_counts_only_successful_store_of_its_key() {
+        let key = approach_key("stock");
+        let stored = vec![store_call("c1", &key), result("c1", "已记住")];
+        assert!(approach_stored(&stored, &key));
+
+        let denied = vec![store_call("c1", &key), result("c1", USER_DENIED_MESSAGE)];
+        assert!(!approach_stored(&denied, &key));
+
+        let other_key = vec![
+            store_call("c1", "routine:other:approach"),
+            result("c1", "已记住"),
+        ];
+        assert!(!approach_stored(&other_key, &key));
+    }
+}
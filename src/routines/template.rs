@@ -0,0 +1,143 @@
+//! Routine 消息模板变量
+//!
+//! 触发时（`prepare_message` 拼接历史方法之前）展开消息中的占位符，时间按 `[routines] timezone`：
+//! - `{date}` / `{today}` → `2025-03-14`
+//! - `{time}` → `08:00`
+//! - `{weekday}` → `星期五` / `Friday`（随界面语言）
+//! - `{env:VAR}` → 环境变量 VAR 的值（调用方决定是否允许，动态 Routine 不展开）
+//!
+//! 未知占位符、未设置的环境变量以及不成对的花括号原样保留，因此消息里的 JSON 等内容不受影响。
+
+use chrono::{Datelike, NaiveDateTime, Weekday};
+
+use crate::i18n::Language;
+
+/// 展开 `message` 中的占位符；`env` 查询环境变量（测试可注入）
+pub fn expand(
+    message: &str,
+    now: NaiveDateTime,
+    lang: Language,
+    env: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let token = after
+            .find('}')
+            .map(|end| &after[..end])
+            .filter(|token| !token.contains('{'));
+        match token.and_then(|token| resolve(token, now, lang, &env).map(|v| (token, v))) {
+            Some((token, value)) => {
+                out.push_str(&value);
+                rest = &after[token.len() + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn resolve(
+    token: &str,
+    now: NaiveDateTime,
+    lang: Language,
+    env: &impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    match token {
+        "date" | "today" => Some(now.format("%Y-%m-%d").to_string()),
+        "time" => Some(now.format("%H:%M").to_string()),
+        "weekday" => Some(weekday_name(now.weekday(), lang).to_string()),
+        _ => token
+            .strip_prefix("env:")
+            .filter(|name| !name.is_empty())
+            .and_then(env),
+    }
+}
+
+fn weekday_name(day: Weekday, lang: Language) -> &'static str {
+    const ZH: [&str; 7] = [
+        "星期一",
+        "星期二",
+        "星期三",
+        "星期四",
+        "星期五",
+        "星期六",
+        "星期日",
+    ];
+    const EN: [&str; 7] = [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ];
+    let index = day.num_days_from_monday() as usize;
+    if lang.is_english() {
+        EN[index]
+    } else {
+        ZH[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn date_time_and_weekday_expand() {
+        let now = at("2025-03-14 08:05");
+        assert_eq!(
+            expand("总结 {date} 的邮件", now, Language::Chinese, no_env),
+            "总结 2025-03-14 的邮件"
+        );
+        assert_eq!(
+            expand("{weekday} {time}", now, Language::English, no_env),
+            "Friday 08:05"
+        );
+        assert_eq!(
+            expand("{today}", now, Language::Chinese, no_env),
+            "2025-03-14"
+        );
+    }
+
+    #[test]
+    fn env_placeholder_reads_variable() {
+        let env = |name: &str| (name == "PROJECT").then(|| "rrclaw".to_string());
+        let now = at("2025-03-14 08:05");
+        assert_eq!(
+            expand("检查 {env:PROJECT} 的 CI", now, Language::Chinese, env),
+            "检查 rrclaw 的 CI"
+        );
+        // 未设置的变量原样保留，方便发现配置错误
+        assert_eq!(
+            expand("{env:MISSING}", now, Language::Chinese, env),
+            "{env:MISSING}"
+        );
+    }
+
+    #[test]
+    fn unknown_tokens_and_stray_braces_are_left_intact() {
+        let now = at("2025-03-14 08:05");
+        let message = r#"{unknown} {"a": {date}} { {date"#;
+        assert_eq!(
+            expand(message, now, Language::English, no_env),
+            r#"{unknown} {"a": 2025-03-14} { {date"#
+        );
+    }
+}
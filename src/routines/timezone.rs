@@ -0,0 +1,151 @@
+//! Routine 调度时区（`[routines] timezone`）
+//!
+//! cron 表达式（包括 `parse_schedule_to_cron` 从"每天早上8点"生成的）按用户所在时区理解：
+//! 配置了 IANA 时区名时使用该时区，否则使用系统本地时区。执行日志的时间戳也按同一时区记录。
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use color_eyre::eyre::{eyre, Result};
+
+/// Routine 调度使用的时区
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoutineTimezone {
+    /// 系统本地时区（未配置 `routines.timezone` 时）
+    Local,
+    /// IANA 时区，如 `Asia/Shanghai`
+    Named(Tz),
+}
+
+impl RoutineTimezone {
+    /// 从配置解析；None / 空字符串表示系统本地时区
+    pub fn from_config(name: Option<&str>) -> Result<Self> {
+        match name.map(str::trim).filter(|s| !s.is_empty()) {
+            None => Ok(Self::Local),
+            Some(name) => name.parse::<Tz>().map(Self::Named).map_err(|_| {
+                eyre!(
+                    "routines.timezone 无效: '{}'（应为 IANA 时区名，如 \"Asia/Shanghai\"）",
+                    name
+                )
+            }),
+        }
+    }
+
+    /// 显示用名称
+    pub fn name(&self) -> String {
+        match self {
+            Self::Local => "local".to_string(),
+            Self::Named(tz) => tz.name().to_string(),
+        }
+    }
+
+    /// 当前时间（带本时区偏移的 RFC 3339），用于执行日志
+    pub fn now_rfc3339(&self) -> String {
+        self.format_rfc3339(Utc::now())
+    }
+
+    /// 本时区的当前墙上时间（消息模板变量用）
+    pub fn now_naive(&self) -> NaiveDateTime {
+        let now = Utc::now();
+        match self {
+            Self::Local => now.with_timezone(&Local).naive_local(),
+            Self::Named(tz) => now.with_timezone(tz).naive_local(),
+        }
+    }
+
+    /// 将 UTC 时间转换为本时区的 RFC 3339 字符串
+    pub fn format_rfc3339(&self, at: DateTime<Utc>) -> String {
+        match self {
+            Self::Local => at.with_timezone(&Local).to_rfc3339(),
+            Self::Named(tz) => at.with_timezone(tz).to_rfc3339(),
+        }
+    }
+
+    /// 本时区的 `YYYY-MM-DD HH:MM`（界面展示用）
+    pub fn format_short(&self, at: DateTime<Utc>) -> String {
+        match self {
+            Self::Local => at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string(),
+            Self::Named(tz) => at.with_timezone(tz).format("%Y-%m-%d %H:%M").to_string(),
+        }
+    }
+
+    /// 5 字段 cron 在本时区解释时，`after` 之后的下一次触发时间（UTC）
+    pub fn next_fire(&self, schedule: &str, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        match self {
+            Self::Local => next_fire_in(schedule, &after.with_timezone(&Local)),
+            Self::Named(tz) => next_fire_in(schedule, &after.with_timezone(tz)),
+        }
+    }
+}
+
+fn next_fire_in<T: TimeZone>(schedule: &str, after: &DateTime<T>) -> Result<DateTime<Utc>> {
+    let cron = croner::Cron::new(schedule)
+        .with_dom_and_dow()
+        .parse()
+        .map_err(|e| eyre!("cron 表达式无效 ({}): {}", schedule, e))?;
+    cron.find_next_occurrence(after, false)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| eyre!("计算下次触发时间失败 ({}): {}", schedule, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn from_config_parses_iana_names() {
+        assert_eq!(
+            RoutineTimezone::from_config(None).unwrap(),
+            RoutineTimezone::Local
+        );
+        assert_eq!(
+            RoutineTimezone::from_config(Some("  ")).unwrap(),
+            RoutineTimezone::Local
+        );
+        assert_eq!(
+            RoutineTimezone::from_config(Some("Asia/Shanghai")).unwrap(),
+            RoutineTimezone::Named(chrono_tz::Asia::Shanghai)
+        );
+        let err = RoutineTimezone::from_config(Some("Mars/Olympus")).unwrap_err();
+        assert!(err.to_string().contains("Mars/Olympus"));
+    }
+
+    #[test]
+    fn eight_am_shanghai_fires_at_midnight_utc() {
+        let tz = RoutineTimezone::from_config(Some("Asia/Shanghai")).unwrap();
+        let cron = crate::routines::parse_schedule_to_cron("每天早上8点").unwrap();
+        // 上海 2026-03-10 09:00 已过当天 8 点 → 下一次是 03-11 08:00 (+08:00) = 03-11 00:00 UTC
+        let next = tz.next_fire(&cron, utc("2026-03-10T01:00:00Z")).unwrap();
+        assert_eq!(next, utc("2026-03-11T00:00:00Z"));
+    }
+
+    #[test]
+    fn eight_am_new_york_follows_daylight_saving() {
+        let tz = RoutineTimezone::from_config(Some("America/New_York")).unwrap();
+        // 冬令时 (EST, UTC-5)
+        let winter = tz
+            .next_fire("0 8 * * *", utc("2026-01-15T00:00:00Z"))
+            .unwrap();
+        assert_eq!(winter, utc("2026-01-15T13:00:00Z"));
+        // 夏令时 (EDT, UTC-4)
+        let summer = tz
+            .next_fire("0 8 * * *", utc("2026-07-15T00:00:00Z"))
+            .unwrap();
+        assert_eq!(summer, utc("2026-07-15T12:00:00Z"));
+    }
+
+    #[test]
+    fn log_timestamps_carry_the_zone_offset() {
+        let tz = RoutineTimezone::Named(chrono_tz::Asia::Tokyo);
+        assert_eq!(
+            tz.format_rfc3339(utc("2026-03-10T00:30:00Z")),
+            "2026-03-10T09:30:00+09:00"
+        );
+    }
+}
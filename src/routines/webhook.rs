@@ -0,0 +1,190 @@
+//! Routine 结果投递到 webhook（`channel = "webhook"`）
+//!
+//! 向 `channel_target` POST JSON `{routine, output, timestamp}`，可直接对接 Slack incoming webhook、
+//! n8n 等。URL 与 http 工具走同一套 SSRF 检查（`[security] http_allowed_hosts` 白名单），不跟随重定向。
+//! 失败（非 2xx / 网络错误）后重试一次，仍失败时由调用方降级为 cli 输出。
+
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::warn;
+
+/// 单次请求超时
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+/// 失败后重试前的等待
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// 错误信息中保留的响应体字符数
+const ERROR_BODY_CHARS: usize = 200;
+
+/// POST 的 JSON 请求体
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub routine: &'a str,
+    pub output: &'a str,
+    /// RFC3339，按 `[routines] timezone`
+    pub timestamp: String,
+}
+
+/// 发送到 `url`；SSRF 检查不通过时直接返回错误（不发起请求、不重试）
+pub async fn deliver(
+    url: &str,
+    http_allowed_hosts: &[String],
+    payload: &WebhookPayload<'_>,
+) -> Result<()> {
+    if let Some(reason) = crate::tools::http::validate_url(url, http_allowed_hosts) {
+        // 原因格式为 "说明|建议"，日志中只保留说明
+        let reason = reason.split('|').next().unwrap_or_default().to_string();
+        return Err(eyre!("webhook 地址被拒绝: {}", reason));
+    }
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    match post(&client, url, payload).await {
+        Ok(()) => Ok(()),
+        Err(first) => {
+            warn!(
+                "webhook 发送失败，{}s 后重试: {}",
+                RETRY_DELAY.as_secs(),
+                first
+            );
+            tokio::time::sleep(RETRY_DELAY).await;
+            post(&client, url, payload)
+                .await
+                .map_err(|e| eyre!("webhook 重试后仍失败: {}", e))
+        }
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, payload: &WebhookPayload<'_>) -> Result<()> {
+    let resp = client.post(url).json(payload).send().await?;
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body: String = resp
+        .text()
+        .await
+        .unwrap_or_default()
+        .chars()
+        .take(ERROR_BODY_CHARS)
+        .collect();
+    Err(eyre!("HTTP {}: {}", status.as_u16(), body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// 本地 webhook 服务：记录每个请求体，前 `failures` 次返回 500，之后返回 200
+    async fn spawn_webhook_server(
+        bodies: Arc<Mutex<Vec<String>>>,
+        failures: usize,
+    ) -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 1024];
+                loop {
+                    let n = stream.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let content_length = text[..end]
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                            })
+                            .unwrap_or(0);
+                        if buf.len() >= end + 4 + content_length {
+                            bodies.lock().unwrap().push(text[end + 4..].to_string());
+                            break;
+                        }
+                    }
+                }
+                let status = if bodies.lock().unwrap().len() <= failures {
+                    "500 Internal Server Error"
+                } else {
+                    "200 OK"
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    fn payload() -> WebhookPayload<'static> {
+        WebhookPayload {
+            routine: "morning_brief",
+            output: "今天有 3 个待办",
+            timestamp: "2025-03-14T08:00:00+08:00".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_json_body_and_retries_once() {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let addr = spawn_webhook_server(bodies.clone(), 1).await;
+        let url = format!("http://{}/hook", addr);
+
+        deliver(&url, &["127.0.0.1".to_string()], &payload())
+            .await
+            .unwrap();
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2, "第一次 500 后应重试一次");
+        let body: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "routine": "morning_brief",
+                "output": "今天有 3 个待办",
+                "timestamp": "2025-03-14T08:00:00+08:00",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn persistent_failure_is_reported_after_retry() {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let addr = spawn_webhook_server(bodies.clone(), usize::MAX).await;
+        let url = format!("http://{}/hook", addr);
+
+        let err = deliver(&url, &["127.0.0.1".to_string()], &payload())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("HTTP 500"), "{}", err);
+        assert_eq!(bodies.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn disallowed_host_is_rejected_without_a_request() {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let addr = spawn_webhook_server(bodies.clone(), 0).await;
+        let url = format!("http://{}/hook", addr);
+
+        // 127.0.0.1 不在白名单中：SSRF 防护拒绝
+        let err = deliver(&url, &[], &payload()).await.unwrap_err();
+        assert!(err.to_string().contains("SSRF"), "{}", err);
+        let err = deliver("http://169.254.169.254/latest", &[], &payload())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("SSRF"), "{}", err);
+        assert!(bodies.lock().unwrap().is_empty());
+    }
+}
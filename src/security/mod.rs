@@ -1,5 +1,7 @@
 pub mod injection;
 pub mod policy;
+pub mod redact;
+pub mod sandbox;
 
 pub use policy::{AutonomyLevel, SecurityPolicy};
 // injection 模块的函数按需在调用处 use，无需 re-export
@@ -1,3 +1,4 @@
+use color_eyre::eyre::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
@@ -101,6 +102,25 @@ impl SecurityPolicy {
         true
     }
 
+    /// 切换工作目录：必须是已存在的目录且不在 blocked_paths 下，保存 canonicalize 后的路径
+    pub fn set_workspace(&mut self, dir: &Path) -> Result<()> {
+        let resolved = dir
+            .canonicalize()
+            .wrap_err_with(|| format!("工作目录不存在: {}", dir.display()))?;
+        if !resolved.is_dir() {
+            bail!("工作目录不是目录: {}", resolved.display());
+        }
+        if let Some(blocked) = self.blocked_paths.iter().find(|b| resolved.starts_with(b)) {
+            bail!(
+                "工作目录 {} 位于受保护路径 {} 下",
+                resolved.display(),
+                blocked.display()
+            );
+        }
+        self.workspace_dir = resolved;
+        Ok(())
+    }
+
     /// Supervised 模式下需要用户确认
     pub fn requires_confirmation(&self) -> bool {
         self.autonomy == AutonomyLevel::Supervised
@@ -284,4 +304,23 @@ mod tests {
         assert_eq!(policy.autonomy, AutonomyLevel::Supervised);
         assert!(policy.requires_confirmation());
     }
+
+    #[test]
+    fn set_workspace_validates_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "x").unwrap();
+        let mut policy = test_policy(Path::new("/nonexistent"));
+
+        policy.set_workspace(&dir.path().join(".")).unwrap();
+        assert_eq!(policy.workspace_dir, dir.path().canonicalize().unwrap());
+        assert!(policy.is_path_allowed(Path::new("notes.txt")));
+
+        assert!(policy.set_workspace(&dir.path().join("missing")).is_err());
+        assert!(policy.set_workspace(&file).is_err());
+        let err = policy.set_workspace(Path::new("/etc")).unwrap_err();
+        assert!(err.to_string().contains("受保护路径"), "{}", err);
+        // 失败时保留原工作目录
+        assert_eq!(policy.workspace_dir, dir.path().canonicalize().unwrap());
+    }
 }
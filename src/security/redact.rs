@@ -0,0 +1,249 @@
+//! 敏感信息脱敏
+//!
+//! 配置中的 API Key、Bot Token、自定义 Header、MCP 环境变量等都可能出现在日志、
+//! 对话历史（用户粘贴）或导出的报告里。`Redactor` 从 Config 收集这些已知密钥值，
+//! 再配合常见密钥形态的正则（`sk-...`、`Bearer ...`），对任意文本做替换。
+//!
+//! 对话摘要写入记忆前同样经过脱敏（`Redactor::for_memory`，`[memory] scrub*`）：
+//! 额外的正则（邮箱、`scrub_patterns`）整段替换为 `[已脱敏]`。
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use tracing::warn;
+
+use crate::config::{Config, McpTransport};
+
+/// 短于此长度的配置值不做全文替换（避免把 "1"、"on" 之类替换得面目全非）
+const MIN_SECRET_LEN: usize = 6;
+
+/// 额外正则匹配内容的替换文本
+pub const REDACTED: &str = "[已脱敏]";
+
+/// 邮箱地址（`[memory] scrub_emails`）
+const EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}";
+
+/// 单个密钥脱敏：显示前 4 字符 + ***（不足 5 字符时全部隐藏）
+pub fn mask_secret(secret: &str) -> String {
+    match secret.char_indices().nth(4) {
+        Some((idx, _)) => format!("{}***", &secret[..idx]),
+        None => "***".to_string(),
+    }
+}
+
+/// 常见密钥形态（不在配置中、但出现在文本里的 Key）
+fn secret_patterns() -> &'static [Regex] {
+    static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            r"sk-[A-Za-z0-9_\-]{12,}",
+            r"(?i)bearer\s+[A-Za-z0-9_\-\.=]{12,}",
+            r"\b\d{8,10}:[A-Za-z0-9_\-]{30,}\b", // Telegram Bot Token
+        ]
+        .iter()
+        .map(|p| Regex::new(p).expect("内置正则合法"))
+        .collect()
+    })
+}
+
+/// 基于配置的文本脱敏器
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    secrets: Vec<String>,
+    /// 额外正则，匹配内容整段替换为 `REDACTED`
+    extra: Vec<Regex>,
+}
+
+impl Redactor {
+    /// 收集配置中的全部密钥值
+    pub fn from_config(config: &Config) -> Self {
+        let mut secrets = Vec::new();
+        for provider in config.providers.values() {
+            secrets.push(provider.api_key.clone());
+            secrets.extend(provider.headers.values().cloned());
+        }
+        if let Some(token) = config.telegram.as_ref().and_then(|t| t.bot_token.clone()) {
+            secrets.push(token);
+        }
+        secrets.push(config.search.api_key.clone());
+        if let Some(mcp) = &config.mcp {
+            for server in mcp.servers.values() {
+                match &server.transport {
+                    McpTransport::Stdio { env, .. } => secrets.extend(env.values().cloned()),
+                    McpTransport::Sse { headers, .. } => secrets.extend(headers.values().cloned()),
+                }
+            }
+        }
+        secrets.retain(|s| s.len() >= MIN_SECRET_LEN);
+        // 长的先替换，避免一个密钥是另一个的前缀时只替换一半
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        secrets.dedup();
+        Self {
+            secrets,
+            extra: Vec::new(),
+        }
+    }
+
+    /// 对话摘要写入记忆前使用的脱敏器；`[memory] scrub = false` 时为 None
+    ///
+    /// 无效的 `scrub_patterns` 记 warn 后跳过（`Config::validate` 同样会提示）。
+    pub fn for_memory(config: &Config) -> Option<Self> {
+        if !config.memory.scrub {
+            return None;
+        }
+        let mut redactor = Self::from_config(config);
+        if config.memory.scrub_emails {
+            redactor
+                .extra
+                .push(Regex::new(EMAIL_PATTERN).expect("内置正则合法"));
+        }
+        for pattern in &config.memory.scrub_patterns {
+            match Regex::new(pattern) {
+                Ok(regex) => redactor.extra.push(regex),
+                Err(e) => warn!("忽略无效的 memory.scrub_patterns '{}': {}", pattern, e),
+            }
+        }
+        Some(redactor)
+    }
+
+    /// 替换文本中的已知密钥与常见密钥形态
+    pub fn redact(&self, text: &str) -> String {
+        let mut result = text.to_string();
+        for secret in &self.secrets {
+            if result.contains(secret.as_str()) {
+                result = result.replace(secret.as_str(), &mask_secret(secret));
+            }
+        }
+        for pattern in secret_patterns() {
+            result = pattern
+                .replace_all(&result, |caps: &regex::Captures| mask_secret(&caps[0]))
+                .into_owned();
+        }
+        for pattern in &self.extra {
+            result = pattern.replace_all(&result, REDACTED).into_owned();
+        }
+        result
+    }
+
+    /// 返回密钥字段已脱敏的配置副本
+    pub fn redact_config(&self, config: &Config) -> Config {
+        let mut config = config.clone();
+        for provider in config.providers.values_mut() {
+            provider.api_key = mask_secret(&provider.api_key);
+            provider.headers.values_mut().for_each(mask_in_place);
+        }
+        if let Some(token) = config.telegram.as_mut().and_then(|t| t.bot_token.as_mut()) {
+            mask_in_place(token);
+        }
+        if let Some(mcp) = config.mcp.as_mut() {
+            for server in mcp.servers.values_mut() {
+                match &mut server.transport {
+                    McpTransport::Stdio { env, .. } => env.values_mut().for_each(mask_in_place),
+                    McpTransport::Sse { headers, .. } => {
+                        headers.values_mut().for_each(mask_in_place)
+                    }
+                }
+            }
+        }
+        config
+    }
+}
+
+fn mask_in_place(value: &mut String) {
+    *value = mask_secret(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ProviderConfig, TelegramConfig};
+
+    fn config_with_secrets() -> Config {
+        let mut config = Config::default();
+        config.providers.insert(
+            "deepseek".to_string(),
+            ProviderConfig {
+                base_url: "https://api.deepseek.com/v1".to_string(),
+                api_key: "ds-secret-key-12345".to_string(),
+                model: "deepseek-chat".to_string(),
+                auth_style: None,
+                headers: [("X-Org".to_string(), "org-private-999".to_string())].into(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
+            },
+        );
+        config.telegram = Some(TelegramConfig {
+            bot_token: Some("tg-token-abcdef".to_string()),
+            allowed_chat_ids: vec![],
+            ..Default::default()
+        });
+        config
+    }
+
+    #[test]
+    fn mask_secret_keeps_short_prefix() {
+        assert_eq!(mask_secret("abc"), "***");
+        assert_eq!(mask_secret("sk-abcdefgh"), "sk-a***");
+        assert_eq!(mask_secret("密钥密钥密钥"), "密钥密钥***");
+    }
+
+    #[test]
+    fn redact_replaces_config_secrets_and_patterns() {
+        let redactor = Redactor::from_config(&config_with_secrets());
+        let text = "key=ds-secret-key-12345 org=org-private-999 tg=tg-token-abcdef \
+                    other=sk-abcdefghijklmnop auth: Bearer abcdefghijklmnopqrst";
+        let redacted = redactor.redact(text);
+        for secret in [
+            "ds-secret-key-12345",
+            "org-private-999",
+            "tg-token-abcdef",
+            "sk-abcdefghijklmnop",
+            "abcdefghijklmnopqrst",
+        ] {
+            assert!(
+                !redacted.contains(secret),
+                "{} leaked: {}",
+                secret,
+                redacted
+            );
+        }
+        assert!(redacted.contains("key=ds-s***"));
+    }
+
+    #[test]
+    fn memory_redactor_follows_scrub_config() {
+        let mut config = config_with_secrets();
+        config.memory.scrub_emails = true;
+        config.memory.scrub_patterns = vec![r"\b\d{11}\b".to_string(), "(".to_string()];
+        let redactor = Redactor::for_memory(&config).unwrap();
+        let redacted =
+            redactor.redact("邮箱 me@example.com 手机 13800138000 key sk-abcdefghijklmnop");
+        assert_eq!(redacted, "邮箱 [已脱敏] 手机 [已脱敏] key sk-a***");
+
+        config.memory.scrub_emails = false;
+        let redacted = Redactor::for_memory(&config)
+            .unwrap()
+            .redact("me@example.com");
+        assert_eq!(redacted, "me@example.com");
+
+        config.memory.scrub = false;
+        assert!(Redactor::for_memory(&config).is_none());
+    }
+
+    #[test]
+    fn redact_config_masks_secret_fields_only() {
+        let config = config_with_secrets();
+        let redacted = Redactor::from_config(&config).redact_config(&config);
+        let provider = &redacted.providers["deepseek"];
+        assert_eq!(provider.api_key, "ds-s***");
+        assert_eq!(provider.headers["X-Org"], "org-***");
+        assert_eq!(provider.model, "deepseek-chat");
+        assert_eq!(
+            redacted.telegram.unwrap().bot_token.as_deref(),
+            Some("tg-t***")
+        );
+    }
+}
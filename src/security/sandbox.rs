@@ -0,0 +1,341 @@
+//! OS 级沙箱集成
+//!
+//! 路径 / 命令白名单都在进程内执行，一次 `shell` 逃逸即可绕过。这里生成交给操作系统执行的
+//! 限制配置：Linux 上是 bubblewrap（不可用时回退 `systemd-run --user`）包装脚本，macOS 上是
+//! `sandbox-exec` profile，只放行 workspace、RRClaw 数据目录和 Provider 端口。
+//!
+//! 包装脚本会设置 `RRCLAW_SANDBOX=<kind>` 环境变量，RRClaw 启动时据此判断自己是否在沙箱中；
+//! `security.require_sandbox = true` 时，沙箱外拒绝 Full 自主模式。
+
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use super::AutonomyLevel;
+
+/// 沙箱包装脚本设置的环境变量，值为沙箱类型（`bwrap` / `systemd-run` / `sandbox-exec`）
+pub const SANDBOX_ENV: &str = "RRCLAW_SANDBOX";
+
+/// 拒绝 Full 模式时给用户的提示
+pub const FULL_REFUSED_MESSAGE: &str =
+    "security.require_sandbox 已开启，但当前未在沙箱中运行：拒绝 Full 模式（运行 `rrclaw sandbox-profile` 生成沙箱配置）";
+
+/// 生成配置的目标平台
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Platform {
+    /// bubblewrap / systemd-run 包装脚本
+    Linux,
+    /// sandbox-exec profile
+    Macos,
+}
+
+impl Platform {
+    /// 当前编译目标对应的平台（其他平台返回 None）
+    pub fn current() -> Option<Self> {
+        if cfg!(target_os = "linux") {
+            Some(Platform::Linux)
+        } else if cfg!(target_os = "macos") {
+            Some(Platform::Macos)
+        } else {
+            None
+        }
+    }
+}
+
+/// 生成沙箱配置所需的参数
+#[derive(Debug, Clone)]
+pub struct ProfileParams {
+    /// 允许读写的工作目录
+    pub workspace_dir: PathBuf,
+    /// RRClaw 需要读写的目录（配置、数据、日志；见 `RrclawPaths::writable_dirs`）
+    pub rrclaw_dirs: Vec<PathBuf>,
+    /// rrclaw 可执行文件
+    pub binary: PathBuf,
+    /// Provider 的 base_url（用于列出需要访问的 host:port）
+    pub provider_urls: Vec<String>,
+}
+
+impl ProfileParams {
+    /// Provider 端点（`host:port`，去重排序）
+    pub fn endpoints(&self) -> Vec<String> {
+        self.provider_urls
+            .iter()
+            .filter_map(|u| url::Url::parse(u).ok())
+            .filter_map(|u| Some(format!("{}:{}", u.host_str()?, u.port_or_known_default()?)))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Provider 端口（去重排序）
+    fn ports(&self) -> Vec<u16> {
+        self.provider_urls
+            .iter()
+            .filter_map(|u| url::Url::parse(u).ok()?.port_or_known_default())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// 生成指定平台的沙箱配置
+pub fn generate_profile(platform: Platform, params: &ProfileParams) -> String {
+    match platform {
+        Platform::Linux => linux_wrapper(params),
+        Platform::Macos => macos_profile(params),
+    }
+}
+
+/// Linux 包装脚本：优先 bubblewrap，不可用时回退 `systemd-run --user`
+pub fn linux_wrapper(params: &ProfileParams) -> String {
+    let workspace = shell_quote(&params.workspace_dir.display().to_string());
+    let dirs: Vec<String> = params
+        .rrclaw_dirs
+        .iter()
+        .map(|d| shell_quote(&d.display().to_string()))
+        .collect();
+    let binary = shell_quote(&params.binary.display().to_string());
+    let mut out = String::new();
+    out.push_str("#!/bin/sh\n");
+    out.push_str("# RRClaw 沙箱包装脚本（由 `rrclaw sandbox-profile` 生成，使用前请检查）\n");
+    out.push_str("# 用法: sh rrclaw-sandbox.sh agent\n");
+    out.push_str("#\n");
+    out.push_str("# 可写: workspace 与 RRClaw 数据目录；系统目录只读；其余 home 目录不可见。\n");
+    out.push_str("# bubblewrap 不过滤网络，需要限制出站时请在防火墙中只放行以下 Provider 端点:\n");
+    for endpoint in params.endpoints() {
+        out.push_str(&format!("#   {}\n", endpoint));
+    }
+    out.push_str("set -eu\n\n");
+    out.push_str("if command -v bwrap >/dev/null 2>&1; then\n");
+    out.push_str(&format!("  export {}=bwrap\n", SANDBOX_ENV));
+    out.push_str("  exec bwrap \\\n");
+    for dir in ["/usr", "/etc", "/bin", "/sbin", "/lib", "/lib64"] {
+        out.push_str(&format!("    --ro-bind-try {dir} {dir} \\\n"));
+    }
+    out.push_str("    --proc /proc --dev /dev --tmpfs /tmp \\\n");
+    out.push_str(&format!("    --ro-bind {binary} {binary} \\\n"));
+    for dir in &dirs {
+        out.push_str(&format!("    --bind {dir} {dir} \\\n"));
+    }
+    out.push_str(&format!("    --bind {workspace} {workspace} \\\n"));
+    out.push_str(&format!("    --chdir {workspace} \\\n"));
+    out.push_str("    --unshare-all --share-net --die-with-parent --new-session \\\n");
+    out.push_str(&format!("    {binary} \"$@\"\n"));
+    out.push_str("fi\n\n");
+    out.push_str(&format!(
+        "exec systemd-run --user --pty --same-dir --wait --collect --quiet \\\n    --setenv={}=systemd-run \\\n",
+        SANDBOX_ENV
+    ));
+    out.push_str("    -p ProtectSystem=strict -p ProtectHome=tmpfs -p PrivateTmp=yes -p NoNewPrivileges=yes \\\n");
+    out.push_str("   ");
+    for dir in &dirs {
+        out.push_str(&format!(" -p BindPaths={dir}"));
+    }
+    out.push_str(&format!(" -p BindPaths={workspace} \\\n"));
+    out.push_str(&format!("    {binary} \"$@\"\n"));
+    out
+}
+
+/// macOS `sandbox-exec` profile（SBPL）
+pub fn macos_profile(params: &ProfileParams) -> String {
+    let workspace = sbpl_quote(&params.workspace_dir.display().to_string());
+    let binary = sbpl_quote(&params.binary.display().to_string());
+    let mut out = String::new();
+    out.push_str("(version 1)\n");
+    out.push_str(
+        ";; RRClaw sandbox-exec profile（由 `rrclaw sandbox-profile` 生成，使用前请检查）\n",
+    );
+    out.push_str(&format!(
+        ";; 用法: {}=sandbox-exec sandbox-exec -f rrclaw.sb {} agent\n",
+        SANDBOX_ENV,
+        params.binary.display()
+    ));
+    out.push_str(";; Provider 端点:\n");
+    for endpoint in params.endpoints() {
+        out.push_str(&format!(";;   {}\n", endpoint));
+    }
+    out.push_str("(deny default)\n");
+    out.push_str(
+        "(allow process-fork process-exec signal sysctl-read mach-lookup ipc-posix-shm iokit-open)\n",
+    );
+    out.push_str("(allow file-read*\n");
+    out.push_str("  (literal \"/\")\n");
+    for dir in [
+        "/usr",
+        "/bin",
+        "/sbin",
+        "/System",
+        "/Library",
+        "/opt/homebrew",
+        "/private/etc",
+        "/private/var/db",
+    ] {
+        out.push_str(&format!("  (subpath \"{}\")\n", dir));
+    }
+    out.push_str(&format!("  (literal {}))\n", binary));
+    out.push_str("(allow file-read* file-write*\n");
+    out.push_str("  (subpath \"/dev\")\n");
+    out.push_str("  (subpath \"/private/tmp\")\n");
+    out.push_str("  (subpath \"/private/var/folders\")\n");
+    for dir in &params.rrclaw_dirs {
+        out.push_str(&format!(
+            "  (subpath {})\n",
+            sbpl_quote(&dir.display().to_string())
+        ));
+    }
+    out.push_str(&format!("  (subpath {}))\n", workspace));
+    out.push_str("(allow system-socket)\n");
+    out.push_str("(allow network-outbound (remote unix-socket))\n");
+    out.push_str("(allow network-bind network-inbound (local unix-socket))\n");
+    out.push_str("(allow network-outbound (remote udp \"*:53\"))\n");
+    for port in params.ports() {
+        out.push_str(&format!(
+            "(allow network-outbound (remote tcp \"*:{}\"))\n",
+            port
+        ));
+    }
+    out
+}
+
+/// 单引号转义（POSIX sh）
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// SBPL 字符串字面量
+fn sbpl_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', r"\\").replace('"', r#"\""#))
+}
+
+/// 从环境变量值判断沙箱类型（空值视为未设置）
+fn detect_from(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && *v != "0")
+        .map(str::to_string)
+}
+
+/// 当前进程所在的沙箱类型（首次调用时读取 `RRCLAW_SANDBOX` 并缓存）
+pub fn current() -> Option<&'static str> {
+    static DETECTED: OnceLock<Option<String>> = OnceLock::new();
+    DETECTED
+        .get_or_init(|| detect_from(std::env::var(SANDBOX_ENV).ok().as_deref()))
+        .as_deref()
+}
+
+/// 是否拒绝 Full 模式（`require_sandbox` 开启且不在沙箱中）
+pub fn refuses_full(require_sandbox: bool) -> bool {
+    require_sandbox && current().is_none()
+}
+
+/// 按 `require_sandbox` 限制自主级别：被拒绝的 Full 降为 Supervised
+pub fn clamp_autonomy(level: AutonomyLevel, require_sandbox: bool) -> AutonomyLevel {
+    clamp_with(level, refuses_full(require_sandbox))
+}
+
+fn clamp_with(level: AutonomyLevel, refuse_full: bool) -> AutonomyLevel {
+    match level {
+        AutonomyLevel::Full if refuse_full => AutonomyLevel::Supervised,
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> ProfileParams {
+        ProfileParams {
+            workspace_dir: PathBuf::from("/home/alice/my project"),
+            rrclaw_dirs: vec![
+                PathBuf::from("/home/alice/.config/rrclaw"),
+                PathBuf::from("/home/alice/.local/share/rrclaw"),
+            ],
+            binary: PathBuf::from("/usr/local/bin/rrclaw"),
+            provider_urls: vec![
+                "https://api.deepseek.com/v1".to_string(),
+                "https://api.anthropic.com".to_string(),
+                "http://localhost:11434/v1".to_string(),
+                "not a url".to_string(),
+            ],
+        }
+    }
+
+    #[test]
+    fn endpoints_are_deduplicated_host_ports() {
+        assert_eq!(
+            params().endpoints(),
+            vec![
+                "api.anthropic.com:443",
+                "api.deepseek.com:443",
+                "localhost:11434"
+            ]
+        );
+        assert_eq!(params().ports(), vec![443, 11434]);
+    }
+
+    #[test]
+    fn linux_wrapper_binds_workspace_and_home_and_sets_marker() {
+        let script = linux_wrapper(&params());
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("--bind '/home/alice/my project' '/home/alice/my project'"));
+        assert!(script.contains("--bind '/home/alice/.config/rrclaw' '/home/alice/.config/rrclaw'"));
+        assert!(script.contains(
+            "--bind '/home/alice/.local/share/rrclaw' '/home/alice/.local/share/rrclaw'"
+        ));
+        assert!(script.contains(
+            "-p BindPaths='/home/alice/.config/rrclaw' -p BindPaths='/home/alice/.local/share/rrclaw' -p BindPaths='/home/alice/my project'"
+        ));
+        assert!(script.contains("--chdir '/home/alice/my project'"));
+        assert!(script.contains("export RRCLAW_SANDBOX=bwrap"));
+        assert!(script.contains("--setenv=RRCLAW_SANDBOX=systemd-run"));
+        assert!(script.contains("-p ProtectHome=tmpfs"));
+        assert!(script.contains("#   api.deepseek.com:443"));
+        assert!(script.contains("'/usr/local/bin/rrclaw' \"$@\""));
+    }
+
+    #[test]
+    fn macos_profile_denies_by_default_and_allows_provider_ports() {
+        let profile = macos_profile(&params());
+        assert!(profile.starts_with("(version 1)\n"));
+        assert!(profile.contains("(deny default)"));
+        assert!(profile.contains("(subpath \"/home/alice/my project\")"));
+        assert!(profile.contains("(subpath \"/home/alice/.config/rrclaw\")"));
+        assert!(profile.contains("(subpath \"/home/alice/.local/share/rrclaw\")"));
+        assert!(profile.contains("(remote tcp \"*:443\")"));
+        assert!(profile.contains("(remote tcp \"*:11434\")"));
+        assert!(profile.contains("RRCLAW_SANDBOX=sandbox-exec sandbox-exec -f rrclaw.sb"));
+    }
+
+    #[test]
+    fn quoting_escapes_special_characters() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(sbpl_quote(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+
+    #[test]
+    fn env_marker_detection() {
+        assert_eq!(detect_from(None), None);
+        assert_eq!(detect_from(Some("")), None);
+        assert_eq!(detect_from(Some("0")), None);
+        assert_eq!(detect_from(Some("bwrap")).as_deref(), Some("bwrap"));
+        assert_eq!(
+            detect_from(Some(" sandbox-exec\n")).as_deref(),
+            Some("sandbox-exec")
+        );
+    }
+
+    #[test]
+    fn full_autonomy_clamped_only_when_refused() {
+        assert_eq!(
+            clamp_with(AutonomyLevel::Full, true),
+            AutonomyLevel::Supervised
+        );
+        assert_eq!(clamp_with(AutonomyLevel::Full, false), AutonomyLevel::Full);
+        assert_eq!(
+            clamp_with(AutonomyLevel::ReadOnly, true),
+            AutonomyLevel::ReadOnly
+        );
+        assert!(!refuses_full(false));
+    }
+}
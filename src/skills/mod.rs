@@ -1,6 +1,9 @@
+pub mod usage;
+
 use std::path::{Path, PathBuf};
 
 use color_eyre::eyre::{eyre, Result};
+use serde::Deserialize;
 
 use crate::i18n::Language;
 
@@ -69,50 +72,119 @@ pub struct SkillContent {
     pub resources: Vec<String>,
 }
 
-/// 解析 SKILL.md 的 YAML frontmatter
-/// 返回 (name, description, tags, body)
-pub fn parse_skill_md(content: &str) -> Result<(String, String, Vec<String>, String)> {
+/// SKILL.md 的 YAML frontmatter
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SkillFrontmatter {
+    #[serde(default)]
+    pub name: String,
+    /// 支持 `>` / `|` 多行写法
+    #[serde(default)]
+    pub description: String,
+    /// 行内 `[a, b]`、块列表（`- a`）或逗号分隔的字符串
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    pub tags: Vec<String>,
+    /// Skill 版本（安装 / 更新时比较用），可省略
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// tags 兼容旧写法：`tags: dev, test` 按逗号拆分
+fn deserialize_tags<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        List(Vec<String>),
+        Text(String),
+    }
+    let tags = match Option::<Tags>::deserialize(deserializer)? {
+        Some(Tags::List(list)) => list,
+        Some(Tags::Text(text)) => text.split(',').map(str::to_string).collect(),
+        None => Vec::new(),
+    };
+    Ok(tags
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect())
+}
+
+/// 解析 SKILL.md：frontmatter（serde_yaml）+ 正文
+///
+/// frontmatter 以首行 `---` 开始，到下一个单独成行的 `---` 结束（描述里的 `---` 不会提前截断）。
+/// YAML 错误附带在 SKILL.md 中的行号 / 列号；name / description 不能为空，name 须符合 `validate_skill_name`。
+pub fn parse_skill_frontmatter(content: &str) -> Result<(SkillFrontmatter, String)> {
     let content = content.trim();
-    if !content.starts_with("---") {
-        return Err(eyre!("SKILL.md 缺少 frontmatter（应以 --- 开头）"));
-    }
-
-    let rest = &content[3..];
-    let end = rest
-        .find("---")
-        .ok_or_else(|| eyre!("frontmatter 未闭合（缺少结束 ---）"))?;
-
-    let frontmatter = rest[..end].trim();
-    let body = rest[end + 3..].trim().to_string();
-
-    let mut name = String::new();
-    let mut description = String::new();
-    let mut tags = Vec::new();
-
-    for line in frontmatter.lines() {
-        let line = line.trim();
-        if let Some(val) = line.strip_prefix("name:") {
-            name = val.trim().trim_matches('"').to_string();
-        } else if let Some(val) = line.strip_prefix("description:") {
-            description = val.trim().trim_matches('"').to_string();
-        } else if let Some(val) = line.strip_prefix("tags:") {
-            let val = val.trim().trim_start_matches('[').trim_end_matches(']');
-            tags = val
-                .split(',')
-                .map(|t| t.trim().trim_matches('"').to_string())
-                .filter(|t| !t.is_empty())
-                .collect();
+    let rest = content
+        .strip_prefix("---")
+        .filter(|rest| rest.starts_with(['\n', '\r']))
+        .ok_or_else(|| eyre!("SKILL.md 缺少 frontmatter（应以 --- 开头）"))?;
+
+    let mut offset = 0;
+    let mut closing = None;
+    for line in rest.split_inclusive('\n') {
+        if offset > 0 && line.trim_end() == "---" {
+            closing = Some((offset, offset + line.len()));
+            break;
         }
-    }
+        offset += line.len();
+    }
+    let (end, body_start) = closing.ok_or_else(|| eyre!("frontmatter 未闭合（缺少结束 ---）"))?;
+    let yaml = &rest[..end];
+    let body = rest[body_start..].trim().to_string();
+
+    let frontmatter: SkillFrontmatter = if yaml.trim().is_empty() {
+        SkillFrontmatter {
+            name: String::new(),
+            description: String::new(),
+            tags: Vec::new(),
+            version: None,
+        }
+    } else {
+        serde_yaml::from_str(yaml).map_err(|e| {
+            let message = e.to_string();
+            let message = message.split(" at line ").next().unwrap_or(&message);
+            match e.location() {
+                // yaml 从 `---` 那一行的换行符开始，行号与 SKILL.md 一致
+                Some(loc) => eyre!(
+                    "SKILL.md frontmatter 解析失败（第 {} 行第 {} 列）: {}",
+                    loc.line(),
+                    loc.column(),
+                    message
+                ),
+                None => eyre!("SKILL.md frontmatter 解析失败: {}", message),
+            }
+        })?
+    };
 
-    if name.is_empty() {
+    let frontmatter = SkillFrontmatter {
+        name: frontmatter.name.trim().to_string(),
+        description: frontmatter.description.trim().to_string(),
+        ..frontmatter
+    };
+    if frontmatter.name.is_empty() {
         return Err(eyre!("SKILL.md frontmatter 缺少 name 字段"));
     }
-    if description.is_empty() {
+    if frontmatter.description.is_empty() {
         return Err(eyre!("SKILL.md frontmatter 缺少 description 字段"));
     }
+    validate_skill_name(&frontmatter.name)?;
+
+    Ok((frontmatter, body))
+}
 
-    Ok((name, description, tags, body))
+/// 解析 SKILL.md 的 YAML frontmatter
+/// 返回 (name, description, tags, body)
+pub fn parse_skill_md(content: &str) -> Result<(String, String, Vec<String>, String)> {
+    let (frontmatter, body) = parse_skill_frontmatter(content)?;
+    Ok((
+        frontmatter.name,
+        frontmatter.description,
+        frontmatter.tags,
+        body,
+    ))
 }
 
 /// 校验 skill name 合法性
@@ -408,6 +480,58 @@ mod tests {
         assert!(tags.is_empty());
     }
 
+    #[test]
+    fn parse_multiline_description_and_block_tags() {
+        let content = "---\nname: release\nversion: \"1.2.0\"\ndescription: >\n  发布新版本。\n  当用户要求发版时使用。\ntags:\n  - dev\n  - release\n---\n\n# 步骤";
+        let (fm, body) = parse_skill_frontmatter(content).unwrap();
+        assert_eq!(fm.name, "release");
+        assert_eq!(fm.description, "发布新版本。 当用户要求发版时使用。");
+        assert_eq!(fm.tags, vec!["dev", "release"]);
+        assert_eq!(fm.version.as_deref(), Some("1.2.0"));
+        assert_eq!(body, "# 步骤");
+
+        // `|` 保留换行；缩进的 --- 属于描述，不会提前结束 frontmatter
+        let content = "---\nname: steps\ndescription: |\n  Step one\n  ---\n  Step two\ntags: [a, b]\n---\nbody";
+        let (fm, body) = parse_skill_frontmatter(content).unwrap();
+        assert_eq!(fm.description, "Step one\n---\nStep two");
+        assert_eq!(fm.tags, vec!["a", "b"]);
+        assert_eq!(fm.version, None);
+        assert_eq!(body, "body");
+    }
+
+    #[test]
+    fn parse_quoted_values_with_colons() {
+        let content = "---\nname: http-debug\ndescription: \"Debug APIs: inspect headers, retry: on 5xx\"\ntags: 'net, http'\n---\n\nbody";
+        let (name, desc, tags, _) = parse_skill_md(content).unwrap();
+        assert_eq!(name, "http-debug");
+        assert_eq!(desc, "Debug APIs: inspect headers, retry: on 5xx");
+        // 旧写法：逗号分隔的字符串
+        assert_eq!(tags, vec!["net", "http"]);
+    }
+
+    #[test]
+    fn parse_malformed_yaml_reports_line_and_column() {
+        let content = "---\nname: my-skill\ndescription: broken: value\n---\n\nbody";
+        let err = parse_skill_md(content).unwrap_err().to_string();
+        assert!(err.contains("第 3 行"), "{}", err);
+        assert!(err.contains("列"), "{}", err);
+
+        let content = "---\nname: my-skill\ndescription: test\ntags: [a, b\n---\n\nbody";
+        assert!(parse_skill_md(content)
+            .unwrap_err()
+            .to_string()
+            .contains("frontmatter 解析失败"));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_skill_name() {
+        let content = "---\nname: My Skill\ndescription: test\n---\n\nbody";
+        assert!(parse_skill_md(content)
+            .unwrap_err()
+            .to_string()
+            .contains("skill name"));
+    }
+
     // --- validate_skill_name 测试 ---
 
     #[test]
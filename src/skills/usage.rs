@@ -0,0 +1,336 @@
+//! Skill 使用统计（`/skill stats`）
+//!
+//! 每次 Phase 1 路由命中、`/skill <name>` 手动注入或 LLM 调用 `skill` 工具加载某个 skill 时，
+//! 向 `<data_dir>/skill_usage.db` 追加一条事件；每次路由结束时，为当时目录中的全部 skill
+//! 累加路由次数 / NeedClarification 次数。统计用于找出长期未被使用、可以删除的 skill。
+//!
+//! Agent 通过 `SkillUsageRecorder` 句柄记录，默认是 `NoopSkillUsage`（routines / 测试不落盘）。
+//! 记录失败只写日志，不影响对话。
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{Context, Result};
+use rusqlite::{params, Connection};
+
+use super::SkillSource;
+use crate::memory::migrations::{migrate, Migration, Step};
+
+/// 超过该天数未使用的 skill 标记为可删除候选
+pub const STALE_AFTER_DAYS: i64 = 30;
+
+/// skill_usage.db schema 版本（只追加，不修改已发布的版本）
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "skill usage",
+    steps: &[Step::Sql(
+        "CREATE TABLE IF NOT EXISTS skill_usage (
+            id      INTEGER PRIMARY KEY AUTOINCREMENT,
+            skill   TEXT NOT NULL,
+            source  TEXT NOT NULL,
+            kind    TEXT NOT NULL,
+            used_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_skill_usage_skill ON skill_usage(skill);
+        CREATE TABLE IF NOT EXISTS skill_catalog (
+            skill          TEXT PRIMARY KEY,
+            first_seen     TEXT NOT NULL,
+            routes         INTEGER NOT NULL DEFAULT 0,
+            clarifications INTEGER NOT NULL DEFAULT 0
+        );",
+    )],
+}];
+
+/// skill 被加载的途径
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkillTrigger {
+    /// Phase 1 路由选中
+    Routed,
+    /// `/skill <name>` 手动注入
+    Manual,
+    /// LLM 调用 `skill` 工具加载
+    Tool,
+}
+
+impl SkillTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Routed => "routed",
+            Self::Manual => "manual",
+            Self::Tool => "tool",
+        }
+    }
+}
+
+/// 存库用的来源标识（与显示语言无关）
+fn source_key(source: &SkillSource) -> &'static str {
+    match source {
+        SkillSource::BuiltIn => "builtin",
+        SkillSource::Global => "global",
+        SkillSource::Project => "project",
+    }
+}
+
+/// Agent 持有的使用记录句柄
+pub trait SkillUsageRecorder: Send + Sync {
+    /// 记录一次 skill 加载
+    fn record(&self, skill: &str, source: &SkillSource, trigger: SkillTrigger);
+
+    /// 记录一次 Phase 1 路由结果；`catalog` 为路由时可选的全部 skill
+    fn record_route(&self, catalog: &[String], clarification: bool);
+}
+
+/// 不记录任何内容（默认实现，routines / 测试使用）
+pub struct NoopSkillUsage;
+
+impl SkillUsageRecorder for NoopSkillUsage {
+    fn record(&self, _skill: &str, _source: &SkillSource, _trigger: SkillTrigger) {}
+
+    fn record_route(&self, _catalog: &[String], _clarification: bool) {}
+}
+
+/// 单个 skill 的统计
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillStats {
+    pub skill: String,
+    pub routed: u32,
+    pub manual: u32,
+    pub tool: u32,
+    pub last_used: Option<DateTime<Utc>>,
+    /// 该 skill 在目录中时经历的路由次数
+    pub routes: u32,
+    /// 其中 NeedClarification 的次数
+    pub clarifications: u32,
+    /// 首次出现在路由目录（或首次被使用）的时间
+    pub first_seen: Option<DateTime<Utc>>,
+}
+
+impl SkillStats {
+    pub fn hits(&self) -> u32 {
+        self.routed + self.manual + self.tool
+    }
+
+    /// NeedClarification 占比（没有路由记录时为 None）
+    pub fn clarification_rate(&self) -> Option<f64> {
+        (self.routes > 0).then(|| self.clarifications as f64 / self.routes as f64)
+    }
+
+    /// 已被统计超过 30 天且这段时间内从未使用
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        let cutoff = now - Duration::days(STALE_AFTER_DAYS);
+        match self.last_used {
+            Some(last) => last < cutoff,
+            None => self.first_seen.is_some_and(|first| first < cutoff),
+        }
+    }
+}
+
+/// SQLite 实现（`<data_dir>/skill_usage.db`）
+pub struct SqliteSkillUsage {
+    db: Mutex<Connection>,
+}
+
+impl SqliteSkillUsage {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(data_dir).wrap_err("创建数据目录失败")?;
+        let conn = Connection::open(data_dir.join("skill_usage.db"))
+            .wrap_err("打开 skill 统计数据库失败")?;
+        crate::memory::sqlite::configure_connection(&conn)?;
+        Self::init(conn)
+    }
+
+    /// 内存数据库（测试用）
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().wrap_err("打开内存数据库失败")?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        migrate(&conn, "skill_usage.db", MIGRATIONS)?;
+        Ok(Self {
+            db: Mutex::new(conn),
+        })
+    }
+
+    fn try_record(
+        &self,
+        skill: &str,
+        source: &SkillSource,
+        trigger: SkillTrigger,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        let db = self.db.lock().unwrap_or_else(|e| e.into_inner());
+        db.execute(
+            "INSERT INTO skill_usage (skill, source, kind, used_at) VALUES (?1, ?2, ?3, ?4)",
+            params![skill, source_key(source), trigger.as_str(), at.to_rfc3339()],
+        )
+        .wrap_err("写入 skill 使用记录失败")?;
+        Ok(())
+    }
+
+    fn try_record_route(
+        &self,
+        catalog: &[String],
+        clarification: bool,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        let db = self.db.lock().unwrap_or_else(|e| e.into_inner());
+        let tx = db.unchecked_transaction().wrap_err("开启事务失败")?;
+        for skill in catalog {
+            tx.execute(
+                "INSERT INTO skill_catalog (skill, first_seen, routes, clarifications)
+                 VALUES (?1, ?2, 1, ?3)
+                 ON CONFLICT(skill) DO UPDATE SET
+                    routes = routes + 1,
+                    clarifications = clarifications + excluded.clarifications",
+                params![skill, at.to_rfc3339(), clarification as i64],
+            )
+            .wrap_err("写入路由统计失败")?;
+        }
+        tx.commit().wrap_err("提交路由统计失败")?;
+        Ok(())
+    }
+
+    /// 全部 skill 的统计，按命中次数降序
+    pub fn stats(&self) -> Result<Vec<SkillStats>> {
+        let db = self.db.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = db
+            .prepare(
+                "WITH hits AS (
+                    SELECT skill,
+                           SUM(kind = 'routed') AS routed,
+                           SUM(kind = 'manual') AS manual,
+                           SUM(kind = 'tool')   AS tool,
+                           MAX(used_at)            AS last_used,
+                           MIN(used_at)            AS first_used
+                    FROM skill_usage GROUP BY skill
+                 ),
+                 names AS (
+                    SELECT skill FROM hits UNION SELECT skill FROM skill_catalog
+                 )
+                 SELECT n.skill,
+                        COALESCE(h.routed, 0), COALESCE(h.manual, 0), COALESCE(h.tool, 0),
+                        h.last_used,
+                        COALESCE(c.routes, 0), COALESCE(c.clarifications, 0),
+                        COALESCE(c.first_seen, h.first_used)
+                 FROM names n
+                 LEFT JOIN hits h ON h.skill = n.skill
+                 LEFT JOIN skill_catalog c ON c.skill = n.skill",
+            )
+            .wrap_err("查询 skill 统计失败")?;
+        let mut stats = stmt
+            .query_map([], |row| {
+                Ok(SkillStats {
+                    skill: row.get(0)?,
+                    routed: row.get(1)?,
+                    manual: row.get(2)?,
+                    tool: row.get(3)?,
+                    last_used: parse_time(row.get(4)?),
+                    routes: row.get(5)?,
+                    clarifications: row.get(6)?,
+                    first_seen: parse_time(row.get(7)?),
+                })
+            })
+            .wrap_err("查询 skill 统计失败")?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .wrap_err("读取 skill 统计失败")?;
+        stats.sort_by(|a, b| b.hits().cmp(&a.hits()).then(a.skill.cmp(&b.skill)));
+        Ok(stats)
+    }
+}
+
+fn parse_time(value: Option<String>) -> Option<DateTime<Utc>> {
+    value
+        .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+        .map(|t| t.with_timezone(&Utc))
+}
+
+impl SkillUsageRecorder for SqliteSkillUsage {
+    fn record(&self, skill: &str, source: &SkillSource, trigger: SkillTrigger) {
+        if let Err(e) = self.try_record(skill, source, trigger, Utc::now()) {
+            tracing::warn!("记录 skill 使用失败: {:#}", e);
+        }
+    }
+
+    fn record_route(&self, catalog: &[String], clarification: bool) {
+        if let Err(e) = self.try_record_route(catalog, clarification, Utc::now()) {
+            tracing::warn!("记录路由统计失败: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn counts_hits_per_trigger() {
+        let store = SqliteSkillUsage::in_memory().unwrap();
+        store.record("code-review", &SkillSource::BuiltIn, SkillTrigger::Routed);
+        store.record("code-review", &SkillSource::BuiltIn, SkillTrigger::Routed);
+        store.record("code-review", &SkillSource::BuiltIn, SkillTrigger::Manual);
+        store.record("deploy", &SkillSource::Project, SkillTrigger::Tool);
+
+        let stats = store.stats().unwrap();
+        assert_eq!(stats[0].skill, "code-review");
+        assert_eq!((stats[0].routed, stats[0].manual, stats[0].tool), (2, 1, 0));
+        assert_eq!(stats[0].hits(), 3);
+        assert!(stats[0].last_used.is_some());
+        assert_eq!(stats[1].skill, "deploy");
+        assert_eq!(stats[1].tool, 1);
+    }
+
+    #[test]
+    fn clarification_rate_counts_routes_while_in_catalog() {
+        let store = SqliteSkillUsage::in_memory().unwrap();
+        store.record_route(&catalog(&["a", "b"]), false);
+        store.record_route(&catalog(&["a", "b"]), true);
+        store.record_route(&catalog(&["a"]), true);
+
+        let stats = store.stats().unwrap();
+        let a = stats.iter().find(|s| s.skill == "a").unwrap();
+        let b = stats.iter().find(|s| s.skill == "b").unwrap();
+        assert_eq!((a.routes, a.clarifications), (3, 2));
+        assert_eq!(b.clarification_rate(), Some(0.5));
+        // 只在目录中出现、从未被使用
+        assert_eq!(a.hits(), 0);
+        assert!(a.last_used.is_none());
+    }
+
+    #[test]
+    fn stale_after_thirty_days_without_use() {
+        let store = SqliteSkillUsage::in_memory().unwrap();
+        let old = Utc::now() - Duration::days(45);
+        store
+            .try_record_route(&catalog(&["unused", "used"]), false, old)
+            .unwrap();
+        store
+            .try_record("used", &SkillSource::Global, SkillTrigger::Manual, old)
+            .unwrap();
+        store.record("used", &SkillSource::Global, SkillTrigger::Manual);
+        store.record_route(&catalog(&["fresh"]), false);
+
+        let now = Utc::now();
+        let stats = store.stats().unwrap();
+        let find = |name: &str| stats.iter().find(|s| s.skill == name).unwrap();
+        assert!(find("unused").is_stale(now));
+        assert!(!find("used").is_stale(now));
+        // 刚出现在目录中的 skill 还没有满 30 天，不算候选
+        assert!(!find("fresh").is_stale(now));
+    }
+
+    #[test]
+    fn reopen_keeps_counts() {
+        let tmp = tempfile::tempdir().unwrap();
+        {
+            let store = SqliteSkillUsage::open(tmp.path()).unwrap();
+            store.record("deploy", &SkillSource::Global, SkillTrigger::Routed);
+        }
+        let store = SqliteSkillUsage::open(tmp.path()).unwrap();
+        assert_eq!(store.stats().unwrap()[0].routed, 1);
+    }
+}
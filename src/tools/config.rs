@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use async_trait::async_trait;
 use color_eyre::eyre::Result;
 use serde_json::json;
@@ -20,7 +22,7 @@ impl Tool for ConfigTool {
         "Read or modify RRClaw configuration. Supported actions: \
          get (read a config value), set (modify an existing value), list (show all config), \
          append (add a new config section, e.g. MCP server). \
-         Changes are written to ~/.rrclaw/config.toml; some settings require a restart."
+         Changes are written to the config file (see self_info for its path); some settings require a restart."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -49,7 +51,10 @@ impl Tool for ConfigTool {
         if let Some(action) = args.get("action").and_then(|v| v.as_str()) {
             if action == "set" {
                 if let Some(key) = args.get("key").and_then(|v| v.as_str()) {
-                    if key == "security.autonomy" {
+                    if matches!(
+                        key,
+                        "security.autonomy" | "security.require_sandbox" | "security.safe_mode"
+                    ) {
                         return Some(
                             "Changing the security level via AI is not allowed. Please edit the config file manually.".to_string(),
                         );
@@ -92,7 +97,7 @@ impl Tool for ConfigTool {
 fn config_list() -> Result<ToolResult> {
     let config_path = Config::config_path()?;
     let content = std::fs::read_to_string(&config_path)?;
-    let sanitized = sanitize_api_keys(&content);
+    let sanitized = sanitize_secrets(&content);
     Ok(ToolResult {
         success: true,
         output: sanitized,
@@ -127,8 +132,14 @@ fn config_get(key: Option<&str>) -> Result<ToolResult> {
     match value {
         Some(v) => {
             let display = v.to_string().trim().to_string();
-            // 脱敏 API Key
-            let display = if key.ends_with("api_key") {
+            // 脱敏 API Key 与 headers（整个 headers 表或其中一项）
+            let display = if parts.contains(&"headers") {
+                if v.is_table_like() {
+                    sanitize_lines(&display, true).trim_end().to_string()
+                } else {
+                    sanitize_single_key(&display)
+                }
+            } else if key.ends_with("api_key") {
                 sanitize_single_key(&display)
             } else {
                 display
@@ -174,14 +185,7 @@ fn config_set(key: Option<&str>, value: Option<&str>) -> Result<ToolResult> {
         }
     };
 
-    let config_path = Config::config_path()?;
-    let content = std::fs::read_to_string(&config_path)?;
-    let mut doc = content
-        .parse::<toml_edit::DocumentMut>()
-        .map_err(|e| color_eyre::eyre::eyre!("Failed to parse config file: {}", e))?;
-
-    let parts: Vec<&str> = key.split('.').collect();
-    if !set_toml_value(&mut doc, &parts, value) {
+    if !set_config_value(key, value, None)? {
         return Ok(ToolResult {
             success: false,
             output: String::new(),
@@ -193,8 +197,6 @@ fn config_set(key: Option<&str>, value: Option<&str>) -> Result<ToolResult> {
         });
     }
 
-    std::fs::write(&config_path, doc.to_string())?;
-
     Ok(ToolResult {
         success: true,
         output: format!(
@@ -206,6 +208,26 @@ fn config_set(key: Option<&str>, value: Option<&str>) -> Result<ToolResult> {
     })
 }
 
+/// 修改单个配置项并写回 config.toml（REPL 的 `/mode` 白名单编辑同样走这里）
+/// 如果提供了 path 则使用它，否则使用 Config::config_path()；路径无效时返回 false，文件不变
+pub(crate) fn set_config_value(key: &str, value: &str, path: Option<&Path>) -> Result<bool> {
+    let config_path = match path {
+        Some(p) => p.to_path_buf(),
+        None => Config::config_path()?,
+    };
+    let content = std::fs::read_to_string(&config_path)?;
+    let mut doc = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| color_eyre::eyre::eyre!("Failed to parse config file: {}", e))?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    if !set_toml_value(&mut doc, &parts, value) {
+        return Ok(false);
+    }
+    std::fs::write(&config_path, doc.to_string())?;
+    Ok(true)
+}
+
 /// 追加新配置段到 config.toml（用于添加 MCP server 等新节）
 fn config_append(value: Option<&str>) -> Result<ToolResult> {
     let toml_text = match value {
@@ -327,8 +349,9 @@ fn set_toml_value(doc: &mut toml_edit::DocumentMut, parts: &[&str], value: &str)
     let last_key = parts[parts.len() - 1];
 
     // 设置最终值
+    // 已有的键原地替换值，保留键上方的注释（insert 会连键的装饰一起换掉）
     if let Some(arr) = parsed_array {
-        current.insert(last_key, arr.into());
+        current[last_key] = arr.into();
         return true;
     }
 
@@ -364,7 +387,7 @@ fn set_toml_value(doc: &mut toml_edit::DocumentMut, parts: &[&str], value: &str)
             },
             _ => toml_edit::value(value),
         };
-        current.insert(last_key, new_val);
+        current[last_key] = new_val;
     } else {
         current.insert(last_key, toml_edit::value(value));
     }
@@ -372,20 +395,29 @@ fn set_toml_value(doc: &mut toml_edit::DocumentMut, parts: &[&str], value: &str)
     true
 }
 
-/// 对配置内容中的 API Key 进行脱敏
-fn sanitize_api_keys(content: &str) -> String {
+/// 对配置内容中的 API Key 与 headers 值进行脱敏
+///
+/// `[providers.<name>.headers]` 等 headers 表的值通常是 `Authorization` / `X-Api-Key`，一律按密钥处理。
+fn sanitize_secrets(content: &str) -> String {
+    sanitize_lines(content, false)
+}
+
+/// 逐行脱敏；`in_headers` 为 true 时内容从 headers 表内部开始（`config get` 取整个表）
+fn sanitize_lines(content: &str, mut in_headers: bool) -> String {
     let mut result = String::new();
     for line in content.lines() {
-        if line.trim_start().starts_with("api_key") {
-            // 找到等号后的值
-            if let Some(eq_pos) = line.find('=') {
-                let prefix = &line[..=eq_pos];
-                let raw_value = line[eq_pos + 1..].trim().trim_matches('"');
-                result.push_str(prefix);
-                result.push(' ');
-                result.push('"');
-                result.push_str(&sanitize_single_key(raw_value));
-                result.push('"');
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            let section = trimmed.trim_start_matches('[');
+            let section = section.split(']').next().unwrap_or_default().trim();
+            in_headers = section.rsplit('.').next() == Some("headers");
+            result.push_str(line);
+        } else if let Some(eq_pos) = line.find('=') {
+            let key = line[..eq_pos].trim();
+            if key == "headers" {
+                result.push_str(&mask_inline_table(line, eq_pos));
+            } else if in_headers || key == "api_key" {
+                result.push_str(&mask_line_value(line, eq_pos));
             } else {
                 result.push_str(line);
             }
@@ -397,14 +429,34 @@ fn sanitize_api_keys(content: &str) -> String {
     result
 }
 
+/// `key = "value"` → `key = "valu***"`
+fn mask_line_value(line: &str, eq_pos: usize) -> String {
+    let raw_value = line[eq_pos + 1..].trim().trim_matches('"');
+    format!(
+        "{} \"{}\"",
+        &line[..=eq_pos],
+        sanitize_single_key(raw_value)
+    )
+}
+
+/// `headers = { Authorization = "..." }` → 每个值分别脱敏；无法解析时整体遮盖
+fn mask_inline_table(line: &str, eq_pos: usize) -> String {
+    let masked = match line[eq_pos + 1..].trim().parse::<toml_edit::Value>() {
+        Ok(toml_edit::Value::InlineTable(mut table)) => {
+            for (_, value) in table.iter_mut() {
+                let raw = value.as_str().map(str::to_string).unwrap_or_default();
+                *value = sanitize_single_key(&raw).into();
+            }
+            table.to_string()
+        }
+        _ => "\"***\"".to_string(),
+    };
+    format!("{} {}", &line[..=eq_pos], masked.trim())
+}
+
 /// 对单个 API Key 值进行脱敏：显示前4字符 + ***
 fn sanitize_single_key(key: &str) -> String {
-    let key = key.trim_matches('"');
-    if key.len() <= 4 {
-        "***".to_string()
-    } else {
-        format!("{}***", &key[..4])
-    }
+    crate::security::redact::mask_secret(key.trim_matches('"'))
 }
 
 #[cfg(test)]
@@ -428,12 +480,44 @@ base_url = "https://api.deepseek.com/v1"
 api_key = "sk-secret-key-12345"
 model = "deepseek-chat"
 "#;
-        let result = sanitize_api_keys(content);
+        let result = sanitize_secrets(content);
         assert!(result.contains("sk-s***"));
         assert!(!result.contains("sk-secret-key-12345"));
         assert!(result.contains("deepseek-chat")); // model 不受影响
     }
 
+    #[test]
+    fn sanitize_secrets_masks_header_values() {
+        let content = r#"[providers.gateway]
+base_url = "https://gateway.example.com/v1"
+headers = { Authorization = "Bearer inline-secret-token" }
+
+[providers.gateway.headers]
+Authorization = "Bearer table-secret-token"
+X-Api-Key = "xk-header-secret"
+
+[agent]
+model = "gpt-4o"
+"#;
+        let result = sanitize_secrets(content);
+        for secret in [
+            "inline-secret-token",
+            "table-secret-token",
+            "xk-header-secret",
+        ] {
+            assert!(!result.contains(secret), "{} leaked:\n{}", secret, result);
+        }
+        assert!(
+            result.contains(r#"Authorization = "Bear***""#),
+            "{}",
+            result
+        );
+        assert!(result.contains("X-Api-Key = \"xk-h***\""));
+        // headers 表之后的普通配置不受影响
+        assert!(result.contains(r#"model = "gpt-4o""#));
+        assert!(result.contains("https://gateway.example.com/v1"));
+    }
+
     #[test]
     fn pre_validate_blocks_autonomy_change() {
         let tool = ConfigTool;
@@ -444,6 +528,13 @@ model = "deepseek-chat"
         });
         let policy = SecurityPolicy::default();
         assert!(tool.pre_validate(&args, &policy).is_some());
+
+        let args = serde_json::json!({
+            "action": "set",
+            "key": "security.require_sandbox",
+            "value": "false"
+        });
+        assert!(tool.pre_validate(&args, &policy).is_some());
     }
 
     #[test]
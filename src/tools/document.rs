@@ -0,0 +1,304 @@
+//! 文档读取工具：把 PDF / DOCX 抽取为纯文本（`documents` feature）
+//!
+//! `file_read` 对二进制格式只能返回乱码；这里按格式解析：PDF 逐页抽取并加 `--- Page N ---` 标记，
+//! DOCX 解压 `word/document.xml` 按段落输出，标题样式段落加 `--- Section: ... ---` 标记。
+//! 与 `file_read` 一样限制在 workspace 内，并限制文件大小与输出长度。
+
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, eyre, Context, Result};
+use regex::Regex;
+use std::io::Read;
+use std::path::Path;
+
+use crate::security::SecurityPolicy;
+
+use super::file::resolve_path;
+use super::traits::{Tool, ToolResult, ToolRisk};
+
+/// 文档文件大小上限（字节）
+const MAX_DOCUMENT_BYTES: u64 = 20 * 1024 * 1024;
+/// 返回给模型的文本上限（字符），超出截断并提示
+const MAX_OUTPUT_CHARS: usize = 100_000;
+
+/// 支持的文档格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DocumentKind {
+    Pdf,
+    Docx,
+}
+
+impl DocumentKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "pdf" => Some(Self::Pdf),
+            "docx" => Some(Self::Docx),
+            _ => None,
+        }
+    }
+}
+
+/// 文档读取工具
+pub struct DocumentReadTool;
+
+#[async_trait]
+impl Tool for DocumentReadTool {
+    fn name(&self) -> &str {
+        "document_read"
+    }
+
+    fn description(&self) -> &str {
+        "Extract plain text from a PDF or DOCX file in the workspace. \
+         PDF output is split with '--- Page N ---' markers, DOCX headings become \
+         '--- Section: ... ---' markers. Use file_read for plain-text files."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path to the .pdf or .docx file"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn risk(&self) -> ToolRisk {
+        ToolRisk::Read
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        let path_str = args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| eyre!("Missing 'path' parameter"))?;
+        let path = resolve_path(path_str, policy);
+
+        if !policy.is_path_allowed(&path) {
+            return Ok(failure(format!(
+                "Path not within allowed workspace: {}",
+                path.display()
+            )));
+        }
+        let Some(kind) = DocumentKind::from_path(&path) else {
+            return Ok(failure(format!(
+                "Unsupported document type: {} (expected .pdf or .docx; use file_read for text files)",
+                path.display()
+            )));
+        };
+
+        let result = tokio::task::spawn_blocking(move || extract(&path, kind))
+            .await
+            .wrap_err("document_read task panicked")?;
+        match result {
+            Ok(text) => Ok(ToolResult {
+                success: true,
+                output: truncate_output(text),
+                error: None,
+                ..Default::default()
+            }),
+            Err(e) => Ok(failure(format!("Failed to read document: {:#}", e))),
+        }
+    }
+}
+
+fn failure(error: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        output: String::new(),
+        error: Some(error),
+        ..Default::default()
+    }
+}
+
+/// 读取文件（先检查大小上限）并按格式抽取文本
+fn extract(path: &Path, kind: DocumentKind) -> Result<String> {
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_DOCUMENT_BYTES {
+        bail!(
+            "file is {} bytes, larger than the {} MB limit",
+            size,
+            MAX_DOCUMENT_BYTES / 1024 / 1024
+        );
+    }
+    let bytes = std::fs::read(path)?;
+    match kind {
+        DocumentKind::Pdf => extract_pdf(&bytes),
+        DocumentKind::Docx => extract_docx(&bytes),
+    }
+}
+
+/// PDF：逐页抽取，每页前加页码标记
+fn extract_pdf(bytes: &[u8]) -> Result<String> {
+    let pages = pdf_extract::extract_text_from_mem_by_pages(bytes)
+        .map_err(|e| eyre!("invalid PDF: {}", e))?;
+    let mut out = String::new();
+    for (i, page) in pages.iter().enumerate() {
+        out.push_str(&format!("--- Page {} ---\n", i + 1));
+        out.push_str(page.trim());
+        out.push_str("\n\n");
+    }
+    Ok(out.trim_end().to_string())
+}
+
+/// DOCX：解压 `word/document.xml`，每个段落一行，标题样式段落作为章节标记
+fn extract_docx(bytes: &[u8]) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))
+        .map_err(|e| eyre!("invalid DOCX (not a zip archive): {}", e))?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|e| eyre!("invalid DOCX (missing word/document.xml): {}", e))?
+        .read_to_string(&mut xml)?;
+    Ok(docx_xml_to_text(&xml))
+}
+
+fn docx_xml_to_text(xml: &str) -> String {
+    // 段落（`\b` 保证不会匹配 `<w:pPr>`）、段落样式、段落内按顺序出现的文本 / 制表符 / 换行
+    let paragraph_re = Regex::new(r"(?s)<w:p\b[^>]*>(.*?)</w:p>").expect("valid regex");
+    let style_re = Regex::new(r#"<w:pStyle w:val="([^"]+)""#).expect("valid regex");
+    let run_re = Regex::new(r"<w:t(?:\s[^>]*)?>([^<]*)</w:t>|<w:tab/>|<w:br(?:\s[^>]*)?/>")
+        .expect("valid regex");
+
+    let mut lines = Vec::new();
+    for paragraph in paragraph_re.captures_iter(xml) {
+        let body = &paragraph[1];
+        let mut text = String::new();
+        for run in run_re.captures_iter(body) {
+            match run.get(1) {
+                Some(t) => text.push_str(&unescape_xml(t.as_str())),
+                None if run[0].starts_with("<w:tab") => text.push('\t'),
+                None => text.push('\n'),
+            }
+        }
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let heading = style_re
+            .captures(body)
+            .is_some_and(|s| s[1].starts_with("Heading") || &s[1] == "Title");
+        if heading {
+            lines.push(String::new());
+            lines.push(format!("--- Section: {} ---", text));
+        } else {
+            lines.push(text.to_string());
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// 按字符截断过长的文本，并说明原长度
+fn truncate_output(text: String) -> String {
+    let total = text.chars().count();
+    if total <= MAX_OUTPUT_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(MAX_OUTPUT_CHARS).collect();
+    format!(
+        "{}\n… (truncated: showing {} of {} characters)",
+        cut, MAX_OUTPUT_CHARS, total
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::AutonomyLevel;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/documents")
+            .join(name)
+    }
+
+    fn policy_for(workspace: &Path) -> SecurityPolicy {
+        SecurityPolicy {
+            autonomy: AutonomyLevel::Full,
+            allowed_commands: vec![],
+            workspace_dir: workspace.canonicalize().unwrap(),
+            blocked_paths: vec![],
+            http_allowed_hosts: vec![],
+            injection_check: true,
+        }
+    }
+
+    async fn read(path: &str, policy: &SecurityPolicy) -> ToolResult {
+        DocumentReadTool
+            .execute(serde_json::json!({ "path": path }), policy)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn extracts_pdf_text_with_page_markers() {
+        let policy = policy_for(&fixture(""));
+        let result = read("sample.pdf", &policy).await;
+        assert!(result.success, "{:?}", result.error);
+        let page_two = result.output.find("--- Page 2 ---").unwrap();
+        let first = result.output.find("RRClaw fixture page one").unwrap();
+        let second = result.output.find("Second page mentions tantivy").unwrap();
+        assert!(result.output.starts_with("--- Page 1 ---"));
+        assert!(first < page_two && page_two < second);
+    }
+
+    #[tokio::test]
+    async fn extracts_docx_paragraphs_and_sections() {
+        let policy = policy_for(&fixture(""));
+        let result = read("sample.docx", &policy).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(
+            result.output,
+            "--- Section: Release Notes ---\n\
+             Retries use exponential backoff & jitter.\n\n\
+             --- Section: 配置 ---\n\
+             fallback_providers\t按顺序尝试"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_paths_outside_workspace_and_other_types() {
+        let tmp = tempfile::tempdir().unwrap();
+        let policy = policy_for(tmp.path());
+        let result = read(fixture("sample.pdf").to_str().unwrap(), &policy).await;
+        assert!(!result.success);
+        assert!(result
+            .error
+            .unwrap()
+            .contains("not within allowed workspace"));
+
+        std::fs::write(tmp.path().join("notes.txt"), "text").unwrap();
+        let result = read("notes.txt", &policy).await;
+        assert!(result.error.unwrap().contains("Unsupported document type"));
+
+        std::fs::write(tmp.path().join("broken.docx"), "not a zip").unwrap();
+        let result = read("broken.docx", &policy).await;
+        assert!(result.error.unwrap().contains("invalid DOCX"));
+    }
+
+    #[test]
+    fn long_output_is_truncated() {
+        let text = "字".repeat(MAX_OUTPUT_CHARS + 5);
+        let out = truncate_output(text);
+        assert!(out.ends_with(&format!(
+            "(truncated: showing {} of {} characters)",
+            MAX_OUTPUT_CHARS,
+            MAX_OUTPUT_CHARS + 5
+        )));
+    }
+}
@@ -1,12 +1,30 @@
 use async_trait::async_trait;
 use color_eyre::eyre::{Context, Result};
-use std::path::Path;
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
 
 use crate::security::SecurityPolicy;
 
-use super::traits::{Tool, ToolResult};
+use super::traits::{Tool, ToolOutputKind, ToolResult, ToolRisk};
+use super::undo::UndoStore;
+
+#[cfg(feature = "documents")]
+pub use super::document::DocumentReadTool;
+
+/// 覆盖已有文件时 diff 的最大字节数（确认提示与工具结果共用，超出截断）
+const MAX_DIFF_BYTES: usize = 8 * 1024;
+/// 按行读取时单次最多返回的行数（`limit_lines` 上限，也是只给 `offset_lines` 时的默认值）
+const MAX_RANGE_LINES: usize = 2000;
+/// `tail` 未指定 `limit_lines` 时返回的行数
+const DEFAULT_TAIL_LINES: usize = 100;
+/// `tail` 从文件末尾向前读取的块大小
+const TAIL_CHUNK_BYTES: u64 = 64 * 1024;
 
 /// 文件读取工具
+///
+/// 只给 `path` 时返回完整内容；给出 `offset_lines` / `limit_lines` / `tail` 时按行读取，
+/// 输出带行号与范围说明。`tail` 从文件末尾按块向前 seek，不读取整个文件。
 pub struct FileReadTool;
 
 #[async_trait]
@@ -16,7 +34,10 @@ impl Tool for FileReadTool {
     }
 
     fn description(&self) -> &str {
-        "Read file contents. Path must be within the workspace directory."
+        "Read file contents. Path must be within the workspace directory. \
+         For large files or logs, read a line range with offset_lines/limit_lines, \
+         or the end of the file with tail=true (e.g. the last lines of a build log); \
+         ranged reads return line-numbered content with the total line count."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -26,12 +47,28 @@ impl Tool for FileReadTool {
                 "path": {
                     "type": "string",
                     "description": "Path to the file to read"
+                },
+                "offset_lines": {
+                    "type": "integer",
+                    "description": "Number of lines to skip from the start (0-based)"
+                },
+                "limit_lines": {
+                    "type": "integer",
+                    "description": format!("Maximum number of lines to return (max {})", MAX_RANGE_LINES)
+                },
+                "tail": {
+                    "type": "boolean",
+                    "description": format!("Return the last limit_lines lines (default {}) without reading the whole file", DEFAULT_TAIL_LINES)
                 }
             },
             "required": ["path"]
         })
     }
 
+    fn risk(&self) -> ToolRisk {
+        ToolRisk::Read
+    }
+
     async fn execute(
         &self,
         args: serde_json::Value,
@@ -57,7 +94,34 @@ impl Tool for FileReadTool {
             });
         }
 
-        match tokio::fs::read_to_string(&path).await {
+        let offset = args.get("offset_lines").and_then(|v| v.as_u64());
+        let limit = args.get("limit_lines").and_then(|v| v.as_u64());
+        let tail = args.get("tail").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let result = if tail || offset.is_some() || limit.is_some() {
+            let limit = limit.map_or(
+                if tail {
+                    DEFAULT_TAIL_LINES
+                } else {
+                    MAX_RANGE_LINES
+                },
+                |n| (n as usize).clamp(1, MAX_RANGE_LINES),
+            );
+            let offset = offset.unwrap_or(0) as usize;
+            tokio::task::spawn_blocking(move || {
+                if tail {
+                    read_tail(&path, limit)
+                } else {
+                    read_range(&path, offset, limit)
+                }
+            })
+            .await
+            .wrap_err("file_read task panicked")?
+        } else {
+            tokio::fs::read_to_string(&path).await
+        };
+
+        match result {
             Ok(content) => Ok(ToolResult {
                 success: true,
                 output: content,
@@ -74,8 +138,409 @@ impl Tool for FileReadTool {
     }
 }
 
+/// 带行号的一行（行号从 1 开始）
+fn numbered_line(out: &mut String, number: usize, line: &[u8]) {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    out.push_str(&format!(
+        "{:>6}\t{}\n",
+        number,
+        String::from_utf8_lossy(line)
+    ));
+}
+
+/// 读取第 `offset + 1` 行起的最多 `limit` 行；流式扫描到文件末尾以统计总行数
+fn read_range(path: &Path, offset: usize, limit: usize) -> std::io::Result<String> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut body = String::new();
+    let mut line = Vec::new();
+    let mut total = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if total >= offset && total < offset.saturating_add(limit) {
+            numbered_line(&mut body, total + 1, &line);
+        }
+        total += 1;
+    }
+    let header = if offset >= total {
+        format!(
+            "[no lines returned: offset {} is past the end; file has {} lines]",
+            offset, total
+        )
+    } else {
+        format!(
+            "[lines {}-{} of {}]",
+            offset + 1,
+            offset.saturating_add(limit).min(total),
+            total
+        )
+    };
+    Ok(format!("{}\n{}", header, body))
+}
+
+/// 读取最后 `limit` 行：从文件末尾按块向前 seek，凑够行数即停止
+fn read_tail(path: &Path, limit: usize) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let (lines, total) = tail_lines(&mut file, len, limit)?;
+
+    let mut body = String::new();
+    let header = match total {
+        // 读到了文件开头：行号与总行数是精确的
+        Some(0) => "[file is empty]".to_string(),
+        Some(total) => {
+            let first = total - lines.len() + 1;
+            for (i, line) in lines.iter().enumerate() {
+                numbered_line(&mut body, first + i, line);
+            }
+            format!("[lines {}-{} of {}]", first, total, total)
+        }
+        None => {
+            for (i, line) in lines.iter().enumerate() {
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                body.push_str(&format!(
+                    "{:>6}\t{}\n",
+                    format!("-{}", lines.len() - i),
+                    String::from_utf8_lossy(line)
+                ));
+            }
+            format!(
+                "[last {} lines of a {} file; total line count not computed, numbers count back from the end]",
+                lines.len(),
+                super::self_info::format_bytes(len)
+            )
+        }
+    };
+    Ok(format!("{}\n{}", header, body))
+}
+
+/// 从 `len` 处向前按块读取，返回最后 `limit` 行（不含换行符）；读到文件开头时同时返回总行数
+///
+/// 末尾的换行符不算作空行。只读取包含这些行的块，读取量与返回内容大小成正比。
+fn tail_lines<R: Read + Seek>(
+    reader: &mut R,
+    len: u64,
+    limit: usize,
+) -> std::io::Result<(Vec<Vec<u8>>, Option<usize>)> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut pos = len;
+    loop {
+        let content = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        // 至少 limit 个换行 → 已包含完整的最后 limit 行
+        if pos == 0 || content.iter().filter(|b| **b == b'\n').count() >= limit {
+            break;
+        }
+        let start = pos.saturating_sub(TAIL_CHUNK_BYTES);
+        let mut chunk = vec![0u8; (pos - start) as usize];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+        pos = start;
+    }
+    let content = buf.strip_suffix(b"\n").unwrap_or(&buf);
+    let mut lines: Vec<Vec<u8>> = if buf.is_empty() {
+        Vec::new()
+    } else {
+        content.split(|b| *b == b'\n').map(<[u8]>::to_vec).collect()
+    };
+    let total = (pos == 0).then_some(lines.len());
+    if lines.len() > limit {
+        lines.drain(..lines.len() - limit);
+    }
+    Ok((lines, total))
+}
+
+/// grep 最多返回的匹配行数
+const MAX_GREP_MATCHES: usize = 200;
+/// grep 跳过超过该大小的文件
+const MAX_GREP_FILE_BYTES: u64 = 1024 * 1024;
+/// grep 最多扫描的文件数
+const MAX_GREP_FILES: usize = 5000;
+/// 单行匹配结果的最大字符数（压缩后的 JS 等超长行截断）
+const MAX_GREP_LINE_CHARS: usize = 300;
+/// grep 不进入的目录（隐藏目录另外整体跳过）
+const GREP_SKIP_DIRS: &[&str] = &["target", "node_modules"];
+
+/// 代码搜索工具：在 workspace 内按正则搜索文件内容，不依赖 shell 的 rg / grep
+///
+/// 不跟随 symlink，逐项检查 `is_path_allowed`（blocked_paths 下的文件不会被读取），
+/// 跳过隐藏目录、二进制文件与超过 `MAX_GREP_FILE_BYTES` 的文件。
+pub struct GrepTool;
+
+#[async_trait]
+impl Tool for GrepTool {
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn description(&self) -> &str {
+        "Search file contents under the workspace with a regex. Returns matching lines as path:line: text. \
+         Prefer this over shell grep/rg."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression (Rust regex syntax)"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Directory or file to search, relative to the workspace (default: workspace root)"
+                },
+                "glob": {
+                    "type": "string",
+                    "description": "Only search files matching this glob, e.g. \"*.rs\" or \"src/**/*.toml\""
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Case-insensitive match (default false)"
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    fn risk(&self) -> ToolRisk {
+        ToolRisk::Read
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        let pattern = args
+            .get("pattern")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| color_eyre::eyre::eyre!("Missing 'pattern' parameter"))?;
+        let case_insensitive = args
+            .get("case_insensitive")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let regex = match regex::RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+        {
+            Ok(re) => re,
+            Err(e) => {
+                return Ok(ToolResult {
+                    success: false,
+                    output: String::new(),
+                    error: Some(format!("Invalid regex: {}", e)),
+                    ..Default::default()
+                })
+            }
+        };
+        let glob = args
+            .get("glob")
+            .and_then(|v| v.as_str())
+            .filter(|g| !g.is_empty())
+            .map(str::to_string);
+        let root = args
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(|p| resolve_path(p, policy))
+            .unwrap_or_else(|| policy.workspace_dir.clone());
+
+        // 安全检查: 搜索根路径限制
+        if !policy.is_path_allowed(&root) {
+            return Ok(ToolResult {
+                success: false,
+                output: String::new(),
+                error: Some(format!(
+                    "Path not within allowed workspace: {}",
+                    root.display()
+                )),
+                ..Default::default()
+            });
+        }
+
+        let policy = policy.clone();
+        let search = tokio::task::spawn_blocking(move || {
+            grep_files(&root, &regex, glob.as_deref(), &policy)
+        })
+        .await
+        .wrap_err("grep task panicked")?;
+
+        if search.matches.is_empty() {
+            return Ok(ToolResult {
+                success: true,
+                output: format!("No matches ({} files searched)", search.files_searched),
+                ..Default::default()
+            });
+        }
+        let mut output = search.matches.join("\n");
+        if search.truncated {
+            output.push_str(&format!(
+                "\n… (results truncated; narrow the pattern, path or glob. {} files searched)",
+                search.files_searched
+            ));
+        }
+        Ok(ToolResult {
+            success: true,
+            output,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct GrepSearch {
+    matches: Vec<String>,
+    files_searched: usize,
+    truncated: bool,
+}
+
+/// 深度优先遍历 `root`，收集匹配行（`相对路径:行号: 内容`）
+fn grep_files(
+    root: &Path,
+    regex: &regex::Regex,
+    glob: Option<&str>,
+    policy: &SecurityPolicy,
+) -> GrepSearch {
+    let base = policy
+        .workspace_dir
+        .canonicalize()
+        .unwrap_or_else(|_| policy.workspace_dir.clone());
+    let mut search = GrepSearch::default();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(path) = stack.pop() {
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.file_type().is_symlink() || !policy.is_path_allowed(&path) {
+            continue;
+        }
+        if meta.is_dir() {
+            let Ok(read_dir) = std::fs::read_dir(&path) else {
+                continue;
+            };
+            let mut children: Vec<PathBuf> = read_dir
+                .filter_map(|e| e.ok())
+                .filter(|e| {
+                    let name = e.file_name();
+                    let name = name.to_string_lossy();
+                    !(e.path().is_dir()
+                        && (name.starts_with('.') || GREP_SKIP_DIRS.contains(&name.as_ref())))
+                })
+                .map(|e| e.path())
+                .collect();
+            // 逆序入栈，使输出按路径字典序
+            children.sort_unstable_by(|a, b| b.cmp(a));
+            stack.extend(children);
+            continue;
+        }
+        if !meta.is_file() || meta.len() > MAX_GREP_FILE_BYTES {
+            continue;
+        }
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let relative = canonical
+            .strip_prefix(&base)
+            .unwrap_or(&canonical)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if let Some(glob) = glob {
+            let target = if glob.contains('/') {
+                relative.as_str()
+            } else {
+                relative.rsplit('/').next().unwrap_or(&relative)
+            };
+            if !glob_match(glob, target) {
+                continue;
+            }
+        }
+        if search.files_searched >= MAX_GREP_FILES {
+            search.truncated = true;
+            break;
+        }
+        search.files_searched += 1;
+        let Ok(bytes) = std::fs::read(&path) else {
+            continue;
+        };
+        // 含 NUL 视为二进制文件
+        if bytes.iter().take(8192).any(|b| *b == 0) {
+            continue;
+        }
+        let content = String::from_utf8_lossy(&bytes);
+        for (index, line) in content.lines().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            if search.matches.len() >= MAX_GREP_MATCHES {
+                search.truncated = true;
+                return search;
+            }
+            let line = match line.char_indices().nth(MAX_GREP_LINE_CHARS) {
+                Some((cut, _)) => format!("{}…", &line[..cut]),
+                None => line.to_string(),
+            };
+            search
+                .matches
+                .push(format!("{}:{}: {}", relative, index + 1, line));
+        }
+    }
+    search
+}
+
+/// 简单 glob 匹配：`*` 不跨 `/`，`**` 跨目录，`?` 匹配单个非 `/` 字符
+fn glob_match(pattern: &str, text: &str) -> bool {
+    fn matches(p: &[char], t: &[char]) -> bool {
+        match p.split_first() {
+            None => t.is_empty(),
+            Some(('*', rest)) if rest.first() == Some(&'*') => {
+                // `**/` 也匹配零层目录
+                let rest = &rest[1..];
+                let rest_no_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+                (0..=t.len()).any(|i| matches(rest, &t[i..]) || matches(rest_no_slash, &t[i..]))
+            }
+            Some(('*', rest)) => {
+                for i in 0..=t.len() {
+                    if matches(rest, &t[i..]) {
+                        return true;
+                    }
+                    if t.get(i) == Some(&'/') {
+                        break;
+                    }
+                }
+                false
+            }
+            Some(('?', rest)) => t.first().is_some_and(|c| *c != '/') && matches(rest, &t[1..]),
+            Some((c, rest)) => t.first() == Some(c) && matches(rest, &t[1..]),
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    matches(&p, &t)
+}
+
 /// 文件写入工具
-pub struct FileWriteTool;
+///
+/// 覆盖已有文件时：结果附带与原内容的 unified diff；内容相同则不写入；
+/// 配置了 undo 目录时先保存原内容快照（`/undo-file` 恢复）。
+/// `append: true` 时以 O_APPEND 单次写入追加到末尾，不截断原文件。
+#[derive(Debug, Clone, Default)]
+pub struct FileWriteTool {
+    undo: Option<UndoStore>,
+}
+
+impl FileWriteTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 覆盖前把原内容保存到 `dir`（`~/.rrclaw/data/undo/`）
+    pub fn with_undo_dir(mut self, dir: PathBuf) -> Self {
+        self.undo = Some(UndoStore::new(dir));
+        self
+    }
+}
 
 #[async_trait]
 impl Tool for FileWriteTool {
@@ -84,7 +549,8 @@ impl Tool for FileWriteTool {
     }
 
     fn description(&self) -> &str {
-        "Write content to a file. Path must be within the workspace directory."
+        "Write content to a file. Path must be within the workspace directory. \
+         Set append=true to add to the end of an existing file (e.g. a notes or log file) instead of rewriting it."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -98,6 +564,14 @@ impl Tool for FileWriteTool {
                 "content": {
                     "type": "string",
                     "description": "Content to write to the file"
+                },
+                "create_only": {
+                    "type": "boolean",
+                    "description": "Fail instead of overwriting if the file already exists. Set this when you intend to create a new file."
+                },
+                "append": {
+                    "type": "boolean",
+                    "description": "Append content to the end of the file instead of replacing it (creates the file if missing). Include a trailing newline yourself if needed."
                 }
             },
             "required": ["path", "content"]
@@ -111,6 +585,29 @@ impl Tool for FileWriteTool {
         None
     }
 
+    fn confirmation_preview(
+        &self,
+        args: &serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Option<String> {
+        let path_str = args.get("path")?.as_str()?;
+        let content = args.get("content")?.as_str()?;
+        if is_append(args) {
+            // 追加不读取原文件（可能是很大的日志），只展示新增行
+            let added: String = content.lines().map(|l| format!("+{}\n", l)).collect();
+            return Some(format!(
+                "追加到 {}:\n{}",
+                path_str,
+                truncate_preview(&added)
+            ));
+        }
+        let old = std::fs::read_to_string(resolve_path(path_str, policy)).ok()?;
+        if old == content {
+            return Some("内容未变化".to_string());
+        }
+        Some(unified_diff(&old, content, path_str))
+    }
+
     async fn execute(
         &self,
         args: serde_json::Value,
@@ -125,6 +622,11 @@ impl Tool for FileWriteTool {
             .get("content")
             .and_then(|v| v.as_str())
             .ok_or_else(|| color_eyre::eyre::eyre!("Missing 'content' parameter"))?;
+        let create_only = args
+            .get("create_only")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let append = is_append(&args);
 
         // 安全检查: ReadOnly 模式拒绝（防御性二次检查）
         if !policy.allows_execution() {
@@ -151,6 +653,54 @@ impl Tool for FileWriteTool {
             });
         }
 
+        if append {
+            if create_only && path.exists() {
+                return Ok(ToolResult {
+                    success: false,
+                    output: String::new(),
+                    error: Some(format!(
+                        "File already exists (create_only): {}",
+                        path.display()
+                    )),
+                    ..Default::default()
+                });
+            }
+            return Ok(append_to_file(&path, path_str, content).await);
+        }
+
+        // 已有文件：create_only 冲突 / 内容未变化 / 计算 diff 并保存快照
+        let existing = tokio::fs::read(&path).await.ok();
+        let mut diff = None;
+        if let Some(old) = &existing {
+            if create_only {
+                return Ok(ToolResult {
+                    success: false,
+                    output: String::new(),
+                    error: Some(format!(
+                        "File already exists (create_only): {}",
+                        path.display()
+                    )),
+                    ..Default::default()
+                });
+            }
+            if old.as_slice() == content.as_bytes() {
+                return Ok(ToolResult {
+                    success: true,
+                    output: format!("内容未变化，未写入: {}", path.display()),
+                    user_facing: Some(format!("unchanged {}", path_str)),
+                    ..Default::default()
+                });
+            }
+            diff = std::str::from_utf8(old)
+                .ok()
+                .map(|old| unified_diff(old, content, path_str));
+            if let Some(undo) = &self.undo {
+                if let Err(e) = undo.save(&path, old) {
+                    warn!("保存 undo 快照失败: {:#}", e);
+                }
+            }
+        }
+
         // 确保父目录存在
         if let Some(parent) = path.parent() {
             if !parent.exists() {
@@ -163,8 +713,25 @@ impl Tool for FileWriteTool {
         match tokio::fs::write(&path, content).await {
             Ok(()) => Ok(ToolResult {
                 success: true,
-                output: format!("Wrote {} bytes to {}", content.len(), path.display()),
+                output: match diff {
+                    Some(diff) => format!(
+                        "Wrote {} bytes to {}\n\n{}",
+                        content.len(),
+                        path.display(),
+                        diff
+                    ),
+                    None => format!("Wrote {} bytes to {}", content.len(), path.display()),
+                },
+                user_facing: Some(format!(
+                    "wrote {} to {}",
+                    super::self_info::format_bytes(content.len() as u64),
+                    path_str
+                )),
                 error: None,
+                kind: Some(ToolOutputKind::FileRef {
+                    path: path.display().to_string(),
+                    bytes: content.len() as u64,
+                }),
                 ..Default::default()
             }),
             Err(e) => Ok(ToolResult {
@@ -177,8 +744,96 @@ impl Tool for FileWriteTool {
     }
 }
 
+fn is_append(args: &serde_json::Value) -> bool {
+    args.get("append")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// 追加写入：O_APPEND 打开后单次 `write_all`，并发追加不会相互覆盖；不读取原内容、不保存 undo 快照
+async fn append_to_file(path: &Path, path_str: &str, content: &str) -> ToolResult {
+    if content.is_empty() {
+        return ToolResult {
+            success: true,
+            output: format!("内容未变化，未写入: {}", path.display()),
+            user_facing: Some(format!("unchanged {}", path_str)),
+            ..Default::default()
+        };
+    }
+    let path_buf = path.to_path_buf();
+    let data = content.as_bytes().to_vec();
+    let written = tokio::task::spawn_blocking(move || -> std::io::Result<u64> {
+        if let Some(parent) = path_buf.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path_buf)?;
+        file.write_all(&data)?;
+        Ok(file.metadata()?.len())
+    })
+    .await;
+    match written {
+        Ok(Ok(size)) => ToolResult {
+            success: true,
+            output: format!(
+                "Appended {} bytes to {} (now {} bytes)",
+                content.len(),
+                path.display(),
+                size
+            ),
+            user_facing: Some(format!(
+                "appended {} to {}",
+                super::self_info::format_bytes(content.len() as u64),
+                path_str
+            )),
+            kind: Some(ToolOutputKind::FileRef {
+                path: path.display().to_string(),
+                bytes: size,
+            }),
+            ..Default::default()
+        },
+        Ok(Err(e)) => ToolResult {
+            success: false,
+            output: String::new(),
+            error: Some(format!("Failed to write file: {}", e)),
+            ..Default::default()
+        },
+        Err(e) => ToolResult {
+            success: false,
+            output: String::new(),
+            error: Some(format!("Failed to write file: {}", e)),
+            ..Default::default()
+        },
+    }
+}
+
+/// 与原内容的 unified diff（超出 `MAX_DIFF_BYTES` 时按行截断）
+pub fn unified_diff(old: &str, new: &str, label: &str) -> String {
+    let text = similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(label, label)
+        .to_string();
+    truncate_preview(&text)
+}
+
+/// 超出 `MAX_DIFF_BYTES` 时按行截断
+fn truncate_preview(text: &str) -> String {
+    if text.len() <= MAX_DIFF_BYTES {
+        return text.to_string();
+    }
+    let mut end = MAX_DIFF_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = text[..end].rfind('\n').map_or(end, |i| i + 1);
+    format!("{}… (diff 已截断，共 {} 字节)\n", &text[..cut], text.len())
+}
+
 /// 解析路径：相对路径基于 workspace_dir
-fn resolve_path(path_str: &str, policy: &SecurityPolicy) -> std::path::PathBuf {
+pub(super) fn resolve_path(path_str: &str, policy: &SecurityPolicy) -> std::path::PathBuf {
     let path = Path::new(path_str);
     if path.is_absolute() {
         path.to_path_buf()
@@ -269,13 +924,166 @@ mod tests {
         assert!(result.error.unwrap().contains("Failed to read"));
     }
 
+    #[tokio::test]
+    async fn file_read_line_range_with_header() {
+        let tmp = tempfile::tempdir().unwrap();
+        let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(tmp.path().join("log.txt"), content).unwrap();
+        let policy = test_policy(tmp.path());
+
+        let result = FileReadTool
+            .execute(
+                serde_json::json!({"path": "log.txt", "offset_lines": 3, "limit_lines": 2}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(
+            result.output,
+            "[lines 4-5 of 10]\n     4\tline 4\n     5\tline 5\n"
+        );
+
+        // 小文件 tail 读到开头，行号精确
+        let result = FileReadTool
+            .execute(
+                serde_json::json!({"path": "log.txt", "tail": true, "limit_lines": 2}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.output,
+            "[lines 9-10 of 10]\n     9\tline 9\n    10\tline 10\n"
+        );
+
+        let result = FileReadTool
+            .execute(
+                serde_json::json!({"path": "log.txt", "offset_lines": 50}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(result.output.contains("file has 10 lines"));
+    }
+
+    /// 统计实际读取字节数的 reader
+    struct CountingReader<R> {
+        inner: R,
+        bytes_read: u64,
+    }
+
+    impl<R: Read> Read for CountingReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.bytes_read += n as u64;
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for CountingReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[tokio::test]
+    async fn file_read_tail_of_large_file_reads_only_the_end() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("build.log");
+        // 约 4 MB，每行固定 32 字节
+        let content: String = (0..131_072)
+            .map(|i| format!("build step {:>20}\n", i))
+            .collect();
+        std::fs::write(&path, &content).unwrap();
+        let len = content.len() as u64;
+
+        let mut reader = CountingReader {
+            inner: std::fs::File::open(&path).unwrap(),
+            bytes_read: 0,
+        };
+        let (lines, total) = tail_lines(&mut reader, len, 3).unwrap();
+        assert_eq!(total, None);
+        assert_eq!(
+            lines,
+            vec![
+                format!("build step {:>20}", 131_069).into_bytes(),
+                format!("build step {:>20}", 131_070).into_bytes(),
+                format!("build step {:>20}", 131_071).into_bytes(),
+            ]
+        );
+        assert!(
+            reader.bytes_read <= TAIL_CHUNK_BYTES,
+            "{}",
+            reader.bytes_read
+        );
+
+        let policy = test_policy(tmp.path());
+        let result = FileReadTool
+            .execute(
+                serde_json::json!({"path": "build.log", "tail": true, "limit_lines": 2}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.starts_with("[last 2 lines of a 4.0 MB file;"));
+        assert!(result
+            .output
+            .ends_with(&format!("    -1\tbuild step {:>20}\n", 131_071)));
+    }
+
+    #[tokio::test]
+    async fn file_write_append_keeps_existing_content() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file_path = tmp.path().join("notes.md");
+        std::fs::write(&file_path, "# Notes\n").unwrap();
+        let policy = test_policy(tmp.path());
+        let tool = FileWriteTool::new();
+        let args = serde_json::json!({"path": "notes.md", "content": "- item\n", "append": true});
+
+        let preview = tool.confirmation_preview(&args, &policy).unwrap();
+        assert!(preview.contains("+- item"));
+
+        let result = tool.execute(args, &policy).await.unwrap();
+        assert!(result.success);
+        assert!(result.output.starts_with("Appended 7 bytes to "));
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "# Notes\n- item\n"
+        );
+
+        // 文件不存在时创建；路径检查照常生效
+        let result = tool
+            .execute(
+                serde_json::json!({"path": "new/log.txt", "content": "a", "append": true}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("new/log.txt")).unwrap(),
+            "a"
+        );
+        let result = tool
+            .execute(
+                serde_json::json!({"path": "/etc/evil.txt", "content": "x", "append": true}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("allowed"));
+    }
+
     #[tokio::test]
     async fn file_write_success() {
         let tmp = tempfile::tempdir().unwrap();
         let file_path = tmp.path().join("output.txt");
         let policy = test_policy(tmp.path());
 
-        let result = FileWriteTool
+        let result = FileWriteTool::new()
             .execute(
                 serde_json::json!({"path": file_path.to_str().unwrap(), "content": "written"}),
                 &policy,
@@ -287,13 +1095,42 @@ mod tests {
         assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "written");
     }
 
+    #[tokio::test]
+    async fn file_write_sets_file_ref_kind() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file_path = tmp.path().join("output.txt");
+        let policy = test_policy(tmp.path());
+
+        let result = FileWriteTool::new()
+            .execute(
+                serde_json::json!({"path": file_path.to_str().unwrap(), "content": "written"}),
+                &policy,
+            )
+            .await
+            .unwrap();
+
+        match result.kind {
+            Some(ToolOutputKind::FileRef { path, bytes }) => {
+                assert!(path.ends_with("output.txt"));
+                assert_eq!(bytes, 7);
+            }
+            other => panic!("expected FileRef, got {:?}", other),
+        }
+        // LLM 可见文本不变
+        assert!(result.output.starts_with("Wrote 7 bytes to "));
+        // 用户摘要单独给出
+        let user_facing = result.user_facing.unwrap();
+        assert!(user_facing.starts_with("wrote 7 B to "));
+        assert!(user_facing.ends_with("output.txt"));
+    }
+
     #[tokio::test]
     async fn file_write_creates_parent_dirs() {
         let tmp = tempfile::tempdir().unwrap();
         let file_path = tmp.path().join("sub").join("dir").join("file.txt");
         let policy = test_policy(tmp.path());
 
-        let result = FileWriteTool
+        let result = FileWriteTool::new()
             .execute(
                 serde_json::json!({"path": file_path.to_str().unwrap(), "content": "nested"}),
                 &policy,
@@ -311,7 +1148,7 @@ mod tests {
         let mut policy = test_policy(tmp.path());
         policy.autonomy = AutonomyLevel::ReadOnly;
 
-        let result = FileWriteTool
+        let result = FileWriteTool::new()
             .execute(
                 serde_json::json!({"path": "file.txt", "content": "data"}),
                 &policy,
@@ -328,7 +1165,7 @@ mod tests {
         let tmp = tempfile::tempdir().unwrap();
         let policy = test_policy(tmp.path());
 
-        let result = FileWriteTool
+        let result = FileWriteTool::new()
             .execute(
                 serde_json::json!({"path": "/etc/evil.txt", "content": "hack"}),
                 &policy,
@@ -340,12 +1177,186 @@ mod tests {
         assert!(result.error.unwrap().contains("allowed"));
     }
 
+    #[tokio::test]
+    async fn file_write_overwrite_reports_diff_and_saves_snapshot() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file_path = tmp.path().join("notes.txt");
+        std::fs::write(&file_path, "keep\nold\n").unwrap();
+        let policy = test_policy(tmp.path());
+        let undo_dir = tmp.path().join("undo");
+        let tool = FileWriteTool::new().with_undo_dir(undo_dir.clone());
+        let args = serde_json::json!({"path": "notes.txt", "content": "keep\nnew\n"});
+
+        let preview = tool.confirmation_preview(&args, &policy).unwrap();
+        assert!(preview.contains("-old") && preview.contains("+new"));
+
+        let result = tool.execute(args, &policy).await.unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("-old\n+new"), "{}", result.output);
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "keep\nnew\n");
+
+        UndoStore::new(undo_dir).restore(&file_path).unwrap();
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "keep\nold\n");
+    }
+
+    #[tokio::test]
+    async fn file_write_create_only_refuses_existing_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file_path = tmp.path().join("exists.txt");
+        std::fs::write(&file_path, "original").unwrap();
+        let policy = test_policy(tmp.path());
+
+        let result = FileWriteTool::new()
+            .execute(
+                serde_json::json!({"path": "exists.txt", "content": "new", "create_only": true}),
+                &policy,
+            )
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("create_only"));
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "original");
+    }
+
+    #[tokio::test]
+    async fn file_write_identical_content_is_noop() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file_path = tmp.path().join("same.txt");
+        std::fs::write(&file_path, "same").unwrap();
+        let policy = test_policy(tmp.path());
+        let undo_dir = tmp.path().join("undo");
+        let tool = FileWriteTool::new().with_undo_dir(undo_dir.clone());
+        let args = serde_json::json!({"path": "same.txt", "content": "same"});
+
+        assert_eq!(
+            tool.confirmation_preview(&args, &policy).as_deref(),
+            Some("内容未变化")
+        );
+        let result = tool.execute(args, &policy).await.unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("内容未变化"));
+        // 未写入也未产生快照
+        assert!(!undo_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn grep_matches_across_files_with_glob() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("src/nested")).unwrap();
+        std::fs::write(
+            tmp.path().join("src/main.rs"),
+            "fn main() {}\n// TODO: wire up\n",
+        )
+        .unwrap();
+        std::fs::write(tmp.path().join("src/nested/lib.rs"), "// todo later\n").unwrap();
+        std::fs::write(tmp.path().join("notes.md"), "TODO: docs\n").unwrap();
+        std::fs::create_dir_all(tmp.path().join(".git")).unwrap();
+        std::fs::write(tmp.path().join(".git/HEAD"), "TODO hidden\n").unwrap();
+        let policy = test_policy(tmp.path());
+
+        let result = GrepTool
+            .execute(
+                serde_json::json!({"pattern": "todo", "case_insensitive": true}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(
+            result.output,
+            "notes.md:1: TODO: docs\nsrc/main.rs:2: // TODO: wire up\nsrc/nested/lib.rs:1: // todo later"
+        );
+
+        let result = GrepTool
+            .execute(
+                serde_json::json!({"pattern": "TODO", "glob": "src/**/*.rs"}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.output, "src/main.rs:2: // TODO: wire up");
+    }
+
+    #[tokio::test]
+    async fn grep_no_match_and_invalid_regex() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a.txt"), "hello\n").unwrap();
+        let policy = test_policy(tmp.path());
+
+        let result = GrepTool
+            .execute(serde_json::json!({"pattern": "absent"}), &policy)
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "No matches (1 files searched)");
+
+        let result = GrepTool
+            .execute(serde_json::json!({"pattern": "(unclosed"}), &policy)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("Invalid regex"));
+    }
+
+    #[tokio::test]
+    async fn grep_confined_to_workspace_and_skips_blocked_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "password\n").unwrap();
+        std::fs::create_dir_all(tmp.path().join("private")).unwrap();
+        std::fs::write(tmp.path().join("private/key.txt"), "password\n").unwrap();
+        std::fs::write(tmp.path().join("public.txt"), "password hint\n").unwrap();
+        let mut policy = test_policy(tmp.path());
+        policy.blocked_paths = vec![policy.workspace_dir.join("private")];
+
+        let result = GrepTool
+            .execute(
+                serde_json::json!({"pattern": "password", "path": outside.path().to_str().unwrap()}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result
+            .error
+            .unwrap()
+            .contains("Path not within allowed workspace"));
+
+        let result = GrepTool
+            .execute(serde_json::json!({"pattern": "password"}), &policy)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "public.txt:1: password hint");
+    }
+
+    #[test]
+    fn glob_match_patterns() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/**/*.rs", "src/main.rs"));
+        assert!(glob_match("src/**/*.rs", "src/a/b/c.rs"));
+        assert!(glob_match("**/*.toml", "Cargo.toml"));
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("src/*.rs", "src/a/b.rs"));
+    }
+
+    #[test]
+    fn unified_diff_truncates_large_output() {
+        let old: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        let new: String = (0..2000).map(|i| format!("LINE {}\n", i)).collect();
+        let diff = unified_diff(&old, &new, "big.txt");
+        assert!(diff.starts_with("--- big.txt\n+++ big.txt\n"));
+        assert!(diff.len() < MAX_DIFF_BYTES + 100);
+        assert!(diff.contains("diff 已截断"));
+    }
+
     #[test]
     fn tool_specs() {
         let read_spec = FileReadTool.spec();
         assert_eq!(read_spec.name, "file_read");
 
-        let write_spec = FileWriteTool.spec();
+        let write_spec = FileWriteTool::new().spec();
         assert_eq!(write_spec.name, "file_write");
         assert!(write_spec.parameters["required"]
             .as_array()
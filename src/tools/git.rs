@@ -3,7 +3,7 @@ use color_eyre::eyre::{eyre, Result};
 use serde_json::json;
 use tracing::debug;
 
-use super::traits::{Tool, ToolResult};
+use super::traits::{Tool, ToolOutputKind, ToolResult};
 use crate::security::SecurityPolicy;
 
 pub struct GitTool;
@@ -101,10 +101,16 @@ impl Tool for GitTool {
                 let stderr = String::from_utf8_lossy(&output.stderr).to_string();
 
                 if output.status.success() {
+                    // diff 有内容时附带 Diff 元数据，供 Channel 高亮渲染
+                    let kind = (action == "diff" && !stdout.trim().is_empty())
+                        .then_some(ToolOutputKind::Diff);
+                    let suggestions = follow_ups(action, &stdout);
                     Ok(ToolResult {
                         success: true,
                         output: if stdout.is_empty() { stderr } else { stdout },
                         error: None,
+                        kind,
+                        suggestions,
                         ..Default::default()
                     })
                 } else {
@@ -133,6 +139,19 @@ impl Tool for GitTool {
     }
 }
 
+/// 成功执行后的后续建议（UI 快捷回复）：有未提交的 diff 时建议解释或提交
+fn follow_ups(action: &str, stdout: &str) -> Vec<String> {
+    if action != "diff" || stdout.trim().is_empty() {
+        return Vec::new();
+    }
+    let en = crate::config::Config::get_language().is_english();
+    let pick = |zh: &str, en_text: &str| if en { en_text } else { zh }.to_string();
+    vec![
+        pick("解释这些改动", "Explain these changes"),
+        pick("提交这些改动", "Commit these changes"),
+    ]
+}
+
 /// 根据 action + 额外参数构造 git 命令参数列表
 fn build_git_args(action: &str, extra: &str) -> Result<Vec<String>> {
     // 验证 action 合法性
@@ -163,6 +182,98 @@ fn build_git_args(action: &str, extra: &str) -> Result<Vec<String>> {
     Ok(args)
 }
 
+/// 安全暂存（`/safety stash`）的 stash 消息前缀，后接会话 ID
+pub const SAFETY_STASH_PREFIX: &str = "rrclaw-safety:";
+
+/// 工作区的 git 状态（脏工作区提醒用）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorktreeState {
+    /// 不在 git 仓库中（或 git 不可用）
+    NotRepo,
+    /// 没有未提交的改动
+    Clean,
+    /// 有未提交的改动（含未跟踪文件），值为改动的路径
+    Dirty(Vec<String>),
+}
+
+impl GitTool {
+    /// 查询工作区是否有未提交的改动（`git status --porcelain`）
+    pub async fn worktree_state(dir: &std::path::Path) -> WorktreeState {
+        match run_git(dir, &["status", "--porcelain"]).await {
+            Ok(out) => {
+                let paths: Vec<String> = out
+                    .lines()
+                    .filter_map(|line| line.get(3..))
+                    .map(|path| path.trim_matches('"').to_string())
+                    .collect();
+                if paths.is_empty() {
+                    WorktreeState::Clean
+                } else {
+                    WorktreeState::Dirty(paths)
+                }
+            }
+            Err(_) => WorktreeState::NotRepo,
+        }
+    }
+
+    /// 把未提交的改动（含未跟踪文件）存入带会话 ID 标记的 stash
+    ///
+    /// 返回 stash 消息；工作区本来就干净时返回 None
+    pub async fn safety_stash(dir: &std::path::Path, session_id: &str) -> Result<Option<String>> {
+        match Self::worktree_state(dir).await {
+            WorktreeState::NotRepo => {
+                return Err(eyre!("Not a git repository: {}", dir.display()));
+            }
+            WorktreeState::Clean => return Ok(None),
+            WorktreeState::Dirty(_) => {}
+        }
+        let message = format!("{}{}", SAFETY_STASH_PREFIX, session_id);
+        run_git(
+            dir,
+            &["stash", "push", "--include-untracked", "-m", &message],
+        )
+        .await?;
+        Ok(Some(message))
+    }
+
+    /// 恢复本会话最近一次安全暂存（`git stash pop`），返回恢复的 stash 引用
+    pub async fn safety_restore(dir: &std::path::Path, session_id: &str) -> Result<String> {
+        let list = run_git(dir, &["stash", "list", "--format=%gd%x09%gs"]).await?;
+        let stash_ref = find_safety_stash(&list, session_id).ok_or_else(|| {
+            eyre!(
+                "No safety stash found for session '{}' (created by /safety stash)",
+                session_id
+            )
+        })?;
+        run_git(dir, &["stash", "pop", &stash_ref]).await?;
+        Ok(stash_ref)
+    }
+}
+
+/// 在 `git stash list --format=%gd%x09%gs` 输出中找本会话最新的安全暂存
+fn find_safety_stash(list: &str, session_id: &str) -> Option<String> {
+    let marker = format!(": {}{}", SAFETY_STASH_PREFIX, session_id);
+    list.lines().find_map(|line| {
+        let (stash_ref, subject) = line.split_once('\t')?;
+        subject.ends_with(&marker).then(|| stash_ref.to_string())
+    })
+}
+
+/// 在工作区执行 git 子命令，失败时返回 stderr
+async fn run_git(dir: &std::path::Path, args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .map_err(|e| eyre!("Failed to execute git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(eyre!("git {} failed: {}", args[0], stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,6 +293,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn non_empty_diff_suggests_follow_ups() {
+        assert_eq!(
+            follow_ups("diff", "diff --git a/x b/x\n+new\n"),
+            vec!["Explain these changes", "Commit these changes"]
+        );
+        assert!(follow_ups("diff", "\n").is_empty());
+        assert!(follow_ups("log", "commit abc").is_empty());
+    }
+
     // --- build_git_args 测试 ---
 
     #[test]
@@ -305,6 +426,147 @@ mod tests {
         assert!(!result.success);
     }
 
+    #[tokio::test]
+    async fn execute_diff_sets_diff_kind() {
+        let tmp = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .output()
+                .unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "t@example.com"]);
+        git(&["config", "user.name", "t"]);
+        std::fs::write(tmp.path().join("a.txt"), "one\n").unwrap();
+        git(&["add", "a.txt"]);
+        git(&["commit", "-m", "init"]);
+        std::fs::write(tmp.path().join("a.txt"), "two\n").unwrap();
+
+        let policy = test_policy(tmp.path());
+        let result = GitTool
+            .execute(serde_json::json!({"action": "diff"}), &policy)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.kind, Some(ToolOutputKind::Diff));
+        // LLM 可见文本仍是原始 diff 输出
+        assert!(result.output.contains("-one"));
+        assert!(result.output.contains("+two"));
+    }
+
+    #[tokio::test]
+    async fn execute_status_has_no_kind() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::process::Command::new("git")
+            .args(["init"])
+            .current_dir(tmp.path())
+            .output()
+            .unwrap();
+
+        let policy = test_policy(tmp.path());
+        let result = GitTool
+            .execute(serde_json::json!({"action": "status"}), &policy)
+            .await
+            .unwrap();
+        assert!(result.kind.is_none());
+    }
+
+    // --- 工作区状态 / 安全暂存 ---
+
+    /// 带一次初始提交的临时仓库
+    fn committed_repo() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .output()
+                .unwrap()
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "t@example.com"]);
+        git(&["config", "user.name", "t"]);
+        std::fs::write(tmp.path().join("a.txt"), "one\n").unwrap();
+        git(&["add", "a.txt"]);
+        git(&["commit", "-m", "init"]);
+        tmp
+    }
+
+    #[tokio::test]
+    async fn worktree_state_clean_dirty_and_not_repo() {
+        let plain = tempfile::tempdir().unwrap();
+        assert_eq!(
+            GitTool::worktree_state(plain.path()).await,
+            WorktreeState::NotRepo
+        );
+
+        let repo = committed_repo();
+        assert_eq!(
+            GitTool::worktree_state(repo.path()).await,
+            WorktreeState::Clean
+        );
+
+        std::fs::write(repo.path().join("a.txt"), "two\n").unwrap();
+        std::fs::write(repo.path().join("new.txt"), "x\n").unwrap();
+        assert_eq!(
+            GitTool::worktree_state(repo.path()).await,
+            WorktreeState::Dirty(vec!["a.txt".to_string(), "new.txt".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn safety_stash_round_trip_is_scoped_to_session() {
+        let repo = committed_repo();
+        let dir = repo.path();
+
+        // 干净工作区无需暂存
+        assert_eq!(GitTool::safety_stash(dir, "s1").await.unwrap(), None);
+
+        std::fs::write(dir.join("a.txt"), "mine\n").unwrap();
+        std::fs::write(dir.join("untracked.txt"), "u\n").unwrap();
+        let message = GitTool::safety_stash(dir, "s1").await.unwrap();
+        assert_eq!(message.as_deref(), Some("rrclaw-safety:s1"));
+        assert_eq!(GitTool::worktree_state(dir).await, WorktreeState::Clean);
+
+        // 其他会话找不到这个 stash
+        let err = GitTool::safety_restore(dir, "s2").await.unwrap_err();
+        assert!(err.to_string().contains("No safety stash"));
+
+        let restored = GitTool::safety_restore(dir, "s1").await.unwrap();
+        assert_eq!(restored, "stash@{0}");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "mine\n"
+        );
+        assert!(dir.join("untracked.txt").exists());
+
+        // 已恢复后不能再恢复
+        assert!(GitTool::safety_restore(dir, "s1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn safety_stash_outside_repo_fails() {
+        let plain = tempfile::tempdir().unwrap();
+        assert!(GitTool::safety_stash(plain.path(), "s1").await.is_err());
+    }
+
+    #[test]
+    fn find_safety_stash_picks_newest_for_session() {
+        let list = "stash@{0}\tOn main: rrclaw-safety:2026-10-17\n\
+                    stash@{1}\tOn main: WIP manual\n\
+                    stash@{2}\tOn main: rrclaw-safety:2026-10-16\n\
+                    stash@{3}\tOn main: rrclaw-safety:2026-10-16\n";
+        assert_eq!(
+            find_safety_stash(list, "2026-10-16").as_deref(),
+            Some("stash@{2}")
+        );
+        assert_eq!(find_safety_stash(list, "10-16"), None);
+        assert_eq!(find_safety_stash(list, "2026-10-18"), None);
+    }
+
     #[test]
     fn tool_spec_correct() {
         let spec = GitTool.spec();
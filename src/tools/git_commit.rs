@@ -0,0 +1,719 @@
+//! GitCommitTool：git-commit 技能的确定性实现
+//!
+//! 两步完成一次提交：
+//! 1. `propose`：收集 `git status` / 暂存区 diff，按大小预算分块后调用 Provider
+//!    生成 Conventional Commits 格式的 message，返回 message + 文件列表（不修改仓库）
+//! 2. `commit`：带上（可修改的）message 和文件列表再次调用；Supervised 模式下确认提示
+//!    展示的就是这些参数。文件集合与 propose 时不一致则拒绝，避免提交未经确认的改动。
+//!
+//! 永远不执行 push。
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
+use serde_json::json;
+use tracing::debug;
+
+use super::traits::{Tool, ToolResult};
+use crate::providers::traits::{ChatMessage, ConversationMessage, GenerationParams, Provider};
+use crate::security::SecurityPolicy;
+
+/// 单次发送给 Provider 的 diff 上限
+const DIFF_CHUNK_BYTES: usize = 12 * 1024;
+/// 单个文件 diff 上限（超出截断，避免一个大文件挤掉其他文件）
+const MAX_FILE_DIFF_BYTES: usize = 6 * 1024;
+/// 最多分析的 diff 块数；其余文件只列出文件名
+const MAX_DIFF_CHUNKS: usize = 4;
+
+const MESSAGE_PROMPT: &str = "You write git commit messages following Conventional Commits.\n\
+Format: `<type>: <summary>` on the first line (type is one of feat, fix, docs, test, refactor, chore; \
+summary in English, imperative mood, at most 72 characters). \
+Optionally add a blank line and a short body explaining why the change was made.\n\
+Reply with the commit message only: no code fences, no quotes, no commentary.";
+
+const SUMMARY_PROMPT: &str =
+    "Summarize the following part of a git diff in 1-3 short bullet points \
+describing what changed and why. Reply with the bullet points only.";
+
+/// 暂存区（或待暂存）中的一个文件
+#[derive(Debug, Clone, PartialEq)]
+struct ChangedFile {
+    /// `git diff --name-status` 的状态（M / A / D / R100 ...）
+    status: String,
+    path: String,
+}
+
+pub struct GitCommitTool {
+    provider: Arc<dyn Provider>,
+    model: String,
+}
+
+impl GitCommitTool {
+    pub fn new(provider: Arc<dyn Provider>, model: String) -> Self {
+        Self { provider, model }
+    }
+}
+
+#[async_trait]
+impl Tool for GitCommitTool {
+    fn name(&self) -> &str {
+        "git_commit"
+    }
+
+    fn description(&self) -> &str {
+        "Commit workflow (preferred over shell/git for commits). Step 1: action=propose reads the staged diff and \
+         returns a generated Conventional Commits message plus the file list, without changing anything. \
+         Step 2: action=commit with the message and files from step 1 runs `git commit`. Never pushes."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["propose", "commit"],
+                    "description": "propose: generate message from the diff (read-only); commit: run git commit"
+                },
+                "stage_tracked": {
+                    "type": "boolean",
+                    "description": "When nothing is staged, include modified tracked files (git add -u). Set true when the user asks to \"commit my changes\". Untracked files are never added."
+                },
+                "message": {
+                    "type": "string",
+                    "description": "Commit message (required for commit; use the proposed one unless the user asked for changes)"
+                },
+                "files": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "File paths returned by propose; commit is refused if the changes no longer match"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    fn pre_validate(&self, args: &serde_json::Value, policy: &SecurityPolicy) -> Option<String> {
+        let action = args.get("action").and_then(|v| v.as_str()).unwrap_or("");
+        if action == "commit" && !policy.allows_execution() {
+            return Some("Read-only mode: git commit not allowed".to_string());
+        }
+        None
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        let action = args
+            .get("action")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| eyre!("Missing 'action' parameter"))?;
+        let stage_tracked = args
+            .get("stage_tracked")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let repo = Repo {
+            dir: &policy.workspace_dir,
+        };
+        if let Err(e) = repo.run(&["rev-parse", "--is-inside-work-tree"]).await {
+            return Ok(failure(format!("Not a git repository: {}", e)));
+        }
+
+        let result = match action {
+            "propose" => self.propose(&repo, stage_tracked).await,
+            "commit" => {
+                let message = args
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .map(str::trim)
+                    .unwrap_or("");
+                let files: Option<Vec<String>> =
+                    args.get("files").and_then(|v| v.as_array()).map(|arr| {
+                        arr.iter()
+                            .filter_map(|f| f.as_str().map(String::from))
+                            .collect()
+                    });
+                commit(&repo, message, files.as_deref(), stage_tracked).await
+            }
+            other => Ok(failure(format!(
+                "Unknown action: '{}'. Supported: propose, commit",
+                other
+            ))),
+        };
+        result.or_else(|e| Ok(failure(format!("{:#}", e))))
+    }
+}
+
+impl GitCommitTool {
+    async fn propose(&self, repo: &Repo<'_>, stage_tracked: bool) -> Result<ToolResult> {
+        let (files, diff, will_stage) = match pending_changes(repo, stage_tracked).await? {
+            Pending::Staged(files) => (files, repo.run(&["diff", "--cached"]).await?, false),
+            Pending::Tracked(files) => (files, repo.run(&["diff"]).await?, true),
+            Pending::Nothing { unstaged } => return Ok(nothing_to_commit(repo, unstaged).await),
+        };
+
+        let chunks = chunk_diff(&diff, DIFF_CHUNK_BYTES);
+        debug!(
+            "git_commit: {} 个文件，diff {} 字节，分 {} 块",
+            files.len(),
+            diff.len(),
+            chunks.len()
+        );
+        let message = self.generate_message(&chunks, &files).await?;
+
+        let mut out = format!(
+            "Proposed commit message:\n---\n{}\n---\nFiles ({}):\n",
+            message,
+            files.len()
+        );
+        for f in &files {
+            out.push_str(&format!("  {}\t{}\n", f.status, f.path));
+        }
+        if will_stage {
+            out.push_str("Nothing is staged; these modified tracked files will be staged (git add -u) at commit time.\n");
+        }
+        out.push_str(&format!(
+            "\nNext: show this to the user, then call git_commit with action=\"commit\", the message, \
+             files={}{}.",
+            serde_json::to_string(&files.iter().map(|f| &f.path).collect::<Vec<_>>())?,
+            if will_stage { ", stage_tracked=true" } else { "" }
+        ));
+
+        Ok(ToolResult {
+            success: true,
+            output: out,
+            error: None,
+            ..Default::default()
+        })
+    }
+
+    /// 生成 commit message；diff 超出单块预算时先逐块摘要再汇总
+    async fn generate_message(&self, chunks: &[String], files: &[ChangedFile]) -> Result<String> {
+        let file_list = files
+            .iter()
+            .map(|f| format!("{}\t{}", f.status, f.path))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let content = match chunks {
+            [] => format!("Changed files:\n{}", file_list),
+            [only] => format!("Changed files:\n{}\n\nDiff:\n{}", file_list, only),
+            _ => {
+                let mut summaries = Vec::new();
+                for chunk in chunks.iter().take(MAX_DIFF_CHUNKS) {
+                    summaries.push(self.ask(SUMMARY_PROMPT, chunk).await?);
+                }
+                let mut content = format!(
+                    "Changed files:\n{}\n\nSummary of the diff:\n{}",
+                    file_list,
+                    summaries.join("\n")
+                );
+                if chunks.len() > MAX_DIFF_CHUNKS {
+                    content.push_str("\n(Diff of the remaining files omitted; see the file list.)");
+                }
+                content
+            }
+        };
+
+        let message = clean_message(&self.ask(MESSAGE_PROMPT, &content).await?);
+        if message.is_empty() {
+            return Err(eyre!("Provider returned an empty commit message"));
+        }
+        Ok(message)
+    }
+
+    async fn ask(&self, system: &str, user: &str) -> Result<String> {
+        let messages = vec![
+            ConversationMessage::Chat(ChatMessage {
+                role: "system".to_string(),
+                content: system.to_string(),
+                reasoning_content: None,
+                turn: 0,
+            }),
+            ConversationMessage::Chat(ChatMessage {
+                role: "user".to_string(),
+                content: user.to_string(),
+                reasoning_content: None,
+                turn: 0,
+            }),
+        ];
+        let resp = self
+            .provider
+            .chat_with_tools(
+                &messages,
+                &[],
+                &self.model,
+                GenerationParams::with_temperature(0.2),
+            )
+            .await?;
+        Ok(resp.text.unwrap_or_default())
+    }
+}
+
+/// 执行提交：校验文件集合 → 按需 `git add -u` → `git commit -m`
+async fn commit(
+    repo: &Repo<'_>,
+    message: &str,
+    expected: Option<&[String]>,
+    stage_tracked: bool,
+) -> Result<ToolResult> {
+    if message.is_empty() {
+        return Ok(failure(
+            "commit requires a message (call action=propose first)".to_string(),
+        ));
+    }
+
+    let (files, needs_stage) = match pending_changes(repo, stage_tracked).await? {
+        Pending::Staged(files) => (files, false),
+        Pending::Tracked(files) => (files, true),
+        Pending::Nothing { unstaged } => return Ok(nothing_to_commit(repo, unstaged).await),
+    };
+
+    if let Some(expected) = expected {
+        let mut actual: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        let mut expected: Vec<&str> = expected.iter().map(String::as_str).collect();
+        actual.sort_unstable();
+        expected.sort_unstable();
+        if actual != expected {
+            return Ok(failure(format!(
+                "The changes no longer match the proposal (now: {}). Run action=propose again.",
+                actual.join(", ")
+            )));
+        }
+    }
+
+    if needs_stage {
+        repo.run(&["add", "-u"]).await?;
+    }
+    let out = repo.run(&["commit", "-m", message]).await?;
+
+    Ok(ToolResult {
+        success: true,
+        output: format!(
+            "{}\nCommitted {} file(s). Not pushed.",
+            out.trim_end(),
+            files.len()
+        ),
+        error: None,
+        ..Default::default()
+    })
+}
+
+/// 待提交内容
+enum Pending {
+    /// 暂存区已有改动
+    Staged(Vec<ChangedFile>),
+    /// 暂存区为空，`stage_tracked` 时取已跟踪文件的未暂存改动
+    Tracked(Vec<ChangedFile>),
+    /// 无可提交内容；`unstaged` 为未暂存的已跟踪文件数
+    Nothing { unstaged: usize },
+}
+
+async fn pending_changes(repo: &Repo<'_>, stage_tracked: bool) -> Result<Pending> {
+    let staged = parse_name_status(&repo.run(&["diff", "--cached", "--name-status"]).await?);
+    if !staged.is_empty() {
+        return Ok(Pending::Staged(staged));
+    }
+    let unstaged = parse_name_status(&repo.run(&["diff", "--name-status"]).await?);
+    if stage_tracked && !unstaged.is_empty() {
+        return Ok(Pending::Tracked(unstaged));
+    }
+    Ok(Pending::Nothing {
+        unstaged: unstaged.len(),
+    })
+}
+
+/// 空 diff：不调用 Provider，说明原因和可选操作
+async fn nothing_to_commit(repo: &Repo<'_>, unstaged: usize) -> ToolResult {
+    let status = repo
+        .run(&["status", "--porcelain"])
+        .await
+        .unwrap_or_default();
+    let mut out = "Nothing to commit: the staging area is empty.".to_string();
+    if unstaged > 0 {
+        out.push_str(&format!(
+            " {} modified tracked file(s) are not staged; call again with stage_tracked=true to include them.",
+            unstaged
+        ));
+    }
+    if !status.trim().is_empty() {
+        out.push_str(&format!(
+            "\n\ngit status --porcelain:\n{}",
+            status.trim_end()
+        ));
+    }
+    ToolResult {
+        success: true,
+        output: out,
+        error: None,
+        ..Default::default()
+    }
+}
+
+fn failure(error: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        output: String::new(),
+        error: Some(error),
+        ..Default::default()
+    }
+}
+
+/// 在工作区执行 git 子命令
+struct Repo<'a> {
+    dir: &'a std::path::Path,
+}
+
+impl Repo<'_> {
+    async fn run(&self, args: &[&str]) -> Result<String> {
+        let output = tokio::process::Command::new("git")
+            .args(args)
+            .current_dir(self.dir)
+            .output()
+            .await
+            .map_err(|e| eyre!("Failed to execute git: {}", e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let detail = if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            };
+            return Err(eyre!("git {} failed: {}", args[0], detail.trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+/// 解析 `git diff --name-status`（重命名取新路径）
+fn parse_name_status(out: &str) -> Vec<ChangedFile> {
+    out.lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\t');
+            let status = parts.next()?.trim();
+            let path = parts.next_back()?;
+            (!status.is_empty()).then(|| ChangedFile {
+                status: status.to_string(),
+                path: path.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// 按文件切分 diff，单文件超出 `MAX_FILE_DIFF_BYTES` 截断，再贪心装入不超过 `budget` 的块
+fn chunk_diff(diff: &str, budget: usize) -> Vec<String> {
+    let mut sections: Vec<String> = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") || sections.is_empty() {
+            sections.push(String::new());
+        }
+        sections.last_mut().unwrap().push_str(line);
+    }
+
+    let per_file = MAX_FILE_DIFF_BYTES.min(budget);
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for section in sections {
+        let section = truncate_section(&section, per_file);
+        if !current.is_empty() && current.len() + section.len() > budget {
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(&section);
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn truncate_section(section: &str, max: usize) -> String {
+    const MARKER: &str = "... (diff truncated)\n";
+    if section.len() <= max {
+        return section.to_string();
+    }
+    let mut end = max.saturating_sub(MARKER.len());
+    while !section.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut s = section[..end].to_string();
+    if !s.ends_with('\n') {
+        s.push('\n');
+    }
+    s.push_str(MARKER);
+    s
+}
+
+/// 去掉模型可能附带的代码块围栏和首尾空白
+fn clean_message(raw: &str) -> String {
+    raw.trim()
+        .lines()
+        .filter(|l| !l.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::traits::{ChatResponse, ToolSpec};
+    use crate::security::AutonomyLevel;
+    use std::path::Path;
+    use std::process::Command;
+    use std::sync::Mutex;
+
+    /// 返回固定 message，并记录每次请求的 user 内容
+    struct MockProvider {
+        reply: String,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl MockProvider {
+        fn new(reply: &str) -> Arc<Self> {
+            Arc::new(Self {
+                reply: reply.to_string(),
+                prompts: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.prompts.lock().unwrap().len()
+        }
+    }
+
+    #[async_trait]
+    impl Provider for MockProvider {
+        async fn chat_with_tools(
+            &self,
+            messages: &[ConversationMessage],
+            _tools: &[ToolSpec],
+            _model: &str,
+            _params: GenerationParams,
+        ) -> Result<ChatResponse> {
+            if let Some(ConversationMessage::Chat(m)) = messages.last() {
+                self.prompts.lock().unwrap().push(m.content.clone());
+            }
+            Ok(ChatResponse {
+                text: Some(self.reply.clone()),
+                reasoning_content: None,
+                tool_calls: vec![],
+                provenance: None,
+            })
+        }
+    }
+
+    fn tool(provider: &Arc<MockProvider>) -> GitCommitTool {
+        GitCommitTool::new(provider.clone(), "mock".to_string())
+    }
+
+    fn policy(workspace: &Path, autonomy: AutonomyLevel) -> SecurityPolicy {
+        SecurityPolicy {
+            autonomy,
+            allowed_commands: vec![],
+            workspace_dir: workspace.canonicalize().unwrap(),
+            blocked_paths: vec![],
+            http_allowed_hosts: vec![],
+            injection_check: true,
+        }
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let out = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&out.stdout).to_string()
+    }
+
+    /// 新建没有任何提交的仓库
+    fn init_repo() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        git(tmp.path(), &["init", "-q"]);
+        git(tmp.path(), &["config", "user.name", "Test"]);
+        git(tmp.path(), &["config", "user.email", "test@example.com"]);
+        git(tmp.path(), &["config", "commit.gpgsign", "false"]);
+        tmp
+    }
+
+    #[tokio::test]
+    async fn propose_and_commit_in_repo_without_commits() {
+        let repo = init_repo();
+        std::fs::write(repo.path().join("a.txt"), "hello\n").unwrap();
+        git(repo.path(), &["add", "a.txt"]);
+        let provider = MockProvider::new("```\nfeat: add greeting file\n```");
+        let policy = policy(repo.path(), AutonomyLevel::Supervised);
+
+        let result = tool(&provider)
+            .execute(json!({"action": "propose"}), &policy)
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.contains("feat: add greeting file"));
+        assert!(result.output.contains("a.txt"));
+        assert!(provider.prompts.lock().unwrap()[0].contains("+hello"));
+        // propose 不修改仓库
+        assert!(git(repo.path(), &["log", "--oneline"]).is_empty());
+
+        let result = tool(&provider)
+            .execute(
+                json!({"action": "commit", "message": "feat: add greeting file", "files": ["a.txt"]}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.contains("Not pushed"));
+        assert_eq!(
+            git(repo.path(), &["log", "-1", "--format=%s"]).trim(),
+            "feat: add greeting file"
+        );
+    }
+
+    #[tokio::test]
+    async fn unstaged_tracked_changes_need_stage_tracked() {
+        let repo = init_repo();
+        std::fs::write(repo.path().join("a.txt"), "v1\n").unwrap();
+        git(repo.path(), &["add", "a.txt"]);
+        git(repo.path(), &["commit", "-qm", "init"]);
+        std::fs::write(repo.path().join("a.txt"), "v2\n").unwrap();
+        std::fs::write(repo.path().join("untracked.txt"), "x\n").unwrap();
+        let provider = MockProvider::new("fix: bump a to v2");
+        let policy = policy(repo.path(), AutonomyLevel::Full);
+
+        let result = tool(&provider)
+            .execute(json!({"action": "propose"}), &policy)
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("Nothing to commit"));
+        assert!(result.output.contains("stage_tracked=true"));
+        assert_eq!(provider.calls(), 0);
+
+        let result = tool(&provider)
+            .execute(json!({"action": "propose", "stage_tracked": true}), &policy)
+            .await
+            .unwrap();
+        assert!(result.output.contains("fix: bump a to v2"));
+        assert!(result.output.contains("git add -u"));
+        assert!(!result.output.contains("untracked.txt"));
+
+        let result = tool(&provider)
+            .execute(
+                json!({"action": "commit", "message": "fix: bump a to v2", "files": ["a.txt"], "stage_tracked": true}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(
+            git(repo.path(), &["log", "-1", "--format=%s"]).trim(),
+            "fix: bump a to v2"
+        );
+        // 未跟踪文件从不自动加入
+        assert!(git(repo.path(), &["status", "--porcelain"]).contains("?? untracked.txt"));
+    }
+
+    #[tokio::test]
+    async fn empty_diff_is_reported_without_calling_provider() {
+        let repo = init_repo();
+        let provider = MockProvider::new("chore: nothing");
+        let policy = policy(repo.path(), AutonomyLevel::Full);
+
+        for args in [
+            json!({"action": "propose"}),
+            json!({"action": "commit", "message": "chore: nothing"}),
+        ] {
+            let result = tool(&provider).execute(args, &policy).await.unwrap();
+            assert!(result.success);
+            assert!(result.output.contains("Nothing to commit"));
+        }
+        assert_eq!(provider.calls(), 0);
+        assert!(git(repo.path(), &["log", "--oneline"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn commit_refused_when_changes_differ_from_proposal() {
+        let repo = init_repo();
+        std::fs::write(repo.path().join("a.txt"), "a\n").unwrap();
+        std::fs::write(repo.path().join("b.txt"), "b\n").unwrap();
+        git(repo.path(), &["add", "a.txt", "b.txt"]);
+        let provider = MockProvider::new("feat: add files");
+        let policy = policy(repo.path(), AutonomyLevel::Full);
+
+        let result = tool(&provider)
+            .execute(
+                json!({"action": "commit", "message": "feat: add a", "files": ["a.txt"]}),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("propose again"));
+        assert!(git(repo.path(), &["log", "--oneline"]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn large_diff_is_summarized_per_chunk() {
+        let repo = init_repo();
+        for i in 0..4 {
+            let body: String = (0..600)
+                .map(|n| format!("line {} of file {}\n", n, i))
+                .collect();
+            std::fs::write(repo.path().join(format!("f{}.txt", i)), body).unwrap();
+        }
+        git(repo.path(), &["add", "."]);
+        let provider = MockProvider::new("chore: add generated fixtures");
+        let policy = policy(repo.path(), AutonomyLevel::Full);
+
+        let result = tool(&provider)
+            .execute(json!({"action": "propose"}), &policy)
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        let prompts = provider.prompts.lock().unwrap();
+        // 每个文件 ~6KB 截断 → 至少 2 块摘要 + 1 次汇总
+        assert!(prompts.len() >= 3);
+        assert!(prompts.iter().all(|p| p.len() <= DIFF_CHUNK_BYTES + 1024));
+        assert!(prompts.last().unwrap().contains("Summary of the diff"));
+    }
+
+    #[test]
+    fn commit_rejected_in_read_only_mode() {
+        let repo = init_repo();
+        let provider = MockProvider::new("x");
+        let policy = policy(repo.path(), AutonomyLevel::ReadOnly);
+        let t = tool(&provider);
+        assert!(t
+            .pre_validate(&json!({"action": "commit", "message": "x"}), &policy)
+            .is_some());
+        assert!(t
+            .pre_validate(&json!({"action": "propose"}), &policy)
+            .is_none());
+    }
+
+    #[test]
+    fn chunk_diff_respects_budget() {
+        let diff: String = (0..10)
+            .map(|i| format!("diff --git a/f{i} b/f{i}\n{}", "+x\n".repeat(100)))
+            .collect();
+        let chunks = chunk_diff(&diff, 1000);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.len() <= 1000));
+        assert_eq!(chunks.concat(), diff);
+    }
+
+    #[test]
+    fn parse_name_status_uses_new_path_for_renames() {
+        let files = parse_name_status("M\tsrc/a.rs\nR100\told.rs\tnew.rs\n");
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].status, "R100");
+        assert_eq!(files[1].path, "new.rs");
+    }
+}
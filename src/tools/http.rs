@@ -3,13 +3,16 @@ use color_eyre::eyre::{eyre, Result};
 use futures_util::StreamExt;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde_json::json;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
-use super::traits::{Tool, ToolResult};
-use crate::providers::traits::{ChatMessage, ConversationMessage, Provider};
+use super::reader;
+use super::recipe::{self, RecipeDir};
+use super::traits::{Tool, ToolOutputKind, ToolResult};
+use crate::providers::traits::{ChatMessage, ConversationMessage, GenerationParams, Provider};
 use crate::security::SecurityPolicy;
 
 /// 响应体最大字节数（1 MiB）
@@ -22,6 +25,14 @@ const MAX_TIMEOUT_SECS: u64 = 120;
 const HTML_STRIP_MAX_BYTES: usize = 200 * 1024;
 /// mini-LLM 提取时输入内容的最大大小（150KB）
 const MINI_LLM_MAX_INPUT_BYTES: usize = 150 * 1024;
+/// read_url 最多跟随的重定向次数（每一跳都做 SSRF 检查）
+const READER_MAX_REDIRECTS: usize = 5;
+/// read_url 默认返回的正文字符数
+const READER_DEFAULT_MAX_CHARS: usize = 20_000;
+/// read_url 正文字符数上限
+const READER_MAX_CHARS_LIMIT: usize = 100_000;
+/// 默认 User-Agent：避免 GitHub 等 API 返回 403
+const USER_AGENT: &str = "RRClaw/1.0 (https://github.com/rrclaw/rrclaw)";
 
 /// HTTP 请求工具
 /// 支持智能响应处理：HTML 自动 strip，大响应 mini-LLM 提取
@@ -32,6 +43,8 @@ pub struct HttpRequestTool {
     model: String,
     /// HTML strip 后的阈值（bytes），0 = 禁用 strip
     strip_threshold_bytes: usize,
+    /// Recipe 查找目录（见 `recipe` 模块）
+    recipe_dirs: Vec<RecipeDir>,
 }
 
 impl HttpRequestTool {
@@ -45,8 +58,15 @@ impl HttpRequestTool {
             provider,
             model,
             strip_threshold_bytes,
+            recipe_dirs: Vec::new(),
         }
     }
+
+    /// 设置 Recipe 查找目录（按顺序查找，先到先得）
+    pub fn with_recipe_dirs(mut self, dirs: Vec<RecipeDir>) -> Self {
+        self.recipe_dirs = dirs;
+        self
+    }
 }
 
 #[async_trait]
@@ -64,7 +84,9 @@ impl Tool for HttpRequestTool {
          - JSON / 纯文本：直接返回，最大 1MB\
          - HTML 页面：自动 strip 标签/脚本/样式，保留文字内容，最大 200KB\
            - strip 后 ≤ 200KB：直接返回全部文字（适合文章、文档）\
-           - strip 后 > 200KB：若提供了 extract 参数则触发精准提取，否则截断并给出提示"
+           - strip 后 > 200KB：若提供了 extract 参数则触发精准提取，否则截断并给出提示\
+         需要多步鉴权的接口（登录换 token 再请求）：用 recipe 参数执行预先保存的流程，凭据不会出现在对话中。\
+         url 与 recipe 二选一。"
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
@@ -97,9 +119,18 @@ impl Tool for HttpRequestTool {
                 "extract": {
                     "type": "string",
                     "description": "（可选）当响应体较大时，指定要从中提取的目标信息。例如：\"当前股价和涨跌幅\"、\"文章正文\"、\"所有链接\"。仅在响应 strip 后仍超过 200KB 时触发 mini-LLM 提取；正常大小的响应直接返回全文，无需此参数。"
+                },
+                "recipe": {
+                    "type": "string",
+                    "description": "（可选）执行已保存的多步请求 recipe（如先登录取 token 再取数据），只返回最后一步结果。使用 recipe 时无需 url"
+                },
+                "vars": {
+                    "type": "object",
+                    "description": "（可选）recipe 模板变量，key-value 对象",
+                    "additionalProperties": {"type": "string"}
                 }
             },
-            "required": ["url"]
+            "required": []
         })
     }
 
@@ -109,52 +140,37 @@ impl Tool for HttpRequestTool {
             return Some("只读模式下不允许发起 HTTP 请求".to_string());
         }
 
-        // 2. 解析 URL
+        // 2. Recipe 模式：每一步的 URL 在执行时代入变量后再做 SSRF 检查
+        if args.get("recipe").is_some() {
+            return None;
+        }
+
+        // 3. 解析 URL + scheme + SSRF 检查
         let url_str = match args.get("url").and_then(|v| v.as_str()) {
             Some(u) if !u.is_empty() => u,
             _ => return Some("缺少 url 参数".to_string()),
         };
 
-        // 3. Scheme 检查：只允许 http/https
-        let url = match url::Url::parse(url_str) {
-            Ok(u) => u,
-            Err(_) => return Some(format!("无效的 URL: {}", url_str)),
-        };
-
-        let scheme = url.scheme();
-        if scheme != "http" && scheme != "https" {
-            return Some(format!(
-                "不支持的 URL scheme '{}'，只允许 http 或 https",
-                scheme
-            ));
-        }
-
-        // 4. SSRF 检查：阻止内网访问
-        // 使用 host() 获取 IpAddr，避免 IPv6 URL 带方括号的问题
-        let host_ip = match url.host() {
-            Some(url::Host::Ipv6(ip)) => Some(ip.to_string()),
-            Some(url::Host::Ipv4(ip)) => Some(ip.to_string()),
-            Some(url::Host::Domain(h)) => Some(h.to_string()),
-            None => None,
-        };
-
-        let host_str = host_ip.as_deref().unwrap_or("");
-
         // 实时读取配置文件中的 http_allowed_hosts（无需重启即生效）
         let http_allowed_hosts = crate::config::Config::get_http_allowed_hosts();
-
-        if let Some(reason) = check_ssrf_risk(host_str, &http_allowed_hosts) {
-            return Some(reason);
-        }
-
-        None
+        validate_url(url_str, &http_allowed_hosts)
     }
 
     async fn execute(
         &self,
         args: serde_json::Value,
-        _policy: &SecurityPolicy,
+        policy: &SecurityPolicy,
     ) -> Result<ToolResult> {
+        if args.get("recipe").is_some() {
+            return self.run_recipe(&args, policy).await;
+        }
+        self.request(args).await
+    }
+}
+
+impl HttpRequestTool {
+    /// 发起单个请求并按 Content-Type 处理响应（不做 SSRF 检查，由调用方负责）
+    async fn request(&self, args: serde_json::Value) -> Result<ToolResult> {
         let url_str = args
             .get("url")
             .and_then(|v| v.as_str())
@@ -191,7 +207,7 @@ impl Tool for HttpRequestTool {
         // 默认 User-Agent：避免 GitHub 等 API 返回 403
         header_map.insert(
             reqwest::header::USER_AGENT,
-            HeaderValue::from_static("RRClaw/1.0 (https://github.com/rrclaw/rrclaw)"),
+            HeaderValue::from_static(USER_AGENT),
         );
 
         if let Some(headers_obj) = args.get("headers").and_then(|v| v.as_object()) {
@@ -232,6 +248,7 @@ impl Tool for HttpRequestTool {
         );
 
         // 发送请求
+        let started = Instant::now();
         let response = match request_builder.send().await {
             Ok(r) => r,
             Err(e) => {
@@ -246,6 +263,7 @@ impl Tool for HttpRequestTool {
                     success: false,
                     output: String::new(),
                     error: Some(err_msg),
+                    duration_ms: Some(started.elapsed().as_millis() as u64),
                     ..Default::default()
                 });
             }
@@ -290,6 +308,9 @@ impl Tool for HttpRequestTool {
             }
         }
 
+        // 耗时只计网络往返（不含后续 HTML strip / mini-LLM 提取）
+        let duration_ms = started.elapsed().as_millis() as u64;
+
         // 尝试 UTF-8 解码，失败则显示字节数
         let body_len = body_bytes.len();
         let body_str = match String::from_utf8(body_bytes) {
@@ -306,6 +327,11 @@ impl Tool for HttpRequestTool {
             .unwrap_or_default();
 
         let is_html = content_type.contains("text/html");
+        let json_kind = json_output_kind(&content_type, &body_str, truncated);
+        let user_facing = match headline(&body_str, is_html) {
+            Some(headline) => format!("{} · {}", status_line, headline),
+            None => status_line.clone(),
+        };
 
         // HTML strip：去除所有标签，保留文字
         let skip_strip = self.strip_threshold_bytes == 0;
@@ -417,12 +443,240 @@ impl Tool for HttpRequestTool {
                 String::new()
             },
             error: if success { None } else { Some(output) },
+            kind: if success { json_kind } else { None },
+            duration_ms: Some(duration_ms),
+            user_facing: Some(user_facing),
             ..Default::default()
         })
     }
+
+    /// Recipe 模式：顺序执行多步请求，只返回最后一步的结果
+    ///
+    /// 中间响应与捕获的变量只留在本函数内，不进入对话历史；每一步都过 SSRF 检查。
+    /// 出错时报告失败的步骤与原因，捕获值、环境变量值一律替换为 `***`。
+    async fn run_recipe(
+        &self,
+        args: &serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        let name = args
+            .get("recipe")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let recipe = match recipe::load_recipe(&self.recipe_dirs, name) {
+            Ok(r) => r,
+            Err(e) => return Ok(failure(format!("{:#}", e))),
+        };
+
+        let mut vars: HashMap<String, String> = args
+            .get("vars")
+            .and_then(|v| v.as_object())
+            .map(|obj| {
+                obj.iter()
+                    .map(|(k, v)| (k.clone(), recipe::capture_text(v)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut secrets: Vec<String> = Vec::new();
+        let mut allowed_hosts = policy.http_allowed_hosts.clone();
+        allowed_hosts.extend(crate::config::Config::get_http_allowed_hosts());
+        let started = Instant::now();
+
+        let (last, intermediate) = recipe.steps.split_last().expect("load_recipe 保证非空");
+        for (index, step) in intermediate.iter().enumerate() {
+            let fail = |reason: String, secrets: &[String]| {
+                Ok(failure(format!(
+                    "recipe '{}' {} 失败: {}",
+                    name,
+                    step.label(index),
+                    recipe::scrub(&reason, secrets)
+                )))
+            };
+            let rendered = match step.render(&vars, &mut secrets) {
+                Ok(r) => r,
+                Err(reason) => return fail(reason, &secrets),
+            };
+            if let Some(reason) = validate_url(&rendered.url, &allowed_hosts) {
+                return fail(reason, &secrets);
+            }
+            let body = match send_step(&rendered, step.timeout_secs).await {
+                Ok(b) => b,
+                Err(reason) => return fail(reason, &secrets),
+            };
+            if step.capture.is_empty() {
+                continue;
+            }
+            let value: serde_json::Value = match serde_json::from_slice(&body) {
+                Ok(v) => v,
+                Err(_) => return fail("响应不是 JSON，无法捕获变量".to_string(), &secrets),
+            };
+            for (var, path) in &step.capture {
+                let Some(found) = recipe::json_path(&value, path) else {
+                    return fail(format!("响应中没有 {}（捕获 {}）", path, var), &secrets);
+                };
+                let text = recipe::capture_text(found);
+                secrets.push(text.clone());
+                vars.insert(var.clone(), text);
+            }
+        }
+
+        // 最后一步走普通请求流程（HTML strip、mini-LLM 提取、JSON 元数据）
+        let index = recipe.steps.len() - 1;
+        let label = last.label(index);
+        let rendered = match last.render(&vars, &mut secrets) {
+            Ok(r) => r,
+            Err(reason) => {
+                return Ok(failure(format!(
+                    "recipe '{}' {} 失败: {}",
+                    name,
+                    label,
+                    recipe::scrub(&reason, &secrets)
+                )))
+            }
+        };
+        if let Some(reason) = validate_url(&rendered.url, &allowed_hosts) {
+            return Ok(failure(format!(
+                "recipe '{}' {} 失败: {}",
+                name,
+                label,
+                recipe::scrub(&reason, &secrets)
+            )));
+        }
+        let mut final_args = json!({
+            "url": rendered.url,
+            "method": rendered.method,
+            "headers": rendered.headers,
+        });
+        if let Some(body) = rendered.body {
+            final_args["body"] = json!(body);
+        }
+        if let Some(timeout) = last.timeout_secs {
+            final_args["timeout_secs"] = json!(timeout);
+        }
+        if let Some(extract) = &last.extract {
+            final_args["extract"] = json!(extract);
+        }
+
+        let mut result = self.request(final_args).await?;
+        result.output = recipe::scrub(&result.output, &secrets);
+        result.error = result.error.map(|e| {
+            format!(
+                "recipe '{}' {} 失败: {}",
+                name,
+                label,
+                recipe::scrub(&e, &secrets)
+            )
+        });
+        result.duration_ms = Some(started.elapsed().as_millis() as u64);
+        Ok(result)
+    }
+}
+
+/// 只有错误信息的失败结果（Recipe 与 read_url 共用）
+fn failure(error: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        output: String::new(),
+        error: Some(error),
+        ..Default::default()
+    }
+}
+
+/// 执行 Recipe 的中间步骤，返回响应体（非 2xx 视为失败）
+///
+/// 错误信息不含 reqwest 原始错误（其中带完整 URL，可能包含代入的凭据）。
+async fn send_step(
+    step: &recipe::RenderedStep,
+    timeout_secs: Option<u64>,
+) -> std::result::Result<Vec<u8>, String> {
+    let method = reqwest::Method::from_bytes(step.method.as_bytes())
+        .map_err(|_| format!("不支持的 HTTP 方法: {}", step.method))?;
+    let timeout = timeout_secs
+        .unwrap_or(DEFAULT_TIMEOUT_SECS)
+        .min(MAX_TIMEOUT_SECS);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| format!("构建 HTTP client 失败: {}", e))?;
+
+    let mut request = client
+        .request(method, &step.url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT);
+    for (key, value) in &step.headers {
+        request = request.header(key.as_str(), value.as_str());
+    }
+    if let Some(body) = &step.body {
+        request = request.body(body.clone());
+    }
+
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            format!("请求超时（{}s）", timeout)
+        } else if e.is_connect() {
+            "连接失败".to_string()
+        } else {
+            "请求失败".to_string()
+        }
+    })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "HTTP {} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("")
+        ));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|_| "读取响应体失败".to_string())?;
+    if body.len() > MAX_RESPONSE_BYTES {
+        return Err(format!("响应体超过 {} 字节", MAX_RESPONSE_BYTES));
+    }
+    Ok(body.to_vec())
+}
+
+/// JSON 响应体解析为结构化元数据（被截断或解析失败时返回 None）
+fn json_output_kind(content_type: &str, body: &str, truncated: bool) -> Option<ToolOutputKind> {
+    if truncated || !content_type.contains("json") {
+        return None;
+    }
+    serde_json::from_str(body)
+        .ok()
+        .map(|value| ToolOutputKind::Json { value })
 }
 
 /// mini-LLM 提取函数
+/// 响应摘要标题：HTML 取 `<title>`，其他取首个含文字的行（截断到 80 字符）
+fn headline(body: &str, is_html: bool) -> Option<String> {
+    static TITLE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+    let text = if is_html {
+        let title = TITLE.get_or_init(|| {
+            regex::Regex::new(r"(?is)<title[^>]*>(.*?)</title>").expect("内置正则合法")
+        });
+        title
+            .captures(body)?
+            .get(1)?
+            .as_str()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        body.lines()
+            .map(str::trim)
+            .find(|l| l.chars().any(char::is_alphanumeric))?
+            .to_string()
+    };
+    if text.is_empty() {
+        return None;
+    }
+    Some(match text.char_indices().nth(80) {
+        Some((idx, _)) => format!("{}…", &text[..idx]),
+        None => text,
+    })
+}
+
 async fn mini_extract(
     content: &str,
     hint: &str,
@@ -444,19 +698,303 @@ async fn mini_extract(
                       如果找不到，返回\"未找到: {原因}\"。"
                 .to_string(),
             reasoning_content: None,
+            turn: 0,
         }),
         ConversationMessage::Chat(ChatMessage {
             role: "user".to_string(),
             content: format!("从以下内容中提取：{}\n\n---\n{}", hint, content_excerpt),
             reasoning_content: None,
+            turn: 0,
         }),
     ];
 
-    let resp = provider.chat_with_tools(&messages, &[], model, 0.0).await?;
+    let resp = provider
+        .chat_with_tools(
+            &messages,
+            &[],
+            model,
+            GenerationParams::with_temperature(0.0),
+        )
+        .await?;
 
     Ok(resp.text.unwrap_or_else(|| "（提取结果为空）".to_string()))
 }
 
+/// 阅读模式工具：抓取网页并抽取正文，直接用于总结
+///
+/// 与 `http_request` 的区别：只做 GET，HTML 经 `reader::extract_article` 去掉导航、页脚、
+/// 侧栏、广告等页面框架；重定向每一跳先过 SSRF 检查再跟随（文章链接常见 http→https 跳转）。
+pub struct ReadUrlTool;
+
+#[async_trait]
+impl Tool for ReadUrlTool {
+    fn name(&self) -> &str {
+        "read_url"
+    }
+
+    fn description(&self) -> &str {
+        "阅读模式读取网页：抓取 URL，去掉导航、页脚、侧栏、广告、Cookie 提示等页面框架，\
+         返回标题和干净的正文文本，适合\"读一下这篇文章并总结\"。\
+         只发 GET 请求；调用 JSON 接口、需要自定义 header 或查看原始 HTML 时用 http_request。\
+         禁止访问内网/localhost/云元数据接口（SSRF 防护，重定向目标同样检查），响应最大 1MB。"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "description": "网页 URL，必须以 http:// 或 https:// 开头"
+                },
+                "max_chars": {
+                    "type": "integer",
+                    "description": "正文最多返回的字符数，默认 20000，最大 100000"
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    fn pre_validate(&self, args: &serde_json::Value, policy: &SecurityPolicy) -> Option<String> {
+        // 与 http_request 一致：ReadOnly 模式不发起任何网络请求
+        if !policy.allows_execution() {
+            return Some("只读模式下不允许读取网页".to_string());
+        }
+        match args.get("url").and_then(|v| v.as_str()) {
+            Some(url) if !url.is_empty() => {
+                validate_url(url, &crate::config::Config::get_http_allowed_hosts())
+            }
+            _ => Some("缺少 url 参数".to_string()),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        let url = args
+            .get("url")
+            .and_then(|v| v.as_str())
+            .filter(|u| !u.is_empty())
+            .ok_or_else(|| eyre!("缺少 url 参数"))?;
+        let max_chars = args
+            .get("max_chars")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(READER_DEFAULT_MAX_CHARS)
+            .clamp(1, READER_MAX_CHARS_LIMIT);
+
+        let mut allowed_hosts = policy.http_allowed_hosts.clone();
+        allowed_hosts.extend(crate::config::Config::get_http_allowed_hosts());
+        if let Some(reason) = validate_url(url, &allowed_hosts) {
+            return Ok(failure(reason));
+        }
+
+        debug!("read_url: GET {}", url);
+        let started = Instant::now();
+        let page = fetch_page(url, allowed_hosts).await;
+        let duration_ms = Some(started.elapsed().as_millis() as u64);
+        let page = match page {
+            Ok(page) => page,
+            Err(reason) => {
+                return Ok(ToolResult {
+                    duration_ms,
+                    ..failure(reason)
+                })
+            }
+        };
+
+        let article = match page.article() {
+            Ok(article) => article,
+            Err(reason) => {
+                return Ok(ToolResult {
+                    duration_ms,
+                    ..failure(reason)
+                })
+            }
+        };
+
+        let total_chars = article.text.chars().count();
+        let mut output = String::new();
+        if let Some(title) = &article.title {
+            output.push_str(&format!("标题: {}\n", title));
+        }
+        output.push_str(&format!("来源: {}\n\n", page.final_url));
+        match article.text.char_indices().nth(max_chars) {
+            Some((cut, _)) => output.push_str(&format!(
+                "{}\n\n[正文已截断：显示前 {} 字符，共 {} 字符]",
+                &article.text[..cut],
+                max_chars,
+                total_chars
+            )),
+            None => output.push_str(&article.text),
+        }
+        if page.truncated {
+            output.push_str(&format!(
+                "\n\n[页面超过 {} 字节，只解析了前面部分]",
+                MAX_RESPONSE_BYTES
+            ));
+        }
+
+        let label = article.title.as_deref().unwrap_or(page.final_url.as_str());
+        Ok(ToolResult {
+            success: true,
+            output,
+            duration_ms,
+            user_facing: Some(format!("{} · {} 字", label, total_chars)),
+            ..Default::default()
+        })
+    }
+}
+
+/// `read_url` 抓取到的页面
+struct FetchedPage {
+    /// 跟随重定向后的地址
+    final_url: String,
+    content_type: String,
+    body: String,
+    /// 响应体超过 `MAX_RESPONSE_BYTES` 被截断
+    truncated: bool,
+}
+
+impl FetchedPage {
+    /// 按 Content-Type 取正文：HTML 走阅读模式抽取，其他文本原样返回
+    fn article(&self) -> std::result::Result<reader::Article, String> {
+        let looks_like_html =
+            self.content_type.is_empty() && self.body.trim_start().starts_with('<');
+        if self.content_type.contains("html") || looks_like_html {
+            let article = reader::extract_article(&self.body);
+            if article.text.is_empty() {
+                return Err("页面没有可提取的正文（可能需要 JavaScript 渲染），\
+                            可用 http_request 查看原始 HTML"
+                    .to_string());
+            }
+            return Ok(article);
+        }
+        if self.content_type.starts_with("text/") {
+            return Ok(reader::Article {
+                title: None,
+                text: self.body.trim().to_string(),
+            });
+        }
+        Err(format!(
+            "不是网页（Content-Type: {}），请改用 http_request",
+            self.content_type
+        ))
+    }
+}
+
+/// GET 页面：重定向逐跳做 SSRF 检查，非 2xx 视为失败，响应体最多读取 `MAX_RESPONSE_BYTES`
+async fn fetch_page(
+    url: &str,
+    allowed_hosts: Vec<String>,
+) -> std::result::Result<FetchedPage, String> {
+    let redirect = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= READER_MAX_REDIRECTS {
+            attempt.error(format!("重定向超过 {} 次", READER_MAX_REDIRECTS))
+        } else if let Some(reason) = validate_url(attempt.url().as_str(), &allowed_hosts) {
+            attempt.error(reason)
+        } else {
+            attempt.follow()
+        }
+    });
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+        .redirect(redirect)
+        .build()
+        .map_err(|e| format!("构建 HTTP client 失败: {}", e))?;
+
+    let response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .header(
+            reqwest::header::ACCEPT,
+            "text/html,text/plain;q=0.9,*/*;q=0.5",
+        )
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_redirect() {
+                // 重定向策略拒绝的原因在 source 里
+                let reason = std::error::Error::source(&e)
+                    .map(|source| source.to_string())
+                    .unwrap_or_else(|| e.to_string());
+                format!("重定向被拒绝: {}", reason)
+            } else if e.is_timeout() {
+                format!("请求超时（{}s）: {}", DEFAULT_TIMEOUT_SECS, e)
+            } else if e.is_connect() {
+                format!("连接失败: {}", e)
+            } else {
+                format!("请求失败: {}", e)
+            }
+        })?;
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "HTTP {} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("")
+        ));
+    }
+    let final_url = response.url().to_string();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let mut body = Vec::new();
+    let mut truncated = false;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let data = chunk.map_err(|e| format!("读取响应体失败: {}", e))?;
+        if body.len() + data.len() > MAX_RESPONSE_BYTES {
+            body.extend_from_slice(&data[..MAX_RESPONSE_BYTES - body.len()]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&data);
+    }
+
+    Ok(FetchedPage {
+        final_url,
+        content_type,
+        body: String::from_utf8_lossy(&body).into_owned(),
+        truncated,
+    })
+}
+
+/// 校验 URL：只允许 http/https，且 host 不能有 SSRF 风险
+/// 返回 Some(原因) 表示拒绝
+pub(crate) fn validate_url(url_str: &str, http_allowed_hosts: &[String]) -> Option<String> {
+    let url = match url::Url::parse(url_str) {
+        Ok(u) => u,
+        Err(_) => return Some(format!("无效的 URL: {}", url_str)),
+    };
+
+    let scheme = url.scheme();
+    if scheme != "http" && scheme != "https" {
+        return Some(format!(
+            "不支持的 URL scheme '{}'，只允许 http 或 https",
+            scheme
+        ));
+    }
+
+    // 使用 host() 获取 IpAddr，避免 IPv6 URL 带方括号的问题
+    let host = match url.host() {
+        Some(url::Host::Ipv6(ip)) => ip.to_string(),
+        Some(url::Host::Ipv4(ip)) => ip.to_string(),
+        Some(url::Host::Domain(h)) => h.to_string(),
+        None => String::new(),
+    };
+
+    check_ssrf_risk(&host, http_allowed_hosts)
+}
+
 /// 检查 host 是否有 SSRF 风险
 /// 返回 Some(原因) 表示有风险，None 表示安全
 fn check_ssrf_risk(host: &str, http_allowed_hosts: &[String]) -> Option<String> {
@@ -751,14 +1289,55 @@ mod tests {
         let tool = HttpRequestTool::new(None, "test-model".to_string(), 200 * 1024);
         let spec = tool.spec();
         assert_eq!(spec.name, "http_request");
-        assert!(spec.parameters["required"]
-            .as_array()
-            .unwrap()
-            .contains(&serde_json::json!("url")));
+        // url 与 recipe 二选一，缺失时由 pre_validate 拒绝
+        assert!(spec.parameters["properties"]["url"].is_object());
+        assert!(spec.parameters["properties"]["recipe"].is_object());
+    }
+
+    // ─── JSON 元数据测试 ───────────────────────────────────────────────
+
+    #[test]
+    fn json_kind_from_json_content_type() {
+        let kind = json_output_kind(
+            "content-type: application/json; charset=utf-8",
+            r#"{"a": 1}"#,
+            false,
+        );
+        assert_eq!(
+            kind,
+            Some(ToolOutputKind::Json {
+                value: serde_json::json!({"a": 1})
+            })
+        );
+    }
+
+    #[test]
+    fn json_kind_skipped_for_non_json() {
+        assert!(json_output_kind("content-type: text/plain", r#"{"a": 1}"#, false).is_none());
+    }
+
+    #[test]
+    fn json_kind_skipped_when_truncated_or_invalid() {
+        assert!(json_output_kind("content-type: application/json", r#"{"a": 1}"#, true).is_none());
+        assert!(json_output_kind("content-type: application/json", "{broken", false).is_none());
     }
 
     // ─── HTML strip 测试 ───────────────────────────────────────────────
 
+    #[test]
+    fn headline_uses_html_title_or_first_text_line() {
+        assert_eq!(
+            headline("<html><title>\n  Example  Domain </title></html>", true).as_deref(),
+            Some("Example Domain")
+        );
+        assert_eq!(
+            headline("{\n  \"ok\": true\n}", false).as_deref(),
+            Some("\"ok\": true")
+        );
+        assert_eq!(headline("", false), None);
+        assert!(headline(&"x".repeat(200), false).unwrap().ends_with('…'));
+    }
+
     #[test]
     fn html_strip_removes_tags() {
         let html = "<html><head><script>var x=1</script></head><body><p>Hello</p></body></html>";
@@ -767,4 +1346,328 @@ mod tests {
         assert!(!stripped.contains("<script>"));
         assert!(!stripped.contains("<p>"));
     }
+
+    // ─── Recipe 测试（本地两步鉴权服务） ──────────────────────────────
+
+    const RECIPE_TOKEN: &str = "tok-7f3a9c2e5b1d";
+    const RECIPE_PASSWORD: &str = "open-sesame-42";
+
+    /// POST /auth 校验密码后返回 access_token；GET /data 校验 Bearer token
+    fn recipe_route(request: &str) -> (&'static str, String) {
+        let first_line = request.lines().next().unwrap_or("");
+        let lower = request.to_lowercase();
+        if first_line.starts_with("POST /auth ") {
+            if request.contains(&format!(r#""password":"{}""#, RECIPE_PASSWORD)) {
+                (
+                    "200 OK",
+                    format!(r#"{{"access_token":"{}"}}"#, RECIPE_TOKEN),
+                )
+            } else {
+                ("401 Unauthorized", r#"{"error":"bad credentials"}"#.into())
+            }
+        } else if first_line.starts_with("GET /data ") {
+            if lower.contains(&format!("authorization: bearer {}", RECIPE_TOKEN)) {
+                ("200 OK", r#"{"items":[1,2,3]}"#.into())
+            } else {
+                ("401 Unauthorized", "{}".into())
+            }
+        } else {
+            ("404 Not Found", "{}".into())
+        }
+    }
+
+    async fn spawn_recipe_server() -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    // 读完 headers + Content-Length 指定的 body
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    loop {
+                        let n = stream.read(&mut chunk).await.unwrap_or(0);
+                        if n == 0 {
+                            break;
+                        }
+                        buf.extend_from_slice(&chunk[..n]);
+                        let text = String::from_utf8_lossy(&buf).to_string();
+                        if let Some(end) = text.find("\r\n\r\n") {
+                            let content_length = text[..end]
+                                .lines()
+                                .find_map(|l| {
+                                    l.to_lowercase()
+                                        .strip_prefix("content-length:")
+                                        .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                                })
+                                .unwrap_or(0);
+                            if buf.len() >= end + 4 + content_length {
+                                break;
+                            }
+                        }
+                    }
+                    let (status, body) = recipe_route(&String::from_utf8_lossy(&buf));
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        addr
+    }
+
+    /// 写入两步 recipe：登录（密码取自 `password_expr`）→ 取数据
+    fn write_login_recipe(dir: &std::path::Path, addr: std::net::SocketAddr, password_expr: &str) {
+        let recipe = serde_json::json!({
+            "description": "login then fetch",
+            "steps": [
+                {
+                    "name": "login",
+                    "method": "POST",
+                    "url": format!("http://{}/auth", addr),
+                    "headers": {"Content-Type": "application/json"},
+                    "body": format!(r#"{{"user":"${{user}}","password":"{}"}}"#, password_expr),
+                    "capture": {"token": "$.access_token"}
+                },
+                {
+                    "name": "fetch",
+                    "url": format!("http://{}/data", addr),
+                    "headers": {"Authorization": "Bearer ${token}"}
+                }
+            ]
+        });
+        std::fs::write(dir.join("report.json"), recipe.to_string()).unwrap();
+    }
+
+    fn recipe_tool(dir: &std::path::Path) -> HttpRequestTool {
+        HttpRequestTool::new(None, "test-model".to_string(), 200 * 1024)
+            .with_recipe_dirs(vec![RecipeDir::Recipes(dir.to_path_buf())])
+    }
+
+    fn loopback_policy() -> SecurityPolicy {
+        SecurityPolicy {
+            http_allowed_hosts: vec!["127.0.0.1".to_string()],
+            ..full_policy()
+        }
+    }
+
+    #[tokio::test]
+    async fn recipe_runs_login_then_fetch_without_leaking_token() {
+        let addr = spawn_recipe_server().await;
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("RRCLAW_TEST_RECIPE_PASSWORD", RECIPE_PASSWORD);
+        write_login_recipe(dir.path(), addr, "${env:RRCLAW_TEST_RECIPE_PASSWORD}");
+
+        let args = serde_json::json!({"recipe": "report", "vars": {"user": "alice"}});
+        let tool = recipe_tool(dir.path());
+        assert!(tool.pre_validate(&args, &loopback_policy()).is_none());
+        let result = tool.execute(args, &loopback_policy()).await.unwrap();
+
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.output.contains(r#"{"items":[1,2,3]}"#));
+        assert!(!result.output.contains(RECIPE_TOKEN));
+        assert!(!result.output.contains(RECIPE_PASSWORD));
+    }
+
+    #[tokio::test]
+    async fn recipe_failure_names_step_without_secrets() {
+        let addr = spawn_recipe_server().await;
+        let dir = tempfile::tempdir().unwrap();
+        write_login_recipe(dir.path(), addr, "${password}");
+
+        let args = serde_json::json!({
+            "recipe": "report",
+            "vars": {"user": "alice", "password": "wrong-password-123"}
+        });
+        let result = recipe_tool(dir.path())
+            .execute(args, &loopback_policy())
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        let error = result.error.unwrap();
+        assert!(error.contains("step 1 (login)"), "{}", error);
+        assert!(error.contains("401"));
+        assert!(!error.contains("wrong-password-123"));
+    }
+
+    #[tokio::test]
+    async fn recipe_steps_must_pass_ssrf_allowlist() {
+        let addr = spawn_recipe_server().await;
+        let dir = tempfile::tempdir().unwrap();
+        write_login_recipe(dir.path(), addr, "${password}");
+
+        let args = serde_json::json!({
+            "recipe": "report",
+            "vars": {"user": "alice", "password": RECIPE_PASSWORD}
+        });
+        let result = recipe_tool(dir.path())
+            .execute(args, &full_policy())
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        let error = result.error.unwrap();
+        assert!(error.contains("step 1 (login)"));
+        assert!(error.contains("SSRF"));
+    }
+
+    #[tokio::test]
+    async fn unknown_recipe_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = recipe_tool(dir.path())
+            .execute(serde_json::json!({"recipe": "missing"}), &loopback_policy())
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("missing"));
+    }
+
+    // ─── read_url 测试（本地页面服务） ─────────────────────────────────
+
+    /// GET /article 返回夹具页面；/moved 302 到 /article；/meta 302 到云元数据地址；/json 返回 JSON
+    async fn spawn_page_server() -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let page = std::fs::read_to_string(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/reader/article.html"),
+        )
+        .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let page = page.clone();
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    while !String::from_utf8_lossy(&buf).contains("\r\n\r\n") {
+                        let n = stream.read(&mut chunk).await.unwrap_or(0);
+                        if n == 0 {
+                            break;
+                        }
+                        buf.extend_from_slice(&chunk[..n]);
+                    }
+                    let request = String::from_utf8_lossy(&buf).to_string();
+                    let (status, extra, body) = if request.starts_with("GET /article ") {
+                        ("200 OK", "Content-Type: text/html; charset=utf-8\r\n", page)
+                    } else if request.starts_with("GET /moved ") {
+                        ("302 Found", "Location: /article\r\n", String::new())
+                    } else if request.starts_with("GET /meta ") {
+                        (
+                            "302 Found",
+                            "Location: http://169.254.169.254/latest/meta-data/\r\n",
+                            String::new(),
+                        )
+                    } else if request.starts_with("GET /json ") {
+                        (
+                            "200 OK",
+                            "Content-Type: application/json\r\n",
+                            "{}".to_string(),
+                        )
+                    } else {
+                        ("404 Not Found", "", String::new())
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        extra,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn read_url(
+        addr: std::net::SocketAddr,
+        path: &str,
+        extra: serde_json::Value,
+    ) -> ToolResult {
+        let mut args = serde_json::json!({"url": format!("http://{}{}", addr, path)});
+        if let (Some(args), Some(extra)) = (args.as_object_mut(), extra.as_object()) {
+            args.extend(extra.clone());
+        }
+        ReadUrlTool.execute(args, &loopback_policy()).await.unwrap()
+    }
+
+    #[test]
+    fn read_url_pre_validate_matches_http_request() {
+        let args = serde_json::json!({"url": "https://example.com/post"});
+        assert!(ReadUrlTool.pre_validate(&args, &full_policy()).is_none());
+        assert!(ReadUrlTool
+            .pre_validate(&args, &readonly_policy())
+            .unwrap()
+            .contains("只读"));
+        let local = serde_json::json!({"url": "http://localhost/admin"});
+        assert!(ReadUrlTool
+            .pre_validate(&local, &full_policy())
+            .unwrap()
+            .contains("SSRF"));
+        assert!(ReadUrlTool
+            .pre_validate(&serde_json::json!({}), &full_policy())
+            .unwrap()
+            .contains("url"));
+    }
+
+    #[tokio::test]
+    async fn read_url_follows_redirect_and_returns_main_content() {
+        let addr = spawn_page_server().await;
+        let result = read_url(addr, "/moved", serde_json::json!({})).await;
+
+        assert!(result.success, "{:?}", result.error);
+        let output = &result.output;
+        assert!(output.starts_with("标题: Why Rust ownership makes concurrency boring\n"));
+        assert!(output.contains(&format!("来源: http://{}/article", addr)));
+        assert!(output.contains("Data races are a compile-time error"));
+        assert!(!output.contains("We use cookies"));
+        assert!(!output.contains("trackPageView"));
+        assert!(result
+            .user_facing
+            .unwrap()
+            .starts_with("Why Rust ownership makes concurrency boring · "));
+    }
+
+    #[tokio::test]
+    async fn read_url_truncates_to_max_chars() {
+        let addr = spawn_page_server().await;
+        let result = read_url(addr, "/article", serde_json::json!({"max_chars": 40})).await;
+
+        assert!(result.success);
+        assert!(result.output.contains("[正文已截断：显示前 40 字符"));
+        assert!(!result.output.contains("borrow checker is a conversation"));
+    }
+
+    #[tokio::test]
+    async fn read_url_refuses_redirect_into_private_network() {
+        let addr = spawn_page_server().await;
+        let result = read_url(addr, "/meta", serde_json::json!({})).await;
+
+        assert!(!result.success);
+        let error = result.error.unwrap();
+        assert!(error.contains("重定向被拒绝"), "{}", error);
+        assert!(error.contains("SSRF"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn read_url_reports_status_and_non_text_content() {
+        let addr = spawn_page_server().await;
+        let missing = read_url(addr, "/nope", serde_json::json!({})).await;
+        assert!(!missing.success);
+        assert!(missing.error.unwrap().contains("404"));
+
+        let json = read_url(addr, "/json", serde_json::json!({})).await;
+        assert!(!json.success);
+        assert!(json.error.unwrap().contains("http_request"));
+    }
 }
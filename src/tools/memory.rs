@@ -4,7 +4,7 @@ use color_eyre::eyre::Result;
 use serde_json::json;
 use std::sync::Arc;
 
-use super::traits::{Tool, ToolResult};
+use super::traits::{Tool, ToolResult, ToolRisk};
 use crate::memory::{Memory, MemoryCategory};
 use crate::security::SecurityPolicy;
 
@@ -145,6 +145,15 @@ impl Tool for MemoryRecallTool {
         })
     }
 
+    /// 记忆会被本轮其他工具修改，检索结果不缓存
+    fn cacheable(&self, _args: &serde_json::Value) -> bool {
+        false
+    }
+
+    fn risk(&self) -> ToolRisk {
+        ToolRisk::Read
+    }
+
     async fn execute(
         &self,
         args: serde_json::Value,
@@ -1,15 +1,24 @@
 pub mod config;
+#[cfg(feature = "documents")]
+pub mod document;
 pub mod file;
 pub mod git;
+pub mod git_commit;
 pub mod http;
 pub mod memory;
+pub mod process;
+pub mod reader;
+pub mod recipe;
 pub mod routine;
+pub mod search;
+pub mod secret;
 pub mod self_info;
 pub mod shell;
 pub mod skill;
 pub mod traits;
+pub mod undo;
 
-pub use traits::{Tool, ToolResult};
+pub use traits::{Tool, ToolOutputKind, ToolResult, ToolRisk};
 
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -20,11 +29,15 @@ use crate::providers::Provider;
 use crate::routines::RoutineEngine;
 use crate::skills::SkillMeta;
 use config::ConfigTool;
-use file::{FileReadTool, FileWriteTool};
+use file::{FileReadTool, FileWriteTool, GrepTool};
 use git::GitTool;
-use http::HttpRequestTool;
+use git_commit::GitCommitTool;
+use http::{HttpRequestTool, ReadUrlTool};
 use memory::{MemoryForgetTool, MemoryRecallTool, MemoryStoreTool};
+use recipe::RecipeDir;
 use routine::RoutineTool;
+use search::WebSearchTool;
+use secret::{SecretStore, SecretTool};
 use self_info::SelfInfoTool;
 use shell::ShellTool;
 use skill::SkillTool;
@@ -42,11 +55,25 @@ pub fn create_tools(
     routine_engine: Option<Arc<RoutineEngine>>,
 ) -> Vec<Box<dyn Tool>> {
     let strip_threshold_bytes = app_config.security.http_strip_threshold_kb * 1024;
+    // Recipes 在配置根目录；XDG 布局下 data 不在配置目录之下，撤销快照按传入的 data_dir 定位
+    let paths = crate::config::RrclawPaths::from_config_file(&config_path);
+    let undo_dir = data_dir.join("undo");
+    let secret_store = SecretStore::system(&data_dir);
+    // Recipe 查找顺序：<配置根目录>/recipes/ → 各文件系统 Skill 目录
+    let recipe_dirs: Vec<RecipeDir> = std::iter::once(RecipeDir::Recipes(paths.recipes_dir()))
+        .chain(
+            skills
+                .iter()
+                .filter_map(|s| s.path.clone())
+                .map(RecipeDir::Skill),
+        )
+        .collect();
 
     let mut tools: Vec<Box<dyn Tool>> = vec![
         Box::new(ShellTool),
         Box::new(FileReadTool),
-        Box::new(FileWriteTool),
+        Box::new(GrepTool),
+        Box::new(FileWriteTool::new().with_undo_dir(undo_dir)),
         Box::new(ConfigTool),
         Box::new(SelfInfoTool::new(
             app_config.clone(),
@@ -56,15 +83,27 @@ pub fn create_tools(
         )),
         Box::new(SkillTool::new(skills)),
         Box::new(GitTool),
+        Box::new(GitCommitTool::new(
+            Arc::clone(&provider),
+            app_config.default.model.clone(),
+        )),
         Box::new(MemoryStoreTool::new(memory.clone())),
         Box::new(MemoryRecallTool::new(memory.clone())),
         Box::new(MemoryForgetTool::new(memory)),
-        Box::new(HttpRequestTool::new(
-            Some(Arc::clone(&provider)),
-            app_config.default.model.clone(),
-            strip_threshold_bytes,
-        )),
+        Box::new(
+            HttpRequestTool::new(
+                Some(Arc::clone(&provider)),
+                app_config.default.model.clone(),
+                strip_threshold_bytes,
+            )
+            .with_recipe_dirs(recipe_dirs),
+        ),
+        Box::new(ReadUrlTool),
+        Box::new(WebSearchTool::new(app_config.search.clone())),
+        Box::new(SecretTool::new(secret_store)),
     ];
+    #[cfg(feature = "documents")]
+    tools.push(Box::new(file::DocumentReadTool));
     if let Some(engine) = routine_engine {
         tools.push(Box::new(RoutineTool::new(
             engine,
@@ -0,0 +1,203 @@
+//! Shell 子进程登记表
+//!
+//! ShellTool 启动的每个子进程都会在这里登记（编号、PID、命令、启动时间），
+//! 执行结束时自动移除。REPL 通过 `/ps` 查看仍在运行的进程，`/kill <id>` 终止其中之一，
+//! 用于中止卡住的 `npm install`、误启动的 dev server 等，而不必等 120 秒超时。
+//!
+//! 子进程以独立进程组启动（unix），终止时向整个进程组发送 SIGKILL，
+//! 避免 `sh -c` 派生的孙进程残留。
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 登记中的子进程快照（用于 `/ps` 展示）
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    /// 登记表内编号（`/kill <id>` 使用）
+    pub id: u64,
+    pub pid: u32,
+    pub command: String,
+    pub started_at: Instant,
+}
+
+impl ProcessInfo {
+    /// 已运行时长
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+/// 子进程登记表
+///
+/// 默认使用进程级全局实例（`ProcessRegistry::global()`），ShellTool 与 REPL 共享；
+/// 测试可构造独立实例避免互相干扰。
+#[derive(Debug, Default)]
+pub struct ProcessRegistry {
+    next_id: AtomicU64,
+    entries: Mutex<BTreeMap<u64, ProcessInfo>>,
+}
+
+impl ProcessRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 进程级共享实例
+    pub fn global() -> Arc<ProcessRegistry> {
+        static GLOBAL: OnceLock<Arc<ProcessRegistry>> = OnceLock::new();
+        GLOBAL
+            .get_or_init(|| Arc::new(ProcessRegistry::new()))
+            .clone()
+    }
+
+    /// 登记子进程，返回的守卫 drop 时自动移除登记
+    pub fn register(self: &Arc<Self>, pid: u32, command: &str) -> TrackedProcess {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.entries.lock().unwrap().insert(
+            id,
+            ProcessInfo {
+                id,
+                pid,
+                command: command.to_string(),
+                started_at: Instant::now(),
+            },
+        );
+        TrackedProcess {
+            registry: Arc::clone(self),
+            id,
+        }
+    }
+
+    /// 移除登记（不终止进程）
+    pub fn remove(&self, id: u64) -> Option<ProcessInfo> {
+        self.entries.lock().unwrap().remove(&id)
+    }
+
+    /// 按编号列出仍在登记中的子进程
+    pub fn list(&self) -> Vec<ProcessInfo> {
+        self.entries.lock().unwrap().values().cloned().collect()
+    }
+
+    /// 终止指定编号的子进程（含其进程组），返回被终止进程的信息
+    ///
+    /// 登记在进程真正退出、ShellTool 收到结果后才移除，这里不主动移除。
+    pub fn kill(&self, id: u64) -> Option<ProcessInfo> {
+        let info = self.entries.lock().unwrap().get(&id).cloned()?;
+        kill_process_group(info.pid);
+        Some(info)
+    }
+}
+
+/// 登记守卫：drop 时从登记表移除
+pub struct TrackedProcess {
+    registry: Arc<ProcessRegistry>,
+    id: u64,
+}
+
+impl TrackedProcess {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// 终止该子进程（超时等场景）
+    pub fn kill(&self) {
+        self.registry.kill(self.id);
+    }
+}
+
+impl Drop for TrackedProcess {
+    fn drop(&mut self) {
+        self.registry.remove(self.id);
+    }
+}
+
+/// 向子进程所在进程组发送 SIGKILL（子进程以 `process_group(0)` 启动，组 ID 即 PID）
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    // SAFETY: kill(2) 只发送信号，不涉及内存安全；负 PID 表示整个进程组
+    unsafe {
+        libc::kill(-(pid as i32), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(pid: u32) {
+    let _ = std::process::Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .output();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::process::Command;
+
+    fn spawn_sleep() -> tokio::process::Child {
+        Command::new("sh")
+            .arg("-c")
+            .arg("sleep 30")
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn register_lists_and_guard_drop_removes() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let child = spawn_sleep();
+        let pid = child.id().unwrap();
+
+        let tracked = registry.register(pid, "sleep 30");
+        let listed = registry.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, tracked.id());
+        assert_eq!(listed[0].pid, pid);
+        assert_eq!(listed[0].command, "sleep 30");
+
+        drop(tracked);
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ids_are_unique_and_remove_is_idempotent() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let a = registry.register(1, "a");
+        let b = registry.register(2, "b");
+        assert_ne!(a.id(), b.id());
+
+        assert!(registry.remove(a.id()).is_some());
+        assert!(registry.remove(a.id()).is_none());
+        assert_eq!(registry.list().len(), 1);
+        // 守卫 drop 时重复移除不报错
+        drop(a);
+        assert_eq!(registry.list().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn kill_terminates_sleeping_child() {
+        let registry = Arc::new(ProcessRegistry::new());
+        let mut child = spawn_sleep();
+        let tracked = registry.register(child.id().unwrap(), "sleep 30");
+
+        let killed = registry.kill(tracked.id()).expect("process registered");
+        assert_eq!(killed.command, "sleep 30");
+
+        let status = tokio::time::timeout(Duration::from_secs(5), child.wait())
+            .await
+            .expect("child should exit after kill")
+            .unwrap();
+        assert!(!status.success());
+
+        drop(tracked);
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn kill_unknown_id_returns_none() {
+        let registry = Arc::new(ProcessRegistry::new());
+        assert!(registry.kill(42).is_none());
+    }
+}
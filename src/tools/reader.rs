@@ -0,0 +1,443 @@
+//! 阅读模式抽取：把网页 HTML 精简为可直接总结的正文
+//!
+//! 供 `read_url` 工具使用。不做完整 DOM 解析，按标签扫描：
+//! 1. 去掉 script / style / 注释等不可见内容
+//! 2. 选正文容器：最长的 `<article>` → `<main>`（或 `role="main"`）→ `<body>`
+//! 3. 在容器内去掉导航、页脚、侧栏、表单，以及 class / id / role 表明是菜单、
+//!    Cookie 提示、分享按钮、评论、相关推荐、广告的元素
+//! 4. html2text 转纯文本（不保留链接脚注），合并多余空行
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// html2text 折行宽度
+const TEXT_WIDTH: usize = 120;
+
+/// `<article>` 正文少于该字符数时视为摘要卡片，改用更大的容器
+const MIN_ARTICLE_CHARS: usize = 200;
+
+/// 整个元素都不可见的标签（连同内容一起删除）
+const INVISIBLE_TAGS: &[&str] = &["script", "style", "noscript", "template", "svg", "iframe"];
+
+/// 正文容器内视为页面框架的标签
+const BOILERPLATE_TAGS: &[&str] = &["nav", "footer", "aside", "form", "button", "select"];
+
+/// 表明页面框架的 ARIA role
+const BOILERPLATE_ROLES: &[&str] = &[
+    "navigation",
+    "banner",
+    "contentinfo",
+    "complementary",
+    "search",
+    "dialog",
+];
+
+/// class / id 按 `-` `_` 空格拆开后，出现这些词即视为噪音
+const NOISE_WORDS: &[&str] = &[
+    "nav",
+    "navbar",
+    "navigation",
+    "menu",
+    "footer",
+    "sidebar",
+    "cookie",
+    "cookies",
+    "consent",
+    "banner",
+    "share",
+    "social",
+    "comments",
+    "related",
+    "breadcrumb",
+    "breadcrumbs",
+    "advert",
+    "advertisement",
+    "ads",
+    "promo",
+    "newsletter",
+    "subscribe",
+    "popup",
+    "modal",
+];
+
+/// 无结束标签的元素
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// 抽取结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Article {
+    /// 页面标题（`og:title` → `<title>` → 首个 `<h1>`）
+    pub title: Option<String>,
+    /// 正文纯文本
+    pub text: String,
+}
+
+/// 从 HTML 中抽取标题与正文
+pub fn extract_article(html: &str) -> Article {
+    let visible = strip_invisible(html);
+    let title = page_title(&visible);
+
+    let (container, whole_page) = main_container(&visible);
+    let content = strip_elements(container, |name, attrs| {
+        // 整页作为容器时，页眉也是框架；article / main 内的 header 通常包含正文标题
+        BOILERPLATE_TAGS.contains(&name)
+            || (whole_page && name == "header")
+            || is_noise_attrs(attrs)
+    });
+
+    let text = html2text::from_read_with_decorator(
+        content.as_bytes(),
+        TEXT_WIDTH,
+        html2text::render::text_renderer::TrivialDecorator::new(),
+    );
+    Article {
+        title,
+        text: tidy_text(&text),
+    }
+}
+
+/// 一个开始或结束标签
+struct Tag<'a> {
+    start: usize,
+    end: usize,
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    attrs: &'a str,
+}
+
+fn tags(html: &str) -> impl Iterator<Item = Tag<'_>> {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let re = TAG.get_or_init(|| {
+        Regex::new(r#"(?s)<(/?)([A-Za-z][A-Za-z0-9-]*)((?:[^>"']|"[^"]*"|'[^']*')*)>"#)
+            .expect("内置正则合法")
+    });
+    re.captures_iter(html).map(|cap| {
+        let whole = cap.get(0).expect("整体匹配存在");
+        let attrs = cap.get(3).map_or("", |m| m.as_str());
+        let name = cap[2].to_ascii_lowercase();
+        Tag {
+            start: whole.start(),
+            end: whole.end(),
+            self_closing: attrs.trim_end().ends_with('/') || VOID_TAGS.contains(&name.as_str()),
+            closing: &cap[1] == "/",
+            name,
+            attrs,
+        }
+    })
+}
+
+/// 删除注释与不可见元素（其内容可能含 `<div` 之类的字符串，先于标签扫描处理）
+fn strip_invisible(html: &str) -> String {
+    static COMMENT: OnceLock<Regex> = OnceLock::new();
+    static RAW_TEXT: OnceLock<Regex> = OnceLock::new();
+    let comment = COMMENT.get_or_init(|| Regex::new(r"(?s)<!--.*?-->").expect("内置正则合法"));
+    let raw_text = RAW_TEXT.get_or_init(|| {
+        Regex::new(r"(?is)<(script|style|noscript|template)\b[^>]*>.*?</(script|style|noscript|template)\s*>")
+            .expect("内置正则合法")
+    });
+    let without_comments = comment.replace_all(html, "");
+    let without_raw = raw_text.replace_all(&without_comments, "");
+    strip_elements(&without_raw, |name, _| INVISIBLE_TAGS.contains(&name))
+}
+
+/// 删除 `is_noise(标签名, 属性)` 为真的元素（含全部子元素）
+///
+/// 同名标签按嵌套深度配对；未闭合的噪音元素一直删到文末。
+fn strip_elements(html: &str, is_noise: impl Fn(&str, &str) -> bool) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut copied_to = 0;
+    // 正在删除的元素：(标签名, 嵌套深度)
+    let mut skipping: Option<(String, usize)> = None;
+    for tag in tags(html) {
+        match &mut skipping {
+            Some((name, depth)) => {
+                if tag.name != *name || tag.self_closing {
+                    continue;
+                }
+                if !tag.closing {
+                    *depth += 1;
+                    continue;
+                }
+                *depth -= 1;
+                if *depth == 0 {
+                    copied_to = tag.end;
+                    skipping = None;
+                }
+            }
+            None => {
+                if !tag.closing && !tag.self_closing && is_noise(&tag.name, tag.attrs) {
+                    out.push_str(&html[copied_to..tag.start]);
+                    skipping = Some((tag.name, 1));
+                }
+            }
+        }
+    }
+    if skipping.is_none() {
+        out.push_str(&html[copied_to..]);
+    }
+    out
+}
+
+/// 所有最外层 `matches` 元素的内部 HTML
+fn inner_html_of(html: &str, matches: impl Fn(&str, &str) -> bool) -> Vec<&str> {
+    let mut found = Vec::new();
+    // 当前最外层匹配元素：(标签名, 嵌套深度, 内容起点)
+    let mut open: Option<(String, usize, usize)> = None;
+    for tag in tags(html) {
+        match &mut open {
+            Some((name, depth, content_start)) => {
+                if tag.name != *name || tag.self_closing {
+                    continue;
+                }
+                if !tag.closing {
+                    *depth += 1;
+                    continue;
+                }
+                *depth -= 1;
+                if *depth == 0 {
+                    found.push(&html[*content_start..tag.start]);
+                    open = None;
+                }
+            }
+            None => {
+                if !tag.closing && !tag.self_closing && matches(&tag.name, tag.attrs) {
+                    open = Some((tag.name, 1, tag.end));
+                }
+            }
+        }
+    }
+    if let Some((_, _, content_start)) = open {
+        found.push(&html[content_start..]);
+    }
+    found
+}
+
+/// 选择正文容器，第二个值表示是否退回到了整页
+fn main_container(html: &str) -> (&str, bool) {
+    let article = inner_html_of(html, |name, _| name == "article")
+        .into_iter()
+        .max_by_key(|inner| visible_chars(inner));
+    if let Some(article) = article.filter(|a| visible_chars(a) >= MIN_ARTICLE_CHARS) {
+        return (article, false);
+    }
+    let main = inner_html_of(html, |name, attrs| {
+        name == "main" || attr_value(attrs, "role").as_deref() == Some("main")
+    });
+    if let Some(main) = main.into_iter().max_by_key(|inner| visible_chars(inner)) {
+        return (main, false);
+    }
+    match inner_html_of(html, |name, _| name == "body")
+        .into_iter()
+        .next()
+    {
+        Some(body) => (body, true),
+        None => (html, true),
+    }
+}
+
+/// 标签之外的字符数（粗略估计正文长度）
+fn visible_chars(html: &str) -> usize {
+    let mut count = 0;
+    let mut last = 0;
+    for tag in tags(html) {
+        count += html[last..tag.start].trim().chars().count();
+        last = tag.end;
+    }
+    count + html[last..].trim().chars().count()
+}
+
+/// 属性值（大小写不敏感，支持单双引号与无引号）
+fn attr_value(attrs: &str, name: &str) -> Option<String> {
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let re = ATTR.get_or_init(|| {
+        Regex::new(r#"(?i)([a-z][a-z0-9:_-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+            .expect("内置正则合法")
+    });
+    re.captures_iter(attrs)
+        .find(|cap| cap[1].eq_ignore_ascii_case(name))
+        .and_then(|cap| cap.get(2).or(cap.get(3)).or(cap.get(4)))
+        .map(|m| m.as_str().to_ascii_lowercase())
+}
+
+/// role / class / id 是否表明页面框架（菜单、页脚、Cookie 提示、分享、广告等）
+fn is_noise_attrs(attrs: &str) -> bool {
+    if attrs.is_empty() {
+        return false;
+    }
+    if attr_value(attrs, "role").is_some_and(|role| BOILERPLATE_ROLES.contains(&role.as_str())) {
+        return true;
+    }
+    if attr_value(attrs, "aria-hidden").as_deref() == Some("true") {
+        return true;
+    }
+    ["class", "id"].iter().any(|attr| {
+        attr_value(attrs, attr).is_some_and(|value| {
+            value
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|word| NOISE_WORDS.contains(&word))
+        })
+    })
+}
+
+/// 页面标题：`og:title` → `<title>` → 首个 `<h1>`
+fn page_title(html: &str) -> Option<String> {
+    static OG_TITLE: OnceLock<Regex> = OnceLock::new();
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    static H1: OnceLock<Regex> = OnceLock::new();
+    static CONTENT: OnceLock<Regex> = OnceLock::new();
+    let og = OG_TITLE.get_or_init(|| {
+        Regex::new(r#"(?is)<meta\b[^>]*property\s*=\s*["']og:title["'][^>]*>"#)
+            .expect("内置正则合法")
+    });
+    let title =
+        TITLE.get_or_init(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").expect("内置正则合法"));
+    let h1 = H1.get_or_init(|| Regex::new(r"(?is)<h1[^>]*>(.*?)</h1>").expect("内置正则合法"));
+
+    let content = CONTENT.get_or_init(|| {
+        Regex::new(r#"(?is)\bcontent\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("内置正则合法")
+    });
+
+    let from_meta = og.find(html).and_then(|m| {
+        content
+            .captures(m.as_str())
+            .and_then(|cap| cap.get(1).or(cap.get(2)))
+            .map(|v| v.as_str().to_string())
+    });
+    let from_tag = |re: &Regex| {
+        re.captures(html)
+            .and_then(|cap| cap.get(1))
+            .map(|m| m.as_str().to_string())
+    };
+    from_meta
+        .or_else(|| from_tag(title))
+        .or_else(|| from_tag(h1))
+        .map(|raw| clean_inline(&raw))
+        .filter(|t| !t.is_empty())
+}
+
+/// 行内 HTML 片段转单行文本（去标签、解码常见实体、合并空白）
+fn clean_inline(fragment: &str) -> String {
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let tag = TAG.get_or_init(|| Regex::new(r"<[^>]*>").expect("内置正则合法"));
+    tag.replace_all(fragment, " ")
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 去掉行尾空白，连续空行合并为一行，去掉首尾空行
+fn tidy_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank_run = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank_run += 1;
+            continue;
+        }
+        if !out.is_empty() && blank_run > 0 {
+            out.push('\n');
+        }
+        blank_run = 0;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> String {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/reader/article.html");
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn fixture_page_keeps_article_and_drops_page_chrome() {
+        let article = extract_article(&fixture());
+        assert_eq!(
+            article.title.as_deref(),
+            Some("Why Rust ownership makes concurrency boring")
+        );
+
+        let text = &article.text;
+        // 正文：标题、段落、列表、代码
+        for kept in [
+            "Why Rust ownership makes concurrency boring",
+            "Data races are a compile-time error",
+            "Send and Sync are marker traits",
+            "Arc<Mutex<T>>",
+            "The borrow checker is a conversation",
+        ] {
+            assert!(text.contains(kept), "缺少正文 {:?}:\n{}", kept, text);
+        }
+        // 页面框架：导航、Cookie 提示、分享按钮、侧栏、相关推荐、评论、页脚、脚本
+        for dropped in [
+            "Home",
+            "Pricing",
+            "We use cookies",
+            "Share on",
+            "Popular posts",
+            "You might also like",
+            "Leave a comment",
+            "All rights reserved",
+            "trackPageView",
+            "font-family",
+            "Subscribe to our newsletter",
+        ] {
+            assert!(!text.contains(dropped), "未去掉 {:?}:\n{}", dropped, text);
+        }
+        // 不保留链接脚注，也没有连续空行
+        assert!(!text.contains("https://"), "{}", text);
+        assert!(!text.contains("\n\n\n"));
+    }
+
+    #[test]
+    fn falls_back_to_main_then_body_without_article() {
+        let html = r#"<html><body>
+            <nav class="top"><a href="/">Home</a></nav>
+            <header class="site-header">Acme Blog</header>
+            <div id="content"><p>Only paragraph of the page.</p></div>
+            <div class="cookie-banner">We use cookies</div>
+            <footer>© Acme</footer>
+            </body></html>"#;
+        let article = extract_article(html);
+        assert_eq!(article.title, None);
+        assert_eq!(article.text, "Only paragraph of the page.");
+
+        let html = r#"<body><div class="menu">Menu</div>
+            <div role="main"><h1>Docs &amp; Guides</h1><p>Main text.</p></div>
+            <p>Outside main.</p></body>"#;
+        let article = extract_article(html);
+        assert_eq!(article.title.as_deref(), Some("Docs & Guides"));
+        assert!(article.text.contains("Main text."));
+        assert!(!article.text.contains("Outside main."));
+        assert!(!article.text.contains("Menu"));
+    }
+
+    #[test]
+    fn nested_noise_elements_are_removed_whole() {
+        let html = r#"<body><p>Before.</p>
+            <div class="share-bar"><div><div>Share on X</div></div><span>Copy link</span></div>
+            <p>After.</p></body>"#;
+        let text = extract_article(html).text;
+        assert!(
+            text.contains("Before.") && text.contains("After."),
+            "{}",
+            text
+        );
+        assert!(!text.contains("Share on X") && !text.contains("Copy link"));
+    }
+}
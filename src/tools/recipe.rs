@@ -0,0 +1,278 @@
+//! HTTP Recipe：声明式多步请求
+//!
+//! 登录换 token 再取数据这类流程，逐步调用 http_request 既不可靠，又会把中间凭据写进对话历史。
+//! Recipe 把步骤写成 JSON 文件，由 HttpRequestTool 在一次工具调用内顺序执行：
+//! 前一步的响应按 JSONPath 捕获变量（`"token": "$.access_token"`），代入后续步骤的
+//! url / headers / body；只有最后一步的结果返回给 LLM。
+//!
+//! 查找位置（先到先得）：
+//! - `~/.rrclaw/recipes/<name>.json`
+//! - 文件系统 Skill 目录下的 `<name>.recipe.json`（作为 L3 资源随 Skill 分发）
+//!
+//! 模板语法：`${var}` 取调用参数 `vars` 或已捕获变量；`${env:NAME}` 取环境变量（用于凭据，
+//! 不必写进 recipe 文件）。
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use color_eyre::eyre::{eyre, Context, Result};
+use serde::Deserialize;
+
+/// Recipe 文件
+#[derive(Debug, Clone, Deserialize)]
+pub struct Recipe {
+    #[serde(default)]
+    pub description: Option<String>,
+    pub steps: Vec<RecipeStep>,
+}
+
+/// 单个请求步骤
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecipeStep {
+    /// 步骤名（出错时用于定位，缺省为序号）
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// 变量名 → JSONPath（`$.data.items[0].id`），仅对非最后一步生效
+    #[serde(default)]
+    pub capture: HashMap<String, String>,
+    /// 最后一步的 mini-LLM 提取提示（同 http_request 的 extract 参数）
+    #[serde(default)]
+    pub extract: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// 代入变量后的步骤
+#[derive(Debug, Clone)]
+pub struct RenderedStep {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+impl RecipeStep {
+    /// 出错提示中的步骤标识：`step 2 (fetch)`
+    pub fn label(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => format!("step {} ({})", index + 1, name),
+            None => format!("step {}", index + 1),
+        }
+    }
+
+    /// 代入变量；展开的环境变量值追加到 `secrets`，供结果脱敏
+    pub fn render(
+        &self,
+        vars: &HashMap<String, String>,
+        secrets: &mut Vec<String>,
+    ) -> std::result::Result<RenderedStep, String> {
+        let mut headers = HashMap::new();
+        for (key, value) in &self.headers {
+            headers.insert(key.clone(), substitute(value, vars, secrets)?);
+        }
+        Ok(RenderedStep {
+            method: self.method.to_uppercase(),
+            url: substitute(&self.url, vars, secrets)?,
+            headers,
+            body: self
+                .body
+                .as_deref()
+                .map(|b| substitute(b, vars, secrets))
+                .transpose()?,
+        })
+    }
+}
+
+/// 在各目录中查找并解析 recipe
+pub fn load_recipe(dirs: &[RecipeDir], name: &str) -> Result<Recipe> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(eyre!("无效的 recipe 名称: {}", name));
+    }
+    let path = dirs
+        .iter()
+        .map(|dir| dir.candidate(name))
+        .find(|p| p.is_file())
+        .ok_or_else(|| eyre!("未找到 recipe '{}'", name))?;
+    let content = std::fs::read_to_string(&path)
+        .wrap_err_with(|| format!("读取 recipe 失败: {}", path.display()))?;
+    let recipe: Recipe = serde_json::from_str(&content)
+        .wrap_err_with(|| format!("解析 recipe 失败: {}", path.display()))?;
+    if recipe.steps.is_empty() {
+        return Err(eyre!("recipe '{}' 没有任何步骤", name));
+    }
+    Ok(recipe)
+}
+
+/// Recipe 查找目录
+#[derive(Debug, Clone)]
+pub enum RecipeDir {
+    /// 专用目录：`<dir>/<name>.json`
+    Recipes(PathBuf),
+    /// Skill 目录：`<dir>/<name>.recipe.json`
+    Skill(PathBuf),
+}
+
+impl RecipeDir {
+    fn candidate(&self, name: &str) -> PathBuf {
+        match self {
+            RecipeDir::Recipes(dir) => dir.join(format!("{}.json", name)),
+            RecipeDir::Skill(dir) => dir.join(format!("{}.recipe.json", name)),
+        }
+    }
+}
+
+/// 展开 `${var}` / `${env:NAME}`；未定义的变量报错（只报名字，不含任何值）
+pub fn substitute(
+    template: &str,
+    vars: &HashMap<String, String>,
+    secrets: &mut Vec<String>,
+) -> std::result::Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("模板缺少右括号: {}", template))?;
+        let key = after[..end].trim();
+        match key.strip_prefix("env:") {
+            Some(env_name) => {
+                let value =
+                    std::env::var(env_name).map_err(|_| format!("环境变量 {} 未设置", env_name))?;
+                secrets.push(value.clone());
+                out.push_str(&value);
+            }
+            None => {
+                let value = vars
+                    .get(key)
+                    .ok_or_else(|| format!("变量 {} 未定义", key))?;
+                out.push_str(value);
+            }
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// 简化 JSONPath：`$`、`.field`、`[index]`（如 `$.data.items[0].id`）
+pub fn json_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    let path = path.trim().strip_prefix('$')?;
+    let mut current = value;
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut field = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next == '.' || next == '[' {
+                        break;
+                    }
+                    field.push(next);
+                    chars.next();
+                }
+                current = current.get(field.as_str())?;
+            }
+            '[' => {
+                let mut index = String::new();
+                for next in chars.by_ref() {
+                    if next == ']' {
+                        break;
+                    }
+                    index.push(next);
+                }
+                current = current.get(index.trim().parse::<usize>().ok()?)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(current)
+}
+
+/// 捕获值转字符串（字符串去引号，其余按 JSON 文本）
+pub fn capture_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// 把文本中出现的密钥值替换为 `***`
+pub fn scrub(text: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        .filter(|s| s.len() >= 4)
+        .fold(text.to_string(), |acc, secret| {
+            acc.replace(secret.as_str(), "***")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn substitute_expands_vars_and_env() {
+        std::env::set_var("RRCLAW_RECIPE_TEST_PASS", "hunter2-secret");
+        let vars: HashMap<String, String> = [("user".to_string(), "alice".to_string())].into();
+        let mut secrets = Vec::new();
+        let out = substitute(
+            r#"{"u":"${user}","p":"${env:RRCLAW_RECIPE_TEST_PASS}"}"#,
+            &vars,
+            &mut secrets,
+        )
+        .unwrap();
+        assert_eq!(out, r#"{"u":"alice","p":"hunter2-secret"}"#);
+        assert_eq!(secrets, vec!["hunter2-secret"]);
+    }
+
+    #[test]
+    fn substitute_reports_missing_variable_by_name() {
+        let err = substitute("Bearer ${token}", &HashMap::new(), &mut Vec::new()).unwrap_err();
+        assert!(err.contains("token"));
+        assert!(substitute("${oops", &HashMap::new(), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn json_path_walks_fields_and_indexes() {
+        let value = json!({"data": {"items": [{"id": 7}, {"id": 8}]}, "token": "t"});
+        assert_eq!(json_path(&value, "$.token"), Some(&json!("t")));
+        assert_eq!(json_path(&value, "$.data.items[1].id"), Some(&json!(8)));
+        assert_eq!(json_path(&value, "$.data.missing"), None);
+        assert_eq!(json_path(&value, "$.data.items[5]"), None);
+        assert_eq!(json_path(&value, "token"), None);
+    }
+
+    #[test]
+    fn load_recipe_searches_recipes_then_skill_dirs() {
+        let recipes = tempfile::tempdir().unwrap();
+        let skill = tempfile::tempdir().unwrap();
+        std::fs::write(
+            skill.path().join("login.recipe.json"),
+            r#"{"steps": [{"url": "https://example.com"}]}"#,
+        )
+        .unwrap();
+        let dirs = vec![
+            RecipeDir::Recipes(recipes.path().to_path_buf()),
+            RecipeDir::Skill(skill.path().to_path_buf()),
+        ];
+
+        let recipe = load_recipe(&dirs, "login").unwrap();
+        assert_eq!(recipe.steps[0].method, "GET");
+        assert!(load_recipe(&dirs, "missing").is_err());
+        assert!(load_recipe(&dirs, "../login").is_err());
+    }
+}
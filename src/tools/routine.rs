@@ -11,14 +11,14 @@ use async_trait::async_trait;
 use color_eyre::eyre::{eyre, Result};
 use serde_json::{json, Value};
 
-use crate::providers::traits::{ChatMessage, ConversationMessage, Provider};
-use crate::routines::RoutineEngine;
+use crate::providers::traits::{ChatMessage, ConversationMessage, GenerationParams, Provider};
+use crate::routines::{Routine, RoutineEngine, SchedulerRole};
 use crate::security::SecurityPolicy;
-use crate::tools::traits::{Tool, ToolResult};
+use crate::tools::traits::{Tool, ToolOutputKind, ToolResult};
 
 /// RoutineTool：通过 LLM 工具调用管理定时任务
 ///
-/// 支持 actions：create / list / delete / enable / disable / run / logs
+/// 支持 actions：create / list / delete / enable / disable / run / cancel / logs
 pub struct RoutineTool {
     engine: Arc<RoutineEngine>,
     provider: Option<Arc<dyn Provider>>,
@@ -61,16 +61,23 @@ impl RoutineTool {
                          - \"每周一早上9点\" → \"0 9 * * 1\""
                     .to_string(),
                 reasoning_content: None,
+                turn: 0,
             }),
             ConversationMessage::Chat(ChatMessage {
                 role: "user".to_string(),
                 content: format!("转换为 cron：{}", desc),
                 reasoning_content: None,
+                turn: 0,
             }),
         ];
 
         let resp = provider
-            .chat_with_tools(&messages, &[], &self.model, 0.0)
+            .chat_with_tools(
+                &messages,
+                &[],
+                &self.model,
+                GenerationParams::with_temperature(0.0),
+            )
             .await?;
 
         let cron = resp.text.unwrap_or_default().trim().to_string();
@@ -92,25 +99,27 @@ impl Tool for RoutineTool {
     }
 
     fn description(&self) -> &str {
-        "管理定时任务（Routines）。支持创建、列出、删除、启用/禁用、手动触发、查看日志。\n\
+        "管理定时任务（Routines）。支持创建、列出、删除、启用/禁用、手动触发、取消正在执行的任务、查看日志。\n\
          schedule 参数支持：\n\
          1. 自然语言：每5分钟、每天9点、每周一早上9点、每20秒（LLM 自动转换为 cron）\n\
          2. 直接使用 cron 表达式：\"0 8 * * *\"（每天早 8 点）、\"* * * * *\"（每分钟）\n\
-         创建/删除/启用/禁用立即对 list/run 生效。"
+         创建/删除/启用/禁用立即对 list/run 生效。\n\
+         另一个 rrclaw 进程负责调度时：调度者是 daemon 则其余操作自动转发，否则仅 list/logs 可用。"
     }
 
     fn parameters_schema(&self) -> Value {
+        // channel 的 enum 按运行时实际可用的通道生成（未配置 Telegram 时不出现 telegram）
         json!({
             "type": "object",
             "properties": {
                 "action": {
                     "type": "string",
-                    "enum": ["create", "list", "delete", "enable", "disable", "run", "logs"],
+                    "enum": ["create", "list", "delete", "enable", "disable", "run", "cancel", "logs"],
                     "description": "操作类型"
                 },
                 "name": {
                     "type": "string",
-                    "description": "任务名称（create/delete/enable/disable/run 时必填，建议用 snake_case）"
+                    "description": "任务名称（create/delete/enable/disable/run/cancel 时必填，建议用 snake_case）"
                 },
                 "schedule": {
                     "type": "string",
@@ -118,12 +127,25 @@ impl Tool for RoutineTool {
                 },
                 "message": {
                     "type": "string",
-                    "description": "触发时发送给 Agent 的提示词（create 时必填）"
+                    "description": "触发时发送给 Agent 的提示词（create 时必填，设置 chain 时可省略）；可用 {date} {time} {weekday}，触发时展开"
                 },
                 "channel": {
                     "type": "string",
-                    "enum": ["cli", "telegram"],
-                    "description": "结果输出通道，默认 cli"
+                    "enum": self.engine.available_channels(),
+                    "description": "结果输出通道，默认 cli；webhook 需同时提供 channel_target"
+                },
+                "channel_target": {
+                    "type": "string",
+                    "description": "channel 为 webhook 时接收结果的 URL（POST JSON，受 SSRF 白名单限制）"
+                },
+                "chain": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "create 时可选：依次执行的已有任务名称，汇总结果后发送（设置后可省略 message）"
+                },
+                "catch_up": {
+                    "type": "boolean",
+                    "description": "create 时可选：机器休眠/RRClaw 未运行而错过触发时，下次启动补跑最近一次（默认 false）"
                 },
                 "limit": {
                     "type": "integer",
@@ -138,7 +160,7 @@ impl Tool for RoutineTool {
 
     async fn execute(&self, args: Value, _policy: &SecurityPolicy) -> Result<ToolResult> {
         let action = match args.get("action").and_then(|v| v.as_str()) {
-            Some(a) => a,
+            Some(a) => a.to_string(),
             None => {
                 return Ok(ToolResult {
                     success: false,
@@ -149,28 +171,65 @@ impl Tool for RoutineTool {
             }
         };
 
+        // 旁观模式：调度者是 daemon 时，增删改与执行转发给它（list / logs 读共享数据库）
+        if let SchedulerRole::Standby { pid: Some(holder) } = self.engine.role() {
+            let forwarded = matches!(
+                action.as_str(),
+                "create" | "delete" | "enable" | "disable" | "run" | "cancel"
+            );
+            if forwarded && crate::daemon::running_pid() == Some(holder) {
+                let args = self.resolve_schedule_for_forward(args).await;
+                if let Some(result) = crate::daemon::forward_routine(holder, &args).await {
+                    return result;
+                }
+                return self.dispatch(&action, args).await;
+            }
+        }
+        self.dispatch(&action, args).await
+    }
+}
+
+impl RoutineTool {
+    async fn dispatch(&self, action: &str, args: Value) -> Result<ToolResult> {
         match action {
             "create" => self.action_create(&args).await,
-            "list" => self.action_list(),
+            "list" => self.action_list().await,
             "delete" => self.action_delete(&args).await,
             "enable" => self.action_set_enabled(&args, true).await,
             "disable" => self.action_set_enabled(&args, false).await,
             "run" => self.action_run(&args).await,
+            "cancel" => self.action_cancel(&args),
             "logs" => self.action_logs(&args).await,
             other => Ok(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some(format!(
-                    "未知 action: {}。可用：create/list/delete/enable/disable/run/logs",
+                    "未知 action: {}。可用：create/list/delete/enable/disable/run/cancel/logs",
                     other
                 )),
                 ..Default::default()
             }),
         }
     }
-}
 
-impl RoutineTool {
+    /// 转发前在本进程把自然语言 schedule 转为 cron（daemon 端不带 LLM）；
+    /// 转换失败时原样转发，由对方报告解析错误
+    async fn resolve_schedule_for_forward(&self, mut args: Value) -> Value {
+        if args.get("action").and_then(|v| v.as_str()) != Some("create") {
+            return args;
+        }
+        let Some(input) = args.get("schedule").and_then(|v| v.as_str()) else {
+            return args;
+        };
+        if input.is_empty() || input.split_whitespace().count() == 5 {
+            return args;
+        }
+        if let Ok(cron) = self.parse_schedule_with_llm(input).await {
+            args["schedule"] = json!(cron);
+        }
+        args
+    }
+
     async fn action_create(&self, args: &Value) -> Result<ToolResult> {
         let name = match args.get("name").and_then(|v| v.as_str()) {
             Some(n) if !n.is_empty() => n.to_string(),
@@ -195,6 +254,37 @@ impl RoutineTool {
             }
         };
 
+        // 通道先于 schedule 校验：写错时不必再花一次 LLM 调用解析时间
+        let channel = match args.get("channel").and_then(|v| v.as_str()) {
+            None => "cli",
+            Some(raw) => match normalize_channel(raw, &self.engine.available_channels()) {
+                Ok(channel) => channel,
+                Err(e) => {
+                    return Ok(ToolResult {
+                        success: false,
+                        output: String::new(),
+                        error: Some(e),
+                        ..Default::default()
+                    })
+                }
+            },
+        };
+        let channel_target = args
+            .get("channel_target")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string());
+        if channel == "webhook" && channel_target.is_none() {
+            return Ok(ToolResult {
+                success: false,
+                output: String::new(),
+                error: Some(
+                    "channel 为 webhook 时需要 channel_target（接收 POST 的 URL）".to_string(),
+                ),
+                ..Default::default()
+            });
+        }
+
         // 解析自然语言时间描述为 cron 表达式
         // 优先判断是否已是 5 字段 cron，直接使用
         // 否则直接用 LLM 解析（不用正则，正则无法处理"每1分钟提醒我喝水"这种复杂自然语言）
@@ -222,42 +312,66 @@ impl RoutineTool {
                 }
             }
         };
+        let chain: Vec<String> = args
+            .get("chain")
+            .and_then(|v| v.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
         let message = match args.get("message").and_then(|v| v.as_str()) {
             Some(m) if !m.is_empty() => m.to_string(),
+            _ if !chain.is_empty() => String::new(),
             _ => {
                 return Ok(ToolResult {
                     success: false,
                     output: String::new(),
-                    error: Some("create 操作需要 message 参数".to_string()),
+                    error: Some("create 操作需要 message 或 chain 参数".to_string()),
                     ..Default::default()
                 })
             }
         };
-        let channel = args
-            .get("channel")
-            .and_then(|v| v.as_str())
-            .unwrap_or("cli")
-            .to_string();
+        let catch_up = args
+            .get("catch_up")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
         let routine = crate::routines::Routine {
             name: name.clone(),
             schedule: schedule.clone(),
             message,
-            channel,
+            channel: channel.to_string(),
+            channel_target,
             enabled: true,
             source: crate::routines::RoutineSource::Dynamic,
+            catch_up,
+            chain,
+            // 前置条件会执行任意 shell 命令，不向 LLM 开放（只能由配置或 /routine add 设置）
+            condition: None,
         };
 
         match self.engine.clone().persist_add_routine(&routine).await {
-            Ok(()) => Ok(ToolResult {
-                success: true,
-                output: format!(
-                    "✓ 已创建定时任务 '{}'（{}）。list/run 立即可用。",
-                    name, schedule
-                ),
-                error: None,
-                ..Default::default()
-            }),
+            Ok(()) => {
+                // 回显解析后的 cron 与下次触发时间，便于模型向用户确认
+                let timezone = self.engine.timezone();
+                let next = timezone
+                    .next_fire(&schedule, chrono::Utc::now())
+                    .map(|at| format!("{}（{}）", timezone.format_short(at), timezone.name()))
+                    .unwrap_or_else(|_| "未知".to_string());
+                Ok(ToolResult {
+                    success: true,
+                    output: format!(
+                        "✓ 已创建定时任务 '{}'。\ncron: {}\n下次触发: {}\n结果通道: {}\nlist/run 立即可用。",
+                        name, schedule, next, channel
+                    ),
+                    error: None,
+                    ..Default::default()
+                })
+            }
             Err(e) => Ok(ToolResult {
                 success: false,
                 output: String::new(),
@@ -267,7 +381,8 @@ impl RoutineTool {
         }
     }
 
-    fn action_list(&self) -> Result<ToolResult> {
+    async fn action_list(&self) -> Result<ToolResult> {
+        self.engine.reload_if_standby().await;
         let routines = self.engine.list_routines();
         if routines.is_empty() {
             return Ok(ToolResult {
@@ -278,10 +393,24 @@ impl RoutineTool {
             });
         }
 
+        let kind = routine_table(&routines);
         let mut lines = vec!["当前定时任务列表：".to_string()];
+        if self.engine.is_paused() {
+            lines.push("（调度已全局暂停，以下任务不会自动触发）".to_string());
+        }
+        if let Some(notice) = self.engine.role().standby_notice() {
+            lines.push(format!(
+                "（{}；create/delete/enable/disable/run 需在该进程中操作，调度者为 daemon 时自动转发）",
+                notice
+            ));
+        }
         for r in routines {
-            let status = if r.enabled { "启用" } else { "禁用" };
-            let preview: String = r.message.chars().take(60).collect();
+            let status = match self.engine.running_elapsed(&r.name) {
+                Some(elapsed) => format!("⏳ 执行中 {}", crate::routines::format_elapsed(elapsed)),
+                None if r.enabled => "启用".to_string(),
+                None => "禁用".to_string(),
+            };
+            let preview: String = r.summary().chars().take(60).collect();
             lines.push(format!(
                 "- {} | {} | {} | {} | {}",
                 r.name, r.schedule, status, r.channel, preview
@@ -291,6 +420,7 @@ impl RoutineTool {
             success: true,
             output: lines.join("\n"),
             error: None,
+            kind: Some(kind),
             ..Default::default()
         })
     }
@@ -381,6 +511,38 @@ impl RoutineTool {
         }
     }
 
+    fn action_cancel(&self, args: &Value) -> Result<ToolResult> {
+        let name = match args.get("name").and_then(|v| v.as_str()) {
+            Some(n) if !n.is_empty() => n,
+            _ => {
+                return Ok(ToolResult {
+                    success: false,
+                    output: String::new(),
+                    error: Some("cancel 操作需要 name 参数".to_string()),
+                    ..Default::default()
+                })
+            }
+        };
+        if self.engine.cancel_routine(name) {
+            Ok(ToolResult {
+                success: true,
+                output: format!(
+                    "✓ 已请求取消定时任务 '{}'（正在运行的工具结束后停止，不再重试）。",
+                    name
+                ),
+                error: None,
+                ..Default::default()
+            })
+        } else {
+            Ok(ToolResult {
+                success: false,
+                output: String::new(),
+                error: Some(format!("定时任务 '{}' 当前没有在执行", name)),
+                ..Default::default()
+            })
+        }
+    }
+
     async fn action_logs(&self, args: &Value) -> Result<ToolResult> {
         let limit = args.get("limit").and_then(|v| v.as_u64()).unwrap_or(5) as usize;
 
@@ -396,7 +558,13 @@ impl RoutineTool {
 
         let mut lines = vec![format!("最近 {} 条执行记录：", logs.len())];
         for log in &logs {
-            let status = if log.success { "成功" } else { "失败" };
+            let status = if log.success {
+                "成功"
+            } else if log.deferred {
+                "延后（离线）"
+            } else {
+                "失败"
+            };
             let started = if log.started_at.len() >= 19 {
                 &log.started_at[..19]
             } else {
@@ -420,6 +588,63 @@ impl RoutineTool {
     }
 }
 
+/// 规范化 LLM 给出的通道名：忽略大小写与 `-` / `_` / 空格差异，接受常见别名
+///
+/// 不在 `available` 中（如未配置 Telegram 时的 telegram）或无法识别时返回错误，列出可用通道。
+fn normalize_channel(
+    raw: &str,
+    available: &[&'static str],
+) -> std::result::Result<&'static str, String> {
+    let key: String = raw
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .collect();
+    let channel = match key.as_str() {
+        "cli" | "terminal" | "console" | "stdout" | "local" => Some("cli"),
+        "telegram" | "tg" | "telegramdm" | "telegrambot" | "telegramchat" => Some("telegram"),
+        "webhook" | "webhooks" | "hook" | "http" => Some("webhook"),
+        _ => None,
+    };
+    match channel {
+        Some(c) if available.contains(&c) => Ok(c),
+        Some(c) => Err(format!(
+            "通道 '{}' 当前不可用（{} 未配置）。可用通道：{}",
+            raw,
+            c,
+            available.join(", ")
+        )),
+        None => Err(format!(
+            "未知通道 '{}'。可用通道：{}",
+            raw,
+            available.join(", ")
+        )),
+    }
+}
+
+/// Routine 列表的表格元数据（供 Channel 渲染）
+fn routine_table(routines: &[Routine]) -> ToolOutputKind {
+    ToolOutputKind::Table {
+        headers: ["name", "schedule", "enabled", "channel", "message"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect(),
+        rows: routines
+            .iter()
+            .map(|r| {
+                vec![
+                    r.name.clone(),
+                    r.schedule.clone(),
+                    r.enabled.to_string(),
+                    r.channel.clone(),
+                    r.summary().chars().take(60).collect(),
+                ]
+            })
+            .collect(),
+    }
+}
+
 // ─── 测试 ─────────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -435,7 +660,7 @@ mod tests {
             "properties": {
                 "action": {
                     "type": "string",
-                    "enum": ["create", "list", "delete", "enable", "disable", "run", "logs"]
+                    "enum": ["create", "list", "delete", "enable", "disable", "run", "cancel", "logs"]
                 }
             },
             "required": ["action"]
@@ -443,7 +668,195 @@ mod tests {
         assert_eq!(schema["type"], "object");
         assert!(schema["properties"]["action"]["enum"].is_array());
         let actions = schema["properties"]["action"]["enum"].as_array().unwrap();
-        assert_eq!(actions.len(), 7);
+        assert_eq!(actions.len(), 8);
+    }
+
+    #[test]
+    fn routine_table_shape() {
+        let routine = Routine {
+            name: "daily".to_string(),
+            schedule: "0 8 * * *".to_string(),
+            message: "早报".to_string(),
+            channel: "cli".to_string(),
+            enabled: true,
+            source: crate::routines::RoutineSource::Dynamic,
+            catch_up: false,
+            chain: vec![],
+            condition: None,
+            channel_target: None,
+        };
+        match routine_table(&[routine]) {
+            ToolOutputKind::Table { headers, rows } => {
+                assert_eq!(headers.len(), 5);
+                assert_eq!(rows.len(), 1);
+                assert_eq!(rows[0][0], "daily");
+                assert_eq!(rows[0][2], "true");
+            }
+            other => panic!("expected Table, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn channel_aliases_normalize_to_canonical_names() {
+        let available = ["cli", "telegram", "webhook"];
+        for raw in [
+            "Telegram",
+            "tg",
+            "telegram-dm",
+            "TELEGRAM_BOT",
+            " telegram ",
+        ] {
+            assert_eq!(
+                normalize_channel(raw, &available),
+                Ok("telegram"),
+                "{}",
+                raw
+            );
+        }
+        for raw in ["CLI", "terminal", "Console"] {
+            assert_eq!(normalize_channel(raw, &available), Ok("cli"), "{}", raw);
+        }
+        assert_eq!(normalize_channel("Web-Hook", &available), Ok("webhook"));
+    }
+
+    #[test]
+    fn unknown_or_unavailable_channel_is_rejected_with_choices() {
+        let err = normalize_channel("slack", &["cli", "telegram", "webhook"]).unwrap_err();
+        assert!(err.contains("slack"), "{}", err);
+        assert!(err.contains("cli, telegram, webhook"), "{}", err);
+
+        // 未配置 Telegram：别名能识别，但不接受
+        let err = normalize_channel("tg", &["cli", "webhook"]).unwrap_err();
+        assert!(err.contains("telegram 未配置"), "{}", err);
+        assert!(err.contains("cli, webhook"), "{}", err);
+    }
+
+    async fn tool_with_config(dir: &std::path::Path, config: crate::config::Config) -> RoutineTool {
+        let engine = RoutineEngine::new(
+            vec![],
+            Arc::new(config),
+            Arc::new(crate::memory::NoopMemory),
+            &dir.join("routines.db"),
+        )
+        .await
+        .unwrap()
+        .with_paths(crate::config::RrclawPaths::in_home(dir));
+        RoutineTool::new(Arc::new(engine), None, String::new())
+    }
+
+    #[tokio::test]
+    async fn create_normalizes_channel_and_echoes_next_fire() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = crate::config::Config {
+            telegram: Some(Default::default()),
+            ..Default::default()
+        };
+        let tool = tool_with_config(dir.path(), config).await;
+        let channels = tool.parameters_schema()["properties"]["channel"]["enum"].clone();
+        assert_eq!(channels, json!(["cli", "telegram", "webhook"]));
+
+        let result = tool
+            .execute(
+                json!({
+                    "action": "create",
+                    "name": "dashboards",
+                    "schedule": "0 9 * * 1-5",
+                    "message": "提醒我检查看板",
+                    "channel": "Telegram"
+                }),
+                &SecurityPolicy::default(),
+            )
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert!(
+            result.output.contains("cron: 0 9 * * 1-5"),
+            "{}",
+            result.output
+        );
+        assert!(result.output.contains("下次触发: 20"), "{}", result.output);
+        assert_eq!(
+            tool.engine.get_routine("dashboards").unwrap().channel,
+            "telegram"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_rejects_telegram_when_not_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = tool_with_config(dir.path(), crate::config::Config::default()).await;
+        let channels = tool.parameters_schema()["properties"]["channel"]["enum"].clone();
+        assert_eq!(channels, json!(["cli", "webhook"]));
+
+        let result = tool
+            .execute(
+                json!({
+                    "action": "create",
+                    "name": "dashboards",
+                    "schedule": "0 9 * * 1-5",
+                    "message": "提醒我检查看板",
+                    "channel": "tg"
+                }),
+                &SecurityPolicy::default(),
+            )
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("cli, webhook"));
+        assert!(tool.engine.get_routine("dashboards").is_none());
+    }
+
+    #[tokio::test]
+    async fn standby_tool_lists_holder_routines_but_refuses_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let holder = tool_with_config(dir.path(), crate::config::Config::default()).await;
+        let standby = tool_with_config(dir.path(), crate::config::Config::default()).await;
+        assert_eq!(
+            holder.engine.claim_scheduler(),
+            crate::routines::SchedulerRole::Scheduler
+        );
+        assert!(matches!(
+            standby.engine.claim_scheduler(),
+            crate::routines::SchedulerRole::Standby { .. }
+        ));
+
+        let policy = SecurityPolicy::default();
+        let created = holder
+            .execute(
+                json!({
+                    "action": "create",
+                    "name": "standup",
+                    "schedule": "0 9 * * 1-5",
+                    "message": "提醒我开站会"
+                }),
+                &policy,
+            )
+            .await
+            .unwrap();
+        assert!(created.success, "{:?}", created.error);
+
+        let listed = standby
+            .execute(json!({ "action": "list" }), &policy)
+            .await
+            .unwrap();
+        assert!(listed.output.contains("standup"), "{}", listed.output);
+        assert!(
+            listed.output.contains("需在该进程中操作"),
+            "{}",
+            listed.output
+        );
+
+        let run = standby
+            .execute(json!({ "action": "run", "name": "standup" }), &policy)
+            .await
+            .unwrap();
+        assert!(!run.success);
+        let err = run.error.unwrap();
+        assert!(
+            err.contains(&format!("pid {}", std::process::id())),
+            "{}",
+            err
+        );
     }
 
     #[test]
@@ -0,0 +1,548 @@
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use super::http::validate_url;
+use super::traits::{Tool, ToolResult};
+use crate::config::{SearchBackendKind, SearchConfig};
+use crate::security::SecurityPolicy;
+
+/// Brave Search API 默认地址
+const BRAVE_API_BASE: &str = "https://api.search.brave.com";
+/// 单次调用最多返回条数
+const MAX_RESULTS_LIMIT: usize = 20;
+/// 搜索请求超时（秒）
+const SEARCH_TIMEOUT_SECS: u64 = 15;
+/// 摘要最大字符数
+const SNIPPET_MAX_CHARS: usize = 300;
+const USER_AGENT: &str = "RRClaw/1.0 (https://github.com/rrclaw/rrclaw)";
+
+/// 未配置后端时的提示
+pub const NOT_CONFIGURED_MESSAGE: &str = "未配置网页搜索后端：在 config.toml 添加 [search]，\
+     backend = \"brave\" 并填写 api_key，或 backend = \"searxng\" 并填写 url（见 rrclaw config --example）";
+
+/// 一条搜索结果（按后端返回的排名顺序）
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// 搜索后端（Brave / SearXNG，可扩展）
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    /// 后端名（结果标题用）
+    fn name(&self) -> &'static str;
+    /// 请求地址（执行前做 SSRF 检查）
+    fn endpoint(&self) -> &str;
+    async fn search(&self, query: &str, count: usize) -> Result<Vec<SearchHit>>;
+}
+
+/// 按 `[search]` 创建后端；配置不完整时返回说明
+pub fn backend_from_config(config: &SearchConfig) -> Result<Box<dyn SearchBackend>, String> {
+    match config.backend {
+        None => Err(NOT_CONFIGURED_MESSAGE.to_string()),
+        Some(SearchBackendKind::Brave) if config.api_key.is_empty() => Err(
+            "search.backend = \"brave\" 需要 search.api_key（Brave Search API Key）".to_string(),
+        ),
+        Some(SearchBackendKind::Brave) => Ok(Box::new(BraveSearch {
+            base_url: config
+                .url
+                .clone()
+                .unwrap_or_else(|| BRAVE_API_BASE.to_string()),
+            api_key: config.api_key.clone(),
+        })),
+        Some(SearchBackendKind::Searxng) => match config.url.as_deref() {
+            Some(url) if !url.is_empty() => Ok(Box::new(SearxSearch {
+                base_url: url.to_string(),
+            })),
+            _ => {
+                Err("search.backend = \"searxng\" 需要 search.url（SearXNG 实例地址）".to_string())
+            }
+        },
+    }
+}
+
+/// 网页搜索工具：返回按排名排列的标题 / 链接 / 摘要
+pub struct WebSearchTool {
+    config: SearchConfig,
+}
+
+impl WebSearchTool {
+    pub fn new(config: SearchConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl Tool for WebSearchTool {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+
+    fn description(&self) -> &str {
+        "搜索网页，返回按相关度排序的标题、链接和摘要。\
+         需要最新信息、查找文档或网页地址时使用；需要页面全文时再用 http_request 打开结果链接。"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "搜索关键词"
+                },
+                "count": {
+                    "type": "integer",
+                    "description": "返回条数，默认按配置（5），最多 20"
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn pre_validate(&self, args: &serde_json::Value, policy: &SecurityPolicy) -> Option<String> {
+        if !policy.allows_execution() {
+            return Some("只读模式下不允许网页搜索".to_string());
+        }
+        match args.get("query").and_then(|v| v.as_str()) {
+            Some(q) if !q.trim().is_empty() => None,
+            _ => Some("缺少 query 参数".to_string()),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        let query = args
+            .get("query")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| eyre!("缺少 query 参数"))?;
+        let count = args
+            .get("count")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(self.config.max_results)
+            .clamp(1, MAX_RESULTS_LIMIT);
+
+        let backend = match backend_from_config(&self.config) {
+            Ok(backend) => backend,
+            Err(message) => return Ok(failure(message)),
+        };
+
+        // 自建后端（内网 SearXNG 等）与 http_request 共用 SSRF 白名单
+        let mut allowed_hosts = policy.http_allowed_hosts.clone();
+        allowed_hosts.extend(crate::config::Config::get_http_allowed_hosts());
+        if let Some(reason) = validate_url(backend.endpoint(), &allowed_hosts) {
+            return Ok(failure(format!("搜索后端地址被拒绝: {}", reason)));
+        }
+
+        debug!("web_search: backend={} query={}", backend.name(), query);
+        let started = Instant::now();
+        let hits = match backend.search(query, count).await {
+            Ok(hits) => hits,
+            Err(e) => return Ok(failure(format!("{} 搜索失败: {:#}", backend.name(), e))),
+        };
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        Ok(ToolResult {
+            success: true,
+            output: format_hits(query, &hits),
+            duration_ms: Some(duration_ms),
+            user_facing: Some(format!("{} 条结果 · {}", hits.len(), backend.name())),
+            ..Default::default()
+        })
+    }
+}
+
+fn failure(message: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        output: String::new(),
+        error: Some(message),
+        ..Default::default()
+    }
+}
+
+/// 编号列表：标题 / 链接 / 摘要各占一行
+fn format_hits(query: &str, hits: &[SearchHit]) -> String {
+    if hits.is_empty() {
+        return format!("没有找到与 \"{}\" 相关的结果", query);
+    }
+    hits.iter()
+        .enumerate()
+        .map(|(i, hit)| {
+            let mut entry = format!("{}. {}\n   {}", i + 1, hit.title, hit.url);
+            if !hit.snippet.is_empty() {
+                entry.push_str(&format!("\n   {}", hit.snippet));
+            }
+            entry
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// ─── Brave ───────────────────────────────────────────────────────────────
+
+struct BraveSearch {
+    base_url: String,
+    api_key: String,
+}
+
+#[derive(Deserialize)]
+struct BraveResponse {
+    #[serde(default)]
+    web: Option<BraveWeb>,
+}
+
+#[derive(Deserialize)]
+struct BraveWeb {
+    #[serde(default)]
+    results: Vec<RawHit>,
+}
+
+#[async_trait]
+impl SearchBackend for BraveSearch {
+    fn name(&self) -> &'static str {
+        "Brave"
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn search(&self, query: &str, count: usize) -> Result<Vec<SearchHit>> {
+        let count_param = count.to_string();
+        let url = search_url(
+            &self.base_url,
+            "res/v1/web/search",
+            &[("q", query), ("count", count_param.as_str())],
+        )?;
+        let body = get_json(
+            client()?
+                .get(url)
+                .header("Accept", "application/json")
+                .header("X-Subscription-Token", &self.api_key),
+        )
+        .await?;
+        Ok(parse_brave(&body)?.into_iter().take(count).collect())
+    }
+}
+
+fn parse_brave(body: &str) -> Result<Vec<SearchHit>> {
+    let response: BraveResponse =
+        serde_json::from_str(body).map_err(|e| eyre!("无法解析 Brave 响应: {}", e))?;
+    Ok(response
+        .web
+        .map(|web| web.results)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(RawHit::into_hit)
+        .collect())
+}
+
+// ─── SearXNG ─────────────────────────────────────────────────────────────
+
+struct SearxSearch {
+    base_url: String,
+}
+
+#[derive(Deserialize)]
+struct SearxResponse {
+    #[serde(default)]
+    results: Vec<RawHit>,
+}
+
+#[async_trait]
+impl SearchBackend for SearxSearch {
+    fn name(&self) -> &'static str {
+        "SearXNG"
+    }
+
+    fn endpoint(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn search(&self, query: &str, count: usize) -> Result<Vec<SearchHit>> {
+        let url = search_url(
+            &self.base_url,
+            "search",
+            &[("q", query), ("format", "json")],
+        )?;
+        let body = get_json(client()?.get(url)).await?;
+        Ok(parse_searxng(&body)?.into_iter().take(count).collect())
+    }
+}
+
+fn parse_searxng(body: &str) -> Result<Vec<SearchHit>> {
+    let response: SearxResponse = serde_json::from_str(body).map_err(|e| {
+        eyre!(
+            "无法解析 SearXNG 响应（实例需在 settings.yml 的 search.formats 中开启 json）: {}",
+            e
+        )
+    })?;
+    Ok(response
+        .results
+        .into_iter()
+        .filter_map(RawHit::into_hit)
+        .collect())
+}
+
+// ─── 公共 ─────────────────────────────────────────────────────────────────
+
+/// 两个后端的结果条目字段相同，只是摘要字段名不同
+#[derive(Deserialize)]
+struct RawHit {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    url: String,
+    /// Brave
+    #[serde(default)]
+    description: Option<String>,
+    /// SearXNG
+    #[serde(default)]
+    content: Option<String>,
+}
+
+impl RawHit {
+    fn into_hit(self) -> Option<SearchHit> {
+        if self.url.is_empty() {
+            return None;
+        }
+        let snippet = self.description.or(self.content).unwrap_or_default();
+        Some(SearchHit {
+            title: clean_text(&self.title),
+            url: self.url,
+            snippet: truncate_chars(&clean_text(&snippet), SNIPPET_MAX_CHARS),
+        })
+    }
+}
+
+/// 去掉高亮标签（Brave 摘要带 `<strong>`）并合并空白
+fn clean_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+/// `base_url` 下的接口地址，参数经 URL 编码
+fn search_url(base_url: &str, path: &str, params: &[(&str, &str)]) -> Result<url::Url> {
+    let endpoint = format!("{}/{}", base_url.trim_end_matches('/'), path);
+    url::Url::parse_with_params(&endpoint, params)
+        .map_err(|e| eyre!("无效的搜索后端地址 {}: {}", base_url, e))
+}
+
+/// 禁用自动重定向：重定向目标不会再次经过 SSRF 检查
+fn client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(SEARCH_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(USER_AGENT)
+        .build()
+        .map_err(|e| eyre!("构建 HTTP client 失败: {}", e))
+}
+
+async fn get_json(request: reqwest::RequestBuilder) -> Result<String> {
+    let response = request.send().await.map_err(|e| eyre!("请求失败: {}", e))?;
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(eyre!(
+            "HTTP {}: {}",
+            status.as_u16(),
+            truncate_chars(body.trim(), 200)
+        ));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::AutonomyLevel;
+
+    const SEARX_BODY: &str = r#"{
+        "query": "rust async",
+        "results": [
+            {"title": "Async Book", "url": "https://rust-lang.github.io/async-book/", "content": "Asynchronous  Programming in Rust", "score": 9.1},
+            {"title": "No URL", "url": "", "content": "skipped"},
+            {"title": "Tokio", "url": "https://tokio.rs", "content": "An asynchronous runtime", "score": 4.0},
+            {"title": "async-std", "url": "https://async.rs", "score": 1.0}
+        ]
+    }"#;
+
+    const BRAVE_BODY: &str = r#"{
+        "type": "search",
+        "web": {"results": [
+            {"title": "The <strong>Rust</strong> Book", "url": "https://doc.rust-lang.org/book/", "description": "Learn <strong>Rust</strong> step by step"}
+        ]}
+    }"#;
+
+    fn full_policy() -> SecurityPolicy {
+        SecurityPolicy {
+            autonomy: AutonomyLevel::Full,
+            ..Default::default()
+        }
+    }
+
+    fn searx_config(url: String) -> SearchConfig {
+        SearchConfig {
+            backend: Some(SearchBackendKind::Searxng),
+            url: Some(url),
+            ..Default::default()
+        }
+    }
+
+    /// 本地 SearXNG 替身：GET /search?...&format=json 返回固定结果，其余 404
+    async fn spawn_mock_searx() -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let first_line = request.lines().next().unwrap_or("");
+                    let (status, body) = if first_line.starts_with("GET /search?")
+                        && first_line.contains("format=json")
+                    {
+                        ("200 OK", SEARX_BODY)
+                    } else {
+                        ("404 Not Found", "{}")
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn parses_ranked_results_from_both_backends() {
+        let hits = parse_searxng(SEARX_BODY).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert_eq!(
+            hits[0],
+            SearchHit {
+                title: "Async Book".to_string(),
+                url: "https://rust-lang.github.io/async-book/".to_string(),
+                snippet: "Asynchronous Programming in Rust".to_string(),
+            }
+        );
+        assert_eq!(hits[1].url, "https://tokio.rs");
+        assert_eq!(hits[2].snippet, "");
+
+        let hits = parse_brave(BRAVE_BODY).unwrap();
+        assert_eq!(hits[0].title, "The Rust Book");
+        assert_eq!(hits[0].snippet, "Learn Rust step by step");
+        assert!(parse_brave(r#"{"type": "search"}"#).unwrap().is_empty());
+        assert!(parse_searxng("<html>").is_err());
+    }
+
+    #[tokio::test]
+    async fn searches_mock_backend_within_allowlist() {
+        let addr = spawn_mock_searx().await;
+        let tool = WebSearchTool::new(searx_config(format!("http://{}", addr)));
+        let policy = SecurityPolicy {
+            http_allowed_hosts: vec!["127.0.0.1".to_string()],
+            ..full_policy()
+        };
+
+        let result = tool
+            .execute(json!({"query": "rust async", "count": 2}), &policy)
+            .await
+            .unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(
+            result.output,
+            "1. Async Book\n   https://rust-lang.github.io/async-book/\n   Asynchronous Programming in Rust\n\
+             2. Tokio\n   https://tokio.rs\n   An asynchronous runtime"
+        );
+        assert_eq!(result.user_facing.as_deref(), Some("2 条结果 · SearXNG"));
+    }
+
+    #[tokio::test]
+    async fn self_hosted_backend_must_pass_ssrf_allowlist() {
+        let addr = spawn_mock_searx().await;
+        let tool = WebSearchTool::new(searx_config(format!("http://{}", addr)));
+        let result = tool
+            .execute(json!({"query": "rust"}), &full_policy())
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("SSRF"));
+    }
+
+    #[tokio::test]
+    async fn unconfigured_backend_reports_how_to_configure() {
+        let tool = WebSearchTool::new(SearchConfig::default());
+        let result = tool
+            .execute(json!({"query": "rust"}), &full_policy())
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some(NOT_CONFIGURED_MESSAGE));
+
+        let brave_without_key = SearchConfig {
+            backend: Some(SearchBackendKind::Brave),
+            ..Default::default()
+        };
+        assert!(backend_from_config(&brave_without_key)
+            .err()
+            .unwrap()
+            .contains("api_key"));
+    }
+
+    #[test]
+    fn pre_validate_requires_query_and_execution() {
+        let tool = WebSearchTool::new(SearchConfig::default());
+        assert!(tool
+            .pre_validate(&json!({}), &full_policy())
+            .unwrap()
+            .contains("query"));
+        let readonly = SecurityPolicy {
+            autonomy: AutonomyLevel::ReadOnly,
+            ..Default::default()
+        };
+        assert!(tool
+            .pre_validate(&json!({"query": "rust"}), &readonly)
+            .is_some());
+        assert!(tool
+            .pre_validate(&json!({"query": "rust"}), &full_policy())
+            .is_none());
+    }
+}
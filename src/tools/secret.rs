@@ -0,0 +1,617 @@
+//! 密钥工具：从系统钥匙串读取具名密钥
+//!
+//! 任务中途需要凭据（如 API token）时，不必把明文写进 config.toml：
+//! 用户在终端执行 `rrclaw secret set <name>` 写入系统钥匙串（macOS Keychain /
+//! Linux Secret Service），Agent 通过 `secret` 工具按名称读取。
+//!
+//! 默认只告诉模型密钥是否存在与长度，**明文不会返回给模型**；`reveal=true` 才返回明文，
+//! 且只能在 Supervised 模式下由用户逐次确认（与 Full 模式下 shell 只能执行白名单命令同理）。
+//! reveal 调用声明 `requires_approval`：没有审批策略的通道（无人可问）由 Agent 直接拒绝。
+//! 明文一旦返回就进入对话历史，确认提示会明确说明这一点。
+//!
+//! 钥匙串只按名称存取、无法按服务列举，已写入的名称另记在 `<data_dir>/secrets.json`（只有名称，没有值）。
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, eyre, Context, Result};
+use serde_json::json;
+
+use crate::security::SecurityPolicy;
+
+use super::traits::{Tool, ToolResult};
+
+/// 钥匙串条目的服务名
+pub const SERVICE: &str = "rrclaw";
+
+/// 名称索引文件（位于数据目录下）
+pub const INDEX_FILE: &str = "secrets.json";
+
+/// 密钥名称最大长度
+const MAX_NAME_LEN: usize = 64;
+
+/// 钥匙串后端（测试用内存实现替换）
+pub trait Keyring: Send + Sync {
+    /// 读取密钥；不存在时返回 None
+    fn get(&self, name: &str) -> Result<Option<String>>;
+    /// 写入密钥（已存在则覆盖）
+    fn set(&self, name: &str, value: &str) -> Result<()>;
+    /// 删除密钥；不存在时返回 false
+    fn delete(&self, name: &str) -> Result<bool>;
+}
+
+/// 系统钥匙串：macOS 调用 `security`，Linux 调用 `secret-tool`（libsecret）
+///
+/// 明文只经过子进程的 stdin / stdout，不出现在命令行参数里。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemKeyring;
+
+#[cfg(target_os = "macos")]
+impl Keyring for SystemKeyring {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let output = run(
+            "security",
+            &["find-generic-password", "-s", SERVICE, "-a", name, "-w"],
+            None,
+        )?;
+        match output.status.code() {
+            Some(0) => Ok(Some(stdout_value(&output))),
+            // errSecItemNotFound
+            Some(44) => Ok(None),
+            _ => Err(command_error("security find-generic-password", &output)),
+        }
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        // `security -i` 从 stdin 读命令，避免明文出现在进程列表中
+        let command = format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            SERVICE,
+            name,
+            quote(value)
+        );
+        let output = run("security", &["-i"], Some(&command))?;
+        if !output.status.success() || !output.stderr.is_empty() {
+            return Err(command_error("security add-generic-password", &output));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        let output = run(
+            "security",
+            &["delete-generic-password", "-s", SERVICE, "-a", name],
+            None,
+        )?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(44) => Ok(false),
+            _ => Err(command_error("security delete-generic-password", &output)),
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Keyring for SystemKeyring {
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let output = run(
+            "secret-tool",
+            &["lookup", "service", SERVICE, "account", name],
+            None,
+        )?;
+        if output.status.success() {
+            return Ok(Some(stdout_value(&output)));
+        }
+        // 未找到时退出码 1 且没有任何输出
+        if output.stdout.is_empty() && output.stderr.is_empty() {
+            return Ok(None);
+        }
+        Err(command_error("secret-tool lookup", &output))
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<()> {
+        let label = format!("{}: {}", SERVICE, name);
+        let output = run(
+            "secret-tool",
+            &[
+                "store", "--label", &label, "service", SERVICE, "account", name,
+            ],
+            Some(value),
+        )?;
+        if !output.status.success() {
+            return Err(command_error("secret-tool store", &output));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, name: &str) -> Result<bool> {
+        // secret-tool clear 不区分"删除了"与"本来就没有"，先查一次
+        if self.get(name)?.is_none() {
+            return Ok(false);
+        }
+        let output = run(
+            "secret-tool",
+            &["clear", "service", SERVICE, "account", name],
+            None,
+        )?;
+        if !output.status.success() {
+            return Err(command_error("secret-tool clear", &output));
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(not(unix))]
+impl Keyring for SystemKeyring {
+    fn get(&self, _name: &str) -> Result<Option<String>> {
+        bail!("当前平台不支持系统钥匙串")
+    }
+
+    fn set(&self, _name: &str, _value: &str) -> Result<()> {
+        bail!("当前平台不支持系统钥匙串")
+    }
+
+    fn delete(&self, _name: &str) -> Result<bool> {
+        bail!("当前平台不支持系统钥匙串")
+    }
+}
+
+/// 执行钥匙串命令；`stdin` 为写入子进程的内容
+#[cfg_attr(not(unix), allow(dead_code))]
+fn run(program: &str, args: &[&str], stdin: Option<&str>) -> Result<Output> {
+    use std::io::Write;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => eyre!(
+                "未找到 {}，无法访问系统钥匙串（Linux 需安装 libsecret-tools）",
+                program
+            ),
+            _ => eyre!("启动 {} 失败: {}", program, e),
+        })?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input.as_bytes())
+            .wrap_err_with(|| format!("写入 {} 失败", program))?;
+    }
+    child
+        .wait_with_output()
+        .wrap_err_with(|| format!("等待 {} 退出失败", program))
+}
+
+/// 命令输出的密钥值（去掉末尾换行）
+#[cfg_attr(not(unix), allow(dead_code))]
+fn stdout_value(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches(['\r', '\n'])
+        .to_string()
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn command_error(what: &str, output: &Output) -> color_eyre::eyre::Report {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    eyre!("{} 失败（{}）: {}", what, output.status, stderr.trim())
+}
+
+/// `security -i` 命令行中的双引号字符串
+#[cfg(target_os = "macos")]
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// 密钥名称：字母、数字、`_` `-` `.`，最长 64 个字符
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        bail!("密钥名称长度需在 1~{} 个字符之间", MAX_NAME_LEN);
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        bail!("密钥名称只能包含字母、数字、_ - .: {}", name);
+    }
+    Ok(())
+}
+
+/// 钥匙串 + 名称索引
+#[derive(Clone)]
+pub struct SecretStore {
+    keyring: Arc<dyn Keyring>,
+    index_path: PathBuf,
+}
+
+impl SecretStore {
+    pub fn new(keyring: Arc<dyn Keyring>, data_dir: &Path) -> Self {
+        Self {
+            keyring,
+            index_path: data_dir.join(INDEX_FILE),
+        }
+    }
+
+    /// 使用系统钥匙串
+    pub fn system(data_dir: &Path) -> Self {
+        Self::new(Arc::new(SystemKeyring), data_dir)
+    }
+
+    /// 写入密钥并记录名称
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        validate_name(name)?;
+        if value.is_empty() {
+            bail!("密钥值不能为空");
+        }
+        if value.contains(['\n', '\r']) {
+            bail!("密钥值不能包含换行");
+        }
+        self.keyring.set(name, value)?;
+        let mut names = self.names();
+        if names.insert(name.to_string()) {
+            self.write_index(&names)?;
+        }
+        Ok(())
+    }
+
+    /// 读取密钥；不存在时返回 None
+    pub fn get(&self, name: &str) -> Result<Option<String>> {
+        validate_name(name)?;
+        self.keyring.get(name)
+    }
+
+    /// 删除密钥与名称记录；不存在时返回 false
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        validate_name(name)?;
+        let existed = self.keyring.delete(name)?;
+        let mut names = self.names();
+        if names.remove(name) {
+            self.write_index(&names)?;
+        }
+        Ok(existed)
+    }
+
+    /// 已记录的密钥名称（索引缺失或损坏时为空）
+    pub fn names(&self) -> BTreeSet<String> {
+        std::fs::read_to_string(&self.index_path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_index(&self, names: &BTreeSet<String>) -> Result<()> {
+        if let Some(dir) = self.index_path.parent() {
+            std::fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("创建数据目录失败: {}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(names)?;
+        std::fs::write(&self.index_path, json + "\n")
+            .wrap_err_with(|| format!("写入 {} 失败", self.index_path.display()))
+    }
+}
+
+/// 读取具名密钥的工具
+pub struct SecretTool {
+    store: SecretStore,
+}
+
+impl SecretTool {
+    pub fn new(store: SecretStore) -> Self {
+        Self { store }
+    }
+}
+
+fn reveal_requested(args: &serde_json::Value) -> bool {
+    args.get("reveal")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// reveal 需要用户逐次确认：只有 Supervised 模式会弹出确认
+fn reveal_rejection(args: &serde_json::Value, policy: &SecurityPolicy) -> Option<String> {
+    (reveal_requested(args) && !policy.requires_confirmation()).then(|| {
+        "reveal=true 会把密钥明文交给模型，需要用户逐次确认，只能在 Supervised 模式下使用；\
+         当前模式请改为不带 reveal 调用，或让用户自行处理该凭据"
+            .to_string()
+    })
+}
+
+fn failure(reason: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        output: String::new(),
+        error: Some(reason),
+        ..Default::default()
+    }
+}
+
+#[async_trait]
+impl Tool for SecretTool {
+    fn name(&self) -> &str {
+        "secret"
+    }
+
+    fn description(&self) -> &str {
+        "读取用户保存在系统钥匙串中的具名密钥（API token 等）。\
+         action=list 列出已保存的名称；action=get 默认只返回密钥是否存在与长度，不返回明文。\
+         确实需要明文时传 reveal=true：仅 Supervised 模式可用，每次都需要用户确认，明文会进入对话历史。\
+         密钥不存在时请用户在终端运行 `rrclaw secret set <name>` 写入，不要让用户把密钥贴进对话。"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["list", "get"],
+                    "description": "list: 列出已保存的密钥名称；get: 查询指定密钥"
+                },
+                "name": {
+                    "type": "string",
+                    "description": "密钥名称（action=get 时必填）"
+                },
+                "reveal": {
+                    "type": "boolean",
+                    "description": "返回明文（需要用户确认），默认 false"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    fn pre_validate(&self, args: &serde_json::Value, policy: &SecurityPolicy) -> Option<String> {
+        reveal_rejection(args, policy)
+    }
+
+    fn confirmation_preview(
+        &self,
+        args: &serde_json::Value,
+        _policy: &SecurityPolicy,
+    ) -> Option<String> {
+        let name = args.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        reveal_requested(args)
+            .then(|| format!("⚠ 将把密钥 '{}' 的明文返回给模型，明文会写入对话历史", name))
+    }
+
+    fn requires_approval(&self, args: &serde_json::Value) -> bool {
+        // 没有确认通道（如 Telegram）时由 Agent 直接拒绝
+        reveal_requested(args)
+    }
+
+    fn cacheable(&self, _args: &serde_json::Value) -> bool {
+        // 用户可能在对话中途用 `rrclaw secret set` 更新
+        false
+    }
+
+    async fn execute(
+        &self,
+        args: serde_json::Value,
+        policy: &SecurityPolicy,
+    ) -> Result<ToolResult> {
+        // pre_validate 之外再检查一次：执行入口不一定经过预验证
+        if let Some(reason) = reveal_rejection(&args, policy) {
+            return Ok(failure(reason));
+        }
+        let store = self.store.clone();
+        match args.get("action").and_then(|v| v.as_str()) {
+            Some("list") => {
+                let names = store.names();
+                let output = if names.is_empty() {
+                    "尚未保存任何密钥（用户可运行 `rrclaw secret set <name>` 添加）".to_string()
+                } else {
+                    format!(
+                        "已保存的密钥:\n{}",
+                        names
+                            .iter()
+                            .map(|n| format!("- {}", n))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    )
+                };
+                Ok(ToolResult {
+                    success: true,
+                    output,
+                    ..Default::default()
+                })
+            }
+            Some("get") => {
+                let Some(name) = args
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .filter(|n| !n.is_empty())
+                    .map(str::to_string)
+                else {
+                    return Ok(failure("缺少 name 参数".to_string()));
+                };
+                // 钥匙串可能弹出系统授权对话框，放到阻塞线程池执行
+                let lookup = {
+                    let name = name.clone();
+                    tokio::task::spawn_blocking(move || store.get(&name)).await?
+                };
+                let value = match lookup {
+                    Ok(Some(value)) => value,
+                    Ok(None) => {
+                        return Ok(failure(format!(
+                            "密钥 '{}' 未设置，请用户在终端运行 `rrclaw secret set {}` 写入",
+                            name, name
+                        )))
+                    }
+                    Err(e) => return Ok(failure(format!("读取密钥失败: {:#}", e))),
+                };
+                if reveal_requested(&args) {
+                    return Ok(ToolResult {
+                        success: true,
+                        output: value,
+                        user_facing: Some(format!("已向模型返回密钥 '{}' 的明文", name)),
+                        ..Default::default()
+                    });
+                }
+                Ok(ToolResult {
+                    success: true,
+                    output: format!(
+                        "密钥 '{}' 已设置（{} 个字符）。明文未返回；确实需要时用 reveal=true 再次调用（需要用户确认）",
+                        name,
+                        value.chars().count()
+                    ),
+                    ..Default::default()
+                })
+            }
+            Some(other) => Ok(failure(format!(
+                "未知 action: {}（可选 list / get）",
+                other
+            ))),
+            None => Ok(failure("缺少 action 参数".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::security::AutonomyLevel;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// 内存钥匙串
+    #[derive(Default)]
+    struct MockKeyring {
+        entries: Mutex<HashMap<String, String>>,
+    }
+
+    impl Keyring for MockKeyring {
+        fn get(&self, name: &str) -> Result<Option<String>> {
+            Ok(self.entries.lock().unwrap().get(name).cloned())
+        }
+
+        fn set(&self, name: &str, value: &str) -> Result<()> {
+            self.entries
+                .lock()
+                .unwrap()
+                .insert(name.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn delete(&self, name: &str) -> Result<bool> {
+            Ok(self.entries.lock().unwrap().remove(name).is_some())
+        }
+    }
+
+    const TOKEN: &str = "ghp_0123456789abcdefghij";
+
+    fn store(dir: &Path) -> SecretStore {
+        SecretStore::new(Arc::new(MockKeyring::default()), dir)
+    }
+
+    fn policy(autonomy: AutonomyLevel) -> SecurityPolicy {
+        SecurityPolicy {
+            autonomy,
+            ..SecurityPolicy::default()
+        }
+    }
+
+    #[test]
+    fn set_get_remove_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+
+        store.set("github_token", TOKEN).unwrap();
+        store.set("openai", "sk-test-value").unwrap();
+        // 覆盖写入不重复记录名称
+        store.set("github_token", TOKEN).unwrap();
+        assert_eq!(store.get("github_token").unwrap().as_deref(), Some(TOKEN));
+        assert_eq!(
+            store.names().into_iter().collect::<Vec<_>>(),
+            vec!["github_token", "openai"]
+        );
+        // 索引只有名称，没有值
+        let index = std::fs::read_to_string(dir.path().join(INDEX_FILE)).unwrap();
+        assert!(!index.contains(TOKEN));
+
+        assert!(store.remove("github_token").unwrap());
+        assert!(!store.remove("github_token").unwrap());
+        assert_eq!(store.get("github_token").unwrap(), None);
+        assert_eq!(store.names().len(), 1);
+
+        assert!(store.set("bad name", "x").is_err());
+        assert!(store.set("../etc", "x").is_err());
+        assert!(store.set("multi", "a\nb").is_err());
+        assert!(store.set("empty", "").is_err());
+    }
+
+    #[tokio::test]
+    async fn get_hides_value_unless_revealed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+        store.set("github_token", TOKEN).unwrap();
+        let tool = SecretTool::new(store);
+        let supervised = policy(AutonomyLevel::Supervised);
+
+        let result = tool
+            .execute(
+                json!({"action": "get", "name": "github_token"}),
+                &supervised,
+            )
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert!(!result.output.contains(TOKEN), "{}", result.output);
+        assert!(result.output.contains("24 个字符"));
+
+        let result = tool
+            .execute(json!({"action": "list"}), &supervised)
+            .await
+            .unwrap();
+        assert!(result.output.contains("- github_token"));
+
+        let result = tool
+            .execute(json!({"action": "get", "name": "missing"}), &supervised)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.error.unwrap().contains("rrclaw secret set missing"));
+    }
+
+    #[tokio::test]
+    async fn reveal_requires_supervised_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path());
+        store.set("github_token", TOKEN).unwrap();
+        let tool = SecretTool::new(store);
+        let reveal = json!({"action": "get", "name": "github_token", "reveal": true});
+
+        // Full / ReadOnly 不会弹确认：预验证与执行都拒绝
+        for autonomy in [AutonomyLevel::Full, AutonomyLevel::ReadOnly] {
+            let policy = policy(autonomy);
+            assert!(tool.pre_validate(&reveal, &policy).is_some());
+            let result = tool.execute(reveal.clone(), &policy).await.unwrap();
+            assert!(!result.success);
+            assert!(!result.output.contains(TOKEN));
+        }
+
+        // Supervised：通过预验证，确认提示说明明文会交给模型；无审批策略的通道由 Agent 拒绝
+        let supervised = policy(AutonomyLevel::Supervised);
+        assert!(tool.pre_validate(&reveal, &supervised).is_none());
+        assert!(tool.requires_approval(&reveal));
+        assert!(!tool.requires_approval(&json!({"action": "list"})));
+        let preview = tool.confirmation_preview(&reveal, &supervised).unwrap();
+        assert!(preview.contains("github_token") && preview.contains("明文"));
+        assert!(tool
+            .confirmation_preview(
+                &json!({"action": "get", "name": "github_token"}),
+                &supervised
+            )
+            .is_none());
+
+        let result = tool.execute(reveal, &supervised).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, TOKEN);
+    }
+}
@@ -7,7 +7,7 @@ use serde_json::json;
 use crate::config::Config;
 use crate::security::SecurityPolicy;
 
-use super::traits::{Tool, ToolResult};
+use super::traits::{Tool, ToolResult, ToolRisk};
 
 /// Agent 自我信息查询工具（纯读取，无副作用）
 pub struct SelfInfoTool {
@@ -116,6 +116,14 @@ impl SelfInfoTool {
 
         let mut lines = Vec::new();
         lines.push(format!("Database Size: {}", db_size));
+        lines.push(format!(
+            "Persistence: {}",
+            if crate::memory::is_degraded() {
+                "degraded (memory store unavailable or writes failing; this session is not being saved)"
+            } else {
+                "ok"
+            }
+        ));
         lines.push(format!(
             "Configured Providers: {}",
             self.config.providers.len()
@@ -164,6 +172,10 @@ impl Tool for SelfInfoTool {
         })
     }
 
+    fn risk(&self) -> ToolRisk {
+        ToolRisk::Read
+    }
+
     async fn execute(
         &self,
         args: serde_json::Value,
@@ -209,7 +221,7 @@ fn mask_api_key(key: &str) -> String {
 }
 
 /// 格式化字节数
-fn format_bytes(bytes: u64) -> String {
+pub fn format_bytes(bytes: u64) -> String {
     if bytes < 1024 {
         format!("{} B", bytes)
     } else if bytes < 1024 * 1024 {
@@ -237,6 +249,12 @@ mod tests {
                 api_key: "sk-secret-key-12345".to_string(),
                 model: "deepseek-chat".to_string(),
                 auth_style: None,
+                headers: Default::default(),
+                endpoint_path: None,
+                max_tools: None,
+                max_tool_schema_bytes: None,
+                context_window: None,
+                max_tokens: None,
             },
         );
         Config {
@@ -253,6 +271,13 @@ mod tests {
             reliability: crate::config::ReliabilityConfig::default(),
             mcp: None,
             routines: RoutinesConfig::default(),
+            cli: Default::default(),
+            agent: Default::default(),
+            daemon: Default::default(),
+            dev: Default::default(),
+            update: Default::default(),
+            search: Default::default(),
+            pricing: Default::default(),
         }
     }
 
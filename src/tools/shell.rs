@@ -1,10 +1,12 @@
 use async_trait::async_trait;
 use color_eyre::eyre::{Context, Result};
-use std::time::Duration;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
 use tokio::process::Command;
 
 use crate::security::SecurityPolicy;
 
+use super::process::ProcessRegistry;
 use super::traits::{Tool, ToolResult};
 
 /// Shell 命令执行工具
@@ -12,6 +14,18 @@ pub struct ShellTool;
 
 const SHELL_TIMEOUT: Duration = Duration::from_secs(120);
 
+/// 输出明显随时间变化的命令（重复调用不返回缓存结果）
+const TIME_DEPENDENT_COMMANDS: &[&str] = &["date", "ps", "top", "uptime", "free", "who", "w"];
+
+/// 命令的任一管道/串联段以时间相关命令开头
+fn is_time_dependent(command: &str) -> bool {
+    command
+        .split(['|', ';', '&', '\n'])
+        .filter_map(|segment| segment.split_whitespace().next())
+        .map(|program| program.rsplit('/').next().unwrap_or(program))
+        .any(|program| TIME_DEPENDENT_COMMANDS.contains(&program))
+}
+
 #[async_trait]
 impl Tool for ShellTool {
     fn name(&self) -> &str {
@@ -52,6 +66,12 @@ impl Tool for ShellTool {
         None
     }
 
+    fn cacheable(&self, args: &serde_json::Value) -> bool {
+        args.get("command")
+            .and_then(|v| v.as_str())
+            .is_none_or(|command| !is_time_dependent(command))
+    }
+
     async fn execute(
         &self,
         args: serde_json::Value,
@@ -83,16 +103,32 @@ impl Tool for ShellTool {
             });
         }
 
-        // 执行命令
-        let result = tokio::time::timeout(
-            SHELL_TIMEOUT,
-            Command::new("sh")
-                .arg("-c")
-                .arg(command)
-                .current_dir(&policy.workspace_dir)
-                .output(),
-        )
-        .await;
+        // 执行命令（登记到进程表，供 /ps、/kill 查看与终止）
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .current_dir(&policy.workspace_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        // 独立进程组：/kill 与超时可一并终止 sh 派生的子进程
+        #[cfg(unix)]
+        cmd.process_group(0);
+        let started = Instant::now();
+        let child = cmd.spawn().wrap_err("执行命令失败")?;
+        let tracked = child
+            .id()
+            .map(|pid| ProcessRegistry::global().register(pid, command));
+
+        let result = tokio::time::timeout(SHELL_TIMEOUT, child.wait_with_output()).await;
+        if result.is_err() {
+            if let Some(tracked) = &tracked {
+                tracked.kill();
+            }
+        }
+        drop(tracked);
+        let duration_ms = Some(started.elapsed().as_millis() as u64);
 
         match result {
             Ok(Ok(output)) => {
@@ -110,19 +146,29 @@ impl Tool for ShellTool {
                     };
                     Ok(ToolResult {
                         success: true,
+                        user_facing: Some(shell_summary(output.status.code(), &combined)),
                         output: combined,
                         error: None,
+                        exit_code: output.status.code(),
+                        duration_ms,
                         ..Default::default()
                     })
                 } else {
+                    // 无退出码说明被信号终止（如用户 /kill）
+                    let reason = match output.status.code() {
+                        Some(code) => format!("Command exited with code: {}", code),
+                        None => "Command was killed".to_string(),
+                    };
                     Ok(ToolResult {
                         success: false,
-                        output: stdout,
-                        error: Some(format!(
-                            "Command exited with code: {}\n{}",
-                            output.status.code().unwrap_or(-1),
-                            stderr
+                        user_facing: Some(shell_summary(
+                            output.status.code(),
+                            &format!("{}\n{}", stdout, stderr),
                         )),
+                        output: stdout,
+                        error: Some(format!("{}\n{}", reason, stderr)),
+                        exit_code: output.status.code(),
+                        duration_ms,
                         ..Default::default()
                     })
                 }
@@ -132,12 +178,29 @@ impl Tool for ShellTool {
                 success: false,
                 output: String::new(),
                 error: Some(format!("Command timed out ({}s)", SHELL_TIMEOUT.as_secs())),
+                duration_ms,
+                user_facing: Some(format!("timed out after {}s", SHELL_TIMEOUT.as_secs())),
                 ..Default::default()
             }),
         }
     }
 }
 
+/// 给用户看的摘要：退出码 + 最后 3 行非空输出
+fn shell_summary(exit_code: Option<i32>, output: &str) -> String {
+    let status = match exit_code {
+        Some(code) => format!("exit {}", code),
+        None => "killed".to_string(),
+    };
+    let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
+    let tail = &lines[lines.len().saturating_sub(3)..];
+    if tail.is_empty() {
+        status
+    } else {
+        format!("{}\n{}", status, tail.join("\n"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -174,6 +237,46 @@ mod tests {
         assert_eq!(result.output.trim(), "hello");
     }
 
+    #[tokio::test]
+    async fn shell_reports_exit_code_and_duration() {
+        let tmp = tempfile::tempdir().unwrap();
+        let policy = test_policy(tmp.path());
+
+        let ok = ShellTool
+            .execute(serde_json::json!({"command": "echo hi"}), &policy)
+            .await
+            .unwrap();
+        assert_eq!(ok.exit_code, Some(0));
+        assert!(ok.duration_ms.is_some());
+
+        let failed = ShellTool
+            .execute(serde_json::json!({"command": "cat missing.txt"}), &policy)
+            .await
+            .unwrap();
+        assert!(!failed.success);
+        assert_eq!(failed.exit_code, Some(1));
+        assert!(failed.duration_ms.is_some());
+        assert!(failed.error.unwrap().contains("exited with code: 1"));
+    }
+
+    #[tokio::test]
+    async fn shell_user_facing_has_exit_code_and_tail() {
+        let tmp = tempfile::tempdir().unwrap();
+        let policy = test_policy(tmp.path());
+
+        let result = ShellTool
+            .execute(
+                serde_json::json!({"command": "echo a; echo b; echo c; echo d"}),
+                &policy,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.user_facing.as_deref(), Some("exit 0\nb\nc\nd"));
+        // LLM 可见输出保持完整
+        assert_eq!(result.output.trim(), "a\nb\nc\nd");
+    }
+
     #[tokio::test]
     async fn shell_rejects_disallowed_command() {
         let tmp = tempfile::tempdir().unwrap();
@@ -228,6 +331,16 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn time_dependent_commands_are_not_cacheable() {
+        for command in ["date", "ps aux | grep rrclaw", "cd src && /bin/date +%s"] {
+            assert!(!ShellTool.cacheable(&serde_json::json!({ "command": command })));
+        }
+        for command in ["ls -la", "cat update.txt", "grep ps README.md"] {
+            assert!(ShellTool.cacheable(&serde_json::json!({ "command": command })));
+        }
+    }
+
     #[test]
     fn shell_spec() {
         let spec = ShellTool.spec();
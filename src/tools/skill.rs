@@ -5,7 +5,7 @@ use serde_json::json;
 use crate::security::SecurityPolicy;
 use crate::skills::{load_skill_content, SkillMeta};
 
-use super::traits::{Tool, ToolResult};
+use super::traits::{Tool, ToolResult, ToolRisk};
 
 /// LLM 通过调用此工具按需加载技能的 L2 指令
 pub struct SkillTool {
@@ -46,6 +46,10 @@ impl Tool for SkillTool {
         })
     }
 
+    fn risk(&self) -> ToolRisk {
+        ToolRisk::Read
+    }
+
     async fn execute(
         &self,
         args: serde_json::Value,
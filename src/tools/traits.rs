@@ -13,6 +13,65 @@ pub struct ToolResult {
     pub error: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub config_suggestion: Option<String>,
+    /// 结构化输出元数据（仅供 Channel 渲染；LLM 只看到 `output` 纯文本）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ToolOutputKind>,
+    /// 进程退出码（ShellTool；被信号终止或超时时为 None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// 执行耗时（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// 给用户看的简短摘要（CLI 按 `[cli] tool_verbosity` 显示在状态行下方；不进入 LLM 可见内容）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_facing: Option<String>,
+    /// 后续建议（最多 3 条短句，如"查看 diff"）；本轮结束后由 UI 显示为快捷回复，不进入 LLM 可见内容
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+impl ToolResult {
+    /// 执行元数据行（附在 LLM 可见结果末尾），无元数据时返回 None
+    ///
+    /// 格式：`[exit_code=1 duration_ms=42]`
+    pub fn metadata_line(&self) -> Option<String> {
+        let mut fields = Vec::new();
+        if let Some(code) = self.exit_code {
+            fields.push(format!("exit_code={}", code));
+        }
+        if let Some(ms) = self.duration_ms {
+            fields.push(format!("duration_ms={}", ms));
+        }
+        (!fields.is_empty()).then(|| format!("[{}]", fields.join(" ")))
+    }
+}
+
+/// 工具输出的结构化类型
+///
+/// `output` 始终是纯文本降级版本，`kind` 让 CLI/Telegram 选择更合适的渲染方式。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolOutputKind {
+    /// unified diff（内容即 `output`）
+    Diff,
+    /// 表格
+    Table {
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    /// 文件引用
+    FileRef { path: String, bytes: u64 },
+    /// JSON 数据
+    Json { value: serde_json::Value },
+}
+
+/// 工具风险分类：`/ask` 只读回合只向模型暴露、也只允许执行 `Read` 类工具
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolRisk {
+    /// 只读取信息，不修改文件、配置、记忆，也不访问外部系统
+    Read,
+    /// 可能写入或产生副作用（默认；未标注的 MCP 工具也按此处理）
+    Write,
 }
 
 /// 工具抽象
@@ -30,6 +89,32 @@ pub trait Tool: Send + Sync {
         None
     }
 
+    /// 确认提示中展示的预览（如覆盖文件的 diff），None 表示只展示参数
+    fn confirmation_preview(
+        &self,
+        _args: &serde_json::Value,
+        _policy: &SecurityPolicy,
+    ) -> Option<String> {
+        None
+    }
+
+    /// 本次调用是否必须经过用户确认（如向模型返回密钥明文）
+    /// 为 true 而 Agent 没有审批策略（无人可问）时，Agent 直接拒绝执行
+    fn requires_approval(&self, _args: &serde_json::Value) -> bool {
+        false
+    }
+
+    /// 风险分类（默认 Write，只读工具显式覆盖）
+    fn risk(&self) -> ToolRisk {
+        ToolRisk::Write
+    }
+
+    /// 本轮内相同参数的重复调用能否直接返回缓存结果（默认 true）
+    /// 结果随时间变化的调用（如 `date`、`ps`、记忆检索）应返回 false
+    fn cacheable(&self, _args: &serde_json::Value) -> bool {
+        true
+    }
+
     /// 懒加载：将此工具升级为完整 L2 schema（默认无操作）
     /// MCP 懒加载工具覆盖此方法，在首次调用后自动升级 schema
     fn load_full_schema(&mut self) {}
@@ -48,3 +133,52 @@ pub trait Tool: Send + Sync {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_result_without_kind_deserializes() {
+        // 旧格式（无 kind 字段）必须仍可解析
+        let json = r#"{"success":true,"output":"ok","error":null}"#;
+        let result: ToolResult = serde_json::from_str(json).unwrap();
+        assert!(result.kind.is_none());
+    }
+
+    #[test]
+    fn tool_result_none_kind_not_serialized() {
+        let result = ToolResult {
+            success: true,
+            output: "ok".to_string(),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(!json.contains("kind"));
+    }
+
+    #[test]
+    fn metadata_line_lists_present_fields() {
+        let mut result = ToolResult::default();
+        assert_eq!(result.metadata_line(), None);
+        result.duration_ms = Some(42);
+        assert_eq!(result.metadata_line().as_deref(), Some("[duration_ms=42]"));
+        result.exit_code = Some(1);
+        assert_eq!(
+            result.metadata_line().as_deref(),
+            Some("[exit_code=1 duration_ms=42]")
+        );
+    }
+
+    #[test]
+    fn tool_output_kind_roundtrip() {
+        let kind = ToolOutputKind::Table {
+            headers: vec!["a".to_string()],
+            rows: vec![vec!["1".to_string()]],
+        };
+        let json = serde_json::to_string(&kind).unwrap();
+        assert!(json.contains("\"type\":\"table\""));
+        let parsed: ToolOutputKind = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, kind);
+    }
+}
@@ -0,0 +1,178 @@
+//! 文件覆盖前快照（`/undo-file` 恢复）
+//!
+//! FileWriteTool 覆盖已有文件前，把原内容写到 `~/.rrclaw/data/undo/<时间戳>-<文件名>`。
+//! 只保留最近 `MAX_UNDO_ENTRIES` 份，超出后删除最旧的。快照名只含文件名，
+//! 恢复时按文件名匹配最新一份（不同目录下的同名文件共享同一序列）。
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context, Result};
+
+/// 最多保留的快照数
+pub const MAX_UNDO_ENTRIES: usize = 50;
+
+/// 时间戳前缀格式（定长，按文件名排序即按时间排序）
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+/// `TIMESTAMP_FORMAT` 输出长度：`20261016-143000123`
+const TIMESTAMP_LEN: usize = 18;
+
+/// 快照目录
+#[derive(Debug, Clone)]
+pub struct UndoStore {
+    dir: PathBuf,
+    capacity: usize,
+}
+
+impl UndoStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            capacity: MAX_UNDO_ENTRIES,
+        }
+    }
+
+    /// 指定保留份数（测试用）
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// 保存 `path` 的原内容，返回快照路径
+    pub fn save(&self, path: &Path, content: &[u8]) -> Result<PathBuf> {
+        let file_name = file_name(path)?;
+        std::fs::create_dir_all(&self.dir)
+            .wrap_err_with(|| format!("创建 undo 目录失败: {}", self.dir.display()))?;
+        let mut timestamp = chrono::Local::now().format(TIMESTAMP_FORMAT).to_string();
+        // 同一毫秒内重复保存同一文件时递增，保持名称唯一且有序
+        for _ in 0..1000 {
+            if !self
+                .dir
+                .join(format!("{}-{}", timestamp, file_name))
+                .exists()
+            {
+                break;
+            }
+            timestamp = bump(&timestamp);
+        }
+        let snapshot = self.dir.join(format!("{}-{}", timestamp, file_name));
+        std::fs::write(&snapshot, content)
+            .wrap_err_with(|| format!("写入快照失败: {}", snapshot.display()))?;
+        self.prune();
+        Ok(snapshot)
+    }
+
+    /// `path` 最新的快照
+    pub fn latest_for(&self, path: &Path) -> Option<PathBuf> {
+        let file_name = file_name(path).ok()?;
+        self.entries()
+            .into_iter()
+            .rev()
+            .find(|entry| snapshot_target(entry) == Some(file_name))
+    }
+
+    /// 用最新快照覆盖 `path` 并删除该快照（再次执行会恢复更早的一份），返回所用快照
+    pub fn restore(&self, path: &Path) -> Result<PathBuf> {
+        let snapshot = self
+            .latest_for(path)
+            .ok_or_else(|| eyre!("没有 {} 的快照", path.display()))?;
+        std::fs::copy(&snapshot, path).wrap_err_with(|| format!("恢复失败: {}", path.display()))?;
+        let _ = std::fs::remove_file(&snapshot);
+        Ok(snapshot)
+    }
+
+    /// 全部快照，按时间从旧到新
+    fn entries(&self) -> Vec<PathBuf> {
+        let Ok(read_dir) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut entries: Vec<PathBuf> = read_dir
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && snapshot_target(p).is_some())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    /// 删除超出保留份数的最旧快照
+    fn prune(&self) {
+        let entries = self.entries();
+        let excess = entries.len().saturating_sub(self.capacity);
+        for old in &entries[..excess] {
+            let _ = std::fs::remove_file(old);
+        }
+    }
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| eyre!("无效的文件路径: {}", path.display()))
+}
+
+/// 快照对应的原文件名（名称不符合 `<时间戳>-<文件名>` 时为 None）
+fn snapshot_target(snapshot: &Path) -> Option<&str> {
+    let name = snapshot.file_name()?.to_str()?;
+    let (timestamp, rest) = name.split_at_checked(TIMESTAMP_LEN)?;
+    if !timestamp.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    rest.strip_prefix('-').filter(|n| !n.is_empty())
+}
+
+/// 时间戳末位毫秒 +1（仅用于同一毫秒内的冲突）
+fn bump(timestamp: &str) -> String {
+    let (head, millis) = timestamp.split_at(TIMESTAMP_LEN - 3);
+    let millis: u32 = millis.parse().unwrap_or(0);
+    format!("{}{:03}", head, (millis + 1).min(999))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_uses_latest_snapshot_then_older_one() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = UndoStore::new(tmp.path().join("undo"));
+        let target = tmp.path().join("main.rs");
+
+        store.save(&target, b"v1").unwrap();
+        store.save(&target, b"v2").unwrap();
+        store.save(&tmp.path().join("other.rs"), b"x").unwrap();
+        std::fs::write(&target, "v3").unwrap();
+
+        store.restore(&target).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "v2");
+        store.restore(&target).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "v1");
+        assert!(store.restore(&target).is_err());
+    }
+
+    #[test]
+    fn keeps_only_most_recent_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = UndoStore::new(tmp.path().to_path_buf()).with_capacity(2);
+        let target = tmp.path().join("a.txt");
+        for content in ["1", "2", "3"] {
+            store.save(&target, content.as_bytes()).unwrap();
+        }
+        let entries = store.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(std::fs::read_to_string(&entries[0]).unwrap(), "2");
+        assert_eq!(
+            std::fs::read_to_string(store.latest_for(&target).unwrap()).unwrap(),
+            "3"
+        );
+    }
+
+    #[test]
+    fn snapshot_names_map_back_to_file_names() {
+        assert_eq!(
+            snapshot_target(Path::new("/u/20261016-143000123-main.rs")),
+            Some("main.rs")
+        );
+        assert_eq!(snapshot_target(Path::new("/u/notes.txt")), None);
+        assert_eq!(bump("20261016-143000123"), "20261016-143000124");
+    }
+}
@@ -0,0 +1,69 @@
+//! 发布源：GitHub Releases API（trait 抽象，测试中替换为内存实现）
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, Result, WrapErr};
+
+use super::release::ReleaseInfo;
+
+/// 最新版本查询接口
+pub const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/yzzting/rrclaw/releases/latest";
+
+/// 下载发布包的超时（启动检查另有更短的整体超时）
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 查询最新发布、下载发布包
+#[async_trait]
+pub trait ReleaseSource: Send + Sync {
+    async fn latest(&self) -> Result<ReleaseInfo>;
+    async fn download(&self, url: &str) -> Result<Vec<u8>>;
+}
+
+/// 通过 GitHub API 访问 yzzting/rrclaw 的 Releases
+pub struct GithubReleases {
+    client: reqwest::Client,
+}
+
+impl GithubReleases {
+    pub fn new() -> Result<Self> {
+        let client = reqwest::Client::builder()
+            // GitHub API 要求 User-Agent
+            .user_agent(concat!("rrclaw/", env!("CARGO_PKG_VERSION")))
+            .timeout(DOWNLOAD_TIMEOUT)
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl ReleaseSource for GithubReleases {
+    async fn latest(&self) -> Result<ReleaseInfo> {
+        let resp = self
+            .client
+            .get(LATEST_RELEASE_URL)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await
+            .wrap_err("查询 GitHub Releases 失败")?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!("查询 GitHub Releases 失败: HTTP {}", status);
+        }
+        resp.json().await.wrap_err("解析 GitHub Releases 响应失败")
+    }
+
+    async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        let resp = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .wrap_err_with(|| format!("下载失败: {}", url))?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!("下载失败: HTTP {} ({})", status, url);
+        }
+        Ok(resp.bytes().await?.to_vec())
+    }
+}
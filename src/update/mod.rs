@@ -0,0 +1,472 @@
+//! 新版本检查与 `rrclaw self-update`
+//!
+//! - 启动 REPL 时查询 GitHub 最新发布（每天最多一次，结果缓存在数据目录的 `update_check.json`），
+//!   有新版本则在 banner 下方提示一行；`[update] check = false` 关闭
+//! - `rrclaw self-update`：下载当前平台的发布包，按 `SHA256SUMS` 校验后原子替换当前可执行文件；
+//!   Windows 无法覆盖运行中的 exe，发布包保存到 exe 旁边并提示手动替换
+
+pub mod github;
+pub mod release;
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::config::UpdateConfig;
+pub use github::{GithubReleases, ReleaseSource};
+use release::{asset_name, binary_name_in_archive, is_newer, verify_checksum, CHECKSUMS_ASSET};
+
+/// 当前版本
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 检查结果缓存文件（位于数据目录）
+const CACHE_FILE: &str = "update_check.json";
+
+/// 两次检查的最小间隔
+const CHECK_INTERVAL: chrono::Duration = chrono::Duration::hours(24);
+
+/// 启动检查的整体超时：网络慢时宁可不提示，也不拖慢 REPL 启动
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 上一次检查的时间与结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CheckCache {
+    checked_at: DateTime<Utc>,
+    /// 最新发布的 tag（查询失败时沿用上次的结果）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latest: Option<String>,
+}
+
+fn load_cache(data_dir: &Path) -> Option<CheckCache> {
+    let content = std::fs::read_to_string(data_dir.join(CACHE_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn save_cache(data_dir: &Path, cache: &CheckCache) {
+    let result = serde_json::to_string(cache)
+        .map_err(std::io::Error::other)
+        .and_then(|json| std::fs::write(data_dir.join(CACHE_FILE), json));
+    if let Err(e) = result {
+        debug!("写入 {} 失败: {}", CACHE_FILE, e);
+    }
+}
+
+/// 启动提示：有比当前版本新的发布时返回其 tag
+///
+/// 关闭检查、查询失败或已是最新时返回 None（失败也不影响启动）。
+pub async fn startup_notice(config: &UpdateConfig, data_dir: &Path) -> Option<String> {
+    if !config.check {
+        return None;
+    }
+    let source = GithubReleases::new().ok()?;
+    check_for_update(&source, data_dir, CURRENT_VERSION, Utc::now()).await
+}
+
+/// 距上次检查不足一天时只读缓存，否则查询发布源并刷新缓存
+///
+/// 查询失败同样记录检查时间，离线时不会每次启动都等待超时。
+async fn check_for_update(
+    source: &dyn ReleaseSource,
+    data_dir: &Path,
+    current: &str,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    let cached = load_cache(data_dir);
+    let latest = match cached {
+        Some(cache) if now - cache.checked_at < CHECK_INTERVAL => cache.latest,
+        previous => {
+            let latest = match tokio::time::timeout(CHECK_TIMEOUT, source.latest()).await {
+                Ok(Ok(release)) => Some(release.tag_name),
+                Ok(Err(e)) => {
+                    debug!("检查新版本失败: {:#}", e);
+                    previous.and_then(|c| c.latest)
+                }
+                Err(_) => {
+                    debug!("检查新版本超时");
+                    previous.and_then(|c| c.latest)
+                }
+            };
+            save_cache(
+                data_dir,
+                &CheckCache {
+                    checked_at: now,
+                    latest: latest.clone(),
+                },
+            );
+            latest
+        }
+    };
+    latest.filter(|tag| is_newer(tag, current))
+}
+
+/// `rrclaw self-update` 的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelfUpdateOutcome {
+    /// 已是最新版本
+    UpToDate { latest: String },
+    /// 可执行文件已替换
+    Updated { to: String },
+    /// 无法原地替换（Windows）：发布包已保存到 `path`，需手动解压替换
+    Staged { path: PathBuf, to: String },
+}
+
+/// 下载最新发布并替换 `exe`
+///
+/// 发布包必须出现在 `SHA256SUMS` 中且哈希一致，否则不做任何改动。
+/// `os` / `arch` 取自 `std::env::consts`。
+pub async fn self_update(
+    source: &dyn ReleaseSource,
+    exe: &Path,
+    current: &str,
+    os: &str,
+    arch: &str,
+) -> Result<SelfUpdateOutcome> {
+    let release = source.latest().await?;
+    if !is_newer(&release.tag_name, current) {
+        return Ok(SelfUpdateOutcome::UpToDate {
+            latest: release.tag_name,
+        });
+    }
+
+    let asset = asset_name(os, arch).ok_or_else(|| {
+        eyre!(
+            "{}-{} 没有预编译的发布包，请用 cargo install rrclaw 安装",
+            os,
+            arch
+        )
+    })?;
+    let archive_url = &release
+        .asset(asset)
+        .ok_or_else(|| eyre!("{} 中没有 {}", release.tag_name, asset))?
+        .browser_download_url;
+    let sums_url = &release
+        .asset(CHECKSUMS_ASSET)
+        .ok_or_else(|| {
+            eyre!(
+                "{} 没有发布 {}，无法校验下载内容",
+                release.tag_name,
+                CHECKSUMS_ASSET
+            )
+        })?
+        .browser_download_url;
+
+    let sums = String::from_utf8(source.download(sums_url).await?)
+        .wrap_err_with(|| format!("{} 不是有效的文本", CHECKSUMS_ASSET))?;
+    let archive = source.download(archive_url).await?;
+    verify_checksum(&archive, &sums, asset)?;
+
+    let dir = exe
+        .parent()
+        .ok_or_else(|| eyre!("无法确定可执行文件所在目录: {}", exe.display()))?;
+
+    if asset.ends_with(".zip") {
+        // 运行中的 exe 不能被覆盖：保存发布包，由用户退出后手动替换
+        let path = dir.join(asset);
+        std::fs::write(&path, &archive)
+            .wrap_err_with(|| format!("写入 {} 失败", path.display()))?;
+        return Ok(SelfUpdateOutcome::Staged {
+            path,
+            to: release.tag_name,
+        });
+    }
+
+    let binary = extract_binary(&archive, binary_name_in_archive(asset))?;
+    replace_executable(exe, &binary)?;
+    Ok(SelfUpdateOutcome::Updated {
+        to: release.tag_name,
+    })
+}
+
+/// 从 tar.gz 发布包中取出可执行文件
+fn extract_binary(archive: &[u8], name: &str) -> Result<Vec<u8>> {
+    let mut tar = tar::Archive::new(GzDecoder::new(archive));
+    for entry in tar.entries().wrap_err("解包发布包失败")? {
+        let mut entry = entry.wrap_err("解包发布包失败")?;
+        let path = entry.path()?.into_owned();
+        if path.file_name().is_some_and(|n| n == name) {
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            return Ok(bytes);
+        }
+    }
+    bail!("发布包中没有 {}", name)
+}
+
+/// 写入同目录临时文件后 rename 覆盖，保证 `exe` 要么是旧版本要么是完整的新版本
+fn replace_executable(exe: &Path, binary: &[u8]) -> Result<()> {
+    let dir = exe
+        .parent()
+        .ok_or_else(|| eyre!("无法确定可执行文件所在目录: {}", exe.display()))?;
+    let staging = dir.join(format!(".rrclaw-update-{}", std::process::id()));
+    let hint = || {
+        format!(
+            "无法写入 {}（没有写权限时请用 sudo 重新执行 rrclaw self-update）",
+            dir.display()
+        )
+    };
+
+    std::fs::write(&staging, binary).wrap_err_with(hint)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staging, std::fs::Permissions::from_mode(0o755))?;
+    }
+    if let Err(e) = std::fs::rename(&staging, exe) {
+        let _ = std::fs::remove_file(&staging);
+        return Err(e).wrap_err_with(hint);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use release::{sha256_hex, ReleaseAsset, ReleaseInfo};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 内存中的发布源：固定的最新 tag + 按 URL 返回的文件
+    struct MockSource {
+        tag: Option<String>,
+        files: HashMap<String, Vec<u8>>,
+        queries: AtomicUsize,
+    }
+
+    impl MockSource {
+        fn new(tag: &str) -> Self {
+            Self {
+                tag: Some(tag.to_string()),
+                files: HashMap::new(),
+                queries: AtomicUsize::new(0),
+            }
+        }
+
+        fn offline() -> Self {
+            Self {
+                tag: None,
+                ..Self::new("")
+            }
+        }
+
+        fn with_file(mut self, name: &str, bytes: Vec<u8>) -> Self {
+            self.files.insert(name.to_string(), bytes);
+            self
+        }
+
+        fn queries(&self) -> usize {
+            self.queries.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl ReleaseSource for MockSource {
+        async fn latest(&self) -> Result<ReleaseInfo> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            let tag = self.tag.clone().ok_or_else(|| eyre!("offline"))?;
+            Ok(ReleaseInfo {
+                tag_name: tag,
+                assets: self
+                    .files
+                    .keys()
+                    .map(|name| ReleaseAsset {
+                        name: name.clone(),
+                        browser_download_url: format!("mock://{}", name),
+                    })
+                    .collect(),
+            })
+        }
+
+        async fn download(&self, url: &str) -> Result<Vec<u8>> {
+            let name = url.strip_prefix("mock://").unwrap_or(url);
+            self.files
+                .get(name)
+                .cloned()
+                .ok_or_else(|| eyre!("404: {}", url))
+        }
+    }
+
+    fn tar_gz(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, name, content).unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn linux_release(tag: &str, binary: &[u8], tamper: bool) -> MockSource {
+        let asset = "rrclaw-linux-x86_64.tar.gz";
+        let archive = tar_gz("rrclaw-linux-x86_64", binary);
+        let hash = if tamper {
+            sha256_hex(b"something else")
+        } else {
+            sha256_hex(&archive)
+        };
+        MockSource::new(tag).with_file(asset, archive).with_file(
+            CHECKSUMS_ASSET,
+            format!("{}  {}\n", hash, asset).into_bytes(),
+        )
+    }
+
+    #[tokio::test]
+    async fn checks_at_most_once_per_day() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = MockSource::new("v0.1.0");
+        let now = Utc::now();
+
+        let notice = check_for_update(&source, tmp.path(), "0.0.3", now).await;
+        assert_eq!(notice.as_deref(), Some("v0.1.0"));
+        assert_eq!(source.queries(), 1);
+
+        // 一天内只读缓存
+        let later = now + chrono::Duration::hours(23);
+        let notice = check_for_update(&source, tmp.path(), "0.0.3", later).await;
+        assert_eq!(notice.as_deref(), Some("v0.1.0"));
+        assert_eq!(source.queries(), 1);
+
+        // 升级后缓存中的版本不再提示
+        assert_eq!(
+            check_for_update(&source, tmp.path(), "0.1.0", later).await,
+            None
+        );
+
+        let next_day = now + chrono::Duration::hours(25);
+        check_for_update(&source, tmp.path(), "0.0.3", next_day).await;
+        assert_eq!(source.queries(), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_check_keeps_previous_result_and_waits_a_day() {
+        let tmp = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        check_for_update(&MockSource::new("v0.2.0"), tmp.path(), "0.0.3", now).await;
+
+        let offline = MockSource::offline();
+        let next_day = now + chrono::Duration::days(2);
+        let notice = check_for_update(&offline, tmp.path(), "0.0.3", next_day).await;
+        assert_eq!(notice.as_deref(), Some("v0.2.0"));
+        assert_eq!(offline.queries(), 1);
+
+        check_for_update(&offline, tmp.path(), "0.0.3", next_day).await;
+        assert_eq!(offline.queries(), 1);
+    }
+
+    #[tokio::test]
+    async fn disabled_check_returns_nothing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = UpdateConfig { check: false };
+        assert_eq!(startup_notice(&config, tmp.path()).await, None);
+        assert!(!tmp.path().join(CACHE_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn self_update_replaces_executable_after_verifying() {
+        let tmp = tempfile::tempdir().unwrap();
+        let exe = tmp.path().join("rrclaw");
+        std::fs::write(&exe, b"old binary").unwrap();
+
+        let source = linux_release("v0.1.0", b"new binary", false);
+        let outcome = self_update(&source, &exe, "0.0.3", "linux", "x86_64")
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            SelfUpdateOutcome::Updated {
+                to: "v0.1.0".to_string()
+            }
+        );
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new binary");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&exe).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+        // 临时文件已被 rename 掉
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch_leaves_executable_untouched() {
+        let tmp = tempfile::tempdir().unwrap();
+        let exe = tmp.path().join("rrclaw");
+        std::fs::write(&exe, b"old binary").unwrap();
+
+        let source = linux_release("v0.1.0", b"new binary", true);
+        let err = self_update(&source, &exe, "0.0.3", "linux", "x86_64")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("校验失败"), "{}", err);
+        assert_eq!(std::fs::read(&exe).unwrap(), b"old binary");
+
+        // 没有 SHA256SUMS 时拒绝更新
+        let asset = "rrclaw-linux-x86_64.tar.gz";
+        let source =
+            MockSource::new("v0.1.0").with_file(asset, tar_gz("rrclaw-linux-x86_64", b"x"));
+        let err = self_update(&source, &exe, "0.0.3", "linux", "x86_64")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains(CHECKSUMS_ASSET), "{}", err);
+        assert_eq!(std::fs::read(&exe).unwrap(), b"old binary");
+    }
+
+    #[tokio::test]
+    async fn up_to_date_and_unsupported_platforms() {
+        let tmp = tempfile::tempdir().unwrap();
+        let exe = tmp.path().join("rrclaw");
+        let source = linux_release("v0.0.3", b"same", false);
+        let outcome = self_update(&source, &exe, "0.0.3", "linux", "x86_64")
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            SelfUpdateOutcome::UpToDate {
+                latest: "v0.0.3".to_string()
+            }
+        );
+
+        let source = linux_release("v0.1.0", b"new", false);
+        let err = self_update(&source, &exe, "0.0.3", "freebsd", "x86_64")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cargo install"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn windows_archive_is_staged_next_to_exe() {
+        let tmp = tempfile::tempdir().unwrap();
+        let exe = tmp.path().join("rrclaw.exe");
+        std::fs::write(&exe, b"old binary").unwrap();
+        let asset = "rrclaw-windows-x86_64.exe.zip";
+        let archive = b"PK zip bytes".to_vec();
+        let sums = format!("{}  {}\n", sha256_hex(&archive), asset);
+        let source = MockSource::new("v0.1.0")
+            .with_file(asset, archive.clone())
+            .with_file(CHECKSUMS_ASSET, sums.into_bytes());
+
+        let outcome = self_update(&source, &exe, "0.0.3", "windows", "x86_64")
+            .await
+            .unwrap();
+        let staged = tmp.path().join(asset);
+        assert_eq!(
+            outcome,
+            SelfUpdateOutcome::Staged {
+                path: staged.clone(),
+                to: "v0.1.0".to_string()
+            }
+        );
+        assert_eq!(std::fs::read(staged).unwrap(), archive);
+        assert_eq!(std::fs::read(&exe).unwrap(), b"old binary");
+    }
+}
@@ -0,0 +1,215 @@
+//! 发布版本的纯逻辑：版本比较、按平台选择发布包、SHA256SUMS 校验
+//!
+//! 发布包命名与 `.github/workflows/release.yml` 一致：`rrclaw-<os>-<arch>.tar.gz`（Windows 为 `.zip`），
+//! 压缩包内的可执行文件与发布包同名（去掉扩展名）。
+
+use color_eyre::eyre::{bail, eyre, Result};
+use serde::Deserialize;
+
+/// 校验和清单的文件名
+pub const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// GitHub `releases/latest` 响应中用到的字段
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseInfo {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl ReleaseInfo {
+    pub fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// 解析后的版本号（`v` 前缀可选；预发布后缀只参与相等版本间的比较）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.strip_prefix('v').unwrap_or(text);
+        let (core, pre) = match text.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (text, None),
+        };
+        let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+        let major = parts.next()??;
+        let minor = parts.next().unwrap_or(Some(0))?;
+        let patch = parts.next().unwrap_or(Some(0))?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            // 同版本号时正式版高于预发布版
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => std::cmp::Ordering::Equal,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+    }
+}
+
+/// `latest` 是否比 `current` 新（任一无法解析时为 false）
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    match (Version::parse(latest), Version::parse(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// 当前平台对应的发布包名（没有预编译包的平台返回 None）
+///
+/// `os` / `arch` 取自 `std::env::consts`。
+pub fn asset_name(os: &str, arch: &str) -> Option<&'static str> {
+    match (os, arch) {
+        ("linux", "x86_64") => Some("rrclaw-linux-x86_64.tar.gz"),
+        ("macos", "x86_64") => Some("rrclaw-macos-x86_64.tar.gz"),
+        ("macos", "aarch64") => Some("rrclaw-macos-aarch64.tar.gz"),
+        ("windows", "x86_64") => Some("rrclaw-windows-x86_64.exe.zip"),
+        _ => None,
+    }
+}
+
+/// 压缩包内可执行文件的名称（发布包名去掉压缩扩展名）
+pub fn binary_name_in_archive(asset: &str) -> &str {
+    asset
+        .strip_suffix(".tar.gz")
+        .or_else(|| asset.strip_suffix(".zip"))
+        .unwrap_or(asset)
+}
+
+/// 从 `sha256sum` 格式的清单中取出 `file` 的期望哈希（小写十六进制）
+///
+/// 每行 `<hex>  <file>`（二进制模式为 `<hex> *<file>`），文件名可带目录前缀。
+pub fn expected_checksum(sums: &str, file: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        let name = name.trim_start().trim_start_matches('*');
+        let name = name.rsplit('/').next().unwrap_or(name);
+        (name == file && hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| hash.to_ascii_lowercase())
+    })
+}
+
+/// SHA-256（小写十六进制）
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 校验下载内容与 SHA256SUMS 中的记录一致
+pub fn verify_checksum(bytes: &[u8], sums: &str, file: &str) -> Result<()> {
+    let expected = expected_checksum(sums, file)
+        .ok_or_else(|| eyre!("{} 中没有 {} 的校验和", CHECKSUMS_ASSET, file))?;
+    let actual = sha256_hex(bytes);
+    if actual != expected {
+        bail!(
+            "{} 校验失败：期望 {}，实际 {}（下载可能损坏或被篡改）",
+            file,
+            expected,
+            actual
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(is_newer("v0.0.10", "0.0.9"));
+        assert!(is_newer("v1.0", "0.9.9"));
+        assert!(is_newer("0.1.0", "0.1.0-rc.1"));
+        assert!(!is_newer("v0.1.0-rc.1", "0.1.0"));
+        assert!(!is_newer("v0.0.3", "0.0.3"));
+        assert!(!is_newer("v0.0.2", "0.0.3"));
+        assert!(!is_newer("nightly", "0.0.3"));
+        assert_eq!(Version::parse("1.2.3.4"), None);
+    }
+
+    #[test]
+    fn asset_follows_release_workflow_names() {
+        assert_eq!(
+            asset_name("macos", "aarch64"),
+            Some("rrclaw-macos-aarch64.tar.gz")
+        );
+        assert_eq!(
+            asset_name("windows", "x86_64"),
+            Some("rrclaw-windows-x86_64.exe.zip")
+        );
+        assert_eq!(asset_name("linux", "aarch64"), None);
+        assert_eq!(
+            binary_name_in_archive("rrclaw-linux-x86_64.tar.gz"),
+            "rrclaw-linux-x86_64"
+        );
+        assert_eq!(
+            binary_name_in_archive("rrclaw-windows-x86_64.exe.zip"),
+            "rrclaw-windows-x86_64.exe"
+        );
+    }
+
+    #[test]
+    fn checksum_is_looked_up_and_verified() {
+        // echo -n "rrclaw" | sha256sum
+        let hash = sha256_hex(b"rrclaw");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let sums = format!(
+            "{}  rrclaw-macos-aarch64.tar.gz\n{} *artifacts/rrclaw-linux-x86_64.tar.gz\n",
+            "0".repeat(64),
+            hash.to_uppercase()
+        );
+        assert_eq!(
+            expected_checksum(&sums, "rrclaw-linux-x86_64.tar.gz"),
+            Some(hash)
+        );
+        assert_eq!(expected_checksum(&sums, "rrclaw-linux"), None);
+
+        verify_checksum(b"rrclaw", &sums, "rrclaw-linux-x86_64.tar.gz").unwrap();
+        let err = verify_checksum(b"tampered", &sums, "rrclaw-linux-x86_64.tar.gz").unwrap_err();
+        assert!(err.to_string().contains("校验失败"), "{}", err);
+        let err = verify_checksum(b"rrclaw", &sums, "missing.tar.gz").unwrap_err();
+        assert!(err.to_string().contains("没有"), "{}", err);
+    }
+}
@@ -21,16 +21,22 @@
 //! ```
 
 use std::collections::VecDeque;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
 use color_eyre::eyre::{eyre, Result};
 
-use rrclaw::providers::{ChatResponse, ConversationMessage, Provider, ToolCall, ToolSpec};
+use rrclaw::providers::{
+    ChatResponse, ConversationMessage, GenerationParams, Provider, ToolCall, ToolSpec,
+};
 
 /// 可插拔 Mock Provider，按队列顺序返回预设响应
 pub struct MockProvider {
     responses: Mutex<VecDeque<ChatResponse>>,
+    /// 每次调用收到的消息（序列化为 JSON 字符串，便于断言上下文）
+    requests: Arc<Mutex<Vec<String>>>,
+    /// 每次调用收到的生成参数
+    params: Arc<Mutex<Vec<GenerationParams>>>,
 }
 
 impl MockProvider {
@@ -38,15 +44,28 @@ impl MockProvider {
     pub fn new(responses: Vec<ChatResponse>) -> Self {
         Self {
             responses: Mutex::new(responses.into()),
+            requests: Arc::new(Mutex::new(Vec::new())),
+            params: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// 请求记录句柄（MockProvider 被 move 进 Agent 后仍可读取）
+    pub fn requests(&self) -> Arc<Mutex<Vec<String>>> {
+        Arc::clone(&self.requests)
+    }
+
+    /// 生成参数记录句柄（与 `requests` 一一对应）
+    pub fn params(&self) -> Arc<Mutex<Vec<GenerationParams>>> {
+        Arc::clone(&self.params)
+    }
+
     /// 构造 Phase 1 路由结果：Direct（无需加载 skill，直接执行）
     pub fn direct_route() -> ChatResponse {
         ChatResponse {
             text: Some("{\"direct\": true}".to_string()),
             reasoning_content: None,
             tool_calls: vec![],
+            provenance: None,
         }
     }
 
@@ -56,6 +75,7 @@ impl MockProvider {
             text: Some(content.to_string()),
             reasoning_content: None,
             tool_calls: vec![],
+            provenance: None,
         }
     }
 
@@ -69,6 +89,7 @@ impl MockProvider {
                 name: name.to_string(),
                 arguments: args,
             }],
+            provenance: None,
         }
     }
 
@@ -82,11 +103,19 @@ impl MockProvider {
 impl Provider for MockProvider {
     async fn chat_with_tools(
         &self,
-        _messages: &[ConversationMessage],
+        messages: &[ConversationMessage],
         _tools: &[ToolSpec],
         _model: &str,
-        _temperature: f64,
+        params: GenerationParams,
     ) -> Result<ChatResponse> {
+        self.requests
+            .lock()
+            .expect("MockProvider mutex 中毒")
+            .push(serde_json::to_string(messages).unwrap_or_default());
+        self.params
+            .lock()
+            .expect("MockProvider mutex 中毒")
+            .push(params);
         let mut queue = self.responses.lock().expect("MockProvider mutex 中毒");
         queue
             .pop_front()
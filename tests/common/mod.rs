@@ -12,7 +12,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 
-use rrclaw::agent::Agent;
+use rrclaw::agent::{Agent, AgentBuilder};
 use rrclaw::config::{Config, DefaultConfig, ProviderConfig, ReliabilityConfig};
 use rrclaw::memory::NoopMemory;
 use rrclaw::routines::{Routine, RoutineEngine, RoutineSource};
@@ -31,6 +31,12 @@ pub fn test_config() -> Arc<Config> {
             api_key: "test-key".to_string(),
             model: "test-model".to_string(),
             auth_style: None,
+            headers: Default::default(),
+            endpoint_path: None,
+            max_tools: None,
+            max_tool_schema_bytes: None,
+            context_window: None,
+            max_tokens: None,
         },
     );
 
@@ -58,8 +64,12 @@ pub fn test_routine(name: &str, schedule: &str) -> Routine {
         schedule: schedule.to_string(),
         message: "test message".to_string(),
         channel: "cli".to_string(),
+        channel_target: None,
         enabled: true,
         source: RoutineSource::Dynamic,
+        catch_up: false,
+        chain: vec![],
+        condition: None,
     }
 }
 
@@ -72,9 +82,11 @@ pub async fn make_test_engine(routines: Vec<Routine>) -> (Arc<RoutineEngine>, te
     let tmp = tempfile::tempdir().expect("创建临时目录失败");
     let db_path = tmp.path().join("test_routines.db");
 
+    // 数据目录指向临时目录：并行测试各自持有调度锁，互不影响
     let engine = RoutineEngine::new(routines, test_config(), Arc::new(NoopMemory), &db_path)
         .await
-        .expect("创建 RoutineEngine 失败");
+        .expect("创建 RoutineEngine 失败")
+        .with_paths(rrclaw::config::RrclawPaths::in_home(tmp.path()));
 
     (Arc::new(engine), tmp)
 }
@@ -87,18 +99,17 @@ pub async fn make_test_engine(routines: Vec<Routine>) -> (Arc<RoutineEngine>, te
 /// - 仅含 ShellTool（够验证 tool call 链路）
 /// - injection_check=false（降低测试噪音）
 pub fn test_agent(mock: MockProvider, policy: SecurityPolicy) -> Agent {
-    Agent::new(
-        Box::new(mock),
-        vec![Box::new(rrclaw::tools::shell::ShellTool)],
-        Box::new(NoopMemory),
-        policy,
-        "mock".to_string(),
-        "http://mock".to_string(),
-        "mock-model".to_string(),
-        0.0,
-        vec![], // 不加载 skills（简化测试）
-        None,   // 不加载 identity
-    )
+    mock_agent_builder(mock, policy)
+        .tool(rrclaw::tools::shell::ShellTool)
+        .build()
+}
+
+/// 测试 Agent 的公共部分；Memory / Skills / 身份文件取 builder 默认值（NoopMemory、无、无）
+fn mock_agent_builder(mock: MockProvider, policy: SecurityPolicy) -> AgentBuilder {
+    Agent::builder(Box::new(mock), "mock-model")
+        .policy(policy)
+        .provider_info("mock", "http://mock")
+        .temperature(0.0)
 }
 
 /// 构造 Full 自主策略，allowed_commands=["echo"]，workspace=tmp_path
@@ -146,19 +157,8 @@ pub fn test_agent_with_file_tool(
     mock: MockProvider,
     policy: SecurityPolicy,
 ) -> rrclaw::agent::Agent {
-    rrclaw::agent::Agent::new(
-        Box::new(mock),
-        vec![
-            Box::new(rrclaw::tools::shell::ShellTool),
-            Box::new(rrclaw::tools::file::FileReadTool),
-        ],
-        Box::new(NoopMemory),
-        policy,
-        "mock".to_string(),
-        "http://mock".to_string(),
-        "mock-model".to_string(),
-        0.0,
-        vec![],
-        None,
-    )
+    mock_agent_builder(mock, policy)
+        .tool(rrclaw::tools::shell::ShellTool)
+        .tool(rrclaw::tools::file::FileReadTool)
+        .build()
 }
@@ -6,7 +6,7 @@
 //!   D5 — Supervised mode Confirm/ConfirmResponse round-trip
 //!   D1 (unit-level) — path helpers, PID file logic
 
-use rrclaw::daemon::protocol::{ClientMessage, DaemonMessage};
+use rrclaw::daemon::protocol::{ClientMessage, ConfirmRequest, ConfirmResponse, DaemonMessage};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{UnixListener, UnixStream};
 
@@ -69,6 +69,7 @@ async fn d2_1_ipc_token_done_roundtrip() {
         &ClientMessage::Message {
             session_id: "test-session".to_string(),
             content: "hello".to_string(),
+            workspace: None,
         },
     )
     .await;
@@ -121,6 +122,7 @@ async fn d2_1_ipc_multiple_tokens() {
         &ClientMessage::Message {
             session_id: "s".to_string(),
             content: "hi".to_string(),
+            workspace: None,
         },
     )
     .await;
@@ -218,6 +220,7 @@ async fn d2_3_concurrent_sessions_independent() {
             &ClientMessage::Message {
                 session_id: "cli-A".to_string(),
                 content: "msg".to_string(),
+                workspace: None,
             },
         )
         .await;
@@ -238,6 +241,7 @@ async fn d2_3_concurrent_sessions_independent() {
             &ClientMessage::Message {
                 session_id: "cli-B".to_string(),
                 content: "msg".to_string(),
+                workspace: None,
             },
         )
         .await;
@@ -278,11 +282,13 @@ async fn d5_1_2_confirm_approved_roundtrip() {
         // Push Confirm to client.
         write_line(
             &mut writer,
-            &DaemonMessage::Confirm {
+            &DaemonMessage::ConfirmRequest(ConfirmRequest {
                 request_id: "req-abc".to_string(),
                 tool: "shell".to_string(),
                 args: serde_json::json!({"command": "ls -la"}),
-            },
+                preview: None,
+                timeout_secs: 120,
+            }),
         )
         .await;
 
@@ -290,10 +296,10 @@ async fn d5_1_2_confirm_approved_roundtrip() {
         let line = lines.next_line().await.unwrap().unwrap();
         let msg: ClientMessage = serde_json::from_str(&line).unwrap();
         match msg {
-            ClientMessage::ConfirmResponse {
+            ClientMessage::ConfirmResponse(ConfirmResponse {
                 request_id,
                 approved,
-            } => {
+            }) => {
                 assert_eq!(request_id, "req-abc");
                 assert!(approved, "client should have approved");
             }
@@ -312,22 +318,22 @@ async fn d5_1_2_confirm_approved_roundtrip() {
     let line = lines.next_line().await.unwrap().unwrap();
     let msg: DaemonMessage = serde_json::from_str(&line).unwrap();
     let request_id = match msg {
-        DaemonMessage::Confirm {
+        DaemonMessage::ConfirmRequest(ConfirmRequest {
             request_id, tool, ..
-        } => {
+        }) => {
             assert_eq!(tool, "shell");
             request_id
         }
-        other => panic!("expected Confirm, got {:?}", other),
+        other => panic!("expected ConfirmRequest, got {:?}", other),
     };
 
     // Client approves.
     write_line(
         &mut writer,
-        &ClientMessage::ConfirmResponse {
+        &ClientMessage::ConfirmResponse(ConfirmResponse {
             request_id,
             approved: true,
-        },
+        }),
     )
     .await;
 
@@ -352,21 +358,23 @@ async fn d5_3_confirm_rejected_roundtrip() {
 
         write_line(
             &mut writer,
-            &DaemonMessage::Confirm {
+            &DaemonMessage::ConfirmRequest(ConfirmRequest {
                 request_id: "req-xyz".to_string(),
                 tool: "shell".to_string(),
                 args: serde_json::json!({"command": "rm -rf /"}),
-            },
+                preview: None,
+                timeout_secs: 120,
+            }),
         )
         .await;
 
         let line = lines.next_line().await.unwrap().unwrap();
         let msg: ClientMessage = serde_json::from_str(&line).unwrap();
         match msg {
-            ClientMessage::ConfirmResponse {
+            ClientMessage::ConfirmResponse(ConfirmResponse {
                 request_id,
                 approved,
-            } => {
+            }) => {
                 assert_eq!(request_id, "req-xyz");
                 assert!(!approved, "client should have rejected");
             }
@@ -382,17 +390,17 @@ async fn d5_3_confirm_rejected_roundtrip() {
     let line = lines.next_line().await.unwrap().unwrap();
     let msg: DaemonMessage = serde_json::from_str(&line).unwrap();
     let request_id = match msg {
-        DaemonMessage::Confirm { request_id, .. } => request_id,
-        other => panic!("expected Confirm, got {:?}", other),
+        DaemonMessage::ConfirmRequest(request) => request.request_id,
+        other => panic!("expected ConfirmRequest, got {:?}", other),
     };
 
     // Client rejects.
     write_line(
         &mut writer,
-        &ClientMessage::ConfirmResponse {
+        &ClientMessage::ConfirmResponse(ConfirmResponse {
             request_id,
             approved: false,
-        },
+        }),
     )
     .await;
 
@@ -197,6 +197,7 @@ async fn e2_5_clarification_returned_without_history() {
         ),
         reasoning_content: None,
         tool_calls: vec![],
+        provenance: None,
     };
     let mock = common::MockProvider::new(vec![
         clarification_response, // Phase 1 路由 → NeedClarification
@@ -392,6 +393,7 @@ async fn e2_7_3_clarification_via_stream() {
         ),
         reasoning_content: None,
         tool_calls: vec![],
+        provenance: None,
     };
     let mock = common::MockProvider::new(vec![
         clarification_response, // Phase 1 → NeedClarification，Phase 2 不应被调用
@@ -543,11 +545,13 @@ async fn e2_9_compact_history_if_needed() {
                     role: "user".to_string(),
                     content: format!("消息 {}", i),
                     reasoning_content: None,
+                    turn: 0,
                 }),
                 ConversationMessage::Chat(ChatMessage {
                     role: "assistant".to_string(),
                     content: format!("回复 {}", i),
                     reasoning_content: None,
+                    turn: 0,
                 }),
             ]
         })
@@ -740,3 +744,171 @@ fn find_test_missing_params(schema: &serde_json::Value, args: &serde_json::Value
 
     missing
 }
+
+// ─── agent --continue：跨进程延续会话 ─────────────────────────────────────────
+//
+// 两次独立的 run_single（模拟两次 CLI 调用，各自新建 Agent）共享同一个数据目录，
+// 第二次应加载第一次保存的历史，并把它发送给 Provider。
+
+#[tokio::test]
+async fn e2_continue_second_invocation_sees_first_context() {
+    let tmp = tempfile::tempdir().unwrap();
+    let memory = rrclaw::memory::SqliteMemory::open(&tmp.path().join("data")).unwrap();
+
+    // 第一次调用
+    let mock = common::MockProvider::new(vec![
+        common::MockProvider::direct_route(),
+        common::MockProvider::text("已记下：项目代号是 AURORA"),
+    ]);
+    let mut agent = common::test_agent(mock, common::full_policy(tmp.path()));
+    rrclaw::channels::cli::run_single(
+        &mut agent,
+        "记住项目代号 AURORA",
+        &memory,
+        true,
+        rrclaw::channels::cli::OutputFormat::Markdown,
+    )
+    .await
+    .unwrap();
+    drop(agent);
+
+    // 第二次调用：全新 Agent，只靠 --continue 恢复上下文
+    let mock = common::MockProvider::new(vec![
+        common::MockProvider::direct_route(),
+        common::MockProvider::text("项目代号是 AURORA"),
+    ]);
+    let requests = mock.requests();
+    let mut agent = common::test_agent(mock, common::full_policy(tmp.path()));
+    rrclaw::channels::cli::run_single(
+        &mut agent,
+        "项目代号是什么？",
+        &memory,
+        true,
+        rrclaw::channels::cli::OutputFormat::Markdown,
+    )
+    .await
+    .unwrap();
+
+    // Phase 2 请求包含第一次的用户消息和回复
+    let phase2 = requests
+        .lock()
+        .unwrap()
+        .last()
+        .cloned()
+        .expect("应有 Provider 调用");
+    assert!(
+        phase2.contains("记住项目代号 AURORA"),
+        "缺少第一次的用户消息"
+    );
+    assert!(
+        phase2.contains("已记下：项目代号是 AURORA"),
+        "缺少第一次的回复"
+    );
+
+    // 两轮都已持久化到同一 session
+    assert_eq!(agent.history().len(), 4);
+    let session = memory.latest_session_id().await.unwrap().unwrap();
+    let saved = memory.load_conversation_history(&session).await.unwrap();
+    assert_eq!(saved.len(), 4);
+}
+
+// ─── 会话生成参数覆盖（/temp、/param set）──────────────────────────────────
+
+#[tokio::test]
+async fn e2_session_params_reach_provider_and_reset() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mock = common::MockProvider::new(vec![
+        common::MockProvider::direct_route(),
+        common::MockProvider::text("first"),
+        common::MockProvider::direct_route(),
+        common::MockProvider::text("second"),
+    ]);
+    let params = mock.params();
+    let mut agent = common::test_agent(mock, common::full_policy(tmp.path()));
+    let default_temperature = agent.temperature();
+
+    agent.set_generation_param("temperature", "1.4").unwrap();
+    agent.set_generation_param("max_tokens", "300").unwrap();
+    assert!(agent.set_generation_param("temperature", "3").is_err());
+    agent.process_message("brainstorm").await.unwrap();
+
+    agent.reset_generation_params();
+    agent.process_message("edit").await.unwrap();
+
+    let params = params.lock().unwrap();
+    assert_eq!(params.len(), 4);
+    // Phase 1 路由保持固定低温，不受覆盖影响
+    assert!(params[0].temperature < 0.5);
+    assert_eq!(params[0].max_tokens, None);
+    assert_eq!(params[1].temperature, 1.4);
+    assert_eq!(params[1].max_tokens, Some(300));
+    // reset 后恢复配置默认值
+    assert_eq!(params[3].temperature, default_temperature);
+    assert_eq!(params[3].max_tokens, None);
+}
+
+// ─── AgentBuilder 与 test_agent 等价 ───────────────────────────────────────
+
+#[tokio::test]
+async fn e2_builder_matches_test_agent() {
+    let tmp = tempfile::tempdir().unwrap();
+    let responses = || {
+        common::MockProvider::new(vec![
+            common::MockProvider::direct_route(),
+            common::MockProvider::shell_call("call_1", "echo built"),
+            common::MockProvider::text("完成"),
+        ])
+    };
+
+    // test_agent 基于 builder 默认值（NoopMemory、无 Skills、无身份文件），
+    // 与它改写前的 Agent::new 构造对比
+    let mut legacy = rrclaw::agent::Agent::new(
+        Box::new(responses()),
+        vec![Box::new(rrclaw::tools::shell::ShellTool)],
+        Box::new(rrclaw::memory::NoopMemory),
+        common::full_policy(tmp.path()),
+        "mock".to_string(),
+        "http://mock".to_string(),
+        "mock-model".to_string(),
+        0.0,
+        vec![],
+        None,
+    );
+    let mut built = common::test_agent(responses(), common::full_policy(tmp.path()));
+
+    assert_eq!(built.model(), legacy.model());
+    assert_eq!(built.provider_name(), legacy.provider_name());
+    assert_eq!(built.base_url(), legacy.base_url());
+    assert_eq!(built.temperature(), legacy.temperature());
+    assert_eq!(built.tool_names(), legacy.tool_names());
+    assert_eq!(built.skills().len(), legacy.skills().len());
+    assert_eq!(built.identity_context(), legacy.identity_context());
+    assert_eq!(built.policy().autonomy, legacy.policy().autonomy);
+
+    let a = legacy.process_message("跑一下 echo").await.unwrap();
+    let b = built.process_message("跑一下 echo").await.unwrap();
+    assert_eq!(a, b);
+    let tool_results = |agent: &rrclaw::agent::Agent| {
+        agent
+            .history()
+            .iter()
+            .filter_map(|m| match m {
+                // 去掉 "[exit_code=0 duration_ms=N]" 中随运行变化的耗时
+                ConversationMessage::ToolResult { content, .. } => {
+                    Some(content.split(" duration_ms=").next().unwrap().to_string())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(built.history().len(), legacy.history().len());
+    assert_eq!(tool_results(&built), tool_results(&legacy));
+    assert!(tool_results(&built)[0].contains("built"));
+
+    // 不指定策略时默认 Full
+    let agent = rrclaw::agent::Agent::builder(Box::new(responses()), "mock-model").build();
+    assert_eq!(
+        agent.policy().autonomy,
+        rrclaw::security::AutonomyLevel::Full
+    );
+}
@@ -0,0 +1,97 @@
+//! `rrclaw agent --no-memory` 端到端测试
+//!
+//! 以子进程运行编译好的 rrclaw（Echo Provider，离线），
+//! 验证运行前后数据目录中的 SqliteMemory 没有任何变化，也不占用 / 创建数据目录。
+
+use std::path::Path;
+use std::process::Command;
+
+use rrclaw::memory::{Memory, MemoryCategory, SqliteMemory};
+
+const CONFIG: &str = r#"
+[default]
+provider = "echo"
+model = "echo"
+
+[providers.echo]
+base_url = "echo://"
+api_key = ""
+model = "echo"
+auth_style = "echo"
+
+[security]
+autonomy = "full"
+"#;
+
+fn run_agent(home: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rrclaw"))
+        .arg("--config")
+        .arg(home.join("config.toml"))
+        .arg("agent")
+        .args(args)
+        .env("HOME", home)
+        .current_dir(home)
+        .output()
+        .expect("无法启动 rrclaw")
+}
+
+#[tokio::test]
+async fn no_memory_run_leaves_sqlite_memory_unchanged() {
+    let home = tempfile::tempdir().unwrap();
+    std::fs::write(home.path().join("config.toml"), CONFIG).unwrap();
+    let data_dir = home.path().join("data");
+    {
+        let memory = SqliteMemory::open(&data_dir).unwrap();
+        memory
+            .store("existing", "运行前已有的记忆", MemoryCategory::Core)
+            .await
+            .unwrap();
+    }
+    let before = SqliteMemory::open(&data_dir)
+        .unwrap()
+        .count()
+        .await
+        .unwrap();
+
+    // Echo Provider 把 `tool:<name> {json}` 转成一次工具调用：LLM 主动要求存储记忆
+    let output = run_agent(
+        home.path(),
+        &[
+            "--no-memory",
+            "--format",
+            "plain",
+            "-m",
+            r#"tool:memory_store {"key": "secret", "content": "不应落盘"}"#,
+        ],
+    );
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let memory = SqliteMemory::open(&data_dir).unwrap();
+    assert_eq!(memory.count().await.unwrap(), before);
+    assert!(memory.recall("不应落盘", 5).await.unwrap().is_empty());
+    assert_eq!(memory.latest_session_id().await.unwrap(), None);
+    // 没有占用数据目录的调度锁（否则正常进程的 Routine 会被挤成旁观）
+    assert!(!data_dir.join("routines.lock").exists());
+}
+
+#[test]
+fn no_memory_run_does_not_create_data_dir() {
+    let home = tempfile::tempdir().unwrap();
+    std::fs::write(home.path().join("config.toml"), CONFIG).unwrap();
+    let data_dir = home.path().join("data");
+
+    let output = run_agent(
+        home.path(),
+        &["--no-memory", "--format", "plain", "-m", "hello"],
+    );
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!data_dir.exists(), "--no-memory 不应创建数据目录");
+}
@@ -0,0 +1,92 @@
+//! 夹具回放 E2E 测试
+//!
+//! `tests/fixtures/replay/<场景>/` 下是 `rrclaw agent --record <dir>` 格式的夹具
+//! （每个文件一次 Provider 请求 / 响应，文件名为规范化请求的哈希）。
+//! 回放走完整的 Agent Loop：路由、工具执行、多轮历史，全程不访问网络。
+//!
+//! 修改 prompt / 路由后夹具未命中时，错误信息会给出缺失的 key；
+//! 用 `--record` 重新录制，或按错误信息补写对应的夹具文件。
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use rrclaw::agent::Agent;
+use rrclaw::memory::NoopMemory;
+use rrclaw::providers::recording::FixtureReplayer;
+use rrclaw::providers::ConversationMessage;
+
+fn fixture_dir(scenario: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/replay")
+        .join(scenario)
+}
+
+/// ShellTool + FileReadTool，Provider 为夹具回放
+fn replay_agent(replayer: FixtureReplayer, workspace: &Path) -> Agent {
+    Agent::new(
+        Box::new(replayer),
+        vec![
+            Box::new(rrclaw::tools::shell::ShellTool),
+            Box::new(rrclaw::tools::file::FileReadTool),
+        ],
+        Box::new(NoopMemory),
+        common::full_policy(workspace),
+        "mock".to_string(),
+        "http://mock".to_string(),
+        "mock-model".to_string(),
+        0.0,
+        vec![],
+        None,
+    )
+}
+
+fn tool_result<'a>(history: &'a [ConversationMessage], id: &str) -> &'a str {
+    history
+        .iter()
+        .find_map(|m| match m {
+            ConversationMessage::ToolResult {
+                tool_call_id,
+                content,
+                ..
+            } if tool_call_id == id => Some(content.as_str()),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("history 中没有 {} 的工具结果", id))
+}
+
+#[tokio::test]
+async fn replays_multi_turn_multi_tool_conversation() {
+    let tmp = tempfile::tempdir().unwrap();
+    let workspace = tmp.path().canonicalize().unwrap();
+    std::fs::write(workspace.join("notes.txt"), "周五发布 v0.1\n").unwrap();
+
+    let replayer = FixtureReplayer::load(&fixture_dir("read_and_echo")).unwrap();
+    // 两轮对话，每轮：路由 + tool call + 最终回复
+    assert_eq!(replayer.len(), 6);
+    let mut agent = replay_agent(replayer, &workspace);
+
+    let first = agent
+        .process_message("看看 notes.txt 里写了什么")
+        .await
+        .unwrap();
+    assert_eq!(first, "notes.txt 里记着：周五发布 v0.1。");
+
+    // 第二轮走流式路径，同样命中夹具
+    let (tx, _rx) = rrclaw::providers::stream_channel();
+    let second = agent
+        .process_message_stream("再用 echo 跟大家打个招呼", tx)
+        .await
+        .unwrap();
+    assert_eq!(second, "已经执行 echo，输出是 hello team。");
+
+    // 工具真实执行：结果来自本地环境，不来自夹具
+    let history = agent.history();
+    assert!(tool_result(history, "call_read").contains("周五发布 v0.1"));
+    assert!(tool_result(history, "call_echo").contains("hello team"));
+    assert_eq!(history.len(), 8);
+
+    // 超出夹具范围的请求直接报错，不会访问网络
+    let err = agent.process_message("录制时没有这句").await.unwrap_err();
+    assert!(format!("{:#}", err).contains("回放夹具未命中"), "{:#}", err);
+}